@@ -42,6 +42,10 @@ reftest: $(S)src/test/harness/reftest/reftest.rs servo
 	@$(call E, compile: $@)
 	$(Q)$(RUSTC) -L$(B)/src/support/png/rust-png/ -L$(B)/src/support/png/libpng/ -o $@ $<
 
+layouttest: $(S)src/test/harness/layouttest/layouttest.rs servo
+	@$(call E, compile: $@)
+	$(Q)$(RUSTC) -o $@ $<
+
 contenttest: $(S)src/test/harness/contenttest/contenttest.rs servo
 	@$(call E, compile: $@)
 	$(Q)$(RUSTC) $(RFLAGS_servo) -o $@ $< -L .
@@ -58,19 +62,19 @@ check-test:
 
 ifeq ($(CFG_OSTYPE),apple-darwin)
 .PHONY: check
-check: $(DEPS_CHECK_TARGETS_FAST) check-servo check-content check-ref tidy
+check: $(DEPS_CHECK_TARGETS_FAST) check-servo check-content check-ref check-layout tidy
 	@$(call E, check: all)
 
 .PHONY: check-all
-check-all: $(DEPS_CHECK_TARGETS_ALL) check-servo check-content check-ref tidy
+check-all: $(DEPS_CHECK_TARGETS_ALL) check-servo check-content check-ref check-layout tidy
 	@$(call E, check: all)
 else
 .PHONY: check
-check: $(DEPS_CHECK_TARGETS_FAST) check-servo tidy
+check: $(DEPS_CHECK_TARGETS_FAST) check-servo check-ref check-layout tidy
 	@$(call E, check: all)
 
 .PHONY: check-all
-check-all: $(DEPS_CHECK_TARGETS_ALL) check-servo tidy
+check-all: $(DEPS_CHECK_TARGETS_ALL) check-servo check-ref check-layout tidy
 	@$(call E, check: all)
 endif
 
@@ -81,11 +85,21 @@ check-servo: $(foreach lib_crate,$(SERVO_LIB_CRATES),check-servo-$(lib_crate)) s
 
 .PHONY: check-ref
 check-ref: reftest
+	@$(call E, check: reftests with headless rendering)
+	$(Q)./reftest $(S)src/test/ref/*.list -- -z
+
+.PHONY: check-ref-windowed
+check-ref-windowed: reftest
 	@$(call E, check: reftests with GPU rendering)
 	$(Q)./reftest $(S)src/test/ref/*.list
 	@$(call E, check: reftests with CPU rendering)
 	$(Q)./reftest $(S)src/test/ref/*.list -- -c
 
+.PHONY: check-layout
+check-layout: layouttest
+	@$(call E, check: layout tests)
+	$(Q)./layouttest $(S)src/test/layout/*.list -- -z
+
 .PHONY: check-content
 check-content: contenttest
 	@$(call E, check: contenttests)
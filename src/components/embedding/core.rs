@@ -58,6 +58,9 @@ pub extern "C" fn cef_run_message_loop() {
         headless: false,
         hard_fail: false,
         bubble_widths_separately: false,
+        dump_display_list: None,
+        dump_flow_tree: None,
+        replay_display_list: None,
     };
     native::start(0, 0 as **u8, proc() {
        servo::run(opts);
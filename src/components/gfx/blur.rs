@@ -0,0 +1,118 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A Gaussian blur of 32-bit premultiplied pixels, computed on the CPU. This is used to draw the
+//! blurred edges of shadows.
+
+/// The number of bytes per pixel.
+static BYTES_PER_PIXEL: uint = 4;
+
+/// Returns the standard deviation of the Gaussian blur described by a CSS blur radius. The
+/// blurred edge of a shadow spans twice the blur radius, so the radius is twice the standard
+/// deviation, per CSS Backgrounds and Borders Level 3 section 7.2.
+pub fn sigma_for_blur_radius(blur_radius: f32) -> f32 {
+    blur_radius * 0.5
+}
+
+/// Returns the number of pixels by which a blur with the given standard deviation spreads each
+/// pixel in each direction.
+pub fn blur_extent(sigma: f32) -> uint {
+    (sigma * 3.0).ceil() as uint
+}
+
+/// Returns the normalized weights of a one-dimensional Gaussian kernel, from the leftmost tap to
+/// the rightmost.
+fn gaussian_kernel(sigma: f32) -> Vec<f32> {
+    let extent = blur_extent(sigma) as int;
+    let mut kernel = Vec::with_capacity((extent * 2 + 1) as uint);
+    let mut sum = 0.0;
+    for offset in range(-extent, extent + 1) {
+        let weight = (-((offset * offset) as f32) / (2.0 * sigma * sigma)).exp();
+        kernel.push(weight);
+        sum += weight;
+    }
+    for weight in kernel.mut_iter() {
+        *weight = *weight / sum;
+    }
+    kernel
+}
+
+/// Convolves `count` pixels of `source`, `step` bytes apart, with the kernel, writing the result
+/// to the same pixels of `destination`. Pixels outside the image are transparent.
+fn convolve(source: &[u8],
+            destination: &mut [u8],
+            start: uint,
+            step: uint,
+            count: uint,
+            kernel: &[f32]) {
+    let extent = (kernel.len() / 2) as int;
+    for i in range(0, count as int) {
+        let mut sums = [0.0f32, ..BYTES_PER_PIXEL];
+        for (tap, weight) in kernel.iter().enumerate() {
+            let j = i + tap as int - extent;
+            if j < 0 || j >= count as int {
+                continue
+            }
+            let offset = start + (j as uint) * step;
+            for channel in range(0, BYTES_PER_PIXEL) {
+                sums[channel] += (source[offset + channel] as f32) * *weight;
+            }
+        }
+        let offset = start + (i as uint) * step;
+        for channel in range(0, BYTES_PER_PIXEL) {
+            destination[offset + channel] = (sums[channel] + 0.5).min(255.0) as u8;
+        }
+    }
+}
+
+/// Blurs an image of 32-bit premultiplied pixels in place. The blur is separable, so it is done
+/// as a horizontal pass followed by a vertical pass.
+pub fn gaussian_blur(pixels: &mut [u8], width: uint, height: uint, stride: uint, sigma: f32) {
+    if sigma <= 0.0 || width == 0 || height == 0 {
+        return
+    }
+
+    let kernel = gaussian_kernel(sigma);
+    let mut scratch = Vec::from_elem(pixels.len(), 0u8);
+    for y in range(0, height) {
+        convolve(pixels, scratch.as_mut_slice(), y * stride, BYTES_PER_PIXEL, width,
+                 kernel.as_slice());
+    }
+    for x in range(0, width) {
+        convolve(scratch.as_slice(), pixels, x * BYTES_PER_PIXEL, stride, height,
+                 kernel.as_slice());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{gaussian_blur, gaussian_kernel};
+
+    #[test]
+    fn test_kernel_is_normalized_and_symmetric() {
+        let kernel = gaussian_kernel(2.0);
+        let kernel = kernel.as_slice();
+        let sum = kernel.iter().fold(0.0, |a, &b| a + b);
+        assert!((sum - 1.0).abs() < 0.0001);
+        assert!(kernel.len() == 13);
+        for i in range(0, kernel.len()) {
+            assert!(kernel[i] == kernel[kernel.len() - 1 - i]);
+        }
+    }
+
+    #[test]
+    fn test_blur_spreads_a_single_pixel() {
+        // A 5x1 image with one opaque white pixel in the middle.
+        let mut pixels = Vec::from_elem(5 * 4, 0u8);
+        for channel in range(0u, 4) {
+            *pixels.get_mut(2 * 4 + channel) = 255;
+        }
+        gaussian_blur(pixels.as_mut_slice(), 5, 1, 5 * 4, 1.0);
+        let pixels = pixels.as_slice();
+        assert!(pixels[2 * 4 + 3] < 255);
+        assert!(pixels[1 * 4 + 3] > 0 && pixels[1 * 4 + 3] == pixels[3 * 4 + 3]);
+        assert!(pixels[1 * 4 + 3] < pixels[2 * 4 + 3]);
+        assert!(pixels[0 * 4 + 3] < pixels[1 * 4 + 3]);
+    }
+}
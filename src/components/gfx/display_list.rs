@@ -14,21 +14,28 @@
 /// They are therefore not exactly analogous to constructs like Skia pictures, which consist of
 /// low-level drawing primitives.
 
+use blur;
 use color::Color;
 use render_context::RenderContext;
 use text::glyph::CharIndex;
 use text::TextRun;
 
+use azure::AzFloat;
 use collections::deque::Deque;
 use collections::dlist::DList;
 use collections::dlist;
+use collections::hashmap::{HashMap, HashSet};
 use geom::{Point2D, Rect, SideOffsets2D, Size2D};
+use geom::matrix2d::Matrix2D;
 use libc::uintptr_t;
 use servo_net::image::base::Image;
 use servo_util::geometry::Au;
+use servo_util::geometry;
 use servo_util::range::Range;
+use std::cmp;
 use std::fmt;
 use std::mem;
+use std::num::Zero;
 use std::slice::Items;
 use style::computed_values::border_style;
 use sync::Arc;
@@ -61,10 +68,8 @@ pub enum StackingLevel {
     FloatStackingLevel,
     /// All other content.
     ContentStackingLevel,
-    /// Positioned descendant stacking contexts, along with their `z-index` levels.
-    ///
-    /// TODO(pcwalton): `z-index` should be the actual CSS property value in order to handle
-    /// `auto`, not just an integer.
+    /// Positioned descendants and stacking contexts, along with their `z-index` levels. Elements
+    /// with `z-index: auto` use level zero.
     PositionedDescendantStackingLevel(i32)
 }
 
@@ -87,10 +92,7 @@ struct StackingContext {
     pub floats: DisplayList,
     /// All other content.
     pub content: DisplayList,
-    /// Positioned descendant stacking contexts, along with their `z-index` levels.
-    ///
-    /// TODO(pcwalton): `z-index` should be the actual CSS property value in order to handle
-    /// `auto`, not just an integer.
+    /// Positioned descendants and stacking contexts, along with their `z-index` levels.
     pub positioned_descendants: Vec<(i32, DisplayList)>,
 }
 
@@ -113,10 +115,14 @@ impl StackingContext {
             match item {
                 ClipDisplayItemClass(box ClipDisplayItem {
                     base: base,
-                    children: sublist
+                    children: sublist,
+                    radii: radii
                 }) => {
                     let sub_stacking_context = StackingContext::new(sublist);
-                    stacking_context.merge_with_clip(sub_stacking_context, &base.bounds, base.node)
+                    stacking_context.merge_with_clip(sub_stacking_context,
+                                                     &base.bounds,
+                                                     &radii,
+                                                     base.node)
                 }
                 item => {
                     match item.base().level {
@@ -151,11 +157,12 @@ impl StackingContext {
         stacking_context
     }
 
-    /// Merges another stacking context into this one, with the given clipping rectangle and DOM
-    /// node that supplies it.
+    /// Merges another stacking context into this one, with the given clipping rectangle, corner
+    /// radii and DOM node that supplies them.
     fn merge_with_clip(&mut self,
                        other: StackingContext,
                        clip_rect: &Rect<Au>,
+                       clip_radii: &BorderRadii<Au>,
                        clipping_dom_node: OpaqueNode) {
         let StackingContext {
             background_and_borders,
@@ -168,7 +175,8 @@ impl StackingContext {
         let push = |destination: &mut DisplayList, source: DisplayList, level| {
             if !source.is_empty() {
                 let base = BaseDisplayItem::new(*clip_rect, clipping_dom_node, level);
-                destination.push(ClipDisplayItemClass(box ClipDisplayItem::new(base, source)))
+                let clip = ClipDisplayItem::new_rounded(base, *clip_radii, source);
+                destination.push(ClipDisplayItemClass(box clip))
             }
         };
 
@@ -211,6 +219,178 @@ pub struct DisplayList {
     pub list: DList<DisplayItem>,
 }
 
+/// The smallest width and height of the cells of the grid of an `IndexedDisplayList`, in pixels.
+static MIN_GRID_CELL_SIZE: i32 = 256;
+
+/// The largest number of columns or rows in the grid of an `IndexedDisplayList`. The cells of
+/// the grids of very large display lists grow to stay under this limit.
+static MAX_GRID_CELLS_PER_AXIS: i32 = 256;
+
+/// A flattened display list with a uniform grid over its top-level items, so that the items that
+/// can draw into a tile or lie under a point can be found without walking the whole list. Each
+/// item is listed in every cell that the area it paints touches.
+pub struct IndexedDisplayList {
+    /// The top-level items of the display list, in painting order.
+    items: Vec<DisplayItem>,
+    /// The areas painted by the top-level items, in the same order.
+    item_bounds: Vec<Rect<Au>>,
+    /// The top left corner of the grid.
+    origin: Point2D<Au>,
+    /// The size of each cell of the grid.
+    cell_size: Size2D<Au>,
+    /// The number of columns of the grid.
+    columns: uint,
+    /// The number of rows of the grid.
+    rows: uint,
+    /// For each cell, row by row, the indices of the items touching it in painting order.
+    cells: Vec<Vec<uint>>,
+}
+
+impl IndexedDisplayList {
+    /// Indexes the given display list, which should have been flattened.
+    pub fn new(display_list: DisplayList) -> IndexedDisplayList {
+        let DisplayList {
+            list: list
+        } = display_list;
+        let items: Vec<DisplayItem> = list.move_iter().collect();
+        let item_bounds: Vec<Rect<Au>> = items.iter().map(|item| item.painted_bounds()).collect();
+
+        let mut bounds: Option<Rect<Au>> = None;
+        for item_bounds in item_bounds.iter() {
+            bounds = Some(match bounds {
+                None => *item_bounds,
+                Some(bounds) => bounds.union(item_bounds),
+            })
+        }
+        let bounds = bounds.unwrap_or(Rect(Point2D(Au(0), Au(0)), Size2D(Au(0), Au(0))));
+
+        let Au(min_cell_length) = Au::from_px(MIN_GRID_CELL_SIZE as int);
+        let cell_length = |length: Au| {
+            let Au(length) = length;
+            Au(cmp::max(min_cell_length, length / MAX_GRID_CELLS_PER_AXIS + 1))
+        };
+        let cell_size = Size2D(cell_length(bounds.size.width), cell_length(bounds.size.height));
+        let (Au(width), Au(height)) = (bounds.size.width, bounds.size.height);
+        let (Au(cell_width), Au(cell_height)) = (cell_size.width, cell_size.height);
+        let columns = (width / cell_width + 1) as uint;
+        let rows = (height / cell_height + 1) as uint;
+
+        let mut index = IndexedDisplayList {
+            items: items,
+            item_bounds: vec!(),
+            origin: bounds.origin,
+            cell_size: cell_size,
+            columns: columns,
+            rows: rows,
+            cells: Vec::from_fn(columns * rows, |_| vec!()),
+        };
+        for (item_index, bounds) in item_bounds.iter().enumerate() {
+            let (first_column, last_column, first_row, last_row) =
+                match index.cells_touching(bounds) {
+                    None => continue,
+                    Some(cell_range) => cell_range,
+                };
+            for row in range(first_row, last_row + 1) {
+                for column in range(first_column, last_column + 1) {
+                    index.cells.get_mut(row * columns + column).push(item_index)
+                }
+            }
+        }
+        index.item_bounds = item_bounds;
+        index
+    }
+
+    /// Returns an iterator over the top-level items of the display list, in painting order.
+    pub fn iter<'a>(&'a self) -> Items<'a,DisplayItem> {
+        self.items.iter()
+    }
+
+    /// Returns the number of top-level items of the display list.
+    pub fn len(&self) -> uint {
+        self.items.len()
+    }
+
+    /// Returns the areas that may look different between the given old version of this display
+    /// list and this one, in the coordinate system of the items.
+    pub fn dirty_rects(&self, old: &IndexedDisplayList) -> Vec<Rect<Au>> {
+        let mut dirty_rects = vec!();
+        add_dirty_rects(old.iter(), self.iter(), &mut dirty_rects);
+        dirty_rects
+    }
+
+    /// Returns the items that can draw into the given rect, for rendering a single tile. Only the
+    /// top level of the list is culled; clips and stacking contexts that touch the rect keep all
+    /// of their children.
+    pub fn cull<'a>(&'a self, rect: &Rect<Au>) -> CulledDisplayList<'a> {
+        let items = self.candidates(rect).move_iter().filter(|&item_index| {
+            self.item_bounds.get(item_index).intersects(rect)
+        }).map(|item_index| self.items.get(item_index)).collect();
+        CulledDisplayList {
+            items: items,
+        }
+    }
+
+    /// Returns the top-level items that can paint at the given point, in painting order.
+    pub fn items_at_point<'a>(&'a self, point: Point2D<Au>) -> Vec<&'a DisplayItem> {
+        let rect = Rect(point, Size2D(Au(0), Au(0)));
+        self.candidates(&rect).move_iter().filter(|&item_index| {
+            geometry::rect_contains_point(*self.item_bounds.get(item_index), point)
+        }).map(|item_index| self.items.get(item_index)).collect()
+    }
+
+    /// Returns the indices of the items listed in the cells that the given rect touches, in
+    /// painting order and without duplicates.
+    fn candidates(&self, rect: &Rect<Au>) -> Vec<uint> {
+        let mut candidates = vec!();
+        match self.cells_touching(rect) {
+            None => {}
+            Some((first_column, last_column, first_row, last_row)) => {
+                for row in range(first_row, last_row + 1) {
+                    for column in range(first_column, last_column + 1) {
+                        candidates.push_all(self.cells.get(row * self.columns + column).as_slice())
+                    }
+                }
+            }
+        }
+        candidates.sort();
+        candidates.dedup();
+        candidates
+    }
+
+    /// Returns the first and last columns and the first and last rows of the cells that the
+    /// given rect touches, or `None` if it lies outside the grid.
+    fn cells_touching(&self, rect: &Rect<Au>) -> Option<(uint, uint, uint, uint)> {
+        let Au(left) = rect.origin.x - self.origin.x;
+        let Au(top) = rect.origin.y - self.origin.y;
+        let Au(right) = rect.origin.x + rect.size.width - self.origin.x;
+        let Au(bottom) = rect.origin.y + rect.size.height - self.origin.y;
+        let (Au(cell_width), Au(cell_height)) = (self.cell_size.width, self.cell_size.height);
+        if right < 0 || bottom < 0 || left >= cell_width * (self.columns as i32) ||
+                top >= cell_height * (self.rows as i32) {
+            return None
+        }
+        Some(((cmp::max(left, 0) / cell_width) as uint,
+              cmp::min((right / cell_width) as uint, self.columns - 1),
+              (cmp::max(top, 0) / cell_height) as uint,
+              cmp::min((bottom / cell_height) as uint, self.rows - 1)))
+    }
+}
+
+/// The items of a display list that can draw into some rect, in painting order.
+pub struct CulledDisplayList<'a> {
+    items: Vec<&'a DisplayItem>,
+}
+
+impl<'a> CulledDisplayList<'a> {
+    /// Draws the items into the given render context. The display list they came from must have
+    /// been flattened.
+    pub fn draw_into_context(&self, render_context: &mut RenderContext) {
+        for item in self.items.iter() {
+            item.draw_into_context(render_context)
+        }
+    }
+}
+
 pub enum DisplayListIterator<'a> {
     EmptyDisplayListIterator,
     ParentDisplayListIterator(Items<'a,DisplayList>),
@@ -261,6 +441,15 @@ impl DisplayList {
         ParentDisplayItemIterator(self.list.iter())
     }
 
+    /// Returns the areas that render differently in this display list than in `old`, in the
+    /// coordinate system of the lists. Items are matched up by the node that generated them, so
+    /// a change to one node invalidates only what that node paints before and after the change.
+    pub fn dirty_rects(&self, old: &DisplayList) -> Vec<Rect<Au>> {
+        let mut dirty_rects = vec!();
+        add_dirty_rects(old.iter(), self.iter(), &mut dirty_rects);
+        dirty_rects
+    }
+
     /// Returns true if this list is empty and false otherwise.
     fn is_empty(&self) -> bool {
         self.list.len() == 0
@@ -271,8 +460,6 @@ impl DisplayList {
     ///
     /// This must be called before `draw_into_context()` is for correct results.
     pub fn flatten(self, resulting_level: StackingLevel) -> DisplayList {
-        // TODO(pcwalton): Sort positioned children according to z-index.
-
         let mut result = DisplayList::new();
         let StackingContext {
             background_and_borders,
@@ -285,7 +472,9 @@ impl DisplayList {
         // Steps 1 and 2: Borders and background for the root.
         result.push_all_move(background_and_borders);
 
-        // TODO(pcwalton): Sort positioned children according to z-index.
+        // Positioned descendants are painted in order of increasing z-index. The sort is stable,
+        // so those with the same z-index stay in tree order.
+        positioned_descendants.sort_by(|&(a, _), &(b, _)| a.cmp(&b));
 
         // Step 3: Positioned descendants with negative z-indices.
         for &(ref mut z_index, ref mut list) in positioned_descendants.mut_iter() {
@@ -318,6 +507,31 @@ impl DisplayList {
         result
     }
 
+    /// Flattens this display list and wraps it in a stacking context display item, so that it is
+    /// painted atomically with the given opacity and transform at the given `z-index` level of
+    /// the enclosing stacking context. Returns a display list containing just that item.
+    ///
+    /// The bounds are those of the untransformed items; the item's bounds are set to the bounding
+    /// box of the transformed bounds.
+    pub fn flatten_into_stacking_context(self,
+                                         bounds: Rect<Au>,
+                                         node: OpaqueNode,
+                                         z_index: i32,
+                                         opacity: AzFloat,
+                                         transform: Matrix2D<AzFloat>)
+                                         -> DisplayList {
+        let level = PositionedDescendantStackingLevel(z_index);
+        let stacking_context = box StackingContextDisplayItem {
+            base: BaseDisplayItem::new(transform_rect(&transform, &bounds), node, level),
+            children: self.flatten(level),
+            opacity: opacity,
+            transform: transform,
+        };
+        let mut result = DisplayList::new();
+        result.push(StackingContextDisplayItemClass(stacking_context));
+        result
+    }
+
     /// Sets the stacking level for this display list and all its subitems.
     fn set_stacking_level(&mut self, new_level: StackingLevel) {
         for item in self.list.mut_iter() {
@@ -335,9 +549,13 @@ pub enum DisplayItem {
     SolidColorDisplayItemClass(Box<SolidColorDisplayItem>),
     TextDisplayItemClass(Box<TextDisplayItem>),
     ImageDisplayItemClass(Box<ImageDisplayItem>),
+    GradientDisplayItemClass(Box<GradientDisplayItem>),
     BorderDisplayItemClass(Box<BorderDisplayItem>),
     LineDisplayItemClass(Box<LineDisplayItem>),
+    BoxShadowDisplayItemClass(Box<BoxShadowDisplayItem>),
+    TextShadowDisplayItemClass(Box<TextShadowDisplayItem>),
     ClipDisplayItemClass(Box<ClipDisplayItem>),
+    StackingContextDisplayItemClass(Box<StackingContextDisplayItem>),
 
     /// A pseudo-display item that exists only so that queries like `ContentBoxQuery` and
     /// `ContentBoxesQuery` can be answered.
@@ -369,12 +587,62 @@ impl BaseDisplayItem {
             level: level,
         }
     }
+
+    /// Creates the base of a display item for a shadow cast by the given bounds. The bounds of the
+    /// item cover everything the shadow can paint: the cast bounds moved by the offset, grown by
+    /// the spread radius and then by the distance over which the blur spreads each edge.
+    pub fn new_for_shadow(casting_bounds: Rect<Au>,
+                          offset: Point2D<Au>,
+                          blur_radius: Au,
+                          spread_radius: Au,
+                          node: OpaqueNode,
+                          level: StackingLevel)
+                          -> BaseDisplayItem {
+        let sigma = blur::sigma_for_blur_radius(blur_radius.to_nearest_px() as f32);
+        let inflation = spread_radius + Au::from_px(blur::blur_extent(sigma) as int);
+        let bounds = Rect(casting_bounds.origin + offset - Point2D(inflation, inflation),
+                          Size2D(casting_bounds.size.width + inflation + inflation,
+                                 casting_bounds.size.height + inflation + inflation));
+        BaseDisplayItem::new(bounds, node, level)
+    }
+}
+
+/// The radii of the four corners of a rounded rectangle. The width of each size is the horizontal
+/// radius of the corner and the height is the vertical radius.
+#[deriving(Clone, Eq)]
+pub struct BorderRadii<T> {
+    pub top_left: Size2D<T>,
+    pub top_right: Size2D<T>,
+    pub bottom_right: Size2D<T>,
+    pub bottom_left: Size2D<T>,
+}
+
+impl<T: Zero + Clone> BorderRadii<T> {
+    /// Returns radii describing a rectangle with square corners.
+    pub fn zero() -> BorderRadii<T> {
+        let zero = Size2D(Zero::zero(), Zero::zero());
+        BorderRadii {
+            top_left: zero.clone(),
+            top_right: zero.clone(),
+            bottom_right: zero.clone(),
+            bottom_left: zero,
+        }
+    }
+
+    /// Returns true if none of the corners are rounded.
+    pub fn is_square(&self) -> bool {
+        let corners = [&self.top_left, &self.top_right, &self.bottom_right, &self.bottom_left];
+        corners.iter().all(|corner| corner.width.is_zero() || corner.height.is_zero())
+    }
 }
 
 /// Renders a solid color.
 pub struct SolidColorDisplayItem {
     pub base: BaseDisplayItem,
     pub color: Color,
+
+    /// The radii of the corners of the filled area.
+    pub radii: BorderRadii<Au>,
 }
 
 /// Text decoration information.
@@ -416,6 +684,42 @@ pub struct ImageDisplayItem {
     pub stretch_size: Size2D<Au>,
 }
 
+/// Renders a gradient filling the bounds.
+pub struct GradientDisplayItem {
+    pub base: BaseDisplayItem,
+
+    /// The shape of the gradient, in page coordinates.
+    pub geometry: GradientGeometry,
+
+    /// The color stops, with offsets between 0.0 (the start of the gradient) and 1.0 (the end).
+    pub stops: Vec<GradientStop>,
+
+    /// True if the gradient repeats beyond its start and end instead of extending the colors at
+    /// its ends.
+    pub repeating: bool,
+}
+
+/// The shape of a gradient.
+#[deriving(Clone)]
+pub enum GradientGeometry {
+    /// A linear gradient from the start point to the end point.
+    LinearGradientGeometry(Point2D<Au>, Point2D<Au>),
+
+    /// A radial gradient around the center point, from the ellipse with the first radii to the
+    /// ellipse with the second. Both ellipses have the same proportions.
+    RadialGradientGeometry(Point2D<Au>, Size2D<Au>, Size2D<Au>),
+}
+
+/// A color stop of a gradient.
+#[deriving(Clone)]
+pub struct GradientStop {
+    /// The position of the stop along the gradient, from 0.0 to 1.0.
+    pub offset: f32,
+
+    /// The color of the stop.
+    pub color: Color,
+}
+
 /// Renders a border.
 pub struct BorderDisplayItem {
     pub base: BaseDisplayItem,
@@ -427,7 +731,61 @@ pub struct BorderDisplayItem {
     pub color: SideOffsets2D<Color>,
 
     /// The border styles.
-    pub style: SideOffsets2D<border_style::T>
+    pub style: SideOffsets2D<border_style::T>,
+
+    /// The radii of the outer edge of the border at each corner.
+    pub radius: BorderRadii<Au>,
+}
+
+/// Renders the shadow of a box.
+pub struct BoxShadowDisplayItem {
+    /// The base information. For shadows outside the box, the bounds include the blurred area.
+    pub base: BaseDisplayItem,
+
+    /// The border box of the element casting the shadow.
+    pub box_bounds: Rect<Au>,
+
+    /// The radii of the corners of the border box.
+    pub radii: BorderRadii<Au>,
+
+    /// The offset of the shadow from the box.
+    pub offset: Point2D<Au>,
+
+    /// The color of the shadow.
+    pub color: Color,
+
+    /// The blur radius of the shadow.
+    pub blur_radius: Au,
+
+    /// The distance by which the shadow is grown, or shrunk if negative, before blurring.
+    pub spread_radius: Au,
+
+    /// True if the shadow is painted inside the box instead of outside it.
+    pub inset: bool,
+}
+
+/// Renders the shadow of a run of text.
+pub struct TextShadowDisplayItem {
+    /// The base information. The bounds include the blurred area.
+    pub base: BaseDisplayItem,
+
+    /// The text run.
+    pub text_run: Arc<Box<TextRun>>,
+
+    /// The range of text within the text run.
+    pub range: Range<CharIndex>,
+
+    /// The bounds of the text casting the shadow, as in `TextDisplayItem`.
+    pub text_bounds: Rect<Au>,
+
+    /// The offset of the shadow from the text.
+    pub offset: Point2D<Au>,
+
+    /// The color of the shadow.
+    pub color: Color,
+
+    /// The blur radius of the shadow.
+    pub blur_radius: Au,
 }
 
 /// Renders a line segment.
@@ -448,17 +806,224 @@ pub struct ClipDisplayItem {
 
     /// The child nodes.
     pub children: DisplayList,
+
+    /// The radii of the corners of the clipping region.
+    pub radii: BorderRadii<Au>,
 }
 
 impl ClipDisplayItem {
     pub fn new(base: BaseDisplayItem, children: DisplayList) -> ClipDisplayItem {
+        ClipDisplayItem::new_rounded(base, BorderRadii::zero(), children)
+    }
+
+    pub fn new_rounded(base: BaseDisplayItem, radii: BorderRadii<Au>, children: DisplayList)
+                       -> ClipDisplayItem {
         ClipDisplayItem {
             base: base,
             children: children,
+            radii: radii,
         }
     }
 }
 
+/// Paints the flattened display items of a stacking context as a group. See CSS 2.1 Appendix E.
+pub struct StackingContextDisplayItem {
+    /// The base information.
+    pub base: BaseDisplayItem,
+
+    /// The display items of the stacking context, flattened in painting order.
+    pub children: DisplayList,
+
+    /// The opacity with which the group is composited into its parent.
+    pub opacity: AzFloat,
+
+    /// The transform applied to the group, in page coordinates measured in pixels.
+    pub transform: Matrix2D<AzFloat>,
+}
+
+impl StackingContextDisplayItem {
+    /// Maps a point in the coordinate system of the parent of this stacking context to the
+    /// coordinate system of its children, for hit testing. Returns `None` if the transform is not
+    /// invertible, in which case nothing in the stacking context can be hit.
+    pub fn point_in_children(&self, point: &Point2D<Au>) -> Option<Point2D<Au>> {
+        let m = &self.transform;
+        let determinant = m.m11 * m.m22 - m.m12 * m.m21;
+        if determinant == 0.0 {
+            return None
+        }
+        let x = point.x.to_nearest_px() as AzFloat - m.m31;
+        let y = point.y.to_nearest_px() as AzFloat - m.m32;
+        Some(Point2D(Au::from_frac_px(((x * m.m22 - y * m.m21) / determinant) as f64),
+                     Au::from_frac_px(((y * m.m11 - x * m.m12) / determinant) as f64)))
+    }
+}
+
+/// Returns the bounding box of the given rectangle after applying a transform in pixels.
+pub fn transform_rect(transform: &Matrix2D<AzFloat>, rect: &Rect<Au>) -> Rect<Au> {
+    let (left, top) = (rect.origin.x.to_nearest_px() as AzFloat,
+                       rect.origin.y.to_nearest_px() as AzFloat);
+    let (right, bottom) = ((rect.origin.x + rect.size.width).to_nearest_px() as AzFloat,
+                           (rect.origin.y + rect.size.height).to_nearest_px() as AzFloat);
+    let corners = [(left, top), (right, top), (right, bottom), (left, bottom)];
+    let mut transformed = corners.iter().map(|&(x, y)| {
+        (x * transform.m11 + y * transform.m21 + transform.m31,
+         x * transform.m12 + y * transform.m22 + transform.m32)
+    });
+    let (first_x, first_y) = transformed.next().unwrap();
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (first_x, first_y, first_x, first_y);
+    for (x, y) in transformed {
+        min_x = min_x.min(x);
+        min_y = min_y.min(y);
+        max_x = max_x.max(x);
+        max_y = max_y.max(y);
+    }
+    let origin = Point2D(Au::from_frac_px(min_x.floor() as f64),
+                         Au::from_frac_px(min_y.floor() as f64));
+    Rect(origin,
+         Size2D(Au::from_frac_px(max_x.ceil() as f64) - origin.x,
+                Au::from_frac_px(max_y.ceil() as f64) - origin.y))
+}
+
+/// The display items that one node generated at one level of a pair of display lists, in
+/// painting order.
+struct NodeItems<'a> {
+    old: Vec<&'a DisplayItem>,
+    new: Vec<&'a DisplayItem>,
+}
+
+/// Adds the areas that render differently in the `new` items than in the `old` ones to
+/// `dirty_rects`.
+fn add_dirty_rects<'a,O:Iterator<&'a DisplayItem>,N:Iterator<&'a DisplayItem>>(
+                   mut old: O,
+                   mut new: N,
+                   dirty_rects: &mut Vec<Rect<Au>>) {
+    let mut nodes: HashMap<uintptr_t, NodeItems<'a>> = HashMap::new();
+    let (mut old_order, mut new_order) = (vec!(), vec!());
+    for item in old {
+        let id = item.base().node.id();
+        let node_items = nodes.find_or_insert_with(id, |_| NodeItems { old: vec!(), new: vec!() });
+        if node_items.old.is_empty() {
+            old_order.push(id)
+        }
+        node_items.old.push(item)
+    }
+    for item in new {
+        let id = item.base().node.id();
+        let node_items = nodes.find_or_insert_with(id, |_| NodeItems { old: vec!(), new: vec!() });
+        if node_items.new.is_empty() {
+            new_order.push(id)
+        }
+        node_items.new.push(item)
+    }
+
+    // A node that now paints before or after a different set of nodes may overlap them
+    // differently, so everything it paints is dirty.
+    let old_order: Vec<uintptr_t> = old_order.move_iter().filter(|id| {
+        !nodes.get(id).new.is_empty()
+    }).collect();
+    let new_order: Vec<uintptr_t> = new_order.move_iter().filter(|id| {
+        !nodes.get(id).old.is_empty()
+    }).collect();
+    let mut reordered = HashSet::new();
+    for (old_id, new_id) in old_order.iter().zip(new_order.iter()) {
+        if old_id != new_id {
+            reordered.insert(*old_id);
+            reordered.insert(*new_id);
+        }
+    }
+
+    for (id, node_items) in nodes.iter() {
+        if node_items.old.len() != node_items.new.len() || reordered.contains(id) {
+            for item in node_items.old.iter().chain(node_items.new.iter()) {
+                dirty_rects.push(item.painted_bounds())
+            }
+            continue
+        }
+        for (old_item, new_item) in node_items.old.iter().zip(node_items.new.iter()) {
+            add_dirty_rects_for_item(*old_item, *new_item, dirty_rects)
+        }
+    }
+}
+
+/// Adds the areas that render differently in `new` than in `old`, two items generated by the
+/// same node, to `dirty_rects`. The children of clips and stacking contexts that did not change
+/// themselves are compared one by one.
+fn add_dirty_rects_for_item(old: &DisplayItem,
+                            new: &DisplayItem,
+                            dirty_rects: &mut Vec<Rect<Au>>) {
+    match (old, new) {
+        (&ClipDisplayItemClass(ref old_clip), &ClipDisplayItemClass(ref new_clip))
+                if old_clip.base.bounds == new_clip.base.bounds &&
+                    old_clip.radii == new_clip.radii => {
+            let mut child_dirty_rects = vec!();
+            add_dirty_rects(old_clip.children.iter(),
+                            new_clip.children.iter(),
+                            &mut child_dirty_rects);
+            for rect in child_dirty_rects.iter() {
+                match rect.intersection(&new_clip.base.bounds) {
+                    Some(rect) => dirty_rects.push(rect),
+                    None => {}
+                }
+            }
+        }
+        (&StackingContextDisplayItemClass(ref old_context),
+         &StackingContextDisplayItemClass(ref new_context))
+                if old_context.opacity == new_context.opacity &&
+                    same_transform(&old_context.transform, &new_context.transform) => {
+            let mut child_dirty_rects = vec!();
+            add_dirty_rects(old_context.children.iter(),
+                            new_context.children.iter(),
+                            &mut child_dirty_rects);
+            for rect in child_dirty_rects.iter() {
+                dirty_rects.push(transform_rect(&new_context.transform, rect))
+            }
+        }
+        _ if old.paints_same_as(new) => {}
+        _ => {
+            dirty_rects.push(old.painted_bounds());
+            dirty_rects.push(new.painted_bounds());
+        }
+    }
+}
+
+fn same_color(a: &Color, b: &Color) -> bool {
+    a.r == b.r && a.g == b.g && a.b == b.b && a.a == b.a
+}
+
+fn same_optional_color(a: &Option<Color>, b: &Option<Color>) -> bool {
+    match (a, b) {
+        (&Some(ref a), &Some(ref b)) => same_color(a, b),
+        (&None, &None) => true,
+        _ => false,
+    }
+}
+
+fn same_transform(a: &Matrix2D<AzFloat>, b: &Matrix2D<AzFloat>) -> bool {
+    a.m11 == b.m11 && a.m12 == b.m12 && a.m21 == b.m21 && a.m22 == b.m22 &&
+        a.m31 == b.m31 && a.m32 == b.m32
+}
+
+/// Returns true if the two ranges of the two text runs contain the same characters in the same
+/// font. Text runs are rebuilt on every reflow, so they have to be compared by value.
+fn same_text(a: &TextRun, a_range: &Range<CharIndex>, b: &TextRun, b_range: &Range<CharIndex>)
+             -> bool {
+    a_range.begin() == b_range.begin() && a_range.length() == b_range.length() &&
+        a.font_descriptor == b.font_descriptor && *a.text == *b.text
+}
+
+fn same_gradient_geometry(a: &GradientGeometry, b: &GradientGeometry) -> bool {
+    match (a, b) {
+        (&LinearGradientGeometry(a_start, a_end), &LinearGradientGeometry(b_start, b_end)) => {
+            a_start == b_start && a_end == b_end
+        }
+        (&RadialGradientGeometry(a_center, a_start_radii, a_end_radii),
+         &RadialGradientGeometry(b_center, b_start_radii, b_end_radii)) => {
+            a_center == b_center && a_start_radii == b_start_radii && a_end_radii == b_end_radii
+        }
+        _ => false,
+    }
+}
+
 pub enum DisplayItemIterator<'a> {
     EmptyDisplayItemIterator,
     ParentDisplayItemIterator(dlist::Items<'a,DisplayItem>),
@@ -482,17 +1047,29 @@ impl DisplayItem {
 
         match *self {
             SolidColorDisplayItemClass(ref solid_color) => {
-                render_context.draw_solid_color(&solid_color.base.bounds, solid_color.color)
+                render_context.draw_rounded_solid_color(&solid_color.base.bounds,
+                                                        &solid_color.radii,
+                                                        solid_color.color)
             }
 
             ClipDisplayItemClass(ref clip) => {
-                render_context.draw_push_clip(&clip.base.bounds);
+                render_context.draw_push_clip(&clip.base.bounds, &clip.radii);
                 for item in clip.children.iter() {
                     (*item).draw_into_context(render_context);
                 }
                 render_context.draw_pop_clip();
             }
 
+            StackingContextDisplayItemClass(ref stacking_context) => {
+                render_context.draw_with_transform(&stacking_context.transform, |render_context| {
+                    render_context.draw_with_opacity(stacking_context.opacity, |render_context| {
+                        for item in stacking_context.children.iter() {
+                            item.draw_into_context(render_context);
+                        }
+                    });
+                });
+            }
+
             TextDisplayItemClass(ref text) => {
                 debug!("Drawing text at {:?}.", text.base.bounds);
 
@@ -560,11 +1137,49 @@ impl DisplayItem {
                 }
             }
 
+            GradientDisplayItemClass(ref gradient) => {
+                render_context.draw_gradient(&gradient.base.bounds,
+                                             &gradient.geometry,
+                                             gradient.stops.as_slice(),
+                                             gradient.repeating)
+            }
+
+            BoxShadowDisplayItemClass(ref shadow) => {
+                render_context.draw_box_shadow(&shadow.base.bounds,
+                                               &shadow.box_bounds,
+                                               &shadow.radii,
+                                               shadow.offset,
+                                               shadow.color,
+                                               shadow.blur_radius,
+                                               shadow.spread_radius,
+                                               shadow.inset)
+            }
+
+            TextShadowDisplayItemClass(ref shadow) => {
+                let text_run = shadow.text_run.clone();
+                let font = render_context.font_ctx
+                                         .get_font_by_descriptor(&text_run.font_descriptor)
+                                         .unwrap();
+                let ascent = font.borrow().metrics.ascent;
+                let origin = shadow.text_bounds.origin + shadow.offset;
+                let baseline_origin = Point2D(origin.x, origin.y + ascent);
+                render_context.draw_blurred(&shadow.base.bounds,
+                                            shadow.blur_radius,
+                                            |render_context| {
+                    font.borrow_mut().draw_text_into_context(render_context,
+                                                             &*shadow.text_run,
+                                                             &shadow.range,
+                                                             baseline_origin,
+                                                             shadow.color);
+                });
+            }
+
             BorderDisplayItemClass(ref border) => {
                 render_context.draw_border(&border.base.bounds,
                                            border.border,
                                            border.color,
-                                           border.style)
+                                           border.style,
+                                           &border.radius)
             }
 
             LineDisplayItemClass(ref line) => {
@@ -582,9 +1197,13 @@ impl DisplayItem {
             SolidColorDisplayItemClass(ref solid_color) => &solid_color.base,
             TextDisplayItemClass(ref text) => &text.base,
             ImageDisplayItemClass(ref image_item) => &image_item.base,
+            GradientDisplayItemClass(ref gradient) => &gradient.base,
             BorderDisplayItemClass(ref border) => &border.base,
+            BoxShadowDisplayItemClass(ref shadow) => &shadow.base,
+            TextShadowDisplayItemClass(ref shadow) => &shadow.base,
             LineDisplayItemClass(ref line) => &line.base,
             ClipDisplayItemClass(ref clip) => &clip.base,
+            StackingContextDisplayItemClass(ref stacking_context) => &stacking_context.base,
             PseudoDisplayItemClass(ref base) => &**base,
         }
     }
@@ -594,9 +1213,15 @@ impl DisplayItem {
             SolidColorDisplayItemClass(ref mut solid_color) => &mut solid_color.base,
             TextDisplayItemClass(ref mut text) => &mut text.base,
             ImageDisplayItemClass(ref mut image_item) => &mut image_item.base,
+            GradientDisplayItemClass(ref mut gradient) => &mut gradient.base,
             BorderDisplayItemClass(ref mut border) => &mut border.base,
+            BoxShadowDisplayItemClass(ref mut shadow) => &mut shadow.base,
+            TextShadowDisplayItemClass(ref mut shadow) => &mut shadow.base,
             LineDisplayItemClass(ref mut line) => &mut line.base,
             ClipDisplayItemClass(ref mut clip) => &mut clip.base,
+            StackingContextDisplayItemClass(ref mut stacking_context) => {
+                &mut stacking_context.base
+            }
             PseudoDisplayItemClass(ref mut base) => &mut **base,
         }
     }
@@ -605,13 +1230,90 @@ impl DisplayItem {
         self.base().bounds
     }
 
+    /// Returns the bounds of everything this item can paint. Unlike `bounds()`, this includes the
+    /// descendants of a stacking context that overflow it.
+    pub fn painted_bounds(&self) -> Rect<Au> {
+        match *self {
+            StackingContextDisplayItemClass(ref stacking_context) => {
+                let transform = &stacking_context.transform;
+                stacking_context.children.iter().fold(stacking_context.base.bounds, |bounds, kid| {
+                    bounds.union(&transform_rect(transform, &kid.painted_bounds()))
+                })
+            }
+            _ => self.bounds(),
+        }
+    }
+
+    /// Returns true if this item paints exactly what `other` paints. Clips and stacking contexts
+    /// are never the same as anything, since their children have to be compared as well.
+    fn paints_same_as(&self, other: &DisplayItem) -> bool {
+        if self.bounds() != other.bounds() {
+            return false
+        }
+        match (self, other) {
+            (&SolidColorDisplayItemClass(ref a), &SolidColorDisplayItemClass(ref b)) => {
+                same_color(&a.color, &b.color) && a.radii == b.radii
+            }
+            (&TextDisplayItemClass(ref a), &TextDisplayItemClass(ref b)) => {
+                same_text(&**a.text_run, &a.range, &**b.text_run, &b.range) &&
+                    same_color(&a.text_color, &b.text_color) &&
+                    same_optional_color(&a.text_decorations.underline,
+                                        &b.text_decorations.underline) &&
+                    same_optional_color(&a.text_decorations.overline,
+                                        &b.text_decorations.overline) &&
+                    same_optional_color(&a.text_decorations.line_through,
+                                        &b.text_decorations.line_through)
+            }
+            (&ImageDisplayItemClass(ref a), &ImageDisplayItemClass(ref b)) => {
+                (&*a.image as *Box<Image>) == (&*b.image as *Box<Image>) &&
+                    a.stretch_size == b.stretch_size
+            }
+            (&GradientDisplayItemClass(ref a), &GradientDisplayItemClass(ref b)) => {
+                same_gradient_geometry(&a.geometry, &b.geometry) &&
+                    a.repeating == b.repeating &&
+                    a.stops.len() == b.stops.len() &&
+                    a.stops.iter().zip(b.stops.iter()).all(|(a, b)| {
+                        a.offset == b.offset && same_color(&a.color, &b.color)
+                    })
+            }
+            (&BorderDisplayItemClass(ref a), &BorderDisplayItemClass(ref b)) => {
+                a.border == b.border && a.style == b.style && a.radius == b.radius &&
+                    same_color(&a.color.top, &b.color.top) &&
+                    same_color(&a.color.right, &b.color.right) &&
+                    same_color(&a.color.bottom, &b.color.bottom) &&
+                    same_color(&a.color.left, &b.color.left)
+            }
+            (&BoxShadowDisplayItemClass(ref a), &BoxShadowDisplayItemClass(ref b)) => {
+                a.box_bounds == b.box_bounds && a.radii == b.radii && a.offset == b.offset &&
+                    same_color(&a.color, &b.color) && a.blur_radius == b.blur_radius &&
+                    a.spread_radius == b.spread_radius && a.inset == b.inset
+            }
+            (&TextShadowDisplayItemClass(ref a), &TextShadowDisplayItemClass(ref b)) => {
+                same_text(&**a.text_run, &a.range, &**b.text_run, &b.range) &&
+                    a.text_bounds == b.text_bounds && a.offset == b.offset &&
+                    same_color(&a.color, &b.color) && a.blur_radius == b.blur_radius
+            }
+            (&LineDisplayItemClass(ref a), &LineDisplayItemClass(ref b)) => {
+                same_color(&a.color, &b.color) && a.style == b.style
+            }
+            (&PseudoDisplayItemClass(_), &PseudoDisplayItemClass(_)) => true,
+            _ => false,
+        }
+    }
+
     pub fn children<'a>(&'a self) -> DisplayItemIterator<'a> {
         match *self {
             ClipDisplayItemClass(ref clip) => ParentDisplayItemIterator(clip.children.list.iter()),
+            StackingContextDisplayItemClass(ref stacking_context) => {
+                ParentDisplayItemIterator(stacking_context.children.list.iter())
+            }
             SolidColorDisplayItemClass(..) |
             TextDisplayItemClass(..) |
             ImageDisplayItemClass(..) |
+            GradientDisplayItemClass(..) |
             BorderDisplayItemClass(..) |
+            BoxShadowDisplayItemClass(..) |
+            TextShadowDisplayItemClass(..) |
             LineDisplayItemClass(..) |
             PseudoDisplayItemClass(..) => EmptyDisplayItemIterator,
         }
@@ -621,10 +1323,16 @@ impl DisplayItem {
     fn mut_sublist<'a>(&'a mut self) -> Option<&'a mut DisplayList> {
         match *self {
             ClipDisplayItemClass(ref mut clip) => Some(&mut clip.children),
+            StackingContextDisplayItemClass(ref mut stacking_context) => {
+                Some(&mut stacking_context.children)
+            }
             SolidColorDisplayItemClass(..) |
             TextDisplayItemClass(..) |
             ImageDisplayItemClass(..) |
+            GradientDisplayItemClass(..) |
             BorderDisplayItemClass(..) |
+            BoxShadowDisplayItemClass(..) |
+            TextShadowDisplayItemClass(..) |
             LineDisplayItemClass(..) |
             PseudoDisplayItemClass(..) => None,
         }
@@ -649,9 +1357,13 @@ impl fmt::Show for DisplayItem {
                 SolidColorDisplayItemClass(_) => "SolidColor",
                 TextDisplayItemClass(_) => "Text",
                 ImageDisplayItemClass(_) => "Image",
+                GradientDisplayItemClass(_) => "Gradient",
                 BorderDisplayItemClass(_) => "Border",
+                BoxShadowDisplayItemClass(_) => "BoxShadow",
+                TextShadowDisplayItemClass(_) => "TextShadow",
                 LineDisplayItemClass(_) => "Line",
                 ClipDisplayItemClass(_) => "Clip",
+                StackingContextDisplayItemClass(_) => "StackingContext",
                 PseudoDisplayItemClass(_) => "Pseudo",
             },
             self.base().bounds,
@@ -659,3 +1371,147 @@ impl fmt::Show for DisplayItem {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{BaseDisplayItem, BorderRadii, ContentStackingLevel, DisplayList};
+    use super::{IndexedDisplayList, OpaqueNode, SolidColorDisplayItem, SolidColorDisplayItemClass};
+    use color;
+    use color::Color;
+
+    use geom::matrix2d::Matrix2D;
+    use geom::{Point2D, Rect, Size2D};
+    use servo_util::geometry::Au;
+
+    fn rect(x: int, y: int, width: int, height: int) -> Rect<Au> {
+        Rect(Point2D(Au::from_px(x), Au::from_px(y)),
+             Size2D(Au::from_px(width), Au::from_px(height)))
+    }
+
+    fn solid_color(list: &mut DisplayList, bounds: Rect<Au>, node: uint, color: Color) {
+        list.push(SolidColorDisplayItemClass(box SolidColorDisplayItem {
+            base: BaseDisplayItem::new(bounds, OpaqueNode(node), ContentStackingLevel),
+            color: color,
+            radii: BorderRadii::zero(),
+        }))
+    }
+
+    #[test]
+    fn test_dirty_rects_of_changed_item() {
+        let (mut old, mut new) = (DisplayList::new(), DisplayList::new());
+        solid_color(&mut old, rect(0, 0, 100, 100), 1, color::rgb(255, 0, 0));
+        solid_color(&mut old, rect(0, 100, 100, 100), 2, color::rgb(0, 255, 0));
+        solid_color(&mut new, rect(0, 0, 100, 100), 1, color::rgb(255, 0, 0));
+        solid_color(&mut new, rect(0, 100, 100, 100), 2, color::rgb(0, 0, 255));
+
+        assert!(new.dirty_rects(&old) == vec!(rect(0, 100, 100, 100), rect(0, 100, 100, 100)));
+        assert!(new.dirty_rects(&new).is_empty());
+    }
+
+    #[test]
+    fn test_dirty_rects_of_moved_item() {
+        let (mut old, mut new) = (DisplayList::new(), DisplayList::new());
+        solid_color(&mut old, rect(0, 0, 10, 10), 1, color::rgb(255, 0, 0));
+        solid_color(&mut new, rect(50, 0, 10, 10), 1, color::rgb(255, 0, 0));
+
+        assert!(new.dirty_rects(&old) == vec!(rect(0, 0, 10, 10), rect(50, 0, 10, 10)));
+    }
+
+    #[test]
+    fn test_dirty_rects_of_changed_stacking_context() {
+        // The child overflows the border box of the stacking context.
+        let stacking_context = |opacity| {
+            let mut children = DisplayList::new();
+            solid_color(&mut children, rect(0, 0, 200, 50), 2, color::rgb(255, 0, 0));
+            children.flatten_into_stacking_context(rect(0, 0, 50, 50),
+                                                   OpaqueNode(1),
+                                                   0,
+                                                   opacity,
+                                                   Matrix2D::identity())
+        };
+        let (old, new) = (stacking_context(1.0), stacking_context(0.5));
+
+        assert!(new.dirty_rects(&old) == vec!(rect(0, 0, 200, 50), rect(0, 0, 200, 50)));
+    }
+
+    /// Returns an indexed list of three items in different cells of a grid of 256px cells, with
+    /// three columns and two rows, and a fourth one on top of the first.
+    fn indexed_display_list() -> IndexedDisplayList {
+        let mut list = DisplayList::new();
+        solid_color(&mut list, rect(0, 0, 100, 100), 1, color::rgb(255, 0, 0));
+        solid_color(&mut list, rect(300, 0, 100, 100), 2, color::rgb(0, 255, 0));
+        solid_color(&mut list, rect(0, 300, 600, 100), 3, color::rgb(0, 0, 255));
+        solid_color(&mut list, rect(50, 50, 100, 100), 4, color::rgb(0, 0, 0));
+        IndexedDisplayList::new(list)
+    }
+
+    #[test]
+    fn test_cells_touching() {
+        let index = indexed_display_list();
+        assert!(index.cells_touching(&rect(0, 0, 100, 100)) == Some((0, 0, 0, 0)));
+        assert!(index.cells_touching(&rect(300, 0, 100, 100)) == Some((1, 1, 0, 0)));
+        assert!(index.cells_touching(&rect(0, 300, 600, 100)) == Some((0, 2, 1, 1)));
+
+        // Rects that stick out of the grid are clipped to it.
+        assert!(index.cells_touching(&rect(-100, -100, 200, 200)) == Some((0, 0, 0, 0)));
+        assert!(index.cells_touching(&rect(500, 300, 1000, 1000)) == Some((1, 2, 1, 1)));
+
+        // Rects outside of the grid touch no cell.
+        assert!(index.cells_touching(&rect(-50, -50, 10, 10)) == None);
+        assert!(index.cells_touching(&rect(900, 0, 10, 10)) == None);
+        assert!(index.cells_touching(&rect(0, 600, 10, 10)) == None);
+    }
+
+    #[test]
+    fn test_candidates() {
+        let index = indexed_display_list();
+        assert!(index.candidates(&rect(0, 0, 600, 400)) == vec!(0, 1, 2, 3));
+        assert!(index.candidates(&rect(0, 0, 10, 10)) == vec!(0, 3));
+
+        // Candidates come from whole cells, so they need not intersect the rect.
+        assert!(index.candidates(&rect(200, 0, 10, 10)) == vec!(0, 3));
+
+        // Items listed in several of the cells are only returned once, in painting order.
+        assert!(index.candidates(&rect(250, 250, 20, 20)) == vec!(0, 1, 2, 3));
+        assert!(index.candidates(&rect(900, 0, 10, 10)).is_empty());
+    }
+
+    #[test]
+    fn test_cull() {
+        let index = indexed_display_list();
+        let culled_nodes = |tile: Rect<Au>| -> Vec<OpaqueNode> {
+            index.cull(&tile).items.iter().map(|item| item.base().node).collect()
+        };
+
+        // A tile keeps the items that intersect it, in painting order.
+        assert!(culled_nodes(rect(0, 0, 256, 256)) == vec!(OpaqueNode(1), OpaqueNode(4)));
+        assert!(culled_nodes(rect(256, 0, 256, 256)) == vec!(OpaqueNode(2)));
+        assert!(culled_nodes(rect(256, 256, 256, 256)) == vec!(OpaqueNode(3)));
+        assert!(culled_nodes(rect(0, 0, 600, 400)) ==
+                vec!(OpaqueNode(1), OpaqueNode(2), OpaqueNode(3), OpaqueNode(4)));
+
+        // Items in the cells of a tile that do not intersect it are culled.
+        assert!(culled_nodes(rect(160, 160, 96, 96)).is_empty());
+        assert!(culled_nodes(rect(120, 120, 40, 40)) == vec!(OpaqueNode(4)));
+        assert!(culled_nodes(rect(900, 900, 256, 256)).is_empty());
+    }
+
+    #[test]
+    fn test_items_at_point() {
+        let index = indexed_display_list();
+        let nodes_at = |x: int, y: int| -> Vec<OpaqueNode> {
+            let point = Point2D(Au::from_px(x), Au::from_px(y));
+            index.items_at_point(point).iter().map(|item| item.base().node).collect()
+        };
+        assert!(nodes_at(20, 20) == vec!(OpaqueNode(1)));
+        assert!(nodes_at(350, 50) == vec!(OpaqueNode(2)));
+        assert!(nodes_at(500, 350) == vec!(OpaqueNode(3)));
+
+        // Overlapping items are returned in painting order.
+        assert!(nodes_at(75, 75) == vec!(OpaqueNode(1), OpaqueNode(4)));
+
+        // Only the items under the point are returned, not all those of its cell.
+        assert!(nodes_at(200, 50).is_empty());
+        assert!(nodes_at(1000, 1000).is_empty());
+    }
+}
@@ -0,0 +1,1252 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A compact binary format for display lists. The render layers of a page captured with
+//! `--dump-display-list` can be rendered again with `--replay-display-list` without running script
+//! or layout, which makes rendering bugs reproducible and lets rasterization be benchmarked on its
+//! own.
+//!
+//! Each layer is written with its ID, its position and clip rect in pixels and its scroll policy,
+//! followed by its display list. Layers are written in the order in which the compositor stacks
+//! them.
+//!
+//! All numbers are little-endian. Text runs are written as the glyphs that their items draw along
+//! with the descriptor of their font, so replaying text requires the same fonts to be installed.
+//! Nodes are written as their addresses, which only serve to tell the items of different nodes
+//! apart.
+
+use color::Color;
+use display_list::{BackgroundAndBordersStackingLevel, BaseDisplayItem};
+use display_list::{BlockBackgroundsAndBordersStackingLevel, BorderDisplayItem};
+use display_list::{BorderDisplayItemClass, BorderRadii, BoxShadowDisplayItem};
+use display_list::{BoxShadowDisplayItemClass, ClipDisplayItem, ClipDisplayItemClass};
+use display_list::{ContentStackingLevel, DisplayItem, DisplayList, FloatStackingLevel};
+use display_list::{GradientDisplayItem, GradientDisplayItemClass, GradientStop};
+use display_list::{ImageDisplayItem, ImageDisplayItemClass, LineDisplayItem, LineDisplayItemClass};
+use display_list::{LinearGradientGeometry, OpaqueNode, PositionedDescendantStackingLevel};
+use display_list::{PseudoDisplayItemClass, RadialGradientGeometry, SolidColorDisplayItem};
+use display_list::{SolidColorDisplayItemClass, StackingContextDisplayItem};
+use display_list::{StackingContextDisplayItemClass, StackingLevel, TextDecorations};
+use display_list::{TextDisplayItem, TextDisplayItemClass, TextShadowDisplayItem};
+use display_list::{TextShadowDisplayItemClass};
+use render_task::RenderLayer;
+use font::{FontDescriptor, FontMetrics, FontStyle, SelectorPlatformIdentifier};
+use text::glyph::{CharIndex, GlyphData, GlyphStore};
+use text::TextRun;
+
+use geom::{Point2D, Rect, SideOffsets2D, Size2D};
+use azure::AzFloat;
+use geom::matrix2d::Matrix2D;
+use libc::uintptr_t;
+use png;
+use servo_msg::compositor_msg::{FixedPosition, LayerId, OverflowScroll, ScrollPolicy, Scrollable};
+use servo_net::image::base::Image;
+use servo_util::geometry::Au;
+use servo_util::range::Range;
+use std::io::{IoError, IoResult, InvalidInput};
+use std::num::Zero;
+use std::str;
+use style::computed_values::text_decoration;
+use sync::Arc;
+
+/// The bytes that every serialized display list starts with.
+static MAGIC: &'static str = "SVDL";
+
+/// The version of the format, which follows the magic bytes. It must be bumped whenever the
+/// encoding of any layer or display item changes.
+static VERSION: u32 = 3;
+
+// The scroll policies of layers.
+static SCROLLABLE_TAG: u8 = 0;
+static FIXED_POSITION_TAG: u8 = 1;
+static OVERFLOW_SCROLL_TAG: u8 = 2;
+
+// The tags that precede each display item.
+static SOLID_COLOR_TAG: u8 = 0;
+static TEXT_TAG: u8 = 1;
+static IMAGE_TAG: u8 = 2;
+static GRADIENT_TAG: u8 = 3;
+static BORDER_TAG: u8 = 4;
+static LINE_TAG: u8 = 5;
+static BOX_SHADOW_TAG: u8 = 6;
+static TEXT_SHADOW_TAG: u8 = 7;
+static CLIP_TAG: u8 = 8;
+static STACKING_CONTEXT_TAG: u8 = 9;
+static PSEUDO_TAG: u8 = 10;
+
+/// A render layer read back by `read_layers`.
+pub struct SerializedLayer {
+    /// The ID of the layer.
+    pub id: LayerId,
+    /// The position of the layer in pixels.
+    pub position: Rect<uint>,
+    /// The visible part of the layer in pixels, in the same coordinate system as `position`.
+    pub clip_rect: Rect<uint>,
+    /// The scrolling policy of the layer.
+    pub scroll_policy: ScrollPolicy,
+    /// The display list of the layer, in the coordinate system of the layer.
+    pub display_list: DisplayList,
+}
+
+/// Writes the given render layers, including the magic bytes and version.
+pub fn write_layers<'a,I:Iterator<&'a RenderLayer>>(writer: &mut Writer, layers: I)
+                                                    -> IoResult<()> {
+    let layers: Vec<&RenderLayer> = layers.collect();
+    try!(writer.write(MAGIC.as_bytes()));
+    try!(writer.write_le_u32(VERSION));
+    try!(writer.write_le_u32(layers.len() as u32));
+    for layer in layers.iter() {
+        let LayerId(node, kind) = layer.id;
+        try!(writer.write_le_u64(node as u64));
+        try!(writer.write_le_u64(kind as u64));
+        try!(write_pixel_rect(writer, &layer.position));
+        try!(write_pixel_rect(writer, &layer.clip_rect));
+        try!(writer.write_u8(match layer.scroll_policy {
+            Scrollable => SCROLLABLE_TAG,
+            FixedPosition => FIXED_POSITION_TAG,
+            OverflowScroll => OVERFLOW_SCROLL_TAG,
+        }));
+        try!(write_list(writer, layer.display_list.iter()))
+    }
+    Ok(())
+}
+
+/// Reads the render layers written by `write_layers`.
+pub fn read_layers(reader: &mut Reader) -> IoResult<Vec<SerializedLayer>> {
+    let magic = try!(reader.read_exact(MAGIC.len()));
+    if magic.as_slice() != MAGIC.as_bytes() {
+        return Err(invalid("not a serialized display list"))
+    }
+    if try!(reader.read_le_u32()) != VERSION {
+        return Err(invalid("unsupported display list version"))
+    }
+    let mut layers = vec!();
+    for _ in range(0, try!(reader.read_le_u32())) {
+        let node = try!(reader.read_le_u64()) as uint;
+        let kind = try!(reader.read_le_u64()) as uint;
+        let position = try!(read_pixel_rect(reader));
+        let clip_rect = try!(read_pixel_rect(reader));
+        let scroll_policy = match try!(reader.read_u8()) {
+            SCROLLABLE_TAG => Scrollable,
+            FIXED_POSITION_TAG => FixedPosition,
+            OVERFLOW_SCROLL_TAG => OverflowScroll,
+            _ => return Err(invalid("unknown scroll policy")),
+        };
+        layers.push(SerializedLayer {
+            id: LayerId(node, kind),
+            position: position,
+            clip_rect: clip_rect,
+            scroll_policy: scroll_policy,
+            display_list: try!(read_list(reader)),
+        })
+    }
+    Ok(layers)
+}
+
+/// Combines layers read by `read_layers` into a single display list that draws each of them at
+/// its position, clipped to its clip rect, in the order in which the compositor stacks them. This
+/// is what the page looks like before anything is scrolled.
+pub fn composite_layers(layers: Vec<SerializedLayer>) -> DisplayList {
+    let mut result = DisplayList::new();
+    for layer in layers.move_iter() {
+        let SerializedLayer {
+            position: position,
+            clip_rect: clip_rect,
+            display_list: display_list,
+            ..
+        } = layer;
+
+        // The clip rect is moved into the coordinate system of the layer.
+        let clip_x = clip_rect.origin.x as int - position.origin.x as int;
+        let clip_y = clip_rect.origin.y as int - position.origin.y as int;
+        let bounds = Rect(Point2D(Au::from_px(clip_x), Au::from_px(clip_y)),
+                          Size2D(Au::from_px(clip_rect.size.width as int),
+                                 Au::from_px(clip_rect.size.height as int)));
+        let base = BaseDisplayItem::new(bounds, OpaqueNode(0), ContentStackingLevel);
+        let mut clipped = DisplayList::new();
+        clipped.push(ClipDisplayItemClass(box ClipDisplayItem::new(base, display_list)));
+        let transform = Matrix2D::identity().translate(position.origin.x as AzFloat,
+                                                       position.origin.y as AzFloat);
+        result.push_all_move(clipped.flatten_into_stacking_context(bounds,
+                                                                   OpaqueNode(0),
+                                                                   0,
+                                                                   1.0,
+                                                                   transform))
+    }
+    result
+}
+
+fn invalid(desc: &'static str) -> IoError {
+    IoError {
+        kind: InvalidInput,
+        desc: desc,
+        detail: None,
+    }
+}
+
+fn write_list<'a,I:Iterator<&'a DisplayItem>>(writer: &mut Writer, mut items: I)
+                                              -> IoResult<()> {
+    let items: Vec<&DisplayItem> = items.collect();
+    try!(writer.write_le_u32(items.len() as u32));
+    for item in items.iter() {
+        try!(write_item(writer, *item))
+    }
+    Ok(())
+}
+
+fn read_list(reader: &mut Reader) -> IoResult<DisplayList> {
+    let mut display_list = DisplayList::new();
+    for _ in range(0, try!(reader.read_le_u32())) {
+        display_list.push(try!(read_item(reader)))
+    }
+    Ok(display_list)
+}
+
+fn write_item(writer: &mut Writer, item: &DisplayItem) -> IoResult<()> {
+    match *item {
+        SolidColorDisplayItemClass(ref solid_color) => {
+            try!(writer.write_u8(SOLID_COLOR_TAG));
+            try!(write_base(writer, &solid_color.base));
+            try!(write_color(writer, &solid_color.color));
+            write_radii(writer, &solid_color.radii)
+        }
+        TextDisplayItemClass(ref text) => {
+            try!(writer.write_u8(TEXT_TAG));
+            try!(write_base(writer, &text.base));
+            try!(write_text_run(writer, &**text.text_run, &text.range));
+            try!(write_color(writer, &text.text_color));
+            try!(write_optional_color(writer, &text.text_decorations.underline));
+            try!(write_optional_color(writer, &text.text_decorations.overline));
+            write_optional_color(writer, &text.text_decorations.line_through)
+        }
+        ImageDisplayItemClass(ref image_item) => {
+            try!(writer.write_u8(IMAGE_TAG));
+            try!(write_base(writer, &image_item.base));
+            try!(write_image(writer, &**image_item.image));
+            write_size(writer, &image_item.stretch_size)
+        }
+        GradientDisplayItemClass(ref gradient) => {
+            try!(writer.write_u8(GRADIENT_TAG));
+            try!(write_base(writer, &gradient.base));
+            match gradient.geometry {
+                LinearGradientGeometry(ref start, ref end) => {
+                    try!(writer.write_u8(0));
+                    try!(write_point(writer, start));
+                    try!(write_point(writer, end));
+                }
+                RadialGradientGeometry(ref center, ref start_radius, ref end_radius) => {
+                    try!(writer.write_u8(1));
+                    try!(write_point(writer, center));
+                    try!(write_size(writer, start_radius));
+                    try!(write_size(writer, end_radius));
+                }
+            }
+            try!(writer.write_le_u32(gradient.stops.len() as u32));
+            for stop in gradient.stops.iter() {
+                try!(writer.write_le_f32(stop.offset));
+                try!(write_color(writer, &stop.color));
+            }
+            write_bool(writer, gradient.repeating)
+        }
+        BorderDisplayItemClass(ref border) => {
+            try!(writer.write_u8(BORDER_TAG));
+            try!(write_base(writer, &border.base));
+            let sides = &border.border;
+            for width in [sides.top, sides.right, sides.bottom, sides.left].iter() {
+                try!(write_au(writer, *width))
+            }
+            let colors = &border.color;
+            for color in [colors.top, colors.right, colors.bottom, colors.left].iter() {
+                try!(write_color(writer, color))
+            }
+            let styles = &border.style;
+            for style in [styles.top, styles.right, styles.bottom, styles.left].iter() {
+                try!(writer.write_u8(*style as u8))
+            }
+            write_radii(writer, &border.radius)
+        }
+        LineDisplayItemClass(ref line) => {
+            try!(writer.write_u8(LINE_TAG));
+            try!(write_base(writer, &line.base));
+            try!(write_color(writer, &line.color));
+            writer.write_u8(line.style as u8)
+        }
+        BoxShadowDisplayItemClass(ref shadow) => {
+            try!(writer.write_u8(BOX_SHADOW_TAG));
+            try!(write_base(writer, &shadow.base));
+            try!(write_rect(writer, &shadow.box_bounds));
+            try!(write_radii(writer, &shadow.radii));
+            try!(write_point(writer, &shadow.offset));
+            try!(write_color(writer, &shadow.color));
+            try!(write_au(writer, shadow.blur_radius));
+            try!(write_au(writer, shadow.spread_radius));
+            write_bool(writer, shadow.inset)
+        }
+        TextShadowDisplayItemClass(ref shadow) => {
+            try!(writer.write_u8(TEXT_SHADOW_TAG));
+            try!(write_base(writer, &shadow.base));
+            try!(write_text_run(writer, &**shadow.text_run, &shadow.range));
+            try!(write_rect(writer, &shadow.text_bounds));
+            try!(write_point(writer, &shadow.offset));
+            try!(write_color(writer, &shadow.color));
+            write_au(writer, shadow.blur_radius)
+        }
+        ClipDisplayItemClass(ref clip) => {
+            try!(writer.write_u8(CLIP_TAG));
+            try!(write_base(writer, &clip.base));
+            try!(write_radii(writer, &clip.radii));
+            write_list(writer, clip.children.iter())
+        }
+        StackingContextDisplayItemClass(ref stacking_context) => {
+            try!(writer.write_u8(STACKING_CONTEXT_TAG));
+            try!(write_base(writer, &stacking_context.base));
+            try!(writer.write_le_f32(stacking_context.opacity));
+            let transform = &stacking_context.transform;
+            for component in [transform.m11, transform.m12,
+                              transform.m21, transform.m22,
+                              transform.m31, transform.m32].iter() {
+                try!(writer.write_le_f32(*component))
+            }
+            write_list(writer, stacking_context.children.iter())
+        }
+        PseudoDisplayItemClass(ref base) => {
+            try!(writer.write_u8(PSEUDO_TAG));
+            write_base(writer, &**base)
+        }
+    }
+}
+
+fn read_item(reader: &mut Reader) -> IoResult<DisplayItem> {
+    let tag = try!(reader.read_u8());
+    let base = try!(read_base(reader));
+    let item = match tag {
+        SOLID_COLOR_TAG => {
+            SolidColorDisplayItemClass(box SolidColorDisplayItem {
+                base: base,
+                color: try!(read_color(reader)),
+                radii: try!(read_radii(reader)),
+            })
+        }
+        TEXT_TAG => {
+            let (text_run, range) = try!(read_text_run(reader));
+            TextDisplayItemClass(box TextDisplayItem {
+                base: base,
+                text_run: text_run,
+                range: range,
+                text_color: try!(read_color(reader)),
+                text_decorations: TextDecorations {
+                    underline: try!(read_optional_color(reader)),
+                    overline: try!(read_optional_color(reader)),
+                    line_through: try!(read_optional_color(reader)),
+                },
+            })
+        }
+        IMAGE_TAG => {
+            ImageDisplayItemClass(box ImageDisplayItem {
+                base: base,
+                image: Arc::new(box try!(read_image(reader))),
+                stretch_size: try!(read_size(reader)),
+            })
+        }
+        GRADIENT_TAG => {
+            let geometry = match try!(reader.read_u8()) {
+                0 => LinearGradientGeometry(try!(read_point(reader)), try!(read_point(reader))),
+                1 => {
+                    RadialGradientGeometry(try!(read_point(reader)),
+                                           try!(read_size(reader)),
+                                           try!(read_size(reader)))
+                }
+                _ => return Err(invalid("unknown gradient geometry")),
+            };
+            let mut stops = vec!();
+            for _ in range(0, try!(reader.read_le_u32())) {
+                stops.push(GradientStop {
+                    offset: try!(reader.read_le_f32()),
+                    color: try!(read_color(reader)),
+                })
+            }
+            GradientDisplayItemClass(box GradientDisplayItem {
+                base: base,
+                geometry: geometry,
+                stops: stops,
+                repeating: try!(read_bool(reader)),
+            })
+        }
+        BORDER_TAG => {
+            let border = SideOffsets2D::new(try!(read_au(reader)),
+                                            try!(read_au(reader)),
+                                            try!(read_au(reader)),
+                                            try!(read_au(reader)));
+            let color = SideOffsets2D::new(try!(read_color(reader)),
+                                           try!(read_color(reader)),
+                                           try!(read_color(reader)),
+                                           try!(read_color(reader)));
+            let style = SideOffsets2D::new(try!(read_keyword(reader)),
+                                           try!(read_keyword(reader)),
+                                           try!(read_keyword(reader)),
+                                           try!(read_keyword(reader)));
+            BorderDisplayItemClass(box BorderDisplayItem {
+                base: base,
+                border: border,
+                color: color,
+                style: style,
+                radius: try!(read_radii(reader)),
+            })
+        }
+        LINE_TAG => {
+            LineDisplayItemClass(box LineDisplayItem {
+                base: base,
+                color: try!(read_color(reader)),
+                style: try!(read_keyword(reader)),
+            })
+        }
+        BOX_SHADOW_TAG => {
+            BoxShadowDisplayItemClass(box BoxShadowDisplayItem {
+                base: base,
+                box_bounds: try!(read_rect(reader)),
+                radii: try!(read_radii(reader)),
+                offset: try!(read_point(reader)),
+                color: try!(read_color(reader)),
+                blur_radius: try!(read_au(reader)),
+                spread_radius: try!(read_au(reader)),
+                inset: try!(read_bool(reader)),
+            })
+        }
+        TEXT_SHADOW_TAG => {
+            let (text_run, range) = try!(read_text_run(reader));
+            TextShadowDisplayItemClass(box TextShadowDisplayItem {
+                base: base,
+                text_run: text_run,
+                range: range,
+                text_bounds: try!(read_rect(reader)),
+                offset: try!(read_point(reader)),
+                color: try!(read_color(reader)),
+                blur_radius: try!(read_au(reader)),
+            })
+        }
+        CLIP_TAG => {
+            let radii = try!(read_radii(reader));
+            ClipDisplayItemClass(box ClipDisplayItem::new_rounded(base,
+                                                                  radii,
+                                                                  try!(read_list(reader))))
+        }
+        STACKING_CONTEXT_TAG => {
+            let opacity = try!(reader.read_le_f32());
+            let transform = Matrix2D::new(try!(reader.read_le_f32()), try!(reader.read_le_f32()),
+                                          try!(reader.read_le_f32()), try!(reader.read_le_f32()),
+                                          try!(reader.read_le_f32()), try!(reader.read_le_f32()));
+            StackingContextDisplayItemClass(box StackingContextDisplayItem {
+                base: base,
+                children: try!(read_list(reader)),
+                opacity: opacity,
+                transform: transform,
+            })
+        }
+        PSEUDO_TAG => PseudoDisplayItemClass(box base),
+        _ => return Err(invalid("unknown display item")),
+    };
+    Ok(item)
+}
+
+fn write_base(writer: &mut Writer, base: &BaseDisplayItem) -> IoResult<()> {
+    try!(write_rect(writer, &base.bounds));
+    try!(writer.write_le_u64(base.node.id() as u64));
+    match base.level {
+        BackgroundAndBordersStackingLevel => writer.write_u8(0),
+        BlockBackgroundsAndBordersStackingLevel => writer.write_u8(1),
+        FloatStackingLevel => writer.write_u8(2),
+        ContentStackingLevel => writer.write_u8(3),
+        PositionedDescendantStackingLevel(z_index) => {
+            try!(writer.write_u8(4));
+            writer.write_le_i32(z_index)
+        }
+    }
+}
+
+fn read_base(reader: &mut Reader) -> IoResult<BaseDisplayItem> {
+    let bounds = try!(read_rect(reader));
+    let node = OpaqueNode(try!(reader.read_le_u64()) as uintptr_t);
+    let level: StackingLevel = match try!(reader.read_u8()) {
+        0 => BackgroundAndBordersStackingLevel,
+        1 => BlockBackgroundsAndBordersStackingLevel,
+        2 => FloatStackingLevel,
+        3 => ContentStackingLevel,
+        4 => PositionedDescendantStackingLevel(try!(reader.read_le_i32())),
+        _ => return Err(invalid("unknown stacking level")),
+    };
+    Ok(BaseDisplayItem::new(bounds, node, level))
+}
+
+/// Writes the part of a text run that the given range covers: the font, the text, and for each
+/// character the glyphs that it is drawn with.
+fn write_text_run(writer: &mut Writer, text_run: &TextRun, range: &Range<CharIndex>)
+                  -> IoResult<()> {
+    try!(write_font_style(writer, &text_run.font_descriptor.style));
+    let SelectorPlatformIdentifier(ref identifier) = text_run.font_descriptor.selector;
+    try!(write_str(writer, *identifier));
+    try!(write_font_metrics(writer, &text_run.font_metrics));
+    try!(write_font_style(writer, &text_run.font_style));
+    try!(write_bool(writer, text_run.decoration.underline));
+    try!(write_bool(writer, text_run.decoration.overline));
+    try!(write_bool(writer, text_run.decoration.line_through));
+
+    let text: ~str = text_run.text
+                             .chars()
+                             .skip(range.begin().to_uint())
+                             .take(range.length().to_uint())
+                             .collect();
+    try!(write_str(writer, text));
+
+    try!(writer.write_le_u32(range.length().to_uint() as u32));
+    for (glyphs, _, slice_range) in text_run.iter_slices_for_range(range) {
+        for i in range(slice_range.begin().to_uint(), slice_range.end().to_uint()) {
+            let char_glyphs: Vec<_> = glyphs.iter_glyphs_for_char_index(CharIndex(i as int))
+                                            .map(|(_, glyph)| glyph)
+                                            .collect();
+            try!(writer.write_le_u16(char_glyphs.len() as u16));
+            for &glyph in char_glyphs.iter() {
+                try!(writer.write_le_u32(glyph.id()));
+                try!(write_au(writer, glyph.advance()));
+                try!(write_point(writer, &glyph.offset().unwrap_or(Zero::zero())));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reads a text run written by `write_text_run`, along with the range of all of its characters.
+fn read_text_run(reader: &mut Reader) -> IoResult<(Arc<Box<TextRun>>, Range<CharIndex>)> {
+    let font_descriptor = FontDescriptor::new(try!(read_font_style(reader)),
+                                              SelectorPlatformIdentifier(try!(read_str(reader))));
+    let font_metrics = try!(read_font_metrics(reader));
+    let font_style = try!(read_font_style(reader));
+    let mut decoration = text_decoration::none;
+    decoration.underline = try!(read_bool(reader));
+    decoration.overline = try!(read_bool(reader));
+    decoration.line_through = try!(read_bool(reader));
+    let text = try!(read_str(reader));
+
+    let char_count = try!(reader.read_le_u32()) as int;
+    let mut glyph_store = GlyphStore::new(char_count, false);
+    for i in range(0, char_count) {
+        let mut glyphs = vec!();
+        for _ in range(0, try!(reader.read_le_u16())) {
+            let id = try!(reader.read_le_u32());
+            let advance = try!(read_au(reader));
+            let offset = try!(read_point(reader));
+            glyphs.push(GlyphData::new(id, advance, Some(offset), false, true, true))
+        }
+        match glyphs.len() {
+            0 => glyph_store.add_nonglyph_for_char_index(CharIndex(i), false, false),
+            1 => glyph_store.add_glyph_for_char_index(CharIndex(i), glyphs.get(0)),
+            _ => glyph_store.add_glyphs_for_char_index(CharIndex(i), glyphs.as_slice()),
+        }
+    }
+    glyph_store.finalize_changes();
+
+    let text_run = TextRun {
+        text: Arc::new(text),
+        font_descriptor: font_descriptor,
+        font_metrics: font_metrics,
+        font_style: font_style,
+        decoration: decoration,
+        glyphs: Arc::new(vec!(Arc::new(glyph_store))),
+    };
+    Ok((Arc::new(box text_run), Range::new(CharIndex(0), CharIndex(char_count))))
+}
+
+fn write_font_style(writer: &mut Writer, style: &FontStyle) -> IoResult<()> {
+    try!(writer.write_le_f64(style.pt_size));
+    try!(writer.write_u8(style.weight as u8));
+    try!(writer.write_u8(style.style as u8));
+    try!(writer.write_le_u32(style.families.len() as u32));
+    for family in style.families.iter() {
+        try!(write_str(writer, *family))
+    }
+    Ok(())
+}
+
+fn read_font_style(reader: &mut Reader) -> IoResult<FontStyle> {
+    let pt_size = try!(reader.read_le_f64());
+    let weight = try!(read_keyword(reader));
+    let style = try!(read_keyword(reader));
+    let mut families = vec!();
+    for _ in range(0, try!(reader.read_le_u32())) {
+        families.push(try!(read_str(reader)))
+    }
+    Ok(FontStyle {
+        pt_size: pt_size,
+        weight: weight,
+        style: style,
+        families: families,
+    })
+}
+
+fn write_font_metrics(writer: &mut Writer, metrics: &FontMetrics) -> IoResult<()> {
+    for value in [metrics.underline_size, metrics.underline_offset,
+                  metrics.strikeout_size, metrics.strikeout_offset,
+                  metrics.leading, metrics.x_height, metrics.em_size,
+                  metrics.ascent, metrics.descent, metrics.max_advance].iter() {
+        try!(write_au(writer, *value))
+    }
+    Ok(())
+}
+
+fn read_font_metrics(reader: &mut Reader) -> IoResult<FontMetrics> {
+    Ok(FontMetrics {
+        underline_size: try!(read_au(reader)),
+        underline_offset: try!(read_au(reader)),
+        strikeout_size: try!(read_au(reader)),
+        strikeout_offset: try!(read_au(reader)),
+        leading: try!(read_au(reader)),
+        x_height: try!(read_au(reader)),
+        em_size: try!(read_au(reader)),
+        ascent: try!(read_au(reader)),
+        descent: try!(read_au(reader)),
+        max_advance: try!(read_au(reader)),
+    })
+}
+
+/// Writes an image with its pixels, which are stored as they are.
+fn write_image(writer: &mut Writer, image: &Image) -> IoResult<()> {
+    let color_type = match image.color_type {
+        png::RGBA8 => 0,
+        png::K8 => 1,
+        png::KA8 => 2,
+        _ => return Err(invalid("unsupported image color type")),
+    };
+    try!(writer.write_le_u32(image.width));
+    try!(writer.write_le_u32(image.height));
+    try!(writer.write_u8(color_type));
+    try!(writer.write_le_u32(image.pixels.len() as u32));
+    writer.write(image.pixels.as_slice())
+}
+
+fn read_image(reader: &mut Reader) -> IoResult<Image> {
+    let width = try!(reader.read_le_u32());
+    let height = try!(reader.read_le_u32());
+    let color_type = match try!(reader.read_u8()) {
+        0 => png::RGBA8,
+        1 => png::K8,
+        2 => png::KA8,
+        _ => return Err(invalid("unsupported image color type")),
+    };
+    let length = try!(reader.read_le_u32()) as uint;
+    Ok(Image(width, height, color_type, try!(reader.read_exact(length))))
+}
+
+fn write_radii(writer: &mut Writer, radii: &BorderRadii<Au>) -> IoResult<()> {
+    try!(write_size(writer, &radii.top_left));
+    try!(write_size(writer, &radii.top_right));
+    try!(write_size(writer, &radii.bottom_right));
+    write_size(writer, &radii.bottom_left)
+}
+
+fn read_radii(reader: &mut Reader) -> IoResult<BorderRadii<Au>> {
+    Ok(BorderRadii {
+        top_left: try!(read_size(reader)),
+        top_right: try!(read_size(reader)),
+        bottom_right: try!(read_size(reader)),
+        bottom_left: try!(read_size(reader)),
+    })
+}
+
+fn write_color(writer: &mut Writer, color: &Color) -> IoResult<()> {
+    try!(writer.write_le_f32(color.r));
+    try!(writer.write_le_f32(color.g));
+    try!(writer.write_le_f32(color.b));
+    writer.write_le_f32(color.a)
+}
+
+fn read_color(reader: &mut Reader) -> IoResult<Color> {
+    Ok(Color {
+        r: try!(reader.read_le_f32()),
+        g: try!(reader.read_le_f32()),
+        b: try!(reader.read_le_f32()),
+        a: try!(reader.read_le_f32()),
+    })
+}
+
+fn write_optional_color(writer: &mut Writer, color: &Option<Color>) -> IoResult<()> {
+    match *color {
+        None => write_bool(writer, false),
+        Some(ref color) => {
+            try!(write_bool(writer, true));
+            write_color(writer, color)
+        }
+    }
+}
+
+fn read_optional_color(reader: &mut Reader) -> IoResult<Option<Color>> {
+    if try!(read_bool(reader)) {
+        Ok(Some(try!(read_color(reader))))
+    } else {
+        Ok(None)
+    }
+}
+
+fn write_rect(writer: &mut Writer, rect: &Rect<Au>) -> IoResult<()> {
+    try!(write_point(writer, &rect.origin));
+    write_size(writer, &rect.size)
+}
+
+fn read_rect(reader: &mut Reader) -> IoResult<Rect<Au>> {
+    Ok(Rect(try!(read_point(reader)), try!(read_size(reader))))
+}
+
+fn write_pixel_rect(writer: &mut Writer, rect: &Rect<uint>) -> IoResult<()> {
+    try!(writer.write_le_u32(rect.origin.x as u32));
+    try!(writer.write_le_u32(rect.origin.y as u32));
+    try!(writer.write_le_u32(rect.size.width as u32));
+    writer.write_le_u32(rect.size.height as u32)
+}
+
+fn read_pixel_rect(reader: &mut Reader) -> IoResult<Rect<uint>> {
+    Ok(Rect(Point2D(try!(reader.read_le_u32()) as uint, try!(reader.read_le_u32()) as uint),
+            Size2D(try!(reader.read_le_u32()) as uint, try!(reader.read_le_u32()) as uint)))
+}
+
+fn write_point(writer: &mut Writer, point: &Point2D<Au>) -> IoResult<()> {
+    try!(write_au(writer, point.x));
+    write_au(writer, point.y)
+}
+
+fn read_point(reader: &mut Reader) -> IoResult<Point2D<Au>> {
+    Ok(Point2D(try!(read_au(reader)), try!(read_au(reader))))
+}
+
+fn write_size(writer: &mut Writer, size: &Size2D<Au>) -> IoResult<()> {
+    try!(write_au(writer, size.width));
+    write_au(writer, size.height)
+}
+
+fn read_size(reader: &mut Reader) -> IoResult<Size2D<Au>> {
+    Ok(Size2D(try!(read_au(reader)), try!(read_au(reader))))
+}
+
+fn write_au(writer: &mut Writer, value: Au) -> IoResult<()> {
+    let Au(value) = value;
+    writer.write_le_i32(value)
+}
+
+fn read_au(reader: &mut Reader) -> IoResult<Au> {
+    Ok(Au(try!(reader.read_le_i32())))
+}
+
+fn write_bool(writer: &mut Writer, value: bool) -> IoResult<()> {
+    writer.write_u8(value as u8)
+}
+
+fn read_bool(reader: &mut Reader) -> IoResult<bool> {
+    Ok(try!(reader.read_u8()) != 0)
+}
+
+/// Reads a CSS keyword value written as its index.
+fn read_keyword<T: FromPrimitive>(reader: &mut Reader) -> IoResult<T> {
+    match FromPrimitive::from_u8(try!(reader.read_u8())) {
+        Some(value) => Ok(value),
+        None => Err(invalid("unknown keyword value")),
+    }
+}
+
+fn write_str(writer: &mut Writer, string: &str) -> IoResult<()> {
+    try!(writer.write_le_u32(string.len() as u32));
+    writer.write_str(string)
+}
+
+fn read_str(reader: &mut Reader) -> IoResult<~str> {
+    let length = try!(reader.read_le_u32()) as uint;
+    let bytes = try!(reader.read_exact(length));
+    match str::from_utf8(bytes.as_slice()) {
+        Some(string) => Ok(string.to_owned()),
+        None => Err(invalid("invalid UTF-8 string")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SerializedLayer, composite_layers, read_layers, read_list, read_text_run};
+    use super::{write_layers, write_list, write_text_run};
+    use color;
+    use color::Color;
+    use display_list::{BaseDisplayItem, BorderDisplayItem, BorderDisplayItemClass, BorderRadii};
+    use display_list::{BoxShadowDisplayItem, BoxShadowDisplayItemClass, ClipDisplayItem};
+    use display_list::{ClipDisplayItemClass, ContentStackingLevel, DisplayItem, DisplayList};
+    use display_list::{GradientDisplayItem, GradientDisplayItemClass, GradientStop};
+    use display_list::{ImageDisplayItem, ImageDisplayItemClass, IndexedDisplayList};
+    use display_list::{LinearGradientGeometry, OpaqueNode, PositionedDescendantStackingLevel};
+    use display_list::{RadialGradientGeometry, SolidColorDisplayItem, SolidColorDisplayItemClass};
+    use display_list::{StackingContextDisplayItem, StackingContextDisplayItemClass};
+    use display_list::{TextShadowDisplayItem, TextShadowDisplayItemClass};
+    use font::{FontDescriptor, FontMetrics, FontStyle, SelectorPlatformIdentifier};
+    use render_task::RenderLayer;
+    use text::glyph::{CharIndex, GlyphData, GlyphStore};
+    use text::TextRun;
+
+    use geom::matrix2d::Matrix2D;
+    use geom::{Point2D, Rect, SideOffsets2D, Size2D};
+    use png;
+    use servo_net::image::base::Image;
+    use servo_msg::compositor_msg::{FixedPosition, LayerId, OverflowScroll, ScrollPolicy};
+    use servo_msg::compositor_msg::{Scrollable};
+    use servo_util::geometry::Au;
+    use servo_util::range::Range;
+    use std::io::{BufReader, MemWriter};
+    use std::num::Zero;
+    use style::computed_values::{border_style, font_style, font_weight, text_decoration};
+    use sync::Arc;
+
+    fn rect(x: int, y: int, width: int, height: int) -> Rect<Au> {
+        Rect(Point2D(Au::from_px(x), Au::from_px(y)),
+             Size2D(Au::from_px(width), Au::from_px(height)))
+    }
+
+    fn pixel_rect(x: uint, y: uint, width: uint, height: uint) -> Rect<uint> {
+        Rect(Point2D(x, y), Size2D(width, height))
+    }
+
+    fn render_layer(id: LayerId,
+                    position: Rect<uint>,
+                    clip_rect: Rect<uint>,
+                    scroll_policy: ScrollPolicy,
+                    display_list: DisplayList)
+                    -> RenderLayer {
+        RenderLayer {
+            id: id,
+            display_list: Arc::new(IndexedDisplayList::new(display_list)),
+            position: position,
+            clip_rect: clip_rect,
+            background_color: color::rgb(255, 255, 255),
+            scroll_policy: scroll_policy,
+            dirty_rects: None,
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut radii = BorderRadii::zero();
+        radii.top_left = Size2D(Au::from_px(4), Au::from_px(2));
+        let mut children = DisplayList::new();
+        children.push(BorderDisplayItemClass(box BorderDisplayItem {
+            base: BaseDisplayItem::new(rect(10, 10, 50, 20),
+                                       OpaqueNode(2),
+                                       PositionedDescendantStackingLevel(-3)),
+            border: SideOffsets2D::new_all_same(Au::from_px(1)),
+            color: SideOffsets2D::new_all_same(color::rgb(0, 0, 255)),
+            style: SideOffsets2D::new(border_style::solid,
+                                      border_style::dashed,
+                                      border_style::dotted,
+                                      border_style::double),
+            radius: BorderRadii::zero(),
+        }));
+        let mut display_list = DisplayList::new();
+        display_list.push(SolidColorDisplayItemClass(box SolidColorDisplayItem {
+            base: BaseDisplayItem::new(rect(0, 0, 100, 100), OpaqueNode(1), ContentStackingLevel),
+            color: color::rgb(255, 0, 0),
+            radii: radii,
+        }));
+        display_list.push(ClipDisplayItemClass(box ClipDisplayItem::new(
+            BaseDisplayItem::new(rect(5, 5, 60, 30), OpaqueNode(1), ContentStackingLevel),
+            children)));
+
+        let mut fixed_display_list = DisplayList::new();
+        fixed_display_list.push(SolidColorDisplayItemClass(box SolidColorDisplayItem {
+            base: BaseDisplayItem::new(rect(0, 0, 20, 20), OpaqueNode(3), ContentStackingLevel),
+            color: color::rgb(0, 255, 0),
+            radii: BorderRadii::zero(),
+        }));
+        let layers = vec!(
+            render_layer(LayerId(1, 0),
+                         pixel_rect(0, 0, 800, 600),
+                         pixel_rect(0, 0, 800, 600),
+                         Scrollable,
+                         display_list),
+            render_layer(LayerId::for_fixed_box(3),
+                         pixel_rect(10, 20, 20, 20),
+                         pixel_rect(10, 20, 20, 20),
+                         FixedPosition,
+                         fixed_display_list),
+            render_layer(LayerId::for_scrollable_box(4),
+                         pixel_rect(50, 60, 200, 1000),
+                         pixel_rect(50, 60, 200, 100),
+                         OverflowScroll,
+                         DisplayList::new()));
+
+        let mut writer = MemWriter::new();
+        write_layers(&mut writer, layers.iter()).unwrap();
+        let bytes = writer.unwrap();
+        let mut layers = read_layers(&mut BufReader::new(bytes.as_slice())).unwrap();
+
+        assert!(layers.len() == 3);
+        let scroll_layer = layers.pop().unwrap();
+        assert!(scroll_layer.id == LayerId::for_scrollable_box(4));
+        assert!(scroll_layer.position == pixel_rect(50, 60, 200, 1000));
+        assert!(scroll_layer.clip_rect == pixel_rect(50, 60, 200, 100));
+        assert!(scroll_layer.scroll_policy == OverflowScroll);
+        assert!(scroll_layer.display_list.iter().next().is_none());
+
+        let fixed_layer = layers.pop().unwrap();
+        assert!(fixed_layer.id == LayerId::for_fixed_box(3));
+        assert!(fixed_layer.position == pixel_rect(10, 20, 20, 20));
+        assert!(fixed_layer.clip_rect == pixel_rect(10, 20, 20, 20));
+        assert!(fixed_layer.scroll_policy == FixedPosition);
+        let fixed_items: Vec<_> = fixed_layer.display_list.iter().collect();
+        assert!(fixed_items.len() == 1);
+
+        let root_layer = layers.pop().unwrap();
+        assert!(root_layer.id == LayerId(1, 0));
+        assert!(root_layer.position == pixel_rect(0, 0, 800, 600));
+        assert!(root_layer.scroll_policy == Scrollable);
+        let items: Vec<_> = root_layer.display_list.iter().collect();
+        assert!(items.len() == 2);
+        match **items.get(0) {
+            SolidColorDisplayItemClass(ref solid_color) => {
+                assert!(solid_color.base.bounds == rect(0, 0, 100, 100));
+                assert!(solid_color.base.node == OpaqueNode(1));
+                assert!(solid_color.color.r == 1.0 && solid_color.color.b == 0.0);
+                assert!(solid_color.radii.top_left == Size2D(Au::from_px(4), Au::from_px(2)));
+            }
+            _ => fail!("expected a solid color item"),
+        }
+        match **items.get(1) {
+            ClipDisplayItemClass(ref clip) => {
+                assert!(clip.base.bounds == rect(5, 5, 60, 30));
+                let children: Vec<_> = clip.children.iter().collect();
+                assert!(children.len() == 1);
+                match **children.get(0) {
+                    BorderDisplayItemClass(ref border) => {
+                        assert!(border.base.level == PositionedDescendantStackingLevel(-3));
+                        assert!(border.border.left == Au::from_px(1));
+                        assert!(border.style.right == border_style::dashed);
+                        assert!(border.style.left == border_style::double);
+                    }
+                    _ => fail!("expected a border item"),
+                }
+            }
+            _ => fail!("expected a clip item"),
+        }
+    }
+
+    #[test]
+    fn test_composite_layers() {
+        let layer = |id: LayerId, position: Rect<uint>, clip_rect: Rect<uint>, color: Color| {
+            let mut display_list = DisplayList::new();
+            display_list.push(SolidColorDisplayItemClass(box SolidColorDisplayItem {
+                base: BaseDisplayItem::new(rect(0, 0, 1000, 1000), OpaqueNode(1),
+                                           ContentStackingLevel),
+                color: color,
+                radii: BorderRadii::zero(),
+            }));
+            SerializedLayer {
+                id: id,
+                position: position,
+                clip_rect: clip_rect,
+                scroll_policy: Scrollable,
+                display_list: display_list,
+            }
+        };
+        let layers = vec!(
+            layer(LayerId(1, 0),
+                  pixel_rect(0, 0, 800, 600),
+                  pixel_rect(0, 0, 800, 600),
+                  color::rgb(0, 0, 0)),
+            layer(LayerId::for_fixed_box(3),
+                  pixel_rect(10, 20, 20, 20),
+                  pixel_rect(10, 20, 20, 20),
+                  color::rgb(0, 255, 0)),
+            layer(LayerId::for_scrollable_box(4),
+                  pixel_rect(50, 60, 200, 1000),
+                  pixel_rect(50, 60, 200, 100),
+                  color::rgb(0, 0, 255)));
+
+        // Each layer is moved to its position and clipped to its clip rect, in stacking order, so
+        // a scrollable box only shows its padding box and not all of what it can scroll to.
+        let display_list = composite_layers(layers);
+        let bounds: Vec<Rect<Au>> = display_list.iter().map(|item| item.bounds()).collect();
+        assert!(bounds == vec!(rect(0, 0, 800, 600),
+                               rect(10, 20, 20, 20),
+                               rect(50, 60, 200, 100)));
+        match *display_list.iter().last().unwrap() {
+            StackingContextDisplayItemClass(ref stacking_context) => {
+                let children: Vec<_> = stacking_context.children.iter().collect();
+                assert!(children.len() == 1);
+                assert!(children.get(0).bounds() == rect(0, 0, 200, 100));
+            }
+            _ => fail!("expected a stacking context"),
+        }
+    }
+
+    #[test]
+    fn test_rejects_other_data() {
+        let mut reader = BufReader::new("not a display list".as_bytes());
+        assert!(read_layers(&mut reader).is_err());
+    }
+
+    /// Returns a text run of the given text with one glyph per character. The glyph of the
+    /// character at index `i` has the ID `i + 1` and an advance of `i + 1` pixels.
+    fn text_run(text: &str) -> Arc<Box<TextRun>> {
+        let char_count = text.char_len() as int;
+        let mut glyph_store = GlyphStore::new(char_count, false);
+        for i in range(0, char_count) {
+            let glyph = GlyphData::new(i as u32 + 1, Au::from_px(i + 1), None, false, true, true);
+            glyph_store.add_glyph_for_char_index(CharIndex(i), &glyph);
+        }
+        glyph_store.finalize_changes();
+
+        let font_style = FontStyle {
+            pt_size: 16.0,
+            weight: font_weight::Weight700,
+            style: font_style::italic,
+            families: vec!("serif".to_owned(), "sans-serif".to_owned()),
+        };
+        let mut decoration = text_decoration::none;
+        decoration.underline = true;
+        Arc::new(box TextRun {
+            text: Arc::new(text.to_owned()),
+            font_descriptor: FontDescriptor::new(font_style.clone(),
+                                                 SelectorPlatformIdentifier("serif".to_owned())),
+            font_metrics: FontMetrics {
+                underline_size: Au::from_px(1),
+                underline_offset: Au::from_px(2),
+                strikeout_size: Au::from_px(1),
+                strikeout_offset: Au::from_px(5),
+                leading: Au(0),
+                x_height: Au::from_px(8),
+                em_size: Au::from_px(16),
+                ascent: Au::from_px(12),
+                descent: Au::from_px(4),
+                max_advance: Au::from_px(10),
+            },
+            font_style: font_style,
+            decoration: decoration,
+            glyphs: Arc::new(vec!(Arc::new(glyph_store))),
+        })
+    }
+
+    /// Writes the given display list and reads it back.
+    fn round_trip(display_list: DisplayList) -> Vec<DisplayItem> {
+        let mut writer = MemWriter::new();
+        write_list(&mut writer, display_list.iter()).unwrap();
+        let bytes = writer.unwrap();
+        let display_list = read_list(&mut BufReader::new(bytes.as_slice())).unwrap();
+        display_list.list.move_iter().collect()
+    }
+
+    #[test]
+    fn test_text_run_round_trip() {
+        let run = text_run("Hello");
+        let mut writer = MemWriter::new();
+        write_text_run(&mut writer, &**run, &Range::new(CharIndex(1), CharIndex(3))).unwrap();
+        let bytes = writer.unwrap();
+        let (read_run, range) = read_text_run(&mut BufReader::new(bytes.as_slice())).unwrap();
+
+        // Only the characters in the range are written, so the read run starts with them.
+        assert!(range.begin() == CharIndex(0) && range.end() == CharIndex(3));
+        assert_eq!(read_run.text.as_slice(), "ell");
+        let glyphs = read_run.glyphs.get(0);
+        for i in range(0, 3) {
+            let char_glyphs: Vec<_> = glyphs.iter_glyphs_for_char_index(CharIndex(i))
+                                            .map(|(_, glyph)| glyph)
+                                            .collect();
+            assert!(char_glyphs.len() == 1);
+            let glyph = *char_glyphs.get(0);
+            assert_eq!(glyph.id(), i as u32 + 2);
+            assert_eq!(glyph.advance(), Au::from_px(i + 2));
+            assert!(glyph.offset().unwrap_or(Zero::zero()) == Point2D(Au(0), Au(0)));
+        }
+
+        assert!(read_run.font_descriptor.style.pt_size == 16.0);
+        let SelectorPlatformIdentifier(ref identifier) = read_run.font_descriptor.selector;
+        assert_eq!(identifier.as_slice(), "serif");
+        assert!(read_run.font_style.weight == font_weight::Weight700);
+        assert!(read_run.font_style.style == font_style::italic);
+        assert!(read_run.font_style.families ==
+                vec!("serif".to_owned(), "sans-serif".to_owned()));
+        assert_eq!(read_run.font_metrics.ascent, Au::from_px(12));
+        assert_eq!(read_run.font_metrics.strikeout_offset, Au::from_px(5));
+        assert_eq!(read_run.font_metrics.max_advance, Au::from_px(10));
+        assert!(read_run.decoration.underline);
+        assert!(!read_run.decoration.overline && !read_run.decoration.line_through);
+    }
+
+    #[test]
+    fn test_gradient_round_trip() {
+        let stops = vec!(GradientStop { offset: 0.0, color: color::rgb(255, 0, 0) },
+                         GradientStop { offset: 0.25, color: color::rgb(0, 0, 255) });
+        let mut display_list = DisplayList::new();
+        display_list.push(GradientDisplayItemClass(box GradientDisplayItem {
+            base: BaseDisplayItem::new(rect(0, 0, 100, 50), OpaqueNode(1), ContentStackingLevel),
+            geometry: LinearGradientGeometry(Point2D(Au(0), Au::from_px(25)),
+                                             Point2D(Au::from_px(100), Au::from_px(25))),
+            stops: stops.clone(),
+            repeating: false,
+        }));
+        display_list.push(GradientDisplayItemClass(box GradientDisplayItem {
+            base: BaseDisplayItem::new(rect(0, 50, 100, 50), OpaqueNode(1), ContentStackingLevel),
+            geometry: RadialGradientGeometry(Point2D(Au::from_px(50), Au::from_px(75)),
+                                             Size2D(Au(0), Au(0)),
+                                             Size2D(Au::from_px(50), Au::from_px(20))),
+            stops: stops,
+            repeating: true,
+        }));
+
+        let items = round_trip(display_list);
+        assert!(items.len() == 2);
+        match *items.get(0) {
+            GradientDisplayItemClass(ref gradient) => {
+                match gradient.geometry {
+                    LinearGradientGeometry(start, end) => {
+                        assert!(start == Point2D(Au(0), Au::from_px(25)));
+                        assert!(end == Point2D(Au::from_px(100), Au::from_px(25)));
+                    }
+                    _ => fail!("expected a linear gradient"),
+                }
+                assert!(gradient.stops.len() == 2);
+                assert!(gradient.stops.get(1).offset == 0.25);
+                assert!(gradient.stops.get(1).color.b == 1.0);
+                assert!(!gradient.repeating);
+            }
+            _ => fail!("expected a gradient item"),
+        }
+        match *items.get(1) {
+            GradientDisplayItemClass(ref gradient) => {
+                match gradient.geometry {
+                    RadialGradientGeometry(center, start_radius, end_radius) => {
+                        assert!(center == Point2D(Au::from_px(50), Au::from_px(75)));
+                        assert!(start_radius == Size2D(Au(0), Au(0)));
+                        assert!(end_radius == Size2D(Au::from_px(50), Au::from_px(20)));
+                    }
+                    _ => fail!("expected a radial gradient"),
+                }
+                assert!(gradient.stops.get(0).color.r == 1.0);
+                assert!(gradient.repeating);
+            }
+            _ => fail!("expected a gradient item"),
+        }
+    }
+
+    #[test]
+    fn test_image_round_trip() {
+        let pixels = vec!(255u8, 0, 0, 255, 0, 255, 0, 128);
+        let mut display_list = DisplayList::new();
+        display_list.push(ImageDisplayItemClass(box ImageDisplayItem {
+            base: BaseDisplayItem::new(rect(0, 0, 20, 10), OpaqueNode(1), ContentStackingLevel),
+            image: Arc::new(box Image(2, 1, png::RGBA8, pixels.clone())),
+            stretch_size: Size2D(Au::from_px(10), Au::from_px(10)),
+        }));
+
+        let items = round_trip(display_list);
+        assert!(items.len() == 1);
+        match *items.get(0) {
+            ImageDisplayItemClass(ref image_item) => {
+                assert_eq!(image_item.image.width, 2);
+                assert_eq!(image_item.image.height, 1);
+                assert!(image_item.image.color_type == png::RGBA8);
+                assert!(image_item.image.pixels == pixels);
+                assert!(image_item.stretch_size == Size2D(Au::from_px(10), Au::from_px(10)));
+            }
+            _ => fail!("expected an image item"),
+        }
+    }
+
+    #[test]
+    fn test_stacking_context_round_trip() {
+        let mut children = DisplayList::new();
+        children.push(SolidColorDisplayItemClass(box SolidColorDisplayItem {
+            base: BaseDisplayItem::new(rect(0, 0, 10, 10), OpaqueNode(2), ContentStackingLevel),
+            color: color::rgb(0, 255, 0),
+            radii: BorderRadii::zero(),
+        }));
+        let mut display_list = DisplayList::new();
+        display_list.push(StackingContextDisplayItemClass(box StackingContextDisplayItem {
+            base: BaseDisplayItem::new(rect(30, 40, 10, 10),
+                                       OpaqueNode(1),
+                                       PositionedDescendantStackingLevel(2)),
+            children: children,
+            opacity: 0.5,
+            transform: Matrix2D::new(2.0, 0.0, 0.0, 3.0, 30.0, 40.0),
+        }));
+
+        let items = round_trip(display_list);
+        assert!(items.len() == 1);
+        match *items.get(0) {
+            StackingContextDisplayItemClass(ref stacking_context) => {
+                assert!(stacking_context.base.bounds == rect(30, 40, 10, 10));
+                assert!(stacking_context.base.level == PositionedDescendantStackingLevel(2));
+                assert!(stacking_context.opacity == 0.5);
+                let transform = &stacking_context.transform;
+                assert!(transform.m11 == 2.0 && transform.m12 == 0.0);
+                assert!(transform.m21 == 0.0 && transform.m22 == 3.0);
+                assert!(transform.m31 == 30.0 && transform.m32 == 40.0);
+                let children: Vec<_> = stacking_context.children.iter().collect();
+                assert!(children.len() == 1);
+                assert!(children.get(0).base().node == OpaqueNode(2));
+            }
+            _ => fail!("expected a stacking context"),
+        }
+    }
+
+    #[test]
+    fn test_box_shadow_round_trip() {
+        let mut radii = BorderRadii::zero();
+        radii.bottom_right = Size2D(Au::from_px(3), Au::from_px(3));
+        let mut display_list = DisplayList::new();
+        display_list.push(BoxShadowDisplayItemClass(box BoxShadowDisplayItem {
+            base: BaseDisplayItem::new(rect(-10, -10, 120, 70),
+                                       OpaqueNode(1),
+                                       ContentStackingLevel),
+            box_bounds: rect(0, 0, 100, 50),
+            radii: radii,
+            offset: Point2D(Au::from_px(2), Au::from_px(-3)),
+            color: color::rgba(0.0, 0.0, 0.0, 0.5),
+            blur_radius: Au::from_px(4),
+            spread_radius: Au::from_px(1),
+            inset: true,
+        }));
+
+        let items = round_trip(display_list);
+        assert!(items.len() == 1);
+        match *items.get(0) {
+            BoxShadowDisplayItemClass(ref shadow) => {
+                assert!(shadow.base.bounds == rect(-10, -10, 120, 70));
+                assert!(shadow.box_bounds == rect(0, 0, 100, 50));
+                assert!(shadow.radii.bottom_right == Size2D(Au::from_px(3), Au::from_px(3)));
+                assert!(shadow.offset == Point2D(Au::from_px(2), Au::from_px(-3)));
+                assert!(shadow.color.a == 0.5);
+                assert_eq!(shadow.blur_radius, Au::from_px(4));
+                assert_eq!(shadow.spread_radius, Au::from_px(1));
+                assert!(shadow.inset);
+            }
+            _ => fail!("expected a box shadow item"),
+        }
+    }
+
+    #[test]
+    fn test_text_shadow_round_trip() {
+        let mut display_list = DisplayList::new();
+        display_list.push(TextShadowDisplayItemClass(box TextShadowDisplayItem {
+            base: BaseDisplayItem::new(rect(0, 0, 40, 20), OpaqueNode(1), ContentStackingLevel),
+            text_run: text_run("Hi"),
+            range: Range::new(CharIndex(0), CharIndex(2)),
+            text_bounds: rect(5, 5, 30, 10),
+            offset: Point2D(Au::from_px(1), Au::from_px(2)),
+            color: color::rgb(0, 0, 255),
+            blur_radius: Au::from_px(3),
+        }));
+
+        let items = round_trip(display_list);
+        assert!(items.len() == 1);
+        match *items.get(0) {
+            TextShadowDisplayItemClass(ref shadow) => {
+                assert_eq!(shadow.text_run.text.as_slice(), "Hi");
+                assert!(shadow.range.begin() == CharIndex(0) && shadow.range.end() == CharIndex(2));
+                assert!(shadow.text_bounds == rect(5, 5, 30, 10));
+                assert!(shadow.offset == Point2D(Au::from_px(1), Au::from_px(2)));
+                assert!(shadow.color.b == 1.0 && shadow.color.r == 0.0);
+                assert_eq!(shadow.blur_radius, Au::from_px(3));
+            }
+            _ => fail!("expected a text shadow item"),
+        }
+    }
+}
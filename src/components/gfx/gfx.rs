@@ -49,11 +49,13 @@ pub use servo_gfx_font = font;
 pub use servo_gfx_font_list = font_list;
 
 // Private rendering modules
+mod blur;
 mod render_context;
 
 // Rendering
 pub mod color;
 pub mod display_list;
+pub mod display_list_serialization;
 pub mod render_task;
 
 // Fonts
@@ -2,13 +2,19 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use blur;
+use display_list::{BorderRadii, GradientGeometry, GradientStop, LinearGradientGeometry};
+use display_list::{RadialGradientGeometry, transform_rect};
 use font_context::FontContext;
 use style::computed_values::border_style;
 
 use azure::azure_hl::{B8G8R8A8, Color, ColorPattern, DrawOptions, DrawSurfaceOptions, DrawTarget};
-use azure::azure_hl::{Linear, SourceOp, StrokeOptions};
+use azure::azure_hl::{ExtendClamp, ExtendRepeat, Linear, LinearGradientPattern, PathBuilder};
+use azure::azure_hl::{RadialGradientPattern, SourceOp, StrokeOptions};
+use azure::azure_hl;
 use azure::AZ_CAP_BUTT;
 use azure::AzFloat;
+use geom::matrix2d::Matrix2D;
 use geom::point::Point2D;
 use geom::rect::Rect;
 use geom::size::Size2D;
@@ -43,6 +49,10 @@ enum DashSize {
     DashedBorder = 3
 }
 
+/// The distance of the control points of a cubic Bezier curve approximating a quarter ellipse from
+/// the ends of the curve, as a fraction of the radius.
+static KAPPA: AzFloat = 0.5522847498;
+
 impl<'a> RenderContext<'a>  {
     pub fn get_draw_target(&self) -> &'a DrawTarget {
         self.draw_target
@@ -53,14 +63,40 @@ impl<'a> RenderContext<'a>  {
         self.draw_target.fill_rect(&bounds.to_azure_rect(), &ColorPattern(color), None);
     }
 
+    /// Fills the given rectangle with rounded corners.
+    pub fn draw_rounded_solid_color(&self,
+                                    bounds: &Rect<Au>,
+                                    radii: &BorderRadii<Au>,
+                                    color: Color) {
+        if radii.is_square() {
+            return self.draw_solid_color(bounds, color)
+        }
+
+        self.draw_target.make_current();
+        let path_builder = self.draw_target.create_path_builder();
+        append_rounded_rect(&path_builder, &bounds.to_azure_rect(), &radii.to_float_px(), true);
+        let path = path_builder.finish();
+        self.draw_target.fill(&path, &ColorPattern(color), &DrawOptions(1.0, 0));
+    }
+
     pub fn draw_border(&self,
                        bounds: &Rect<Au>,
                        border: SideOffsets2D<Au>,
                        color: SideOffsets2D<Color>,
-                       style: SideOffsets2D<border_style::T>) {
+                       style: SideOffsets2D<border_style::T>,
+                       radius: &BorderRadii<Au>) {
         let border = border.to_float_px();
         self.draw_target.make_current();
 
+        if !radius.is_square() {
+            let radius = radius.to_float_px();
+            self.draw_rounded_border_segment(Top, bounds, border, color, style, &radius);
+            self.draw_rounded_border_segment(Right, bounds, border, color, style, &radius);
+            self.draw_rounded_border_segment(Bottom, bounds, border, color, style, &radius);
+            self.draw_rounded_border_segment(Left, bounds, border, color, style, &radius);
+            return
+        }
+
         self.draw_border_segment(Top, bounds, border, color, style);
         self.draw_border_segment(Right, bounds, border, color, style);
         self.draw_border_segment(Bottom, bounds, border, color, style);
@@ -76,10 +112,17 @@ impl<'a> RenderContext<'a>  {
         self.draw_line_segment(bounds, color, style);
     }
 
-    pub fn draw_push_clip(&self, bounds: &Rect<Au>) {
+    pub fn draw_push_clip(&self, bounds: &Rect<Au>, radii: &BorderRadii<Au>) {
         let rect = bounds.to_azure_rect();
         let path_builder = self.draw_target.create_path_builder();
 
+        if !radii.is_square() {
+            append_rounded_rect(&path_builder, &rect, &radii.to_float_px(), true);
+            let path = path_builder.finish();
+            self.draw_target.push_clip(&path);
+            return
+        }
+
         let left_top = Point2D(rect.origin.x, rect.origin.y);
         let right_top = Point2D(rect.origin.x + rect.size.width, rect.origin.y);
         let left_bottom = Point2D(rect.origin.x, rect.origin.y + rect.size.height);
@@ -124,6 +167,246 @@ impl<'a> RenderContext<'a>  {
                                      draw_options);
     }
 
+    /// Fills the bounds with a gradient. The gradient stops are created by this context's draw
+    /// target, so they match its backend whether painting happens on the CPU or the GPU.
+    pub fn draw_gradient(&self,
+                         bounds: &Rect<Au>,
+                         geometry: &GradientGeometry,
+                         stops: &[GradientStop],
+                         repeating: bool) {
+        let azure_stops: Vec<azure_hl::GradientStop> = stops.iter().map(|stop| {
+            azure_hl::GradientStop {
+                position: stop.offset,
+                color: stop.color,
+            }
+        }).collect();
+        let extend_mode = if repeating {
+            ExtendRepeat
+        } else {
+            ExtendClamp
+        };
+
+        self.draw_target.make_current();
+        let gradient_stops = self.draw_target.create_gradient_stops(azure_stops.as_slice(),
+                                                                    extend_mode);
+        let rect = bounds.to_azure_rect();
+        match *geometry {
+            LinearGradientGeometry(ref start_point, ref end_point) => {
+                let pattern = LinearGradientPattern::new(&start_point.to_azure_point(),
+                                                         &end_point.to_azure_point(),
+                                                         gradient_stops,
+                                                         &Matrix2D::identity());
+                self.draw_target.fill_rect(&rect, &pattern, None);
+            }
+            RadialGradientGeometry(ref center, ref start_radius, ref end_radius) => {
+                // Draw circles around the origin and stretch them into ellipses centered on the
+                // center point.
+                let end_width = end_radius.width.to_nearest_px() as AzFloat;
+                let end_height = end_radius.height.to_nearest_px() as AzFloat;
+                if end_width <= 0.0 || end_height <= 0.0 {
+                    return
+                }
+                let center = center.to_azure_point();
+                let matrix = Matrix2D::new(1.0, 0.0,
+                                           0.0, end_height / end_width,
+                                           center.x, center.y);
+                let origin = Point2D(0.0 as AzFloat, 0.0);
+                let pattern = RadialGradientPattern::new(&origin,
+                                                         &origin,
+                                                         start_radius.width.to_nearest_px()
+                                                                           as AzFloat,
+                                                         end_width,
+                                                         gradient_stops,
+                                                         &matrix);
+                self.draw_target.fill_rect(&rect, &pattern, None);
+            }
+        }
+    }
+
+    /// Draws the items painted by `f` with the given transform applied before the current one.
+    pub fn draw_with_transform(&mut self,
+                               transform: &Matrix2D<AzFloat>,
+                               f: |&mut RenderContext|) {
+        let old_transform = self.draw_target.get_transform();
+        self.draw_target.set_transform(&transform.mul(&old_transform));
+        f(self);
+        self.draw_target.set_transform(&old_transform);
+    }
+
+    /// Draws the items painted by `f` as a single group composited with the given opacity. The
+    /// group is rendered into a temporary draw target the size of this one, which is then drawn
+    /// into this draw target.
+    pub fn draw_with_opacity(&mut self, opacity: AzFloat, f: |&mut RenderContext|) {
+        if opacity >= 1.0 {
+            return f(self)
+        }
+        if opacity <= 0.0 {
+            return
+        }
+
+        let size = Size2D(self.screen_rect.size.width as i32,
+                          self.screen_rect.size.height as i32);
+        let temporary_draw_target = self.draw_target.create_similar_draw_target(&size, B8G8R8A8);
+        let transform = self.draw_target.get_transform();
+        temporary_draw_target.set_transform(&transform);
+        {
+            let mut temporary_context = RenderContext {
+                draw_target: &temporary_draw_target,
+                font_ctx: &mut *self.font_ctx,
+                opts: self.opts,
+                page_rect: self.page_rect,
+                screen_rect: self.screen_rect,
+            };
+            f(&mut temporary_context);
+        }
+
+        // Both draw targets cover the same pixels, so composite in device space.
+        let rect = Rect(Point2D(0 as AzFloat, 0 as AzFloat),
+                        Size2D(size.width as AzFloat, size.height as AzFloat));
+        self.draw_target.make_current();
+        self.draw_target.set_transform(&Matrix2D::identity());
+        self.draw_target.draw_surface(temporary_draw_target.snapshot(),
+                                      rect,
+                                      rect,
+                                      DrawSurfaceOptions(Linear, true),
+                                      DrawOptions(opacity, 0));
+        self.draw_target.set_transform(&transform);
+    }
+
+    /// Draws the items painted by `f`, blurred with the given CSS blur radius. Only the part of
+    /// the painting within `bounds` is drawn; the caller is responsible for making the bounds
+    /// large enough to contain the blurred edges.
+    ///
+    /// The painting is blurred in device pixels, so the bounds and the blur radius are scaled by
+    /// the current transform, which includes the device scale factor.
+    pub fn draw_blurred(&mut self, bounds: &Rect<Au>, blur_radius: Au, f: |&mut RenderContext|) {
+        if blur_radius <= Au(0) {
+            return f(self)
+        }
+
+        let transform = self.draw_target.get_transform();
+        let rect = transform_rect(&transform, bounds).to_azure_rect();
+        let size = Size2D(rect.size.width as i32, rect.size.height as i32);
+        if size.width <= 0 || size.height <= 0 {
+            return
+        }
+
+        let temporary_draw_target = self.draw_target.create_similar_draw_target(&size, B8G8R8A8);
+        let device_offset = Matrix2D::identity().translate(-rect.origin.x, -rect.origin.y);
+        temporary_draw_target.set_transform(&transform.mul(&device_offset));
+        {
+            let mut temporary_context = RenderContext {
+                draw_target: &temporary_draw_target,
+                font_ctx: &mut *self.font_ctx,
+                opts: self.opts,
+                page_rect: self.page_rect,
+                screen_rect: self.screen_rect,
+            };
+            f(&mut temporary_context);
+        }
+
+        // Blur a copy of the pixels on the CPU, then draw the result back over the bounds.
+        let scale = (transform.m11 * transform.m11 + transform.m12 * transform.m12).sqrt();
+        let stride = (size.width * 4) as uint;
+        let mut pixels = Vec::new();
+        temporary_draw_target.snapshot().get_data_surface().with_data(|data| {
+            pixels.push_all(data)
+        });
+        let sigma = blur::sigma_for_blur_radius(blur_radius.to_nearest_px() as f32 * scale);
+        blur::gaussian_blur(pixels.as_mut_slice(),
+                            size.width as uint,
+                            size.height as uint,
+                            stride,
+                            sigma);
+
+        self.draw_target.make_current();
+        let blurred_surface = self.draw_target.create_source_surface_from_data(pixels.as_slice(),
+                                                                               size,
+                                                                               stride as i32,
+                                                                               B8G8R8A8);
+        let source_rect = Rect(Point2D(0 as AzFloat, 0 as AzFloat), rect.size);
+        self.draw_target.set_transform(&Matrix2D::identity());
+        self.draw_target.draw_surface(blurred_surface,
+                                      rect,
+                                      source_rect,
+                                      DrawSurfaceOptions(Linear, true),
+                                      DrawOptions(1.0, 0));
+        self.draw_target.set_transform(&transform);
+    }
+
+    /// Draws a box shadow, as described in CSS Backgrounds and Borders Level 3 section 7.1.
+    /// `bounds` is the area the shadow may paint and `box_bounds` the border box casting it. A
+    /// shadow outside the box is never painted under the box, and a shadow inside the box is
+    /// never painted outside of it.
+    pub fn draw_box_shadow(&mut self,
+                           bounds: &Rect<Au>,
+                           box_bounds: &Rect<Au>,
+                           radii: &BorderRadii<Au>,
+                           offset: Point2D<Au>,
+                           color: Color,
+                           blur_radius: Au,
+                           spread_radius: Au,
+                           inset: bool) {
+        let rect = bounds.to_azure_rect();
+        let box_rect = box_bounds.to_azure_rect();
+        let float_radii = radii.to_float_px();
+        let offset = Point2D(offset.x.to_nearest_px() as AzFloat,
+                             offset.y.to_nearest_px() as AzFloat);
+        let spread = spread_radius.to_nearest_px() as AzFloat;
+
+        // Clip to the side of the border box the shadow belongs on. Outside the box this is the
+        // painted area with the box cut out of it.
+        self.draw_target.make_current();
+        let clip_builder = self.draw_target.create_path_builder();
+        append_rounded_rect(&clip_builder, &box_rect, &float_radii, true);
+        if !inset {
+            append_rounded_rect(&clip_builder, &rect, &BorderRadii::zero(), false);
+        }
+        let clip = clip_builder.finish();
+        self.draw_target.push_clip(&clip);
+
+        // The shadow shape is the border box moved by the offset and grown by the spread radius,
+        // or shrunk by it for shadows inside the box.
+        let widths = if inset {
+            SideOffsets2D::new_all_same(spread)
+        } else {
+            SideOffsets2D::new_all_same(-spread)
+        };
+        let shifted_rect = Rect(box_rect.origin + offset, box_rect.size);
+        let (shape_rect, shape_radii) = if radii.is_square() {
+            let (shape_rect, _) = inset_rounded_rect(&shifted_rect, &float_radii, widths);
+            (shape_rect, BorderRadii::zero())
+        } else {
+            inset_rounded_rect(&shifted_rect, &float_radii, widths)
+        };
+
+        let paint_bounds = if inset {
+            box_bounds.clone()
+        } else {
+            bounds.clone()
+        };
+        self.draw_blurred(&paint_bounds, blur_radius, |render_context| {
+            let path_builder = render_context.draw_target.create_path_builder();
+            if inset {
+                // Fill everything around the shape, far enough out that the blur does not bring
+                // transparent pixels in past the edges of the box.
+                let margin = (blur_radius.to_nearest_px() as AzFloat) * 2.0 + spread.abs() +
+                    offset.x.abs() + offset.y.abs();
+                let outer_rect = Rect(box_rect.origin - Point2D(margin, margin),
+                                      Size2D(box_rect.size.width + margin * 2.0,
+                                             box_rect.size.height + margin * 2.0));
+                append_rounded_rect(&path_builder, &outer_rect, &BorderRadii::zero(), true);
+                append_rounded_rect(&path_builder, &shape_rect, &shape_radii, false);
+            } else {
+                append_rounded_rect(&path_builder, &shape_rect, &shape_radii, true);
+            }
+            let path = path_builder.finish();
+            render_context.draw_target.fill(&path, &ColorPattern(color), &DrawOptions(1.0, 0));
+        });
+
+        self.draw_target.pop_clip();
+    }
+
     pub fn clear(&self) {
         let pattern = ColorPattern(Color(0.0, 0.0, 0.0, 0.0));
         let rect = Rect(Point2D(self.page_rect.origin.x as AzFloat,
@@ -171,6 +454,112 @@ impl<'a> RenderContext<'a>  {
         }
     }
 
+    /// Draws one side of a border with rounded corners. The side is clipped to the triangle
+    /// between its edge and the center of the border box, and the part of the ring between the
+    /// outer and inner rounded rectangles that lies within it is filled.
+    fn draw_rounded_border_segment(&self,
+                                   direction: Direction,
+                                   bounds: &Rect<Au>,
+                                   border: SideOffsets2D<f32>,
+                                   color: SideOffsets2D<Color>,
+                                   style: SideOffsets2D<border_style::T>,
+                                   radius: &BorderRadii<f32>) {
+        let (style_select, color_select) = match direction {
+            Top => (style.top, color.top),
+            Left => (style.left, color.left),
+            Right => (style.right, color.right),
+            Bottom => (style.bottom, color.bottom)
+        };
+
+        let rect = bounds.to_azure_rect();
+        let left_top = rect.origin;
+        let right_top = left_top + Point2D(rect.size.width, 0.0);
+        let left_bottom = left_top + Point2D(0.0, rect.size.height);
+        let right_bottom = left_top + Point2D(rect.size.width, rect.size.height);
+        let center = left_top + Point2D(rect.size.width * 0.5, rect.size.height * 0.5);
+        let (start, end) = match direction {
+            Top => (left_top, right_top),
+            Right => (right_top, right_bottom),
+            Bottom => (right_bottom, left_bottom),
+            Left => (left_bottom, left_top),
+        };
+        let path_builder = self.draw_target.create_path_builder();
+        path_builder.move_to(start);
+        path_builder.line_to(end);
+        path_builder.line_to(center);
+        let clip = path_builder.finish();
+        self.draw_target.push_clip(&clip);
+
+        match style_select {
+            border_style::none | border_style::hidden => {}
+            // FIXME: Dotted and dashed rounded borders are drawn as solid.
+            border_style::solid | border_style::dotted | border_style::dashed => {
+                self.fill_rounded_ring(&rect, radius, border, 1.0, 0.0, color_select);
+            }
+            border_style::double => {
+                self.fill_rounded_ring(&rect, radius, border, 1.0/3.0, 0.0, color_select);
+                self.fill_rounded_ring(&rect, radius, border, 1.0/3.0, 2.0/3.0, color_select);
+            }
+            border_style::groove | border_style::ridge => {
+                let is_groove = style_select == border_style::groove;
+                let darker_color = self.scale_color(color_select,
+                                                    if is_groove { 1.0/3.0 } else { 2.0/3.0 });
+                let (outer_color, inner_color) = match (direction, is_groove) {
+                    (Top, true)  | (Left, true)  | (Right, false) | (Bottom, false) => {
+                        (darker_color, color_select)
+                    }
+                    (Top, false) | (Left, false) | (Right, true)  | (Bottom, true)  => {
+                        (color_select, darker_color)
+                    }
+                };
+                self.fill_rounded_ring(&rect, radius, border, 0.5, 0.0, outer_color);
+                self.fill_rounded_ring(&rect, radius, border, 0.5, 0.5, inner_color);
+            }
+            border_style::inset | border_style::outset => {
+                let is_inset = style_select == border_style::inset;
+                let scaled_color = match direction {
+                    Top => self.scale_color(color_select, if is_inset { 2.0/3.0 } else { 1.0 }),
+                    Left => self.scale_color(color_select, if is_inset { 1.0/6.0 } else { 0.5 }),
+                    Right | Bottom => {
+                        self.scale_color(color_select, if is_inset { 1.0 } else { 2.0/3.0 })
+                    }
+                };
+                self.fill_rounded_ring(&rect, radius, border, 1.0, 0.0, scaled_color);
+            }
+        }
+
+        self.draw_target.pop_clip();
+    }
+
+    /// Fills part of the area between the outer edge of a rounded border and its inner edge. The
+    /// filled ring starts `offset` of the way from the outer edge to the inner edge, and is
+    /// `fraction` of the border widths thick.
+    fn fill_rounded_ring(&self,
+                         rect: &Rect<f32>,
+                         radius: &BorderRadii<f32>,
+                         border: SideOffsets2D<f32>,
+                         fraction: f32,
+                         offset: f32,
+                         color: Color) {
+        let outer_border = SideOffsets2D::new(border.top * offset,
+                                              border.right * offset,
+                                              border.bottom * offset,
+                                              border.left * offset);
+        let ring_border = SideOffsets2D::new(border.top * fraction,
+                                             border.right * fraction,
+                                             border.bottom * fraction,
+                                             border.left * fraction);
+        let (outer_rect, outer_radii) = inset_rounded_rect(rect, radius, outer_border);
+        let (inner_rect, inner_radii) = inset_rounded_rect(&outer_rect, &outer_radii, ring_border);
+
+        // The inner rectangle winds the other way, so that it cuts a hole out of the outer one.
+        let path_builder = self.draw_target.create_path_builder();
+        append_rounded_rect(&path_builder, &outer_rect, &outer_radii, true);
+        append_rounded_rect(&path_builder, &inner_rect, &inner_radii, false);
+        let path = path_builder.finish();
+        self.draw_target.fill(&path, &ColorPattern(color), &DrawOptions(1.0, 0));
+    }
+
     fn draw_line_segment(&self, bounds: &Rect<Au>, color: Color, style: border_style::T) {
         let border = SideOffsets2D::new_all_same(bounds.size.width).to_float_px();
 
@@ -390,6 +779,16 @@ impl<'a> RenderContext<'a>  {
 
 }
 
+trait ToAzurePoint {
+    fn to_azure_point(&self) -> Point2D<AzFloat>;
+}
+
+impl ToAzurePoint for Point2D<Au> {
+    fn to_azure_point(&self) -> Point2D<AzFloat> {
+        Point2D(self.x.to_nearest_px() as AzFloat, self.y.to_nearest_px() as AzFloat)
+    }
+}
+
 trait ToAzureRect {
     fn to_azure_rect(&self) -> Rect<AzFloat>;
 }
@@ -403,6 +802,92 @@ impl ToAzureRect for Rect<Au> {
     }
 }
 
+/// Shrinks a rounded rectangle by the given widths, reducing the radii of its corners to match as
+/// described in CSS Backgrounds and Borders Level 3 section 5.2.
+fn inset_rounded_rect(rect: &Rect<f32>, radii: &BorderRadii<f32>, widths: SideOffsets2D<f32>)
+                      -> (Rect<f32>, BorderRadii<f32>) {
+    let shrink = |corner: &Size2D<f32>, horizontal: f32, vertical: f32| {
+        Size2D((corner.width - horizontal).max(0.0), (corner.height - vertical).max(0.0))
+    };
+    let inner_rect = Rect(rect.origin + Point2D(widths.left, widths.top),
+                          Size2D((rect.size.width - widths.left - widths.right).max(0.0),
+                                 (rect.size.height - widths.top - widths.bottom).max(0.0)));
+    let inner_radii = BorderRadii {
+        top_left: shrink(&radii.top_left, widths.left, widths.top),
+        top_right: shrink(&radii.top_right, widths.right, widths.top),
+        bottom_right: shrink(&radii.bottom_right, widths.right, widths.bottom),
+        bottom_left: shrink(&radii.bottom_left, widths.left, widths.bottom),
+    };
+    (inner_rect, inner_radii)
+}
+
+/// Appends a rounded rectangle to the path being built, as a closed subpath winding clockwise or
+/// counterclockwise. Each corner is approximated by a cubic Bezier curve.
+fn append_rounded_rect(path_builder: &PathBuilder,
+                       rect: &Rect<f32>,
+                       radii: &BorderRadii<f32>,
+                       clockwise: bool) {
+    let (left, top) = (rect.origin.x, rect.origin.y);
+    let (right, bottom) = (left + rect.size.width, top + rect.size.height);
+    let (tl, tr, br, bl) = (radii.top_left, radii.top_right, radii.bottom_right, radii.bottom_left);
+
+    path_builder.move_to(Point2D(left + tl.width, top));
+    if clockwise {
+        path_builder.line_to(Point2D(right - tr.width, top));
+        path_builder.bezier_to(Point2D(right - tr.width * (1.0 - KAPPA), top),
+                               Point2D(right, top + tr.height * (1.0 - KAPPA)),
+                               Point2D(right, top + tr.height));
+        path_builder.line_to(Point2D(right, bottom - br.height));
+        path_builder.bezier_to(Point2D(right, bottom - br.height * (1.0 - KAPPA)),
+                               Point2D(right - br.width * (1.0 - KAPPA), bottom),
+                               Point2D(right - br.width, bottom));
+        path_builder.line_to(Point2D(left + bl.width, bottom));
+        path_builder.bezier_to(Point2D(left + bl.width * (1.0 - KAPPA), bottom),
+                               Point2D(left, bottom - bl.height * (1.0 - KAPPA)),
+                               Point2D(left, bottom - bl.height));
+        path_builder.line_to(Point2D(left, top + tl.height));
+        path_builder.bezier_to(Point2D(left, top + tl.height * (1.0 - KAPPA)),
+                               Point2D(left + tl.width * (1.0 - KAPPA), top),
+                               Point2D(left + tl.width, top));
+    } else {
+        path_builder.bezier_to(Point2D(left + tl.width * (1.0 - KAPPA), top),
+                               Point2D(left, top + tl.height * (1.0 - KAPPA)),
+                               Point2D(left, top + tl.height));
+        path_builder.line_to(Point2D(left, bottom - bl.height));
+        path_builder.bezier_to(Point2D(left, bottom - bl.height * (1.0 - KAPPA)),
+                               Point2D(left + bl.width * (1.0 - KAPPA), bottom),
+                               Point2D(left + bl.width, bottom));
+        path_builder.line_to(Point2D(right - br.width, bottom));
+        path_builder.bezier_to(Point2D(right - br.width * (1.0 - KAPPA), bottom),
+                               Point2D(right, bottom - br.height * (1.0 - KAPPA)),
+                               Point2D(right, bottom - br.height));
+        path_builder.line_to(Point2D(right, top + tr.height));
+        path_builder.bezier_to(Point2D(right, top + tr.height * (1.0 - KAPPA)),
+                               Point2D(right - tr.width * (1.0 - KAPPA), top),
+                               Point2D(right - tr.width, top));
+        path_builder.line_to(Point2D(left + tl.width, top));
+    }
+}
+
+trait ToBorderRadiiPx {
+    fn to_float_px(&self) -> BorderRadii<AzFloat>;
+}
+
+impl ToBorderRadiiPx for BorderRadii<Au> {
+    fn to_float_px(&self) -> BorderRadii<AzFloat> {
+        let to_px = |corner: &Size2D<Au>| {
+            Size2D(corner.width.to_nearest_px() as AzFloat,
+                   corner.height.to_nearest_px() as AzFloat)
+        };
+        BorderRadii {
+            top_left: to_px(&self.top_left),
+            top_right: to_px(&self.top_right),
+            bottom_right: to_px(&self.bottom_right),
+            bottom_left: to_px(&self.bottom_left),
+        }
+    }
+}
+
 trait ToSideOffsetsPx {
     fn to_float_px(&self) -> SideOffsets2D<AzFloat>;
 }
@@ -5,30 +5,33 @@
 //! The task that handles all rendering/painting.
 
 use buffer_map::BufferMap;
-use display_list::DisplayList;
+use display_list::IndexedDisplayList;
 use font_context::{FontContext, FontContextInfo};
 use render_context::RenderContext;
 
 use azure::azure_hl::{B8G8R8A8, Color, DrawTarget, StolenGLResources};
 use azure::AzFloat;
 use geom::matrix2d::Matrix2D;
+use geom::point::Point2D;
 use geom::rect::Rect;
 use geom::size::Size2D;
 use layers::platform::surface::{NativePaintingGraphicsContext, NativeSurface};
 use layers::platform::surface::{NativeSurfaceMethods};
 use layers;
-use servo_msg::compositor_msg::{Epoch, IdleRenderState, LayerBuffer};
+use servo_msg::compositor_msg::{CpuLayerBuffer, Epoch, IdleRenderState, LayerBuffer};
 use servo_msg::compositor_msg::{LayerBufferSet, LayerId, LayerMetadata, RenderListener};
 use servo_msg::compositor_msg::{RenderingRenderState, ScrollPolicy};
 use servo_msg::constellation_msg::{ConstellationChan, PipelineId, RendererReadyMsg};
 use servo_msg::constellation_msg::{Failure, FailureMsg};
 use servo_msg::platform::surface::NativeSurfaceAzureMethods;
+use servo_util::geometry::Au;
 use servo_util::opts::Opts;
 use servo_util::smallvec::{SmallVec, SmallVec1};
 use servo_util::time::{ProfilerChan, profile};
 use servo_util::time;
 use servo_util::task::send_on_failure;
 
+use std::cmp;
 use std::comm::{channel, Receiver, Sender};
 use std::task::TaskBuilder;
 use sync::Arc;
@@ -37,14 +40,21 @@ use sync::Arc;
 pub struct RenderLayer {
     /// A per-pipeline ID describing this layer that should be stable across reflows.
     pub id: LayerId,
-    /// The display list describing the contents of this layer.
-    pub display_list: Arc<DisplayList>,
+    /// The display list describing the contents of this layer, indexed so that the items that
+    /// intersect a tile can be found quickly.
+    pub display_list: Arc<IndexedDisplayList>,
     /// The position of the layer in pixels.
     pub position: Rect<uint>,
+    /// The visible part of the layer in pixels. This is the padding box of a scrollable box for
+    /// layers that scroll their contents, and equal to `position` otherwise.
+    pub clip_rect: Rect<uint>,
     /// The color of the background in this layer. Used for unrendered content.
     pub background_color: Color,
     /// The scrolling policy of this layer.
     pub scroll_policy: ScrollPolicy,
+    /// The parts of the layer, in pixels relative to its origin, that render differently than
+    /// they did after the previous reflow. `None` if the whole layer must be rendered again.
+    pub dirty_rects: Option<Vec<Rect<uint>>>,
 }
 
 pub enum Msg {
@@ -128,6 +138,10 @@ pub struct RenderTask<C> {
 
     /// A data structure to store unused LayerBuffers
     buffer_map: BufferMap<Box<LayerBuffer>>,
+
+    /// The workers that rasterize tiles in parallel, if CPU painting with more than one render
+    /// thread.
+    worker_pool: Option<RenderWorkerPool>,
 }
 
 // If we implement this as a function, we get borrowck errors from borrowing
@@ -147,8 +161,10 @@ fn initialize_layers<C:RenderListener>(
         LayerMetadata {
             id: render_layer.id,
             position: render_layer.position,
+            clip_rect: render_layer.clip_rect,
             background_color: render_layer.background_color,
             scroll_policy: render_layer.scroll_policy,
+            dirty_rects: render_layer.dirty_rects.clone(),
         }
     }).collect();
     compositor.initialize_layers_for_pipeline(pipeline_id, metadata, epoch);
@@ -172,6 +188,13 @@ impl<C: RenderListener + Send> RenderTask<C> {
                 let native_graphics_context = compositor.get_graphics_metadata().map(
                     |md| NativePaintingGraphicsContext::from_metadata(&md));
                 let cpu_painting = opts.cpu_painting;
+                let worker_pool = if cpu_painting && opts.n_render_threads > 1 {
+                    Some(RenderWorkerPool::new(opts.n_render_threads,
+                                               &opts,
+                                               profiler_chan.clone()))
+                } else {
+                    None
+                };
 
                 // FIXME: rust/#5967
                 let mut render_task = RenderTask {
@@ -200,6 +223,7 @@ impl<C: RenderListener + Send> RenderTask<C> {
                     paint_permission: false,
                     epoch: Epoch(0),
                     buffer_map: BufferMap::new(10000000),
+                    worker_pool: worker_pool,
                 };
 
                 render_task.start();
@@ -292,126 +316,154 @@ impl<C: RenderListener + Send> RenderTask<C> {
 
             self.compositor.set_render_state(RenderingRenderState);
 
-            // Divide up the layer into tiles.
-            for tile in tiles.iter() {
-                let width = tile.screen_rect.size.width;
-                let height = tile.screen_rect.size.height;
-
-                let size = Size2D(width as i32, height as i32);
-                let draw_target = match self.graphics_context {
-                    CpuGraphicsContext => {
-                        DrawTarget::new(self.opts.render_backend, size, B8G8R8A8)
+            // Without a native graphics context, as in headless mode, there are no native
+            // surfaces to render into, so the pixels of the tiles are sent to the compositor.
+            if self.native_graphics_context.is_none() {
+                let all_pixels = profile(time::RenderingDrawingCategory,
+                                         self.profiler_chan.clone(),
+                                         || {
+                    match self.worker_pool {
+                        Some(ref mut worker_pool) => {
+                            worker_pool.render(&mut self.font_ctx,
+                                               &self.opts,
+                                               &render_layer.display_list,
+                                               render_layer.position.origin,
+                                               tiles.as_slice(),
+                                               scale)
+                        }
+                        None => {
+                            tiles.iter().map(|tile| {
+                                render_tile_to_pixels(&mut self.font_ctx,
+                                                      &self.opts,
+                                                      &*render_layer.display_list,
+                                                      render_layer.position.origin,
+                                                      tile,
+                                                      scale)
+                            }).collect()
+                        }
                     }
-                    GpuGraphicsContext => {
-                        // FIXME(pcwalton): Cache the components of draw targets
-                        // (texture color buffer, renderbuffers) instead of recreating them.
-                        let draw_target =
-                            DrawTarget::new_with_fbo(self.opts.render_backend,
-                                                     native_graphics_context!(self),
-                                                     size,
-                                                     B8G8R8A8);
-                        draw_target.make_current();
-                        draw_target
+                });
+                let buffers = tiles.iter().zip(all_pixels.move_iter()).map(|(tile, pixels)| {
+                    CpuLayerBuffer {
+                        rect: tile.page_rect,
+                        screen_pos: tile.screen_rect,
+                        resolution: scale,
+                        pixels: pixels,
                     }
-                };
-
-                {
-                    // Build the render context.
-                    let mut ctx = RenderContext {
-                        draw_target: &draw_target,
-                        font_ctx: &mut self.font_ctx,
-                        opts: &self.opts,
-                        page_rect: tile.page_rect,
-                        screen_rect: tile.screen_rect,
-                    };
+                }).collect();
 
-                    // Apply the translation to render the tile we want.
-                    let matrix: Matrix2D<AzFloat> = Matrix2D::identity();
-                    let matrix = matrix.scale(scale as AzFloat, scale as AzFloat);
-                    let matrix = matrix.translate(-(tile.page_rect.origin.x) as AzFloat,
-                                                  -(tile.page_rect.origin.y) as AzFloat);
-                    let matrix = matrix.translate(-(render_layer.position.origin.x as AzFloat),
-                                                  -(render_layer.position.origin.y as AzFloat));
-
-                    ctx.draw_target.set_transform(&matrix);
+                if self.paint_permission {
+                    self.compositor.paint_pixels(self.id, render_layer.id, buffers, self.epoch);
+                } else {
+                    let ConstellationChan(ref mut c) = self.constellation_chan;
+                    c.send(RendererReadyMsg(self.id));
+                }
+                self.compositor.set_render_state(IdleRenderState);
+                return
+            }
 
-                    // Clear the buffer.
-                    ctx.clear();
+            // In CPU painting mode, the tiles can be rasterized on the render workers in parallel
+            // and uploaded here afterward.
+            if self.worker_pool.is_some() {
+                let all_pixels = profile(time::RenderingDrawingCategory,
+                                         self.profiler_chan.clone(),
+                                         || {
+                    let worker_pool = self.worker_pool.get_mut_ref();
+                    worker_pool.render(&mut self.font_ctx,
+                                       &self.opts,
+                                       &render_layer.display_list,
+                                       render_layer.position.origin,
+                                       tiles.as_slice(),
+                                       scale)
+                });
+                for (tile, pixels) in tiles.iter().zip(all_pixels.iter()) {
+                    let buffer = cpu_buffer_for_tile(&mut self.buffer_map,
+                                                     native_graphics_context!(self),
+                                                     tile,
+                                                     scale);
+                    buffer.native_surface.upload(native_graphics_context!(self),
+                                                 pixels.as_slice());
+                    new_buffers.push(buffer);
+                }
+            } else {
+                // Otherwise, divide up the layer into tiles and render them one at a time.
+                for tile in tiles.iter() {
+                    let width = tile.screen_rect.size.width;
+                    let height = tile.screen_rect.size.height;
+
+                    let size = Size2D(width as i32, height as i32);
+                    let draw_target = match self.graphics_context {
+                        CpuGraphicsContext => {
+                            DrawTarget::new(self.opts.render_backend, size, B8G8R8A8)
+                        }
+                        GpuGraphicsContext => {
+                            // FIXME(pcwalton): Cache the components of draw targets
+                            // (texture color buffer, renderbuffers) instead of recreating them.
+                            let draw_target =
+                                DrawTarget::new_with_fbo(self.opts.render_backend,
+                                                         native_graphics_context!(self),
+                                                         size,
+                                                         B8G8R8A8);
+                            draw_target.make_current();
+                            draw_target
+                        }
+                    };
 
-                    // Draw the display list.
                     profile(time::RenderingDrawingCategory, self.profiler_chan.clone(), || {
-                        render_layer.display_list.draw_into_context(&mut ctx);
-                        ctx.draw_target.flush();
+                        render_tile(&draw_target,
+                                    &mut self.font_ctx,
+                                    &self.opts,
+                                    &*render_layer.display_list,
+                                    render_layer.position.origin,
+                                    tile,
+                                    scale);
                     });
-                }
 
-                // Extract the texture from the draw target and place it into its slot in the
-                // buffer. If using CPU rendering, upload it first.
-                //
-                // FIXME(pcwalton): We should supply the texture and native surface *to* the
-                // draw target in GPU rendering mode, so that it doesn't have to recreate it.
-                let buffer = match self.graphics_context {
-                    CpuGraphicsContext => {
-                        let buffer = match self.buffer_map.find(tile.screen_rect.size) {
-                            Some(buffer) => {
-                                let mut buffer = buffer;
-                                buffer.rect = tile.page_rect;
-                                buffer.screen_pos = tile.screen_rect;
-                                buffer.resolution = scale;
-                                buffer.native_surface.mark_wont_leak();
-                                buffer
-                            }
-                            None => {
-                                // Create an empty native surface. We mark it as not leaking
-                                // in case it dies in transit to the compositor task.
-                                let mut native_surface: NativeSurface =
-                                    layers::platform::surface::NativeSurfaceMethods::new(
-                                        native_graphics_context!(self),
-                                        Size2D(width as i32, height as i32),
-                                        width as i32 * 4);
-                                native_surface.mark_wont_leak();
-
-                                box LayerBuffer {
-                                    native_surface: native_surface,
-                                    rect: tile.page_rect,
-                                    screen_pos: tile.screen_rect,
-                                    resolution: scale,
-                                    stride: (width * 4) as uint
-                                }
+                    // Extract the texture from the draw target and place it into its slot in
+                    // the buffer. If using CPU rendering, upload it first.
+                    //
+                    // FIXME(pcwalton): We should supply the texture and native surface *to* the
+                    // draw target in GPU rendering mode, so that it doesn't have to recreate
+                    // it.
+                    let buffer = match self.graphics_context {
+                        CpuGraphicsContext => {
+                            let buffer = cpu_buffer_for_tile(&mut self.buffer_map,
+                                                             native_graphics_context!(self),
+                                                             tile,
+                                                             scale);
+                            draw_target.snapshot().get_data_surface().with_data(|data| {
+                                buffer.native_surface.upload(native_graphics_context!(self),
+                                                             data);
+                                debug!("RENDERER uploading to native surface {:d}",
+                                       buffer.native_surface.get_id() as int);
+                            });
+
+                            buffer
+                        }
+                        GpuGraphicsContext => {
+                            draw_target.make_current();
+                            let StolenGLResources {
+                                surface: native_surface
+                            } = draw_target.steal_gl_resources().unwrap();
+
+                            // We mark the native surface as not leaking in case the surfaces
+                            // die on their way to the compositor task.
+                            let mut native_surface: NativeSurface =
+                                NativeSurfaceAzureMethods::from_azure_surface(native_surface);
+                            native_surface.mark_wont_leak();
+
+                            box LayerBuffer {
+                                native_surface: native_surface,
+                                rect: tile.page_rect,
+                                screen_pos: tile.screen_rect,
+                                resolution: scale,
+                                stride: (width * 4) as uint
                             }
-                        };
-
-                        draw_target.snapshot().get_data_surface().with_data(|data| {
-                            buffer.native_surface.upload(native_graphics_context!(self), data);
-                            debug!("RENDERER uploading to native surface {:d}",
-                                   buffer.native_surface.get_id() as int);
-                        });
-
-                        buffer
-                    }
-                    GpuGraphicsContext => {
-                        draw_target.make_current();
-                        let StolenGLResources {
-                            surface: native_surface
-                        } = draw_target.steal_gl_resources().unwrap();
-
-                        // We mark the native surface as not leaking in case the surfaces
-                        // die on their way to the compositor task.
-                        let mut native_surface: NativeSurface =
-                            NativeSurfaceAzureMethods::from_azure_surface(native_surface);
-                        native_surface.mark_wont_leak();
-
-                        box LayerBuffer {
-                            native_surface: native_surface,
-                            rect: tile.page_rect,
-                            screen_pos: tile.screen_rect,
-                            resolution: scale,
-                            stride: (width * 4) as uint
                         }
-                    }
-                };
+                    };
 
-                new_buffers.push(buffer);
+                    new_buffers.push(buffer);
+                }
             }
 
             let layer_buffer_set = box LayerBufferSet {
@@ -431,3 +483,306 @@ impl<C: RenderListener + Send> RenderTask<C> {
     }
 }
 
+/// Draws the given tile of a layer into the draw target, which must be the size of the tile. Only
+/// the display items that intersect the tile are drawn.
+fn render_tile(draw_target: &DrawTarget,
+               font_ctx: &mut Box<FontContext>,
+               opts: &Opts,
+               display_list: &IndexedDisplayList,
+               layer_origin: Point2D<uint>,
+               tile: &BufferRequest,
+               scale: f32) {
+    // Build the render context.
+    let mut ctx = RenderContext {
+        draw_target: draw_target,
+        font_ctx: font_ctx,
+        opts: opts,
+        page_rect: tile.page_rect,
+        screen_rect: tile.screen_rect,
+    };
+
+    // Apply the translation to render the tile we want.
+    let matrix: Matrix2D<AzFloat> = Matrix2D::identity();
+    let matrix = matrix.scale(scale as AzFloat, scale as AzFloat);
+    let matrix = matrix.translate(-(tile.page_rect.origin.x) as AzFloat,
+                                  -(tile.page_rect.origin.y) as AzFloat);
+    let matrix = matrix.translate(-(layer_origin.x as AzFloat), -(layer_origin.y as AzFloat));
+
+    ctx.draw_target.set_transform(&matrix);
+
+    // Clear the buffer.
+    ctx.clear();
+
+    // Draw the display items that can touch the tile. The display list is in the coordinates of
+    // the page rather than those of the layer.
+    let tile_rect = Rect(Point2D(Au::from_frac_px((tile.page_rect.origin.x +
+                                                   layer_origin.x as f32) as f64),
+                                 Au::from_frac_px((tile.page_rect.origin.y +
+                                                   layer_origin.y as f32) as f64)),
+                         Size2D(Au::from_frac_px(tile.page_rect.size.width as f64),
+                                Au::from_frac_px(tile.page_rect.size.height as f64)));
+    display_list.cull(&tile_rect).draw_into_context(&mut ctx);
+    ctx.draw_target.flush();
+}
+
+/// Draws the given tile of a layer into a new draw target on the CPU and returns its pixels, in
+/// BGRA order with premultiplied alpha.
+fn render_tile_to_pixels(font_ctx: &mut Box<FontContext>,
+                         opts: &Opts,
+                         display_list: &IndexedDisplayList,
+                         layer_origin: Point2D<uint>,
+                         tile: &BufferRequest,
+                         scale: f32)
+                         -> Vec<u8> {
+    let size = Size2D(tile.screen_rect.size.width as i32, tile.screen_rect.size.height as i32);
+    let draw_target = DrawTarget::new(opts.render_backend, size, B8G8R8A8);
+    render_tile(&draw_target, font_ctx, opts, display_list, layer_origin, tile, scale);
+    let mut pixels = Vec::new();
+    draw_target.snapshot().get_data_surface().with_data(|data| pixels.push_all(data));
+    pixels
+}
+
+/// Draws a whole display list on the CPU, from the origin of the page to the bottom right corner
+/// of its items, without a render task. Returns the size of the drawing and its pixels, in BGRA
+/// order with premultiplied alpha. This is how serialized display lists are replayed.
+pub fn render_display_list(opts: &Opts,
+                           profiler_chan: ProfilerChan,
+                           display_list: &IndexedDisplayList)
+                           -> (Size2D<uint>, Vec<u8>) {
+    let (mut width, mut height) = (1, 1);
+    for item in display_list.iter() {
+        let bounds = item.painted_bounds();
+        width = cmp::max(width, (bounds.origin.x + bounds.size.width).to_nearest_px());
+        height = cmp::max(height, (bounds.origin.y + bounds.size.height).to_nearest_px());
+    }
+    let size = Size2D(width as uint, height as uint);
+    let tile = BufferRequest(Rect(Point2D(0u, 0u), size),
+                             Rect(Point2D(0.0f32, 0.0f32), Size2D(width as f32, height as f32)));
+
+    let mut font_ctx = box FontContext::new(FontContextInfo {
+        backend: opts.render_backend.clone(),
+        needs_font_list: false,
+        profiler_chan: profiler_chan,
+    });
+    let pixels = render_tile_to_pixels(&mut font_ctx,
+                                       opts,
+                                       display_list,
+                                       Point2D(0u, 0u),
+                                       &tile,
+                                       1.0);
+    (size, pixels)
+}
+
+/// Converts pixels in BGRA order with premultiplied alpha, as draw targets hold them, to RGBA
+/// with straight alpha, as PNG files hold them.
+pub fn premultiplied_bgra_to_rgba(pixels: &mut [u8]) {
+    for pixel in pixels.mut_chunks(4) {
+        let alpha = pixel[3] as uint;
+        let unpremultiply = |value: u8| {
+            if alpha == 0 {
+                0
+            } else {
+                cmp::min(value as uint * 255 / alpha, 255) as u8
+            }
+        };
+        let (blue, green, red) = (pixel[0], pixel[1], pixel[2]);
+        pixel[0] = unpremultiply(red);
+        pixel[1] = unpremultiply(green);
+        pixel[2] = unpremultiply(blue);
+    }
+}
+
+/// Returns a buffer for a tile rendered on the CPU, reusing an unused buffer of the same size if
+/// there is one.
+fn cpu_buffer_for_tile(buffer_map: &mut BufferMap<Box<LayerBuffer>>,
+                       native_graphics_context: &NativePaintingGraphicsContext,
+                       tile: &BufferRequest,
+                       scale: f32)
+                       -> Box<LayerBuffer> {
+    match buffer_map.find(tile.screen_rect.size) {
+        Some(buffer) => {
+            let mut buffer = buffer;
+            buffer.rect = tile.page_rect;
+            buffer.screen_pos = tile.screen_rect;
+            buffer.resolution = scale;
+            buffer.native_surface.mark_wont_leak();
+            buffer
+        }
+        None => {
+            // Create an empty native surface. We mark it as not leaking in case it dies in
+            // transit to the compositor task.
+            let width = tile.screen_rect.size.width;
+            let height = tile.screen_rect.size.height;
+            let mut native_surface: NativeSurface =
+                layers::platform::surface::NativeSurfaceMethods::new(
+                    native_graphics_context,
+                    Size2D(width as i32, height as i32),
+                    width as i32 * 4);
+            native_surface.mark_wont_leak();
+
+            box LayerBuffer {
+                native_surface: native_surface,
+                rect: tile.page_rect,
+                screen_pos: tile.screen_rect,
+                resolution: scale,
+                stride: (width * 4) as uint
+            }
+        }
+    }
+}
+
+/// Messages to a render worker.
+enum WorkerMsg {
+    /// Renders a tile of a layer with the given display list and origin at the given scale. The
+    /// index identifies the tile in the reply.
+    RenderTileMsg(uint, Arc<IndexedDisplayList>, Point2D<uint>, BufferRequest, f32),
+    ExitWorkerMsg,
+}
+
+/// Messages from the render workers to the pool.
+enum WorkerReply {
+    /// The pixels of the tile with the given index.
+    TileRenderedReply(uint, Vec<u8>),
+    /// The worker with the given index failed. It will not render the tiles it was sent.
+    WorkerFailedReply(uint),
+}
+
+/// A pool of tasks that rasterize the tiles of a layer in parallel in CPU painting mode. Each
+/// worker has its own font context and draws into draw targets of its own; the render task
+/// uploads the pixels they send back.
+struct RenderWorkerPool {
+    /// The channels to the workers, or `None` for the workers that failed.
+    workers: Vec<Option<Sender<WorkerMsg>>>,
+    /// The port on which the workers send back their tiles, and their watchers any failure.
+    port: Receiver<WorkerReply>,
+}
+
+impl RenderWorkerPool {
+    fn new(thread_count: uint, opts: &Opts, profiler_chan: ProfilerChan) -> RenderWorkerPool {
+        let (result_chan, result_port) = channel();
+        let workers = range(0, thread_count).map(|worker_index| {
+            let (worker_chan, worker_port) = channel();
+            let result_chan = result_chan.clone();
+            let opts = opts.clone();
+            let profiler_chan = profiler_chan.clone();
+            let mut builder = TaskBuilder::new().named("RenderWorker");
+            send_on_failure(&mut builder, WorkerFailedReply(worker_index), result_chan.clone());
+            builder.spawn(proc() {
+                let mut font_ctx = box FontContext::new(FontContextInfo {
+                    backend: opts.render_backend.clone(),
+                    needs_font_list: false,
+                    profiler_chan: profiler_chan,
+                });
+                loop {
+                    match worker_port.recv() {
+                        RenderTileMsg(index, display_list, layer_origin, tile, scale) => {
+                            let pixels = render_tile_to_pixels(&mut font_ctx,
+                                                               &opts,
+                                                               &*display_list,
+                                                               layer_origin,
+                                                               &tile,
+                                                               scale);
+                            result_chan.send(TileRenderedReply(index, pixels))
+                        }
+                        ExitWorkerMsg => break,
+                    }
+                }
+            });
+            Some(worker_chan)
+        }).collect();
+
+        RenderWorkerPool {
+            workers: workers,
+            port: result_port,
+        }
+    }
+
+    /// Renders the given tiles of a layer and returns their pixels, in the order of the tiles.
+    /// The tiles that were sent to workers that failed are rendered with the given font context
+    /// on the calling task instead.
+    fn render(&mut self,
+              font_ctx: &mut Box<FontContext>,
+              opts: &Opts,
+              display_list: &Arc<IndexedDisplayList>,
+              layer_origin: Point2D<uint>,
+              tiles: &[BufferRequest],
+              scale: f32)
+              -> Vec<Vec<u8>> {
+        // The index of the worker that renders each tile, if any.
+        let mut assignments = Vec::from_elem(tiles.len(), None);
+        let mut pending_count = 0;
+        let live_workers: Vec<uint> = range(0, self.workers.len()).filter(|&worker_index| {
+            self.workers.get(worker_index).is_some()
+        }).collect();
+        if !live_workers.is_empty() {
+            for (index, tile) in tiles.iter().enumerate() {
+                let worker_index = *live_workers.get(index % live_workers.len());
+                let sent = match *self.workers.get(worker_index) {
+                    None => false,
+                    Some(ref worker) => {
+                        worker.send_opt(RenderTileMsg(index,
+                                                      display_list.clone(),
+                                                      layer_origin,
+                                                      tile.clone(),
+                                                      scale)).is_ok()
+                    }
+                };
+                if sent {
+                    *assignments.get_mut(index) = Some(worker_index);
+                    pending_count += 1;
+                } else {
+                    *self.workers.get_mut(worker_index) = None;
+                }
+            }
+        }
+
+        // Every worker either sends back its tiles or fails, and then its watcher tells us.
+        let mut all_pixels = Vec::from_fn(tiles.len(), |_| None);
+        while pending_count > 0 {
+            match self.port.recv() {
+                TileRenderedReply(index, pixels) => {
+                    *all_pixels.get_mut(index) = Some(pixels);
+                    *assignments.get_mut(index) = None;
+                    pending_count -= 1;
+                }
+                WorkerFailedReply(failed_worker_index) => {
+                    debug!("render worker {:u} failed", failed_worker_index);
+                    *self.workers.get_mut(failed_worker_index) = None;
+                    for assignment in assignments.mut_iter() {
+                        if *assignment == Some(failed_worker_index) {
+                            *assignment = None;
+                            pending_count -= 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        all_pixels.move_iter().zip(tiles.iter()).map(|(pixels, tile)| {
+            match pixels {
+                Some(pixels) => pixels,
+                None => {
+                    render_tile_to_pixels(font_ctx,
+                                          opts,
+                                          &**display_list,
+                                          layer_origin,
+                                          tile,
+                                          scale)
+                }
+            }
+        }).collect()
+    }
+}
+
+impl Drop for RenderWorkerPool {
+    fn drop(&mut self) {
+        for worker in self.workers.iter() {
+            match *worker {
+                Some(ref worker) => {
+                    worker.send_opt(ExitWorkerMsg);
+                }
+                None => {}
+            }
+        }
+    }
+}
@@ -283,8 +283,9 @@ impl IOCompositor {
                                                                          scroll_behavior);
                 }
 
-                (Ok(SetLayerPageSize(pipeline_id, layer_id, new_size, epoch)), false) => {
-                    self.set_layer_page_size(pipeline_id, layer_id, new_size, epoch);
+                (Ok(SetLayerPageSize(pipeline_id, layer_id, new_size, epoch, dirty_rects)),
+                 false) => {
+                    self.set_layer_page_size(pipeline_id, layer_id, new_size, epoch, dirty_rects);
                 }
 
                 (Ok(SetLayerClipRect(pipeline_id, layer_id, new_rect)), false) => {
@@ -299,6 +300,12 @@ impl IOCompositor {
                     self.paint(pipeline_id, layer_id, new_layer_buffer_set, epoch);
                 }
 
+                (Ok(PaintPixels(..)), false) => {
+                    // Renderers only send plain pixels to compositors that have no graphics
+                    // metadata to give them, which this one always has.
+                    debug!("compositor: ignoring tiles rendered without native surfaces");
+                }
+
                 (Ok(ScrollFragmentPoint(pipeline_id, layer_id, point)), false) => {
                     self.scroll_fragment_to_point(pipeline_id, layer_id, point);
                 }
@@ -428,14 +435,15 @@ impl IOCompositor {
                            pipeline_id: PipelineId,
                            layer_id: LayerId,
                            new_size: Size2D<f32>,
-                           epoch: Epoch) {
+                           epoch: Epoch,
+                           dirty_rects: Option<Vec<Rect<f32>>>) {
         let (ask, move): (bool, bool) = match self.compositor_layer {
             Some(ref mut layer) => {
                 let window_size = &self.window_size;
                 let world_zoom = self.world_zoom;
                 let page_window = Size2D(window_size.width as f32 / world_zoom,
                                          window_size.height as f32 / world_zoom);
-                layer.resize(pipeline_id, layer_id, new_size, page_window, epoch);
+                layer.resize(pipeline_id, layer_id, new_size, page_window, epoch, &dirty_rects);
                 let move = self.fragment_point.take().map_or(false, |point| {
                     layer.move(pipeline_id, layer_id, point, page_window)
                 });
@@ -2,7 +2,7 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use compositing::quadtree::{Quadtree, Normal, Hidden};
+use compositing::quadtree::{Quadtree, Normal, Hidden, Invalid};
 use pipeline::CompositionPipeline;
 use windowing::{MouseWindowEvent, MouseWindowClickEvent, MouseWindowMouseDownEvent};
 use windowing::{MouseWindowMouseUpEvent};
@@ -18,10 +18,10 @@ use layers::layers::{ContainerLayerKind, ContainerLayer, Flip, NoFlip, TextureLa
 use layers::layers::TextureLayerKind;
 use layers::platform::surface::{NativeCompositingGraphicsContext, NativeSurfaceMethods};
 use layers::texturegl::{Texture, TextureTarget};
-use script::dom::event::{ClickEvent, MouseDownEvent, MouseMoveEvent, MouseUpEvent};
+use script::dom::event::{ClickEvent, MouseDownEvent, MouseMoveEvent, MouseUpEvent, ScrollEvent};
 use script::script_task::{ScriptChan, SendEventMsg};
 use servo_msg::compositor_msg::{Epoch, FixedPosition, LayerBuffer, LayerBufferSet, LayerId};
-use servo_msg::compositor_msg::{ScrollPolicy, Tile};
+use servo_msg::compositor_msg::{OverflowScroll, ScrollPolicy, Tile};
 use servo_msg::constellation_msg::PipelineId;
 use std::rc::Rc;
 
@@ -134,6 +134,13 @@ fn create_container_layer_from_rect(rect: Rect<f32>) -> Rc<ContainerLayer> {
     container
 }
 
+/// Moves the given container layer to the given rect in its parent and clips it to that rect.
+fn set_container_rect(container: &Rc<ContainerLayer>, rect: Rect<f32>) {
+    container.common.borrow_mut().set_transform(
+        identity().translate(rect.origin.x, rect.origin.y, 0.0));
+    *container.scissor.borrow_mut() = Some(rect);
+}
+
 trait Clampable {
     fn clamp(&self, mn: &Self, mx: &Self) -> Self;
 }
@@ -253,13 +260,19 @@ impl CompositorLayer {
             return true
         }
 
+        // Layers holding the contents of scrollable boxes scroll on their own.
+        let wants_scroll_events = if scroll_policy == OverflowScroll {
+            WantsScrollEvents
+        } else {
+            DoesntWantScrollEvents
+        };
         let mut kid = box CompositorLayer::new(self.pipeline.clone(),
                                                child_layer_id,
                                                rect,
                                                Some(page_size),
                                                self.quadtree.tile_size(),
                                                self.cpu_painting,
-                                               DoesntWantScrollEvents,
+                                               wants_scroll_events,
                                                scroll_policy);
 
         kid.hidden = false;
@@ -300,10 +313,17 @@ impl CompositorLayer {
             return false
         }
 
-        // Allow children to scroll.
+        // Allow children to scroll, topmost first. Layers that scroll their own contents are
+        // hit tested against their bounds in page coordinates, since their containers move with
+        // this layer.
         let cursor = cursor - self.scroll_offset;
-        for child in self.children.mut_iter() {
-            match *child.container.scissor.borrow() {
+        for child in self.children.mut_iter().rev() {
+            let scissor = if child.child.scroll_policy == OverflowScroll {
+                Some(child.child.bounds)
+            } else {
+                *child.container.scissor.borrow()
+            };
+            match scissor {
                 None => {
                     error!("CompositorLayer: unable to perform cursor hit test for layer");
                 }
@@ -338,9 +358,19 @@ impl CompositorLayer {
             return false
         }
 
+        self.send_scroll_event();
         self.scroll(self.scroll_offset)
     }
 
+    /// Tells script about the new scroll offset of this layer, so that `scrollTop` and
+    /// `scrollLeft` of the scrollable box it belongs to stay up to date and so that layout can
+    /// hit test the page as it is scrolled.
+    fn send_scroll_event(&self) {
+        let message = ScrollEvent(self.id, Point2D(0f32, 0f32) - self.scroll_offset);
+        let ScriptChan(ref chan) = self.pipeline.script_chan;
+        chan.send_opt(SendEventMsg(self.pipeline.id.clone(), message));
+    }
+
     #[allow(dead_code)]
     fn dump_layer_tree(&self, layer: Rc<ContainerLayer>, indent: ~str) {
         println!("{}scissor {:?}", indent, layer.scissor.borrow());
@@ -373,7 +403,14 @@ impl CompositorLayer {
         }
 
         for kid_holder in self.children.mut_iter() {
-            result = kid_holder.child.scroll(scroll_offset) || result;
+            if kid_holder.child.scroll_policy == OverflowScroll {
+                // Layers that scroll their own contents move along with this layer instead.
+                set_container_rect(&kid_holder.container,
+                                   kid_holder.child.bounds.translate(&scroll_offset));
+                result = true
+            } else {
+                result = kid_holder.child.scroll(scroll_offset) || result;
+            }
         }
 
         result
@@ -383,8 +420,12 @@ impl CompositorLayer {
     // sends the event off to the appropriate pipeline. NB: the cursor position is in
     // page coordinates.
     pub fn send_mouse_event(&self, event: MouseWindowEvent, cursor: Point2D<f32>) {
+        // Layers of this pipeline are hit tested by layout, which knows what is in them and how
+        // far they are scrolled, so only the layers of other pipelines are looked at here.
         let cursor = cursor - self.scroll_offset;
-        for child in self.children.iter().filter(|&x| !x.child.hidden) {
+        for child in self.children.iter().filter(|&x| {
+                !x.child.hidden && x.child.pipeline.id != self.pipeline.id
+            }) {
             match *child.container.scissor.borrow() {
                 None => {
                     error!("CompositorLayer: unable to perform cursor hit test for layer");
@@ -452,16 +493,27 @@ impl CompositorLayer {
         let transform = |x: &mut CompositorLayerChild| -> bool {
             match *x.container.scissor.borrow() {
                 Some(scissor) => {
-                    let mut new_rect = window_rect;
-                    new_rect.origin.x = new_rect.origin.x - x.child.scroll_offset.x;
-                    new_rect.origin.y = new_rect.origin.y - x.child.scroll_offset.y;
-                    match new_rect.intersection(&scissor) {
-                        Some(new_rect) => {
+                    let child_rect = if x.child.scroll_policy == OverflowScroll {
+                        // The container of a layer that scrolls its own contents has already
+                        // been moved along with this layer, so only the visible part of the
+                        // container needs to be offset by the scroll offset of the child.
+                        window_rect.intersection(&scissor).map(|new_rect| {
+                            Rect(new_rect.origin.sub(&scissor.origin) - x.child.scroll_offset,
+                                 new_rect.size)
+                        })
+                    } else {
+                        let mut new_rect = window_rect;
+                        new_rect.origin.x = new_rect.origin.x - x.child.scroll_offset.x;
+                        new_rect.origin.y = new_rect.origin.y - x.child.scroll_offset.y;
+                        new_rect.intersection(&scissor).map(|new_rect| {
                             // Child layers act as if they are rendered at (0,0), so we
                             // subtract the layer's (x,y) coords in its containing page
                             // to make the child_rect appear in coordinates local to it.
-                            let child_rect = Rect(new_rect.origin.sub(&scissor.origin),
-                                                  new_rect.size);
+                            Rect(new_rect.origin.sub(&scissor.origin), new_rect.size)
+                        })
+                    };
+                    match child_rect {
+                        Some(child_rect) => {
                             x.child.get_buffer_request(graphics_context, child_rect, scale)
                         }
                         None => {
@@ -494,11 +546,16 @@ impl CompositorLayer {
             }) {
             Some(i) => {
                 debug!("compositor_layer: node found for set_clipping_rect()");
+                let scroll_offset = self.scroll_offset;
                 let child_node = self.children.get_mut(i);
-                child_node.container.common.borrow_mut().set_transform(
-                    identity().translate(new_rect.origin.x, new_rect.origin.y, 0.0));
                 let old_rect = child_node.container.scissor.borrow().clone();
-                *child_node.container.scissor.borrow_mut() = Some(new_rect);
+                child_node.child.bounds = new_rect;
+                if child_node.child.scroll_policy == OverflowScroll {
+                    // Keep the container where this layer has scrolled it to.
+                    set_container_rect(&child_node.container, new_rect.translate(&scroll_offset));
+                } else {
+                    set_container_rect(&child_node.container, new_rect);
+                }
                 match self.quadtree {
                     NoTree(..) => {} // Nothing to do
                         Tree(ref mut quadtree) => {
@@ -536,30 +593,16 @@ impl CompositorLayer {
                   layer_id: LayerId,
                   new_size: Size2D<f32>,
                   window_size: Size2D<f32>,
-                  epoch: Epoch)
+                  epoch: Epoch,
+                  dirty_rects: &Option<Vec<Rect<f32>>>)
                   -> bool {
         debug!("compositor_layer: starting resize()");
         if self.pipeline.id != pipeline_id || self.id != layer_id {
-            return self.resize_helper(pipeline_id, layer_id, new_size, epoch)
+            return self.resize_helper(pipeline_id, layer_id, new_size, epoch, dirty_rects)
         }
 
         debug!("compositor_layer: layer found for resize()");
-        self.epoch = epoch;
-        self.page_size = Some(new_size);
-        match self.quadtree {
-            Tree(ref mut quadtree) => {
-                self.pipeline
-                    .render_chan
-                    .send_opt(UnusedBufferMsg(quadtree.resize(new_size.width as uint,
-                                                              new_size.height as uint)));
-            }
-            NoTree(tile_size, max_mem) => {
-                self.quadtree = Tree(Quadtree::new(Size2D(new_size.width as uint,
-                                                          new_size.height as uint),
-                                                   tile_size,
-                                                   max_mem))
-            }
-        }
+        self.set_contents(new_size, epoch, dirty_rects);
         // Call scroll for bounds checking if the page shrunk. Use (-1, -1) as the cursor position
         // to make sure the scroll isn't propagated downwards.
         self.handle_scroll_event(Point2D(0f32, 0f32), Point2D(-1f32, -1f32), window_size);
@@ -585,6 +628,13 @@ impl CompositorLayer {
             return false
         }
 
+        // Layers that scroll their own contents show them through their bounds, not the window.
+        let window_size = if self.scroll_policy == OverflowScroll {
+            self.bounds.size
+        } else {
+            window_size
+        };
+
         // Scroll this layer!
         let old_origin = self.scroll_offset;
         self.scroll_offset = Point2D(0f32, 0f32) - origin;
@@ -604,6 +654,7 @@ impl CompositorLayer {
             return false;
         }
 
+        self.send_scroll_event();
         self.scroll(self.scroll_offset)
     }
 
@@ -640,7 +691,8 @@ impl CompositorLayer {
                      pipeline_id: PipelineId,
                      layer_id: LayerId,
                      new_size: Size2D<f32>,
-                     epoch: Epoch)
+                     epoch: Epoch,
+                     dirty_rects: &Option<Vec<Rect<f32>>>)
                      -> bool {
         debug!("compositor_layer: starting resize_helper()");
         let found = match self.children.iter().position(|kid_holder| {
@@ -651,20 +703,7 @@ impl CompositorLayer {
                 debug!("compositor_layer: layer found for resize_helper()");
                 let child_node = self.children.get_mut(i);
                 let child = &mut child_node.child;
-                child.epoch = epoch;
-                child.page_size = Some(new_size);
-                match child.quadtree {
-                    Tree(ref mut quadtree) => {
-                        child.pipeline.render_chan.send_opt(UnusedBufferMsg(quadtree.resize(new_size.width as uint,
-                                                                                            new_size.height as uint)));
-                    }
-                    NoTree(tile_size, max_mem) => {
-                        child.quadtree = Tree(Quadtree::new(Size2D(new_size.width as uint,
-                                                                   new_size.height as uint),
-                                                            tile_size,
-                                                            max_mem))
-                    }
-                }
+                child.set_contents(new_size, epoch, dirty_rects);
                 match *child_node.container.scissor.borrow() {
                     Some(scissor) => {
                         // Call scroll for bounds checking if the page shrunk. Use (-1, -1) as the
@@ -691,7 +730,44 @@ impl CompositorLayer {
         self.children
             .mut_iter()
             .map(|kid_holder| &mut kid_holder.child)
-            .any(|kid_holder| kid_holder.resize_helper(pipeline_id, layer_id, new_size, epoch))
+            .any(|kid_holder| {
+                kid_holder.resize_helper(pipeline_id, layer_id, new_size, epoch, dirty_rects)
+            })
+    }
+
+    /// Prepares the quadtree of this layer for the contents of a new epoch. If the size of the
+    /// layer is unchanged and the parts that changed are known, only the tiles touching those
+    /// parts are invalidated, and the old tiles stay on screen until their replacements arrive.
+    /// Otherwise all tiles are thrown away.
+    fn set_contents(&mut self,
+                    new_size: Size2D<f32>,
+                    epoch: Epoch,
+                    dirty_rects: &Option<Vec<Rect<f32>>>) {
+        let old_size = self.page_size;
+        self.epoch = epoch;
+        self.page_size = Some(new_size);
+        match self.quadtree {
+            Tree(ref mut quadtree) => {
+                match *dirty_rects {
+                    Some(ref dirty_rects) if old_size == Some(new_size) => {
+                        for dirty_rect in dirty_rects.iter() {
+                            quadtree.set_status_page(*dirty_rect, Invalid, true);
+                        }
+                    }
+                    _ => {
+                        let unused_buffers = quadtree.resize(new_size.width as uint,
+                                                             new_size.height as uint);
+                        self.pipeline.render_chan.send_opt(UnusedBufferMsg(unused_buffers));
+                    }
+                }
+            }
+            NoTree(tile_size, max_mem) => {
+                self.quadtree = Tree(Quadtree::new(Size2D(new_size.width as uint,
+                                                          new_size.height as uint),
+                                                   tile_size,
+                                                   max_mem))
+            }
+        }
     }
 
     // Collect buffers from the quadtree. This method IS NOT recursive, so child CompositorLayers
@@ -829,6 +905,16 @@ impl CompositorLayer {
                    self.epoch,
                    epoch,
                    self.pipeline.id);
+            // These tiles were rendered from old contents. Their nodes in the quadtree may have
+            // survived the new epoch, so ask for them again instead of waiting for them forever.
+            match self.quadtree {
+                Tree(ref mut quadtree) => {
+                    for buffer in new_buffers.buffers.iter() {
+                        quadtree.set_status_page(buffer.rect, Invalid, true);
+                    }
+                }
+                NoTree(..) => {}
+            }
             self.pipeline.render_chan.send_opt(UnusedBufferMsg(new_buffers.buffers));
             return None
         }
@@ -13,7 +13,8 @@ use geom::point::Point2D;
 use geom::rect::Rect;
 use geom::size::Size2D;
 use layers::platform::surface::{NativeCompositingGraphicsContext, NativeGraphicsMetadata};
-use servo_msg::compositor_msg::{Epoch, LayerBufferSet, LayerId, LayerMetadata, ReadyState};
+use servo_msg::compositor_msg::{CpuLayerBuffer, Epoch, LayerBufferSet, LayerId, LayerMetadata};
+use servo_msg::compositor_msg::{ReadyState};
 use servo_msg::compositor_msg::{RenderListener, RenderState, ScriptListener, ScrollPolicy};
 use servo_msg::constellation_msg::{ConstellationChan, PipelineId};
 use servo_util::opts::Opts;
@@ -79,20 +80,30 @@ impl RenderListener for CompositorChan {
         self.chan.send(Paint(pipeline_id, layer_id, layer_buffer_set, epoch))
     }
 
+    fn paint_pixels(&self,
+                    pipeline_id: PipelineId,
+                    layer_id: LayerId,
+                    buffers: Vec<CpuLayerBuffer>,
+                    epoch: Epoch) {
+        self.chan.send(PaintPixels(pipeline_id, layer_id, buffers, epoch))
+    }
+
     fn initialize_layers_for_pipeline(&self,
                                       pipeline_id: PipelineId,
                                       metadata: Vec<LayerMetadata>,
                                       epoch: Epoch) {
         // FIXME(#2004, pcwalton): This assumes that the first layer determines the page size, and
         // that all other layers are immediate children of it. This is sufficient to handle
-        // `position: fixed` but will not be sufficient to handle `overflow: scroll` or transforms.
+        // `position: fixed` and `overflow: scroll` boxes that are not nested in one another, but
+        // will not be sufficient to handle nested scrollable boxes or transforms.
         let mut first = true;
         for metadata in metadata.iter() {
-            let origin = Point2D(metadata.position.origin.x as f32,
-                                 metadata.position.origin.y as f32);
             let size = Size2D(metadata.position.size.width as f32,
                               metadata.position.size.height as f32);
-            let rect = Rect(origin, size);
+            let rect = Rect(Point2D(metadata.clip_rect.origin.x as f32,
+                                    metadata.clip_rect.origin.y as f32),
+                            Size2D(metadata.clip_rect.size.width as f32,
+                                   metadata.clip_rect.size.height as f32));
             if first {
                 self.chan.send(CreateRootCompositorLayerIfNecessary(pipeline_id,
                                                                     metadata.id,
@@ -109,7 +120,13 @@ impl RenderListener for CompositorChan {
             self.chan.send(SetUnRenderedColor(pipeline_id,
                                               metadata.id,
                                               metadata.background_color));
-            self.chan.send(SetLayerPageSize(pipeline_id, metadata.id, size, epoch));
+            let dirty_rects = metadata.dirty_rects.as_ref().map(|dirty_rects| {
+                dirty_rects.iter().map(|dirty_rect| {
+                    Rect(Point2D(dirty_rect.origin.x as f32, dirty_rect.origin.y as f32),
+                         Size2D(dirty_rect.size.width as f32, dirty_rect.size.height as f32))
+                }).collect()
+            });
+            self.chan.send(SetLayerPageSize(pipeline_id, metadata.id, size, epoch, dirty_rects));
             self.chan.send(SetLayerClipRect(pipeline_id, metadata.id, rect));
         }
     }
@@ -170,8 +187,9 @@ pub enum Msg {
     /// Tells the compositor to create a descendant layer for a pipeline if necessary (i.e. if no
     /// layer with that ID exists).
     CreateDescendantCompositorLayerIfNecessary(PipelineId, LayerId, Rect<f32>, ScrollPolicy),
-    /// Alerts the compositor that the specified layer has changed size.
-    SetLayerPageSize(PipelineId, LayerId, Size2D<f32>, Epoch),
+    /// Alerts the compositor that the specified layer has new contents of the given size. The
+    /// rects are the parts of the layer that changed, or `None` if all of it did.
+    SetLayerPageSize(PipelineId, LayerId, Size2D<f32>, Epoch, Option<Vec<Rect<f32>>>),
     /// Alerts the compositor that the specified layer's clipping rect has changed.
     SetLayerClipRect(PipelineId, LayerId, Rect<f32>),
     /// Alerts the compositor that the specified pipeline has been deleted.
@@ -180,6 +198,9 @@ pub enum Msg {
     ScrollFragmentPoint(PipelineId, LayerId, Point2D<f32>),
     /// Requests that the compositor paint the given layer buffer set for the given page size.
     Paint(PipelineId, LayerId, Box<LayerBufferSet>, Epoch),
+    /// Requests that the compositor paint the given tiles rendered on the CPU. Only the headless
+    /// compositor receives these.
+    PaintPixels(PipelineId, LayerId, Vec<CpuLayerBuffer>, Epoch),
     /// Alerts the compositor to the current status of page loading.
     ChangeReadyState(ReadyState),
     /// Alerts the compositor to the current status of rendering.
@@ -242,9 +263,10 @@ impl CompositorTask {
                                                  profiler_chan)
             }
             Headless => {
-                headless::NullCompositor::create(port,
-                                                 constellation_chan.clone(),
-                                                 profiler_chan)
+                headless::HeadlessCompositor::create(opts,
+                                                     port,
+                                                     constellation_chan.clone(),
+                                                     profiler_chan)
             }
         };
     }
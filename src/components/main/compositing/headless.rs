@@ -3,39 +3,120 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use compositing::*;
+use pipeline::CompositionPipeline;
 
+use azure::azure_hl::Color;
+use geom::point::Point2D;
+use geom::rect::Rect;
 use geom::size::Size2D;
-use servo_msg::constellation_msg::{ConstellationChan, ExitMsg, ResizedWindowMsg};
+use gfx::render_task::{BufferRequest, ReRenderMsg};
+use gfx::render_task;
+use png;
+use servo_msg::compositor_msg::{Blank, CpuLayerBuffer, Epoch, FinishedLoading, LayerId};
+use servo_msg::compositor_msg::{ReadyState};
+use servo_msg::constellation_msg::{ConstellationChan, ExitMsg, PipelineId, ResizedWindowMsg};
+use servo_util::opts::Opts;
 use servo_util::time::ProfilerChan;
 use servo_util::time;
+use std::cmp;
+use std::iter::range_step;
+use std::path::Path;
+
+/// A layer of the root pipeline, as the headless compositor keeps it.
+struct HeadlessLayer {
+    /// The ID of the layer.
+    id: LayerId,
+    /// The part of the page that the layer shows, in pixels. The contents of the layer start at
+    /// the origin of this rect and are clipped to it.
+    clip_rect: Rect<f32>,
+    /// The size of the contents of the layer.
+    page_size: Size2D<f32>,
+    /// The color that the layer is filled with under its contents.
+    background_color: Color,
+    /// The epoch of the contents of the layer.
+    epoch: Epoch,
+    /// The tiles of the contents for the current epoch, or `None` if they have not arrived yet.
+    tiles: Option<Vec<CpuLayerBuffer>>,
+}
+
+impl HeadlessLayer {
+    fn new(id: LayerId, clip_rect: Rect<f32>) -> HeadlessLayer {
+        HeadlessLayer {
+            id: id,
+            clip_rect: clip_rect,
+            page_size: clip_rect.size,
+            background_color: Color {
+                r: 0.0,
+                g: 0.0,
+                b: 0.0,
+                a: 0.0,
+            },
+            epoch: Epoch(0),
+            tiles: None,
+        }
+    }
+}
 
 /// Starts the compositor, which listens for messages on the specified port.
 ///
-/// This is the null compositor which doesn't draw anything to the screen.
-/// It's intended for headless testing.
-pub struct NullCompositor {
+/// This is the compositor for headless mode, which has no window or graphics context. It draws
+/// nothing to the screen; when an output file is given, it asks for the layers of the page to be
+/// rendered on the CPU, composites them in memory, and writes the whole page to the file once it
+/// has loaded. It's intended for testing and for taking screenshots on machines without a GPU or
+/// display.
+pub struct HeadlessCompositor {
     /// The port on which we receive messages.
     pub port: Receiver<Msg>,
+
+    /// The command line options.
+    opts: Opts,
+
+    /// The channel on which messages can be sent to the constellation.
+    constellation_chan: ConstellationChan,
+
+    /// The pipeline of the page, whose render task renders the tiles.
+    root_pipeline: Option<CompositionPipeline>,
+
+    /// The layers of the page, the root layer first.
+    layers: Vec<HeadlessLayer>,
+
+    /// The current loading state of the page.
+    ready_state: ReadyState,
+
+    /// Whether the page has finished loading.
+    load_complete: bool,
+
+    /// Whether we have asked the constellation to shut down.
+    shutting_down: bool,
 }
 
-impl NullCompositor {
-    fn new(port: Receiver<Msg>) -> NullCompositor {
-        NullCompositor {
+impl HeadlessCompositor {
+    fn new(opts: Opts, port: Receiver<Msg>, constellation_chan: ConstellationChan)
+           -> HeadlessCompositor {
+        HeadlessCompositor {
             port: port,
+            opts: opts,
+            constellation_chan: constellation_chan,
+            root_pipeline: None,
+            layers: vec!(),
+            ready_state: Blank,
+            load_complete: false,
+            shutting_down: false,
         }
     }
 
-    pub fn create(port: Receiver<Msg>,
+    pub fn create(opts: Opts,
+                  port: Receiver<Msg>,
                   constellation_chan: ConstellationChan,
                   profiler_chan: ProfilerChan) {
-        let compositor = NullCompositor::new(port);
+        let mut compositor = HeadlessCompositor::new(opts, port, constellation_chan.clone());
 
         // Tell the constellation about the initial fake size.
         {
             let ConstellationChan(ref chan) = constellation_chan;
             chan.send(ResizedWindowMsg(Size2D(640u, 480u)));
         }
-        compositor.handle_message(constellation_chan);
+        compositor.handle_message();
 
         // Drain compositor port, sometimes messages contain channels that are blocking
         // another task from finishing (i.e. SetIds)
@@ -49,14 +130,15 @@ impl NullCompositor {
         profiler_chan.send(time::ExitMsg);
     }
 
-    fn handle_message(&self, constellation_chan: ConstellationChan) {
+    fn handle_message(&mut self) {
         loop {
             match self.port.recv() {
                 Exit(chan) => {
                     debug!("shutting down the constellation");
-                    let ConstellationChan(ref con_chan) = constellation_chan;
+                    let ConstellationChan(ref con_chan) = self.constellation_chan;
                     con_chan.send(ExitMsg);
                     chan.send(());
+                    self.shutting_down = true;
                 }
 
                 ShutdownComplete => {
@@ -68,20 +150,345 @@ impl NullCompositor {
                     chan.send(None);
                 }
 
-                SetIds(_, response_chan, _) => {
+                SetIds(frame_tree, response_chan, _) => {
+                    self.root_pipeline = Some(frame_tree.pipeline.clone());
                     response_chan.send(());
                 }
 
+                CreateRootCompositorLayerIfNecessary(pipeline_id, layer_id, size) => {
+                    if self.is_root_pipeline(pipeline_id) &&
+                            self.layers.as_slice().head().map(|root| root.id) != Some(layer_id) {
+                        let clip_rect = Rect(Point2D(0f32, 0f32), size);
+                        self.layers = vec!(HeadlessLayer::new(layer_id, clip_rect));
+                    }
+                }
+
+                CreateDescendantCompositorLayerIfNecessary(pipeline_id, layer_id, rect, _) => {
+                    if self.is_root_pipeline(pipeline_id) &&
+                            !self.layers.is_empty() &&
+                            self.find_layer(pipeline_id, layer_id).is_none() {
+                        self.layers.push(HeadlessLayer::new(layer_id, rect))
+                    }
+                }
+
+                SetLayerPageSize(pipeline_id, layer_id, new_size, epoch, _) => {
+                    if self.is_root_pipeline(pipeline_id) {
+                        drop_old_layer_tree(&mut self.layers, layer_id, epoch)
+                    }
+                    let write_output = self.opts.output_file.is_some();
+                    let root_pipeline = self.root_pipeline.clone();
+                    let tile_size = self.opts.tile_size;
+                    match self.find_layer(pipeline_id, layer_id) {
+                        Some(layer) => {
+                            layer.page_size = new_size;
+                            layer.epoch = epoch;
+                            layer.tiles = None;
+                            if write_output {
+                                request_tiles(root_pipeline.get_ref(), layer, tile_size)
+                            }
+                        }
+                        None => {}
+                    }
+                }
+
+                SetLayerClipRect(pipeline_id, layer_id, new_rect) => {
+                    match self.find_layer(pipeline_id, layer_id) {
+                        Some(layer) => layer.clip_rect = new_rect,
+                        None => {}
+                    }
+                }
+
+                SetUnRenderedColor(pipeline_id, layer_id, color) => {
+                    match self.find_layer(pipeline_id, layer_id) {
+                        Some(layer) => layer.background_color = color,
+                        None => {}
+                    }
+                }
+
+                PaintPixels(pipeline_id, layer_id, buffers, epoch) => {
+                    match self.find_layer(pipeline_id, layer_id) {
+                        Some(layer) if layer.epoch == epoch => layer.tiles = Some(buffers),
+                        _ => {}
+                    }
+                }
+
+                ChangeReadyState(ready_state) => {
+                    self.ready_state = ready_state;
+                }
+
+                LoadComplete(..) => {
+                    self.load_complete = true;
+                }
+
                 // Explicitly list ignored messages so that when we add a new one,
                 // we'll notice and think about whether it needs a response, like
-                // SetIds.
+                // SetIds. Renderers never send `Paint`, since we give them no graphics metadata
+                // to create native surfaces with.
+
+                DeleteLayerGroup(..) | Paint(..) | ChangeRenderState(..) |
+                ScrollFragmentPoint(..) => ()
+            }
+
+            self.write_output_file_if_ready();
+        }
+    }
+
+    fn is_root_pipeline(&self, pipeline_id: PipelineId) -> bool {
+        match self.root_pipeline {
+            Some(ref root_pipeline) => root_pipeline.id == pipeline_id,
+            None => false,
+        }
+    }
+
+    fn find_layer<'a>(&'a mut self, pipeline_id: PipelineId, layer_id: LayerId)
+                      -> Option<&'a mut HeadlessLayer> {
+        if !self.is_root_pipeline(pipeline_id) {
+            return None
+        }
+        self.layers.mut_iter().find(|layer| layer.id == layer_id)
+    }
+
+    /// Once the page has loaded and all of its layers have been rendered, composites them,
+    /// writes the result to the output file, and shuts down.
+    fn write_output_file_if_ready(&mut self) {
+        if self.shutting_down || !self.load_complete || self.ready_state != FinishedLoading ||
+                self.layers.is_empty() || self.layers.iter().any(|layer| layer.tiles.is_none()) {
+            return
+        }
+        let path = match self.opts.output_file {
+            None => return,
+            Some(ref output_file) => Path::new(output_file.as_slice()),
+        };
+
+        let root_size = self.layers.get(0).page_size;
+        let (width, height) = (root_size.width.ceil() as uint, root_size.height.ceil() as uint);
+        let mut pixels = Vec::from_elem(width * height * 4, 0u8);
+        for layer in self.layers.iter() {
+            composite_layer(pixels.as_mut_slice(), width, height, layer)
+        }
+        render_task::premultiplied_bgra_to_rgba(pixels.as_mut_slice());
+
+        let image = png::Image {
+            width: width as u32,
+            height: height as u32,
+            color_type: png::RGBA8,
+            pixels: pixels,
+        };
+        match png::store_png(&image, &path) {
+            Ok(()) => {}
+            Err(error) => fail!("failed to write {}: {}", path.display(), error),
+        }
 
-                CreateRootCompositorLayerIfNecessary(..) |
-                CreateDescendantCompositorLayerIfNecessary(..) | SetLayerPageSize(..) |
-                SetLayerClipRect(..) | DeleteLayerGroup(..) | Paint(..) |
-                ChangeReadyState(..) | ChangeRenderState(..) | ScrollFragmentPoint(..) |
-                SetUnRenderedColor(..) | LoadComplete(..) => ()
+        debug!("shutting down the constellation after generating an output file");
+        let ConstellationChan(ref chan) = self.constellation_chan;
+        chan.send(ExitMsg);
+        self.shutting_down = true;
+    }
+}
+
+/// Drops the layers other than the root layer when the root layer gets a new epoch. The render
+/// task sends the whole layer tree for each epoch, root layer first, so the other layers of the
+/// new tree are created again by the messages that follow, and the layers that are no longer in
+/// the tree are not composited.
+fn drop_old_layer_tree(layers: &mut Vec<HeadlessLayer>, layer_id: LayerId, epoch: Epoch) {
+    let is_new_tree = match layers.as_slice().head() {
+        Some(root) => root.id == layer_id && root.epoch != epoch,
+        None => false,
+    };
+    if is_new_tree {
+        layers.truncate(1)
+    }
+}
+
+/// Asks the render task to render all of the given layer, in tiles no bigger than the tile size.
+fn request_tiles(pipeline: &CompositionPipeline, layer: &HeadlessLayer, tile_size: uint) {
+    let width = layer.page_size.width.ceil() as uint;
+    let height = layer.page_size.height.ceil() as uint;
+    let mut tiles = vec!();
+    for y in range_step(0, height, tile_size) {
+        for x in range_step(0, width, tile_size) {
+            let size = Size2D(cmp::min(tile_size, width - x), cmp::min(tile_size, height - y));
+            tiles.push(BufferRequest(Rect(Point2D(x, y), size),
+                                     Rect(Point2D(x as f32, y as f32),
+                                          Size2D(size.width as f32, size.height as f32))));
+        }
+    }
+    pipeline.render_chan.send_opt(ReRenderMsg(tiles, 1.0, layer.id, layer.epoch));
+}
+
+/// Draws the background and tiles of a layer over the given pixels, which are the size of the
+/// page, in BGRA order with premultiplied alpha.
+fn composite_layer(pixels: &mut [u8], width: uint, height: uint, layer: &HeadlessLayer) {
+    let left = cmp::max(layer.clip_rect.origin.x as int, 0);
+    let top = cmp::max(layer.clip_rect.origin.y as int, 0);
+    let right = cmp::min((layer.clip_rect.origin.x + layer.clip_rect.size.width) as int,
+                         width as int);
+    let bottom = cmp::min((layer.clip_rect.origin.y + layer.clip_rect.size.height) as int,
+                          height as int);
+
+    let color = &layer.background_color;
+    let background = [
+        (color.b * color.a * 255.0) as u8,
+        (color.g * color.a * 255.0) as u8,
+        (color.r * color.a * 255.0) as u8,
+        (color.a * 255.0) as u8,
+    ];
+    for y in range(top, bottom) {
+        for x in range(left, right) {
+            blend_pixel(pixels, ((y as uint) * width + (x as uint)) * 4, background.as_slice())
+        }
+    }
+
+    let origin = Point2D(layer.clip_rect.origin.x as int, layer.clip_rect.origin.y as int);
+    for tile in layer.tiles.get_ref().iter() {
+        let tile_width = tile.screen_pos.size.width;
+        for row in range(0, tile.screen_pos.size.height) {
+            let y = origin.y + (tile.screen_pos.origin.y + row) as int;
+            if y < top || y >= bottom {
+                continue
+            }
+            for column in range(0, tile_width) {
+                let x = origin.x + (tile.screen_pos.origin.x + column) as int;
+                if x < left || x >= right {
+                    continue
+                }
+                let source = (row * tile_width + column) * 4;
+                blend_pixel(pixels,
+                            ((y as uint) * width + (x as uint)) * 4,
+                            tile.pixels.slice(source, source + 4))
             }
         }
     }
 }
+
+/// Draws a pixel over the pixel at the given index, both with premultiplied alpha.
+fn blend_pixel(pixels: &mut [u8], index: uint, source: &[u8]) {
+    let inverse_alpha = 255 - source[3] as uint;
+    for channel in range(0u, 4) {
+        let value = source[channel] as uint + pixels[index + channel] as uint * inverse_alpha / 255;
+        pixels[index + channel] = cmp::min(value, 255) as u8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HeadlessLayer, blend_pixel, composite_layer, drop_old_layer_tree};
+
+    use azure::azure_hl::Color;
+    use geom::point::Point2D;
+    use geom::rect::Rect;
+    use geom::size::Size2D;
+    use gfx::render_task::premultiplied_bgra_to_rgba;
+    use servo_msg::compositor_msg::{CpuLayerBuffer, Epoch, LayerId};
+
+    fn layer(id: uint, clip_rect: Rect<f32>, tiles: Vec<CpuLayerBuffer>) -> HeadlessLayer {
+        let mut layer = HeadlessLayer::new(LayerId(id, 0), clip_rect);
+        layer.tiles = Some(tiles);
+        layer
+    }
+
+    /// Returns a tile at the given place in its layer, filled with the given BGRA pixel.
+    fn tile(x: uint, y: uint, width: uint, height: uint, pixel: [u8, ..4]) -> CpuLayerBuffer {
+        let mut pixels = vec!();
+        for _ in range(0, width * height) {
+            pixels.push_all(pixel.as_slice())
+        }
+        CpuLayerBuffer {
+            rect: Rect(Point2D(x as f32, y as f32), Size2D(width as f32, height as f32)),
+            screen_pos: Rect(Point2D(x, y), Size2D(width, height)),
+            resolution: 1.0,
+            pixels: pixels,
+        }
+    }
+
+    fn pixel_at<'a>(pixels: &'a [u8], width: uint, x: uint, y: uint) -> &'a [u8] {
+        let index = (y * width + x) * 4;
+        pixels.slice(index, index + 4)
+    }
+
+    #[test]
+    fn test_blend_pixel() {
+        // An opaque pixel replaces what is under it, and a transparent one leaves it alone.
+        let mut pixels = [10u8, 20, 30, 255];
+        blend_pixel(pixels.as_mut_slice(), 0, [1u8, 2, 3, 255].as_slice());
+        assert_eq!(pixels.as_slice(), [1u8, 2, 3, 255].as_slice());
+        blend_pixel(pixels.as_mut_slice(), 0, [0u8, 0, 0, 0].as_slice());
+        assert_eq!(pixels.as_slice(), [1u8, 2, 3, 255].as_slice());
+
+        // Half-transparent blue over opaque red.
+        let mut pixels = [0u8, 0, 255, 255];
+        blend_pixel(pixels.as_mut_slice(), 0, [128u8, 0, 0, 128].as_slice());
+        assert_eq!(pixels.as_slice(), [128u8, 0, 127, 255].as_slice());
+
+        // Only the pixel at the given index is drawn over.
+        let mut pixels = [0u8, ..8];
+        blend_pixel(pixels.as_mut_slice(), 4, [9u8, 9, 9, 255].as_slice());
+        assert_eq!(pixels.as_slice(), [0u8, 0, 0, 0, 9, 9, 9, 255].as_slice());
+    }
+
+    #[test]
+    fn test_premultiplied_bgra_to_rgba() {
+        let mut pixels = [
+            255u8, 0, 0, 255,   // Opaque blue.
+            0, 64, 128, 128,    // Half-transparent orange.
+            10, 20, 30, 0,      // Transparent.
+        ];
+        premultiplied_bgra_to_rgba(pixels.as_mut_slice());
+        assert_eq!(pixels.as_slice(),
+                   [0u8, 0, 255, 255, 255, 127, 0, 128, 0, 0, 0, 0].as_slice());
+    }
+
+    #[test]
+    fn test_composite_layer_places_tiles() {
+        let (width, height) = (8u, 6u);
+        let mut pixels = Vec::from_elem(width * height * 4, 0u8);
+
+        // An opaque gray background with two tiles of a layer at (2, 1), clipped to 5x4 pixels.
+        let mut layer = layer(1,
+                              Rect(Point2D(2f32, 1f32), Size2D(5f32, 4f32)),
+                              vec!(tile(0, 0, 4, 2, [255, 0, 0, 255]),
+                                   tile(4, 0, 4, 2, [0, 255, 0, 255])));
+        layer.background_color = Color { r: 0.5, g: 0.5, b: 0.5, a: 1.0 };
+        composite_layer(pixels.as_mut_slice(), width, height, &layer);
+
+        let pixels = pixels.as_slice();
+        let (blue, green, gray) = ([255u8, 0, 0, 255], [0u8, 255, 0, 255], [127u8, 127, 127, 255]);
+        assert_eq!(pixel_at(pixels, width, 2, 1), blue.as_slice());
+        assert_eq!(pixel_at(pixels, width, 5, 2), blue.as_slice());
+        assert_eq!(pixel_at(pixels, width, 6, 1), green.as_slice());
+        // Below the tiles, the background shows through; outside the clip rect, nothing is drawn.
+        assert_eq!(pixel_at(pixels, width, 2, 3), gray.as_slice());
+        assert_eq!(pixel_at(pixels, width, 6, 4), gray.as_slice());
+        assert_eq!(pixel_at(pixels, width, 7, 1), [0u8, 0, 0, 0].as_slice());
+        assert_eq!(pixel_at(pixels, width, 1, 1), [0u8, 0, 0, 0].as_slice());
+        assert_eq!(pixel_at(pixels, width, 2, 5), [0u8, 0, 0, 0].as_slice());
+    }
+
+    #[test]
+    fn test_composite_layer_clips_to_the_page() {
+        let (width, height) = (4u, 4u);
+        let mut pixels = Vec::from_elem(width * height * 4, 0u8);
+        let layer = layer(1,
+                          Rect(Point2D(2f32, 2f32), Size2D(10f32, 10f32)),
+                          vec!(tile(0, 0, 10, 10, [1, 2, 3, 255])));
+        composite_layer(pixels.as_mut_slice(), width, height, &layer);
+        assert_eq!(pixel_at(pixels.as_slice(), width, 3, 3), [1u8, 2, 3, 255].as_slice());
+        assert_eq!(pixel_at(pixels.as_slice(), width, 1, 1), [0u8, 0, 0, 0].as_slice());
+    }
+
+    #[test]
+    fn test_drop_old_layer_tree() {
+        let rect = Rect(Point2D(0f32, 0f32), Size2D(10f32, 10f32));
+        let mut layers = vec!(layer(1, rect, vec!()), layer(2, rect, vec!()));
+        layers.get_mut(0).epoch = Epoch(1);
+
+        // Other layers and the current epoch of the root layer keep the tree.
+        drop_old_layer_tree(&mut layers, LayerId(2, 0), Epoch(2));
+        drop_old_layer_tree(&mut layers, LayerId(1, 0), Epoch(1));
+        assert_eq!(layers.len(), 2);
+
+        drop_old_layer_tree(&mut layers, LayerId(1, 0), Epoch(2));
+        assert_eq!(layers.len(), 1);
+        assert!(layers.get(0).id == LayerId(1, 0));
+    }
+}
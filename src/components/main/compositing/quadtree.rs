@@ -687,6 +687,43 @@ pub fn test_resize() {
     assert!(q.get_all_tiles().len() == 1);
 }
 
+#[test]
+pub fn test_invalidate() {
+    struct T {
+        a: int,
+    }
+
+    impl Tile for T {
+        fn get_mem(&self) -> uint {
+            1
+        }
+
+        fn is_valid(&self, _: f32) -> bool {
+            true
+        }
+        fn get_size_2d(&self) -> Size2D<uint> {
+            Size2D(0u, 0u)
+        }
+        fn mark_wont_leak(&mut self) {}
+        fn destroy(self, _: &NativePaintingGraphicsContext) {}
+    }
+
+    let mut q = Quadtree::new(Size2D(4u, 4), 2, None);
+    q.add_tile_pixel(0, 0, 1f32, T{a: 0});
+    q.add_tile_pixel(2, 0, 1f32, T{a: 1});
+    q.add_tile_pixel(0, 2, 1f32, T{a: 2});
+    q.add_tile_pixel(2, 2, 1f32, T{a: 3});
+    let (request, _) = q.get_tile_rects_pixel(Rect(Point2D(0, 0), Size2D(4, 4)), 1f32);
+    assert!(request.is_empty());
+
+    // Only the tile under the invalidated rect is requested again, and the old tile is kept
+    // until its replacement arrives.
+    q.set_status_page(Rect(Point2D(2.5f32, 0.5), Size2D(1f32, 1f32)), Invalid, true);
+    let (request, _) = q.get_tile_rects_pixel(Rect(Point2D(0, 0), Size2D(4, 4)), 1f32);
+    assert!(request.len() == 1);
+    assert!(q.get_all_tiles().len() == 4);
+}
+
 #[test]
 pub fn test() {
     struct T {
@@ -5,13 +5,16 @@
 // High-level interface to CSS selector matching.
 
 use css::node_style::StyledNode;
+use layout::animation;
 use layout::construct::FlowConstructor;
 use layout::context::LayoutContext;
 use layout::extra::LayoutAuxMethods;
-use layout::util::{LayoutDataAccess, LayoutDataWrapper};
+use layout::util::{LayoutDataAccess, LayoutDataWrapper, OpaqueNodeMethods};
 use layout::wrapper::{LayoutElement, LayoutNode, PostorderNodeMutTraversal, ThreadSafeLayoutNode};
 
+use gfx::display_list::OpaqueNode;
 use gfx::font_context::FontContext;
+use servo_util::bloom::BloomFilter;
 use servo_util::cache::{Cache, LRUCache, SimpleHashCache};
 use servo_util::namespace::Null;
 use servo_util::smallvec::{SmallVec, SmallVec0, SmallVec16};
@@ -19,13 +22,18 @@ use servo_util::str::DOMString;
 use std::cast;
 use std::hash::{Hash, sip};
 use std::slice::Items;
-use style::{After, Before, ComputedValues, MatchedProperty, Stylist, TElement, TNode, cascade};
+use style::{After, Before, ComputedValues, FirstLetter, FirstLine, MatchedProperty, Stylist};
+use style::{TElement, TNode, cascade, insert_element_into_bloom_filter};
+use style::{RestyleDescendants, RestyleHint, RestyleLaterSiblings, RestyleSelf};
+use style::{has_presentational_hints, remove_element_from_bloom_filter};
 use sync::Arc;
 
 pub struct ApplicableDeclarations {
     pub normal: SmallVec16<MatchedProperty>,
     pub before: SmallVec0<MatchedProperty>,
     pub after: SmallVec0<MatchedProperty>,
+    pub first_line: SmallVec0<MatchedProperty>,
+    pub first_letter: SmallVec0<MatchedProperty>,
 
     /// Whether the `normal` declarations are shareable with other nodes.
     pub normal_shareable: bool,
@@ -37,6 +45,8 @@ impl ApplicableDeclarations {
             normal: SmallVec16::new(),
             before: SmallVec0::new(),
             after: SmallVec0::new(),
+            first_line: SmallVec0::new(),
+            first_letter: SmallVec0::new(),
             normal_shareable: false,
         }
     }
@@ -45,6 +55,8 @@ impl ApplicableDeclarations {
         self.normal = SmallVec16::new();
         self.before = SmallVec0::new();
         self.after = SmallVec0::new();
+        self.first_line = SmallVec0::new();
+        self.first_letter = SmallVec0::new();
         self.normal_shareable = false;
     }
 }
@@ -221,6 +233,12 @@ impl StyleSharingCandidate {
             return None
         }
 
+        // A node that shared this style would neither start nor run its own transitions and
+        // animations.
+        if animation::has_transitions(&**style.get_ref()) {
+            return None
+        }
+
         Some(StyleSharingCandidate {
             style: style.take_unwrap(),
             parent_style: parent_style.take_unwrap(),
@@ -270,6 +288,41 @@ impl StyleSharingCandidateCache {
     }
 }
 
+/// A per-thread Bloom filter of the ancestors of the last node styled by a parallel style recalc
+/// worker. Workers usually style a child right after its parent, so keeping the filter around
+/// avoids rebuilding it from scratch for every node.
+pub struct StyleBloom {
+    /// The hashes of `node` and all of its ancestors.
+    pub filter: Box<BloomFilter>,
+    /// The node whose hashes were most recently inserted, if any.
+    pub node: Option<OpaqueNode>,
+    /// The reflow generation in which `filter` was built.
+    pub generation: uint,
+}
+
+impl StyleBloom {
+    pub fn new() -> StyleBloom {
+        StyleBloom {
+            filter: box BloomFilter::new(),
+            node: None,
+            generation: 0,
+        }
+    }
+}
+
+/// Inserts the hashes of all of the element ancestors of `node`, but not `node` itself, into the
+/// given Bloom filter.
+pub fn insert_ancestors_into_bloom_filter(node: &LayoutNode, bf: &mut BloomFilter) {
+    let mut current = node.parent_node();
+    while current.is_some() {
+        let ancestor = current.take_unwrap();
+        if ancestor.is_element() {
+            insert_element_into_bloom_filter(&ancestor, bf)
+        }
+        current = ancestor.parent_node()
+    }
+}
+
 /// The results of attempting to share a style.
 pub enum StyleSharingResult<'ln> {
     /// We didn't find anybody to share the style with. The boolean indicates whether the style
@@ -283,6 +336,9 @@ pub enum StyleSharingResult<'ln> {
 pub trait MatchMethods {
     /// Performs aux initialization, selector matching, cascading, and flow construction
     /// sequentially.
+    ///
+    /// `parent_bf` must contain the hashes of all of this node's ancestors; it is restored to
+    /// that state before returning.
     fn recalc_style_for_subtree(&self,
                                 stylist: &Stylist,
                                 layout_context: &mut LayoutContext,
@@ -290,11 +346,13 @@ pub trait MatchMethods {
                                 applicable_declarations: &mut ApplicableDeclarations,
                                 applicable_declarations_cache: &mut ApplicableDeclarationsCache,
                                 style_sharing_candidate_cache: &mut StyleSharingCandidateCache,
+                                parent_bf: &mut BloomFilter,
                                 parent: Option<LayoutNode>)
                                 -> Box<FontContext>;
 
     fn match_node(&self,
                   stylist: &Stylist,
+                  parent_bf: Option<&BloomFilter>,
                   applicable_declarations: &mut ApplicableDeclarations,
                   shareable: &mut bool);
 
@@ -310,7 +368,21 @@ pub trait MatchMethods {
     unsafe fn cascade_node(&self,
                            parent: Option<LayoutNode>,
                            applicable_declarations: &ApplicableDeclarations,
-                           applicable_declarations_cache: &mut ApplicableDeclarationsCache);
+                           applicable_declarations_cache: &mut ApplicableDeclarationsCache,
+                           layout_context: &LayoutContext);
+
+    /// Returns true if this node must be matched and cascaded in this reflow: because every node
+    /// must be, because it has never been styled, because a restyle hint flagged it, or because
+    /// a running transition changes its style on every frame. Clears the flag.
+    fn needs_restyle(&self, layout_context: &LayoutContext) -> bool;
+
+    /// Flags the nodes that may need restyling after an attribute of this element changed.
+    fn note_restyle_hint(&self, hint: RestyleHint);
+
+    /// Flags this node's children for restyling, since they inherit from its new style.
+    ///
+    /// FIXME(pcwalton): The children only need to be cascaded again, not matched.
+    fn set_children_need_restyle(&self);
 }
 
 trait PrivateMatchMethods {
@@ -326,6 +398,8 @@ trait PrivateMatchMethods {
                                               parent_node: Option<LayoutNode>,
                                               candidate: &StyleSharingCandidate)
                                               -> Option<Arc<ComputedValues>>;
+
+    fn set_needs_restyle(&self);
 }
 
 impl<'ln> PrivateMatchMethods for LayoutNode<'ln> {
@@ -406,30 +480,98 @@ impl<'ln> PrivateMatchMethods for LayoutNode<'ln> {
 
         None
     }
+
+    fn set_needs_restyle(&self) {
+        let mut layout_data_ref = self.mutate_layout_data();
+        match &mut *layout_data_ref {
+            // Nodes that have never been laid out will be styled anyway.
+            &None => {}
+            &Some(ref mut layout_data) => layout_data.data.needs_restyle = true,
+        }
+    }
 }
 
 impl<'ln> MatchMethods for LayoutNode<'ln> {
+    fn needs_restyle(&self, layout_context: &LayoutContext) -> bool {
+        let node = OpaqueNodeMethods::from_layout_node(self);
+        let mut layout_data_ref = self.mutate_layout_data();
+        match &mut *layout_data_ref {
+            &None => fail!("no layout data"),
+            &Some(ref mut layout_data) => {
+                let flagged = layout_data.data.needs_restyle;
+                layout_data.data.needs_restyle = false;
+                layout_context.restyle_all || flagged || layout_data.shared_data.style.is_none() ||
+                    layout_context.running_transitions.iter().any(|transition| {
+                        transition.node == node
+                    }) || layout_context.running_animations.iter().any(|animation| {
+                        animation.node == node && animation.needs_frames()
+                    })
+            }
+        }
+    }
+
+    fn note_restyle_hint(&self, hint: RestyleHint) {
+        if hint.has(RestyleSelf) {
+            self.set_needs_restyle()
+        }
+        if hint.has(RestyleDescendants) {
+            self.set_children_need_restyle()
+        }
+        if hint.has(RestyleLaterSiblings) {
+            let mut sibling = self.next_sibling();
+            while sibling.is_some() {
+                let node = sibling.take_unwrap();
+                node.set_needs_restyle();
+                sibling = node.next_sibling()
+            }
+        }
+    }
+
+    fn set_children_need_restyle(&self) {
+        for kid in self.children() {
+            kid.set_needs_restyle()
+        }
+    }
+
     fn match_node(&self,
                   stylist: &Stylist,
+                  parent_bf: Option<&BloomFilter>,
                   applicable_declarations: &mut ApplicableDeclarations,
                   shareable: &mut bool) {
         let style_attribute = self.as_element().style_attribute().as_ref();
 
         applicable_declarations.normal_shareable =
             stylist.push_applicable_declarations(self,
+                                                 parent_bf,
                                                  style_attribute,
                                                  None,
                                                  &mut applicable_declarations.normal);
         stylist.push_applicable_declarations(self,
+                                             parent_bf,
                                              None,
                                              Some(Before),
                                              &mut applicable_declarations.before);
         stylist.push_applicable_declarations(self,
+                                             parent_bf,
                                              None,
                                              Some(After),
                                              &mut applicable_declarations.after);
-
-        *shareable = applicable_declarations.normal_shareable
+        stylist.push_applicable_declarations(self,
+                                             parent_bf,
+                                             None,
+                                             Some(FirstLine),
+                                             &mut applicable_declarations.first_line);
+        stylist.push_applicable_declarations(self,
+                                             parent_bf,
+                                             None,
+                                             Some(FirstLetter),
+                                             &mut applicable_declarations.first_letter);
+
+        // Sharing only copies the element's own style, so elements with `::first-line` or
+        // `::first-letter` styles must not be used as style sharing candidates.
+        *shareable = applicable_declarations.normal_shareable &&
+            applicable_declarations.first_line.len() == 0 &&
+            applicable_declarations.first_letter.len() == 0
     }
 
     unsafe fn share_style_if_possible(&self,
@@ -442,7 +584,8 @@ impl<'ln> MatchMethods for LayoutNode<'ln> {
         }
         let ok = {
             let element = self.as_element();
-            element.style_attribute().is_none() && element.get_attr(&Null, "id").is_none()
+            element.style_attribute().is_none() && element.get_attr(&Null, "id").is_none() &&
+                !has_presentational_hints(&element)
         };
         if !ok {
             return CannotShare(false)
@@ -470,36 +613,50 @@ impl<'ln> MatchMethods for LayoutNode<'ln> {
                                 applicable_declarations: &mut ApplicableDeclarations,
                                 applicable_declarations_cache: &mut ApplicableDeclarationsCache,
                                 style_sharing_candidate_cache: &mut StyleSharingCandidateCache,
+                                parent_bf: &mut BloomFilter,
                                 parent: Option<LayoutNode>)
                                 -> Box<FontContext> {
         self.initialize_layout_data(layout_context.layout_chan.clone());
 
-        // First, check to see whether we can share a style with someone.
-        let sharing_result = unsafe {
-            self.share_style_if_possible(style_sharing_candidate_cache, parent.clone())
-        };
+        if self.needs_restyle(&*layout_context) {
+            // First, check to see whether we can share a style with someone.
+            let sharing_result = unsafe {
+                self.share_style_if_possible(style_sharing_candidate_cache, parent.clone())
+            };
 
-        // Otherwise, match and cascade selectors.
-        match sharing_result {
-            CannotShare(mut shareable) => {
-                if self.is_element() {
-                    self.match_node(stylist, applicable_declarations, &mut shareable)
-                }
+            // Otherwise, match and cascade selectors.
+            match sharing_result {
+                CannotShare(mut shareable) => {
+                    if self.is_element() {
+                        self.match_node(stylist,
+                                        Some(&*parent_bf),
+                                        applicable_declarations,
+                                        &mut shareable)
+                    }
 
-                unsafe {
-                    self.cascade_node(parent,
-                                      applicable_declarations,
-                                      applicable_declarations_cache)
-                }
+                    unsafe {
+                        self.cascade_node(parent,
+                                          applicable_declarations,
+                                          applicable_declarations_cache,
+                                          &*layout_context)
+                    }
 
-                applicable_declarations.clear();
+                    applicable_declarations.clear();
 
-                // Add ourselves to the LRU cache.
-                if shareable {
-                    style_sharing_candidate_cache.insert_if_possible(self)
+                    // Add ourselves to the LRU cache.
+                    if shareable {
+                        style_sharing_candidate_cache.insert_if_possible(self)
+                    }
                 }
+                StyleWasShared(index) => style_sharing_candidate_cache.touch(index),
             }
-            StyleWasShared(index) => style_sharing_candidate_cache.touch(index),
+
+            self.set_children_need_restyle()
+        }
+
+        // Add ourselves to the Bloom filter while our descendants are matched.
+        if self.is_element() {
+            insert_element_into_bloom_filter(self, parent_bf)
         }
 
         for kid in self.children() {
@@ -509,9 +666,14 @@ impl<'ln> MatchMethods for LayoutNode<'ln> {
                                                         applicable_declarations,
                                                         applicable_declarations_cache,
                                                         style_sharing_candidate_cache,
+                                                        parent_bf,
                                                         Some(self.clone()))
         }
 
+        if self.is_element() {
+            remove_element_from_bloom_filter(self, parent_bf)
+        }
+
         // Construct flows.
         let layout_node = ThreadSafeLayoutNode::new(self);
         let mut flow_constructor = FlowConstructor::new(layout_context, Some(font_context));
@@ -522,7 +684,8 @@ impl<'ln> MatchMethods for LayoutNode<'ln> {
     unsafe fn cascade_node(&self,
                            parent: Option<LayoutNode>,
                            applicable_declarations: &ApplicableDeclarations,
-                           applicable_declarations_cache: &mut ApplicableDeclarationsCache) {
+                           applicable_declarations_cache: &mut ApplicableDeclarationsCache,
+                           layout_context: &LayoutContext) {
         // Get our parent's style. This must be unsafe so that we don't touch the parent's
         // borrow flags.
         //
@@ -548,11 +711,37 @@ impl<'ln> MatchMethods for LayoutNode<'ln> {
         match &mut *layout_data_ref {
             &None => fail!("no layout data"),
             &Some(ref mut layout_data) => {
+                let old_style = layout_data.shared_data.style.clone();
                 self.cascade_node_pseudo_element(parent_style,
                                                  applicable_declarations.normal.as_slice(),
                                                  &mut layout_data.shared_data.style,
                                                  applicable_declarations_cache,
                                                  applicable_declarations.normal_shareable);
+
+                // Changes to the style of a node that has been styled before may start
+                // transitions, and the properties that are transitioning or animating take their
+                // current values rather than the ones that were just computed.
+                let node = OpaqueNodeMethods::from_layout_node(self);
+                let animations =
+                    animation::update_animations(node,
+                                                 old_style.as_ref().map(|style| &**style),
+                                                 parent_style.map(|style| &**style),
+                                                 &**layout_data.shared_data.style.get_ref(),
+                                                 layout_context);
+                match old_style {
+                    Some(ref old_style) => {
+                        animation::update_transitions(node,
+                                                      old_style,
+                                                      layout_data.shared_data.style.get_mut_ref(),
+                                                      animations.as_slice(),
+                                                      layout_context)
+                    }
+                    None => {}
+                }
+                animation::apply_animations(node,
+                                            animations,
+                                            layout_data.shared_data.style.get_mut_ref(),
+                                            layout_context);
                 if applicable_declarations.before.len() > 0 {
                     self.cascade_node_pseudo_element(parent_style,
                                                      applicable_declarations.before.as_slice(),
@@ -567,6 +756,31 @@ impl<'ln> MatchMethods for LayoutNode<'ln> {
                                                      applicable_declarations_cache,
                                                      false);
                 }
+
+                // `::first-line` and `::first-letter` inherit from the element itself, and
+                // `::first-letter` additionally from `::first-line` (CSS 2.1 § 5.12.3).
+                layout_data.data.first_line_style = None;
+                layout_data.data.first_letter_style = None;
+                let style = layout_data.shared_data.style.clone();
+                if applicable_declarations.first_line.len() > 0 {
+                    self.cascade_node_pseudo_element(style.as_ref(),
+                                                     applicable_declarations.first_line.as_slice(),
+                                                     &mut layout_data.data.first_line_style,
+                                                     applicable_declarations_cache,
+                                                     false);
+                }
+                if applicable_declarations.first_letter.len() > 0 {
+                    let parent_style = match layout_data.data.first_line_style {
+                        Some(ref first_line_style) => Some(first_line_style.clone()),
+                        None => style,
+                    };
+                    self.cascade_node_pseudo_element(parent_style.as_ref(),
+                                                     applicable_declarations.first_letter
+                                                                            .as_slice(),
+                                                     &mut layout_data.data.first_letter_style,
+                                                     applicable_declarations_cache,
+                                                     false);
+                }
             }
         }
     }
@@ -0,0 +1,680 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! CSS transitions and animations. See CSS Transitions § 3 and CSS Animations § 3.
+//!
+//! Transitions are started during the style recalc, when the cascade produces a new value for an
+//! animatable property that the new style transitions. Animations are started when a node's style
+//! first names a `@keyframes` rule in `animation-name`. The layout task keeps both between
+//! reflows and schedules a reflow for every frame while any of them runs, restyling only the nodes
+//! that they animate.
+
+use layout::context::LayoutContext;
+
+use gfx::display_list::OpaqueNode;
+use std::cast;
+use style::{ANIMATABLE_PROPERTIES, AnimatableProperty, ComputedValues, KeyframesRule};
+use style::{MatchedProperty, Stylist, cascade};
+use style::computed_values::TimingFunction;
+use style::computed_values::{animation_direction, animation_fill_mode};
+use style::computed_values::{animation_iteration_count, animation_play_state};
+use style::computed_values::transition_property::{TransitionAll, TransitionProperty};
+use style::computed_values::transition_property::{TransitionUnknown};
+use sync::Arc;
+
+/// The number of milliseconds between two frames of a running transition or animation.
+pub static FRAME_INTERVAL_MS: u64 = 16;
+
+/// A transition of one property of one node.
+#[deriving(Clone)]
+pub struct PropertyTransition {
+    /// The node whose style is transitioning.
+    pub node: OpaqueNode,
+    /// The transitioning property.
+    pub property: AnimatableProperty,
+    /// The style that the property transitions from.
+    pub from: Arc<ComputedValues>,
+    /// The style that the property transitions to.
+    pub to: Arc<ComputedValues>,
+    /// The time at which the transition starts, once its delay has elapsed, in seconds as
+    /// returned by `precise_time_s()`.
+    pub start_time: f64,
+    /// The duration of the transition, in seconds.
+    pub duration: f64,
+    /// The timing function of the transition.
+    pub timing_function: TimingFunction,
+}
+
+impl PropertyTransition {
+    /// Returns true if the transition has run to completion at the given time.
+    pub fn has_ended(&self, now: f64) -> bool {
+        now >= self.start_time + self.duration
+    }
+
+    /// Sets the transitioning property in `style` to its value at the given time.
+    pub fn apply(&self, now: f64, style: &mut ComputedValues) {
+        let progress = if now <= self.start_time {
+            0.
+        } else if self.has_ended(now) {
+            1.
+        } else {
+            (now - self.start_time) / self.duration
+        };
+        self.property.interpolate(&*self.from,
+                                  &*self.to,
+                                  self.timing_function.solve(progress),
+                                  style)
+    }
+}
+
+/// Returns the item of a list of transition values that applies to the transitioned property
+/// at the given index. Lists shorter than `transition-property` repeat (CSS Transitions § 2).
+fn cyclic_get<'a, T>(list: &'a Vec<T>, index: uint) -> &'a T {
+    list.get(index % list.len())
+}
+
+/// Updates the transitions of a node whose style has just been recomputed from `old_style` to
+/// `new_style`: transitions that still head for the value in the new style keep running, and
+/// transitions start for the changed properties that the new style transitions. The properties
+/// that are transitioning in `new_style` are then set to their current values. Properties that
+/// the given animations of the node animate do not transition (CSS Animations § 3).
+///
+/// All these transitions are reported to the layout task, which drops any transition that is not
+/// reported during a reflow.
+pub fn update_transitions(node: OpaqueNode,
+                          old_style: &Arc<ComputedValues>,
+                          new_style: &mut Arc<ComputedValues>,
+                          animations: &[KeyframesAnimation],
+                          layout_context: &LayoutContext) {
+    let target = new_style.clone();
+    let now = layout_context.animation_time;
+
+    let mut transitions: Vec<PropertyTransition> =
+        layout_context.running_transitions.iter().filter(|transition| {
+            transition.node == node && transition.property.has_same_value(&*transition.to,
+                                                                          &*target)
+        }).map(|transition| transition.clone()).collect();
+
+    // Later entries of `transition-property` override earlier ones for the same property.
+    let animation = target.get_animation();
+    let mut requested: Vec<(AnimatableProperty, uint)> = vec!();
+    for (index, transition_property) in animation.transition_property.iter().enumerate() {
+        let mut request = |property: AnimatableProperty| {
+            requested.retain(|&(other, _)| other != property);
+            requested.push((property, index))
+        };
+        match *transition_property {
+            TransitionAll => {
+                for property in ANIMATABLE_PROPERTIES.iter() {
+                    request(*property)
+                }
+            }
+            TransitionProperty(property) => request(property),
+            TransitionUnknown(_) => {}
+        }
+    }
+
+    requested.retain(|&(property, _)| {
+        !animations.iter().any(|animation| animation.properties.contains(&property))
+    });
+    transitions.retain(|transition| {
+        !animations.iter().any(|animation| animation.properties.contains(&transition.property))
+    });
+
+    for &(property, index) in requested.iter() {
+        if transitions.iter().any(|transition| transition.property == property) ||
+                !property.can_interpolate(&**old_style, &*target) {
+            continue
+        }
+        let duration = cyclic_get(&animation.transition_duration, index).seconds();
+        let delay = cyclic_get(&animation.transition_delay, index).seconds();
+        if duration + delay <= 0. {
+            continue
+        }
+        transitions.push(PropertyTransition {
+            node: node,
+            property: property,
+            from: old_style.clone(),
+            to: target.clone(),
+            start_time: now + delay,
+            duration: duration,
+            timing_function: cyclic_get(&animation.transition_timing_function, index).clone(),
+        })
+    }
+
+    if transitions.is_empty() {
+        return
+    }
+
+    let mut style = (**new_style).clone();
+    for transition in transitions.iter() {
+        transition.apply(now, &mut style)
+    }
+    *new_style = Arc::new(style);
+
+    for transition in transitions.move_iter() {
+        layout_context.transitions_chan.send(transition)
+    }
+}
+
+/// Returns true if the given style transitions or animates any property, in which case it must
+/// not be shared between nodes, since transitions and animations start and run separately for
+/// every node.
+pub fn has_transitions(style: &ComputedValues) -> bool {
+    let animation = style.get_animation();
+    let transitions = !animation.transition_property.is_empty() &&
+        (animation.transition_duration.iter().any(|duration| duration.seconds() > 0.) ||
+         animation.transition_delay.iter().any(|delay| delay.seconds() > 0.));
+    transitions || animation.animation_name.iter().any(|name| name.is_some())
+}
+
+/// A `@keyframes` animation of one node.
+#[deriving(Clone)]
+pub struct KeyframesAnimation {
+    /// The node that is animated.
+    pub node: OpaqueNode,
+    /// The name of the `@keyframes` rule that the animation runs.
+    pub name: ~str,
+    /// The time at which the first iteration starts, once the delay has elapsed, in seconds as
+    /// returned by `precise_time_s()`. Pausing the animation pushes it back.
+    pub start_time: f64,
+    /// The time at which the animation was paused, if it is paused.
+    pub paused_time: Option<f64>,
+    /// The duration of one iteration, in seconds.
+    pub duration: f64,
+    /// The number of iterations, or `None` if the animation repeats forever.
+    pub iteration_count: Option<f64>,
+    pub direction: animation_direction::SingleComputedValue,
+    pub fill_mode: animation_fill_mode::SingleComputedValue,
+    /// The timing function of the keyframes that do not set their own.
+    pub timing_function: TimingFunction,
+    /// The properties that the keyframes set.
+    pub properties: Vec<AnimatableProperty>,
+    /// The keyframes, cascaded once when the animation starts.
+    pub keyframes: Arc<Vec<ComputedKeyframe>>,
+    /// The last iteration whose start was reported to script, or `None` if `animationstart` has
+    /// not been fired yet.
+    pub reported_iteration: Option<u64>,
+    /// Whether `animationend` has been fired.
+    pub ended: bool,
+}
+
+impl KeyframesAnimation {
+    /// Returns the time elapsed since the start of the first iteration, not counting the time
+    /// spent paused.
+    pub fn elapsed_time(&self, now: f64) -> f64 {
+        self.paused_time.unwrap_or(now) - self.start_time
+    }
+
+    /// Returns the duration of all the iterations together, or `None` if the animation repeats
+    /// forever.
+    pub fn active_duration(&self) -> Option<f64> {
+        if self.duration <= 0. {
+            return Some(0.)
+        }
+        self.iteration_count.map(|count| count * self.duration)
+    }
+
+    /// Returns true if all the iterations have run at the given time.
+    pub fn has_ended(&self, now: f64) -> bool {
+        let elapsed_time = self.elapsed_time(now);
+        match self.active_duration() {
+            Some(active_duration) => elapsed_time >= 0. && elapsed_time >= active_duration,
+            None => false,
+        }
+    }
+
+    /// Returns the iteration running at the given time, which must be after the start of the
+    /// first iteration and before the end of the last one.
+    pub fn current_iteration(&self, now: f64) -> u64 {
+        (self.elapsed_time(now) / self.duration).floor() as u64
+    }
+
+    /// Returns true if the animation may still change or fire events as time passes.
+    pub fn needs_frames(&self) -> bool {
+        self.paused_time.is_none() && !self.ended
+    }
+
+    /// Returns true if the animation keeps its last values once it has ended.
+    pub fn fills_forwards(&self) -> bool {
+        match self.fill_mode {
+            animation_fill_mode::forwards | animation_fill_mode::both => true,
+            _ => false,
+        }
+    }
+
+    /// Returns the progress through the keyframes at the given time, between 0 and 1, or `None` if
+    /// the animation has no effect at that time (CSS Animations § 3.4 and 3.7).
+    fn keyframes_progress(&self, now: f64) -> Option<f64> {
+        let elapsed_time = self.elapsed_time(now);
+        let (iteration, progress) = if elapsed_time < 0. {
+            match self.fill_mode {
+                animation_fill_mode::backwards | animation_fill_mode::both => (0., 0.),
+                _ => return None,
+            }
+        } else if self.has_ended(now) {
+            if !self.fills_forwards() {
+                return None
+            }
+            // The animation stays at the end of its last, possibly partial, iteration.
+            let count = if self.duration <= 0. { 1. } else { self.iteration_count.unwrap() };
+            if count <= 0. {
+                (0., 0.)
+            } else {
+                let last_iteration = count.ceil() - 1.;
+                (last_iteration, count - last_iteration)
+            }
+        } else {
+            let iterations = elapsed_time / self.duration;
+            (iterations.floor(), iterations - iterations.floor())
+        };
+        let reversed = match self.direction {
+            animation_direction::normal => false,
+            animation_direction::reverse => true,
+            animation_direction::alternate => iteration % 2. == 1.,
+            animation_direction::alternate_reverse => iteration % 2. == 0.,
+        };
+        Some(if reversed { 1. - progress } else { progress })
+    }
+
+    /// Sets the animated properties in `style` to their values at the given time. The properties
+    /// that the first or last keyframe leaves out keep the values in `base_style` there.
+    fn apply(&self, now: f64, base_style: &ComputedValues, style: &mut ComputedValues) {
+        let progress = match self.keyframes_progress(now) {
+            Some(progress) => progress,
+            None => return,
+        };
+
+        for property in self.properties.iter() {
+            let mut frames: Vec<(f64, &ComputedValues, &TimingFunction)> =
+                self.keyframes.iter().filter(|keyframe| {
+                    keyframe.properties.contains(property)
+                }).map(|keyframe| {
+                    let timing_function =
+                        keyframe.timing_function.as_ref().unwrap_or(&self.timing_function);
+                    (keyframe.offset, &keyframe.style, timing_function)
+                }).collect();
+            if frames.iter().all(|&(offset, _, _)| offset > 0.) {
+                frames.unshift((0., base_style, &self.timing_function))
+            }
+            if frames.iter().all(|&(offset, _, _)| offset < 1.) {
+                frames.push((1., base_style, &self.timing_function))
+            }
+
+            // Interpolate within the last pair of keyframes that starts at or before the progress.
+            let mut index = 0;
+            while index + 2 < frames.len() {
+                let (next_offset, _, _) = *frames.get(index + 1);
+                if next_offset > progress {
+                    break
+                }
+                index += 1
+            }
+            let (from_offset, from_style, timing_function) = *frames.get(index);
+            let (to_offset, to_style, _) = *frames.get(index + 1);
+            let local_progress = if to_offset > from_offset {
+                (progress - from_offset) / (to_offset - from_offset)
+            } else {
+                1.
+            };
+            property.interpolate(from_style, to_style, timing_function.solve(local_progress), style)
+        }
+    }
+}
+
+/// One keyframe of a `@keyframes` rule with its declarations computed.
+pub struct ComputedKeyframe {
+    /// The offset of the keyframe, between 0 and 1.
+    pub offset: f64,
+    /// The style that the declarations of the keyframe compute to.
+    pub style: ComputedValues,
+    /// The animatable properties that the keyframe declares.
+    pub properties: Vec<AnimatableProperty>,
+    /// The timing function that the keyframe declares, if any.
+    pub timing_function: Option<TimingFunction>,
+}
+
+/// Cascades the keyframes of a `@keyframes` rule against the parent style of the animated node.
+///
+/// FIXME: Values relative to the parent style, such as `em` lengths, keep the values they have
+/// when the animation starts.
+fn cascade_keyframes(keyframes_rule: &KeyframesRule, parent_style: Option<&ComputedValues>)
+                     -> Vec<ComputedKeyframe> {
+    keyframes_rule.keyframes.iter().map(|keyframe| {
+        let (keyframe_style, _) =
+            cascade([MatchedProperty::from_declarations(keyframe.declarations.clone())].as_slice(),
+                    false,
+                    parent_style,
+                    None);
+        let mut timing_function = None;
+        let mut properties = vec!();
+        for declaration in keyframe.declarations.iter() {
+            if declaration.name() == "animation-timing-function" {
+                timing_function =
+                    Some(keyframe_style.get_animation().animation_timing_function.get(0).clone())
+            }
+            match AnimatableProperty::from_name(declaration.name()) {
+                Some(property) if !properties.contains(&property) => properties.push(property),
+                _ => {}
+            }
+        }
+        ComputedKeyframe {
+            offset: keyframe.offset,
+            style: keyframe_style,
+            properties: properties,
+            timing_function: timing_function,
+        }
+    }).collect()
+}
+
+/// Returns true if the given style names the given animation in `animation-name`.
+fn names_animation(style: &ComputedValues, name: &str) -> bool {
+    style.get_animation().animation_name.iter().any(|animation_name| {
+        animation_name.as_ref().map_or(false, |animation_name| animation_name.as_slice() == name)
+    })
+}
+
+/// Starts the animations that the style of a node names and that are not running yet, and updates
+/// the ones that are running from the style, since changes to the `animation-*` properties other
+/// than `animation-name` apply to running animations. Names without a `@keyframes` rule are
+/// ignored, and so are names that the old style of the node already had but that no longer run,
+/// since those animations have finished (CSS Animations § 3).
+pub fn update_animations(node: OpaqueNode,
+                         old_style: Option<&ComputedValues>,
+                         parent_style: Option<&ComputedValues>,
+                         style: &ComputedValues,
+                         layout_context: &LayoutContext)
+                         -> Vec<KeyframesAnimation> {
+    let stylist: &Stylist = unsafe {
+        cast::transmute(layout_context.stylist)
+    };
+    let now = layout_context.animation_time;
+    let animation_style = style.get_animation();
+
+    let mut animations: Vec<KeyframesAnimation> = vec!();
+    for (index, name) in animation_style.animation_name.iter().enumerate() {
+        let name = match *name {
+            Some(ref name) => name,
+            None => continue,
+        };
+        let keyframes_rule = match stylist.get_keyframes(name.as_slice()) {
+            Some(keyframes_rule) => keyframes_rule,
+            None => continue,
+        };
+        // The last animation with a given name wins.
+        animations.retain(|animation| animation.name != *name);
+
+        let delay = cyclic_get(&animation_style.animation_delay, index).seconds();
+        let mut animation = match layout_context.running_animations.iter().find(|animation| {
+            animation.node == node && animation.name == *name
+        }) {
+            Some(animation) => animation.clone(),
+            None if old_style.map_or(false, |old_style| {
+                names_animation(old_style, name.as_slice())
+            }) => continue,
+            None => {
+                let keyframes = cascade_keyframes(keyframes_rule, parent_style);
+                let mut properties = vec!();
+                for keyframe in keyframes.iter() {
+                    for property in keyframe.properties.iter() {
+                        if !properties.contains(property) {
+                            properties.push(property.clone())
+                        }
+                    }
+                }
+                KeyframesAnimation {
+                    node: node,
+                    name: name.clone(),
+                    start_time: now + delay,
+                    paused_time: None,
+                    duration: 0.,
+                    iteration_count: None,
+                    direction: animation_direction::normal,
+                    fill_mode: animation_fill_mode::none,
+                    timing_function: animation_style.animation_timing_function.get(0).clone(),
+                    properties: properties,
+                    keyframes: Arc::new(keyframes),
+                    reported_iteration: None,
+                    ended: false,
+                }
+            }
+        };
+
+        animation.duration = cyclic_get(&animation_style.animation_duration, index).seconds();
+        animation.iteration_count =
+            match *cyclic_get(&animation_style.animation_iteration_count, index) {
+                animation_iteration_count::Infinite => None,
+                animation_iteration_count::Iterations(count) => Some(count),
+            };
+        animation.direction = *cyclic_get(&animation_style.animation_direction, index);
+        animation.fill_mode = *cyclic_get(&animation_style.animation_fill_mode, index);
+        animation.timing_function =
+            cyclic_get(&animation_style.animation_timing_function, index).clone();
+        match (*cyclic_get(&animation_style.animation_play_state, index), animation.paused_time) {
+            (animation_play_state::paused, None) => animation.paused_time = Some(now),
+            (animation_play_state::running, Some(paused_time)) => {
+                animation.start_time += now - paused_time;
+                animation.paused_time = None
+            }
+            _ => {}
+        }
+        animations.push(animation)
+    }
+    animations
+}
+
+/// Sets the properties that the given animations of a node animate in `style` to their current
+/// values, and reports the animations to the layout task, which replaces the ones it keeps for the
+/// node with them. Later animations override earlier ones.
+pub fn apply_animations(node: OpaqueNode,
+                        animations: Vec<KeyframesAnimation>,
+                        style: &mut Arc<ComputedValues>,
+                        layout_context: &LayoutContext) {
+    if animations.is_empty() {
+        // Only report nodes whose animations stopped since the last reflow.
+        if layout_context.running_animations.iter().any(|animation| animation.node == node) {
+            layout_context.animations_chan.send((node, animations))
+        }
+        return
+    }
+
+    let base_style = style.clone();
+    let mut animated_style = (**style).clone();
+    for animation in animations.iter() {
+        animation.apply(layout_context.animation_time, &*base_style, &mut animated_style)
+    }
+    *style = Arc::new(animated_style);
+
+    layout_context.animations_chan.send((node, animations))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{KeyframesAnimation, PropertyTransition};
+
+    use gfx::display_list::OpaqueNode;
+    use servo_util::geometry::Au;
+    use style::computed_values::{CubicBezierTimingFunction, LPA_Length, RGBA, StepEnd};
+    use style::computed_values::{StepsTimingFunction, TimingFunction};
+    use style::computed_values::{animation_direction, animation_fill_mode};
+    use style::{AnimatableProperty, ComputedValues, MatchedProperty, cascade};
+    use style::parse_style_attribute;
+    use sync::Arc;
+    use url::Url;
+
+    fn style_from_attribute(style_attribute: &str) -> Arc<ComputedValues> {
+        let base_url = from_str::<Url>("http://example.com/").unwrap();
+        let declarations = parse_style_attribute(style_attribute, &base_url);
+        let (style, _) = cascade([MatchedProperty::from_declarations(declarations.normal)]
+                                     .as_slice(),
+                                 false,
+                                 None,
+                                 None);
+        Arc::new(style)
+    }
+
+    /// A transition of `property` from `from` to `to` that starts at 10 seconds and lasts 2.
+    fn transition(property: &str, from: &str, to: &str, timing_function: TimingFunction)
+                  -> PropertyTransition {
+        PropertyTransition {
+            node: OpaqueNode(0),
+            property: AnimatableProperty::from_name(property).unwrap(),
+            from: style_from_attribute(from),
+            to: style_from_attribute(to),
+            start_time: 10.,
+            duration: 2.,
+            timing_function: timing_function,
+        }
+    }
+
+    fn linear() -> TimingFunction {
+        CubicBezierTimingFunction(0., 0., 1., 1.)
+    }
+
+    /// Returns the width of the style that the transition produces at the given time.
+    fn width_at(transition: &PropertyTransition, now: f64) -> Au {
+        let mut style = (*transition.from).clone();
+        transition.apply(now, &mut style);
+        match style.get_box().width {
+            LPA_Length(width) => width,
+            _ => fail!("expected a length"),
+        }
+    }
+
+    #[test]
+    fn test_transition_interpolates_lengths() {
+        let transition = transition("width", "width: 0px", "width: 100px", linear());
+        assert!(width_at(&transition, 10.) == Au::from_px(0));
+        assert!(width_at(&transition, 10.5) == Au::from_px(25));
+        assert!(width_at(&transition, 11.) == Au::from_px(50));
+        assert!(width_at(&transition, 11.5) == Au::from_px(75));
+        assert!(width_at(&transition, 12.) == Au::from_px(100));
+    }
+
+    #[test]
+    fn test_transition_applies_its_timing_function() {
+        let transition = transition("width",
+                                    "width: 0px",
+                                    "width: 100px",
+                                    StepsTimingFunction(4, StepEnd));
+        assert!(width_at(&transition, 10.4) == Au::from_px(0));
+        assert!(width_at(&transition, 11.2) == Au::from_px(50));
+        assert!(width_at(&transition, 11.9) == Au::from_px(75));
+    }
+
+    #[test]
+    fn test_transition_interpolates_colors_and_numbers() {
+        let color = transition("color", "color: red", "color: blue", linear());
+        let mut style = (*color.from).clone();
+        color.apply(11., &mut style);
+        let expected = RGBA { red: 0.5, green: 0., blue: 0.5, alpha: 1. };
+        assert!(style.get_color().color == expected);
+
+        let opacity = transition("opacity", "opacity: 1", "opacity: 0", linear());
+        let mut style = (*opacity.from).clone();
+        opacity.apply(10.5, &mut style);
+        assert!((style.get_effects().opacity - 0.75).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_transition_start_and_end() {
+        let transition = transition("width", "width: 0px", "width: 100px", linear());
+        // During the delay, the property keeps the value it transitions from.
+        assert!(!transition.has_ended(5.));
+        assert!(width_at(&transition, 5.) == Au::from_px(0));
+        assert!(!transition.has_ended(11.99));
+        // Once the transition has ended, the property has the value it transitions to.
+        assert!(transition.has_ended(12.));
+        assert!(transition.has_ended(20.));
+        assert!(width_at(&transition, 20.) == Au::from_px(100));
+    }
+
+    /// An animation without keyframes whose first iteration starts at 10 seconds and lasts 2.
+    fn animation(iteration_count: Option<f64>,
+                 direction: animation_direction::SingleComputedValue,
+                 fill_mode: animation_fill_mode::SingleComputedValue)
+                 -> KeyframesAnimation {
+        KeyframesAnimation {
+            node: OpaqueNode(0),
+            name: "test".to_owned(),
+            start_time: 10.,
+            paused_time: None,
+            duration: 2.,
+            iteration_count: iteration_count,
+            direction: direction,
+            fill_mode: fill_mode,
+            timing_function: linear(),
+            properties: vec!(),
+            keyframes: Arc::new(vec!()),
+            reported_iteration: None,
+            ended: false,
+        }
+    }
+
+    #[test]
+    fn test_keyframes_progress_iteration_count() {
+        let partial =
+            animation(Some(2.5), animation_direction::normal, animation_fill_mode::none);
+        assert!(partial.keyframes_progress(10.) == Some(0.));
+        assert!(partial.keyframes_progress(11.) == Some(0.5));
+        assert!(partial.keyframes_progress(13.) == Some(0.5));
+        assert!(partial.current_iteration(13.) == 1);
+        assert!(partial.keyframes_progress(14.5) == Some(0.25));
+        assert!(partial.current_iteration(14.5) == 2);
+        assert!(partial.has_ended(15.));
+        assert!(partial.keyframes_progress(15.) == None);
+
+        let infinite = animation(None, animation_direction::normal, animation_fill_mode::none);
+        assert!(!infinite.has_ended(1000.));
+        assert!(infinite.keyframes_progress(1001.) == Some(0.5));
+    }
+
+    #[test]
+    fn test_keyframes_progress_direction() {
+        let alternate =
+            animation(Some(3.), animation_direction::alternate, animation_fill_mode::none);
+        assert!(alternate.keyframes_progress(10.5) == Some(0.25));
+        assert!(alternate.keyframes_progress(12.5) == Some(0.75));
+        assert!(alternate.keyframes_progress(14.5) == Some(0.25));
+
+        let alternate_reverse =
+            animation(Some(3.), animation_direction::alternate_reverse, animation_fill_mode::none);
+        assert!(alternate_reverse.keyframes_progress(10.5) == Some(0.75));
+        assert!(alternate_reverse.keyframes_progress(12.5) == Some(0.25));
+
+        let reverse = animation(Some(3.), animation_direction::reverse, animation_fill_mode::none);
+        assert!(reverse.keyframes_progress(10.5) == Some(0.75));
+        assert!(reverse.keyframes_progress(12.5) == Some(0.75));
+    }
+
+    #[test]
+    fn test_keyframes_progress_fill_modes() {
+        let none = animation(Some(1.), animation_direction::normal, animation_fill_mode::none);
+        assert!(none.keyframes_progress(9.) == None);
+        assert!(none.keyframes_progress(12.) == None);
+
+        let forwards =
+            animation(Some(1.), animation_direction::normal, animation_fill_mode::forwards);
+        assert!(forwards.keyframes_progress(9.) == None);
+        assert!(forwards.keyframes_progress(12.) == Some(1.));
+
+        let backwards =
+            animation(Some(1.), animation_direction::normal, animation_fill_mode::backwards);
+        assert!(backwards.keyframes_progress(9.) == Some(0.));
+        assert!(backwards.keyframes_progress(12.) == None);
+
+        let both = animation(Some(1.), animation_direction::reverse, animation_fill_mode::both);
+        assert!(both.keyframes_progress(9.) == Some(1.));
+        assert!(both.keyframes_progress(12.) == Some(0.));
+
+        // Filling forwards keeps the end of the last iteration, which may be partial and which
+        // an alternating animation runs in reverse.
+        let partial =
+            animation(Some(1.5), animation_direction::normal, animation_fill_mode::forwards);
+        assert!(partial.keyframes_progress(20.) == Some(0.5));
+        let alternate =
+            animation(Some(2.), animation_direction::alternate, animation_fill_mode::forwards);
+        assert!(alternate.keyframes_progress(20.) == Some(0.));
+    }
+}
@@ -30,17 +30,19 @@ use layout::ftl_layout::BlockFlowFtlAttrs;
 //use geom::{Point2D, Rect, SideOffsets2D, Size2D};
 //use gfx::display_list::DisplayList;
 use style::ComputedValues;
-use style::computed_values::{clear, position};
+use style::computed_values::{clear, position, z_index};
 
 use collections::Deque;
 use collections::dlist::DList;
-use geom::{Point2D, Rect, Size2D};
+use geom::{Point2D, Rect, SideOffsets2D, Size2D};
+use geom::matrix2d::Matrix2D;
 use gfx::color;
 use gfx::display_list::{BackgroundAndBorderLevel, BlockLevel, ContentStackingLevel, DisplayList};
-use gfx::display_list::{FloatStackingLevel, PositionedDescendantStackingLevel};
+use gfx::display_list::{FloatStackingLevel, IndexedDisplayList};
+use gfx::display_list::{PositionedDescendantStackingLevel};
 use gfx::display_list::{RootOfStackingContextLevel};
 use gfx::render_task::RenderLayer;
-use servo_msg::compositor_msg::{FixedPosition, LayerId, Scrollable};
+use servo_msg::compositor_msg::{FixedPosition, LayerId, OverflowScroll, Scrollable};
 use servo_util::geometry::Au;
 use servo_util::geometry;
 use std::fmt;
@@ -285,11 +287,11 @@ impl HeightConstraintSolution {
 /// current calculated value of `height`.
 ///
 /// See CSS 2.1 § 10.7.
-struct CandidateHeightIterator {
+pub struct CandidateHeightIterator {
     height: MaybeAuto,
     max_height: Option<Au>,
     min_height: Au,
-    candidate_value: Au,
+    pub candidate_value: Au,
     status: CandidateHeightIteratorStatus,
 }
 
@@ -449,6 +451,8 @@ enum BlockType {
     AbsoluteNonReplacedType,
     FloatReplacedType,
     FloatNonReplacedType,
+    InlineBlockNonReplacedType,
+    FlexItemType,
 }
 
 #[deriving(Clone, Eq)]
@@ -474,7 +478,7 @@ enum FormattingContextType {
 //
 // TODO(#1244, #2007, pcwalton): Do this for CSS transforms and opacity too, at least if they're
 // animating.
-fn propagate_layer_flag_from_child(layers_needed_for_descendants: &mut bool, kid: &mut Flow) {
+pub fn propagate_layer_flag_from_child(layers_needed_for_descendants: &mut bool, kid: &mut Flow) {
     if kid.is_absolute_containing_block() {
         let kid_base = flow::mut_base(kid);
         if kid_base.flags.needs_layer() {
@@ -488,6 +492,83 @@ fn propagate_layer_flag_from_child(layers_needed_for_descendants: &mut bool, kid
     }
 }
 
+/// Moves the display items of the contents of a scrollable box into a render layer of their own,
+/// which the compositor clips to the padding box of the box and scrolls. `border_box` and the
+/// display items are in page coordinates. If the box has `overflow: auto` and its contents fit,
+/// the items are pushed onto `display_list` instead and no layer is built.
+pub fn build_scroll_layer(fragment: &Fragment,
+                          border_box: Rect<Au>,
+                          border: SideOffsets2D<Au>,
+                          contents: DisplayList,
+                          display_list: &mut DisplayList)
+                          -> Option<RenderLayer> {
+    let padding_box = Rect(Point2D(border_box.origin.x + border.left,
+                                   border_box.origin.y + border.top),
+                           Size2D(border_box.size.width - border.horizontal(),
+                                  border_box.size.height - border.vertical()));
+
+    // The contents can be scrolled to their right and bottom edges, but not past the top left
+    // corner of the padding box.
+    let mut max_x = padding_box.origin.x + padding_box.size.width;
+    let mut max_y = padding_box.origin.y + padding_box.size.height;
+    for item in contents.iter() {
+        let bounds = item.bounds();
+        max_x = geometry::max(max_x, bounds.origin.x + bounds.size.width);
+        max_y = geometry::max(max_y, bounds.origin.y + bounds.size.height);
+    }
+    let scrollable_size = Size2D(max_x - padding_box.origin.x, max_y - padding_box.origin.y);
+    if fragment.style().get_box().overflow == overflow::auto &&
+            scrollable_size == padding_box.size {
+        display_list.push_all_move(contents);
+        return None
+    }
+
+    let origin = Point2D(padding_box.origin.x.to_nearest_px() as uint,
+                         padding_box.origin.y.to_nearest_px() as uint);
+    let size = Size2D(scrollable_size.width.to_nearest_px() as uint,
+                      scrollable_size.height.to_nearest_px() as uint);
+    let clip_size = Size2D(padding_box.size.width.to_nearest_px() as uint,
+                           padding_box.size.height.to_nearest_px() as uint);
+    Some(RenderLayer {
+        id: LayerId::for_scrollable_box(fragment.node.id() as uint),
+        display_list: Arc::new(IndexedDisplayList::new(contents.flatten(ContentStackingLevel))),
+        position: Rect(origin, size),
+        clip_rect: Rect(origin, clip_size),
+        background_color: color::rgba(255.0, 255.0, 255.0, 0.0),
+        scroll_policy: OverflowScroll,
+        dirty_rects: None,
+    })
+}
+
+/// Builds the render layer holding the display items of a `position: fixed` box, which the
+/// compositor keeps in place while the page scrolls. `border_box` and the display items are in
+/// the coordinates of the viewport. The layer covers the parts of the items that overflow the
+/// box too, but nothing above or to the left of the viewport.
+pub fn build_fixed_layer(fragment: &Fragment, border_box: Rect<Au>, display_list: DisplayList)
+                         -> RenderLayer {
+    let mut bounds = border_box;
+    for item in display_list.iter() {
+        bounds = bounds.union(&item.bounds())
+    }
+    let min_x = geometry::max(bounds.origin.x, Au(0));
+    let min_y = geometry::max(bounds.origin.y, Au(0));
+    let max_x = geometry::max(bounds.origin.x + bounds.size.width, min_x);
+    let max_y = geometry::max(bounds.origin.y + bounds.size.height, min_y);
+
+    let origin = Point2D(min_x.to_nearest_px() as uint, min_y.to_nearest_px() as uint);
+    let size = Size2D((max_x - min_x).to_nearest_px() as uint,
+                      (max_y - min_y).to_nearest_px() as uint);
+    RenderLayer {
+        id: LayerId::for_fixed_box(fragment.node.id() as uint),
+        display_list: Arc::new(IndexedDisplayList::new(display_list.flatten(ContentStackingLevel))),
+        position: Rect(origin, size),
+        clip_rect: Rect(origin, size),
+        background_color: color::rgba(255.0, 255.0, 255.0, 0.0),
+        scroll_policy: FixedPosition,
+        dirty_rects: None,
+    }
+}
+
 // A block formatting context.
 pub struct BlockFlow {
     /// Data common to all flows.
@@ -511,7 +592,10 @@ pub struct BlockFlow {
     previous_float_width: Option<Au>,
 
     /// Additional floating flow members.
-    pub float: Option<Box<FloatedBlockInfo>>
+    pub float: Option<Box<FloatedBlockInfo>>,
+
+    /// The width of the margin box of this flow, if it was assigned by a flex container.
+    pub flex_item_width: Option<Au>,
 }
 
 impl BlockFlow {
@@ -524,20 +608,26 @@ impl BlockFlow {
             is_root: false,
             static_y_offset: Au::new(0),
             previous_float_width: None,
-            float: None
+            float: None,
+            flex_item_width: None,
         }
     }
 
     pub fn from_node_and_fragment(node: &ThreadSafeLayoutNode, fragment: Fragment) -> BlockFlow {
+        BlockFlow::from_base_and_fragment(BaseFlow::new((*node).clone()), fragment)
+    }
+
+    pub fn from_base_and_fragment(base: BaseFlow, fragment: Fragment) -> BlockFlow {
         BlockFlow {
-            base: BaseFlow::new((*node).clone()),
+            base: base,
             ftl_attrs: BlockFlowFtlAttrs::new(),
             screenwidth: Au(0),
             fragment: fragment,
             is_root: false,
             static_y_offset: Au::new(0),
             previous_float_width: None,
-            float: None
+            float: None,
+            flex_item_width: None,
         }
     }
 
@@ -553,7 +643,8 @@ impl BlockFlow {
             is_root: false,
             static_y_offset: Au::new(0),
             previous_float_width: None,
-            float: Some(box FloatedBlockInfo::new(float_kind))
+            float: Some(box FloatedBlockInfo::new(float_kind)),
+            flex_item_width: None,
         }
     }
 
@@ -568,12 +659,16 @@ impl BlockFlow {
             } else {
                 AbsoluteNonReplacedType
             }
+        } else if self.flex_item_width.is_some() {
+            FlexItemType
         } else if self.is_float() {
             if self.is_replaced_content() {
                 FloatReplacedType
             } else {
                 FloatNonReplacedType
             }
+        } else if self.is_inline_block() {
+            InlineBlockNonReplacedType
         } else {
             if self.is_replaced_content() {
                 BlockReplacedType
@@ -611,7 +706,47 @@ impl BlockFlow {
                 let width_computer = BlockNonReplaced;
                 width_computer.compute_used_width(self, ctx, containing_block_width);
             }
+            InlineBlockNonReplacedType => {
+                let width_computer = InlineBlockNonReplaced;
+                width_computer.compute_used_width(self, ctx, containing_block_width);
+            }
+            FlexItemType => {
+                let width_computer = FlexItem;
+                width_computer.compute_used_width(self, ctx, containing_block_width);
+            }
+        }
+    }
+
+    /// Returns true if this is the block flow of an inline-block fragment. Inline flex containers
+    /// are sized the same way.
+    pub fn is_inline_block(&self) -> bool {
+        match self.fragment.style().get_box().display {
+            display::inline_block | display::inline_flex => true,
+            _ => false,
+        }
+    }
+
+    /// Returns the distance from the top of the border box of this flow to the baseline of its
+    /// last in-flow line box, if it has any.
+    pub fn baseline_offset_of_last_line_box(&mut self) -> Option<Au> {
+        let mut result = None;
+        for kid in self.base.child_iter() {
+            if kid.is_absolutely_positioned() || kid.is_float() {
+                continue
+            }
+            let kid_baseline = if kid.is_inline_flow() {
+                kid.as_inline().last_line_baseline
+            } else if kid.is_block_like() {
+                kid.as_block().baseline_offset_of_last_line_box()
+            } else {
+                None
+            };
+            match kid_baseline {
+                None => {}
+                Some(baseline) => result = Some(flow::base(kid).position.origin.y + baseline),
+            }
         }
+        result
     }
 
     /// Return this flow's fragment.
@@ -1125,27 +1260,53 @@ impl BlockFlow {
                                              background_border_level,
                                              None);
 
+        // The contents of a scrollable box go into a render layer of their own.
+        let is_scroll_container = self.is_scroll_container();
+        let mut contents = DisplayList::new();
         let mut child_layers = DList::new();
-        for kid in self.base.child_iter() {
-            if kid.is_absolutely_positioned() {
-                // All absolute flows will be handled by their containing block.
-                continue
+        {
+            let kid_display_list = if is_scroll_container {
+                &mut contents
+            } else {
+                &mut display_list
+            };
+            for kid in self.base.child_iter() {
+                if kid.is_absolutely_positioned() {
+                    // All absolute flows will be handled by their containing block.
+                    continue
+                }
+
+                accumulator.push_child(&mut *kid_display_list, kid);
+                child_layers.append(mem::replace(&mut flow::mut_base(kid).layers, DList::new()))
             }
 
-            accumulator.push_child(&mut display_list, kid);
-            child_layers.append(mem::replace(&mut flow::mut_base(kid).layers, DList::new()))
+            // Process absolute descendant links.
+            for abs_descendant_link in self.base.abs_descendants.iter() {
+                match abs_descendant_link.resolve() {
+                    Some(kid) => {
+                        // TODO(pradeep): Send in our absolute position directly.
+                        accumulator.push_child(&mut *kid_display_list, kid);
+                        child_layers.append(mem::replace(&mut flow::mut_base(kid).layers,
+                                                         DList::new()));
+                    }
+                    None => fail!("empty Rawlink to a descendant")
+                }
+            }
         }
 
-        // Process absolute descendant links.
-        for abs_descendant_link in self.base.abs_descendants.iter() {
-            match abs_descendant_link.resolve() {
-                Some(kid) => {
-                    // TODO(pradeep): Send in our absolute position directly.
-                    accumulator.push_child(&mut display_list, kid);
-                    child_layers.append(mem::replace(&mut flow::mut_base(kid).layers,
-                                                     DList::new()));
-                }
-                None => fail!("empty Rawlink to a descendant")
+        if is_scroll_container {
+            let border_box = self.fragment
+                                 .border_box
+                                 .translate(&(self.base.abs_position + rel_offset + offset));
+            let border = model::border_from_style(self.fragment.style());
+            match build_scroll_layer(&self.fragment,
+                                     border_box,
+                                     border,
+                                     contents,
+                                     &mut display_list) {
+                // The layer of the box goes below the layers of its descendants.
+                Some(layer) => child_layers.push_front(layer),
+                None => {}
             }
         }
 
@@ -1153,6 +1314,15 @@ impl BlockFlow {
         self.base.layers = child_layers
     }
 
+    /// Returns true if this block clips its contents to its padding box and lets the user scroll
+    /// through them, that is, if its `overflow` is `scroll` or `auto`.
+    pub fn is_scroll_container(&self) -> bool {
+        match self.fragment.style().get_box().overflow {
+            overflow::scroll | overflow::auto => true,
+            overflow::visible | overflow::hidden => false,
+        }
+    }
+
     /// Add display items for current block.
     ///
     /// Set the absolute position for children after doing any offsetting for
@@ -1164,6 +1334,11 @@ impl BlockFlow {
             self.build_display_list_float(layout_context)
         } else if self.is_absolutely_positioned() {
             self.build_display_list_abs(layout_context)
+        } else if self.stacking_context_z_index_and_opacity().is_some() {
+            self.build_display_list_block_common(layout_context,
+                                                 Zero::zero(),
+                                                 RootOfStackingContextLevel);
+            self.build_stacking_context_if_necessary(Zero::zero());
         } else {
             self.build_display_list_block_common(layout_context, Zero::zero(), BlockLevel)
         }
@@ -1174,10 +1349,58 @@ impl BlockFlow {
         self.build_display_list_block_common(layout_context,
                                              float_offset,
                                              RootOfStackingContextLevel);
+        if self.build_stacking_context_if_necessary(float_offset) {
+            return
+        }
         self.base.display_list = mem::replace(&mut self.base.display_list,
                                               DisplayList::new()).flatten(FloatStackingLevel)
     }
 
+    /// Returns the `z-index` level and opacity of the stacking context that this block
+    /// establishes, or `None` if it does not establish one. Positioned blocks with an integer
+    /// `z-index` (CSS 2.1 § 9.9.1), blocks with an opacity less than one (CSS Color Module
+    /// Level 3 § 3.2) and transformed blocks (CSS Transforms § 6) establish stacking contexts.
+    pub fn stacking_context_z_index_and_opacity(&self) -> Option<(i32, f32)> {
+        let style = self.fragment.style();
+        let z_index = style.get_box().z_index;
+        let opacity = style.get_effects().opacity;
+        let has_z_index = self.is_positioned() && z_index != z_index::Auto;
+        let is_transformed = !style.get_effects().transform.is_empty();
+        if has_z_index || opacity < 1.0 || is_transformed {
+            Some((z_index.number_or_zero(), opacity as f32))
+        } else {
+            None
+        }
+    }
+
+    /// If this block establishes a stacking context, replaces its display list with a single
+    /// stacking context display item that paints the flattened list as a group. Returns true if
+    /// the display list was replaced.
+    fn build_stacking_context_if_necessary(&mut self, offset: Point2D<Au>) -> bool {
+        let (z_index, opacity) = match self.stacking_context_z_index_and_opacity() {
+            None => return false,
+            Some(z_index_and_opacity) => z_index_and_opacity,
+        };
+
+        let rel_offset =
+            self.fragment.relative_position(&self.base
+                                             .absolute_position_info
+                                             .relative_containing_block_size,
+                                        None);
+        let bounds = self.fragment
+                         .border_box
+                         .translate(&(self.base.abs_position + rel_offset + offset));
+        let transform = model::transform_from_style(self.fragment.style(), &bounds)
+                                   .unwrap_or(Matrix2D::identity());
+        let display_list = mem::replace(&mut self.base.display_list, DisplayList::new());
+        self.base.display_list = display_list.flatten_into_stacking_context(bounds,
+                                                                            self.fragment.node,
+                                                                            z_index,
+                                                                            opacity,
+                                                                            transform);
+        true
+    }
+
     /// Calculate and set the height, offsets, etc. for absolutely positioned flow.
     ///
     /// The layout for its in-flow children has been done during normal layout.
@@ -1269,15 +1492,17 @@ impl BlockFlow {
         self.build_display_list_block_common(layout_context,
                                              Zero::zero(),
                                              RootOfStackingContextLevel);
+        let is_stacking_context = self.build_stacking_context_if_necessary(Zero::zero());
 
         if !self.base.absolute_position_info.layers_needed_for_positioned_flows &&
                 !self.base.flags.needs_layer() {
-            // We didn't need a layer.
-            //
-            // TODO(#781, pcwalton): `z-index`.
-            self.base.display_list =
-                mem::replace(&mut self.base.display_list,
-                             DisplayList::new()).flatten(PositionedDescendantStackingLevel(0));
+            // We didn't need a layer. Positioned blocks with `z-index: auto` are painted at level
+            // zero but do not form a stacking context of their own.
+            if !is_stacking_context {
+                self.base.display_list =
+                    mem::replace(&mut self.base.display_list,
+                                 DisplayList::new()).flatten(PositionedDescendantStackingLevel(0));
+            }
             return
         }
 
@@ -1292,12 +1517,15 @@ impl BlockFlow {
             Scrollable
         };
         let display_list = mem::replace(&mut self.base.display_list, DisplayList::new());
+        let display_list = IndexedDisplayList::new(display_list.flatten(ContentStackingLevel));
         let new_layer = RenderLayer {
             id: self.layer_id(0),
-            display_list: Arc::new(display_list.flatten(ContentStackingLevel)),
+            display_list: Arc::new(display_list),
             position: Rect(origin, size),
+            clip_rect: Rect(origin, size),
             background_color: color::rgba(255.0, 255.0, 255.0, 0.0),
             scroll_policy: scroll_policy,
+            dirty_rects: None,
         };
         self.base.layers.push_back(new_layer)
     }
@@ -1414,7 +1642,8 @@ impl BlockFlow {
             return OtherFormattingContext
         }
         match style.get_box().display {
-            display::table_cell | display::table_caption | display::inline_block => {
+            display::table_cell | display::table_caption | display::inline_block |
+            display::flex | display::inline_flex => {
                 OtherFormattingContext
             }
             _ if style.get_box().position == position::static_ &&
@@ -1431,6 +1660,10 @@ impl Flow for BlockFlow {
         BlockFlowClass
     }
 
+    fn as_immutable_block<'a>(&'a self) -> &'a BlockFlow {
+        self
+    }
+
     fn as_block<'a>(&'a mut self) -> &'a mut BlockFlow {
         self
     }
@@ -1995,6 +2228,8 @@ struct BlockNonReplaced;
 struct BlockReplaced;
 struct FloatNonReplaced;
 struct FloatReplaced;
+struct InlineBlockNonReplaced;
+struct FlexItem;
 
 impl WidthAndMarginsComputer for AbsoluteNonReplaced {
     /// Solve the horizontal constraint equation for absolute non-replaced elements.
@@ -2327,6 +2562,44 @@ impl WidthAndMarginsComputer for FloatNonReplaced {
     }
 }
 
+impl WidthAndMarginsComputer for InlineBlockNonReplaced {
+    /// CSS Section 10.3.9
+    ///
+    /// If width is computed as 'auto', the used value is the 'shrink-to-fit' width.
+    fn solve_width_constraints(&self,
+                               block: &mut BlockFlow,
+                               input: &WidthConstraintInput)
+                               -> WidthConstraintSolution {
+        let margin_left = input.left_margin.specified_or_zero();
+        let margin_right = input.right_margin.specified_or_zero();
+        let available_width = input.available_width - margin_left - margin_right;
+        let shrink_to_fit = block.get_shrink_to_fit_width(available_width);
+        let width = input.computed_width.specified_or_default(shrink_to_fit);
+        debug!("assign_widths_inline_block -- width: {}", width);
+        WidthConstraintSolution::new(width, margin_left, margin_right)
+    }
+}
+
+impl WidthAndMarginsComputer for FlexItem {
+    /// CSS Flexible Box Layout § 9.7
+    ///
+    /// The flex container has already resolved the size of the margin box of this item, so the
+    /// used width is whatever is left of it after margins, borders, and padding.
+    fn solve_width_constraints(&self,
+                               block: &mut BlockFlow,
+                               input: &WidthConstraintInput)
+                               -> WidthConstraintSolution {
+        let margin_left = input.left_margin.specified_or_zero();
+        let margin_right = input.right_margin.specified_or_zero();
+        let flex_item_width = block.flex_item_width.unwrap();
+        let width = geometry::max(Au(0),
+                                  flex_item_width - margin_left - margin_right -
+                                  block.fragment.border_padding.horizontal());
+        debug!("assign_widths_flex_item -- width: {}", width);
+        WidthConstraintSolution::new(width, margin_left, margin_right)
+    }
+}
+
 impl WidthAndMarginsComputer for FloatReplaced {
     /// CSS Section 10.3.5
     ///
@@ -23,14 +23,20 @@
 use css::node_style::StyledNode;
 use layout::block::BlockFlow;
 use layout::context::LayoutContext;
+use layout::flex::FlexFlow;
 use layout::floats::FloatKind;
 use layout::flow::{Flow, ImmutableFlowUtils, MutableOwnedFlowUtils};
 use layout::flow::{Descendants, AbsDescendants};
+use layout::flow;
 use layout::flow_list::{Rawlink};
-use layout::fragment::{Fragment, GenericFragment, IframeFragment, IframeFragmentInfo, ImageFragment, ImageFragmentInfo};
+use layout::fragment::{ContentItems, Fragment, GeneratedContentFragment, GenericFragment};
+use layout::fragment::{IframeFragment, IframeFragmentInfo, ImageFragment, ImageFragmentInfo};
+use layout::fragment::{InlineBlockFragment, InlineBlockFragmentInfo};
+use layout::fragment::{ListItemMarker};
 use layout::fragment::{SpecificFragmentInfo, TableFragment, TableCellFragment, TableColumnFragment, TableColumnFragmentInfo};
 use layout::fragment::{TableRowFragment, TableWrapperFragment, UnscannedTextFragment, UnscannedTextFragmentInfo};
-use layout::inline::{FragmentIndex, InlineFragments, InlineFlow};
+use layout::inline::{FirstLineStyle, FragmentIndex, InlineFragments, InlineFlow};
+use layout::list_item::ListItemFlow;
 use layout::table_wrapper::TableWrapperFlow;
 use layout::table::TableFlow;
 use layout::table_caption::TableCaptionFlow;
@@ -60,8 +66,9 @@ use servo_util::range::Range;
 use servo_util::str::is_whitespace;
 use servo_util::url::{is_image_data, parse_url};
 use std::mem;
-use style::ComputedValues;
-use style::computed_values::{display, position, float, white_space};
+use style::{ComputedValues, cascade_anonymous, cascade_pseudo_element_fragment};
+use style::computed_values::{content, display, position, float, list_style_position};
+use style::computed_values::{list_style_type, white_space};
 use sync::Arc;
 use url::Url;
 
@@ -289,7 +296,19 @@ impl<'a> FlowConstructor<'a> {
             Some(ElementNodeTypeId(HTMLTableHeaderCellElementTypeId)) => TableCellFragment,
             Some(ElementNodeTypeId(HTMLTableRowElementTypeId)) |
             Some(ElementNodeTypeId(HTMLTableSectionElementTypeId)) => TableRowFragment,
-            None | Some(TextNodeTypeId) => UnscannedTextFragment(UnscannedTextFragmentInfo::new(node)),
+            None => {
+                // Pseudo-element content that shows counters, or that changes counters, can only
+                // be resolved once the whole flow tree has been constructed.
+                let style = node.style();
+                match style.get_box().content {
+                    content::Content(ref items) if has_counters(items.as_slice()) ||
+                            changes_counters(&**style) => {
+                        GeneratedContentFragment(ContentItems(items.clone()))
+                    }
+                    _ => UnscannedTextFragment(UnscannedTextFragmentInfo::new(node)),
+                }
+            }
+            Some(TextNodeTypeId) => UnscannedTextFragment(UnscannedTextFragmentInfo::new(node)),
             _ => GenericFragment,
         }
     }
@@ -327,8 +346,27 @@ impl<'a> FlowConstructor<'a> {
             }
         }
 
+        // If nothing precedes this inline flow in the block, it holds the block's first formatted
+        // line, so the `::first-letter` and `::first-line` pseudo-elements apply to it.
+        //
+        // FIXME: The first formatted line of a block may also be inside a descendant block.
+        let is_first_line = flow::base(&**flow).children.len() == 0 && flow_list.is_empty();
+        if is_first_line {
+            match node.first_letter_style() {
+                Some(ref first_letter_style) => {
+                    split_first_letter(&mut fragments, &**node.style(), &**first_letter_style)
+                }
+                None => {}
+            }
+        }
+
         let mut inline_flow = box InlineFlow::from_fragments((*node).clone(), fragments);
         inline_flow.compute_minimum_ascent_and_descent(self.font_context(), &**node.style());
+        if is_first_line {
+            inline_flow.first_line_style = node.first_line_style().map(|first_line_style| {
+                FirstLineStyle::new(node.style().clone(), first_line_style)
+            });
+        }
         let mut inline_flow = inline_flow as Box<Flow:Share>;
         TextRunScanner::new().scan_for_runs(self.font_context(), inline_flow);
         inline_flow.finish(self.layout_context);
@@ -457,12 +495,20 @@ impl<'a> FlowConstructor<'a> {
     /// this block flow.
     /// Also, deal with the absolute and fixed descendants bubbled up by
     /// children nodes.
-    fn build_flow_using_children(&mut self,
-                                 mut flow: Box<Flow:Share>,
-                                 node: &ThreadSafeLayoutNode)
+    fn build_flow_using_children(&mut self, flow: Box<Flow:Share>, node: &ThreadSafeLayoutNode)
                                  -> ConstructionResult {
+        self.build_flow_using_children_and_fragments(flow, node, InlineFragmentsAccumulator::new())
+    }
+
+    /// Like `build_flow_using_children()`, but places the given inline fragments before the
+    /// fragments of the children of the node.
+    fn build_flow_using_children_and_fragments(&mut self,
+                                               mut flow: Box<Flow:Share>,
+                                               node: &ThreadSafeLayoutNode,
+                                               mut inline_fragment_accumulator:
+                                                InlineFragmentsAccumulator)
+                                               -> ConstructionResult {
         // Gather up fragments for the inline flows we might need to create.
-        let mut inline_fragment_accumulator = InlineFragmentsAccumulator::new();
         let mut consecutive_siblings = vec!();
         let mut first_fragment = true;
 
@@ -520,6 +566,51 @@ impl<'a> FlowConstructor<'a> {
         self.build_flow_using_children(flow, node)
     }
 
+    /// Builds a flow for a node with `display: flex`. This yields a `FlexFlow` whose children are
+    /// its flex items; runs of inline content become anonymous `InlineFlow` items.
+    fn build_flow_for_flex(&mut self, node: &ThreadSafeLayoutNode) -> ConstructionResult {
+        let flow = box FlexFlow::from_node(self, node) as Box<Flow:Share>;
+        self.build_flow_using_children(flow, node)
+    }
+
+    /// Builds a flow for a node with `display: list-item`. This yields a `ListItemFlow` with a
+    /// marker, which is placed either outside of the item or before its first inline fragment
+    /// according to `list-style-position`.
+    fn build_flow_for_list_item(&mut self, node: &ThreadSafeLayoutNode) -> ConstructionResult {
+        let mut initial_fragments = InlineFragmentsAccumulator::new();
+        let marker = self.build_marker_for_list_item(node);
+        let marker = match (node.style().get_list().list_style_position, marker) {
+            (list_style_position::inside, Some(marker)) => {
+                let style = marker.style.clone();
+                initial_fragments.fragments.push(marker, style);
+                None
+            }
+            (_, marker) => marker,
+        };
+        let flow = box ListItemFlow::from_node(self, node, marker) as Box<Flow:Share>;
+        self.build_flow_using_children_and_fragments(flow, node, initial_fragments)
+    }
+
+    /// Builds the marker of a list item: the `list-style-image` if there is one, or else a
+    /// placeholder for the text of the `list-style-type`, which is only known once the
+    /// `list-item` counter has been computed. The marker inherits the style of the item.
+    fn build_marker_for_list_item(&mut self, node: &ThreadSafeLayoutNode) -> Option<Fragment> {
+        let style = node.style();
+        let specific = match style.get_list().list_style_image {
+            Some(ref url) => {
+                ImageFragment(ImageFragmentInfo::new(node,
+                                                     url.clone(),
+                                                     self.layout_context.image_cache.clone()))
+            }
+            None if style.get_list().list_style_type == list_style_type::none => return None,
+            None => GeneratedContentFragment(ListItemMarker(style.get_list().list_style_type)),
+        };
+        let opaque_node = OpaqueNodeMethods::from_thread_safe_layout_node(node);
+        Some(Fragment::from_opaque_node_and_style(opaque_node,
+                                                  Arc::new(cascade_anonymous(&**style)),
+                                                  specific))
+    }
+
     /// Builds the flow for a node with `float: {left|right}`. This yields a float `BlockFlow` with
     /// a `BlockFlow` underneath it.
     fn build_flow_for_floated_block(&mut self, node: &ThreadSafeLayoutNode, float_kind: FloatKind)
@@ -645,6 +736,39 @@ impl<'a> FlowConstructor<'a> {
         ConstructionItemConstructionResult(construction_item)
     }
 
+    /// Builds a fragment for a node with `display: inline-block` or `display: inline-flex`. The
+    /// contents of the node are laid out in a `BlockFlow` or `FlexFlow` nested inside the
+    /// fragment, which yields an `InlineFragmentsConstructionResult` containing just that
+    /// fragment.
+    fn build_fragment_for_inline_block(&mut self, node: &ThreadSafeLayoutNode)
+                                       -> ConstructionResult {
+        let construction_result = if node.style().get_box().display == display::inline_flex {
+            self.build_flow_for_flex(node)
+        } else {
+            self.build_flow_for_block(node)
+        };
+        let (block_flow, abs_descendants) = match construction_result {
+            FlowConstructionResult(flow, abs_descendants) => (flow, abs_descendants),
+            _ => fail!("build_fragment_for_inline_block didn't yield a flow"),
+        };
+
+        let fragment = Fragment::new_from_specific_info(
+            node,
+            InlineBlockFragment(InlineBlockFragmentInfo::new(block_flow)));
+
+        // The borders, padding, and relative offset of the inline-block are handled by its block
+        // flow, so it must not contribute them to the inline fragment context.
+        let mut fragments = InlineFragments::new();
+        fragments.push(fragment, Arc::new(cascade_anonymous(&**node.style())));
+
+        let construction_item = InlineFragmentsConstructionItem(InlineFragmentsConstructionResult {
+            splits: Vec::new(),
+            fragments: fragments,
+            abs_descendants: abs_descendants,
+        });
+        ConstructionItemConstructionResult(construction_item)
+    }
+
     /// Builds one or more fragments for a node with `display: inline`. This yields an
     /// `InlineFragmentsConstructionResult`.
     fn build_fragments_for_inline(&mut self, node: &ThreadSafeLayoutNode) -> ConstructionResult {
@@ -877,6 +1001,12 @@ impl<'a> PostorderNodeMutTraversal for FlowConstructor<'a> {
                 node.set_flow_construction_result(construction_result)
             }
 
+            // Flex containers contribute flex flow construction results, even when they are
+            // absolutely positioned. Floated ones are laid out as floated blocks for now.
+            (display::flex, float::none, _) => {
+                node.set_flow_construction_result(self.build_flow_for_flex(node))
+            }
+
             // Absolutely positioned elements will have computed value of
             // `float` as 'none' and `display` as per the table.
             // Only match here for block items. If an item is absolutely
@@ -893,6 +1023,13 @@ impl<'a> PostorderNodeMutTraversal for FlowConstructor<'a> {
                 node.set_flow_construction_result(construction_result)
             }
 
+            // Inline-blocks and inline flex containers that are not floated contribute inline
+            // fragment construction results.
+            (display::inline_block, float::none, _) | (display::inline_flex, float::none, _) => {
+                let construction_result = self.build_fragment_for_inline_block(node);
+                node.set_flow_construction_result(construction_result)
+            }
+
             // Table items contribute table flow construction results.
             (display::table_caption, _, _) => {
                 let construction_result = self.build_flow_for_table_caption(node);
@@ -930,6 +1067,11 @@ impl<'a> PostorderNodeMutTraversal for FlowConstructor<'a> {
                 node.set_flow_construction_result(construction_result)
             }
 
+            // List items that are not floated contribute list item flow construction results.
+            (display::list_item, float::none, _) => {
+                node.set_flow_construction_result(self.build_flow_for_list_item(node))
+            }
+
             // Block flows that are not floated contribute block flow construction results.
             //
             // TODO(pcwalton): Make this only trigger for blocks and handle the other `display`
@@ -951,6 +1093,23 @@ impl<'a> PostorderNodeMutTraversal for FlowConstructor<'a> {
     }
 }
 
+/// Returns true if the given `content` shows the value of a counter.
+fn has_counters(items: &[content::Content]) -> bool {
+    items.iter().any(|item| {
+        match *item {
+            content::StringContent(_) => false,
+            content::CounterContent(..) | content::CountersContent(..) => true,
+        }
+    })
+}
+
+/// Returns true if the given style resets, increments or sets a counter.
+fn changes_counters(style: &ComputedValues) -> bool {
+    let box_style = style.get_box();
+    !box_style.counter_reset.is_empty() || !box_style.counter_increment.is_empty() ||
+        !box_style.counter_set.is_empty()
+}
+
 /// A utility trait with some useful methods for node queries.
 trait NodeUtils {
     /// Returns true if this node doesn't render its kids and false otherwise.
@@ -1139,3 +1298,194 @@ fn strip_ignorable_whitespace_from_end(fragments: &mut InlineFragments) {
         map: map,
     }
 }
+
+/// Returns the length in bytes of the first typographic letter unit at the start of `text`: any
+/// leading whitespace and punctuation, the first letter or digit, and any punctuation directly
+/// following it (CSS 2.1 § 5.12.2). Returns `None` if `text` contains no letter or digit.
+fn first_letter_length(text: &str) -> Option<uint> {
+    let mut found_letter = false;
+    for (i, ch) in text.char_indices() {
+        if found_letter {
+            if ch.is_alphanumeric() || ch.is_whitespace() {
+                return Some(i)
+            }
+        } else if ch.is_alphanumeric() {
+            found_letter = true
+        }
+    }
+    if found_letter {
+        Some(text.len())
+    } else {
+        None
+    }
+}
+
+/// Splits the first typographic letter off the first fragment of a block's first line into a
+/// fragment of its own, styled with the block's `::first-letter` style.
+///
+/// FIXME: The first letter is only found if it is in the first fragment of the line.
+fn split_first_letter(fragments: &mut InlineFragments,
+                      block_style: &ComputedValues,
+                      first_letter_style: &ComputedValues) {
+    if fragments.len() == 0 {
+        return
+    }
+
+    let (letter, rest) = match fragments.get(0).specific {
+        UnscannedTextFragment(ref text_fragment_info) => {
+            let text = text_fragment_info.text.as_slice();
+            match first_letter_length(text) {
+                None => return,
+                Some(length) => (text.slice_to(length).to_owned(), text.slice_from(length).to_owned()),
+            }
+        }
+        _ => return,
+    };
+
+    let InlineFragments {
+        fragments: old_fragments,
+        map: mut map
+    } = mem::replace(fragments, InlineFragments::new());
+
+    let mut new_fragments = Vec::new();
+    {
+        let first_fragment = old_fragments.get(0);
+        let letter_style = cascade_pseudo_element_fragment(&*first_fragment.style,
+                                                           block_style,
+                                                           first_letter_style);
+        new_fragments.push(Fragment::from_opaque_node_and_style(
+                first_fragment.node,
+                Arc::new(letter_style),
+                UnscannedTextFragment(UnscannedTextFragmentInfo::from_text(letter))));
+        if rest.len() > 0 {
+            new_fragments.push(Fragment::from_opaque_node_and_style(
+                    first_fragment.node,
+                    first_fragment.style.clone(),
+                    UnscannedTextFragment(UnscannedTextFragmentInfo::from_text(rest))));
+        }
+    }
+    for fragment in old_fragments.iter().skip(1) {
+        new_fragments.push(fragment.clone())
+    }
+
+    map.fixup(old_fragments.as_slice(), new_fragments.as_slice());
+    *fragments = InlineFragments {
+        fragments: new_fragments,
+        map: map,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{first_letter_length, split_first_letter};
+    use layout::fragment::{Fragment, GenericFragment, SpecificFragmentInfo};
+    use layout::fragment::{UnscannedTextFragment, UnscannedTextFragmentInfo};
+    use layout::inline::InlineFragments;
+
+    use gfx::display_list::OpaqueNode;
+    use servo_util::geometry::Au;
+    use style::{ComputedValues, MatchedProperty, cascade, parse_style_attribute};
+    use sync::Arc;
+    use url::Url;
+
+    fn style(style_attribute: &str) -> Arc<ComputedValues> {
+        let base_url = from_str::<Url>("http://example.com/").unwrap();
+        let declarations = parse_style_attribute(style_attribute, &base_url);
+        let (style, _) = cascade([MatchedProperty::from_declarations(declarations.normal)]
+                                     .as_slice(),
+                                 false,
+                                 None,
+                                 None);
+        Arc::new(style)
+    }
+
+    fn text_fragment(style: &Arc<ComputedValues>, text: &str) -> Fragment {
+        let info = UnscannedTextFragmentInfo::from_text(text.to_owned());
+        fragment(style, UnscannedTextFragment(info))
+    }
+
+    fn fragment(style: &Arc<ComputedValues>, specific: SpecificFragmentInfo) -> Fragment {
+        Fragment::from_opaque_node_and_style(OpaqueNode(0), style.clone(), specific)
+    }
+
+    /// Returns the text of each fragment, or an empty string for fragments without text.
+    fn texts(fragments: &InlineFragments) -> Vec<~str> {
+        fragments.fragments.iter().map(|fragment| {
+            match fragment.specific {
+                UnscannedTextFragment(ref info) => info.text.clone(),
+                _ => "".to_owned(),
+            }
+        }).collect()
+    }
+
+    #[test]
+    fn test_first_letter_length() {
+        assert!(first_letter_length("Hello") == Some(1));
+        assert!(first_letter_length("x") == Some(1));
+        assert!(first_letter_length("42 apples") == Some(1));
+
+        // Leading whitespace and punctuation belong to the first letter, and so does punctuation
+        // directly after it.
+        assert!(first_letter_length("  hello") == Some(3));
+        assert!(first_letter_length("\"Quoted\" text") == Some(2));
+        assert!(first_letter_length("(a) b") == Some(3));
+        assert!(first_letter_length("A. B") == Some(2));
+        assert!(first_letter_length(" \"A\" b") == Some(4));
+
+        // Lengths are in bytes.
+        assert!(first_letter_length("\u00e9t\u00e9") == Some(2));
+        assert!(first_letter_length("\u00ab\u00c9lan\u00bb") == Some(4));
+        assert!(first_letter_length("\u00ab\u00c9\u00bb") == Some(6));
+        assert!(first_letter_length("\u65e5\u672c") == Some(3));
+
+        // Text without letters or digits has no first letter.
+        assert!(first_letter_length("") == None);
+        assert!(first_letter_length("   ") == None);
+        assert!(first_letter_length("...!") == None);
+    }
+
+    #[test]
+    fn test_split_first_letter() {
+        let block_style = style("font-size: 10px");
+        let first_letter_style = style("font-size: 20px");
+        let text_style = style("");
+
+        let mut fragments = InlineFragments::new();
+        fragments.push(text_fragment(&text_style, "\u00ab\u00c9lan\u00bb vital"),
+                       text_style.clone());
+        fragments.push(text_fragment(&text_style, " again"), text_style.clone());
+        split_first_letter(&mut fragments, &*block_style, &*first_letter_style);
+        assert!(texts(&fragments) ==
+                vec!("\u00ab\u00c9".to_owned(), "lan\u00bb vital".to_owned(), " again".to_owned()));
+        assert!(fragments.get(0).style.get_font().font_size == Au::from_px(20));
+        assert!(fragments.get(1).style.get_font().font_size == text_style.get_font().font_size);
+
+        // A fragment that is only its first letter is not followed by an empty one.
+        let mut fragments = InlineFragments::new();
+        fragments.push(text_fragment(&text_style, "A."), text_style.clone());
+        split_first_letter(&mut fragments, &*block_style, &*first_letter_style);
+        assert!(texts(&fragments) == vec!("A.".to_owned()));
+        assert!(fragments.get(0).style.get_font().font_size == Au::from_px(20));
+    }
+
+    #[test]
+    fn test_split_first_letter_without_a_letter() {
+        let block_style = style("");
+        let first_letter_style = style("font-size: 20px");
+        let text_style = style("");
+
+        // Text without letters and fragments other than text are left alone.
+        let mut fragments = InlineFragments::new();
+        fragments.push(text_fragment(&text_style, "... "), text_style.clone());
+        fragments.push(text_fragment(&text_style, "later"), text_style.clone());
+        split_first_letter(&mut fragments, &*block_style, &*first_letter_style);
+        assert!(texts(&fragments) == vec!("... ".to_owned(), "later".to_owned()));
+
+        let mut fragments = InlineFragments::new();
+        fragments.push(fragment(&text_style, GenericFragment), text_style.clone());
+        fragments.push(text_fragment(&text_style, "later"), text_style.clone());
+        split_first_letter(&mut fragments, &*block_style, &*first_letter_style);
+        assert!(texts(&fragments) == vec!("".to_owned(), "later".to_owned()));
+        assert!(fragments.get(1).style.get_font().font_size == text_style.get_font().font_size);
+    }
+}
@@ -4,7 +4,8 @@
 
 //! Data needed by the layout task.
 
-use css::matching::{ApplicableDeclarationsCache, StyleSharingCandidateCache};
+use css::matching::{ApplicableDeclarationsCache, StyleBloom, StyleSharingCandidateCache};
+use layout::animation::{KeyframesAnimation, PropertyTransition};
 
 use geom::rect::Rect;
 use geom::size::Size2D;
@@ -18,6 +19,7 @@ use servo_net::image::holder::LocalImageCacheHandle;
 use servo_util::geometry::Au;
 use servo_util::opts::Opts;
 use std::cast;
+use std::comm::Sender;
 #[cfg(not(target_os="android"))]
 use std::ptr;
 #[cfg(not(target_os="android"))]
@@ -25,6 +27,7 @@ use std::rt::local::Local;
 #[cfg(not(target_os="android"))]
 use std::rt::task::Task;
 use style::Stylist;
+use sync::Arc;
 use url::Url;
 
 #[cfg(not(target_os="android"))]
@@ -50,6 +53,13 @@ static mut STYLE_SHARING_CANDIDATE_CACHE: *mut StyleSharingCandidateCache =
 #[cfg(target_os="android")]
 local_data_key!(style_sharing_candidate_cache: *mut StyleSharingCandidateCache)
 
+#[cfg(not(target_os="android"))]
+#[thread_local]
+static mut STYLE_BLOOM: *mut StyleBloom = 0 as *mut StyleBloom;
+
+#[cfg(target_os="android")]
+local_data_key!(style_bloom: *mut StyleBloom)
+
 /// Data shared by all layout workers.
 #[deriving(Clone)]
 pub struct LayoutContext {
@@ -84,6 +94,29 @@ pub struct LayoutContext {
 
     /// The dirty rectangle, used during display list building.
     pub dirty: Rect<Au>,
+
+    /// The reflow generation, used to detect stale per-thread style Bloom filters.
+    pub generation: uint,
+
+    /// Whether every node must be restyled, rather than only the nodes flagged by restyle hints
+    /// and those that have never been styled.
+    pub restyle_all: bool,
+
+    /// The transitions that were running when this reflow started.
+    pub running_transitions: Arc<Vec<PropertyTransition>>,
+
+    /// The channel on which the style recalc reports the transitions that run after this reflow.
+    pub transitions_chan: Sender<PropertyTransition>,
+
+    /// The animations that were running, paused or filling forwards when this reflow started.
+    pub running_animations: Arc<Vec<KeyframesAnimation>>,
+
+    /// The channel on which the style recalc reports the animations of each restyled node that
+    /// has or had any.
+    pub animations_chan: Sender<(OpaqueNode, Vec<KeyframesAnimation>)>,
+
+    /// The time at which transitions and animations are sampled during this reflow, in seconds.
+    pub animation_time: f64,
 }
 
 #[cfg(not(target_os="android"))]
@@ -153,6 +186,28 @@ impl LayoutContext {
             cast::transmute(STYLE_SHARING_CANDIDATE_CACHE)
         }
     }
+
+    pub fn style_bloom<'a>(&'a self) -> &'a mut StyleBloom {
+        // Sanity check.
+        {
+            let mut task = Local::borrow(None::<Task>);
+            match task.maybe_take_runtime::<GreenTask>() {
+                Some(green) => {
+                    task.put_runtime(green);
+                    fail!("can't call this on a green task!")
+                }
+                None => {}
+            }
+        }
+
+        unsafe {
+            if STYLE_BLOOM == ptr::mut_null() {
+                let bloom = box StyleBloom::new();
+                STYLE_BLOOM = cast::transmute(bloom)
+            }
+            cast::transmute(STYLE_BLOOM)
+        }
+    }
 }
 
 
@@ -207,5 +262,20 @@ impl LayoutContext {
             cast::transmute(cache)
         }
     }
+
+    pub fn style_bloom<'a>(&'a self) -> &'a mut StyleBloom {
+        unsafe {
+            let opt = style_bloom.replace(None);
+            let mut bloom;
+            match opt {
+                Some(b) => bloom = cast::transmute(b),
+                None => {
+                    bloom = cast::transmute(box StyleBloom::new());
+                }
+            }
+            style_bloom.replace(Some(bloom));
+            cast::transmute(bloom)
+        }
+    }
 }
 
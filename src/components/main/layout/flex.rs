@@ -0,0 +1,1007 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Layout for elements with `display: flex` and `display: inline-flex`.
+//!
+//! The container is laid out like a block, and then its in-flow children ("flex items") are
+//! resized and moved according to CSS Flexible Box Layout § 9. Items are always laid out as if
+//! `align-content` were `flex-start`.
+
+use layout::block::{BlockFlow, CandidateHeightIterator, MarginsMayNotCollapse};
+use layout::construct::FlowConstructor;
+use layout::context::LayoutContext;
+use layout::flow::{FlexFlowClass, FlowClass, Flow, ImmutableFlowUtils};
+use layout::flow;
+use layout::ftl_layout::FtlNode;
+use layout::model::{Auto, IntrinsicWidths, MaybeAuto, Specified, specified, specified_or_none};
+use layout::wrapper::ThreadSafeLayoutNode;
+
+use geom::Rect;
+use servo_msg::compositor_msg::LayerId;
+use servo_util::geometry::Au;
+use servo_util::geometry;
+use std::fmt;
+use style::ComputedValues;
+use style::computed_values::{clear, position};
+use style::computed_values::{LPA_Auto, LPA_Length, LPN_Length, LP_Length};
+use style::computed_values::{align_items, align_self, flex_direction, flex_wrap};
+use style::computed_values::justify_content;
+use sync::Arc;
+
+/// An in-flow child of a flex container. All sizes refer to the margin box of the item.
+struct FlexItem {
+    /// The index of this item among the children of the container.
+    index: uint,
+    /// The style of the item, or `None` if it is an anonymous item wrapping inline content.
+    style: Option<Arc<ComputedValues>>,
+    /// The value of the `order` property.
+    order: i32,
+    grow: f64,
+    shrink: f64,
+    /// The used value of `align-self`, with `auto` resolved against the container.
+    align: align_items::T,
+    /// True if the item may be stretched in the cross axis.
+    cross_size_is_auto: bool,
+    /// The flex base size.
+    base_size: Au,
+    /// The flex base size clamped by the minimum and maximum main sizes.
+    hypothetical_size: Au,
+    min_size: Au,
+    max_size: Option<Au>,
+    main_size: Au,
+    /// The position of the item along the main axis, relative to the content box of the
+    /// container.
+    main_position: Au,
+    cross_size: Au,
+    /// The position of the item along the cross axis, relative to the content box of the
+    /// container.
+    cross_position: Au,
+}
+
+impl FlexItem {
+    fn new(index: uint,
+           style: Option<Arc<ComputedValues>>,
+           align_items: align_items::T,
+           is_row: bool)
+           -> FlexItem {
+        let (order, grow, shrink, align, cross_size_is_auto) = match style {
+            None => (0, 0.0, 1.0, align_items, false),
+            Some(ref style) => {
+                let flex = style.get_flex();
+                let align = match flex.align_self {
+                    align_self::auto => align_items,
+                    align_self::stretch => align_items::stretch,
+                    align_self::flex_start => align_items::flex_start,
+                    align_self::flex_end => align_items::flex_end,
+                    align_self::center => align_items::center,
+                };
+                let cross_size = if is_row {
+                    style.get_box().height
+                } else {
+                    style.get_box().width
+                };
+                (flex.order, flex.flex_grow, flex.flex_shrink, align, cross_size == LPA_Auto)
+            }
+        };
+        FlexItem {
+            index: index,
+            style: style,
+            order: order,
+            grow: grow,
+            shrink: shrink,
+            align: align,
+            cross_size_is_auto: cross_size_is_auto,
+            base_size: Au(0),
+            hypothetical_size: Au(0),
+            min_size: Au(0),
+            max_size: None,
+            main_size: Au(0),
+            main_position: Au(0),
+            cross_size: Au(0),
+            cross_position: Au(0),
+        }
+    }
+
+    fn clamp(&self, size: Au) -> Au {
+        let size = match self.max_size {
+            Some(max_size) => geometry::min(size, max_size),
+            None => size,
+        };
+        geometry::max(size, self.min_size)
+    }
+
+    /// Computes the flex base size and the limits of the main size of an item of a row container
+    /// from its intrinsic widths.
+    fn size_horizontally(&mut self, intrinsic_widths: &IntrinsicWidths, containing_width: Au) {
+        let surround = intrinsic_widths.surround_width;
+        match self.style {
+            None => {
+                self.base_size = intrinsic_widths.total_preferred_width();
+                self.min_size = Au(0);
+                self.max_size = None;
+            }
+            Some(ref style) => {
+                let specified_size =
+                    match MaybeAuto::from_style(style.get_flex().flex_basis, containing_width) {
+                        Specified(basis) => Some(basis),
+                        Auto => {
+                            match MaybeAuto::from_style(style.get_box().width, containing_width) {
+                                Specified(width) => Some(width),
+                                Auto => None,
+                            }
+                        }
+                    };
+                self.base_size = match specified_size {
+                    Some(size) => size + surround,
+                    None => intrinsic_widths.total_preferred_width(),
+                };
+                self.min_size = specified(style.get_box().min_width, containing_width) + surround;
+                self.max_size = specified_or_none(style.get_box().max_width, containing_width)
+                    .map(|max_width| max_width + surround);
+            }
+        }
+        self.hypothetical_size = self.clamp(self.base_size)
+    }
+
+    /// Computes the flex base size and the limits of the main size of an item of a column
+    /// container from the height it was laid out at. Percentages are ignored, since the height
+    /// of the container is not known yet.
+    fn size_vertically(&mut self, outer_height: Au, surround: Au) {
+        match self.style {
+            None => {
+                self.base_size = outer_height;
+                self.min_size = Au(0);
+                self.max_size = None;
+            }
+            Some(ref style) => {
+                self.base_size = match style.get_flex().flex_basis {
+                    LPA_Length(basis) => basis + surround,
+                    _ => outer_height,
+                };
+                self.min_size = match style.get_box().min_height {
+                    LP_Length(min_height) => min_height + surround,
+                    _ => surround,
+                };
+                self.max_size = match style.get_box().max_height {
+                    LPN_Length(max_height) => Some(max_height + surround),
+                    _ => None,
+                };
+            }
+        }
+        self.hypothetical_size = self.clamp(self.base_size)
+    }
+}
+
+/// A range of items laid out together along the main axis.
+struct FlexLine {
+    start: uint,
+    end: uint,
+    cross_size: Au,
+    cross_position: Au,
+}
+
+impl FlexLine {
+    fn new(start: uint, end: uint) -> FlexLine {
+        FlexLine {
+            start: start,
+            end: end,
+            cross_size: Au(0),
+            cross_position: Au(0),
+        }
+    }
+}
+
+/// A flex container.
+pub struct FlexFlow {
+    pub block_flow: BlockFlow,
+
+    /// The in-flow children of this flow, in `order`-modified document order.
+    items: Vec<FlexItem>,
+
+    /// The flex lines, as ranges of `items`.
+    lines: Vec<FlexLine>,
+}
+
+impl FlexFlow {
+    pub fn from_node(constructor: &mut FlowConstructor, node: &ThreadSafeLayoutNode) -> FlexFlow {
+        FlexFlow {
+            block_flow: BlockFlow::from_node(constructor, node),
+            items: Vec::new(),
+            lines: Vec::new(),
+        }
+    }
+
+    fn is_row(&self) -> bool {
+        match self.block_flow.fragment.style().get_flex().flex_direction {
+            flex_direction::row | flex_direction::row_reverse => true,
+            flex_direction::column | flex_direction::column_reverse => false,
+        }
+    }
+
+    fn is_main_axis_reversed(&self) -> bool {
+        match self.block_flow.fragment.style().get_flex().flex_direction {
+            flex_direction::row_reverse | flex_direction::column_reverse => true,
+            flex_direction::row | flex_direction::column => false,
+        }
+    }
+
+    fn is_multi_line(&self) -> bool {
+        self.block_flow.fragment.style().get_flex().flex_wrap != flex_wrap::nowrap
+    }
+
+    /// Collects the in-flow children of this flow as flex items, sorted by `order`.
+    fn collect_items(&mut self) {
+        let is_row = self.is_row();
+        let align_items = self.block_flow.fragment.style().get_flex().align_items;
+        let mut items = Vec::new();
+        for (index, kid) in self.block_flow.base.child_iter().enumerate() {
+            if kid.is_absolutely_positioned() {
+                continue
+            }
+            let style = if kid.is_inline_flow() {
+                None
+            } else {
+                Some(kid.as_block().fragment.style.clone())
+            };
+            items.push(FlexItem::new(index, style, align_items, is_row))
+        }
+        items.as_mut_slice().sort_by(|a, b| a.order.cmp(&b.order));
+        self.items = items
+    }
+
+    /// Breaks the items into lines, sizes them along the main axis, and positions them within
+    /// their lines. The hypothetical sizes of the items must have been computed.
+    fn lay_out_main_axis(&mut self, available_size: Au) {
+        let multi_line = self.is_multi_line();
+        let reversed = self.is_main_axis_reversed();
+        let justify_content = self.block_flow.fragment.style().get_flex().justify_content;
+        self.lines = break_into_lines(self.items.as_slice(), available_size, multi_line);
+        for line in self.lines.iter() {
+            let items = self.items.mut_slice(line.start, line.end);
+            resolve_flexible_lengths(items, available_size);
+            justify(items, available_size, justify_content, reversed);
+        }
+    }
+
+    /// Sizes the lines along the cross axis and positions the items within them. The cross sizes
+    /// of the items must have been computed. `definite_cross_size` is the cross size of the
+    /// content box of the container, if it is known. Returns the total cross size of the lines.
+    fn lay_out_cross_axis(&mut self, definite_cross_size: Option<Au>) -> Au {
+        let multi_line = self.is_multi_line();
+        let wrap_reversed =
+            self.block_flow.fragment.style().get_flex().flex_wrap == flex_wrap::wrap_reverse;
+
+        let mut total_cross_size = Au(0);
+        for line in self.lines.mut_iter() {
+            line.cross_size = match definite_cross_size {
+                Some(cross_size) if !multi_line => cross_size,
+                _ => {
+                    self.items.slice(line.start, line.end).iter().fold(Au(0), |size, item| {
+                        geometry::max(size, item.cross_size)
+                    })
+                }
+            };
+            line.cross_position = total_cross_size;
+            total_cross_size = total_cross_size + line.cross_size
+        }
+
+        let container_cross_size = match definite_cross_size {
+            Some(cross_size) => cross_size,
+            None => total_cross_size,
+        };
+        for line in self.lines.mut_iter() {
+            if wrap_reversed {
+                line.cross_position = container_cross_size - line.cross_position - line.cross_size
+            }
+            for item in self.items.mut_slice(line.start, line.end).mut_iter() {
+                if item.align == align_items::stretch && item.cross_size_is_auto {
+                    item.cross_size = line.cross_size
+                }
+                let free_space = line.cross_size - item.cross_size;
+                let offset = match (item.align, wrap_reversed) {
+                    (align_items::center, _) => free_space.scale_by(0.5),
+                    (align_items::flex_end, false) | (align_items::flex_start, true) => {
+                        free_space
+                    }
+                    _ => Au(0),
+                };
+                item.cross_position = line.cross_position + offset
+            }
+        }
+        total_cross_size
+    }
+
+    /// Sets the height of the content box of this flow, taking `height`, `min-height`, and
+    /// `max-height` into account.
+    fn set_content_height(&mut self, content_height: Au) {
+        if self.block_flow.is_absolutely_positioned() {
+            // The absolute flow tree computes the final height.
+            self.block_flow.fragment.border_box.size.height = content_height;
+            return
+        }
+
+        let mut candidate_height_iterator =
+            CandidateHeightIterator::new(self.block_flow.fragment.style(), None);
+        for (candidate_height, new_candidate_height) in candidate_height_iterator {
+            *new_candidate_height = match candidate_height {
+                Auto => content_height,
+                Specified(value) => value,
+            }
+        }
+
+        let height = candidate_height_iterator.candidate_value +
+            self.block_flow.fragment.border_padding.vertical();
+        self.block_flow.fragment.border_box.size.height = height;
+        self.block_flow.base.position.size.height = height;
+    }
+
+    /// Returns the height of the content box of this flow if it is specified as a length and
+    /// this container has a single line. Percentages are ignored.
+    fn definite_content_height(&self) -> Option<Au> {
+        match self.block_flow.fragment.style().get_box().height {
+            LPA_Length(height) if !self.is_multi_line() => Some(height),
+            _ => None,
+        }
+    }
+
+    pub fn build_display_list_flex(&mut self, layout_context: &LayoutContext) {
+        self.block_flow.build_display_list_block(layout_context)
+    }
+}
+
+impl Flow for FlexFlow {
+    fn class(&self) -> FlowClass {
+        FlexFlowClass
+    }
+
+    fn as_flex<'a>(&'a mut self) -> &'a mut FlexFlow {
+        self
+    }
+
+    fn as_immutable_block<'a>(&'a self) -> &'a BlockFlow {
+        &self.block_flow
+    }
+
+    fn as_block<'a>(&'a mut self) -> &'a mut BlockFlow {
+        &mut self.block_flow
+    }
+
+    /// The intrinsic widths of a row container are those of its items laid out side by side.
+    fn bubble_widths(&mut self, ctx: &mut LayoutContext) {
+        self.block_flow.bubble_widths(ctx);
+        if !self.is_row() {
+            return
+        }
+
+        let multi_line = self.is_multi_line();
+        let (mut minimum_width, mut preferred_width) = (Au(0), Au(0));
+        for kid in self.block_flow.base.child_iter() {
+            if kid.is_absolutely_positioned() {
+                continue
+            }
+            let kid_widths = &flow::base(kid).intrinsic_widths;
+            minimum_width = if multi_line {
+                geometry::max(minimum_width, kid_widths.total_minimum_width())
+            } else {
+                minimum_width + kid_widths.total_minimum_width()
+            };
+            preferred_width = preferred_width + kid_widths.total_preferred_width()
+        }
+
+        let fragment_widths = self.block_flow.fragment.intrinsic_widths(None);
+        let widths = &mut self.block_flow.base.intrinsic_widths;
+        widths.minimum_width = geometry::max(minimum_width, fragment_widths.minimum_width);
+        widths.preferred_width = geometry::max(preferred_width, fragment_widths.preferred_width)
+    }
+
+    /// Lays out the items of a row container along the main axis, and sizes the items of a
+    /// column container along the cross axis.
+    fn assign_widths(&mut self, ctx: &mut LayoutContext) {
+        debug!("assign_widths({}): assigning width for flow", "flex");
+        self.block_flow.assign_widths(ctx);
+        self.collect_items();
+
+        let left_content_edge = self.block_flow.fragment.border_box.origin.x +
+            self.block_flow.fragment.border_padding.left;
+        let content_width = self.block_flow.fragment.border_box.size.width -
+            self.block_flow.fragment.border_padding.horizontal();
+
+        // Flex items establish independent formatting contexts, so floats never impact them.
+        for kid in self.block_flow.base.child_iter() {
+            let kid_base = flow::mut_base(kid);
+            kid_base.flags.set_impacted_by_left_floats(false);
+            kid_base.flags.set_impacted_by_right_floats(false);
+        }
+
+        if self.is_row() {
+            for_each_item(&mut self.block_flow.base, self.items.as_mut_slice(), |item, kid| {
+                item.size_horizontally(&flow::base(kid).intrinsic_widths, content_width)
+            });
+            self.lay_out_main_axis(content_width);
+
+            for_each_item(&mut self.block_flow.base, self.items.as_mut_slice(), |item, kid| {
+                set_item_width(kid, Some(item.main_size));
+                flow::mut_base(kid).position.origin.x = left_content_edge + item.main_position
+            });
+        } else {
+            for_each_item(&mut self.block_flow.base, self.items.as_mut_slice(), |item, kid| {
+                if item.align == align_items::stretch && item.cross_size_is_auto {
+                    set_item_width(kid, None)
+                } else {
+                    let width = shrink_to_fit_width(&flow::base(kid).intrinsic_widths,
+                                                    content_width);
+                    set_item_width(kid, Some(width))
+                }
+            });
+        }
+    }
+
+    /// Lays out the items along the axis that depends on their heights, and then computes the
+    /// height of the container.
+    fn assign_height(&mut self, ctx: &mut LayoutContext) {
+        debug!("assign_height: assigning height for flex");
+
+        // Let the block flow handle clearance, collapsible margins, layers, and the static
+        // positions of absolutely-positioned children. The items are moved afterward.
+        //
+        // FIXME: Absolute descendants of a container that is the root of an absolute flow tree
+        // see the height of the container as if the items were stacked.
+        self.block_flow.assign_height_block_base(ctx, MarginsMayNotCollapse);
+
+        let left_content_edge = self.block_flow.fragment.border_box.origin.x +
+            self.block_flow.fragment.border_padding.left;
+        let top_content_edge = self.block_flow.fragment.border_padding.top;
+        let content_width = self.block_flow.fragment.border_box.size.width -
+            self.block_flow.fragment.border_padding.horizontal();
+
+        let content_height = if self.is_row() {
+            for_each_item(&mut self.block_flow.base, self.items.as_mut_slice(), |item, kid| {
+                item.cross_size = outer_height(kid)
+            });
+            let definite_content_height = self.definite_content_height();
+            let content_height = self.lay_out_cross_axis(definite_content_height);
+
+            for_each_item(&mut self.block_flow.base, self.items.as_mut_slice(), |item, kid| {
+                set_item_outer_height(kid, item.cross_size);
+                flow::mut_base(kid).position.origin.y = top_content_edge + item.cross_position +
+                    margin_top(kid)
+            });
+            content_height
+        } else {
+            let mut stacked_height = Au(0);
+            for_each_item(&mut self.block_flow.base, self.items.as_mut_slice(), |item, kid| {
+                let outer_height = outer_height(kid);
+                item.size_vertically(outer_height, outer_height - content_height_of(kid));
+                stacked_height = stacked_height + item.hypothetical_size
+            });
+
+            // The main size of a column container is its height, which may depend on the items.
+            let mut candidate_height_iterator =
+                CandidateHeightIterator::new(self.block_flow.fragment.style(), None);
+            for (candidate_height, new_candidate_height) in candidate_height_iterator {
+                *new_candidate_height = match candidate_height {
+                    Auto => stacked_height,
+                    Specified(value) => value,
+                }
+            }
+            let content_height = candidate_height_iterator.candidate_value;
+            self.lay_out_main_axis(content_height);
+
+            for_each_item(&mut self.block_flow.base, self.items.as_mut_slice(), |item, kid| {
+                set_item_outer_height(kid, item.main_size);
+                item.cross_size = outer_width(kid)
+            });
+            self.lay_out_cross_axis(Some(content_width));
+
+            for_each_item(&mut self.block_flow.base, self.items.as_mut_slice(), |item, kid| {
+                let kid_top_margin = margin_top(kid);
+                let kid_base = flow::mut_base(kid);
+                kid_base.position.origin.x = left_content_edge + item.cross_position;
+                kid_base.position.origin.y = top_content_edge + item.main_position +
+                    kid_top_margin
+            });
+            content_height
+        };
+
+        self.set_content_height(content_height)
+    }
+
+    fn assign_height_for_inorder_child_if_necessary(&mut self, layout_context: &mut LayoutContext)
+                                                    -> bool {
+        if self.block_flow.is_float() {
+            return self.block_flow.assign_height_for_inorder_child_if_necessary(layout_context)
+        }
+        let impacted = self.block_flow.base.flags.impacted_by_floats();
+        if impacted {
+            self.assign_height(layout_context)
+        }
+        impacted
+    }
+
+    fn compute_absolute_position(&mut self) {
+        self.block_flow.compute_absolute_position()
+    }
+
+    fn float_clearance(&self) -> clear::T {
+        self.block_flow.float_clearance()
+    }
+
+    fn mark_as_root(&mut self) {
+        self.block_flow.mark_as_root()
+    }
+
+    fn is_store_overflow_delayed(&mut self) -> bool {
+        self.block_flow.is_store_overflow_delayed()
+    }
+
+    fn is_root(&self) -> bool {
+        self.block_flow.is_root()
+    }
+
+    fn is_float(&self) -> bool {
+        self.block_flow.is_float()
+    }
+
+    fn positioning(&self) -> position::T {
+        self.block_flow.positioning()
+    }
+
+    fn is_root_of_absolute_flow_tree(&self) -> bool {
+        self.block_flow.is_root_of_absolute_flow_tree()
+    }
+
+    fn generated_containing_block_rect(&self) -> Rect<Au> {
+        self.block_flow.generated_containing_block_rect()
+    }
+
+    fn layer_id(&self, fragment_index: uint) -> LayerId {
+        self.block_flow.layer_id(fragment_index)
+    }
+
+    fn is_absolute_containing_block(&self) -> bool {
+        self.block_flow.is_absolute_containing_block()
+    }
+}
+
+impl fmt::Show for FlexFlow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f.buf, "FlexFlow: {}", self.block_flow)
+    }
+}
+
+/// The FTL layout runs the block passes and then moves and resizes the items, in the same way as
+/// the classic layout. The main sizes of the block items of a row container are passed on in
+/// `flex_item_width`, which the FTL width pass applies after sizing them from their styles.
+impl FtlNode for FlexFlow {
+    fn with_all_children(&mut self, func: |&mut FtlNode|) {
+        self.block_flow.with_all_children(func)
+    }
+
+    fn visit_0(&mut self) {
+        self.block_flow.visit_0();
+        self.collect_items();
+
+        let content_width = self.block_flow.ftl_attrs.computedwidth;
+        if self.is_row() {
+            for_each_item(&mut self.block_flow.base, self.items.as_mut_slice(), |item, kid| {
+                item.size_horizontally(&flow::base(kid).intrinsic_widths, content_width)
+            });
+            self.lay_out_main_axis(content_width);
+
+            for_each_item(&mut self.block_flow.base, self.items.as_mut_slice(), |item, kid| {
+                set_item_width(kid, Some(item.main_size));
+                flow::mut_base(kid).ftl_attrs.availablewidth = item.main_size
+            });
+        } else {
+            // A specified width is the cross size of an item of a column container, so the items
+            // are sized from their styles.
+            for_each_item(&mut self.block_flow.base, self.items.as_mut_slice(), |item, kid| {
+                set_item_width(kid, None);
+                if item.align != align_items::stretch || !item.cross_size_is_auto {
+                    let kid_base = flow::mut_base(kid);
+                    kid_base.ftl_attrs.availablewidth =
+                        shrink_to_fit_width(&kid_base.intrinsic_widths, content_width)
+                }
+            });
+        }
+    }
+
+    fn visit_1(&mut self) {
+        self.block_flow.visit_1();
+
+        let left_content_edge = self.block_flow.ftl_attrs.bl + self.block_flow.ftl_attrs.pl;
+        let top_content_edge = self.block_flow.ftl_attrs.bt + self.block_flow.ftl_attrs.pt;
+        let content_width = self.block_flow.ftl_attrs.computedwidth;
+
+        let content_height = if self.is_row() {
+            for_each_item(&mut self.block_flow.base, self.items.as_mut_slice(), |item, kid| {
+                item.cross_size = flow::base(kid).ftl_attrs.totalheight
+            });
+            let definite_content_height = self.definite_content_height();
+            let content_height = self.lay_out_cross_axis(definite_content_height);
+
+            for_each_item(&mut self.block_flow.base, self.items.as_mut_slice(), |item, kid| {
+                let kid_base = flow::mut_base(kid);
+                set_ftl_total_height(kid_base, item.cross_size);
+                kid_base.ftl_attrs.containingx = left_content_edge + item.main_position;
+                kid_base.ftl_attrs.containingy = top_content_edge + item.cross_position
+            });
+            content_height
+        } else {
+            let mut stacked_height = Au(0);
+            for_each_item(&mut self.block_flow.base, self.items.as_mut_slice(), |item, kid| {
+                let kid_base = flow::mut_base(kid);
+                let outer_height = kid_base.ftl_attrs.totalheight;
+                let surround = outer_height - kid_base.position.size.height;
+                item.size_vertically(outer_height, surround);
+                stacked_height = stacked_height + item.hypothetical_size
+            });
+
+            let content_height = if self.block_flow.ftl_attrs.selfintrinsheight == Au(0) {
+                stacked_height
+            } else {
+                self.block_flow.ftl_attrs.selfintrinsheight
+            };
+            self.lay_out_main_axis(content_height);
+
+            for_each_item(&mut self.block_flow.base, self.items.as_mut_slice(), |item, kid| {
+                let kid_base = flow::mut_base(kid);
+                set_ftl_total_height(kid_base, item.main_size);
+                item.cross_size = kid_base.ftl_attrs.totalwidth
+            });
+            self.lay_out_cross_axis(Some(content_width));
+
+            for_each_item(&mut self.block_flow.base, self.items.as_mut_slice(), |item, kid| {
+                let kid_base = flow::mut_base(kid);
+                kid_base.ftl_attrs.containingx = left_content_edge + item.cross_position;
+                kid_base.ftl_attrs.containingy = top_content_edge + item.main_position
+            });
+            content_height
+        };
+
+        let attrs = &self.block_flow.ftl_attrs;
+        if attrs.selfintrinsheight == Au(0) {
+            self.block_flow.base.position.size.height =
+                content_height + attrs.pt + attrs.pb + attrs.bt + attrs.bb
+        }
+        self.block_flow.base.ftl_attrs.totalheight =
+            self.block_flow.base.position.size.height + attrs.mt + attrs.mb
+    }
+
+    fn visit_2(&mut self) {
+        self.block_flow.visit_2()
+    }
+
+    fn visit_3(&mut self) {
+        self.block_flow.visit_3()
+    }
+}
+
+/// Breaks the items into lines no longer than the available size, or into a single line if the
+/// container is single-line. Every line holds at least one item.
+fn break_into_lines(items: &[FlexItem], available_size: Au, multi_line: bool) -> Vec<FlexLine> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    let mut line_size = Au(0);
+    for (index, item) in items.iter().enumerate() {
+        if multi_line && index > start && line_size + item.hypothetical_size > available_size {
+            lines.push(FlexLine::new(start, index));
+            start = index;
+            line_size = Au(0)
+        }
+        line_size = line_size + item.hypothetical_size
+    }
+    if start < items.len() {
+        lines.push(FlexLine::new(start, items.len()))
+    }
+    lines
+}
+
+/// Resolves the main sizes of the items of a line by distributing the free space according to
+/// their flex factors (CSS Flexible Box Layout § 9.7).
+fn resolve_flexible_lengths(items: &mut [FlexItem], available_size: Au) {
+    let hypothetical_size = items.iter().fold(Au(0), |size, item| size + item.hypothetical_size);
+    let growing = hypothetical_size < available_size;
+
+    // Items that cannot flex in the needed direction are frozen at their hypothetical size.
+    let mut frozen = Vec::new();
+    for item in items.mut_iter() {
+        item.main_size = item.hypothetical_size;
+        let inflexible = if growing {
+            item.grow == 0.0 || item.base_size > item.hypothetical_size
+        } else {
+            item.shrink == 0.0 || item.base_size < item.hypothetical_size
+        };
+        frozen.push(inflexible)
+    }
+
+    let mut violations = Vec::from_elem(items.len(), Au(0));
+    loop {
+        let mut free_space = available_size;
+        let (mut total_grow, mut total_scaled_shrink) = (0.0, 0.0);
+        let mut any_unfrozen = false;
+        for (index, item) in items.iter().enumerate() {
+            if *frozen.get(index) {
+                free_space = free_space - item.main_size
+            } else {
+                free_space = free_space - item.base_size;
+                total_grow += item.grow;
+                total_scaled_shrink += item.shrink * item.base_size.to_f64().unwrap();
+                any_unfrozen = true
+            }
+        }
+        if !any_unfrozen {
+            break
+        }
+        if growing && total_grow < 1.0 {
+            free_space = free_space.scale_by(total_grow)
+        }
+
+        let mut total_violation = Au(0);
+        for (index, item) in items.mut_iter().enumerate() {
+            if *frozen.get(index) {
+                continue
+            }
+            let target_size = if growing {
+                item.base_size + free_space.scale_by(item.grow / total_grow)
+            } else if total_scaled_shrink == 0.0 {
+                item.base_size
+            } else {
+                let scaled_shrink = item.shrink * item.base_size.to_f64().unwrap();
+                item.base_size + free_space.scale_by(scaled_shrink / total_scaled_shrink)
+            };
+            item.main_size = item.clamp(target_size);
+            *violations.get_mut(index) = item.main_size - target_size;
+            total_violation = total_violation + *violations.get(index)
+        }
+
+        // Freeze the items that violate their limits in the direction of the total violation,
+        // or every item if there is none, and distribute the free space again.
+        for index in range(0, items.len()) {
+            let violation = *violations.get(index);
+            if total_violation == Au(0) ||
+                    (total_violation > Au(0) && violation > Au(0)) ||
+                    (total_violation < Au(0) && violation < Au(0)) {
+                *frozen.get_mut(index) = true
+            }
+        }
+    }
+}
+
+/// Positions the items of a line along the main axis according to `justify-content`. Positions
+/// are mirrored if the main axis is reversed.
+fn justify(items: &mut [FlexItem],
+           available_size: Au,
+           justify_content: justify_content::T,
+           reversed: bool) {
+    let used_size = items.iter().fold(Au(0), |size, item| size + item.main_size);
+    let free_space = available_size - used_size;
+    let count = items.len() as i32;
+    let (mut position, gap) = match justify_content {
+        justify_content::flex_start => (Au(0), Au(0)),
+        justify_content::flex_end => (free_space, Au(0)),
+        justify_content::center => (free_space.scale_by(0.5), Au(0)),
+        justify_content::space_between if count > 1 && free_space > Au(0) => {
+            (Au(0), free_space / Au(count - 1))
+        }
+        justify_content::space_between => (Au(0), Au(0)),
+        justify_content::space_around if count > 0 && free_space > Au(0) => {
+            (free_space / Au(count * 2), free_space / Au(count))
+        }
+        justify_content::space_around => (free_space.scale_by(0.5), Au(0)),
+    };
+    for item in items.mut_iter() {
+        item.main_position = if reversed {
+            available_size - position - item.main_size
+        } else {
+            position
+        };
+        position = position + item.main_size + gap
+    }
+}
+
+/// Calls `f` with each flex item and the child flow it was collected from, in document order.
+fn for_each_item(base: &mut flow::BaseFlow, items: &mut [FlexItem], f: |&mut FlexItem, &mut Flow|) {
+    let mut item_indices = Vec::from_elem(base.children.len(), None);
+    for (item_index, item) in items.iter().enumerate() {
+        *item_indices.get_mut(item.index) = Some(item_index)
+    }
+    for (index, kid) in base.child_iter().enumerate() {
+        match *item_indices.get(index) {
+            Some(item_index) => f(&mut items[item_index], kid),
+            None => {}
+        }
+    }
+}
+
+/// Returns the width of the margin box of an item sized to fit its content within the given
+/// width.
+fn shrink_to_fit_width(intrinsic_widths: &IntrinsicWidths, available_width: Au) -> Au {
+    geometry::min(intrinsic_widths.total_preferred_width(),
+                  geometry::max(intrinsic_widths.total_minimum_width(), available_width))
+}
+
+/// Makes the margin box of an item as wide as the given width, or lets a block item fill its
+/// containing block if the width is `None`.
+fn set_item_width(kid: &mut Flow, width: Option<Au>) {
+    if kid.is_block_like() {
+        kid.as_block().flex_item_width = width
+    } else {
+        match width {
+            Some(width) => flow::mut_base(kid).position.size.width = width,
+            None => {}
+        }
+    }
+}
+
+fn margin_top(kid: &mut Flow) -> Au {
+    if kid.is_inline_flow() {
+        Au(0)
+    } else {
+        kid.as_block().fragment.margin.top
+    }
+}
+
+fn outer_width(kid: &mut Flow) -> Au {
+    if kid.is_inline_flow() {
+        flow::base(kid).position.size.width
+    } else {
+        let fragment = &kid.as_block().fragment;
+        fragment.border_box.size.width + fragment.margin.horizontal()
+    }
+}
+
+fn outer_height(kid: &mut Flow) -> Au {
+    let margin_height = if kid.is_inline_flow() {
+        Au(0)
+    } else {
+        kid.as_block().fragment.margin.vertical()
+    };
+    flow::base(kid).position.size.height + margin_height
+}
+
+/// Returns the height of the content box of an item.
+fn content_height_of(kid: &mut Flow) -> Au {
+    let border_padding_height = if kid.is_inline_flow() {
+        Au(0)
+    } else {
+        kid.as_block().fragment.border_padding.vertical()
+    };
+    flow::base(kid).position.size.height - border_padding_height
+}
+
+/// Resizes the border box of a block item so that its margin box is as tall as the given height.
+/// The content of the item is not laid out again.
+fn set_item_outer_height(kid: &mut Flow, height: Au) {
+    if kid.is_inline_flow() {
+        return
+    }
+    let border_box_height = {
+        let block = kid.as_block();
+        let border_box_height = height - block.fragment.margin.vertical();
+        block.fragment.border_box.size.height = border_box_height;
+        border_box_height
+    };
+    flow::mut_base(kid).position.size.height = border_box_height
+}
+
+/// The FTL counterpart of `set_item_outer_height`.
+fn set_ftl_total_height(kid_base: &mut flow::BaseFlow, height: Au) {
+    let delta = height - kid_base.ftl_attrs.totalheight;
+    kid_base.position.size.height = kid_base.position.size.height + delta;
+    kid_base.ftl_attrs.totalheight = height
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FlexItem, FlexLine, break_into_lines, justify, resolve_flexible_lengths};
+
+    use servo_util::geometry::Au;
+    use style::computed_values::{align_items, justify_content};
+
+    /// Returns an anonymous item with the given flex base size and flex factors.
+    fn item(base_size: int, grow: f64, shrink: f64) -> FlexItem {
+        let mut item = FlexItem::new(0, None, align_items::stretch, true);
+        item.base_size = Au(base_size);
+        item.hypothetical_size = Au(base_size);
+        item.grow = grow;
+        item.shrink = shrink;
+        item
+    }
+
+    fn main_sizes(items: &[FlexItem]) -> Vec<Au> {
+        items.iter().map(|item| item.main_size).collect()
+    }
+
+    fn main_positions(items: &[FlexItem]) -> Vec<Au> {
+        items.iter().map(|item| item.main_position).collect()
+    }
+
+    #[test]
+    fn test_break_into_lines() {
+        let items = vec!(item(40, 0., 1.), item(40, 0., 1.), item(40, 0., 1.));
+        let ranges = |items: &[FlexItem], available_size: int, multi_line: bool| {
+            let lines: Vec<FlexLine> = break_into_lines(items, Au(available_size), multi_line);
+            lines.iter().map(|line| (line.start, line.end)).collect::<Vec<(uint, uint)>>()
+        };
+
+        // Single-line containers put every item on one line, even if it overflows.
+        assert_eq!(ranges(items.as_slice(), 100, false), vec!((0, 3)));
+
+        // Multi-line containers break before the item that does not fit.
+        assert_eq!(ranges(items.as_slice(), 100, true), vec!((0, 2), (2, 3)));
+        assert_eq!(ranges(items.as_slice(), 120, true), vec!((0, 3)));
+
+        // Every line holds at least one item, even one that is too wide.
+        assert_eq!(ranges(items.as_slice(), 10, true), vec!((0, 1), (1, 2), (2, 3)));
+
+        assert_eq!(ranges(&[], 100, true), vec!());
+    }
+
+    #[test]
+    fn test_resolve_flexible_lengths_grow() {
+        // The free space is shared in proportion to the grow factors.
+        let mut items = vec!(item(10, 1., 1.), item(10, 3., 1.));
+        resolve_flexible_lengths(items.as_mut_slice(), Au(100));
+        assert_eq!(main_sizes(items.as_slice()), vec!(Au(30), Au(70)));
+
+        // Items that do not grow keep their base size.
+        let mut items = vec!(item(10, 0., 1.), item(10, 1., 1.));
+        resolve_flexible_lengths(items.as_mut_slice(), Au(100));
+        assert_eq!(main_sizes(items.as_slice()), vec!(Au(10), Au(90)));
+
+        // An item clamped by its maximum size is frozen and the rest is shared again.
+        let mut items = vec!(item(10, 1., 1.), item(10, 1., 1.));
+        items.get_mut(0).max_size = Some(Au(20));
+        resolve_flexible_lengths(items.as_mut_slice(), Au(100));
+        assert_eq!(main_sizes(items.as_slice()), vec!(Au(20), Au(80)));
+
+        // Grow factors that sum to less than 1 only take that part of the free space.
+        let mut items = vec!(item(0, 0.5, 1.));
+        resolve_flexible_lengths(items.as_mut_slice(), Au(100));
+        assert_eq!(main_sizes(items.as_slice()), vec!(Au(50)));
+    }
+
+    #[test]
+    fn test_resolve_flexible_lengths_shrink() {
+        // The overflow is taken in proportion to the shrink factors scaled by the base sizes.
+        let mut items = vec!(item(96, 0., 1.), item(32, 0., 1.));
+        resolve_flexible_lengths(items.as_mut_slice(), Au(96));
+        assert_eq!(main_sizes(items.as_slice()), vec!(Au(72), Au(24)));
+
+        // Items that do not shrink keep their base size.
+        let mut items = vec!(item(100, 0., 0.), item(50, 0., 1.));
+        resolve_flexible_lengths(items.as_mut_slice(), Au(120));
+        assert_eq!(main_sizes(items.as_slice()), vec!(Au(100), Au(20)));
+
+        // An item clamped by its minimum size is frozen and the others shrink further.
+        let mut items = vec!(item(96, 0., 1.), item(32, 0., 1.));
+        items.get_mut(1).min_size = Au(28);
+        resolve_flexible_lengths(items.as_mut_slice(), Au(96));
+        assert_eq!(main_sizes(items.as_slice()), vec!(Au(68), Au(28)));
+    }
+
+    #[test]
+    fn test_justify() {
+        let justified = |justify_content: justify_content::T, reversed: bool| -> Vec<Au> {
+            let mut items = vec!(item(10, 0., 1.), item(20, 0., 1.), item(30, 0., 1.));
+            for item in items.mut_iter() {
+                item.main_size = item.base_size
+            }
+            justify(items.as_mut_slice(), Au(120), justify_content, reversed);
+            main_positions(items.as_slice())
+        };
+
+        assert_eq!(justified(justify_content::flex_start, false), vec!(Au(0), Au(10), Au(30)));
+        assert_eq!(justified(justify_content::flex_end, false), vec!(Au(60), Au(70), Au(90)));
+        assert_eq!(justified(justify_content::center, false), vec!(Au(30), Au(40), Au(60)));
+        assert_eq!(justified(justify_content::space_between, false),
+                   vec!(Au(0), Au(40), Au(90)));
+        assert_eq!(justified(justify_content::space_around, false),
+                   vec!(Au(10), Au(40), Au(80)));
+
+        // Reversed main axes mirror the positions.
+        assert_eq!(justified(justify_content::flex_start, true), vec!(Au(110), Au(90), Au(60)));
+    }
+}
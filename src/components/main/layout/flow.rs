@@ -28,11 +28,13 @@
 use css::node_style::StyledNode;
 use layout::block::BlockFlow;
 use layout::context::LayoutContext;
+use layout::flex::FlexFlow;
 use layout::floats::Floats;
 //use layout::flow_list::{FlowList, Link, Rawlink, FlowListIterator, MutFlowListIterator};
 use layout::fragment::{Fragment, TableRowFragment, TableCellFragment};
 use layout::incremental::RestyleDamage;
 use layout::inline::InlineFlow;
+use layout::list_item::ListItemFlow;
 use layout::model::{CollapsibleMargins, IntrinsicWidths, MarginCollapseInfo};
 use layout::parallel::FlowParallelInfo;
 use layout::parallel;
@@ -76,6 +78,12 @@ pub trait Flow: fmt::Show + ToStr {
     /// Returns the class of flow that this is.
     fn class(&self) -> FlowClass;
 
+    /// If this is a block flow, returns the underlying object, borrowed immutably. Fails
+    /// otherwise.
+    fn as_immutable_block<'a>(&'a self) -> &'a BlockFlow {
+        fail!("called as_immutable_block() on a non-block flow")
+    }
+
     /// If this is a block flow, returns the underlying object. Fails otherwise.
     fn as_block<'a>(&'a mut self) -> &'a mut BlockFlow {
         debug!("called as_block() on a flow of type {}", self.class());
@@ -93,6 +101,22 @@ pub trait Flow: fmt::Show + ToStr {
         fail!("called as_inline() on a non-inline flow")
     }
 
+    /// If this is a list item flow, returns the underlying object, borrowed immutably. Fails
+    /// otherwise.
+    fn as_immutable_list_item<'a>(&'a self) -> &'a ListItemFlow {
+        fail!("called as_immutable_list_item() on a non-listitem flow")
+    }
+
+    /// If this is a list item flow, returns the underlying object. Fails otherwise.
+    fn as_list_item<'a>(&'a mut self) -> &'a mut ListItemFlow {
+        fail!("called as_list_item() on a non-listitem flow")
+    }
+
+    /// If this is a flex container flow, returns the underlying object. Fails otherwise.
+    fn as_flex<'a>(&'a mut self) -> &'a mut FlexFlow {
+        fail!("called as_flex() on a non-flex flow")
+    }
+
     /// If this is a table wrapper flow, returns the underlying object. Fails otherwise.
     fn as_table_wrapper<'a>(&'a mut self) -> &'a mut TableWrapperFlow {
         fail!("called as_table_wrapper() on a non-tablewrapper flow")
@@ -103,6 +127,12 @@ pub trait Flow: fmt::Show + ToStr {
         fail!("called as_table() on a non-table flow")
     }
 
+    /// If this is a table colgroup flow, returns the underlying object, borrowed immutably.
+    /// Fails otherwise.
+    fn as_immutable_table_colgroup<'a>(&'a self) -> &'a TableColGroupFlow {
+        fail!("called as_immutable_table_colgroup() on a non-tablecolgroup flow")
+    }
+
     /// If this is a table colgroup flow, returns the underlying object. Fails otherwise.
     fn as_table_colgroup<'a>(&'a mut self) -> &'a mut TableColGroupFlow {
         fail!("called as_table_colgroup() on a non-tablecolgroup flow")
@@ -418,6 +448,8 @@ pub trait MutableOwnedFlowUtils {
 pub enum FlowClass {
     BlockFlowClass,
     InlineFlowClass,
+    ListItemFlowClass,
+    FlexFlowClass,
     TableWrapperFlowClass,
     TableFlowClass,
     TableColGroupFlowClass,
@@ -727,8 +759,14 @@ impl Drop for BaseFlow {
 impl BaseFlow {
     #[inline]
     pub fn new(node: ThreadSafeLayoutNode) -> BaseFlow {
+        BaseFlow::from_restyle_damage(node.restyle_damage())
+    }
+
+    /// Creates the base of a flow with the given damage, for flows that are made without a node,
+    /// as in tests.
+    pub fn from_restyle_damage(restyle_damage: RestyleDamage) -> BaseFlow {
         BaseFlow {
-            restyle_damage: node.restyle_damage(),
+            restyle_damage: restyle_damage,
 
             children: FlowList::new(),
             next_sibling: None,
@@ -768,7 +806,7 @@ impl<'a> ImmutableFlowUtils for &'a Flow {
     /// Returns true if this flow is a block or a float flow.
     fn is_block_like(self) -> bool {
         match self.class() {
-            BlockFlowClass => true,
+            BlockFlowClass | ListItemFlowClass | FlexFlowClass => true,
             _ => false,
         }
     }
@@ -888,8 +926,7 @@ impl<'a> ImmutableFlowUtils for &'a Flow {
     /// containers.
     fn is_block_container(self) -> bool {
         match self.class() {
-            // TODO: Change this when inline-blocks are supported.
-            BlockFlowClass | TableCaptionFlowClass | TableCellFlowClass => {
+            BlockFlowClass | ListItemFlowClass | TableCaptionFlowClass | TableCellFlowClass => {
                 // FIXME: Actually check the type of the node
                 self.child_count() != 0
             }
@@ -897,10 +934,11 @@ impl<'a> ImmutableFlowUtils for &'a Flow {
         }
     }
 
-    /// Returns true if this flow is a block flow.
+    /// Returns true if this flow is a block flow. List items are block flows with a marker, and
+    /// flex containers are block flows that lay out their children differently.
     fn is_block_flow(self) -> bool {
         match self.class() {
-            BlockFlowClass => true,
+            BlockFlowClass | ListItemFlowClass | FlexFlowClass => true,
             _ => false,
         }
     }
@@ -1060,6 +1098,8 @@ impl<'a> MutableFlowUtils for &'a mut Flow {
         match self.class() {
             BlockFlowClass => self.as_block().build_display_list_block(layout_context),
             InlineFlowClass => self.as_inline().build_display_list_inline(layout_context),
+            ListItemFlowClass => self.as_list_item().build_display_list_list_item(layout_context),
+            FlexFlowClass => self.as_flex().build_display_list_flex(layout_context),
             TableWrapperFlowClass => {
                 self.as_table_wrapper().build_display_list_table_wrapper(layout_context)
             }
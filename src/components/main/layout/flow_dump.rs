@@ -0,0 +1,269 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A stable textual dump of the flow tree after layout, written with `--dump-flow-tree`.
+//!
+//! Each flow is written on its own line with its class and its position relative to its parent
+//! flow. It is followed by its fragments, with their border boxes relative to the flow, and then
+//! by its children, each indented by one more level. Text fragments also show the range of their
+//! text run that they hold and the text in it. Lengths are in CSS pixels.
+//!
+//! The border boxes are the ones that the FTL passes compute, which mostly keep them in the FTL
+//! attributes rather than in `Fragment::border_box`. The fragments of table column groups are
+//! not positioned by FTL, so they are written without geometry.
+//!
+//! Unlike the `Show` output of flows, the dump leaves out addresses and transient layout state,
+//! so the dump of a page can be compared against an expected file and a layout change shows up as
+//! a text diff.
+
+use layout::flow::{BlockFlowClass, FlexFlowClass, Flow, FlowClass, InlineFlowClass};
+use layout::flow::{ListItemFlowClass, TableCaptionFlowClass, TableCellFlowClass};
+use layout::flow::{TableColGroupFlowClass, TableFlowClass, TableRowFlowClass};
+use layout::flow::{TableRowGroupFlowClass, TableWrapperFlowClass};
+use layout::flow;
+use layout::fragment::{Fragment, InlineBlockFragment, ScannedTextFragment};
+use layout::fragment::{UnscannedTextFragment};
+
+use geom::point::Point2D;
+use geom::rect::Rect;
+use servo_util::geometry::Au;
+use servo_util::geometry;
+use std::io::{IoResult, Writer};
+
+/// Writes the flow tree rooted at the given flow.
+pub fn write_flow_tree(writer: &mut Writer, flow: &Flow) -> IoResult<()> {
+    write_flow(writer, flow, 0)
+}
+
+fn write_flow(writer: &mut Writer, flow: &Flow, level: uint) -> IoResult<()> {
+    try!(write_indent(writer, level));
+    try!(write!(writer, "{} {}\n",
+                flow_class_name(flow.class()),
+                format_rect(&flow::base(flow).position)));
+
+    match flow.class() {
+        InlineFlowClass => {
+            let flow_y = flow::base(flow).ftl_attrs.absy;
+            for fragment in flow.as_immutable_inline().fragments.fragments.iter() {
+                // The line passes keep the right edge of the fragment within its line and its
+                // top in page coordinates.
+                let origin = Point2D(fragment.ftl_attrs.right - fragment.border_box.size.width,
+                                     fragment.ftl_attrs.posy - flow_y);
+                let border_box = Rect(origin, fragment.border_box.size);
+                try!(write_fragment(writer, "", fragment, Some(border_box), level + 1));
+            }
+        }
+        ListItemFlowClass => {
+            let list_item = flow.as_immutable_list_item();
+            try!(write_fragment(writer,
+                                "",
+                                &list_item.block_flow.fragment,
+                                Some(block_border_box(flow)),
+                                level + 1));
+            match list_item.marker {
+                Some(ref marker) => {
+                    // The marker is placed in page coordinates.
+                    let block = &list_item.block_flow;
+                    let origin = Point2D(block.base.ftl_attrs.absx + block.ftl_attrs.ml,
+                                         block.base.ftl_attrs.absy + block.ftl_attrs.mt);
+                    let marker_origin = Point2D(marker.border_box.origin.x - origin.x,
+                                                marker.border_box.origin.y - origin.y);
+                    let border_box = Rect(marker_origin, marker.border_box.size);
+                    try!(write_fragment(writer, "marker ", marker, Some(border_box), level + 1))
+                }
+                None => {}
+            }
+        }
+        TableColGroupFlowClass => {
+            let colgroup = flow.as_immutable_table_colgroup();
+            match colgroup.fragment {
+                Some(ref fragment) => try!(write_fragment(writer, "", fragment, None, level + 1)),
+                None => {}
+            }
+            for col in colgroup.cols.iter() {
+                try!(write_fragment(writer, "", col, None, level + 1));
+            }
+        }
+        _ => {
+            try!(write_fragment(writer,
+                                "",
+                                &flow.as_immutable_block().fragment,
+                                Some(block_border_box(flow)),
+                                level + 1))
+        }
+    }
+
+    for kid in flow::imm_child_iter(flow) {
+        try!(write_flow(writer, kid, level + 1));
+    }
+    Ok(())
+}
+
+/// The border box of the fragment of a block-like flow, which is the box of the flow itself.
+fn block_border_box(flow: &Flow) -> Rect<Au> {
+    Rect(Point2D(Au(0), Au(0)), flow::base(flow).position.size)
+}
+
+fn write_fragment(writer: &mut Writer,
+                  prefix: &str,
+                  fragment: &Fragment,
+                  border_box: Option<Rect<Au>>,
+                  level: uint)
+                  -> IoResult<()> {
+    try!(write_indent(writer, level));
+    try!(write!(writer, "{}{}", prefix, fragment.specific.name()));
+    match border_box {
+        Some(ref border_box) => try!(write!(writer, " {}", format_rect(border_box))),
+        None => {}
+    }
+    match fragment.specific {
+        ScannedTextFragment(ref info) => {
+            let (begin, end) = (info.range.begin().to_uint(), info.range.end().to_uint());
+            let text = info.run.text.as_slice().slice_chars(begin, end);
+            try!(write!(writer, " [{}..{}] \"{}\"", begin, end, text.escape_default()));
+        }
+        UnscannedTextFragment(ref info) => {
+            try!(write!(writer, " \"{}\"", info.text.escape_default()));
+        }
+        _ => {}
+    }
+    try!(write!(writer, "\n"));
+
+    // The flow of an inline-block is not a child of the inline flow, so write it under the
+    // fragment that holds it.
+    match fragment.specific {
+        InlineBlockFragment(ref info) => write_flow(writer, info.flow_ref.get(), level + 1),
+        _ => Ok(()),
+    }
+}
+
+fn write_indent(writer: &mut Writer, level: uint) -> IoResult<()> {
+    for _ in range(0, level) {
+        try!(writer.write_str("  "));
+    }
+    Ok(())
+}
+
+fn flow_class_name(class: FlowClass) -> &'static str {
+    match class {
+        BlockFlowClass => "BlockFlow",
+        InlineFlowClass => "InlineFlow",
+        ListItemFlowClass => "ListItemFlow",
+        FlexFlowClass => "FlexFlow",
+        TableWrapperFlowClass => "TableWrapperFlow",
+        TableFlowClass => "TableFlow",
+        TableColGroupFlowClass => "TableColGroupFlow",
+        TableRowGroupFlowClass => "TableRowGroupFlow",
+        TableRowFlowClass => "TableRowFlow",
+        TableCaptionFlowClass => "TableCaptionFlow",
+        TableCellFlowClass => "TableCellFlow",
+    }
+}
+
+/// Formats a rectangle as `(x, y) widthxheight` in CSS pixels.
+fn format_rect(rect: &Rect<Au>) -> ~str {
+    format!("({}, {}) {}x{}",
+            geometry::to_frac_px(rect.origin.x),
+            geometry::to_frac_px(rect.origin.y),
+            geometry::to_frac_px(rect.size.width),
+            geometry::to_frac_px(rect.size.height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_rect, write_flow_tree};
+    use layout::block::BlockFlow;
+    use layout::flow::{BaseFlow, Flow, MutableOwnedFlowUtils};
+    use layout::fragment::{Fragment, GenericFragment, InlineBlockFragment};
+    use layout::fragment::{InlineBlockFragmentInfo, SpecificFragmentInfo, UnscannedTextFragment};
+    use layout::fragment::{UnscannedTextFragmentInfo};
+    use layout::incremental::RestyleDamage;
+    use layout::inline::{InlineFlow, InlineFragments};
+
+    use geom::point::Point2D;
+    use geom::rect::Rect;
+    use geom::size::Size2D;
+    use gfx::display_list::OpaqueNode;
+    use servo_util::geometry::Au;
+    use std::io::MemWriter;
+    use std::str;
+    use style::{ComputedValues, cascade};
+    use sync::Arc;
+
+    fn rect(x: int, y: int, width: int, height: int) -> Rect<Au> {
+        Rect(Point2D(Au::from_px(x), Au::from_px(y)),
+             Size2D(Au::from_px(width), Au::from_px(height)))
+    }
+
+    fn fragment(style: &Arc<ComputedValues>, specific: SpecificFragmentInfo) -> Fragment {
+        Fragment::from_opaque_node_and_style(OpaqueNode(0), style.clone(), specific)
+    }
+
+    /// Returns a fragment of an inline flow at the top of the page that the line passes put at
+    /// the given place in its flow.
+    fn inline_fragment(style: &Arc<ComputedValues>,
+                       specific: SpecificFragmentInfo,
+                       border_box: Rect<Au>)
+                       -> Fragment {
+        let mut fragment = fragment(style, specific);
+        fragment.border_box.size = border_box.size;
+        fragment.ftl_attrs.right = border_box.origin.x + border_box.size.width;
+        fragment.ftl_attrs.posy = border_box.origin.y;
+        fragment
+    }
+
+    fn block_flow(style: &Arc<ComputedValues>, position: Rect<Au>) -> Box<Flow:Share> {
+        let mut flow = box BlockFlow::from_base_and_fragment(
+            BaseFlow::from_restyle_damage(RestyleDamage::none()),
+            fragment(style, GenericFragment));
+        flow.base.position = position;
+        flow as Box<Flow:Share>
+    }
+
+    #[test]
+    fn test_format_rect() {
+        let rect = Rect(Point2D(Au(480), Au(30)), Size2D(Au(47040), Au(1080)));
+        assert_eq!(format_rect(&rect), "(8, 0.5) 784x18".to_owned());
+    }
+
+    #[test]
+    fn test_write_flow_tree() {
+        let (style, _) = cascade([].as_slice(), false, None, None);
+        let style = Arc::new(style);
+
+        // A block holding a line with some text and an inline-block.
+        let mut fragments = InlineFragments::new();
+        let text = UnscannedTextFragmentInfo {
+            text: "Hello \"world\"".to_owned(),
+        };
+        fragments.push(inline_fragment(&style, UnscannedTextFragment(text), rect(0, 0, 90, 18)),
+                       style.clone());
+        let inline_block = InlineBlockFragmentInfo::new(block_flow(&style, rect(0, 0, 20, 10)));
+        fragments.push(inline_fragment(&style,
+                                       InlineBlockFragment(inline_block),
+                                       rect(90, 8, 20, 10)),
+                       style.clone());
+        let mut inline_flow = box InlineFlow::from_base_and_fragments(
+            BaseFlow::from_restyle_damage(RestyleDamage::none()),
+            fragments);
+        inline_flow.base.position = rect(8, 8, 784, 18);
+
+        let mut root = block_flow(&style, rect(0, 0, 800, 34));
+        root.add_new_child(inline_flow as Box<Flow:Share>);
+
+        let mut writer = MemWriter::new();
+        write_flow_tree(&mut writer, &*root).unwrap();
+        let lines: Vec<&str> = str::from_utf8(writer.get_ref()).unwrap().lines().collect();
+        assert_eq!(lines, vec!(
+            "BlockFlow (0, 0) 800x34",
+            "  GenericFragment (0, 0) 800x34",
+            "  InlineFlow (8, 8) 784x18",
+            "    UnscannedTextFragment (0, 0) 90x18 \"Hello \\\"world\\\"\"",
+            "    InlineBlockFragment (90, 8) 20x10",
+            "      BlockFlow (0, 0) 20x10",
+            "        GenericFragment (0, 0) 20x10"));
+
+        root.destroy();
+    }
+}
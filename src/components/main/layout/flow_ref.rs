@@ -0,0 +1,80 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Reference-counted pointers to flows.
+//!
+//! Fragments are cloned freely during line breaking, so a fragment that owns a flow, such as an
+//! inline-block fragment, shares it between all of its copies through a `FlowRef`.
+
+use layout::flow::{Flow, MutableOwnedFlowUtils};
+
+use std::cast;
+use std::ptr;
+use std::sync::atomics::{AtomicUint, SeqCst};
+
+struct FlowRefInner {
+    ref_count: AtomicUint,
+    flow: Box<Flow:Share>,
+}
+
+pub struct FlowRef {
+    ptr: *mut FlowRefInner,
+}
+
+impl FlowRef {
+    pub fn new(flow: Box<Flow:Share>) -> FlowRef {
+        unsafe {
+            FlowRef {
+                ptr: cast::transmute(box FlowRefInner {
+                    ref_count: AtomicUint::new(1),
+                    flow: flow,
+                }),
+            }
+        }
+    }
+
+    pub fn get<'a>(&'a self) -> &'a Flow {
+        unsafe {
+            let flow: &'a Flow = &*(*self.ptr).flow;
+            flow
+        }
+    }
+
+    /// Returns the flow mutably.
+    ///
+    /// This is unsafe because all the clones of this `FlowRef` point to the same flow, so borrowing
+    /// one of them mutably does not make the returned reference unique. The caller must make sure
+    /// that no other reference to the flow is alive. This holds during layout, where the copies of
+    /// a fragment made while breaking lines replace the original in the fragment list, and a flow
+    /// is visited by one traversal at a time.
+    pub unsafe fn get_mut<'a>(&'a mut self) -> &'a mut Flow {
+        let flow: &'a mut Flow = &mut *(*self.ptr).flow;
+        flow
+    }
+}
+
+impl Clone for FlowRef {
+    fn clone(&self) -> FlowRef {
+        unsafe {
+            drop((*self.ptr).ref_count.fetch_add(1, SeqCst));
+        }
+        FlowRef {
+            ptr: self.ptr,
+        }
+    }
+}
+
+impl Drop for FlowRef {
+    fn drop(&mut self) {
+        unsafe {
+            if self.ptr.is_null() || (*self.ptr).ref_count.fetch_sub(1, SeqCst) > 1 {
+                return
+            }
+            let mut inner: Box<FlowRefInner> = cast::transmute(self.ptr);
+            inner.flow.destroy();
+            drop(inner);
+            self.ptr = ptr::mut_null()
+        }
+    }
+}
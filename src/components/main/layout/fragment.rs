@@ -5,11 +5,14 @@
 //! The `Box` type, which represents the leaves of the layout tree.
 
 use css::node_style::StyledNode;
+use layout::block::BlockFlow;
 use layout::construct::FlowConstructor;
 use layout::context::LayoutContext;
 use layout::floats::{ClearBoth, ClearLeft, ClearRight, ClearType};
 use layout::flow::Flow;
 use layout::flow;
+use layout::flow_ref::FlowRef;
+use layout::gradient;
 use layout::inline::{InlineFragmentContext, InlineMetrics};
 use layout::model::{Auto, IntrinsicWidths, MaybeAuto, Specified, specified};
 use layout::model;
@@ -22,12 +25,15 @@ use geom::{Point2D, Rect, Size2D, SideOffsets2D};
 use geom::approxeq::ApproxEq;
 use gfx::color::rgb;
 use gfx::display_list::{BackgroundAndBorderLevel, BaseDisplayItem, BorderDisplayItem};
-use gfx::display_list::{BorderDisplayItemClass, ClipDisplayItem, ClipDisplayItemClass};
-use gfx::display_list::{ContentStackingLevel, DisplayItem, DisplayList, ImageDisplayItem};
+use gfx::display_list::{BorderDisplayItemClass, BorderRadii, BoxShadowDisplayItem};
+use gfx::display_list::{BoxShadowDisplayItemClass, ClipDisplayItem, ClipDisplayItemClass};
+use gfx::display_list::{ContentStackingLevel, DisplayItem, DisplayList, GradientDisplayItem};
+use gfx::display_list::{GradientDisplayItemClass, ImageDisplayItem};
 use gfx::display_list::{ImageDisplayItemClass, LineDisplayItem};
 use gfx::display_list::{LineDisplayItemClass, OpaqueNode, PseudoDisplayItemClass};
 use gfx::display_list::{SolidColorDisplayItem, SolidColorDisplayItemClass, StackingLevel};
 use gfx::display_list::{TextDecorations, TextDisplayItem, TextDisplayItemClass};
+use gfx::display_list::{TextShadowDisplayItem, TextShadowDisplayItemClass};
 use gfx::font::FontStyle;
 use gfx::text::glyph::CharIndex;
 use gfx::text::text_run::TextRun;
@@ -49,6 +55,8 @@ use style::{ComputedValues, TElement, TNode, cascade_anonymous};
 use style::computed_values::{LengthOrPercentageOrAuto, overflow, LPA_Auto, background_attachment};
 use style::computed_values::{background_repeat, border_style, clear, position, text_align};
 use style::computed_values::{text_decoration, vertical_align, visibility, white_space};
+use style::computed_values::{LinearGradientImage, RadialGradientImage, UrlImage};
+use style::computed_values::{content, list_style_type};
 use sync::Arc;
 use url::Url;
 
@@ -115,6 +123,28 @@ pub enum SpecificFragmentInfo {
     TableRowFragment,
     TableWrapperFragment,
     UnscannedTextFragment(UnscannedTextFragmentInfo),
+    GeneratedContentFragment(GeneratedContentInfo),
+    InlineBlockFragment(InlineBlockFragmentInfo),
+}
+
+impl SpecificFragmentInfo {
+    /// Returns the name of this kind of fragment, as used in debugging output.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            GenericFragment => "GenericFragment",
+            IframeFragment(_) => "IframeFragment",
+            ImageFragment(_) => "ImageFragment",
+            ScannedTextFragment(_) => "ScannedTextFragment",
+            TableFragment => "TableFragment",
+            TableCellFragment => "TableCellFragment",
+            TableColumnFragment(_) => "TableColumnFragment",
+            TableRowFragment => "TableRowFragment",
+            TableWrapperFragment => "TableWrapperFragment",
+            UnscannedTextFragment(_) => "UnscannedTextFragment",
+            GeneratedContentFragment(_) => "GeneratedContentFragment",
+            InlineBlockFragment(_) => "InlineBlockFragment",
+        }
+    }
 }
 
 /// A fragment that represents a replaced content image and its accompanying borders, shadows, etc.
@@ -241,6 +271,33 @@ impl ScannedTextFragmentInfo {
             range: range,
         }
     }
+
+    /// Returns the display items that paint the text shadows in the given style beneath this
+    /// text, whose bounds are given, in painting order. The first shadow in the list is painted on
+    /// top, so the items come in reverse.
+    pub fn text_shadow_display_items(&self,
+                                     style: &ComputedValues,
+                                     node: OpaqueNode,
+                                     bounds: &Rect<Au>)
+                                     -> Vec<DisplayItem> {
+        style.get_inheritedtext().text_shadow.iter().rev().map(|shadow| {
+            let offset = Point2D(shadow.offset_x, shadow.offset_y);
+            TextShadowDisplayItemClass(box TextShadowDisplayItem {
+                base: BaseDisplayItem::new_for_shadow(*bounds,
+                                                      offset,
+                                                      shadow.blur_radius,
+                                                      Au(0),
+                                                      node,
+                                                      ContentStackingLevel),
+                text_run: self.run.clone(),
+                range: self.range,
+                text_bounds: *bounds,
+                offset: offset,
+                color: style.resolve_color(shadow.color).to_gfx_color(),
+                blur_radius: shadow.blur_radius,
+            })
+        }).collect()
+    }
 }
 
 #[deriving(Show)]
@@ -286,6 +343,57 @@ impl UnscannedTextFragmentInfo {
     }
 }
 
+/// Generated content whose text depends on the values of counters, which are only known once the
+/// whole flow tree has been constructed. `generated_content::resolve_generated_content()` turns
+/// these fragments into unscanned text fragments before layout.
+#[deriving(Clone)]
+pub enum GeneratedContentInfo {
+    /// The marker of a list item, showing the value of the `list-item` counter in the given style.
+    ListItemMarker(list_style_type::T),
+    /// The `content` of a `::before` or `::after` pseudo-element.
+    ContentItems(Vec<content::Content>),
+}
+
+/// A fragment that represents an inline-block element. It lays out as an atomic inline, like
+/// replaced content, and wraps the block flow of the element, which holds its borders, padding,
+/// margins and contents.
+#[deriving(Clone)]
+pub struct InlineBlockFragmentInfo {
+    /// The block flow of the element.
+    pub flow_ref: FlowRef,
+
+    /// The distance from the top of the margin box of the block flow to the baseline of this
+    /// fragment. This is set by the layout of the containing inline flow.
+    pub baseline: Au,
+}
+
+impl InlineBlockFragmentInfo {
+    pub fn new(flow: Box<Flow:Share>) -> InlineBlockFragmentInfo {
+        InlineBlockFragmentInfo {
+            flow_ref: FlowRef::new(flow),
+            baseline: Au(0),
+        }
+    }
+
+    /// Returns the distance from the top of the margin box of the given block flow, which must
+    /// have been laid out, to its baseline. Per CSS 2.1 § 10.8.1, this is the baseline of its last
+    /// line box, or its bottom margin edge if it has no line boxes or if its `overflow` is not
+    /// `visible`.
+    pub fn baseline_of_block(block_flow: &mut BlockFlow) -> Au {
+        let last_line_baseline = if block_flow.fragment.style().get_box().overflow ==
+                overflow::visible {
+            block_flow.baseline_offset_of_last_line_box()
+        } else {
+            None
+        };
+        let fragment = &block_flow.fragment;
+        fragment.margin.top + match last_line_baseline {
+            Some(baseline) => baseline,
+            None => fragment.border_box.size.height + fragment.margin.bottom,
+        }
+    }
+}
+
 /// A fragment that represents a table column.
 #[deriving(Clone)]
 pub struct TableColumnFragmentInfo {
@@ -416,7 +524,8 @@ impl Fragment {
             TableFragment | TableCellFragment => (false, true),
             TableWrapperFragment => (true, false),
             TableRowFragment => (false, false),
-            ScannedTextFragment(_) | TableColumnFragment(_) | UnscannedTextFragment(_) => {
+            ScannedTextFragment(_) | TableColumnFragment(_) | UnscannedTextFragment(_) |
+            GeneratedContentFragment(_) | InlineBlockFragment(_) => {
                 // Styles are irrelevant for these kinds of fragments.
                 return IntrinsicWidths::new()
             }
@@ -478,7 +587,9 @@ impl Fragment {
         // Compute vertical margins. Note that this value will be ignored by layout if the style
         // specifies `auto`.
         match self.specific {
-            TableFragment | TableCellFragment | TableRowFragment | TableColumnFragment(_) => {
+            TableFragment | TableCellFragment | TableRowFragment | TableColumnFragment(_) |
+            InlineBlockFragment(_) => {
+                // The margins of inline-blocks belong to their block flows.
                 self.margin.top = Au(0);
                 self.margin.bottom = Au(0)
             }
@@ -655,11 +766,13 @@ impl Fragment {
         // inefficient. What we really want is something like "nearest ancestor element that
         // doesn't have a fragment".
         let style = self.style();
+        let radii = model::border_radii_from_style(style, &absolute_bounds.size);
         let background_color = style.resolve_color(style.get_background().background_color);
         if !background_color.alpha.approx_eq(&0.0) {
             let display_item = box SolidColorDisplayItem {
                 base: BaseDisplayItem::new(*absolute_bounds, self.node, level),
                 color: background_color.to_gfx_color(),
+                radii: radii,
             };
 
             list.push(SolidColorDisplayItemClass(display_item))
@@ -668,11 +781,74 @@ impl Fragment {
         // The background image is painted on top of the background color.
         // Implements background image, per spec:
         // http://www.w3.org/TR/CSS21/colors.html#background
-        let background = style.get_background();
-        let image_url = match background.background_image {
+        match style.get_background().background_image {
+            Some(UrlImage(ref image_url)) => {
+                self.build_display_list_for_background_image(list,
+                                                             layout_context,
+                                                             level,
+                                                             absolute_bounds,
+                                                             radii,
+                                                             image_url)
+            }
+            _ => self.build_display_list_for_background_gradient_if_applicable(list,
+                                                                               level,
+                                                                               absolute_bounds),
+        }
+    }
+
+    /// Adds the display items necessary to paint the background gradient of this fragment to the
+    /// display list if it has one.
+    pub fn build_display_list_for_background_gradient_if_applicable(&self,
+                                                                    list: &mut DisplayList,
+                                                                    level: StackingLevel,
+                                                                    absolute_bounds: &Rect<Au>) {
+        let style = self.style();
+        let radii = model::border_radii_from_style(style, &absolute_bounds.size);
+        let (gradient, repeating) = match style.get_background().background_image {
+            None | Some(UrlImage(_)) => return,
+            Some(LinearGradientImage(ref linear)) => {
+                (gradient::linear_gradient(style, linear, absolute_bounds), linear.repeating)
+            }
+            Some(RadialGradientImage(ref radial)) => {
+                (gradient::radial_gradient(style, radial, absolute_bounds), radial.repeating)
+            }
+        };
+
+        // Gradients fill the background without regard to `background-position` and
+        // `background-repeat`.
+        //
+        // TODO: Support `background-attachment: fixed` for gradients.
+        let (geometry, stops) = match gradient {
             None => return,
-            Some(ref image_url) => image_url,
+            Some(gradient) => gradient,
         };
+        let gradient_display_item = GradientDisplayItemClass(box GradientDisplayItem {
+            base: BaseDisplayItem::new(*absolute_bounds, self.node, level),
+            geometry: geometry,
+            stops: stops,
+            repeating: repeating,
+        });
+        if radii.is_square() {
+            list.push(gradient_display_item)
+        } else {
+            let base = BaseDisplayItem::new(*absolute_bounds, self.node, level);
+            let mut clip_display_item = box ClipDisplayItem::new_rounded(base,
+                                                                         radii,
+                                                                         DisplayList::new());
+            clip_display_item.children.push(gradient_display_item);
+            list.push(ClipDisplayItemClass(clip_display_item))
+        }
+    }
+
+    /// Adds the display items necessary to paint a background image loaded from the given URL.
+    fn build_display_list_for_background_image(&self,
+                                               list: &mut DisplayList,
+                                               layout_context: &LayoutContext,
+                                               level: StackingLevel,
+                                               absolute_bounds: &Rect<Au>,
+                                               radii: BorderRadii<Au>,
+                                               image_url: &Url) {
+        let background = self.style().get_background();
 
         let mut holder = ImageHolder::new(image_url.clone(), layout_context.image_cache.clone());
         let image = match holder.get_image() {
@@ -697,17 +873,23 @@ impl Fragment {
         let clip_display_item;
         match background.background_attachment {
             background_attachment::scroll => {
-                clip_display_item = None;
+                // Rounded corners clip the background image.
+                clip_display_item = if radii.is_square() {
+                    None
+                } else {
+                    let base = BaseDisplayItem::new(bounds, self.node, level);
+                    Some(box ClipDisplayItem::new_rounded(base, radii, DisplayList::new()))
+                };
                 bounds.origin.x = bounds.origin.x + horizontal_position;
                 bounds.origin.y = bounds.origin.y + vertical_position;
                 bounds.size.width = bounds.size.width - horizontal_position;
                 bounds.size.height = bounds.size.height - vertical_position;
             }
             background_attachment::fixed => {
-                clip_display_item = Some(box ClipDisplayItem {
-                    base: BaseDisplayItem::new(bounds, self.node, level),
-                    children: DisplayList::new(),
-                });
+                let base = BaseDisplayItem::new(bounds, self.node, level);
+                clip_display_item = Some(box ClipDisplayItem::new_rounded(base,
+                                                                          radii,
+                                                                          DisplayList::new()));
 
                 bounds = Rect {
                     origin: Point2D(horizontal_position, vertical_position),
@@ -749,6 +931,48 @@ impl Fragment {
         }
     }
 
+    /// Adds the display items necessary to paint the box shadows of this fragment to the display
+    /// list if necessary. Shadows outside the box are painted below the background and shadows
+    /// inside the box above it, so the two kinds are added by separate calls.
+    pub fn build_display_list_for_box_shadows_if_applicable(&self,
+                                                            list: &mut DisplayList,
+                                                            level: StackingLevel,
+                                                            absolute_bounds: &Rect<Au>,
+                                                            inset: bool) {
+        let style = self.style();
+        let radii = model::border_radii_from_style(style, &absolute_bounds.size);
+
+        // The first shadow in the list is painted on top, so add them in reverse.
+        for shadow in style.get_effects().box_shadow.iter().rev() {
+            if shadow.inset != inset {
+                continue
+            }
+
+            let offset = Point2D(shadow.offset_x, shadow.offset_y);
+            let base = if inset {
+                BaseDisplayItem::new(*absolute_bounds, self.node, level)
+            } else {
+                BaseDisplayItem::new_for_shadow(*absolute_bounds,
+                                                offset,
+                                                shadow.blur_radius,
+                                                shadow.spread_radius,
+                                                self.node,
+                                                level)
+            };
+            let display_item = box BoxShadowDisplayItem {
+                base: base,
+                box_bounds: *absolute_bounds,
+                radii: radii.clone(),
+                offset: offset,
+                color: style.resolve_color(shadow.color).to_gfx_color(),
+                blur_radius: shadow.blur_radius,
+                spread_radius: shadow.spread_radius,
+                inset: inset,
+            };
+            list.push(BoxShadowDisplayItemClass(display_item))
+        }
+    }
+
     /// Adds the display items necessary to paint the borders of this fragment to a display list if
     /// necessary.
     pub fn build_display_list_for_borders_if_applicable(&self,
@@ -780,7 +1004,8 @@ impl Fragment {
             style: SideOffsets2D::new(style.get_border().border_top_style,
                                       style.get_border().border_right_style,
                                       style.get_border().border_bottom_style,
-                                      style.get_border().border_left_style)
+                                      style.get_border().border_left_style),
+            radius: model::border_radii_from_style(style, &abs_bounds.size),
         };
 
         list.push(BorderDisplayItemClass(border_display_item))
@@ -800,7 +1025,8 @@ impl Fragment {
             base: BaseDisplayItem::new(absolute_fragment_bounds, self.node, ContentStackingLevel),
             border: debug_border,
             color: SideOffsets2D::new_all_same(rgb(0, 0, 200)),
-            style: SideOffsets2D::new_all_same(border_style::solid)
+            style: SideOffsets2D::new_all_same(border_style::solid),
+            radius: BorderRadii::zero(),
         };
         display_list.push(BorderDisplayItemClass(border_display_item));
 
@@ -830,7 +1056,8 @@ impl Fragment {
             base: BaseDisplayItem::new(absolute_fragment_bounds, self.node, ContentStackingLevel),
             border: debug_border,
             color: SideOffsets2D::new_all_same(rgb(0, 0, 200)),
-            style: SideOffsets2D::new_all_same(border_style::solid)
+            style: SideOffsets2D::new_all_same(border_style::solid),
+            radius: BorderRadii::zero(),
         };
         display_list.push(BorderDisplayItemClass(border_display_item))
     }
@@ -869,6 +1096,13 @@ impl Fragment {
             return accumulator
         }
 
+        // The block flow of an inline-block paints its own backgrounds, borders and contents. See
+        // `InlineFlow::build_display_list_inline()`.
+        match self.specific {
+            InlineBlockFragment(_) => return accumulator,
+            _ => {}
+        }
+
         if !absolute_fragment_bounds.intersects(&layout_context.dirty) {
             debug!("Fragment::build_display_list: Did not intersect...");
             return accumulator
@@ -884,11 +1118,19 @@ impl Fragment {
             let base_display_item = box BaseDisplayItem::new(absolute_fragment_bounds, self.node, level);
             display_list.push(PseudoDisplayItemClass(base_display_item));
 
-            // Add the background to the list, if applicable.
+            // Add the box shadows and the background to the list, if applicable.
+            self.build_display_list_for_box_shadows_if_applicable(display_list,
+                                                                  level,
+                                                                  &absolute_fragment_bounds,
+                                                                  false);
             self.build_display_list_for_background_if_applicable(display_list,
                                                                  layout_context,
                                                                  level,
                                                                  &absolute_fragment_bounds);
+            self.build_display_list_for_box_shadows_if_applicable(display_list,
+                                                                  level,
+                                                                  &absolute_fragment_bounds,
+                                                                  true);
 
             // Add a border, if applicable.
             //
@@ -902,6 +1144,7 @@ impl Fragment {
         // Add a clip, if applicable.
         match self.specific {
             UnscannedTextFragment(_) => fail!("Shouldn't see unscanned fragments here."),
+            GeneratedContentFragment(_) => fail!("Shouldn't see unresolved generated content here."),
             TableColumnFragment(_) => fail!("Shouldn't see table column fragments here."),
             ScannedTextFragment(ref text_fragment) => {
                 // Compute text color.
@@ -922,6 +1165,14 @@ impl Fragment {
                 bounds.origin.x = bounds.origin.x + self.border_padding.left;
                 bounds.size.width = bounds.size.width - self.border_padding.horizontal();
 
+                // Add the text shadows, if any, beneath the text.
+                let shadows = text_fragment.text_shadow_display_items(self.style(),
+                                                                      self.node,
+                                                                      &bounds);
+                for shadow in shadows.move_iter() {
+                    accumulator.push(display_list, shadow);
+                }
+
                 // Create the text fragment.
                 let text_display_item = box TextDisplayItem {
                     base: BaseDisplayItem::new(bounds, self.node, ContentStackingLevel),
@@ -941,7 +1192,7 @@ impl Fragment {
                                                                           text_fragment))
             },
             GenericFragment | IframeFragment(..) | TableFragment | TableCellFragment | TableRowFragment |
-            TableWrapperFragment => {
+            TableWrapperFragment | InlineBlockFragment(_) => {
                 // FIXME(pcwalton): This is a bit of an abuse of the logging infrastructure. We
                 // should have a real `SERVO_DEBUG` system.
                 debug!("{:?}", self.build_debug_borders_around_fragment(display_list, flow_origin))
@@ -1034,7 +1285,18 @@ impl Fragment {
                 result.minimum_width = geometry::max(result.minimum_width, min_line_width);
                 result.preferred_width = geometry::max(result.preferred_width, max_line_width);
             }
+            InlineBlockFragment(ref mut info) => {
+                // The intrinsic widths of the block flow include its borders, padding and margins.
+                let block_flow = unsafe { info.flow_ref.get_mut() }.as_block();
+                result.minimum_width =
+                    geometry::max(result.minimum_width,
+                                  block_flow.base.intrinsic_widths.total_minimum_width());
+                result.preferred_width =
+                    geometry::max(result.preferred_width,
+                                  block_flow.base.intrinsic_widths.total_preferred_width());
+            }
             UnscannedTextFragment(..) => fail!("Unscanned text fragments should have been scanned by now!"),
+            GeneratedContentFragment(..) => fail!("Generated content should have been resolved by now!"),
         }
 
         // Take borders and padding for parent inline fragments into account, if necessary.
@@ -1067,8 +1329,10 @@ impl Fragment {
                 let text_bounds = run.metrics_for_range(range).bounding_box;
                 text_bounds.size.width
             }
+            InlineBlockFragment(_) => self.border_box.size.width,
             TableColumnFragment(_) => fail!("Table column fragments do not have width"),
             UnscannedTextFragment(_) => fail!("Unscanned text fragments should have been scanned by now!"),
+            GeneratedContentFragment(_) => fail!("Generated content should have been resolved by now!"),
         }
     }
 
@@ -1090,8 +1354,10 @@ impl Fragment {
                 let em_size = text_bounds.size.height;
                 self.calculate_line_height(em_size)
             }
+            InlineBlockFragment(_) => self.border_box.size.height,
             TableColumnFragment(_) => fail!("Table column fragments do not have height"),
             UnscannedTextFragment(_) => fail!("Unscanned text fragments should have been scanned by now!"),
+            GeneratedContentFragment(_) => fail!("Generated content should have been resolved by now!"),
         }
     }
 
@@ -1121,9 +1387,10 @@ impl Fragment {
             -> Option<(SplitInfo, Option<SplitInfo>, Arc<Box<TextRun>> /* TODO(bjz): remove */)> {
         match self.specific {
             GenericFragment | IframeFragment(_) | ImageFragment(_) | TableFragment | TableCellFragment |
-            TableRowFragment | TableWrapperFragment => None,
+            TableRowFragment | TableWrapperFragment | InlineBlockFragment(_) => None,
             TableColumnFragment(_) => fail!("Table column fragments do not need to split"),
             UnscannedTextFragment(_) => fail!("Unscanned text fragments should have been scanned by now!"),
+            GeneratedContentFragment(_) => fail!("Generated content should have been resolved by now!"),
             ScannedTextFragment(ref text_fragment_info) => {
                 let mut new_line_pos = self.new_line_pos.clone();
                 let cur_new_line_pos = new_line_pos.shift().unwrap();
@@ -1161,9 +1428,10 @@ impl Fragment {
             -> Option<(Option<SplitInfo>, Option<SplitInfo>, Arc<Box<TextRun>> /* TODO(bjz): remove */)> {
         match self.specific {
             GenericFragment | IframeFragment(_) | ImageFragment(_) | TableFragment | TableCellFragment |
-            TableRowFragment | TableWrapperFragment => None,
+            TableRowFragment | TableWrapperFragment | InlineBlockFragment(_) => None,
             TableColumnFragment(_) => fail!("Table column fragments do not have width"),
             UnscannedTextFragment(_) => fail!("Unscanned text fragments should have been scanned by now!"),
+            GeneratedContentFragment(_) => fail!("Generated content should have been resolved by now!"),
             ScannedTextFragment(ref text_fragment_info) => {
                 let mut pieces_processed_count: uint = 0;
                 let mut remaining_width: Au = max_width;
@@ -1270,6 +1538,15 @@ impl Fragment {
             TableWrapperFragment => return,
             TableColumnFragment(_) => fail!("Table column fragments do not have width"),
             UnscannedTextFragment(_) => fail!("Unscanned text fragments should have been scanned by now!"),
+            GeneratedContentFragment(_) => fail!("Generated content should have been resolved by now!"),
+            InlineBlockFragment(ref mut info) => {
+                // The block flow has already been given its shrink-to-fit width. This fragment is
+                // its margin box.
+                let block_flow = unsafe { info.flow_ref.get_mut() }.as_block();
+                self.border_box.size.width = block_flow.fragment.border_box.size.width +
+                    block_flow.fragment.margin.horizontal();
+                return
+            }
             ImageFragment(_) | ScannedTextFragment(_) => {}
         };
 
@@ -1321,6 +1598,14 @@ impl Fragment {
             TableWrapperFragment => return,
             TableColumnFragment(_) => fail!("Table column fragments do not have height"),
             UnscannedTextFragment(_) => fail!("Unscanned text fragments should have been scanned by now!"),
+            GeneratedContentFragment(_) => fail!("Generated content should have been resolved by now!"),
+            InlineBlockFragment(ref mut info) => {
+                let block_flow = unsafe { info.flow_ref.get_mut() }.as_block();
+                self.border_box.size.height = block_flow.fragment.border_box.size.height +
+                    block_flow.fragment.margin.vertical();
+                info.baseline = InlineBlockFragmentInfo::baseline_of_block(block_flow);
+                return
+            }
             ImageFragment(_) | ScannedTextFragment(_) => {}
         }
 
@@ -1382,6 +1667,13 @@ impl Fragment {
                 let line_height = self.calculate_line_height(font_size);
                 InlineMetrics::from_font_metrics(&text_fragment.run.font_metrics, line_height)
             }
+            InlineBlockFragment(ref info) => {
+                InlineMetrics {
+                    height_above_baseline: info.baseline,
+                    depth_below_baseline: self.border_box.size.height - info.baseline,
+                    ascent: info.baseline,
+                }
+            }
             _ => {
                 InlineMetrics {
                     height_above_baseline: self.border_box.size.height,
@@ -1452,19 +1744,7 @@ impl Fragment {
 impl fmt::Show for Fragment {
     /// Outputs a debugging string describing this fragment.
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        try!(write!(f.buf, "({} ",
-            match self.specific {
-                GenericFragment => "GenericFragment",
-                IframeFragment(_) => "IframeFragment",
-                ImageFragment(_) => "ImageFragment",
-                ScannedTextFragment(_) => "ScannedTextFragment",
-                TableFragment => "TableFragment",
-                TableCellFragment => "TableCellFragment",
-                TableColumnFragment(_) => "TableColumnFragment",
-                TableRowFragment => "TableRowFragment",
-                TableWrapperFragment => "TableWrapperFragment",
-                UnscannedTextFragment(_) => "UnscannedTextFragment",
-        }));
+        try!(write!(f.buf, "({} ", self.specific.name()));
         try!(self.side_offsets_debug_fmt("bp", self.border_padding, f));
         try!(write!(f.buf, " "));
         try!(self.side_offsets_debug_fmt("m", self.margin, f));
@@ -1484,10 +1764,9 @@ impl ChildDisplayListAccumulator {
         ChildDisplayListAccumulator {
             clip_display_item: match style.get_box().overflow {
                 overflow::hidden => {
-                    Some(box ClipDisplayItem {
-                        base: BaseDisplayItem::new(bounds, node, level),
-                        children: DisplayList::new(),
-                    })
+                    let base = BaseDisplayItem::new(bounds, node, level);
+                    let radii = model::border_radii_from_style(style, &bounds.size);
+                    Some(box ClipDisplayItem::new_rounded(base, radii, DisplayList::new()))
                 }
                 _ => None,
             }
@@ -3,20 +3,30 @@ use std::mem;
 use std::vec::MoveItems;
 use layout;
 use layout::model::MaybeAuto::;
+use layout::model;
 use layout::fragment::{Fragment, SplitInfo, ScannedTextFragmentInfo,
-                       ScannedTextFragment};
+                       ScannedTextFragment, InlineBlockFragment};
 use layout::inline::InlineFragments;
 use collections::{Deque, RingBuf};
-use layout::flow::{Flow, BlockFlowClass,InlineFlowClass,TableWrapperFlowClass,
-                   TableFlowClass,TableColGroupFlowClass,TableRowGroupFlowClass,
-                   TableRowFlowClass,TableCaptionFlowClass,TableCellFlowClass};
+use collections::dlist::DList;
+use layout::flow;
+use layout::block::{BlockFlow, build_fixed_layer, build_scroll_layer};
+use layout::list_item::ListItemFlow;
+use layout::flow::{ImmutableFlowUtils};
+use layout::ftl_layout::FtlNode;
+use layout::flow::{Flow, BlockFlowClass,InlineFlowClass,ListItemFlowClass,FlexFlowClass,
+                   TableWrapperFlowClass,TableFlowClass,TableColGroupFlowClass,
+                   TableRowGroupFlowClass,TableRowFlowClass,TableCaptionFlowClass,
+                   TableCellFlowClass};
 use layout::util::ToGfxColor;
 
 use geom::approxeq::ApproxEq;
 
 use servo_util::geometry::Au;
+use servo_util::geometry;
 use gfx::text::glyph::CharIndex;
 use geom::{Point2D, Rect, Size2D, SideOffsets2D};
+use geom::matrix2d::Matrix2D;
 use style::computed_values::{LengthOrPercentageOrAuto, LPA_Auto};
 use gfx::display_list::{DisplayList, BaseDisplayItem,
                         BorderDisplayItem, BorderDisplayItemClass};
@@ -25,6 +35,8 @@ use gfx::display_list::{SolidColorDisplayItem, SolidColorDisplayItemClass,
 use gfx::display_list::{TextDecorations, TextDisplayItem, TextDisplayItemClass};
 
 use gfx::display_list::OpaqueNode;
+use gfx::font_context::FontContext;
+use gfx::render_task::RenderLayer;
 use layout::fragment::SpecificFragmentInfo;
 use style::ComputedValues;
 use sync::Arc;
@@ -135,8 +147,9 @@ pub fn add_border(list: FTLDisplayList, frag: &Fragment,
     let left_color = style.resolve_color(style.get_border().border_left_color);
 
     let border = SideOffsets2D::new(t, r, b, l);
+    let bounds = make_rect(x, y, width, height);
     let border_display_item = box BorderDisplayItem {
-        base: BaseDisplayItem::new(make_rect(x, y, width, height),
+        base: BaseDisplayItem::new(bounds,
                                    frag.node,
                                    BackgroundAndBordersStackingLevel),
         border: border,
@@ -147,33 +160,52 @@ pub fn add_border(list: FTLDisplayList, frag: &Fragment,
         style: SideOffsets2D::new(style.get_border().border_top_style,
                                       style.get_border().border_right_style,
                                       style.get_border().border_bottom_style,
-                                      style.get_border().border_left_style)
+                                      style.get_border().border_left_style),
+        radius: model::border_radii_from_style(style, &bounds.size),
     };
     let mut list = list;
     list.push(BorderDisplayItemClass(border_display_item));
     Some(list)
 }
 
+/// Adds the background color and gradient of the given fragment, whose border box has the given
+/// bounds, to the list, along with its box shadows: those outside the box go below the background
+/// and those inside the box above it.
 pub fn add_background(list: FTLDisplayList, frag: &Fragment,
                      x: Au, y: Au, width: Au, height: Au) -> FTLDisplayList {
-    let list = list.unwrap();
+    let mut list = list.unwrap();
     let style = frag.style();
+    let bounds = make_rect(x, y, width, height);
+    frag.build_display_list_for_box_shadows_if_applicable(&mut list,
+                                                          BackgroundAndBordersStackingLevel,
+                                                          &bounds,
+                                                          false);
     let background_color = style.resolve_color(style.get_background().background_color);
-    let mut list = list;
     if !background_color.alpha.approx_eq(&0.0) {
         let solid_color_display_item = box SolidColorDisplayItem {
-            base: BaseDisplayItem::new(make_rect(x, y, width, height),
+            base: BaseDisplayItem::new(bounds,
                                        frag.node,
                                        BackgroundAndBordersStackingLevel),
             color: background_color.to_gfx_color(),
+            radii: model::border_radii_from_style(style, &bounds.size),
         };
         list.push(SolidColorDisplayItemClass(solid_color_display_item));
     }
+
+    // FIXME: Background images other than gradients need the image cache, which the FTL passes
+    // do not have access to yet.
+    frag.build_display_list_for_background_gradient_if_applicable(&mut list,
+                                                                  BackgroundAndBordersStackingLevel,
+                                                                  &bounds);
+    frag.build_display_list_for_box_shadows_if_applicable(&mut list,
+                                                          BackgroundAndBordersStackingLevel,
+                                                          &bounds,
+                                                          true);
     Some(list)
 }
 
 pub fn add_text_fragment(list: FTLDisplayList,
-                         specific: SpecificFragmentInfo,
+                         mut specific: SpecificFragmentInfo,
                          style: Arc<ComputedValues>,
                          node: OpaqueNode,
                          x: Au, y: Au, width: Au, height: Au) -> FTLDisplayList {
@@ -200,6 +232,12 @@ pub fn add_text_fragment(list: FTLDisplayList,
 
             //debug!("{}", text_fragment.range);
 
+            // Add the text shadows, if any, beneath the text.
+            let shadows = text_fragment.text_shadow_display_items(style, node, &bounds);
+            for shadow in shadows.move_iter() {
+                list.push(shadow);
+            }
+
             // Create the text fragment.
             let text_display_item = box TextDisplayItem {
                 base: BaseDisplayItem::new(bounds, node, ContentStackingLevel),
@@ -210,6 +248,23 @@ pub fn add_text_fragment(list: FTLDisplayList,
             };
             list.push(TextDisplayItemClass(text_display_item));
         },
+        InlineBlockFragment(ref mut info) => {
+            // Position the block flow at the margin box of the fragment, then build its display
+            // list.
+            let block_flow = unsafe { info.flow_ref.get_mut() };
+            {
+                let block_base = flow::mut_base(block_flow);
+                block_base.ftl_attrs.absx = x;
+                block_base.ftl_attrs.absy = y;
+                block_base.ftl_attrs.containingx = Au(0);
+                block_base.ftl_attrs.containingy = Au(0);
+            }
+            inherit(|node: &mut FtlNode| node.visit_2(), as_ftl_node(block_flow));
+            synthesize(|node: &mut FtlNode| node.visit_3(), as_ftl_node(block_flow));
+            build_stacking_context_if_necessary(block_flow.as_block());
+            let block_list = flow::mut_base(block_flow).ftl_attrs.display_list.take_unwrap();
+            list.push_all_move(block_list.flatten(ContentStackingLevel));
+        },
         // fail!("Each fragment in inline flow should be a ScannedTextFragment?")
         _ => {},
     }
@@ -238,6 +293,267 @@ pub fn synthesize(visit: |&mut layout::ftl_layout::FtlNode|,node: &mut layout::f
     visit(node);
 }
 
+/// Runs the FTL layout passes over the flow tree rooted at `root` like the generated `layout`,
+/// except that the passes are driven flow by flow so that `position: fixed` boxes can be taken
+/// out of the flow and placed in the viewport of the given size, and so that the display items
+/// of fixed boxes and of the contents of scrollable boxes can be moved into render layers of
+/// their own. Returns those layers, bottommost first.
+pub fn layout_with_layers(root: &mut Flow,
+                          viewport_size: Size2D<Au>,
+                          font_context: &mut FontContext)
+                          -> DList<RenderLayer> {
+    assign_widths(&mut *root, &viewport_size);
+    assign_heights(&mut *root, font_context);
+    assign_positions(&mut *root, &viewport_size);
+    let mut layers = DList::new();
+    build_display_lists(root, &mut layers, false);
+    layers
+}
+
+/// The width pass of `layout_with_layers`, which runs `visit_0` top-down. Fixed boxes are sized
+/// against the viewport rather than their parent.
+fn assign_widths(flow: &mut Flow, viewport_size: &Size2D<Au>) {
+    visit_widths(&mut *flow);
+    for kid in flow::child_iter(flow) {
+        if kid.is_fixed() {
+            flow::mut_base(kid).ftl_attrs.availablewidth = viewport_size.width
+        }
+        assign_widths(kid, viewport_size)
+    }
+}
+
+/// Runs `visit_0` on a single flow. The generated pass sizes a block with a specified width to
+/// that width whatever its available width is, so a block that a flex container has given a main
+/// size is resized to it afterward.
+fn visit_widths(flow: &mut Flow) {
+    as_ftl_node(&mut *flow).visit_0();
+    if flow.is_block_like() {
+        set_flex_item_width(flow.as_block())
+    }
+}
+
+/// Makes the margin box of a flex item as wide as the width its container assigned, if any, and
+/// makes its children available the new content width.
+fn set_flex_item_width(block: &mut BlockFlow) {
+    let width = match block.flex_item_width {
+        Some(width) => width,
+        None => return,
+    };
+    let attrs = &mut block.ftl_attrs;
+    attrs.computedwidth = max(width - attrs.mbphoriz, Au(0));
+    block.base.position.size.width =
+        attrs.computedwidth + attrs.bl + attrs.pl + attrs.br + attrs.pr;
+    block.base.ftl_attrs.totalwidth = block.base.position.size.width + attrs.ml + attrs.mr;
+    for kid in block.base.child_iter() {
+        flow::mut_base(kid).ftl_attrs.availablewidth = attrs.computedwidth
+    }
+}
+
+/// Runs the width pass over the flow tree rooted at `flow`, top-down.
+fn assign_subtree_widths(flow: &mut Flow) {
+    visit_widths(&mut *flow);
+    for kid in flow::child_iter(flow) {
+        assign_subtree_widths(kid)
+    }
+}
+
+/// The height pass of `layout_with_layers`, which runs `visit_1` bottom-up. Fixed boxes take up
+/// no space in their parent, so their sizes are hidden from it while it stacks up its children.
+/// They end up at their static position, which is where they stay unless they have offsets.
+///
+/// Once an inline flow has broken its lines, the `::first-line` style is applied to the
+/// fragments on its first line and the lines are broken again, since the restyled text may have
+/// changed width.
+fn assign_heights(flow: &mut Flow, font_context: &mut FontContext) {
+    let mut fixed_kid_sizes = Vec::new();
+    for kid in flow::child_iter(flow) {
+        assign_heights(kid, font_context);
+        if kid.is_fixed() {
+            let kid_base = flow::mut_base(kid);
+            fixed_kid_sizes.push((kid_base.ftl_attrs.totalwidth, kid_base.ftl_attrs.totalheight));
+            kid_base.ftl_attrs.totalwidth = Au(0);
+            kid_base.ftl_attrs.totalheight = Au(0);
+        }
+    }
+
+    as_ftl_node(&mut *flow).visit_1();
+    if flow.is_inline_flow() && flow.as_inline().apply_first_line_style(font_context) {
+        as_ftl_node(&mut *flow).visit_1();
+    }
+
+    let mut fixed_kid_sizes = fixed_kid_sizes.move_iter();
+    for kid in flow::child_iter(flow) {
+        if kid.is_fixed() {
+            let (total_width, total_height) = fixed_kid_sizes.next().unwrap();
+            let kid_base = flow::mut_base(kid);
+            kid_base.ftl_attrs.totalwidth = total_width;
+            kid_base.ftl_attrs.totalheight = total_height;
+        }
+    }
+}
+
+/// The position pass of `layout_with_layers`, which runs `visit_2` top-down. Fixed boxes are
+/// moved to the position their offsets give them in the viewport before their own display
+/// items are made, and list item markers are placed outside of their items.
+fn assign_positions(flow: &mut Flow, viewport_size: &Size2D<Au>) {
+    as_ftl_node(&mut *flow).visit_2();
+    if flow.class() == ListItemFlowClass {
+        flow.as_list_item().place_marker_ftl()
+    }
+    for kid in flow::child_iter(flow) {
+        if kid.is_fixed() {
+            place_fixed_box(kid.as_block(), viewport_size)
+        }
+        assign_positions(kid, viewport_size)
+    }
+}
+
+/// Sets the position of the margin box of the given fixed box from its `top`, `right`, `bottom`
+/// and `left` offsets, resolved against the viewport (CSS 2.1 section 10.3.7 and 10.6.4). A box
+/// with `auto` offsets on both sides of an axis keeps its static position in that axis.
+fn place_fixed_box(block: &mut BlockFlow, viewport_size: &Size2D<Au>) {
+    let offsets = block.fragment.style().get_positionoffsets();
+    let attrs = &mut block.base.ftl_attrs;
+    match (MaybeAuto::from_style(offsets.left, viewport_size.width),
+           MaybeAuto::from_style(offsets.right, viewport_size.width)) {
+        (model::Specified(left), _) => attrs.absx = left,
+        (model::Auto, model::Specified(right)) => {
+            attrs.absx = viewport_size.width - right - attrs.totalwidth
+        }
+        (model::Auto, model::Auto) => {}
+    }
+    match (MaybeAuto::from_style(offsets.top, viewport_size.height),
+           MaybeAuto::from_style(offsets.bottom, viewport_size.height)) {
+        (model::Specified(top), _) => attrs.absy = top,
+        (model::Auto, model::Specified(bottom)) => {
+            attrs.absy = viewport_size.height - bottom - attrs.totalheight
+        }
+        (model::Auto, model::Auto) => {}
+    }
+}
+
+/// The display list pass of `layout_with_layers`, which runs `visit_3` bottom-up.
+/// `in_scroll_container` is true for the descendants of a box whose contents are in a layer of
+/// their own.
+fn build_display_lists(flow: &mut Flow,
+                       layers: &mut DList<RenderLayer>,
+                       in_scroll_container: bool) {
+    // Scrollable boxes inside scrollable boxes are not supported: the compositor cannot clip and
+    // scroll a layer along with the layer around it. The contents of such a box stay in the layer
+    // of the outer box, so they scroll with it and are not clipped to the inner box.
+    let mut is_scroll_container = flow.is_block_flow() && flow.as_block().is_scroll_container();
+    if is_scroll_container && in_scroll_container {
+        debug!("ignoring the overflow of a scrollable box nested in another one");
+        is_scroll_container = false
+    }
+
+    let mut kid_layers = DList::new();
+    for kid in flow::child_iter(flow) {
+        build_display_lists(kid, &mut kid_layers, in_scroll_container || is_scroll_container)
+    }
+
+    let scroll_layer = if is_scroll_container {
+        build_display_lists_for_scroll_container(flow)
+    } else {
+        as_ftl_node(&mut *flow).visit_3();
+        None
+    };
+    if flow.class() == ListItemFlowClass {
+        add_list_item_marker(flow.as_list_item())
+    }
+    if flow.is_block_like() {
+        build_stacking_context_if_necessary(flow.as_block())
+    }
+
+    // A fixed box paints into its own layer, which goes below the layers of its descendants.
+    if flow.is_fixed() {
+        let block = flow.as_block();
+        let display_list = block.base.ftl_attrs.display_list.take_unwrap();
+        block.base.ftl_attrs.display_list = new_display_list();
+        layers.push_back(build_fixed_layer(&block.fragment, border_box_of(block), display_list))
+    }
+    match scroll_layer {
+        Some(scroll_layer) => layers.push_back(scroll_layer),
+        None => {}
+    }
+    layers.append(kid_layers)
+}
+
+/// Runs `visit_3` for a scrollable box after moving the display items of its children into a
+/// render layer of their own, if they need one, and returns that layer.
+fn build_display_lists_for_scroll_container(flow: &mut Flow) -> Option<RenderLayer> {
+    let mut contents = DisplayList::new();
+    for kid in flow::child_iter(flow) {
+        let kid_base = flow::mut_base(kid);
+        contents.push_all_move(kid_base.ftl_attrs.display_list.take_unwrap());
+        kid_base.ftl_attrs.display_list = new_display_list();
+    }
+
+    // Contents that stay with the box are painted after the box itself.
+    let mut display_list = DisplayList::new();
+    let layer = {
+        let block = flow.as_block();
+        let border = SideOffsets2D::new(block.ftl_attrs.bt,
+                                        block.ftl_attrs.br,
+                                        block.ftl_attrs.bb,
+                                        block.ftl_attrs.bl);
+        build_scroll_layer(&block.fragment, border_box_of(block), border, contents,
+                           &mut display_list)
+    };
+    as_ftl_node(&mut *flow).visit_3();
+    flow::mut_base(flow).ftl_attrs.display_list.get_mut_ref().push_all_move(display_list);
+    layer
+}
+
+/// Adds the display items of the `outside` marker of the given list item, if it has one, to the
+/// display list of the item.
+fn add_list_item_marker(list_item: &mut ListItemFlow) {
+    let marker = match list_item.marker {
+        None => return,
+        Some(ref marker) => marker,
+    };
+    let bounds = marker.border_box;
+    let list = list_item.block_flow.base.ftl_attrs.display_list.take();
+    list_item.block_flow.base.ftl_attrs.display_list =
+        add_text_fragment(list,
+                          marker.specific.clone(),
+                          marker.style.clone(),
+                          marker.node,
+                          bounds.origin.x,
+                          bounds.origin.y,
+                          bounds.size.width,
+                          bounds.size.height);
+}
+
+/// If the given block establishes a stacking context, replaces its display list, which holds the
+/// display items of its descendants by now, with a single stacking context display item that
+/// paints the flattened list as a group, transformed by the block's `transform`.
+fn build_stacking_context_if_necessary(block: &mut BlockFlow) {
+    let (z_index, opacity) = match block.stacking_context_z_index_and_opacity() {
+        None => return,
+        Some(z_index_and_opacity) => z_index_and_opacity,
+    };
+    let bounds = border_box_of(block);
+    let transform = model::transform_from_style(block.fragment.style(), &bounds)
+                           .unwrap_or(Matrix2D::identity());
+    let display_list = block.base.ftl_attrs.display_list.take_unwrap();
+    block.base.ftl_attrs.display_list =
+        Some(display_list.flatten_into_stacking_context(bounds,
+                                                        block.fragment.node,
+                                                        z_index,
+                                                        opacity,
+                                                        transform));
+}
+
+/// Returns the border box of the given block in page coordinates, as `blockflow_makeList` paints
+/// it.
+fn border_box_of(block: &BlockFlow) -> Rect<Au> {
+    make_rect(block.base.ftl_attrs.absx + block.ftl_attrs.ml,
+              block.base.ftl_attrs.absy + block.ftl_attrs.mt,
+              block.base.position.size.width,
+              block.base.position.size.height)
+}
+
 pub fn log(logstr: &str){
     debug!("{:s}",logstr)
 }
@@ -246,6 +562,8 @@ pub fn as_ftl_node<'a>(flow: &'a mut Flow) -> &'a mut layout::ftl_layout::FtlNod
     match flow.class() {
         BlockFlowClass         => flow.as_block() as &'a mut layout::ftl_layout::FtlNode,
         InlineFlowClass        => flow.as_inline() as &'a mut layout::ftl_layout::FtlNode,
+        ListItemFlowClass      => flow.as_block() as &'a mut layout::ftl_layout::FtlNode,
+        FlexFlowClass          => flow.as_flex() as &'a mut layout::ftl_layout::FtlNode,
         TableColGroupFlowClass => flow.as_table_colgroup() as &'a mut layout::ftl_layout::FtlNode,
         TableWrapperFlowClass  => flow.as_block() as &'a mut layout::ftl_layout::FtlNode,
         TableFlowClass         => flow.as_block() as &'a mut layout::ftl_layout::FtlNode,
@@ -260,6 +578,8 @@ struct Spliterator {
     items: MoveItems<Fragment>,
     leftover: Vec<Fragment>,
     work_list: RingBuf<Fragment>,
+    /// The width of the lines, which is the width left when a line starts.
+    line_width: Au,
 }
 
 pub trait Splittable {
@@ -273,7 +593,8 @@ impl Splittable for InlineFragments {
         Spliterator {
             items: mem::replace(&mut self.fragments, Vec::new()).move_iter(),
             leftover: Vec::new(),
-            work_list: RingBuf::new()
+            work_list: RingBuf::new(),
+            line_width: Au(0),
         }
     }
 
@@ -299,6 +620,39 @@ impl Spliterator {
             fragment
         };
 
+        if starts_line {
+            self.line_width = remaining_width
+        }
+
+        let mut cur_fragment = cur_fragment;
+        let is_inline_block = match cur_fragment.specific {
+            InlineBlockFragment(ref mut info) => {
+                layout_inline_block(unsafe { info.flow_ref.get_mut() }, self.line_width);
+                info.baseline = inline_block_baseline(unsafe { info.flow_ref.get_mut() });
+                let block_base = flow::base(info.flow_ref.get());
+                cur_fragment.border_box.size = Size2D(block_base.ftl_attrs.totalwidth,
+                                                      block_base.ftl_attrs.totalheight);
+                true
+            }
+            _ => false,
+        };
+
+        // An inline-block is atomic, so if it does not fit in what is left of the line, it goes
+        // on the next one, by ending the line at the fragment before it. It only overflows a line
+        // that it starts.
+        if is_inline_block && !starts_line &&
+                cur_fragment.border_box.size.width > remaining_width {
+            match self.leftover.mut_last() {
+                None => {}
+                Some(previous_fragment) => {
+                    previous_fragment.ftl_attrs.endofline = true;
+                    previous_fragment.ftl_attrs.availabletextwidth = self.line_width;
+                }
+            }
+            self.leftover.push(cur_fragment);
+            return unsafe { Some(cast::transmute_mut_lifetime(self.leftover.mut_last().unwrap())) }
+        }
+
         let split = cur_fragment.find_split_info_for_width(CharIndex(0), remaining_width, starts_line);
 
         let mut has_rest = false;
@@ -341,6 +695,56 @@ impl Spliterator {
     }
 }
 
+/// Lays out the block flow of an inline-block with its shrink-to-fit width (CSS 2.1 § 10.3.9)
+/// against the width of its containing block. The intrinsic widths of the flow were computed when
+/// it was constructed.
+fn layout_inline_block(block_flow: &mut Flow, containing_block_width: Au) {
+    {
+        let block_base = flow::mut_base(block_flow);
+        let intrinsic_widths = &block_base.intrinsic_widths;
+        block_base.ftl_attrs.availablewidth =
+            geometry::min(intrinsic_widths.total_preferred_width(),
+                          max(intrinsic_widths.total_minimum_width(),
+                              containing_block_width));
+    }
+    assign_subtree_widths(&mut *block_flow);
+    synthesize(|node: &mut FtlNode| node.visit_1(), as_ftl_node(block_flow));
+}
+
+/// Returns the distance from the top of the margin box of an inline-block to its baseline, which
+/// is the baseline of its last line box or else its bottom margin edge (CSS 2.1 § 10.8.1).
+fn inline_block_baseline(block_flow: &mut Flow) -> Au {
+    let margin_top = block_flow.as_block().ftl_attrs.mt;
+    match last_line_baseline(block_flow) {
+        Some(baseline) => margin_top + baseline,
+        None => flow::base(block_flow).ftl_attrs.totalheight,
+    }
+}
+
+/// Returns the distance from the top of the border box of the given block-like flow to the
+/// baseline of its last line box, if it has any.
+fn last_line_baseline(block_flow: &mut Flow) -> Option<Au> {
+    let mut result = None;
+    for kid in flow::child_iter(block_flow) {
+        let containing_y = flow::base(kid).ftl_attrs.containingy;
+        let kid_baseline = if kid.is_inline_flow() {
+            kid.as_inline().fragments.fragments.last().map(|fragment| {
+                fragment.ftl_attrs.lineposy + fragment.ftl_attrs.baselinefinal
+            })
+        } else if kid.is_block_like() {
+            let margin_top = kid.as_block().ftl_attrs.mt;
+            last_line_baseline(kid).map(|baseline| margin_top + baseline)
+        } else {
+            None
+        };
+        match kid_baseline {
+            Some(baseline) => result = Some(containing_y + baseline),
+            None => {}
+        }
+    }
+    result
+}
+
 pub trait LineMetrics {
     fn get_ascent(&mut self) -> Au;
     fn get_descent(&mut self) -> Au;
@@ -358,3 +762,200 @@ impl LineMetrics for Fragment {
         self.border_box.size.height
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Spliterator, add_text_fragment, new_display_list};
+    use layout::block::BlockFlow;
+    use layout::flow::{BaseFlow, Flow};
+    use layout::fragment::{Fragment, GenericFragment, InlineBlockFragment};
+    use layout::fragment::{InlineBlockFragmentInfo, ScannedTextFragment, ScannedTextFragmentInfo};
+    use layout::incremental::RestyleDamage;
+
+    use collections::RingBuf;
+    use geom::{Point2D, Rect, Size2D};
+    use gfx::display_list::{OpaqueNode, TextDisplayItemClass, TextShadowDisplayItemClass};
+    use gfx::font::{FontDescriptor, FontMetrics, FontStyle, SelectorPlatformIdentifier};
+    use gfx::text::glyph::{CharIndex, GlyphData, GlyphStore};
+    use gfx::text::text_run::TextRun;
+    use servo_util::geometry::Au;
+    use servo_util::range::Range;
+    use style::computed_values::{font_style, font_weight, text_decoration};
+    use style::{ComputedValues, MatchedProperty, cascade, parse_style_attribute};
+    use sync::Arc;
+    use url::Url;
+
+    /// Returns a text run of the given text with one glyph per character, each of the given
+    /// advance.
+    fn text_run(text: &str, advance: Au) -> Arc<Box<TextRun>> {
+        let char_count = text.char_len() as int;
+        let mut glyph_store = GlyphStore::new(char_count, false);
+        for i in range(0, char_count) {
+            let glyph = GlyphData::new(i as u32 + 1, advance, None, false, true, true);
+            glyph_store.add_glyph_for_char_index(CharIndex(i), &glyph);
+        }
+        glyph_store.finalize_changes();
+
+        let used_style = FontStyle {
+            pt_size: 12.0,
+            weight: font_weight::Weight400,
+            style: font_style::normal,
+            families: vec!("serif".to_owned()),
+        };
+        Arc::new(box TextRun {
+            text: Arc::new(text.to_owned()),
+            font_descriptor: FontDescriptor::new(used_style.clone(),
+                                                 SelectorPlatformIdentifier("serif".to_owned())),
+            font_metrics: FontMetrics {
+                underline_size: Au(0),
+                underline_offset: Au(0),
+                strikeout_size: Au(0),
+                strikeout_offset: Au(0),
+                leading: Au(0),
+                x_height: Au(0),
+                em_size: Au(0),
+                ascent: Au(0),
+                descent: Au(0),
+                max_advance: advance,
+            },
+            font_style: used_style,
+            decoration: text_decoration::none,
+            glyphs: Arc::new(vec!(Arc::new(glyph_store))),
+        })
+    }
+
+    fn style_from_attribute(style_attribute: &str) -> Arc<ComputedValues> {
+        let base_url = from_str::<Url>("http://example.com/").unwrap();
+        let declarations = parse_style_attribute(style_attribute, &base_url);
+        let (style, _) = cascade([MatchedProperty::from_declarations(declarations.normal)]
+                                     .as_slice(),
+                                 false,
+                                 None,
+                                 None);
+        Arc::new(style)
+    }
+
+    #[test]
+    fn test_add_text_fragment_with_text_shadows() {
+        let run = text_run("Hi", Au::from_px(5));
+        let info = ScannedTextFragmentInfo::new(run, Range::new(CharIndex(0), CharIndex(2)));
+        let style = style_from_attribute("text-shadow: 1px 2px red, 3px 4px 5px blue");
+        let list = add_text_fragment(new_display_list(),
+                                     ScannedTextFragment(info),
+                                     style,
+                                     OpaqueNode(1),
+                                     Au::from_px(10),
+                                     Au::from_px(20),
+                                     Au::from_px(10),
+                                     Au::from_px(16)).unwrap();
+
+        // The shadows come beneath the text, and the first one in the list is painted on top.
+        let items: Vec<_> = list.iter().collect();
+        assert!(items.len() == 3);
+        match **items.get(0) {
+            TextShadowDisplayItemClass(ref shadow) => {
+                assert!(shadow.offset == Point2D(Au::from_px(3), Au::from_px(4)));
+                assert!(shadow.blur_radius == Au::from_px(5));
+                assert!(shadow.color.b == 1.0 && shadow.color.r == 0.0);
+            }
+            _ => fail!("expected a text shadow item"),
+        }
+        match **items.get(1) {
+            TextShadowDisplayItemClass(ref shadow) => {
+                assert!(shadow.offset == Point2D(Au::from_px(1), Au::from_px(2)));
+                assert!(shadow.blur_radius == Au(0));
+                assert!(shadow.color.r == 1.0 && shadow.color.b == 0.0);
+                assert!(shadow.text_bounds == items.get(2).bounds());
+            }
+            _ => fail!("expected a text shadow item"),
+        }
+        match **items.get(2) {
+            TextDisplayItemClass(ref text) => {
+                assert!(text.range.begin() == CharIndex(0) && text.range.end() == CharIndex(2));
+            }
+            _ => fail!("expected a text item"),
+        }
+    }
+
+    #[test]
+    fn test_add_text_fragment_without_text_shadows() {
+        let run = text_run("Hi", Au::from_px(5));
+        let info = ScannedTextFragmentInfo::new(run, Range::new(CharIndex(0), CharIndex(2)));
+        let list = add_text_fragment(new_display_list(),
+                                     ScannedTextFragment(info),
+                                     style_from_attribute(""),
+                                     OpaqueNode(1),
+                                     Au(0),
+                                     Au(0),
+                                     Au::from_px(10),
+                                     Au::from_px(16)).unwrap();
+        let items: Vec<_> = list.iter().collect();
+        assert!(items.len() == 1);
+    }
+
+    fn fragment_of_width(width: Au) -> Fragment {
+        let mut fragment = Fragment::from_opaque_node_and_style(OpaqueNode(0),
+                                                                style_from_attribute(""),
+                                                                GenericFragment);
+        fragment.border_box = Rect(Point2D(Au(0), Au(0)), Size2D(width, Au::from_px(10)));
+        fragment
+    }
+
+    /// Returns an inline-block fragment whose block flow has the given intrinsic widths.
+    fn inline_block_fragment(minimum_width: Au, preferred_width: Au) -> Fragment {
+        let style = style_from_attribute("display: inline-block");
+        let mut flow = box BlockFlow::from_base_and_fragment(
+            BaseFlow::from_restyle_damage(RestyleDamage::none()),
+            Fragment::from_opaque_node_and_style(OpaqueNode(0), style.clone(), GenericFragment));
+        flow.base.intrinsic_widths.minimum_width = minimum_width;
+        flow.base.intrinsic_widths.preferred_width = preferred_width;
+        let info = InlineBlockFragmentInfo::new(flow as Box<Flow:Share>);
+        Fragment::from_opaque_node_and_style(OpaqueNode(0), style, InlineBlockFragment(info))
+    }
+
+    fn spliterator(fragments: Vec<Fragment>) -> Spliterator {
+        Spliterator {
+            items: fragments.move_iter(),
+            leftover: Vec::new(),
+            work_list: RingBuf::new(),
+            line_width: Au(0),
+        }
+    }
+
+    #[test]
+    fn test_inline_block_that_does_not_fit_goes_on_the_next_line() {
+        let mut fragments = spliterator(vec!(fragment_of_width(Au::from_px(60)),
+                                             inline_block_fragment(Au::from_px(30),
+                                                                   Au::from_px(80))));
+        assert!(fragments.split_to_width(Au::from_px(100), true).is_some());
+
+        // The inline-block is sized against the whole line, not the 40px left of it.
+        let width = fragments.split_to_width(Au::from_px(40), false).unwrap().border_box.size.width;
+        assert_eq!(width, Au::from_px(80));
+        assert!(fragments.leftover.len() == 2);
+        let previous_fragment = fragments.leftover.get(0);
+        assert!(previous_fragment.ftl_attrs.endofline);
+        assert_eq!(previous_fragment.ftl_attrs.availabletextwidth, Au::from_px(100));
+        assert!(fragments.split_to_width(Au::from_px(20), false).is_none());
+    }
+
+    #[test]
+    fn test_inline_block_that_fits_stays_on_the_line() {
+        let mut fragments = spliterator(vec!(fragment_of_width(Au::from_px(60)),
+                                             inline_block_fragment(Au::from_px(30),
+                                                                   Au::from_px(40))));
+        assert!(fragments.split_to_width(Au::from_px(100), true).is_some());
+        let width = fragments.split_to_width(Au::from_px(40), false).unwrap().border_box.size.width;
+        assert_eq!(width, Au::from_px(40));
+        assert!(!fragments.leftover.get(0).ftl_attrs.endofline);
+    }
+
+    #[test]
+    fn test_inline_block_wider_than_the_line_overflows_it() {
+        let mut fragments = spliterator(vec!(inline_block_fragment(Au::from_px(150),
+                                                                   Au::from_px(200))));
+        let width = fragments.split_to_width(Au::from_px(100), true).unwrap().border_box.size.width;
+        assert_eq!(width, Au::from_px(150));
+        assert!(fragments.leftover.len() == 1);
+    }
+}
@@ -0,0 +1,334 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Generated content that depends on CSS counters: list item markers and the `counter()` and
+//! `counters()` functions of the `content` property. See CSS 2.1 § 12.4 and CSS Lists and
+//! Counters Module Level 3.
+//!
+//! Counters depend on everything that precedes a box in document order, so they cannot be computed
+//! during the bottom-up flow construction. Instead, flow construction leaves
+//! `GeneratedContentFragment` placeholders behind, and this sequential pass over the finished flow
+//! tree replaces them with text.
+//!
+//! TODO: Counter properties are only honored on block-level boxes, list items and `::before` and
+//! `::after` pseudo-elements, not on other inline-level elements.
+
+use layout::context::LayoutContext;
+use layout::flow::{Flow, ImmutableFlowUtils, InlineFlowClass, ListItemFlowClass, TableFlowClass};
+use layout::flow;
+use layout::fragment::{ContentItems, Fragment, GeneratedContentFragment, InlineBlockFragment};
+use layout::fragment::{ListItemMarker};
+use layout::fragment::{UnscannedTextFragment, UnscannedTextFragmentInfo};
+use layout::text::TextRunScanner;
+
+use collections::hashmap::HashMap;
+use gfx::font_context::FontContext;
+use gfx::text::glyph::CharIndex;
+use std::ascii::StrAsciiExt;
+use std::str;
+use style::ComputedValues;
+use style::computed_values::{content, list_style_position, list_style_type};
+
+/// The name of the counter that list items increment implicitly and that their markers show.
+static LIST_ITEM_COUNTER: &'static str = "list-item";
+
+/// Replaces all the generated content placeholders in the given flow tree with text.
+pub fn resolve_generated_content(root: &mut Flow, layout_context: &mut LayoutContext) {
+    let mut counters = Counters::new();
+    resolve_generated_content_for_flow(root, 0, &mut counters, layout_context)
+}
+
+fn resolve_generated_content_for_flow(flow: &mut Flow,
+                                      depth: uint,
+                                      counters: &mut Counters,
+                                      layout_context: &mut LayoutContext) {
+    counters.leave_scopes_deeper_than(depth);
+
+    match flow.class() {
+        InlineFlowClass => {
+            resolve_generated_content_for_inline_flow(flow, depth + 1, counters, layout_context)
+        }
+        ListItemFlowClass => {
+            let list_item = flow.as_list_item();
+            counters.update(&*list_item.block_flow.fragment.style, depth, true);
+            let marker = match list_item.marker {
+                Some(ref marker) => {
+                    match marker.specific {
+                        GeneratedContentFragment(ListItemMarker(list_style_type)) => {
+                            let text = marker_text(marker.style(),
+                                                   list_style_type,
+                                                   counters.value(LIST_ITEM_COUNTER, depth));
+                            scan_marker(layout_context.font_context(), marker, text)
+                        }
+                        _ => Some(marker.clone()),
+                    }
+                }
+                None => None,
+            };
+            list_item.marker = marker
+        }
+        // The table wrapper flow has the same style as the table flow, and it has already
+        // updated the counters.
+        TableFlowClass => {}
+        _ if flow.is_block_like() || flow.is_table_kind() => {
+            counters.update(&*flow.as_block().fragment.style, depth, false)
+        }
+        _ => {}
+    }
+
+    for kid in flow::child_iter(flow) {
+        resolve_generated_content_for_flow(kid, depth + 1, counters, layout_context)
+    }
+}
+
+/// Replaces the placeholders among the fragments of an inline flow with unscanned text, and then
+/// scans the text again. Fragments are one level deeper than their flow.
+fn resolve_generated_content_for_inline_flow(flow: &mut Flow,
+                                             depth: uint,
+                                             counters: &mut Counters,
+                                             layout_context: &mut LayoutContext) {
+    let mut resolved = false;
+    for fragment in flow.as_inline().fragments.fragments.mut_iter() {
+        let text = match fragment.specific {
+            GeneratedContentFragment(ListItemMarker(list_style_type)) => {
+                marker_text(fragment.style(),
+                            list_style_type,
+                            counters.value(LIST_ITEM_COUNTER, depth))
+            }
+            GeneratedContentFragment(ContentItems(ref items)) => {
+                counters.update(&*fragment.style, depth, false);
+                content_text(items.as_slice(), counters, depth)
+            }
+            InlineBlockFragment(ref mut info) => {
+                resolve_generated_content_for_flow(unsafe { info.flow_ref.get_mut() },
+                                                   depth,
+                                                   counters,
+                                                   layout_context);
+                continue
+            }
+            _ => continue,
+        };
+        fragment.specific = UnscannedTextFragment(UnscannedTextFragmentInfo::from_text(text));
+        resolved = true
+    }
+
+    if resolved {
+        TextRunScanner::new().scan_for_runs(layout_context.font_context(), flow)
+    }
+}
+
+/// Turns an `outside` list item marker into a text fragment with the given text. Returns `None`
+/// if the text is empty.
+fn scan_marker(font_context: &mut FontContext, marker: &Fragment, text: ~str)
+               -> Option<Fragment> {
+    let specific = UnscannedTextFragment(UnscannedTextFragmentInfo::from_text(text));
+    let marker = marker.transform(marker.border_box.size, specific);
+    let mut scanner = TextRunScanner::new();
+    scanner.clump.extend_by(CharIndex(1));
+    let mut fragments = vec!();
+    drop(scanner.flush_clump_to_list(font_context, &[marker], &mut fragments, false));
+    fragments.pop()
+}
+
+/// The counters in scope at the current position of the traversal. Each counter has a stack of
+/// instances, innermost last, along with the depth of the box that created each instance. An
+/// instance is in scope for the box that created it, its following siblings, and all their
+/// descendants.
+struct Counters {
+    counters: HashMap<~str, Vec<(uint, i32)>>,
+}
+
+impl Counters {
+    fn new() -> Counters {
+        Counters {
+            counters: HashMap::new(),
+        }
+    }
+
+    /// Drops the instances that are out of scope for a box at the given depth.
+    fn leave_scopes_deeper_than(&mut self, depth: uint) {
+        for (_, instances) in self.counters.mut_iter() {
+            while instances.last().map_or(false, |&(instance_depth, _)| instance_depth > depth) {
+                instances.pop();
+            }
+        }
+    }
+
+    /// Applies the `counter-reset`, `counter-increment` and `counter-set` properties of a box at
+    /// the given depth, in that order. List items also increment the `list-item` counter by one,
+    /// unless `counter-increment` mentions it.
+    fn update(&mut self, style: &ComputedValues, depth: uint, is_list_item: bool) {
+        let box_style = style.get_box();
+        for &(ref name, value) in box_style.counter_reset.iter() {
+            self.reset(name.as_slice(), depth, value)
+        }
+        for &(ref name, value) in box_style.counter_increment.iter() {
+            let new_value = self.value(name.as_slice(), depth) + value;
+            self.set(name.as_slice(), depth, new_value)
+        }
+        if is_list_item && !box_style.counter_increment.iter().any(|&(ref name, _)| {
+            name.as_slice() == LIST_ITEM_COUNTER
+        }) {
+            let new_value = self.value(LIST_ITEM_COUNTER, depth) + 1;
+            self.set(LIST_ITEM_COUNTER, depth, new_value)
+        }
+        for &(ref name, value) in box_style.counter_set.iter() {
+            self.set(name.as_slice(), depth, value)
+        }
+    }
+
+    /// Creates a new instance of the counter for a box at the given depth. An instance created by
+    /// a previous sibling is replaced.
+    fn reset(&mut self, name: &str, depth: uint, value: i32) {
+        let instances = self.counters.find_or_insert_with(name.to_owned(), |_| vec!());
+        match instances.last() {
+            Some(&(instance_depth, _)) if instance_depth == depth => {
+                instances.pop();
+            }
+            _ => {}
+        }
+        instances.push((depth, value))
+    }
+
+    /// Sets the innermost instance of the counter, creating one if there is none in scope.
+    fn set(&mut self, name: &str, depth: uint, value: i32) {
+        if self.values(name, depth).is_empty() {
+            self.reset(name, depth, value)
+        }
+        let instances = self.counters.get_mut(&name.to_owned());
+        let (instance_depth, _) = instances.pop().unwrap();
+        instances.push((instance_depth, value))
+    }
+
+    /// Returns the value of the innermost instance of the counter, creating one with a value of
+    /// zero if there is none in scope.
+    fn value(&mut self, name: &str, depth: uint) -> i32 {
+        *self.values(name, depth).last().unwrap()
+    }
+
+    /// Returns the values of all the instances of the counter, outermost first, creating one with
+    /// a value of zero if there is none in scope.
+    fn values(&mut self, name: &str, depth: uint) -> Vec<i32> {
+        let is_empty = match self.counters.find_equiv(&name) {
+            None => true,
+            Some(instances) => instances.is_empty(),
+        };
+        if is_empty {
+            self.reset(name, depth, 0)
+        }
+        self.counters.find_equiv(&name).unwrap().iter().map(|&(_, value)| value).collect()
+    }
+}
+
+/// Returns the text of a list item marker. Markers inside the item are followed by a space.
+fn marker_text(style: &ComputedValues, list_style_type: list_style_type::T, value: i32) -> ~str {
+    let mut text = StrBuf::from_owned_str(format_counter(value, list_style_type));
+    match list_style_type {
+        list_style_type::disc | list_style_type::circle | list_style_type::square |
+        list_style_type::none => {}
+        _ => text.push_char('.'),
+    }
+    if style.get_list().list_style_position == list_style_position::inside {
+        text.push_char(' ')
+    }
+    text.into_owned()
+}
+
+/// Returns the text of the given `content`, with the counters replaced by their values.
+fn content_text(items: &[content::Content], counters: &mut Counters, depth: uint) -> ~str {
+    let mut text = StrBuf::new();
+    for item in items.iter() {
+        match *item {
+            content::StringContent(ref string) => text.push_str(string.as_slice()),
+            content::CounterContent(ref name, list_style_type) => {
+                let value = counters.value(name.as_slice(), depth);
+                text.push_str(format_counter(value, list_style_type).as_slice())
+            }
+            content::CountersContent(ref name, ref separator, list_style_type) => {
+                let values = counters.values(name.as_slice(), depth);
+                let values: Vec<~str> = values.iter().map(|&value| {
+                    format_counter(value, list_style_type)
+                }).collect();
+                text.push_str(values.as_slice().connect(separator.as_slice()).as_slice())
+            }
+        }
+    }
+    text.into_owned()
+}
+
+/// Formats the value of a counter in the given style. Alphabetic and roman styles fall back to
+/// decimal for values that they cannot represent.
+pub fn format_counter(value: i32, list_style_type: list_style_type::T) -> ~str {
+    match list_style_type {
+        list_style_type::none => "".to_owned(),
+        list_style_type::disc => "•".to_owned(),
+        list_style_type::circle => "◦".to_owned(),
+        list_style_type::square => "▪".to_owned(),
+        list_style_type::decimal => value.to_str(),
+        list_style_type::lower_alpha | list_style_type::lower_latin => {
+            format_alphabetic(value, 'a')
+        }
+        list_style_type::upper_alpha | list_style_type::upper_latin => {
+            format_alphabetic(value, 'A')
+        }
+        list_style_type::lower_roman => format_roman(value).to_ascii_lower(),
+        list_style_type::upper_roman => format_roman(value),
+    }
+}
+
+/// Formats a positive value as a, b, ..., z, aa, ab, ...
+fn format_alphabetic(value: i32, first_letter: char) -> ~str {
+    if value <= 0 {
+        return value.to_str()
+    }
+    let mut letters = vec!();
+    let mut value = value as u32;
+    while value > 0 {
+        value -= 1;
+        letters.push(((first_letter as u32) + value % 26) as u8 as char);
+        value /= 26
+    }
+    letters.reverse();
+    str::from_chars(letters.as_slice())
+}
+
+/// Formats a value between 1 and 3999 as an uppercase roman numeral.
+fn format_roman(value: i32) -> ~str {
+    static NUMERALS: [(i32, &'static str), ..13] = [
+        (1000, "M"), (900, "CM"), (500, "D"), (400, "CD"), (100, "C"), (90, "XC"), (50, "L"),
+        (40, "XL"), (10, "X"), (9, "IX"), (5, "V"), (4, "IV"), (1, "I")
+    ];
+    if value <= 0 || value >= 4000 {
+        return value.to_str()
+    }
+    let mut text = StrBuf::new();
+    let mut value = value;
+    for &(numeral_value, numeral) in NUMERALS.iter() {
+        while value >= numeral_value {
+            text.push_str(numeral);
+            value -= numeral_value
+        }
+    }
+    text.into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format_counter;
+    use style::computed_values::list_style_type;
+
+    #[test]
+    fn test_format_counter() {
+        assert_eq!(format_counter(3, list_style_type::decimal), "3".to_owned());
+        assert_eq!(format_counter(-1, list_style_type::decimal), "-1".to_owned());
+        assert_eq!(format_counter(1, list_style_type::lower_alpha), "a".to_owned());
+        assert_eq!(format_counter(26, list_style_type::lower_alpha), "z".to_owned());
+        assert_eq!(format_counter(28, list_style_type::upper_latin), "AB".to_owned());
+        assert_eq!(format_counter(0, list_style_type::upper_alpha), "0".to_owned());
+        assert_eq!(format_counter(1994, list_style_type::upper_roman), "MCMXCIV".to_owned());
+        assert_eq!(format_counter(4, list_style_type::lower_roman), "iv".to_owned());
+        assert_eq!(format_counter(4000, list_style_type::lower_roman), "4000".to_owned());
+        assert_eq!(format_counter(7, list_style_type::none), "".to_owned());
+    }
+}
@@ -0,0 +1,293 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Resolution of CSS gradients against the box they are painted into. See CSS Image Values and
+//! Replaced Content Level 3 § 4.
+
+use layout::model;
+use layout::util::ToGfxColor;
+
+use computed = style::computed_values;
+use geom::{Point2D, Rect, Size2D};
+use gfx::display_list::{GradientGeometry, GradientStop, LinearGradientGeometry};
+use gfx::display_list::RadialGradientGeometry;
+use servo_util::geometry::Au;
+use servo_util::geometry;
+use std::f64::consts::SQRT2;
+use style::ComputedValues;
+use style::computed_values::{AngleDirection, Bottom, Circle, ClosestCorner, ClosestSide};
+use style::computed_values::{CornerDirection, ExplicitCircleExtent, ExplicitEllipseExtent};
+use style::computed_values::{FarthestCorner, FarthestSide, Left, Right, Top};
+
+/// Returns the geometry and color stops of a linear gradient painted into the given bounds, or
+/// `None` if nothing should be painted.
+pub fn linear_gradient(style: &ComputedValues,
+                       gradient: &computed::LinearGradient,
+                       bounds: &Rect<Au>)
+                       -> Option<(GradientGeometry, Vec<GradientStop>)> {
+    let width = geometry::to_frac_px(bounds.size.width);
+    let height = geometry::to_frac_px(bounds.size.height);
+
+    // Angles are measured clockwise from the top. A corner points the gradient line so that the
+    // two neighboring corners are at 50%.
+    let angle = match gradient.direction {
+        AngleDirection(angle) => angle.radians(),
+        CornerDirection(horizontal, vertical) => {
+            let x = match horizontal {
+                Left => -height,
+                Right => height,
+            };
+            let y = match vertical {
+                Top => -width,
+                Bottom => width,
+            };
+            x.atan2(-y)
+        }
+    };
+    let (sin, cos) = (angle.sin(), angle.cos());
+    let length = (width * sin).abs() + (height * cos).abs();
+    if length <= 0.0 {
+        return None
+    }
+
+    let center_x = geometry::to_frac_px(bounds.origin.x) + width * 0.5;
+    let center_y = geometry::to_frac_px(bounds.origin.y) + height * 0.5;
+    let (direction_x, direction_y) = (sin * length, -cos * length);
+    let (stops, start, end) = resolve_color_stops(style,
+                                                  gradient.stops.as_slice(),
+                                                  Au::from_frac_px(length),
+                                                  gradient.repeating);
+    let point_at = |fraction: f64| {
+        Point2D(Au::from_frac_px(center_x + direction_x * (fraction - 0.5)),
+                Au::from_frac_px(center_y + direction_y * (fraction - 0.5)))
+    };
+    Some((LinearGradientGeometry(point_at(start), point_at(end)), stops))
+}
+
+/// Returns the geometry and color stops of a radial gradient painted into the given bounds, or
+/// `None` if nothing should be painted.
+pub fn radial_gradient(style: &ComputedValues,
+                       gradient: &computed::RadialGradient,
+                       bounds: &Rect<Au>)
+                       -> Option<(GradientGeometry, Vec<GradientStop>)> {
+    let center_x = model::specified(gradient.center_x, bounds.size.width);
+    let center_y = model::specified(gradient.center_y, bounds.size.height);
+
+    // The distances from the center to the nearest and farthest sides in each direction.
+    let (left, right) = (center_x, bounds.size.width - center_x);
+    let (top, bottom) = (center_y, bounds.size.height - center_y);
+    let closest = (geometry::to_frac_px(Au::min(left, right)).abs(),
+                   geometry::to_frac_px(Au::min(top, bottom)).abs());
+    let farthest = (geometry::to_frac_px(Au::max(left, right)).abs(),
+                    geometry::to_frac_px(Au::max(top, bottom)).abs());
+
+    let circle = gradient.shape == Circle;
+    let (radius_x, radius_y) = match gradient.extent {
+        ExplicitCircleExtent(radius) => {
+            let radius = geometry::to_frac_px(radius);
+            (radius, radius)
+        }
+        ExplicitEllipseExtent(width, height) => {
+            (geometry::to_frac_px(model::specified(width, bounds.size.width)),
+             geometry::to_frac_px(model::specified(height, bounds.size.height)))
+        }
+        ClosestSide | FarthestSide => {
+            let (x, y) = if gradient.extent == ClosestSide { closest } else { farthest };
+            if circle {
+                let radius = if gradient.extent == ClosestSide { x.min(y) } else { x.max(y) };
+                (radius, radius)
+            } else {
+                (x, y)
+            }
+        }
+        ClosestCorner | FarthestCorner => {
+            // An ellipse through a corner keeps the proportions it would have touching the
+            // sides, which scales both radii by the square root of two.
+            let (x, y) = if gradient.extent == ClosestCorner { closest } else { farthest };
+            if circle {
+                let radius = x.hypot(y);
+                (radius, radius)
+            } else {
+                (x * SQRT2, y * SQRT2)
+            }
+        }
+    };
+    if radius_x <= 0.0 || radius_y <= 0.0 {
+        return None
+    }
+
+    // Color stops are placed along the horizontal radius.
+    let (stops, start, end) = resolve_color_stops(style,
+                                                  gradient.stops.as_slice(),
+                                                  Au::from_frac_px(radius_x),
+                                                  gradient.repeating);
+    let radii_at = |fraction: f64| {
+        Size2D(Au::from_frac_px(radius_x * fraction), Au::from_frac_px(radius_y * fraction))
+    };
+    let center = Point2D(bounds.origin.x + center_x, bounds.origin.y + center_y);
+    Some((RadialGradientGeometry(center, radii_at(start), radii_at(end)), stops))
+}
+
+/// Places the color stops of a gradient whose gradient line has the given length, per CSS Image
+/// Values and Replaced Content Level 3 § 4.4.
+///
+/// Returns the stops along with the fractions of the gradient line at which the resulting
+/// gradient starts and ends. These are 0.0 and 1.0 except for repeating gradients, which repeat
+/// the part of the line between their first and last stops.
+fn resolve_color_stops(style: &ComputedValues,
+                       stops: &[computed::ColorStop],
+                       length: Au,
+                       repeating: bool)
+                       -> (Vec<GradientStop>, f64, f64) {
+    // Resolve the stops that have positions, making sure that no stop comes before the ones
+    // preceding it.
+    let mut positions: Vec<Option<f64>> = stops.iter().map(|stop| {
+        stop.position.map(|position| {
+            geometry::to_frac_px(model::specified(position, length)) /
+                geometry::to_frac_px(length)
+        })
+    }).collect();
+    if positions.get(0).is_none() {
+        *positions.get_mut(0) = Some(0.0)
+    }
+    let last_index = positions.len() - 1;
+    if positions.get(last_index).is_none() {
+        *positions.get_mut(last_index) = Some(1.0)
+    }
+    let mut greatest = (*positions.get(0)).unwrap();
+    for position in positions.mut_iter() {
+        match *position {
+            Some(value) if value < greatest => *position = Some(greatest),
+            Some(value) => greatest = value,
+            None => {}
+        }
+    }
+
+    // Space the stops without positions evenly between their neighbors.
+    let mut index = 0;
+    while index < positions.len() {
+        if positions.get(index).is_some() {
+            index += 1;
+            continue
+        }
+        let previous = (*positions.get(index - 1)).unwrap();
+        let mut next_index = index;
+        while positions.get(next_index).is_none() {
+            next_index += 1
+        }
+        let next = (*positions.get(next_index)).unwrap();
+        let step = (next - previous) / ((next_index - index + 1) as f64);
+        for i in range(index, next_index) {
+            *positions.get_mut(i) = Some(previous + step * ((i - index + 1) as f64))
+        }
+        index = next_index
+    }
+
+    // A repeating gradient repeats the span from its first stop to its last, so express the
+    // stops as fractions of that span. The stops of other gradients are clamped to the gradient
+    // line.
+    let (start, end) = ((*positions.get(0)).unwrap(), (*positions.get(last_index)).unwrap());
+    let (start, end) = if repeating && end > start {
+        (start, end)
+    } else {
+        (0.0, 1.0)
+    };
+    let stops = stops.iter().zip(positions.iter()).map(|(stop, position)| {
+        let offset = (position.unwrap() - start) / (end - start);
+        GradientStop {
+            offset: offset.max(0.0).min(1.0) as f32,
+            color: style.resolve_color(stop.color).to_gfx_color(),
+        }
+    }).collect();
+    (stops, start, end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::resolve_color_stops;
+
+    use servo_util::geometry::Au;
+    use style::computed_values::LinearGradientImage;
+    use style::{MatchedProperty, cascade, parse_style_attribute};
+    use url::Url;
+
+    /// Returns the offsets of the stops of the given linear gradient on a 100px gradient line,
+    /// along with the part of the line that the gradient spans.
+    fn stop_offsets(gradient: &str) -> (Vec<f32>, f64, f64) {
+        let base_url = from_str::<Url>("http://example.com/").unwrap();
+        let style_attribute = format!("background-image: {}", gradient);
+        let declarations = parse_style_attribute(style_attribute.as_slice(), &base_url);
+        let (style, _) = cascade([MatchedProperty::from_declarations(declarations.normal)]
+                                     .as_slice(),
+                                 false,
+                                 None,
+                                 None);
+        let (stops, start, end) = match style.get_background().background_image {
+            Some(LinearGradientImage(ref gradient)) => {
+                resolve_color_stops(&style,
+                                    gradient.stops.as_slice(),
+                                    Au::from_px(100),
+                                    gradient.repeating)
+            }
+            _ => fail!("not a linear gradient: {}", gradient),
+        };
+        (stops.iter().map(|stop| stop.offset).collect(), start, end)
+    }
+
+    fn assert_offsets(gradient: &str, expected: &[f32]) {
+        assert_offsets_and_span(gradient, expected, (0.0, 1.0))
+    }
+
+    fn assert_offsets_and_span(gradient: &str, expected: &[f32], expected_span: (f64, f64)) {
+        let (offsets, start, end) = stop_offsets(gradient);
+        let (expected_start, expected_end) = expected_span;
+        assert!((start - expected_start).abs() < 1e-9 && (end - expected_end).abs() < 1e-9,
+                "{}: expected the span {}..{}, got {}..{}",
+                gradient, expected_start, expected_end, start, end);
+        assert!(offsets.len() == expected.len());
+        for (offset, expected) in offsets.iter().zip(expected.iter()) {
+            assert!((*offset - *expected).abs() < 1e-5,
+                    "{}: expected {}, got {}", gradient, expected, offset);
+        }
+    }
+
+    #[test]
+    fn test_explicit_positions() {
+        assert_offsets("linear-gradient(red 10px, green 25%, blue 90px)", [0.1, 0.25, 0.9]);
+    }
+
+    #[test]
+    fn test_implicit_positions() {
+        // The first and last stops default to the ends of the line, and the others are spaced
+        // evenly between the stops around them.
+        assert_offsets("linear-gradient(red, green, blue)", [0.0, 0.5, 1.0]);
+        assert_offsets("linear-gradient(red 20%, green, blue, white 80%)", [0.2, 0.4, 0.6, 0.8]);
+        assert_offsets("linear-gradient(red, green 40%, blue, white)", [0.0, 0.4, 0.7, 1.0]);
+    }
+
+    #[test]
+    fn test_decreasing_positions() {
+        // A stop before the ones preceding it moves to the greatest position before it.
+        assert_offsets("linear-gradient(red 40%, green 10px, blue)", [0.4, 0.4, 1.0]);
+        assert_offsets("linear-gradient(red 30%, green 60%, blue 50%, white)",
+                       [0.3, 0.6, 0.6, 1.0]);
+        assert_offsets("linear-gradient(red 50%, green, blue 20%)", [0.5, 0.5, 0.5]);
+    }
+
+    #[test]
+    fn test_positions_outside_the_line_are_clamped() {
+        assert_offsets("linear-gradient(red -20%, blue 150%)", [0.0, 1.0]);
+        // Implicit positions are resolved before clamping.
+        assert_offsets("linear-gradient(red -50%, green, blue 150%)", [0.0, 0.5, 1.0]);
+    }
+
+    #[test]
+    fn test_repeating_gradient_spans_its_stops() {
+        assert_offsets_and_span("repeating-linear-gradient(red 20px, green, blue 70px)",
+                                [0.0, 0.5, 1.0],
+                                (0.2, 0.7));
+        // Stops that do not span any of the line do not repeat.
+        assert_offsets("repeating-linear-gradient(red 50%, blue 50%)", [0.5, 0.5]);
+    }
+}
@@ -123,9 +123,13 @@ pub fn compute_damage(old: &ComputedValues, new: &ComputedValues) -> RestyleDama
     // FIXME: We can short-circuit more of this.
 
     add_if_not_equal!(old, new, damage, [ Repaint ],
-        [ get_color.color, get_background.background_color,
+        [ get_color.color, get_background.background_color, get_background.background_image,
           get_border.border_top_color, get_border.border_right_color,
-          get_border.border_bottom_color, get_border.border_left_color ]);
+          get_border.border_bottom_color, get_border.border_left_color,
+          get_border.border_top_left_radius, get_border.border_top_right_radius,
+          get_border.border_bottom_right_radius, get_border.border_bottom_left_radius,
+          get_box.z_index, get_effects.opacity, get_effects.box_shadow, get_effects.transform,
+          get_effects.transform_origin, get_inheritedtext.text_shadow ]);
 
     add_if_not_equal!(old, new, damage, [ Repaint, BubbleWidths, Reflow ],
         [ get_border.border_top_width, get_border.border_right_width,
@@ -5,20 +5,25 @@
 use css::node_style::StyledNode;
 use layout::context::LayoutContext;
 use layout::floats::{FloatLeft, Floats, PlacementInfo};
-use layout::flow::{BaseFlow, FlowClass, Flow, InlineFlowClass};
+use layout::flow::{BaseFlow, FlowClass, Flow, InlineFlowClass, MutableFlowUtils};
+use layout::flow::{PostorderFlowTraversal, PreorderFlowTraversal};
 use layout::flow;
-use layout::fragment::{Fragment, ScannedTextFragment, ScannedTextFragmentInfo, SplitInfo};
+use layout::fragment::{Fragment, InlineBlockFragment, ScannedTextFragment};
+use layout::fragment::{ScannedTextFragmentInfo, SplitInfo};
 use layout::model::IntrinsicWidths;
 use layout::model;
 use layout::text;
+use layout::traversal::{AssignHeightsAndStoreOverflowTraversal, AssignWidthsTraversal};
+use layout::traversal::{BubbleWidthsTraversal, BuildDisplayListTraversal};
 use layout::wrapper::ThreadSafeLayoutNode;
 
 use collections::{Deque, RingBuf};
 use geom::{Point2D, Rect, SideOffsets2D, Size2D};
-use gfx::display_list::ContentLevel;
+use gfx::display_list::{ContentLevel, ContentStackingLevel, DisplayList, OpaqueNode};
 use gfx::font::FontMetrics;
 use gfx::font_context::FontContext;
 use gfx::text::glyph::CharIndex;
+use gfx::text::text_run::TextRun;
 use servo_util::geometry::Au;
 use servo_util::geometry;
 use servo_util::range;
@@ -27,10 +32,11 @@ use std::iter::Enumerate;
 use std::fmt;
 use std::mem;
 use std::num;
+use std::num::Zero;
 use std::slice::{Items, MutItems};
 use std::u16;
 use style::computed_values::{text_align, vertical_align, white_space};
-use style::ComputedValues;
+use style::{ComputedValues, cascade_pseudo_element_fragment};
 use sync::Arc;
 
 /// `Line`s are represented as offsets into the child list, rather than
@@ -715,16 +721,90 @@ pub struct InlineFlow {
     /// The minimum depth below the baseline for each line, as specified by the line height and
     /// font style.
     pub minimum_depth_below_baseline: Au,
+
+    /// The `::first-line` style to apply to the first line of this flow, if any. This is set only
+    /// if this flow holds the first formatted line of its block.
+    pub first_line_style: Option<FirstLineStyle>,
+
+    /// The distance from the top of this flow to the baseline of its last line, if it has any
+    /// lines. Inline-blocks are aligned on the baseline of their last line.
+    pub last_line_baseline: Option<Au>,
+}
+
+/// The styles needed to apply a `::first-line` pseudo-element to the first line of an inline flow.
+pub struct FirstLineStyle {
+    /// The style of the block that the pseudo-element belongs to.
+    pub block_style: Arc<ComputedValues>,
+    /// The style of the pseudo-element itself.
+    pub style: Arc<ComputedValues>,
+    /// The text that has been shaped with the `::first-line` style in this layout, so that text
+    /// still on the first line when the lines are broken again is not cascaded and shaped a
+    /// second time. The flow tree is rebuilt on every reflow, so this starts out empty each time.
+    shaped: Vec<FirstLineText>,
+}
+
+/// A piece of text on the first line, restyled and shaped with the `::first-line` style.
+struct FirstLineText {
+    /// The node that the text belongs to.
+    node: OpaqueNode,
+    /// The text itself.
+    text: ~str,
+    /// The style of the fragment, with the `::first-line` style cascaded onto it.
+    style: Arc<ComputedValues>,
+    /// The text run shaped with that style.
+    run: Arc<Box<TextRun>>,
+}
+
+impl FirstLineStyle {
+    pub fn new(block_style: Arc<ComputedValues>, style: Arc<ComputedValues>) -> FirstLineStyle {
+        FirstLineStyle {
+            block_style: block_style,
+            style: style,
+            shaped: Vec::new(),
+        }
+    }
+
+    /// Returns the index of the cached text for the given fragment text, cascading and shaping
+    /// it first if it is not cached yet.
+    fn shape(&mut self, font_context: &mut FontContext, fragment: &Fragment, text: ~str) -> uint {
+        match self.shaped.iter().position(|shaped| {
+            shaped.node == fragment.node && shaped.text == text
+        }) {
+            Some(index) => return index,
+            None => {}
+        }
+
+        let style = Arc::new(cascade_pseudo_element_fragment(&*fragment.style,
+                                                             &*self.block_style,
+                                                             &*self.style));
+        let font_style = text::computed_style_to_font_style(&*style);
+        let fontgroup = font_context.get_resolved_font_for_style(&font_style);
+        let run = fontgroup.borrow().create_textrun(text.clone(),
+                                                    style.get_text().text_decoration);
+        self.shaped.push(FirstLineText {
+            node: fragment.node,
+            text: text,
+            style: style,
+            run: Arc::new(box run),
+        });
+        self.shaped.len() - 1
+    }
 }
 
 impl InlineFlow {
     pub fn from_fragments(node: ThreadSafeLayoutNode, fragments: InlineFragments) -> InlineFlow {
+        InlineFlow::from_base_and_fragments(BaseFlow::new(node), fragments)
+    }
+
+    pub fn from_base_and_fragments(base: BaseFlow, fragments: InlineFragments) -> InlineFlow {
         InlineFlow {
-            base: BaseFlow::new(node),
+            base: base,
             fragments: fragments,
             lines: Vec::new(),
             minimum_height_above_baseline: Au(0),
             minimum_depth_below_baseline: Au(0),
+            first_line_style: None,
+            last_line_baseline: None,
         }
     }
 
@@ -748,12 +828,61 @@ impl InlineFlow {
                                              self.base.abs_position + rel_offset,
                                              ContentLevel,
                                              Some(context)));
+
+            match fragment.specific {
+                InlineBlockFragment(ref mut info) => {
+                    let block_flow = unsafe { info.flow_ref.get_mut() };
+                    let margin_top = block_flow.as_block().fragment.margin.top;
+                    {
+                        let block_base = flow::mut_base(block_flow);
+                        block_base.abs_position = self.base.abs_position + rel_offset +
+                            fragment.border_box.origin + Point2D(Au(0), margin_top);
+                        block_base.absolute_position_info = self.base.absolute_position_info;
+                    }
+                    let mut traversal = BuildDisplayListTraversal {
+                        layout_context: layout_context,
+                    };
+                    traversal.process(block_flow);
+
+                    // Inline-blocks are painted atomically, as if they established a stacking
+                    // context (CSS 2.1 Appendix E).
+                    let block_display_list = mem::replace(&mut flow::mut_base(block_flow)
+                                                                   .display_list,
+                                                          DisplayList::new());
+                    self.base.display_list.push_all_move(block_display_list.flatten(
+                            ContentStackingLevel))
+                }
+                _ => {}
+            }
         }
+    }
 
-        // TODO(#225): Should `inline-block` elements have flows as children of the inline flow or
-        // should the flow be nested inside the fragment somehow?
+    /// Lays out the block flows of the inline-block fragments of this flow with the given
+    /// preorder traversal.
+    fn traverse_inline_block_flows_preorder<T:PreorderFlowTraversal>(&mut self,
+                                                                      traversal: &mut T) {
+        for fragment in self.fragments.fragments.mut_iter() {
+            match fragment.specific {
+                InlineBlockFragment(ref mut info) => {
+                    drop(unsafe { info.flow_ref.get_mut() }.traverse_preorder(traversal))
+                }
+                _ => {}
+            }
+        }
+    }
 
-        // For now, don't traverse the subtree rooted here.
+    /// Lays out the block flows of the inline-block fragments of this flow with the given
+    /// postorder traversal.
+    fn traverse_inline_block_flows_postorder<T:PostorderFlowTraversal>(&mut self,
+                                                                        traversal: &mut T) {
+        for fragment in self.fragments.fragments.mut_iter() {
+            match fragment.specific {
+                InlineBlockFragment(ref mut info) => {
+                    drop(unsafe { info.flow_ref.get_mut() }.traverse_postorder(traversal))
+                }
+                _ => {}
+            }
+        }
     }
 
     /// Returns the distance from the baseline for the logical top left corner of this fragment,
@@ -855,6 +984,62 @@ impl InlineFlow {
         }
     }
 
+    /// Restyles the text fragments on the first line, as broken by the FTL inline pass, with the
+    /// `::first-line` style, if any. The restyled text is shaped only the first time it shows up
+    /// on the first line in each layout. Returns true if any fragment was restyled, in which case
+    /// the lines need to be broken again.
+    ///
+    /// FIXME: Content that moves off the first line when breaking again keeps the
+    /// `::first-line` style, and content that moves onto it does not get it.
+    pub fn apply_first_line_style(&mut self, font_context: &mut FontContext) -> bool {
+        let first_line_style = match self.first_line_style {
+            None => return false,
+            Some(ref mut first_line_style) => first_line_style,
+        };
+
+        let mut restyled = false;
+        for fragment in self.fragments.fragments.mut_iter() {
+            let ends_line = fragment.ftl_attrs.endofline;
+            let text = match fragment.specific {
+                ScannedTextFragment(ref text_fragment_info) => {
+                    let range = text_fragment_info.range;
+                    text_fragment_info.run.text.as_slice().slice_chars(range.begin().to_uint(),
+                                                                       range.end().to_uint())
+                                                          .to_owned()
+                }
+                _ => {
+                    if ends_line {
+                        break
+                    }
+                    continue
+                }
+            };
+
+            let index = first_line_style.shape(font_context, fragment, text);
+            let shaped = first_line_style.shaped.get(index);
+            let is_shaped = match fragment.specific {
+                ScannedTextFragment(ref text_fragment_info) => {
+                    &**text_fragment_info.run as *TextRun == &**shaped.run as *TextRun
+                }
+                _ => false,
+            };
+            if !is_shaped {
+                let range = Range::new(CharIndex(0), shaped.run.char_len());
+                let size = shaped.run.metrics_for_range(&range).bounding_box.size;
+                let info = ScannedTextFragmentInfo::new(shaped.run.clone(), range);
+                let mut new_fragment = fragment.transform(size, ScannedTextFragment(info));
+                new_fragment.style = shaped.style.clone();
+                *fragment = new_fragment;
+                restyled = true
+            }
+
+            if ends_line {
+                break
+            }
+        }
+        restyled
+    }
+
     /// Computes the minimum ascent and descent for each line. This is done during flow
     /// construction.
     ///
@@ -884,34 +1069,47 @@ impl Flow for InlineFlow {
         self
     }
 
-    fn bubble_widths(&mut self, _: &mut LayoutContext) {
+    fn bubble_widths(&mut self, layout_context: &mut LayoutContext) {
         for kid in self.base.child_iter() {
             flow::mut_base(kid).floats = Floats::new();
         }
 
-        let mut intrinsic_widths = IntrinsicWidths::new();
-        for (fragment, context) in self.fragments.mut_iter() {
-            debug!("Flow: measuring {}", *fragment);
-
-            let fragment_intrinsic_widths = fragment.intrinsic_widths(Some(context));
-            intrinsic_widths.minimum_width = geometry::max(intrinsic_widths.minimum_width,
-                                                           fragment_intrinsic_widths.minimum_width);
-            intrinsic_widths.preferred_width = geometry::max(intrinsic_widths.preferred_width,
-                                                             fragment_intrinsic_widths.preferred_width);
-        }
+        self.traverse_inline_block_flows_postorder(&mut BubbleWidthsTraversal {
+            layout_context: &mut *layout_context,
+        });
 
-        self.base.intrinsic_widths = intrinsic_widths;
+        self.base.intrinsic_widths =
+            combine_fragment_intrinsic_widths(self.fragments.mut_iter().map(|(fragment, context)| {
+                debug!("Flow: measuring {}", *fragment);
+                (fragment.intrinsic_widths(Some(context)), !fragment.new_line_pos.is_empty())
+            }));
     }
 
     /// Recursively (top-down) determines the actual width of child contexts and fragments. When called
     /// on this context, the context has had its width set by the parent context.
-    fn assign_widths(&mut self, _: &mut LayoutContext) {
+    fn assign_widths(&mut self, layout_context: &mut LayoutContext) {
         // Initialize content fragment widths if they haven't been initialized already.
         //
         // TODO: Combine this with `LineBreaker`'s walk in the fragment list, or put this into `Fragment`.
 
         debug!("InlineFlow::assign_widths: floats in: {:?}", self.base.floats);
 
+        // The block flows of inline-blocks shrink to fit in the width of this flow.
+        let width = self.base.position.size.width;
+        for fragment in self.fragments.fragments.mut_iter() {
+            match fragment.specific {
+                InlineBlockFragment(ref mut info) => {
+                    let block_base = flow::mut_base(unsafe { info.flow_ref.get_mut() });
+                    block_base.position.origin = Zero::zero();
+                    block_base.position.size.width = width
+                }
+                _ => {}
+            }
+        }
+        self.traverse_inline_block_flows_preorder(&mut AssignWidthsTraversal {
+            layout_context: &mut *layout_context,
+        });
+
         {
             let this = &mut *self;
             for (fragment, context) in this.fragments.mut_iter() {
@@ -923,17 +1121,12 @@ impl Flow for InlineFlow {
         assert!(self.base.children.len() == 0,
                 "InlineFlow: should not have children flows in the current layout implementation.");
 
-        // There are no child contexts, so stop here.
-
-        // TODO(Issue #225): once there are 'inline-block' elements, this won't be
-        // true.  In that case, set the InlineBlockFragment's width to the
-        // shrink-to-fit width, perform inline flow, and set the block
-        // flow context's width as the assigned width of the
-        // 'inline-block' fragment that created this flow before recursing.
+        // There are no child contexts, so stop here. The flows of inline-blocks are nested in
+        // their fragments instead.
     }
 
     /// Calculate and set the height of this flow. See CSS 2.1 § 10.6.1.
-    fn assign_height(&mut self, _: &mut LayoutContext) {
+    fn assign_height(&mut self, layout_context: &mut LayoutContext) {
         debug!("assign_height_inline: assigning height for flow");
 
         // Divide the fragments into lines.
@@ -948,6 +1141,9 @@ impl Flow for InlineFlow {
         debug!("assign_height_inline: floats in: {:?}", self.base.floats);
 
         // assign height for inline fragments
+        self.traverse_inline_block_flows_postorder(&mut AssignHeightsAndStoreOverflowTraversal {
+            layout_context: &mut *layout_context,
+        });
         for (fragment, _) in self.fragments.mut_iter() {
             fragment.assign_replaced_height_if_necessary();
         }
@@ -961,6 +1157,7 @@ impl Flow for InlineFlow {
 
         // Now, go through each line and lay out the fragments inside.
         let mut line_distance_from_flow_top = Au(0);
+        self.last_line_baseline = None;
         for line in self.lines.mut_iter() {
             // Lay out fragments horizontally.
             InlineFlow::set_horizontal_fragment_positions(&mut self.fragments, line, text_align);
@@ -1051,6 +1248,8 @@ impl Flow for InlineFlow {
             // Now, the distance from the logical top of the line to the baseline can be
             // computed as `largest_height_above_baseline`.
             let baseline_distance_from_top = largest_height_above_baseline;
+            self.last_line_baseline = Some(line_distance_from_flow_top +
+                                           baseline_distance_from_top);
 
             // Compute the final positions in the block direction of each fragment. Recall that
             // `fragment.border_box.origin.y` was set to the distance from the baseline above.
@@ -1368,3 +1567,66 @@ impl InlineMetrics {
     }
 }
 
+/// Combines the intrinsic widths of the fragments of an inline flow, in order, into those of the
+/// flow. Each fragment comes with whether a forced line break ends it. Lines may break between any
+/// two fragments, so the minimum width is that of the widest fragment. Without a forced line break
+/// the fragments are laid out side by side, so the preferred width is the widest sum of the
+/// preferred widths of the fragments between two forced line breaks (CSS 2.1 § 10.3.5). The
+/// legacy layout took the widest fragment instead, which made shrink-to-fit boxes too narrow for
+/// a line of several fragments, such as text next to an inline-block.
+///
+/// FIXME: A fragment with a forced line break counts its whole preferred width on the line that
+/// it ends.
+pub fn combine_fragment_intrinsic_widths<I:Iterator<(IntrinsicWidths, bool)>>(mut fragments: I)
+                                                                             -> IntrinsicWidths {
+    let mut intrinsic_widths = IntrinsicWidths::new();
+    let mut line_preferred_width = Au(0);
+    for (fragment_intrinsic_widths, forced_line_break) in fragments {
+        intrinsic_widths.minimum_width = geometry::max(intrinsic_widths.minimum_width,
+                                                       fragment_intrinsic_widths.minimum_width);
+        line_preferred_width = line_preferred_width + fragment_intrinsic_widths.preferred_width;
+        intrinsic_widths.preferred_width = geometry::max(intrinsic_widths.preferred_width,
+                                                         line_preferred_width);
+        if forced_line_break {
+            line_preferred_width = Au(0)
+        }
+    }
+    intrinsic_widths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::combine_fragment_intrinsic_widths;
+    use layout::model::IntrinsicWidths;
+
+    use servo_util::geometry::Au;
+
+    fn widths(minimum_width: int, preferred_width: int) -> IntrinsicWidths {
+        IntrinsicWidths {
+            minimum_width: Au(minimum_width),
+            preferred_width: Au(preferred_width),
+            surround_width: Au(0),
+        }
+    }
+
+    #[test]
+    fn test_combine_fragment_intrinsic_widths() {
+        let empty: Vec<(IntrinsicWidths, bool)> = vec!();
+        let combined = combine_fragment_intrinsic_widths(empty.move_iter());
+        assert_eq!((combined.minimum_width, combined.preferred_width), (Au(0), Au(0)));
+
+        // Text next to an inline-block fits on one line that is as wide as both.
+        let combined = combine_fragment_intrinsic_widths(
+            vec!((widths(30, 100), false), (widths(50, 50), false)).move_iter());
+        assert_eq!((combined.minimum_width, combined.preferred_width), (Au(50), Au(150)));
+
+        // Forced line breaks start new lines, and the widest line wins.
+        let combined = combine_fragment_intrinsic_widths(
+            vec!((widths(10, 40), false),
+                 (widths(20, 20), true),
+                 (widths(70, 70), false),
+                 (widths(5, 5), true),
+                 (widths(10, 10), false)).move_iter());
+        assert_eq!((combined.minimum_width, combined.preferred_width), (Au(70), Au(75)));
+    }
+}
@@ -6,18 +6,24 @@
 /// rendered.
 
 use css::matching::{ApplicableDeclarations, ApplicableDeclarationsCache, MatchMethods};
-use css::matching::{StyleSharingCandidateCache};
+use css::matching::{StyleSharingCandidateCache, insert_ancestors_into_bloom_filter};
 use css::select::new_stylist;
 use css::node_style::StyledNode;
+use layout::animation::{FRAME_INTERVAL_MS, KeyframesAnimation, PropertyTransition};
 use layout::construct::{FlowConstructionResult, NoConstructionResult};
 use layout::context::LayoutContext;
 
 use layout::flow::{Flow, ImmutableFlowUtils, MutableFlowUtils, MutableOwnedFlowUtils};
-use layout::flow::{PreorderFlowTraversal, PostorderFlowTraversal};
 use layout::flow;
-use layout::incremental::RestyleDamage;
+use layout::flow_dump;
+use layout::generated_content;
 use layout::parallel::PaddedUnsafeFlow;
 use layout::parallel;
+use layout::traversal::{AssignHeightsAndStoreOverflowTraversal, AssignWidthsTraversal};
+use layout::traversal::{BubbleWidthsTraversal, BuildDisplayListTraversal};
+use layout::traversal::{ComputeDamageTraversal, PropagateDamageTraversal};
+#[cfg(debug)]
+use layout::traversal::FlowTreeVerificationTraversal;
 use layout::util::{LayoutDataAccess, LayoutDataWrapper, OpaqueNodeMethods};
 use layout::wrapper::{LayoutNode, TLayoutNode};
 
@@ -30,42 +36,54 @@ use layout::wrapper::{LayoutNode, TLayoutNode};
 //use gfx::display_list::{DisplayList};
 
 //use collections::dlist::DList;
+use collections::hashmap::HashMap;
 use geom::point::Point2D;
 use geom::rect::Rect;
 use geom::size::Size2D;
-use gfx::display_list::{ClipDisplayItemClass, ContentStackingLevel, DisplayItem};
-use gfx::display_list::{DisplayItemIterator, DisplayList, OpaqueNode};
+use gfx::display_list::{BoxShadowDisplayItemClass, ClipDisplayItemClass, ContentStackingLevel};
+use gfx::display_list::{DisplayItem, IndexedDisplayList, OpaqueNode};
+use gfx::display_list::{StackingContextDisplayItemClass, TextShadowDisplayItemClass};
+use gfx::display_list_serialization;
 
 use gfx::font_context::{FontContext, FontContextInfo};
 use gfx::render_task::{RenderMsg, RenderChan, RenderLayer};
 use gfx::{render_task, color};
 use script::dom::bindings::js::JS;
-use script::dom::event::ReflowEvent;
+use script::dom::event::{AnimationEndEvent, AnimationFrameEvent, AnimationIterationEvent};
+use script::dom::event::{AnimationStartEvent};
+use script::dom::event::{ReflowEvent, TransitionEndEvent};
 use script::dom::node::{LayoutDataRef, Node};
 //use script::dom::element::{HTMLBodyElementTypeId, HTMLHtmlElementTypeId};
-use script::layout_interface::{AddStylesheetMsg, ContentBoxQuery};
+use script::layout_interface::{AddStylesheetMsg, ComputedStyleQuery, ComputedStyleResponse};
+use script::layout_interface::{ContentBoxQuery};
 use script::layout_interface::{ContentBoxesQuery, ContentBoxesResponse, ExitNowMsg, LayoutQuery};
 use script::layout_interface::{HitTestQuery, ContentBoxResponse, HitTestResponse, MouseOverQuery, MouseOverResponse};
 use script::layout_interface::{ContentChangedDocumentDamage, LayoutChan, Msg, PrepareToExitMsg};
+use script::layout_interface::{RestyleDocumentDamage};
 use script::layout_interface::{QueryMsg, ReapLayoutDataMsg, Reflow, UntrustedNodeAddress};
-use script::layout_interface::{ReflowMsg};
+use script::layout_interface::{ReflowMsg, SetScrollOffsetMsg};
 use script::script_task::{ReflowCompleteMsg, ScriptChan, SendEventMsg};
+use servo_msg::compositor_msg::{FixedPosition, LayerId, OverflowScroll, ScrollPolicy};
 use servo_msg::compositor_msg::Scrollable;
 use servo_msg::constellation_msg::{ConstellationChan, PipelineId, Failure, FailureMsg};
 use servo_net::image::holder::LocalImageCacheHandle;
 use servo_net::image_cache_task::{ImageCacheTask, ImageResponseMsg};
 use servo_net::local_image_cache::{ImageResponder, LocalImageCache};
+use servo_util::bloom::BloomFilter;
 use servo_util::geometry::Au;
 use servo_util::geometry;
 use servo_util::opts::Opts;
 use servo_util::smallvec::{SmallVec, SmallVec1};
 use servo_util::time::{ProfilerChan, profile};
 use servo_util::time;
-use servo_util::task::send_on_failure;
+use servo_util::task::{send_on_failure, spawn_named};
 use servo_util::workqueue::WorkQueue;
 use std::cast::transmute;
 use std::cast;
+use std::cmp;
 use std::comm::{channel, Sender, Receiver};
+use std::io::File;
+use std::io::timer::sleep;
 use std::mem;
 use std::ptr;
 //use std::task;
@@ -73,12 +91,12 @@ use std::ptr;
 //use style::computed_values::{border_style};
 //use style;
 use std::task::TaskBuilder;
-use style::{AuthorOrigin, Stylesheet, Stylist};
+use time::precise_time_s;
+use style::{After, AuthorOrigin, Before, FirstLetter, FirstLine, Stylesheet, Stylist};
 use sync::{Arc, Mutex};
 use url::Url;
 
-use layout::ftl_layout::{layout};
-use layout::ftl_lib::{as_ftl_node};
+use layout::ftl_lib::layout_with_layers;
 
 /// Information needed by the layout task.
 pub struct LayoutTask {
@@ -110,7 +128,15 @@ pub struct LayoutTask {
     pub screen_size: Size2D<Au>,
 
     /// A cached display list.
-    pub display_list: Option<Arc<DisplayList>>,
+    pub display_list: Option<Arc<IndexedDisplayList>>,
+
+    /// The display lists of the layers other than the root layer, bottommost first. Queries look
+    /// at these as well as at `display_list`.
+    pub layer_display_lists: Vec<LayerDisplayList>,
+
+    /// The scroll offsets of the page and of its scrollable boxes as last reported by script,
+    /// keyed by layer ID. The page itself has the null layer ID.
+    pub scroll_offsets: HashMap<LayerId, Point2D<f32>>,
 
     pub stylist: Box<Stylist>,
 
@@ -125,149 +151,56 @@ pub struct LayoutTask {
 
     /// The dirty rect. Used during display list construction.
     pub dirty: Rect<Au>,
-}
 
-/// The damage computation traversal.
-#[deriving(Clone)]
-struct ComputeDamageTraversal;
+    /// Whether a stylesheet has been added since the last reflow, in which case every node must
+    /// be restyled regardless of restyle hints.
+    pub stylesheets_changed: bool,
 
-impl PostorderFlowTraversal for ComputeDamageTraversal {
-    #[inline]
-    fn process(&mut self, flow: &mut Flow) -> bool {
-        let mut damage = flow::base(flow).restyle_damage;
-        for child in flow::child_iter(flow) {
-            damage.union_in_place(flow::base(child).restyle_damage.propagate_up())
-        }
-        flow::mut_base(flow).restyle_damage = damage;
-        true
-    }
-}
+    /// A counter incremented on every reflow. Used to invalidate per-thread state, such as the
+    /// style Bloom filters, that must not survive from one reflow to the next.
+    pub generation: uint,
 
-/// Propagates restyle damage up and down the tree as appropriate.
-///
-/// FIXME(pcwalton): Merge this with flow tree building and/or other traversals.
-struct PropagateDamageTraversal {
-    all_style_damage: bool,
-}
+    /// The transitions that are running.
+    pub running_transitions: Arc<Vec<PropertyTransition>>,
 
-impl PreorderFlowTraversal for PropagateDamageTraversal {
-    #[inline]
-    fn process(&mut self, flow: &mut Flow) -> bool {
-        if self.all_style_damage {
-            flow::mut_base(flow).restyle_damage.union_in_place(RestyleDamage::all())
-        }
-        debug!("restyle damage = {:?}", flow::base(flow).restyle_damage);
+    /// The port on which the style recalc reports the transitions that run after a reflow.
+    pub transitions_port: Receiver<PropertyTransition>,
 
-        let prop = flow::base(flow).restyle_damage.propagate_down();
-        if prop.is_nonempty() {
-            for kid_ctx in flow::child_iter(flow) {
-                flow::mut_base(kid_ctx).restyle_damage.union_in_place(prop)
-            }
-        }
-        true
-    }
-}
+    /// The channel on which the style recalc reports the transitions that run after a reflow.
+    pub transitions_chan: Sender<PropertyTransition>,
 
-/// The flow tree verification traversal. This is only on in debug builds.
-#[cfg(debug)]
-struct FlowTreeVerificationTraversal;
+    /// The animations that are running, paused, or that have ended but still fill forwards.
+    pub running_animations: Arc<Vec<KeyframesAnimation>>,
 
-#[cfg(debug)]
-impl PreorderFlowTraversal for FlowTreeVerificationTraversal {
-    #[inline]
-    fn process(&mut self, flow: &mut Flow) -> bool {
-        let base = flow::base(flow);
-        if !base.flags.is_leaf() && !base.flags.is_nonleaf() {
-            println("flow tree verification failed: flow wasn't a leaf or a nonleaf!");
-            flow.dump();
-            fail!("flow tree verification failed")
-        }
-        true
-    }
-}
+    /// The port on which the style recalc reports the animations of each restyled node.
+    pub animations_port: Receiver<(OpaqueNode, Vec<KeyframesAnimation>)>,
 
-/// The bubble-widths traversal, the first part of layout computation. This computes preferred
-/// and intrinsic widths and bubbles them up the tree.
-pub struct BubbleWidthsTraversal<'a> {
-    pub layout_context: &'a mut LayoutContext,
-}
-
-impl<'a> PostorderFlowTraversal for BubbleWidthsTraversal<'a> {
-    #[inline]
-    fn process(&mut self, flow: &mut Flow) -> bool {
-        flow.bubble_widths(self.layout_context);
-        true
-    }
-
-    // FIXME: We can't prune until we start reusing flows
-    /*
-    #[inline]
-    fn should_prune(&mut self, flow: &mut Flow) -> bool {
-        flow::mut_base(flow).restyle_damage.lacks(BubbleWidths)
-    }
-    */
-}
+    /// The channel on which the style recalc reports the animations of each restyled node.
+    pub animations_chan: Sender<(OpaqueNode, Vec<KeyframesAnimation>)>,
 
-/// The assign-widths traversal. In Gecko this corresponds to `Reflow`.
-pub struct AssignWidthsTraversal<'a> {
-    pub layout_context: &'a mut LayoutContext,
-}
+    /// The time at which the reflow for the next frame of the running transitions and animations
+    /// was requested, if that reflow has not happened yet.
+    pub next_frame_time: Option<f64>,
 
-impl<'a> PreorderFlowTraversal for AssignWidthsTraversal<'a> {
-    #[inline]
-    fn process(&mut self, flow: &mut Flow) -> bool {
-        flow.assign_widths(self.layout_context);
-        true
-    }
+    /// The channel on which the reflow for the next frame is requested from the frame timer task,
+    /// once that task has been started.
+    pub frame_timer_chan: Option<Sender<()>>,
 }
 
-/// The assign-heights-and-store-overflow traversal, the last (and most expensive) part of layout
-/// computation. Determines the final heights for all layout objects, computes positions, and
-/// computes overflow regions. In Gecko this corresponds to `FinishAndStoreOverflow`.
-pub struct AssignHeightsAndStoreOverflowTraversal<'a> {
-    pub layout_context: &'a mut LayoutContext,
-}
-
-impl<'a> PostorderFlowTraversal for AssignHeightsAndStoreOverflowTraversal<'a> {
-    #[inline]
-    fn process(&mut self, flow: &mut Flow) -> bool {
-        flow.assign_height(self.layout_context);
-        // Skip store-overflow for absolutely positioned flows. That will be
-        // done in a separate traversal.
-        if !flow.is_store_overflow_delayed() {
-            flow.store_overflow(self.layout_context);
-        }
-        true
-    }
-
-    #[inline]
-    fn should_process(&mut self, flow: &mut Flow) -> bool {
-        !flow::base(flow).flags.impacted_by_floats()
-    }
-}
-
-/// The display list construction traversal.
-pub struct BuildDisplayListTraversal<'a> {
-    layout_context: &'a LayoutContext,
-}
-
-impl<'a> BuildDisplayListTraversal<'a> {
-    #[inline]
-    fn process(&mut self, flow: &mut Flow) {
-        flow.compute_absolute_position();
-
-        for kid in flow::mut_base(flow).child_iter() {
-            if !kid.is_absolutely_positioned() {
-                self.process(kid)
-            }
-        }
-
-        for absolute_descendant_link in flow::mut_base(flow).abs_descendants.iter() {
-            self.process(absolute_descendant_link.resolve().unwrap())
-        }
-
-        flow.build_display_list(self.layout_context)
-    }
+/// The display list of a layer other than the root layer, along with what hit testing needs to
+/// know to find the display items in it.
+pub struct LayerDisplayList {
+    /// The ID of the layer.
+    pub id: LayerId,
+    /// The scrolling policy of the layer.
+    pub scroll_policy: ScrollPolicy,
+    /// The position of the layer in pixels, as sent to the renderer.
+    pub position: Rect<uint>,
+    /// The visible part of the layer. This is in the coordinates of the viewport for fixed
+    /// layers and in page coordinates otherwise.
+    pub clip_rect: Rect<Au>,
+    /// The display items of the layer.
+    pub display_list: Arc<IndexedDisplayList>,
 }
 
 struct LayoutImageResponder {
@@ -344,6 +277,9 @@ impl LayoutTask {
             None
         };
 
+        let (transitions_chan, transitions_port) = channel();
+        let (animations_chan, animations_port) = channel();
+
         LayoutTask {
             id: id,
             port: port,
@@ -356,11 +292,23 @@ impl LayoutTask {
             screen_size: screen_size,
 
             display_list: None,
+            layer_display_lists: vec!(),
+            scroll_offsets: HashMap::new(),
             stylist: box new_stylist(),
             parallel_traversal: parallel_traversal,
             profiler_chan: profiler_chan,
             opts: opts.clone(),
             dirty: Rect::zero(),
+            stylesheets_changed: false,
+            generation: 0,
+            running_transitions: Arc::new(vec!()),
+            transitions_port: transitions_port,
+            transitions_chan: transitions_chan,
+            running_animations: Arc::new(vec!()),
+            animations_port: animations_port,
+            animations_chan: animations_chan,
+            next_frame_time: None,
+            frame_timer_chan: None,
         }
     }
 
@@ -390,6 +338,13 @@ impl LayoutTask {
             reflow_root: OpaqueNodeMethods::from_layout_node(reflow_root),
             opts: self.opts.clone(),
             dirty: Rect::zero(),
+            generation: self.generation,
+            restyle_all: true,
+            running_transitions: self.running_transitions.clone(),
+            transitions_chan: self.transitions_chan.clone(),
+            running_animations: self.running_animations.clone(),
+            animations_chan: self.animations_chan.clone(),
+            animation_time: precise_time_s(),
         }
     }
 
@@ -408,6 +363,9 @@ impl LayoutTask {
                     self.handle_query(query.take_unwrap());
                 });
             }
+            SetScrollOffsetMsg(layer_id, scroll_offset) => {
+                self.scroll_offsets.insert(layer_id, scroll_offset);
+            }
             ReapLayoutDataMsg(dead_layout_data) => {
                 unsafe {
                     self.handle_reap_layout_data(dead_layout_data)
@@ -468,7 +426,8 @@ impl LayoutTask {
     }
 
     fn handle_add_stylesheet(&mut self, sheet: Stylesheet) {
-        self.stylist.add_stylesheet(sheet, AuthorOrigin)
+        self.stylist.add_stylesheet(sheet, AuthorOrigin);
+        self.stylesheets_changed = true
     }
 
     /// Retrieves the flow tree root from the root node.
@@ -617,8 +576,30 @@ impl LayoutTask {
         }
         self.screen_size = current_screen_size;
 
+        // If only attributes used by selectors changed, restyle just the elements that the
+        // restyle hints point to. The nodes of running transitions and animations are restyled on
+        // every frame regardless, see `MatchMethods::needs_restyle`.
+        let restyle_all = all_style_damage || self.stylesheets_changed ||
+            data.damage.level != RestyleDocumentDamage;
+        self.stylesheets_changed = false;
+        if !restyle_all {
+            for mutation in data.damage.attribute_mutations.iter() {
+                let hint = self.stylist.compute_restyle_hint(
+                    mutation.local_name.as_slice(),
+                    mutation.old_value.as_ref().map(|value| value.as_slice()),
+                    mutation.new_value.as_ref().map(|value| value.as_slice()));
+                let mutated_node: LayoutNode = unsafe {
+                    let mutated_node: JS<Node> = JS::from_trusted_node_address(mutation.node);
+                    transmute(mutated_node)
+                };
+                mutated_node.note_restyle_hint(hint)
+            }
+        }
+
         // Create a layout context for use throughout the following passes.
+        self.generation += 1;
         let mut layout_ctx = self.build_layout_context(node, &data.url);
+        layout_ctx.restyle_all = restyle_all;
 
         // Create a font context, if this is sequential.
         //
@@ -639,12 +620,15 @@ impl LayoutTask {
                     let mut applicable_declarations = ApplicableDeclarations::new();
                     let mut applicable_declarations_cache = ApplicableDeclarationsCache::new();
                     let mut style_sharing_candidate_cache = StyleSharingCandidateCache::new();
+                    let mut parent_bf = box BloomFilter::new();
+                    insert_ancestors_into_bloom_filter(node, &mut *parent_bf);
                     drop(node.recalc_style_for_subtree(self.stylist,
                                                        &mut layout_ctx,
                                                        font_context_opt.take_unwrap(),
                                                        &mut applicable_declarations,
                                                        &mut applicable_declarations_cache,
                                                        &mut style_sharing_candidate_cache,
+                                                       &mut *parent_bf,
                                                        None))
                 }
                 Some(ref mut traversal) => {
@@ -655,11 +639,17 @@ impl LayoutTask {
             self.get_layout_root((*node).clone())
         });
 
+        self.update_running_animations(layout_ctx.animation_time, layout_ctx.restyle_all);
+
         // Verification of the flow tree, which ensures that all nodes were either marked as leaves
         // or as non-leaves. This becomes a no-op in release builds. (It is inconsequential to
         // memory safety but is a useful debugging tool.)
         self.verify_flow_tree(&mut layout_root);
 
+        // Now that the whole flow tree is known, replace the placeholders for list item markers
+        // and counters with text.
+        generated_content::resolve_generated_content(layout_root, &mut layout_ctx);
+
         // Propagate damage.
         profile(time::LayoutDamagePropagateCategory, self.profiler_chan.clone(), || {
             layout_root.traverse_preorder(&mut PropagateDamageTraversal {
@@ -694,25 +684,30 @@ impl LayoutTask {
 
         debug!("root position:{}", flow::base(layout_root).position);
 
-        layout(as_ftl_node(layout_root));
+        let screen_size = layout_ctx.screen_size;
+        let mut layers = layout_with_layers(layout_root, screen_size, layout_ctx.font_context());
 
         debug!("Finished FTL");
 
+        self.dump_flow_tree(layout_root);
+
         let mut color = color::rgba(255.0, 255.0, 255.0, 255.0);
 
         let mut lists = flow::mut_base(layout_root).ftl_attrs.display_list.take_unwrap();
 
-        let display_list = Arc::new(lists.flatten(ContentStackingLevel));
+        let display_list = Arc::new(IndexedDisplayList::new(lists.flatten(ContentStackingLevel)));
         let root_size = flow::base(layout_root).position.size;
         let root_size = Size2D(root_size.width.to_nearest_px() as uint,
                                root_size.height.to_nearest_px() as uint);
 
-        let render_layer = RenderLayer {
+        let mut render_layer = RenderLayer {
             id: layout_root.layer_id(0),
             display_list: display_list.clone(),
             position: Rect(Point2D(0u, 0u), root_size),
+            clip_rect: Rect(Point2D(0u, 0u), root_size),
             background_color: color,
             scroll_policy: Scrollable,
+            dirty_rects: None,
         };
 
         /*
@@ -790,12 +785,36 @@ impl LayoutTask {
 
         debug!("Layout done!");
 
+        // Only the parts of the layers that changed since the last reflow need to be rendered
+        // again.
+        render_layer.dirty_rects = self.dirty_rects_for_layer(&render_layer, true);
+        for layer in layers.mut_iter() {
+            layer.dirty_rects = self.dirty_rects_for_layer(layer, false);
+        }
+
         self.display_list = Some(display_list.clone());
+        self.layer_display_lists = layers.iter().map(|layer| {
+            let clip_rect = layer.clip_rect;
+            LayerDisplayList {
+                id: layer.id,
+                scroll_policy: layer.scroll_policy,
+                position: layer.position,
+                clip_rect: Rect(Point2D(Au::from_px(clip_rect.origin.x as int),
+                                        Au::from_px(clip_rect.origin.y as int)),
+                                Size2D(Au::from_px(clip_rect.size.width as int),
+                                       Au::from_px(clip_rect.size.height as int))),
+                display_list: layer.display_list.clone(),
+            }
+        }).collect();
 
-        let mut layers = SmallVec1::new();
-        layers.push(render_layer);
+        let mut render_layers = SmallVec1::new();
+        render_layers.push(render_layer);
+        for layer in layers.move_iter() {
+            render_layers.push(layer)
+        }
+        self.dump_display_lists(&render_layers);
 
-        self.render_chan.send(RenderMsg(layers));
+        self.render_chan.send(RenderMsg(render_layers));
 
         debug!("@@@@@@@@@@@@@@@ New Flow @@@@@@@@@@@@@@@");
 
@@ -811,6 +830,234 @@ impl LayoutTask {
         chan.send(ReflowCompleteMsg(self.id, data.id));
     }
 
+    /// Collects the transitions and animations that the style recalc reported as running, fires
+    /// their events and requests a reflow for the next frame if any of them still changes.
+    fn update_running_animations(&mut self, now: f64, restyle_all: bool) {
+        self.update_running_transitions(now);
+        self.update_running_keyframes_animations(now, restyle_all);
+
+        if self.running_transitions.is_empty() &&
+                !self.running_animations.iter().any(|animation| animation.needs_frames()) {
+            return
+        }
+        match self.next_frame_time {
+            Some(next_frame_time) if next_frame_time > now => return,
+            _ => {}
+        }
+        self.next_frame_time = Some(now + (FRAME_INTERVAL_MS as f64) / 1000.);
+
+        // Ask script for a reflow once the frame interval has passed. One timer task serves all
+        // the frames; it waits for a request for each frame and ends with the layout task.
+        if self.frame_timer_chan.is_none() {
+            let (frame_timer_chan, frame_timer_port) = channel();
+            let id = self.id.clone();
+            let script_chan = self.script_chan.clone();
+            spawn_named("AnimationFrameTimer", proc() {
+                let ScriptChan(chan) = script_chan;
+                for _ in frame_timer_port.iter() {
+                    sleep(FRAME_INTERVAL_MS);
+                    if chan.send_opt(SendEventMsg(id.clone(), AnimationFrameEvent)).is_err() {
+                        return
+                    }
+                }
+            });
+            self.frame_timer_chan = Some(frame_timer_chan)
+        }
+        self.frame_timer_chan.get_ref().send(())
+    }
+
+    /// Collects the transitions that the style recalc reported as running and fires
+    /// `transitionend` for those that have ended.
+    fn update_running_transitions(&mut self, now: f64) {
+        let mut running_transitions = vec!();
+        loop {
+            let transition = match self.transitions_port.try_recv() {
+                Ok(transition) => transition,
+                Err(_) => break,
+            };
+            if !transition.has_ended(now) {
+                running_transitions.push(transition);
+                continue
+            }
+            let ScriptChan(ref chan) = self.script_chan;
+            chan.send(SendEventMsg(self.id.clone(),
+                                   TransitionEndEvent(transition.node.to_untrusted_node_address(),
+                                                      transition.property.name().to_owned(),
+                                                      transition.duration as f32)));
+        }
+        self.running_transitions = Arc::new(running_transitions);
+    }
+
+    /// Collects the animations that the style recalc reported for the restyled nodes, fires
+    /// `animationstart`, `animationiteration` and `animationend` for those that reached these
+    /// points since the last reflow, and drops those that have ended and no longer fill. The nodes
+    /// that were not restyled keep their animations, unless every node was restyled.
+    fn update_running_keyframes_animations(&mut self, now: f64, restyle_all: bool) {
+        let mut reported_animations = vec!();
+        loop {
+            let (node, animations) = match self.animations_port.try_recv() {
+                Ok(report) => report,
+                Err(_) => break,
+            };
+            reported_animations.retain(|animation: &KeyframesAnimation| animation.node != node);
+            reported_animations.push_all_move(animations)
+        }
+        let kept_animations = if restyle_all {
+            vec!()
+        } else {
+            self.running_animations.iter().filter(|animation| {
+                !reported_animations.iter().any(|reported| reported.node == animation.node)
+            }).map(|animation| animation.clone()).collect()
+        };
+
+        let mut running_animations = vec!();
+        for mut animation in reported_animations.move_iter() {
+            if animation.elapsed_time(now) >= 0. {
+                let address = animation.node.to_untrusted_node_address();
+                let ScriptChan(ref chan) = self.script_chan;
+                if animation.reported_iteration.is_none() {
+                    animation.reported_iteration = Some(0);
+                    chan.send(SendEventMsg(self.id.clone(),
+                                           AnimationStartEvent(address,
+                                                               animation.name.clone(),
+                                                               0.)));
+                }
+                if animation.has_ended(now) {
+                    if !animation.ended {
+                        animation.ended = true;
+                        let active_duration = animation.active_duration().unwrap();
+                        chan.send(SendEventMsg(self.id.clone(),
+                                               AnimationEndEvent(address,
+                                                                 animation.name.clone(),
+                                                                 active_duration as f32)));
+                    }
+                    if !animation.fills_forwards() {
+                        continue
+                    }
+                } else {
+                    let iteration = animation.current_iteration(now);
+                    if Some(iteration) > animation.reported_iteration {
+                        animation.reported_iteration = Some(iteration);
+                        let elapsed_time = (iteration as f64) * animation.duration;
+                        chan.send(SendEventMsg(self.id.clone(),
+                                               AnimationIterationEvent(address,
+                                                                       animation.name.clone(),
+                                                                       elapsed_time as f32)));
+                    }
+                }
+            }
+            running_animations.push(animation)
+        }
+        running_animations.push_all_move(kept_animations);
+        self.running_animations = Arc::new(running_animations);
+    }
+
+    /// Returns the display lists that can have items at the given point in page coordinates,
+    /// topmost first, each paired with the point in the coordinates of its items.
+    fn display_lists_at_point<'a>(&'a self, point: Point2D<Au>)
+                                  -> Vec<(Point2D<Au>, &'a IndexedDisplayList)> {
+        let page_scroll_offset = self.scroll_offset(LayerId::null());
+        let mut display_lists = vec!();
+        for layer in self.layer_display_lists.iter().rev() {
+            // Fixed layers stay in the viewport while the page scrolls, and scrollable boxes
+            // move their contents by their own scroll offsets.
+            let (visible_point, item_point) = match layer.scroll_policy {
+                FixedPosition => {
+                    let point_in_viewport = point - page_scroll_offset;
+                    (point_in_viewport, point_in_viewport)
+                }
+                OverflowScroll => (point, point + self.scroll_offset(layer.id)),
+                Scrollable => (point, point),
+            };
+            if geometry::rect_contains_point(layer.clip_rect, visible_point) {
+                display_lists.push((item_point, &*layer.display_list))
+            }
+        }
+        match self.display_list {
+            None => fail!("no display list!"),
+            Some(ref display_list) => display_lists.push((point, &**display_list)),
+        }
+        display_lists
+    }
+
+    /// Writes the render layers to the file given with `--dump-display-list`, if any, so that they
+    /// can be rendered again with `--replay-display-list`.
+    fn dump_display_lists(&self, render_layers: &SmallVec1<RenderLayer>) {
+        let path = match self.opts.dump_display_list {
+            None => return,
+            Some(ref path) => path,
+        };
+        let result = File::create(&Path::new(path.as_slice())).and_then(|mut file| {
+            display_list_serialization::write_layers(&mut file, render_layers.iter())
+        });
+        match result {
+            Ok(()) => {}
+            Err(error) => error!("failed to dump the display list to {}: {}", *path, error),
+        }
+    }
+
+    /// Writes the flow tree to the file given with `--dump-flow-tree`, if any.
+    fn dump_flow_tree(&self, layout_root: &Flow) {
+        let path = match self.opts.dump_flow_tree {
+            None => return,
+            Some(ref path) => path,
+        };
+        let result = File::create(&Path::new(path.as_slice())).and_then(|mut file| {
+            flow_dump::write_flow_tree(&mut file, layout_root)
+        });
+        match result {
+            Ok(()) => {}
+            Err(error) => error!("failed to dump the flow tree to {}: {}", *path, error),
+        }
+    }
+
+    /// Returns the parts of the given layer that render differently than they did after the last
+    /// reflow, in pixels relative to the origin of the layer. Returns `None` if the layer is new
+    /// or has moved, in which case all of it must be rendered again.
+    fn dirty_rects_for_layer(&self, layer: &RenderLayer, is_root: bool)
+                             -> Option<Vec<Rect<uint>>> {
+        let old_display_list = if is_root {
+            match self.display_list {
+                None => return None,
+                Some(ref display_list) => display_list,
+            }
+        } else {
+            match self.layer_display_lists.iter().find(|old| old.id == layer.id) {
+                Some(old) if old.position == layer.position => &old.display_list,
+                _ => return None,
+            }
+        };
+
+        // Round outward, so that pixels the changed items cover only partially are rendered
+        // again too.
+        let origin = layer.position.origin;
+        let dirty_rects = layer.display_list.dirty_rects(&**old_display_list);
+        Some(dirty_rects.iter().filter_map(|rect| {
+            let left = geometry::to_frac_px(rect.origin.x).floor() as int - origin.x as int;
+            let top = geometry::to_frac_px(rect.origin.y).floor() as int - origin.y as int;
+            let right = geometry::to_frac_px(rect.origin.x + rect.size.width).ceil() as int -
+                origin.x as int;
+            let bottom = geometry::to_frac_px(rect.origin.y + rect.size.height).ceil() as int -
+                origin.y as int;
+            let (left, top) = (cmp::max(left, 0), cmp::max(top, 0));
+            if right <= left || bottom <= top {
+                return None
+            }
+            Some(Rect(Point2D(left as uint, top as uint),
+                      Size2D((right - left) as uint, (bottom - top) as uint)))
+        }).collect())
+    }
+
+    /// Returns the last scroll offset script reported for the given layer.
+    fn scroll_offset(&self, layer_id: LayerId) -> Point2D<Au> {
+        match self.scroll_offsets.find(&layer_id) {
+            None => Point2D(Au(0), Au(0)),
+            Some(offset) => {
+                Point2D(Au::from_frac_px(offset.x as f64), Au::from_frac_px(offset.y as f64))
+            }
+        }
+    }
+
     /// Handles a query from the script task. This is the main routine that DOM functions like
     /// `getClientRects()` or `getBoundingClientRect()` ultimately invoke.
     fn handle_query(&self, query: LayoutQuery) {
@@ -819,8 +1066,9 @@ impl LayoutTask {
             // need to compare nodes for equality. Thus we can safely work only with `OpaqueNode`.
             ContentBoxQuery(node, reply_chan) => {
                 let node: OpaqueNode = OpaqueNodeMethods::from_script_node(node);
-                fn union_boxes_for_node(accumulator: &mut Option<Rect<Au>>,
-                                        mut iter: DisplayItemIterator,
+                fn union_boxes_for_node<'a,I:Iterator<&'a DisplayItem>>(
+                                        accumulator: &mut Option<Rect<Au>>,
+                                        mut iter: I,
                                         node: OpaqueNode) {
                     for item in iter {
                         union_boxes_for_node(accumulator, item.children(), node);
@@ -840,13 +1088,19 @@ impl LayoutTask {
                         union_boxes_for_node(&mut rect, display_list.iter(), node)
                     }
                 }
+                for layer in self.layer_display_lists.iter() {
+                    union_boxes_for_node(&mut rect,
+                                         layer.display_list.iter(),
+                                         node)
+                }
                 reply_chan.send(ContentBoxResponse(rect.unwrap_or(Rect::zero())))
             }
             ContentBoxesQuery(node, reply_chan) => {
                 let node: OpaqueNode = OpaqueNodeMethods::from_script_node(node);
 
-                fn add_boxes_for_node(accumulator: &mut Vec<Rect<Au>>,
-                                      mut iter: DisplayItemIterator,
+                fn add_boxes_for_node<'a,I:Iterator<&'a DisplayItem>>(
+                                      accumulator: &mut Vec<Rect<Au>>,
+                                      mut iter: I,
                                       node: OpaqueNode) {
                     for item in iter {
                         add_boxes_for_node(accumulator, item.children(), node);
@@ -863,6 +1117,9 @@ impl LayoutTask {
                         add_boxes_for_node(&mut boxes, display_list.iter(), node)
                     }
                 }
+                for layer in self.layer_display_lists.iter() {
+                    add_boxes_for_node(&mut boxes, layer.display_list.iter(), node)
+                }
                 reply_chan.send(ContentBoxesResponse(boxes))
             }
             HitTestQuery(_, point, reply_chan) => {
@@ -879,6 +1136,27 @@ impl LayoutTask {
                                 }
                                 continue
                             }
+                            StackingContextDisplayItemClass(ref stacking_context) => {
+                                // Descendants of a stacking context may overflow its bounds.
+                                // Test them in their own, possibly transformed, coordinates.
+                                let point = match stacking_context.point_in_children(
+                                        &Point2D(x, y)) {
+                                    None => continue,
+                                    Some(point) => point,
+                                };
+                                let ret = hit_test(point.x, point.y, stacking_context.children
+                                                                                     .list
+                                                                                     .iter()
+                                                                                     .rev());
+                                if !ret.is_none() {
+                                    return ret
+                                }
+                                continue
+                            }
+                            // Shadows do not affect hit testing.
+                            BoxShadowDisplayItemClass(..) | TextShadowDisplayItemClass(..) => {
+                                continue
+                            }
                             _ => {}
                         }
 
@@ -899,15 +1177,15 @@ impl LayoutTask {
                     ret
                 }
 
-                let (x, y) = (Au::from_frac_px(point.x as f64),
-                              Au::from_frac_px(point.y as f64));
-                let resp = match self.display_list {
-                    None => fail!("no display list!"),
-                    Some(ref display_list) => hit_test(x, y, display_list.list.iter().rev()),
-                };
-                if resp.is_some() {
-                    reply_chan.send(Ok(resp.unwrap()));
-                    return
+                let point = Point2D(Au::from_frac_px(point.x as f64),
+                                    Au::from_frac_px(point.y as f64));
+                for &(point, display_list) in self.display_lists_at_point(point).iter() {
+                    let items = display_list.items_at_point(point);
+                    let resp = hit_test(point.x, point.y, items.move_iter().rev());
+                    if resp.is_some() {
+                        reply_chan.send(Ok(resp.unwrap()));
+                        return
+                    }
                 }
                 reply_chan.send(Err(()));
 
@@ -924,6 +1202,21 @@ impl LayoutTask {
                             ClipDisplayItemClass(ref cc) => {
                                 mouse_over_test(x, y, cc.children.list.iter().rev(), result);
                             }
+                            StackingContextDisplayItemClass(ref stacking_context) => {
+                                match stacking_context.point_in_children(&Point2D(x, y)) {
+                                    None => {}
+                                    Some(point) => {
+                                        mouse_over_test(point.x,
+                                                        point.y,
+                                                        stacking_context.children
+                                                                        .list
+                                                                        .iter()
+                                                                        .rev(),
+                                                        result)
+                                    }
+                                }
+                            }
+                            BoxShadowDisplayItemClass(..) | TextShadowDisplayItemClass(..) => {}
                             _ => {
                                 let bounds = item.bounds();
 
@@ -943,16 +1236,14 @@ impl LayoutTask {
                 }
 
                 let mut mouse_over_list: Vec<UntrustedNodeAddress> = vec!();
-                let (x, y) = (Au::from_frac_px(point.x as f64), Au::from_frac_px(point.y as f64));
-                match self.display_list {
-                    None => fail!("no display list!"),
-                    Some(ref display_list) => {
-                        mouse_over_test(x,
-                                        y,
-                                        display_list.list.iter().rev(),
-                                        &mut mouse_over_list);
-                    }
-                };
+                let point = Point2D(Au::from_frac_px(point.x as f64),
+                                    Au::from_frac_px(point.y as f64));
+                for &(point, display_list) in self.display_lists_at_point(point).iter() {
+                    mouse_over_test(point.x,
+                                    point.y,
+                                    display_list.items_at_point(point).move_iter().rev(),
+                                    &mut mouse_over_list);
+                }
 
                 if mouse_over_list.is_empty() {
                     reply_chan.send(Err(()));
@@ -960,6 +1251,26 @@ impl LayoutTask {
                     reply_chan.send(Ok(MouseOverResponse(mouse_over_list)));
                 }
             }
+            ComputedStyleQuery(node, pseudo_element, reply_chan) => {
+                let node: LayoutNode = unsafe {
+                    let node: JS<Node> = JS::from_trusted_node_address(node);
+                    transmute(node)
+                };
+                let layout_data_ref = node.borrow_layout_data();
+                let style = match *layout_data_ref {
+                    None => None,
+                    Some(ref layout_data) => {
+                        match pseudo_element {
+                            None => layout_data.shared_data.style.clone(),
+                            Some(Before) => layout_data.data.before_style.clone(),
+                            Some(After) => layout_data.data.after_style.clone(),
+                            Some(FirstLine) => layout_data.data.first_line_style.clone(),
+                            Some(FirstLetter) => layout_data.data.first_letter_style.clone(),
+                        }
+                    }
+                };
+                reply_chan.send(ComputedStyleResponse(style))
+            }
         }
     }
 
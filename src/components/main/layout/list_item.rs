@@ -0,0 +1,217 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Layout for elements with `display: list-item`.
+
+use layout::block::BlockFlow;
+use layout::construct::FlowConstructor;
+use layout::context::LayoutContext;
+use layout::flow::{ListItemFlowClass, FlowClass, Flow};
+use layout::fragment::Fragment;
+use layout::wrapper::ThreadSafeLayoutNode;
+
+use geom::{Point2D, Rect};
+use gfx::display_list::{ContentLevel, DisplayList};
+use servo_msg::compositor_msg::LayerId;
+use servo_util::geometry::Au;
+use std::fmt;
+use style::computed_values::{clear, position};
+
+/// A block with a list item marker. Markers with `list-style-position: inside` are ordinary
+/// inline fragments of the first line of the item, so only `outside` markers are stored here.
+pub struct ListItemFlow {
+    pub block_flow: BlockFlow,
+
+    /// The `outside` marker of this list item, if any. It is positioned to the left of the
+    /// content box of the item, outside of its normal flow.
+    pub marker: Option<Fragment>,
+}
+
+impl ListItemFlow {
+    pub fn from_node(constructor: &mut FlowConstructor,
+                     node: &ThreadSafeLayoutNode,
+                     marker: Option<Fragment>)
+                     -> ListItemFlow {
+        ListItemFlow {
+            block_flow: BlockFlow::from_node(constructor, node),
+            marker: marker,
+        }
+    }
+
+    pub fn build_display_list_list_item(&mut self, layout_context: &LayoutContext) {
+        self.block_flow.build_display_list_block(layout_context);
+
+        match self.marker {
+            None => {}
+            Some(ref marker) => {
+                let rel_offset =
+                    self.block_flow.fragment.relative_position(&self.block_flow
+                                                                    .base
+                                                                    .absolute_position_info
+                                                                    .relative_containing_block_size,
+                                                               None);
+                let mut display_list = DisplayList::new();
+                drop(marker.build_display_list(&mut display_list,
+                                               layout_context,
+                                               self.block_flow.base.abs_position + rel_offset,
+                                               ContentLevel,
+                                               None));
+                self.block_flow.base.display_list.push_all_move(display_list)
+            }
+        }
+    }
+
+    /// Sizes the marker and positions it against the content box that the FTL passes gave the
+    /// item, in page coordinates. The item must have been positioned already.
+    pub fn place_marker_ftl(&mut self) {
+        match self.marker {
+            None => return,
+            Some(ref mut marker) => {
+                marker.assign_replaced_width_if_necessary(Au(0), None);
+                marker.assign_replaced_height_if_necessary()
+            }
+        }
+        let content_origin = {
+            let block = &self.block_flow;
+            Point2D(block.base.ftl_attrs.absx + block.ftl_attrs.ml + block.ftl_attrs.bl +
+                        block.ftl_attrs.pl,
+                    block.base.ftl_attrs.absy + block.ftl_attrs.mt + block.ftl_attrs.bt +
+                        block.ftl_attrs.pt)
+        };
+        self.position_marker(content_origin)
+    }
+
+    /// Positions the marker relative to the flow, for the legacy display list construction.
+    fn position_marker_in_flow(&mut self) {
+        let content_origin = {
+            let fragment = &self.block_flow.fragment;
+            fragment.border_box.origin +
+                Point2D(fragment.border_padding.left, fragment.border_padding.top)
+        };
+        self.position_marker(content_origin)
+    }
+
+    /// Positions the marker so that it ends half an em to the left of the content box of the item,
+    /// whose origin is given, and starts at the top of the content box.
+    ///
+    /// FIXME: The marker should be aligned with the baseline of the first line of the item.
+    fn position_marker(&mut self, content_origin: Point2D<Au>) {
+        match self.marker {
+            None => {}
+            Some(ref mut marker) => {
+                let gap = marker.style().get_font().font_size.scale_by(0.5);
+                marker.border_box.origin.x = content_origin.x - gap - marker.border_box.size.width;
+                marker.border_box.origin.y = content_origin.y
+            }
+        }
+    }
+}
+
+impl Flow for ListItemFlow {
+    fn class(&self) -> FlowClass {
+        ListItemFlowClass
+    }
+
+    fn as_immutable_list_item<'a>(&'a self) -> &'a ListItemFlow {
+        self
+    }
+
+    fn as_list_item<'a>(&'a mut self) -> &'a mut ListItemFlow {
+        self
+    }
+
+    fn as_immutable_block<'a>(&'a self) -> &'a BlockFlow {
+        &self.block_flow
+    }
+
+    fn as_block<'a>(&'a mut self) -> &'a mut BlockFlow {
+        &mut self.block_flow
+    }
+
+    fn float_clearance(&self) -> clear::T {
+        self.block_flow.float_clearance()
+    }
+
+    fn bubble_widths(&mut self, ctx: &mut LayoutContext) {
+        self.block_flow.bubble_widths(ctx);
+    }
+
+    fn assign_widths(&mut self, ctx: &mut LayoutContext) {
+        debug!("assign_widths({}): assigning width for flow", "list_item");
+        self.block_flow.assign_widths(ctx);
+
+        // Outside markers do not take up space in the item, so they are laid out as if their
+        // containing block were empty.
+        match self.marker {
+            None => {}
+            Some(ref mut marker) => marker.assign_replaced_width_if_necessary(Au(0), None),
+        }
+    }
+
+    fn assign_height(&mut self, ctx: &mut LayoutContext) {
+        debug!("assign_height: assigning height for list_item");
+        self.block_flow.assign_height(ctx);
+
+        match self.marker {
+            None => {}
+            Some(ref mut marker) => marker.assign_replaced_height_if_necessary(),
+        }
+        self.position_marker_in_flow()
+    }
+
+    fn assign_height_for_inorder_child_if_necessary(&mut self, layout_context: &mut LayoutContext)
+                                                    -> bool {
+        let impacted = self.block_flow.assign_height_for_inorder_child_if_necessary(layout_context);
+        if impacted {
+            self.position_marker_in_flow()
+        }
+        impacted
+    }
+
+    fn compute_absolute_position(&mut self) {
+        self.block_flow.compute_absolute_position()
+    }
+
+    fn mark_as_root(&mut self) {
+        self.block_flow.mark_as_root()
+    }
+
+    fn is_store_overflow_delayed(&mut self) -> bool {
+        self.block_flow.is_store_overflow_delayed()
+    }
+
+    fn is_root(&self) -> bool {
+        self.block_flow.is_root()
+    }
+
+    fn is_float(&self) -> bool {
+        self.block_flow.is_float()
+    }
+
+    fn positioning(&self) -> position::T {
+        self.block_flow.positioning()
+    }
+
+    fn is_root_of_absolute_flow_tree(&self) -> bool {
+        self.block_flow.is_root_of_absolute_flow_tree()
+    }
+
+    fn generated_containing_block_rect(&self) -> Rect<Au> {
+        self.block_flow.generated_containing_block_rect()
+    }
+
+    fn layer_id(&self, fragment_index: uint) -> LayerId {
+        self.block_flow.layer_id(fragment_index)
+    }
+
+    fn is_absolute_containing_block(&self) -> bool {
+        self.block_flow.is_absolute_containing_block()
+    }
+}
+
+impl fmt::Show for ListItemFlow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f.buf, "ListItemFlow: {}", self.block_flow)
+    }
+}
@@ -6,8 +6,11 @@
 
 use layout::fragment::Fragment;
 
+use azure::AzFloat;
 use computed = style::computed_values;
-use geom::SideOffsets2D;
+use geom::{Rect, SideOffsets2D, Size2D};
+use geom::matrix2d::Matrix2D;
+use gfx::display_list::BorderRadii;
 use style::computed_values::{LPA_Auto, LPA_Length, LPA_Percentage, LP_Length, LP_Percentage};
 use style::ComputedValues;
 use servo_util::geometry::Au;
@@ -329,6 +332,104 @@ pub fn border_from_style(style: &ComputedValues) -> SideOffsets2D<Au> {
                        border_style.border_left_width)
 }
 
+/// Resolves the `border-*-radius` properties against the size of the border box. If the radii of
+/// adjacent corners would overlap, all radii are scaled down until they no longer do, per CSS
+/// Backgrounds and Borders Level 3 § 5.5.
+pub fn border_radii_from_style(style: &ComputedValues, size: &Size2D<Au>) -> BorderRadii<Au> {
+    let border_style = style.get_border();
+    let corner = |radius: &computed::border_top_left_radius::T| {
+        Size2D(specified(radius.width, size.width), specified(radius.height, size.height))
+    };
+    let radii = BorderRadii {
+        top_left: corner(&border_style.border_top_left_radius),
+        top_right: corner(&border_style.border_top_right_radius),
+        bottom_right: corner(&border_style.border_bottom_right_radius),
+        bottom_left: corner(&border_style.border_bottom_left_radius),
+    };
+    if radii.is_square() {
+        return radii
+    }
+
+    let ratio = |length: Au, first_radius: Au, second_radius: Au| {
+        let sum = first_radius + second_radius;
+        if sum <= length {
+            1.0
+        } else {
+            geometry::to_frac_px(length) / geometry::to_frac_px(sum)
+        }
+    };
+    let factor = [
+        ratio(size.width, radii.top_left.width, radii.top_right.width),
+        ratio(size.width, radii.bottom_left.width, radii.bottom_right.width),
+        ratio(size.height, radii.top_left.height, radii.bottom_left.height),
+        ratio(size.height, radii.top_right.height, radii.bottom_right.height),
+    ].iter().fold(1.0, |a, &b| if b < a { b } else { a });
+    if factor >= 1.0 {
+        return radii
+    }
+
+    let scale = |corner: Size2D<Au>| {
+        Size2D(corner.width.scale_by(factor), corner.height.scale_by(factor))
+    };
+    BorderRadii {
+        top_left: scale(radii.top_left),
+        top_right: scale(radii.top_right),
+        bottom_right: scale(radii.bottom_right),
+        bottom_left: scale(radii.bottom_left),
+    }
+}
+
+/// Resolves the `transform` and `transform-origin` properties against the border box, which is
+/// given in page coordinates. Returns `None` if the element is not transformed. The resulting
+/// matrix maps page coordinates measured in pixels to transformed page coordinates.
+pub fn transform_from_style(style: &ComputedValues, border_box: &Rect<Au>)
+                            -> Option<Matrix2D<AzFloat>> {
+    let effects = style.get_effects();
+    if effects.transform.is_empty() {
+        return None
+    }
+
+    let size = &border_box.size;
+    let origin_x = geometry::to_frac_px(border_box.origin.x +
+                                        specified(effects.transform_origin.horizontal,
+                                                  size.width)) as AzFloat;
+    let origin_y = geometry::to_frac_px(border_box.origin.y +
+                                        specified(effects.transform_origin.vertical,
+                                                  size.height)) as AzFloat;
+
+    // The functions apply from right to left, so the rightmost one is multiplied in first.
+    let mut transform = Matrix2D::new(1.0, 0.0, 0.0, 1.0, -origin_x, -origin_y);
+    for operation in effects.transform.iter().rev() {
+        let matrix = match *operation {
+            computed::transform::Matrix(a, b, c, d, e, f) => {
+                Matrix2D::new(a as AzFloat, b as AzFloat, c as AzFloat, d as AzFloat,
+                              geometry::to_frac_px(e) as AzFloat,
+                              geometry::to_frac_px(f) as AzFloat)
+            }
+            computed::transform::Translate(x, y) => {
+                Matrix2D::new(1.0, 0.0, 0.0, 1.0,
+                              geometry::to_frac_px(specified(x, size.width)) as AzFloat,
+                              geometry::to_frac_px(specified(y, size.height)) as AzFloat)
+            }
+            computed::transform::Scale(x, y) => {
+                Matrix2D::new(x as AzFloat, 0.0, 0.0, y as AzFloat, 0.0, 0.0)
+            }
+            computed::transform::Rotate(angle) => {
+                let (sin, cos) = (angle.radians().sin() as AzFloat,
+                                  angle.radians().cos() as AzFloat);
+                Matrix2D::new(cos, sin, -sin, cos, 0.0, 0.0)
+            }
+            computed::transform::Skew(x, y) => {
+                Matrix2D::new(1.0, y.radians().tan() as AzFloat,
+                              x.radians().tan() as AzFloat, 1.0,
+                              0.0, 0.0)
+            }
+        };
+        transform = transform.mul(&matrix)
+    }
+    Some(transform.mul(&Matrix2D::new(1.0, 0.0, 0.0, 1.0, origin_x, origin_y)))
+}
+
 #[inline]
 pub fn padding_from_style(style: &ComputedValues, containing_block_width: Au)
                           -> SideOffsets2D<Au> {
@@ -339,3 +440,73 @@ pub fn padding_from_style(style: &ComputedValues, containing_block_width: Au)
                        specified(padding_style.padding_left, containing_block_width))
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::border_radii_from_style;
+
+    use geom::Size2D;
+    use gfx::display_list::BorderRadii;
+    use servo_util::geometry::Au;
+    use style::{MatchedProperty, cascade, parse_style_attribute};
+    use url::Url;
+
+    /// Returns the radii of the corners of a border box of the given size, for an element with the
+    /// given style attribute.
+    fn border_radii(style_attribute: &str, width: int, height: int) -> BorderRadii<Au> {
+        let base_url = from_str::<Url>("http://example.com/").unwrap();
+        let declarations = parse_style_attribute(style_attribute, &base_url);
+        let (style, _) = cascade([MatchedProperty::from_declarations(declarations.normal)]
+                                     .as_slice(),
+                                 false,
+                                 None,
+                                 None);
+        border_radii_from_style(&style, &Size2D(Au::from_px(width), Au::from_px(height)))
+    }
+
+    /// Returns radii in pixels, starting at the top left corner.
+    fn radii(corners: [(int, int), ..4]) -> BorderRadii<Au> {
+        let corner = |(width, height): (int, int)| Size2D(Au::from_px(width), Au::from_px(height));
+        BorderRadii {
+            top_left: corner(corners[0]),
+            top_right: corner(corners[1]),
+            bottom_right: corner(corners[2]),
+            bottom_left: corner(corners[3]),
+        }
+    }
+
+    #[test]
+    fn test_border_radii_lengths() {
+        assert!(border_radii("", 100, 100) == BorderRadii::zero());
+        assert!(border_radii("border-radius: 10px 20px / 5px", 100, 100) ==
+                radii([(10, 5), (20, 5), (10, 5), (20, 5)]));
+        assert!(border_radii("border-top-right-radius: 10px 30px", 100, 100) ==
+                radii([(0, 0), (10, 30), (0, 0), (0, 0)]));
+    }
+
+    #[test]
+    fn test_border_radii_percentages() {
+        // Horizontal radii are resolved against the width and vertical ones against the height.
+        assert!(border_radii("border-radius: 10%", 200, 100) ==
+                radii([(20, 10), (20, 10), (20, 10), (20, 10)]));
+        assert!(border_radii("border-radius: 10px 20% / 50%", 200, 100) ==
+                radii([(10, 50), (40, 50), (10, 50), (40, 50)]));
+    }
+
+    #[test]
+    fn test_overlapping_border_radii_are_scaled_down() {
+        // Radii that fit exactly are left alone.
+        assert!(border_radii("border-radius: 80px 20px / 50px", 100, 100) ==
+                radii([(80, 50), (20, 50), (80, 50), (20, 50)]));
+
+        // All radii are scaled by the same factor, the smallest one that makes every side fit.
+        assert!(border_radii("border-radius: 100px", 100, 50) ==
+                radii([(25, 25), (25, 25), (25, 25), (25, 25)]));
+        assert!(border_radii("border-radius: 150px 50px / 20px", 100, 100) ==
+                radii([(75, 10), (25, 10), (75, 10), (25, 10)]));
+        assert!(border_radii("border-top-left-radius: 100%; border-top-right-radius: 100%",
+                             100,
+                             40) ==
+                radii([(50, 20), (50, 20), (0, 0), (0, 0)]));
+    }
+}
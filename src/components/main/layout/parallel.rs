@@ -7,13 +7,14 @@
 //! This code is highly unsafe. Keep this file small and easy to audit.
 
 use css::matching::{ApplicableDeclarations, CannotShare, MatchMethods, StyleWasShared};
+use css::matching::{insert_ancestors_into_bloom_filter};
 use layout::construct::FlowConstructor;
 use layout::context::LayoutContext;
 use layout::extra::LayoutAuxMethods;
 use layout::flow::{Flow, MutableFlowUtils, PreorderFlowTraversal, PostorderFlowTraversal};
 use layout::flow;
-use layout::layout_task::{AssignHeightsAndStoreOverflowTraversal, AssignWidthsTraversal};
-use layout::layout_task::{BubbleWidthsTraversal};
+use layout::traversal::{AssignHeightsAndStoreOverflowTraversal, AssignWidthsTraversal};
+use layout::traversal::{BubbleWidthsTraversal};
 use layout::util::{LayoutDataAccess, OpaqueNodeMethods};
 use layout::wrapper::{layout_node_to_unsafe_layout_node, LayoutNode, PostorderNodeMutTraversal};
 use layout::wrapper::{ThreadSafeLayoutNode, UnsafeLayoutNode};
@@ -25,7 +26,8 @@ use servo_util::workqueue::{WorkQueue, WorkUnit, WorkerProxy};
 use std::cast;
 use std::ptr;
 use std::sync::atomics::{AtomicInt, Relaxed, SeqCst};
-use style::{Stylist, TNode};
+use style::{Stylist, TNode, insert_element_into_bloom_filter};
+use style::{remove_element_from_bloom_filter};
 
 #[allow(dead_code)]
 fn static_assertion(node: UnsafeLayoutNode) {
@@ -256,34 +258,61 @@ fn recalc_style_for_node(unsafe_layout_node: UnsafeLayoutNode,
             node.parent_node()
         };
 
-        // First, check to see whether we can share a style with someone.
-        let style_sharing_candidate_cache = layout_context.style_sharing_candidate_cache();
-        let sharing_result = node.share_style_if_possible(style_sharing_candidate_cache,
-                                                          parent_opt.clone());
-
-        // Otherwise, match and cascade selectors.
-        match sharing_result {
-            CannotShare(mut shareable) => {
-                let mut applicable_declarations = ApplicableDeclarations::new();
+        // Get this thread's Bloom filter of ancestors. If it was left behind by our parent during
+        // this reflow, it's already correct; otherwise, rebuild it from scratch.
+        let bloom = layout_context.style_bloom();
+        let parent_opaque_node = node.parent_node().map(|parent| {
+            OpaqueNodeMethods::from_layout_node(&parent)
+        });
+        if bloom.generation != layout_context.generation || bloom.node != parent_opaque_node {
+            bloom.filter.clear();
+            insert_ancestors_into_bloom_filter(&node, &mut *bloom.filter);
+            bloom.generation = layout_context.generation;
+        }
 
-                if node.is_element() {
-                    // Perform the CSS selector matching.
-                    let stylist: &Stylist = cast::transmute(layout_context.stylist);
-                    node.match_node(stylist, &mut applicable_declarations, &mut shareable);
-                }
+        if node.needs_restyle(&*layout_context) {
+            // First, check to see whether we can share a style with someone.
+            let style_sharing_candidate_cache = layout_context.style_sharing_candidate_cache();
+            let sharing_result = node.share_style_if_possible(style_sharing_candidate_cache,
+                                                              parent_opt.clone());
+
+            // Otherwise, match and cascade selectors.
+            match sharing_result {
+                CannotShare(mut shareable) => {
+                    let mut applicable_declarations = ApplicableDeclarations::new();
+
+                    if node.is_element() {
+                        // Perform the CSS selector matching.
+                        let stylist: &Stylist = cast::transmute(layout_context.stylist);
+                        node.match_node(stylist,
+                                        Some(&*bloom.filter),
+                                        &mut applicable_declarations,
+                                        &mut shareable);
+                    }
 
-                // Perform the CSS cascade.
-                node.cascade_node(parent_opt,
-                                  &applicable_declarations,
-                                  layout_context.applicable_declarations_cache());
+                    // Perform the CSS cascade.
+                    node.cascade_node(parent_opt,
+                                      &applicable_declarations,
+                                      layout_context.applicable_declarations_cache(),
+                                      &*layout_context);
 
-                // Add ourselves to the LRU cache.
-                if shareable {
-                    style_sharing_candidate_cache.insert_if_possible(&node);
+                    // Add ourselves to the LRU cache.
+                    if shareable {
+                        style_sharing_candidate_cache.insert_if_possible(&node);
+                    }
                 }
+                StyleWasShared(index) => style_sharing_candidate_cache.touch(index),
             }
-            StyleWasShared(index) => style_sharing_candidate_cache.touch(index),
+
+            node.set_children_need_restyle()
+        }
+
+        // Add ourselves to the Bloom filter, so that a child styled next on this thread can reuse
+        // it. `construct_flows` pops us off again.
+        if node.is_element() {
+            insert_element_into_bloom_filter(&node, &mut *bloom.filter);
         }
+        bloom.node = Some(opaque_node);
 
         // Prepare for flow construction by counting the node's children and storing that count.
         let mut child_count = 0;
@@ -340,6 +369,21 @@ fn construct_flows(mut unsafe_layout_node: UnsafeLayoutNode,
             flow_constructor.process(&ThreadSafeLayoutNode::new(&node));
         }
 
+        // If this thread's Bloom filter ends with this node, pop it off so that a sibling styled
+        // next can reuse the filter.
+        let opaque_node: OpaqueNode = OpaqueNodeMethods::from_layout_node(&node);
+        {
+            let bloom = layout_context.style_bloom();
+            if bloom.generation == layout_context.generation && bloom.node == Some(opaque_node) {
+                if node.is_element() {
+                    remove_element_from_bloom_filter(&node, &mut *bloom.filter);
+                }
+                bloom.node = node.parent_node().map(|parent| {
+                    OpaqueNodeMethods::from_layout_node(&parent)
+                });
+            }
+        }
+
         // Reset the count of children for the next traversal.
         //
         // FIXME(pcwalton): Use children().len() when the implementation of that is efficient.
@@ -358,7 +402,6 @@ fn construct_flows(mut unsafe_layout_node: UnsafeLayoutNode,
         }
 
         // If this is the reflow root, we're done.
-        let opaque_node: OpaqueNode = OpaqueNodeMethods::from_layout_node(&node);
         if layout_context.reflow_root == opaque_node {
             break
         }
@@ -46,6 +46,10 @@ impl Flow for TableColGroupFlow {
         TableColGroupFlowClass
     }
 
+    fn as_immutable_table_colgroup<'a>(&'a self) -> &'a TableColGroupFlow {
+        self
+    }
+
     fn as_table_colgroup<'a>(&'a mut self) -> &'a mut TableColGroupFlow {
         self
     }
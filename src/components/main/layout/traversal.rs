@@ -0,0 +1,156 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The flow tree traversals that make up a reflow.
+
+use layout::context::LayoutContext;
+use layout::flow::{Flow, MutableFlowUtils, PreorderFlowTraversal, PostorderFlowTraversal};
+use layout::flow;
+use layout::incremental::RestyleDamage;
+
+#[cfg(debug)]
+use layout::flow::ImmutableFlowUtils;
+
+/// The damage computation traversal.
+#[deriving(Clone)]
+pub struct ComputeDamageTraversal;
+
+impl PostorderFlowTraversal for ComputeDamageTraversal {
+    #[inline]
+    fn process(&mut self, flow: &mut Flow) -> bool {
+        let mut damage = flow::base(flow).restyle_damage;
+        for child in flow::child_iter(flow) {
+            damage.union_in_place(flow::base(child).restyle_damage.propagate_up())
+        }
+        flow::mut_base(flow).restyle_damage = damage;
+        true
+    }
+}
+
+/// Propagates restyle damage up and down the tree as appropriate.
+///
+/// FIXME(pcwalton): Merge this with flow tree building and/or other traversals.
+pub struct PropagateDamageTraversal {
+    pub all_style_damage: bool,
+}
+
+impl PreorderFlowTraversal for PropagateDamageTraversal {
+    #[inline]
+    fn process(&mut self, flow: &mut Flow) -> bool {
+        if self.all_style_damage {
+            flow::mut_base(flow).restyle_damage.union_in_place(RestyleDamage::all())
+        }
+        debug!("restyle damage = {:?}", flow::base(flow).restyle_damage);
+
+        let prop = flow::base(flow).restyle_damage.propagate_down();
+        if prop.is_nonempty() {
+            for kid_ctx in flow::child_iter(flow) {
+                flow::mut_base(kid_ctx).restyle_damage.union_in_place(prop)
+            }
+        }
+        true
+    }
+}
+
+/// The flow tree verification traversal. This is only on in debug builds.
+#[cfg(debug)]
+pub struct FlowTreeVerificationTraversal;
+
+#[cfg(debug)]
+impl PreorderFlowTraversal for FlowTreeVerificationTraversal {
+    #[inline]
+    fn process(&mut self, flow: &mut Flow) -> bool {
+        let base = flow::base(flow);
+        if !base.flags.is_leaf() && !base.flags.is_nonleaf() {
+            println("flow tree verification failed: flow wasn't a leaf or a nonleaf!");
+            flow.dump();
+            fail!("flow tree verification failed")
+        }
+        true
+    }
+}
+
+/// The bubble-widths traversal, the first part of layout computation. This computes preferred
+/// and intrinsic widths and bubbles them up the tree.
+pub struct BubbleWidthsTraversal<'a> {
+    pub layout_context: &'a mut LayoutContext,
+}
+
+impl<'a> PostorderFlowTraversal for BubbleWidthsTraversal<'a> {
+    #[inline]
+    fn process(&mut self, flow: &mut Flow) -> bool {
+        flow.bubble_widths(self.layout_context);
+        true
+    }
+
+    // FIXME: We can't prune until we start reusing flows
+    /*
+    #[inline]
+    fn should_prune(&mut self, flow: &mut Flow) -> bool {
+        flow::mut_base(flow).restyle_damage.lacks(BubbleWidths)
+    }
+    */
+}
+
+/// The assign-widths traversal. In Gecko this corresponds to `Reflow`.
+pub struct AssignWidthsTraversal<'a> {
+    pub layout_context: &'a mut LayoutContext,
+}
+
+impl<'a> PreorderFlowTraversal for AssignWidthsTraversal<'a> {
+    #[inline]
+    fn process(&mut self, flow: &mut Flow) -> bool {
+        flow.assign_widths(self.layout_context);
+        true
+    }
+}
+
+/// The assign-heights-and-store-overflow traversal, the last (and most expensive) part of layout
+/// computation. Determines the final heights for all layout objects, computes positions, and
+/// computes overflow regions. In Gecko this corresponds to `FinishAndStoreOverflow`.
+pub struct AssignHeightsAndStoreOverflowTraversal<'a> {
+    pub layout_context: &'a mut LayoutContext,
+}
+
+impl<'a> PostorderFlowTraversal for AssignHeightsAndStoreOverflowTraversal<'a> {
+    #[inline]
+    fn process(&mut self, flow: &mut Flow) -> bool {
+        flow.assign_height(self.layout_context);
+        // Skip store-overflow for absolutely positioned flows. That will be
+        // done in a separate traversal.
+        if !flow.is_store_overflow_delayed() {
+            flow.store_overflow(self.layout_context);
+        }
+        true
+    }
+
+    #[inline]
+    fn should_process(&mut self, flow: &mut Flow) -> bool {
+        !flow::base(flow).flags.impacted_by_floats()
+    }
+}
+
+/// The display list construction traversal.
+pub struct BuildDisplayListTraversal<'a> {
+    pub layout_context: &'a LayoutContext,
+}
+
+impl<'a> BuildDisplayListTraversal<'a> {
+    #[inline]
+    pub fn process(&mut self, flow: &mut Flow) {
+        flow.compute_absolute_position();
+
+        for kid in flow::mut_base(flow).child_iter() {
+            if !kid.is_absolutely_positioned() {
+                self.process(kid)
+            }
+        }
+
+        for absolute_descendant_link in flow::mut_base(flow).abs_descendants.iter() {
+            self.process(absolute_descendant_link.resolve().unwrap())
+        }
+
+        flow.build_display_list(self.layout_context)
+    }
+}
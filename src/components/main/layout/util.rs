@@ -27,9 +27,18 @@ pub struct PrivateLayoutData {
     /// The results of CSS styling for this node's `after` pseudo-element, if any.
     pub after_style: Option<Arc<ComputedValues>>,
 
+    /// The results of CSS styling for this node's `first-line` pseudo-element, if any.
+    pub first_line_style: Option<Arc<ComputedValues>>,
+
+    /// The results of CSS styling for this node's `first-letter` pseudo-element, if any.
+    pub first_letter_style: Option<Arc<ComputedValues>>,
+
     /// Description of how to account for recent style changes.
     pub restyle_damage: Option<int>,
 
+    /// Whether a restyle hint requires this node to be matched and cascaded in the next reflow.
+    pub needs_restyle: bool,
+
     /// The current results of flow construction for this node. This is either a flow or a
     /// `ConstructionItem`. See comments in `construct.rs` for more details.
     pub flow_construction_result: ConstructionResult,
@@ -48,7 +57,10 @@ impl PrivateLayoutData {
         PrivateLayoutData {
             before_style: None,
             after_style: None,
+            first_line_style: None,
+            first_letter_style: None,
             restyle_damage: None,
+            needs_restyle: false,
             flow_construction_result: NoConstructionResult,
             before_flow_construction_result: NoConstructionResult,
             after_flow_construction_result: NoConstructionResult,
@@ -38,9 +38,14 @@ use script::dom::bindings::codegen::InheritTypes::{HTMLImageElementDerived, Text
 use script::dom::bindings::js::JS;
 use script::dom::element::{Element, HTMLAreaElementTypeId, HTMLAnchorElementTypeId};
 use script::dom::element::{HTMLLinkElementTypeId, LayoutElementHelpers, RawLayoutElementHelpers};
+use script::dom::element::{HTMLButtonElementTypeId, HTMLFieldSetElementTypeId};
+use script::dom::element::{HTMLInputElementTypeId, HTMLOptGroupElementTypeId};
+use script::dom::element::{HTMLOptionElementTypeId, HTMLSelectElementTypeId};
+use script::dom::element::{HTMLTextAreaElementTypeId};
 use script::dom::htmliframeelement::HTMLIFrameElement;
 use script::dom::htmlimageelement::{HTMLImageElement, LayoutHTMLImageElementHelpers};
 use script::dom::node::{DocumentNodeTypeId, ElementNodeTypeId, Node, NodeTypeId};
+use script::dom::node::{TextNodeTypeId};
 use script::dom::node::{LayoutNodeHelpers, RawLayoutNodeHelpers};
 use script::dom::text::Text;
 use servo_msg::constellation_msg::{PipelineId, SubpageId};
@@ -50,9 +55,10 @@ use std::cast;
 use std::cell::{Ref, RefMut};
 use std::kinds::marker::ContravariantLifetime;
 use style::{PropertyDeclarationBlock, TElement, TNode, AttrSelector, SpecificNamespace};
-use style::{AnyNamespace};
+use style::{AnyNamespace, ComputedValues};
 use style::computed_values::{content, display};
 use layout::util::LayoutDataWrapper;
+use sync::Arc;
 use url::Url;
 
 /// Allows some convenience methods on generic layout nodes.
@@ -279,6 +285,24 @@ impl<'ln> TNode<LayoutElement<'ln>> for LayoutNode<'ln> {
             AnyNamespace => false,
         }
     }
+
+    fn is_empty(&self) -> bool {
+        for kid in self.children() {
+            match kid.type_id() {
+                Some(ElementNodeTypeId(..)) => return false,
+                Some(TextNodeTypeId) => {
+                    unsafe {
+                        let text: JS<Text> = kid.get_jsmanaged().transmute_copy();
+                        if !(*text.unsafe_get()).characterdata.data.is_empty() {
+                            return false
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        true
+    }
 }
 
 pub struct LayoutNodeChildrenIterator<'a> {
@@ -347,6 +371,25 @@ impl<'le> LayoutElement<'le> {
     pub fn style_attribute(&self) -> &'le Option<PropertyDeclarationBlock> {
         &self.element.style_attribute
     }
+
+    /// Returns true if this element can be disabled, per the `:enabled` and `:disabled`
+    /// pseudo-classes.
+    ///
+    /// FIXME: This is HTML only, and does not account for disabled `<fieldset>` ancestors.
+    fn is_form_control(&self) -> bool {
+        match self.element.node.type_id {
+            // http://www.whatwg.org/specs/web-apps/current-work/multipage/selectors.html#
+            // selector-enabled
+            ElementNodeTypeId(HTMLButtonElementTypeId) |
+            ElementNodeTypeId(HTMLInputElementTypeId) |
+            ElementNodeTypeId(HTMLSelectElementTypeId) |
+            ElementNodeTypeId(HTMLTextAreaElementTypeId) |
+            ElementNodeTypeId(HTMLOptGroupElementTypeId) |
+            ElementNodeTypeId(HTMLOptionElementTypeId) |
+            ElementNodeTypeId(HTMLFieldSetElementTypeId) => true,
+            _ => false,
+        }
+    }
 }
 
 impl<'le> TElement for LayoutElement<'le> {
@@ -384,16 +427,54 @@ impl<'le> TElement for LayoutElement<'le> {
             self.element.node.get_hover_state_for_layout()
         }
     }
+
+    fn get_focus_state(&self) -> bool {
+        unsafe {
+            self.element.node.get_focus_state_for_layout()
+        }
+    }
+
+    fn get_active_state(&self) -> bool {
+        unsafe {
+            self.element.node.get_active_state_for_layout()
+        }
+    }
+
+    fn get_checked_state(&self) -> bool {
+        unsafe {
+            self.element.node.get_checked_state_for_layout()
+        }
+    }
+
+    fn get_disabled_state(&self) -> bool {
+        self.is_form_control() &&
+            unsafe { self.element.get_attr_val_for_layout(&namespace::Null, "disabled").is_some() }
+    }
+
+    fn get_enabled_state(&self) -> bool {
+        self.is_form_control() && !self.get_disabled_state()
+    }
+
+    fn get_target_state(&self) -> bool {
+        unsafe {
+            self.element.node.get_target_state_for_layout()
+        }
+    }
 }
 
+/// Returns the text of the strings in the given `content`. Counters are resolved later, by
+/// `generated_content::resolve_generated_content()`.
 fn get_content(content_list: &content::T) -> ~str {
     match *content_list {
         content::Content(ref value) => {
-            let iter = &mut value.clone().move_iter().peekable();
-            match iter.next() {
-                Some(content::StringContent(content)) => content,
-                _ => "".to_owned(),
+            let mut text = StrBuf::new();
+            for item in value.iter() {
+                match *item {
+                    content::StringContent(ref string) => text.push_str(string.as_slice()),
+                    content::CounterContent(..) | content::CountersContent(..) => {}
+                }
             }
+            text.into_owned()
         }
         _ => "".to_owned(),
     }
@@ -580,6 +661,20 @@ impl<'ln> ThreadSafeLayoutNode<'ln> {
         layout_data_wrapper_ref.data.after_style.is_some()
     }
 
+    /// Returns the style of this node's `::first-line` pseudo-element, if any.
+    pub fn first_line_style(&self) -> Option<Arc<ComputedValues>> {
+        let layout_data_wrapper = self.borrow_layout_data();
+        let layout_data_wrapper_ref = layout_data_wrapper.get_ref();
+        layout_data_wrapper_ref.data.first_line_style.clone()
+    }
+
+    /// Returns the style of this node's `::first-letter` pseudo-element, if any.
+    pub fn first_letter_style(&self) -> Option<Arc<ComputedValues>> {
+        let layout_data_wrapper = self.borrow_layout_data();
+        let layout_data_wrapper_ref = layout_data_wrapper.get_ref();
+        layout_data_wrapper_ref.data.first_letter_style.clone()
+    }
+
     /// Borrows the layout data immutably. Fails on a conflicting borrow.
     #[inline(always)]
     pub fn borrow_layout_data<'a>(&'a self) -> Ref<'a,Option<LayoutDataWrapper>> {
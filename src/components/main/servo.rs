@@ -56,6 +56,13 @@ use constellation::Constellation;
 #[cfg(not(test))]
 use servo_msg::constellation_msg::{ConstellationChan, InitLoadUrlMsg};
 
+#[cfg(not(test))]
+use gfx::display_list::IndexedDisplayList;
+#[cfg(not(test))]
+use gfx::display_list_serialization;
+#[cfg(not(test))]
+use gfx::render_task;
+
 #[cfg(not(test))]
 use servo_net::image_cache_task::{ImageCacheTask, SyncImageCacheTask};
 #[cfg(not(test))]
@@ -74,6 +81,8 @@ use std::os;
 #[cfg(not(test), target_os="android")]
 use std::str;
 #[cfg(not(test))]
+use std::io::File;
+#[cfg(not(test))]
 use std::task::TaskOpts;
 #[cfg(not(test))]
 use url::Url;
@@ -94,15 +103,22 @@ pub mod constellation;
 pub mod pipeline;
 
 pub mod layout {
+    pub mod animation;
     pub mod block;
     pub mod construct;
     pub mod context;
+    pub mod flex;
     pub mod floats;
     pub mod flow;
+    pub mod flow_dump;
     pub mod flow_list;
+    pub mod flow_ref;
     pub mod fragment;
+    pub mod gradient;
+    pub mod generated_content;
     pub mod layout_task;
     pub mod inline;
+    pub mod list_item;
     pub mod model;
     pub mod parallel;
     pub mod table_wrapper;
@@ -113,6 +129,7 @@ pub mod layout {
     pub mod table_row;
     pub mod table_cell;
     pub mod text;
+    pub mod traversal;
     pub mod util;
     pub mod incremental;
     pub mod wrapper;
@@ -163,6 +180,11 @@ pub extern "C" fn android_start(argc: int, argv: **u8) -> int {
 
 #[cfg(not(test))]
 pub fn run(opts: opts::Opts) {
+    match opts.replay_display_list {
+        Some(ref path) => return replay_display_list(&opts, *path),
+        None => {}
+    }
+
     let mut pool_config = green::PoolConfig::new();
     pool_config.event_loop_factory = rustuv::event_loop;
     let mut pool = green::SchedPool::new(pool_config);
@@ -221,3 +243,38 @@ pub fn run(opts: opts::Opts) {
 
     pool.shutdown();
 }
+
+/// Renders the layers written with `--dump-display-list` to the output file as a PNG, without
+/// starting script, layout or the compositor.
+#[cfg(not(test))]
+fn replay_display_list(opts: &opts::Opts, path: &str) {
+    let layers = File::open(&Path::new(path)).and_then(|mut file| {
+        display_list_serialization::read_layers(&mut file)
+    });
+    let display_list = match layers {
+        Ok(layers) => {
+            IndexedDisplayList::new(display_list_serialization::composite_layers(layers))
+        }
+        Err(error) => fail!("failed to read the display list in {}: {}", path, error),
+    };
+
+    let profiler_chan = Profiler::create(opts.profiler_period);
+    let start_time = time::precise_time_ns();
+    let (size, mut pixels) = render_task::render_display_list(opts, profiler_chan, &display_list);
+    info!("rendered the display list in {:.3f} ms",
+          (time::precise_time_ns() - start_time) as f64 / 1000000.0);
+
+    render_task::premultiplied_bgra_to_rgba(pixels.as_mut_slice());
+
+    let image = png::Image {
+        width: size.width as u32,
+        height: size.height as u32,
+        color_type: png::RGBA8,
+        pixels: pixels,
+    };
+    let output_file = opts.output_file.get_ref();
+    match png::store_png(&image, &Path::new(output_file.as_slice())) {
+        Ok(()) => {}
+        Err(error) => fail!("failed to write {}: {}", *output_file, error),
+    }
+}
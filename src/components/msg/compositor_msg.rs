@@ -47,6 +47,24 @@ impl LayerBufferSet {
     }
 }
 
+/// A tile rendered on the CPU, for compositors that cannot share native surfaces with the
+/// renderer. The headless compositor, which has no graphics context, receives these instead of
+/// `LayerBuffer`s.
+pub struct CpuLayerBuffer {
+    /// The rect in the containing RenderLayer that this represents.
+    pub rect: Rect<f32>,
+
+    /// The rect in pixels that will be drawn to the screen.
+    pub screen_pos: Rect<uint>,
+
+    /// The scale at which this tile is rendered.
+    pub resolution: f32,
+
+    /// The pixels of the tile, row by row, in BGRA order with premultiplied alpha. Rows are
+    /// `screen_pos.size.width` pixels long.
+    pub pixels: Vec<u8>,
+}
+
 /// The status of the renderer.
 #[deriving(Eq, Clone)]
 pub enum RenderState {
@@ -77,7 +95,7 @@ impl Epoch {
     }
 }
 
-#[deriving(Clone, Eq)]
+#[deriving(Clone, Eq, TotalEq, Hash)]
 pub struct LayerId(pub uint, pub uint);
 
 impl Show for LayerId {
@@ -93,6 +111,19 @@ impl LayerId {
     pub fn null() -> LayerId {
         LayerId(0, 0)
     }
+
+    /// Returns the ID of the layer holding the contents of the scrollable box generated by the
+    /// node with the given address. Unlike the IDs of other layers, this one stays the same
+    /// across reflows, so script can use it to scroll the box.
+    pub fn for_scrollable_box(node_address: uint) -> LayerId {
+        LayerId(node_address, 1)
+    }
+
+    /// Returns the ID of the layer holding the `position: fixed` box generated by the node with
+    /// the given address. Like the IDs of scrollable boxes, this one is stable across reflows.
+    pub fn for_fixed_box(node_address: uint) -> LayerId {
+        LayerId(node_address, 2)
+    }
 }
 
 /// The scrolling policy of a layer.
@@ -102,6 +133,10 @@ pub enum ScrollPolicy {
     Scrollable,
     /// These layers do not scroll when the parent receives a scrolling message.
     FixedPosition,
+    /// These layers hold the contents of boxes with `overflow: scroll` or `overflow: auto`. They
+    /// move with the parent, but scroll their own contents when they receive a scrolling
+    /// message.
+    OverflowScroll,
 }
 
 /// All layer-specific information that the painting task sends to the compositor other than the
@@ -111,10 +146,16 @@ pub struct LayerMetadata {
     pub id: LayerId,
     /// The position and size of the layer in pixels.
     pub position: Rect<uint>,
+    /// The visible part of the layer in pixels. This is smaller than `position` for layers that
+    /// scroll their contents.
+    pub clip_rect: Rect<uint>,
     /// The background color of the layer.
     pub background_color: Color,
     /// The scrolling policy of this layer.
     pub scroll_policy: ScrollPolicy,
+    /// The parts of the layer that changed since the last epoch, in pixels relative to the
+    /// origin of the layer. `None` if the whole layer changed.
+    pub dirty_rects: Option<Vec<Rect<uint>>>,
 }
 
 /// The interface used by the renderer to acquire draw targets for each render frame and
@@ -143,6 +184,14 @@ pub trait RenderListener {
              layer_buffer_set: Box<LayerBufferSet>,
              epoch: Epoch);
 
+    /// Sends new tiles rendered on the CPU for the given layer to the compositor. Renderers use
+    /// this instead of `paint` when the compositor gave them no graphics metadata.
+    fn paint_pixels(&self,
+                    pipeline_id: PipelineId,
+                    layer_id: LayerId,
+                    buffers: Vec<CpuLayerBuffer>,
+                    epoch: Epoch);
+
     fn set_render_state(&self, render_state: RenderState);
 }
 
@@ -0,0 +1,90 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::BindingDeclarations::AnimationEventBinding;
+use dom::bindings::codegen::InheritTypes::{AnimationEventDerived, EventCast};
+use dom::bindings::error::Fallible;
+use dom::bindings::js::{JSRef, Temporary};
+use dom::bindings::utils::{Reflectable, Reflector, reflect_dom_object};
+use dom::event::{AnimationEventTypeId, Event, EventMethods};
+use dom::window::Window;
+use servo_util::str::DOMString;
+
+#[deriving(Encodable)]
+pub struct AnimationEvent {
+    event: Event,
+    animation_name: DOMString,
+    elapsed_time: f32,
+    pseudo_element: DOMString,
+}
+
+impl AnimationEventDerived for Event {
+    fn is_animationevent(&self) -> bool {
+        self.type_id == AnimationEventTypeId
+    }
+}
+
+impl AnimationEvent {
+    pub fn new_inherited(animation_name: DOMString, elapsed_time: f32, pseudo_element: DOMString)
+                         -> AnimationEvent {
+        AnimationEvent {
+            event: Event::new_inherited(AnimationEventTypeId),
+            animation_name: animation_name,
+            elapsed_time: elapsed_time,
+            pseudo_element: pseudo_element,
+        }
+    }
+    pub fn new(window: &JSRef<Window>, type_: DOMString,
+               can_bubble: bool, cancelable: bool,
+               animation_name: DOMString, elapsed_time: f32, pseudo_element: DOMString)
+               -> Temporary<AnimationEvent> {
+        let mut ev = reflect_dom_object(box AnimationEvent::new_inherited(animation_name,
+                                                                          elapsed_time,
+                                                                          pseudo_element),
+                                        window,
+                                        AnimationEventBinding::Wrap).root();
+        {
+            let event: &mut JSRef<Event> = EventCast::from_mut_ref(&mut *ev);
+            event.InitEvent(type_, can_bubble, cancelable);
+        }
+        Temporary::from_rooted(&*ev)
+    }
+    pub fn Constructor(owner: &JSRef<Window>,
+                       type_: DOMString,
+                       init: &AnimationEventBinding::AnimationEventInit)
+                       -> Fallible<Temporary<AnimationEvent>> {
+        let ev = AnimationEvent::new(owner, type_, init.parent.bubbles, init.parent.cancelable,
+                                     init.animationName.clone(), init.elapsedTime,
+                                     init.pseudoElement.clone());
+        Ok(ev)
+    }
+}
+
+pub trait AnimationEventMethods {
+    fn AnimationName(&self) -> DOMString;
+    fn ElapsedTime(&self) -> f32;
+    fn PseudoElement(&self) -> DOMString;
+}
+
+impl<'a> AnimationEventMethods for JSRef<'a, AnimationEvent> {
+    fn AnimationName(&self) -> DOMString {
+        self.animation_name.clone()
+    }
+    fn ElapsedTime(&self) -> f32 {
+        self.elapsed_time
+    }
+    fn PseudoElement(&self) -> DOMString {
+        self.pseudo_element.clone()
+    }
+}
+
+impl Reflectable for AnimationEvent {
+    fn reflector<'a>(&'a self) -> &'a Reflector {
+        self.event.reflector()
+    }
+
+    fn mut_reflector<'a>(&'a mut self) -> &'a mut Reflector {
+        self.event.mut_reflector()
+    }
+}
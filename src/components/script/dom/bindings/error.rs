@@ -17,6 +17,7 @@ pub enum Error {
     NotFound,
     HierarchyRequest,
     InvalidCharacter,
+    NoModificationAllowed,
     NotSupported,
     InvalidState,
     Syntax,
@@ -0,0 +1,314 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::BindingDeclarations::CSSStyleDeclarationBinding;
+use dom::bindings::codegen::InheritTypes::NodeCast;
+use dom::bindings::error::{ErrorResult, Fallible, NoModificationAllowed};
+use dom::bindings::js::{JS, JSRef, Temporary, OptionalRootedRootable, OptionalUnrootable};
+use dom::bindings::trace::Untraceable;
+use dom::bindings::utils::{Reflectable, Reflector, reflect_dom_object};
+pub use dom::cssstyledeclarationmethods::CSSStyleDeclarationMethods;
+use dom::element::{AttributeHandlers, Element};
+use dom::node::{Node, NodeHelpers, document_from_node, window_from_node};
+use dom::window::{Window, WindowHelpers};
+use layout_interface::{ComputedStyleQuery, ComputedStyleResponse};
+use servo_util::str::DOMString;
+
+use std::ascii::StrAsciiExt;
+use std::comm::channel;
+use style::{COMPUTED_PROPERTY_NAMES, ComputedValues, PseudoElement, SourceDeclaration};
+use style::{ValidOrIgnoredDeclaration, computed_value_to_css, longhands_for_property};
+use style::{parse_source_declarations, parse_style_attribute, serialize_source_declarations};
+use sync::Arc;
+
+/// A CSS declaration block exposed to script. This is either the live inline style of an
+/// element, as returned by `HTMLElement.style`, or a read-only view of the computed style of an
+/// element or one of its pseudo-elements, as returned by `getComputedStyle()`.
+///
+/// Neither kind stores any declarations itself: inline style is read from the style attribute
+/// and computed style is fetched from layout each time it is read, so both always reflect the
+/// current state of the element.
+#[deriving(Encodable)]
+pub struct CSSStyleDeclaration {
+    pub reflector_: Reflector,
+    /// The element whose style this is, or `None` if `getComputedStyle()` was asked for a
+    /// pseudo-element we do not know, in which case the declaration is empty.
+    pub owner: Option<JS<Element>>,
+    pub pseudo_element: Untraceable<Option<PseudoElement>>,
+    /// True for computed style, which cannot be modified.
+    pub readonly: bool,
+}
+
+impl CSSStyleDeclaration {
+    pub fn new_inherited(owner: Option<JSRef<Element>>,
+                         pseudo_element: Option<PseudoElement>,
+                         readonly: bool) -> CSSStyleDeclaration {
+        CSSStyleDeclaration {
+            reflector_: Reflector::new(),
+            owner: owner.unrooted(),
+            pseudo_element: Untraceable::new(pseudo_element),
+            readonly: readonly,
+        }
+    }
+
+    pub fn new(window: &JSRef<Window>,
+               owner: Option<JSRef<Element>>,
+               pseudo_element: Option<PseudoElement>,
+               readonly: bool) -> Temporary<CSSStyleDeclaration> {
+        reflect_dom_object(box CSSStyleDeclaration::new_inherited(owner, pseudo_element, readonly),
+                           window, CSSStyleDeclarationBinding::Wrap)
+    }
+}
+
+/// The declaration of `property` among `declarations` that takes effect, if any.
+///
+/// TODO: Serialize longhands set by a shorthand declaration, and shorthands whose longhands
+/// are all declared.
+fn find_source_declaration<'a>(declarations: &'a [SourceDeclaration], property: &str)
+                               -> Option<&'a SourceDeclaration> {
+    let property = property.to_ascii_lower();
+    // An important declaration wins over any normal one; otherwise the last one does.
+    let mut result: Option<&'a SourceDeclaration> = None;
+    for declaration in declarations.iter() {
+        if declaration.name != property {
+            continue
+        }
+        match result {
+            Some(&SourceDeclaration { important: true, .. }) if !declaration.important => (),
+            _ => result = Some(declaration),
+        }
+    }
+    result
+}
+
+/// The names of the longhands that `declarations` set, in the order they are first set.
+/// Shorthand declarations contribute each of their longhands.
+fn longhand_names(declarations: &[SourceDeclaration]) -> Vec<&'static str> {
+    let mut names = vec!();
+    for declaration in declarations.iter() {
+        for longhand in longhands_for_property(declaration.name.as_slice()).iter() {
+            if !names.contains(longhand) {
+                names.push(*longhand)
+            }
+        }
+    }
+    names
+}
+
+/// Removes the declarations of `property` and of the longhands it sets. Returns true if any
+/// were removed.
+fn remove_source_declarations(declarations: &mut Vec<SourceDeclaration>, property: &str)
+                              -> bool {
+    let longhands = longhands_for_property(property);
+    let length = declarations.len();
+    declarations.retain(|declaration| {
+        declaration.name.as_slice() != property &&
+            !longhands.iter().any(|longhand| declaration.name.as_slice() == *longhand)
+    });
+    declarations.len() != length
+}
+
+trait PrivateCSSStyleDeclarationHelpers {
+    fn computed_style(&self) -> Option<Arc<ComputedValues>>;
+    fn source_declarations(&self) -> Vec<SourceDeclaration>;
+}
+
+impl<'a> PrivateCSSStyleDeclarationHelpers for JSRef<'a, CSSStyleDeclaration> {
+    /// Asks layout for the current computed style of the owner.
+    fn computed_style(&self) -> Option<Arc<ComputedValues>> {
+        let owner = match self.owner.root() {
+            None => return None,
+            Some(owner) => owner,
+        };
+        let node: &JSRef<Node> = NodeCast::from_ref(&*owner);
+        let window = window_from_node(node).root();
+        window.deref().flush_layout();
+        let page = window.deref().page();
+        let (chan, port) = channel();
+        let addr = node.to_trusted_node_address();
+        let query = ComputedStyleQuery(addr, (*self.pseudo_element).clone(), chan);
+        let ComputedStyleResponse(style) = page.query_layout(query, port);
+        style
+    }
+
+    /// The declarations of the owner's style attribute, in source order. This parses the
+    /// attribute, so each method calls it at most once.
+    fn source_declarations(&self) -> Vec<SourceDeclaration> {
+        match self.owner.root() {
+            None => vec!(),
+            Some(owner) => {
+                parse_source_declarations(owner.get_string_attribute("style").as_slice())
+            }
+        }
+    }
+}
+
+impl<'a> CSSStyleDeclarationMethods for JSRef<'a, CSSStyleDeclaration> {
+    fn CssText(&self) -> DOMString {
+        if self.readonly {
+            // Computed style declarations serialize to the empty string.
+            return "".to_owned()
+        }
+        serialize_source_declarations(self.source_declarations().as_slice())
+    }
+
+    fn SetCssText(&mut self, value: DOMString) -> ErrorResult {
+        if self.readonly {
+            return Err(NoModificationAllowed)
+        }
+        let mut owner = self.owner.root().unwrap();
+        owner.set_string_attribute("style", value);
+        Ok(())
+    }
+
+    fn Length(&self) -> u32 {
+        if self.owner.is_none() {
+            return 0
+        }
+        if self.readonly {
+            COMPUTED_PROPERTY_NAMES.len() as u32
+        } else {
+            longhand_names(self.source_declarations().as_slice()).len() as u32
+        }
+    }
+
+    fn Item(&self, index: u32) -> DOMString {
+        let mut found = false;
+        self.IndexedGetter(index, &mut found)
+    }
+
+    fn IndexedGetter(&self, index: u32, found: &mut bool) -> DOMString {
+        let name = if self.owner.is_none() {
+            None
+        } else if self.readonly {
+            COMPUTED_PROPERTY_NAMES.get(index as uint).map(|name| *name)
+        } else {
+            let declarations = self.source_declarations();
+            longhand_names(declarations.as_slice()).as_slice().get(index as uint).map(|name| *name)
+        };
+        *found = name.is_some();
+        name.unwrap_or("").to_owned()
+    }
+
+    // http://dev.w3.org/csswg/cssom/#dom-cssstyledeclaration-getpropertyvalue
+    fn GetPropertyValue(&self, property: DOMString) -> DOMString {
+        if !self.readonly {
+            let declarations = self.source_declarations();
+            return match find_source_declaration(declarations.as_slice(), property.as_slice()) {
+                None => "".to_owned(),
+                Some(declaration) => declaration.value.clone(),
+            }
+        }
+
+        let style = match self.computed_style() {
+            None => return "".to_owned(),
+            Some(style) => style,
+        };
+        let property = property.to_ascii_lower();
+        computed_value_to_css(&*style, property.as_slice()).unwrap_or("".to_owned())
+    }
+
+    // http://dev.w3.org/csswg/cssom/#dom-cssstyledeclaration-getpropertypriority
+    fn GetPropertyPriority(&self, property: DOMString) -> DOMString {
+        if self.readonly {
+            return "".to_owned()
+        }
+        let declarations = self.source_declarations();
+        match find_source_declaration(declarations.as_slice(), property.as_slice()) {
+            Some(&SourceDeclaration { important: true, .. }) => "important".to_owned(),
+            _ => "".to_owned(),
+        }
+    }
+
+    // http://dev.w3.org/csswg/cssom/#dom-cssstyledeclaration-setproperty
+    fn SetProperty(&mut self, property: DOMString, value: DOMString, priority: DOMString)
+                   -> ErrorResult {
+        if self.readonly {
+            return Err(NoModificationAllowed)
+        }
+        let property = property.to_ascii_lower();
+        if value.is_empty() {
+            return self.RemoveProperty(property).map(|_| ())
+        }
+        let important = match priority.to_ascii_lower().as_slice() {
+            "" => false,
+            "important" => true,
+            _ => return Ok(()),
+        };
+
+        let mut owner = self.owner.root().unwrap();
+        {
+            let node: &JSRef<Node> = NodeCast::from_ref(&*owner);
+            node.wait_until_safe_to_modify_dom();
+        }
+        let doc = document_from_node(&*owner).root();
+        let base_url = doc.deref().url().clone();
+
+        // Update the declarations that layout uses in place, rather than parsing the whole
+        // attribute again.
+        let result = {
+            let element = owner.deref_mut().deref_mut();
+            if element.style_attribute.is_none() {
+                element.style_attribute = Some(parse_style_attribute("", &base_url))
+            }
+            element.style_attribute.get_mut_ref().set_property(property.as_slice(),
+                                                               value.as_slice(),
+                                                               important,
+                                                               &base_url)
+        };
+        match result {
+            ValidOrIgnoredDeclaration => (),
+            // Invalid values are ignored.
+            _ => return Ok(()),
+        }
+
+        let mut declarations = self.source_declarations();
+        remove_source_declarations(&mut declarations, property.as_slice());
+        declarations.push(SourceDeclaration {
+            name: property,
+            value: value,
+            important: important,
+        });
+        owner.set_style_attribute_text(serialize_source_declarations(declarations.as_slice()));
+        Ok(())
+    }
+
+    // http://dev.w3.org/csswg/cssom/#dom-cssstyledeclaration-removeproperty
+    fn RemoveProperty(&mut self, property: DOMString) -> Fallible<DOMString> {
+        if self.readonly {
+            return Err(NoModificationAllowed)
+        }
+        let property = property.to_ascii_lower();
+        let mut declarations = self.source_declarations();
+        let value = match find_source_declaration(declarations.as_slice(), property.as_slice()) {
+            None => "".to_owned(),
+            Some(declaration) => declaration.value.clone(),
+        };
+        if !remove_source_declarations(&mut declarations, property.as_slice()) {
+            return Ok(value)
+        }
+
+        let mut owner = self.owner.root().unwrap();
+        {
+            let node: &JSRef<Node> = NodeCast::from_ref(&*owner);
+            node.wait_until_safe_to_modify_dom();
+        }
+        match owner.deref_mut().deref_mut().style_attribute {
+            None => (),
+            Some(ref mut block) => block.remove_property(property.as_slice()),
+        }
+        owner.set_style_attribute_text(serialize_source_declarations(declarations.as_slice()));
+        Ok(value)
+    }
+}
+
+impl Reflectable for CSSStyleDeclaration {
+    fn reflector<'a>(&'a self) -> &'a Reflector {
+        &self.reflector_
+    }
+
+    fn mut_reflector<'a>(&'a mut self) -> &'a mut Reflector {
+        &mut self.reflector_
+    }
+}
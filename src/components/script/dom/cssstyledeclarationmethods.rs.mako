@@ -0,0 +1,43 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+## This file is a Mako template. The camel-cased accessors are generated from the properties in
+## style/properties.rs.mako, whose path is passed in as `properties_template`, like the attributes
+## of CSSStyleDeclaration.webidl.
+<%
+    from mako.template import Template
+    properties = Template(filename=properties_template)
+    properties.render()
+%>\
+//! The methods of `CSSStyleDeclaration`. The implementation in `cssstyledeclaration.rs` provides
+//! the methods of the interface itself; the camel-cased attributes of each property are provided
+//! here on top of them.
+
+use dom::bindings::error::{ErrorResult, Fallible};
+use servo_util::str::DOMString;
+
+pub trait CSSStyleDeclarationMethods {
+    fn CssText(&self) -> DOMString;
+    fn SetCssText(&mut self, value: DOMString) -> ErrorResult;
+    fn Length(&self) -> u32;
+    fn Item(&self, index: u32) -> DOMString;
+    fn IndexedGetter(&self, index: u32, found: &mut bool) -> DOMString;
+    fn GetPropertyValue(&self, property: DOMString) -> DOMString;
+    fn GetPropertyPriority(&self, property: DOMString) -> DOMString;
+    fn SetProperty(&mut self, property: DOMString, value: DOMString, priority: DOMString)
+                   -> ErrorResult;
+    fn RemoveProperty(&mut self, property: DOMString) -> Fallible<DOMString>;
+
+    // http://dev.w3.org/csswg/cssom/#dom-cssstyledeclaration-camel-cased-attribute
+% for (name, camel_case) in properties.module.cssom_properties():
+
+    fn ${camel_case}(&self) -> DOMString {
+        self.GetPropertyValue("${name}".to_owned())
+    }
+
+    fn Set${camel_case}(&mut self, value: DOMString) -> ErrorResult {
+        self.SetProperty("${name}".to_owned(), value, "".to_owned())
+    }
+% endfor
+}
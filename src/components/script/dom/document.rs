@@ -9,7 +9,7 @@ use dom::bindings::codegen::InheritTypes::{DocumentTypeCast, HTMLHtmlElementCast
 use dom::bindings::codegen::InheritTypes::EventTargetCast;
 use dom::bindings::codegen::BindingDeclarations::DocumentBinding;
 use dom::bindings::js::{JS, JSRef, Temporary, OptionalSettable, TemporaryPushable};
-use dom::bindings::js::OptionalRootable;
+use dom::bindings::js::{OptionalRootable, OptionalRootedRootable};
 use dom::bindings::trace::Untraceable;
 use dom::bindings::utils::{Reflectable, Reflector, reflect_dom_object};
 use dom::bindings::error::{ErrorResult, Fallible, NotSupported, InvalidCharacter, HierarchyRequest, NamespaceError};
@@ -40,7 +40,8 @@ use dom::window::{Window, WindowMethods, WindowHelpers};
 use dom::location::Location;
 use html::hubbub_html_parser::build_element_from_tag;
 use hubbub::hubbub::{QuirksMode, NoQuirks, LimitedQuirks, FullQuirks};
-use layout_interface::{DocumentDamageLevel, ContentChangedDocumentDamage};
+use layout_interface::{AttributeMutation, DocumentDamageLevel, ContentChangedDocumentDamage};
+use layout_interface::MatchSelectorsDocumentDamage;
 use servo_util::namespace;
 use servo_util::namespace::{Namespace, Null};
 use servo_util::str::{DOMString, null_str_as_empty_ref};
@@ -68,6 +69,10 @@ pub struct Document {
     pub is_html_document: bool,
     pub url: Untraceable<Url>,
     pub quirks_mode: Untraceable<QuirksMode>,
+    /// The element that currently has focus, if any.
+    pub focused: Option<JS<Element>>,
+    /// The element targeted by the fragment of the document's URL, if any.
+    pub target: Option<JS<Element>>,
 }
 
 impl DocumentDerived for EventTarget {
@@ -83,10 +88,13 @@ pub trait DocumentHelpers {
     fn set_encoding_name(&mut self, name: DOMString);
     fn content_changed(&self);
     fn damage_and_reflow(&self, damage: DocumentDamageLevel);
+    fn damage_attribute_and_reflow(&self, mutation: AttributeMutation);
     fn wait_until_safe_to_modify_dom(&self);
     fn unregister_named_element(&mut self, to_unregister: &JSRef<Element>, id: DOMString);
     fn register_named_element(&mut self, element: &JSRef<Element>, id: DOMString);
     fn load_anchor_href(&self, href: DOMString);
+    fn set_focused_element(&mut self, element: Option<&JSRef<Element>>);
+    fn set_target_element(&mut self, element: Option<&JSRef<Element>>);
 }
 
 impl<'a> DocumentHelpers for JSRef<'a, Document> {
@@ -114,6 +122,10 @@ impl<'a> DocumentHelpers for JSRef<'a, Document> {
         self.window.root().damage_and_reflow(damage);
     }
 
+    fn damage_attribute_and_reflow(&self, mutation: AttributeMutation) {
+        self.window.root().damage_attribute_and_reflow(mutation);
+    }
+
     fn wait_until_safe_to_modify_dom(&self) {
         self.window.root().wait_until_safe_to_modify_dom();
     }
@@ -185,6 +197,49 @@ impl<'a> DocumentHelpers for JSRef<'a, Document> {
         let mut window = self.window.root();
         window.load_url(href);
     }
+
+    /// Moves focus to the given element, or clears it, and restyles the document.
+    fn set_focused_element(&mut self, element: Option<&JSRef<Element>>) {
+        match self.focused.root() {
+            Some(mut old_element) => {
+                let node: &mut JSRef<Node> = NodeCast::from_mut_ref(&mut *old_element);
+                node.set_focus_state(false);
+            }
+            None => {}
+        }
+        match element {
+            Some(element) => {
+                let mut element = element.clone();
+                let node: &mut JSRef<Node> = NodeCast::from_mut_ref(&mut element);
+                node.set_focus_state(true);
+            }
+            None => {}
+        }
+        self.focused.assign(element.map(|element| element.clone()));
+        self.damage_and_reflow(MatchSelectorsDocumentDamage);
+    }
+
+    /// Makes the given element the target of the URL fragment, or clears it, and restyles the
+    /// document.
+    fn set_target_element(&mut self, element: Option<&JSRef<Element>>) {
+        match self.target.root() {
+            Some(mut old_element) => {
+                let node: &mut JSRef<Node> = NodeCast::from_mut_ref(&mut *old_element);
+                node.set_target_state(false);
+            }
+            None => {}
+        }
+        match element {
+            Some(element) => {
+                let mut element = element.clone();
+                let node: &mut JSRef<Node> = NodeCast::from_mut_ref(&mut element);
+                node.set_target_state(true);
+            }
+            None => {}
+        }
+        self.target.assign(element.map(|element| element.clone()));
+        self.damage_and_reflow(MatchSelectorsDocumentDamage);
+    }
 }
 
 impl Document {
@@ -229,6 +284,8 @@ impl Document {
             // http://dom.spec.whatwg.org/#concept-document-encoding
             encoding_name: "utf-8".to_owned(),
             is_html_document: is_html_document == HTMLDocument,
+            focused: None,
+            target: None,
         }
     }
 
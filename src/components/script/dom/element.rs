@@ -15,6 +15,7 @@ use dom::bindings::error::{ErrorResult, Fallible, NamespaceError, InvalidCharact
 use dom::bindings::utils::{QName, Name, InvalidXMLName, xml_name_type};
 use dom::clientrect::ClientRect;
 use dom::clientrectlist::ClientRectList;
+use dom::cssstyledeclaration::CSSStyleDeclaration;
 use dom::document::{Document, DocumentHelpers};
 use dom::eventtarget::{EventTarget, NodeTargetTypeId};
 use dom::htmlcollection::HTMLCollection;
@@ -22,15 +23,18 @@ use dom::htmlserializer::serialize;
 use dom::node::{ElementNodeTypeId, Node, NodeHelpers, NodeIterator, document_from_node};
 use dom::node::{window_from_node, LayoutNodeHelpers};
 use dom::virtualmethods::{VirtualMethods, vtable_for};
-use layout_interface::ContentChangedDocumentDamage;
-use layout_interface::MatchSelectorsDocumentDamage;
+use layout_interface::AttributeMutation;
 use style;
+use geom::point::Point2D;
+use servo_msg::compositor_msg::LayerId;
 use servo_util::namespace;
 use servo_util::namespace::{Namespace, Null};
 use servo_util::str::{DOMString, null_str_as_empty_ref, split_html_space_chars};
 
 use std::ascii::StrAsciiExt;
 use std::cast;
+use std::cmp;
+use std::mem;
 
 #[deriving(Encodable)]
 pub struct Element {
@@ -40,7 +44,9 @@ pub struct Element {
     pub prefix: Option<DOMString>,
     pub attrs: Vec<JS<Attr>>,
     pub style_attribute: Option<style::PropertyDeclarationBlock>,
-    pub attr_list: Option<JS<AttrList>>
+    pub attr_list: Option<JS<AttrList>>,
+    /// The live `CSSStyleDeclaration` for the style attribute, created on first access.
+    pub style_declaration: Option<JS<CSSStyleDeclaration>>,
 }
 
 impl ElementDerived for EventTarget {
@@ -150,6 +156,7 @@ impl Element {
             attrs: vec!(),
             attr_list: None,
             style_attribute: None,
+            style_declaration: None,
         }
     }
 
@@ -194,6 +201,11 @@ impl LayoutElementHelpers for JS<Element> {
 
 pub trait ElementHelpers {
     fn html_element_in_html_document(&self) -> bool;
+    fn is_focusable_area(&self) -> bool;
+    fn style_declaration(&mut self) -> Temporary<CSSStyleDeclaration>;
+    fn scroll_layer_id(&self) -> LayerId;
+    fn scroll_offset(&self) -> Point2D<f32>;
+    fn set_scroll_offset(&self, offset: Point2D<f32>);
 }
 
 impl<'a> ElementHelpers for JSRef<'a, Element> {
@@ -202,6 +214,64 @@ impl<'a> ElementHelpers for JSRef<'a, Element> {
         let node: &JSRef<Node> = NodeCast::from_ref(self);
         is_html && node.owner_doc().root().is_html_document
     }
+
+    // http://www.whatwg.org/html/#focusable-area
+    // FIXME: This does not account for disabled form controls or for elements that are not
+    // being rendered.
+    fn is_focusable_area(&self) -> bool {
+        if self.get_attribute(Null, "tabindex").is_some() {
+            return true
+        }
+        let node: &JSRef<Node> = NodeCast::from_ref(self);
+        match node.type_id() {
+            ElementNodeTypeId(HTMLAnchorElementTypeId) => {
+                self.get_attribute(Null, "href").is_some()
+            }
+            ElementNodeTypeId(HTMLButtonElementTypeId) |
+            ElementNodeTypeId(HTMLInputElementTypeId) |
+            ElementNodeTypeId(HTMLSelectElementTypeId) |
+            ElementNodeTypeId(HTMLTextAreaElementTypeId) => true,
+            _ => false,
+        }
+    }
+
+    // http://dev.w3.org/csswg/cssom/#dom-elementcssinlinestyle-style
+    fn style_declaration(&mut self) -> Temporary<CSSStyleDeclaration> {
+        match self.style_declaration {
+            None => (),
+            Some(ref declaration) => return Temporary::new(declaration.clone()),
+        }
+
+        let window = window_from_node(self).root();
+        let declaration = CSSStyleDeclaration::new(&*window, Some(self.clone()), None, false);
+        self.style_declaration.assign(Some(declaration));
+        Temporary::new(self.style_declaration.get_ref().clone())
+    }
+
+    /// Returns the ID of the layer that holds the contents of this element if its box is
+    /// scrollable. Layout derives it from the same address.
+    fn scroll_layer_id(&self) -> LayerId {
+        let node: &JSRef<Node> = NodeCast::from_ref(self);
+        LayerId::for_scrollable_box(node.reflector().get_jsobject() as uint)
+    }
+
+    /// Returns the last known scroll offset of the box of this element.
+    fn scroll_offset(&self) -> Point2D<f32> {
+        let window = window_from_node(self).root();
+        let page = window.deref().page();
+        let scroll_offsets = page.scroll_offsets.deref().borrow();
+        scroll_offsets.find(&self.scroll_layer_id()).map_or(Point2D(0f32, 0f32), |offset| *offset)
+    }
+
+    /// Asks the compositor to scroll the box of this element to the given offset. The compositor
+    /// clamps the offset to the scrollable area and reports the result back with a scroll event.
+    fn set_scroll_offset(&self, offset: Point2D<f32>) {
+        let window = window_from_node(self).root();
+        let page = window.deref().page();
+        let layer_id = self.scroll_layer_id();
+        page.scroll_offsets.deref().borrow_mut().insert(layer_id, offset);
+        window.deref().compositor.scroll_fragment_point(page.id, layer_id, offset);
+    }
 }
 
 pub trait AttributeHandlers {
@@ -216,7 +286,11 @@ pub trait AttributeHandlers {
                         prefix: Option<DOMString>, cb: |&JSRef<Attr>| -> bool);
 
     fn remove_attribute(&mut self, namespace: Namespace, name: DOMString) -> ErrorResult;
-    fn notify_attribute_changed(&self, local_name: DOMString);
+    fn notify_attribute_changed(&self,
+                                local_name: DOMString,
+                                old_value: Option<DOMString>,
+                                new_value: Option<DOMString>);
+    fn set_style_attribute_text(&mut self, value: DOMString);
     fn has_class(&self, name: &str) -> bool;
 
     // http://www.whatwg.org/html/#reflecting-content-attributes-in-idl-attributes
@@ -329,18 +403,43 @@ impl<'a> AttributeHandlers for JSRef<'a, Element> {
         Ok(())
     }
 
-    fn notify_attribute_changed(&self, local_name: DOMString) {
+    fn notify_attribute_changed(&self,
+                                local_name: DOMString,
+                                old_value: Option<DOMString>,
+                                new_value: Option<DOMString>) {
         let node: &JSRef<Node> = NodeCast::from_ref(self);
         if node.is_in_doc() {
-            let damage = match local_name.as_slice() {
-                "style" | "id" | "class" => MatchSelectorsDocumentDamage,
-                _ => ContentChangedDocumentDamage
-            };
+            // Layout restyles the element and the elements whose selectors depend on the
+            // attribute.
             let document = node.owner_doc().root();
-            document.deref().damage_and_reflow(damage);
+            document.deref().damage_attribute_and_reflow(AttributeMutation {
+                node: node.to_trusted_node_address(),
+                local_name: local_name,
+                old_value: old_value,
+                new_value: new_value,
+            })
         }
     }
 
+    /// Sets the text of the style attribute after `style_attribute` has already been updated to
+    /// match, so that the declarations are not parsed again.
+    fn set_style_attribute_text(&mut self, value: DOMString) {
+        let old_value = match self.get_attribute(Null, "style").root() {
+            Some(mut attr) => {
+                let attr: &mut JSRef<Attr> = &mut *attr;
+                Some(mem::replace(&mut attr.deref_mut().value, value.clone()))
+            }
+            None => {
+                let window = window_from_node(self).root();
+                let attr = Attr::new(&*window, "style".to_owned(), value.clone(),
+                                     "style".to_owned(), Null, None, self);
+                self.deref_mut().attrs.push_unrooted(&attr);
+                None
+            }
+        };
+        self.notify_attribute_changed("style".to_owned(), old_value, Some(value));
+    }
+
     fn has_class(&self, name: &str) -> bool {
         let class_names = self.get_string_attribute("class");
         let mut classes = split_html_space_chars(class_names);
@@ -414,6 +513,10 @@ pub trait ElementMethods {
     fn GetElementsByClassName(&self, classes: DOMString) -> Temporary<HTMLCollection>;
     fn GetClientRects(&self) -> Temporary<ClientRectList>;
     fn GetBoundingClientRect(&self) -> Temporary<ClientRect>;
+    fn ScrollTop(&self) -> i32;
+    fn SetScrollTop(&mut self, value: i32);
+    fn ScrollLeft(&self) -> i32;
+    fn SetScrollLeft(&mut self, value: i32);
     fn GetInnerHTML(&self) -> Fallible<DOMString>;
     fn GetOuterHTML(&self) -> Fallible<DOMString>;
     fn Children(&self) -> Temporary<HTMLCollection>;
@@ -677,6 +780,26 @@ impl<'a> ElementMethods for JSRef<'a, Element> {
             rect.origin.x + rect.size.width)
     }
 
+    // http://dev.w3.org/csswg/cssom-view/#dom-element-scrolltop
+    fn ScrollTop(&self) -> i32 {
+        self.scroll_offset().y as i32
+    }
+
+    fn SetScrollTop(&mut self, value: i32) {
+        let offset = self.scroll_offset();
+        self.set_scroll_offset(Point2D(offset.x, cmp::max(value, 0) as f32))
+    }
+
+    // http://dev.w3.org/csswg/cssom-view/#dom-element-scrollleft
+    fn ScrollLeft(&self) -> i32 {
+        self.scroll_offset().x as i32
+    }
+
+    fn SetScrollLeft(&mut self, value: i32) {
+        let offset = self.scroll_offset();
+        self.set_scroll_offset(Point2D(cmp::max(value, 0) as f32, offset.y))
+    }
+
     fn GetInnerHTML(&self) -> Fallible<DOMString> {
         //XXX TODO: XML case
         Ok(serialize(&mut NodeIterator::new(NodeCast::from_ref(self), false, false)))
@@ -727,7 +850,8 @@ impl<'a> VirtualMethods for JSRef<'a, Element> {
             "style" => {
                 let doc = document_from_node(self).root();
                 let base_url = doc.deref().url().clone();
-                self.deref_mut().style_attribute = Some(style::parse_style_attribute(value, &base_url))
+                self.deref_mut().style_attribute =
+                    Some(style::parse_style_attribute(value.clone(), &base_url))
             }
             "id" => {
                 let node: &JSRef<Node> = NodeCast::from_ref(self);
@@ -739,7 +863,7 @@ impl<'a> VirtualMethods for JSRef<'a, Element> {
             _ => ()
         }
 
-        self.notify_attribute_changed(name);
+        self.notify_attribute_changed(name, None, Some(value));
     }
 
     fn before_remove_attr(&mut self, name: DOMString, value: DOMString) {
@@ -756,13 +880,13 @@ impl<'a> VirtualMethods for JSRef<'a, Element> {
                 let node: &JSRef<Node> = NodeCast::from_ref(self);
                 if node.is_in_doc() {
                     let mut doc = document_from_node(self).root();
-                    doc.unregister_named_element(self, value);
+                    doc.unregister_named_element(self, value.clone());
                 }
             }
             _ => ()
         }
 
-        self.notify_attribute_changed(name);
+        self.notify_attribute_changed(name, Some(value), None);
     }
 
     fn bind_to_tree(&mut self) {
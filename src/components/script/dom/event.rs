@@ -9,6 +9,8 @@ use dom::bindings::utils::{Reflectable, Reflector, reflect_dom_object};
 use dom::bindings::error::Fallible;
 use dom::eventtarget::EventTarget;
 use dom::window::Window;
+use layout_interface::UntrustedNodeAddress;
+use servo_msg::compositor_msg::LayerId;
 use servo_util::str::DOMString;
 
 use geom::point::Point2D;
@@ -18,10 +20,26 @@ use time;
 pub enum Event_ {
     ResizeEvent(uint, uint),
     ReflowEvent,
+    /// A frame of the running transitions and animations is due. Layout restyles the nodes they
+    /// style.
+    AnimationFrameEvent,
     ClickEvent(uint, Point2D<f32>),
     MouseDownEvent(uint, Point2D<f32>),
     MouseUpEvent(uint, Point2D<f32>),
-    MouseMoveEvent(Point2D<f32>)
+    MouseMoveEvent(Point2D<f32>),
+    /// The page or the scrollable box whose contents are in the given layer was scrolled to the
+    /// given offset. The page itself has the null layer ID.
+    ScrollEvent(LayerId, Point2D<f32>),
+    /// A transition of the named property of the node ended after running for the given number
+    /// of seconds.
+    TransitionEndEvent(UntrustedNodeAddress, DOMString, f32),
+    /// The named animation of the node started. The number of seconds it had run is always 0.
+    AnimationStartEvent(UntrustedNodeAddress, DOMString, f32),
+    /// The named animation of the node started a new iteration after running for the given
+    /// number of seconds.
+    AnimationIterationEvent(UntrustedNodeAddress, DOMString, f32),
+    /// The named animation of the node ended after running for the given number of seconds.
+    AnimationEndEvent(UntrustedNodeAddress, DOMString, f32),
 }
 
 #[deriving(Encodable)]
@@ -34,11 +52,13 @@ pub enum EventPhase {
 
 #[deriving(Eq, Encodable)]
 pub enum EventTypeId {
+    AnimationEventTypeId,
     CustomEventTypeId,
     HTMLEventTypeId,
     KeyEventTypeId,
     MouseEventTypeId,
     ProgressEventTypeId,
+    TransitionEventTypeId,
     UIEventTypeId
 }
 
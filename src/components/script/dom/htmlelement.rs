@@ -6,13 +6,14 @@ use dom::bindings::codegen::BindingDeclarations::HTMLElementBinding;
 use dom::bindings::codegen::EventHandlerBinding::EventHandlerNonNull;
 use dom::bindings::codegen::InheritTypes::{ElementCast, HTMLFrameSetElementDerived};
 use dom::bindings::codegen::InheritTypes::{HTMLElementDerived, HTMLBodyElementDerived};
-use dom::bindings::codegen::InheritTypes::EventTargetCast;
+use dom::bindings::codegen::InheritTypes::{EventTargetCast, NodeCast};
 use dom::bindings::js::{JSRef, Temporary};
 use dom::bindings::error::{ErrorResult, Fallible};
-use dom::document::Document;
-use dom::element::{Element, ElementTypeId, HTMLElementTypeId};
+use dom::cssstyledeclaration::CSSStyleDeclaration;
+use dom::document::{Document, DocumentHelpers};
+use dom::element::{Element, ElementHelpers, ElementTypeId, HTMLElementTypeId};
 use dom::eventtarget::{EventTarget, NodeTargetTypeId};
-use dom::node::{Node, ElementNodeTypeId, window_from_node};
+use dom::node::{Node, NodeHelpers, ElementNodeTypeId, document_from_node, window_from_node};
 use dom::virtualmethods::VirtualMethods;
 use dom::window::WindowMethods;
 use js::jsapi::JSContext;
@@ -90,6 +91,7 @@ pub trait HTMLElementMethods {
     fn OffsetLeft(&self) -> i32;
     fn OffsetWidth(&self) -> i32;
     fn OffsetHeight(&self) -> i32;
+    fn Style(&mut self) -> Temporary<CSSStyleDeclaration>;
     fn GetOnload(&self) -> Option<EventHandlerNonNull>;
     fn SetOnload(&mut self, listener: Option<EventHandlerNonNull>);
 }
@@ -144,11 +146,22 @@ impl<'a> HTMLElementMethods for JSRef<'a, HTMLElement> {
         Ok(())
     }
 
+    // http://www.whatwg.org/specs/web-apps/current-work/multipage/editing.html#dom-focus
+    // FIXME: Only focusable areas should be able to receive focus.
     fn Focus(&self) -> ErrorResult {
+        let element: &JSRef<Element> = ElementCast::from_ref(self);
+        let mut doc = document_from_node(self).root();
+        doc.set_focused_element(Some(element));
         Ok(())
     }
 
+    // http://www.whatwg.org/specs/web-apps/current-work/multipage/editing.html#dom-blur
     fn Blur(&self) -> ErrorResult {
+        let node: &JSRef<Node> = NodeCast::from_ref(self);
+        if node.get_focus_state() {
+            let mut doc = document_from_node(self).root();
+            doc.set_focused_element(None);
+        }
         Ok(())
     }
 
@@ -212,6 +225,11 @@ impl<'a> HTMLElementMethods for JSRef<'a, HTMLElement> {
         0
     }
 
+    fn Style(&mut self) -> Temporary<CSSStyleDeclaration> {
+        let element: &mut JSRef<Element> = ElementCast::from_mut_ref(self);
+        element.style_declaration()
+    }
+
     fn GetOnload(&self) -> Option<EventHandlerNonNull> {
         if self.is_body_or_frameset() {
             let win = window_from_node(self).root();
@@ -4,18 +4,30 @@
 
 use dom::bindings::codegen::BindingDeclarations::HTMLInputElementBinding;
 use dom::bindings::codegen::InheritTypes::HTMLInputElementDerived;
-use dom::bindings::js::{JSRef, Temporary};
+use dom::bindings::codegen::InheritTypes::{ElementCast, HTMLElementCast, NodeCast};
+use dom::bindings::js::{JSRef, Temporary, OptionalRootable};
 use dom::bindings::error::{ErrorResult, Fallible};
-use dom::document::Document;
-use dom::element::HTMLInputElementTypeId;
+use dom::attr::AttrMethods;
+use dom::document::{Document, DocumentHelpers};
+use dom::element::{AttributeHandlers, Element, HTMLInputElementTypeId};
+use dom::event::{Event, EventMethods};
 use dom::eventtarget::{EventTarget, NodeTargetTypeId};
 use dom::htmlelement::HTMLElement;
-use dom::node::{Node, ElementNodeTypeId};
+use dom::node::{Node, NodeHelpers, ElementNodeTypeId, document_from_node};
+use dom::virtualmethods::VirtualMethods;
+use layout_interface::MatchSelectorsDocumentDamage;
+use servo_util::namespace::Null;
 use servo_util::str::DOMString;
 
+use std::ascii::StrAsciiExt;
+
 #[deriving(Encodable)]
 pub struct HTMLInputElement {
     pub htmlelement: HTMLElement,
+    /// Whether the checkedness has been changed by the user or by script, in which case the
+    /// `checked` content attribute no longer affects it.
+    /// http://www.whatwg.org/html/#concept-input-checked-dirty-flag
+    pub dirty_checkedness: bool,
 }
 
 impl HTMLInputElementDerived for EventTarget {
@@ -27,7 +39,8 @@ impl HTMLInputElementDerived for EventTarget {
 impl HTMLInputElement {
     pub fn new_inherited(localName: DOMString, document: &JSRef<Document>) -> HTMLInputElement {
         HTMLInputElement {
-            htmlelement: HTMLElement::new_inherited(HTMLInputElementTypeId, localName, document)
+            htmlelement: HTMLElement::new_inherited(HTMLInputElementTypeId, localName, document),
+            dirty_checkedness: false,
         }
     }
 
@@ -152,26 +165,41 @@ impl<'a> HTMLInputElementMethods for JSRef<'a, HTMLInputElement> {
     }
 
     fn DefaultChecked(&self) -> bool {
-        false
+        let element: &JSRef<Element> = ElementCast::from_ref(self);
+        element.get_attribute(Null, "checked").is_some()
     }
 
-    fn SetDefaultChecked(&mut self, _default_checked: bool) -> ErrorResult {
-        Ok(())
+    fn SetDefaultChecked(&mut self, default_checked: bool) -> ErrorResult {
+        let element: &mut JSRef<Element> = ElementCast::from_mut_ref(self);
+        if default_checked {
+            element.set_attribute(Null, "checked".to_owned(), "".to_owned())
+        } else {
+            element.remove_attribute(Null, "checked".to_owned())
+        }
     }
 
     fn Checked(&self) -> bool {
-        false
+        let node: &JSRef<Node> = NodeCast::from_ref(self);
+        node.get_checked_state()
     }
 
-    fn SetChecked(&mut self, _checked: bool) {
+    fn SetChecked(&mut self, checked: bool) {
+        self.deref_mut().dirty_checkedness = true;
+        self.set_checkedness(checked);
     }
 
     fn Disabled(&self) -> bool {
-        false
+        let element: &JSRef<Element> = ElementCast::from_ref(self);
+        element.get_attribute(Null, "disabled").is_some()
     }
 
-    fn SetDisabled(&mut self, _disabled: bool) -> ErrorResult {
-        Ok(())
+    fn SetDisabled(&mut self, disabled: bool) -> ErrorResult {
+        let element: &mut JSRef<Element> = ElementCast::from_mut_ref(self);
+        if disabled {
+            element.set_attribute(Null, "disabled".to_owned(), "".to_owned())
+        } else {
+            element.remove_attribute(Null, "disabled".to_owned())
+        }
     }
 
     fn FormAction(&self) -> DOMString {
@@ -425,3 +453,98 @@ impl<'a> HTMLInputElementMethods for JSRef<'a, HTMLInputElement> {
         Ok(())
     }
 }
+
+trait PrivateHTMLInputElementHelpers {
+    fn input_type(&self) -> DOMString;
+    fn set_checkedness(&mut self, checked: bool);
+    fn handle_event_impl(&mut self, event: &JSRef<Event>);
+}
+
+impl<'a> PrivateHTMLInputElementHelpers for JSRef<'a, HTMLInputElement> {
+    fn input_type(&self) -> DOMString {
+        let element: &JSRef<Element> = ElementCast::from_ref(self);
+        element.get_attribute(Null, "type").root().map_or("text".to_owned(), |attr| {
+            attr.Value().to_ascii_lower()
+        })
+    }
+
+    /// Sets the checkedness of this element and restyles the document if it changed.
+    fn set_checkedness(&mut self, checked: bool) {
+        {
+            let node: &mut JSRef<Node> = NodeCast::from_mut_ref(self);
+            if node.get_checked_state() == checked {
+                return
+            }
+            node.set_checked_state(checked);
+        }
+        let node: &JSRef<Node> = NodeCast::from_ref(self);
+        if node.is_in_doc() {
+            let doc = document_from_node(self).root();
+            doc.damage_and_reflow(MatchSelectorsDocumentDamage);
+        }
+    }
+
+    // http://www.whatwg.org/html/#checkbox-state-(type=checkbox)
+    // http://www.whatwg.org/html/#radio-button-state-(type=radio)
+    //
+    // FIXME: Checking a radio button should uncheck the other buttons in its group.
+    fn handle_event_impl(&mut self, event: &JSRef<Event>) {
+        if "click" != event.Type() || event.DefaultPrevented() {
+            return
+        }
+        let element: &JSRef<Element> = ElementCast::from_ref(self);
+        if element.get_attribute(Null, "disabled").is_some() {
+            return
+        }
+        let checked = match self.input_type().as_slice() {
+            "checkbox" => !self.Checked(),
+            "radio" => true,
+            _ => return,
+        };
+        self.deref_mut().dirty_checkedness = true;
+        self.set_checkedness(checked);
+    }
+}
+
+impl<'a> VirtualMethods for JSRef<'a, HTMLInputElement> {
+    fn super_type<'a>(&'a mut self) -> Option<&'a mut VirtualMethods:> {
+        let htmlelement: &mut JSRef<HTMLElement> = HTMLElementCast::from_mut_ref(self);
+        Some(htmlelement as &mut VirtualMethods:)
+    }
+
+    // The checkedness is updated before the element notifies the document of the attribute
+    // change, so that the restyle it triggers sees the new state.
+    fn after_set_attr(&mut self, name: DOMString, value: DOMString) {
+        if "checked" == name && !self.dirty_checkedness {
+            let node: &mut JSRef<Node> = NodeCast::from_mut_ref(self);
+            node.set_checked_state(true);
+        }
+
+        match self.super_type() {
+            Some(ref mut s) => s.after_set_attr(name.clone(), value.clone()),
+            _ => (),
+        }
+    }
+
+    fn before_remove_attr(&mut self, name: DOMString, value: DOMString) {
+        if "checked" == name && !self.dirty_checkedness {
+            let node: &mut JSRef<Node> = NodeCast::from_mut_ref(self);
+            node.set_checked_state(false);
+        }
+
+        match self.super_type() {
+            Some(ref mut s) => s.before_remove_attr(name.clone(), value.clone()),
+            _ => (),
+        }
+    }
+
+    fn handle_event(&mut self, event: &JSRef<Event>) {
+        match self.super_type() {
+            Some(s) => {
+                s.handle_event(event);
+            }
+            None => {}
+        }
+        self.handle_event_impl(event);
+    }
+}
@@ -127,6 +127,14 @@ impl NodeFlags {
 bitfield!(NodeFlags, is_in_doc, set_is_in_doc, 0x01)
 /// Specifies whether this node is hover state for this node
 bitfield!(NodeFlags, get_in_hover_state, set_is_in_hover_state, 0x02)
+/// Specifies whether this node is the focused element of its document
+bitfield!(NodeFlags, get_in_focus_state, set_is_in_focus_state, 0x04)
+/// Specifies whether this node is being activated (e.g. the mouse button is held down on it)
+bitfield!(NodeFlags, get_in_active_state, set_is_in_active_state, 0x08)
+/// Specifies whether this node is a checked form control
+bitfield!(NodeFlags, get_in_checked_state, set_is_in_checked_state, 0x10)
+/// Specifies whether this node is the target of the document's URL fragment
+bitfield!(NodeFlags, get_in_target_state, set_is_in_target_state, 0x20)
 
 #[unsafe_destructor]
 impl Drop for Node {
@@ -393,6 +401,7 @@ impl<'a> PrivateNodeHelpers for JSRef<'a, Node> {
 
 pub trait NodeHelpers {
     fn ancestors(&self) -> AncestorIterator;
+    fn inclusive_ancestors(&self) -> AncestorIterator;
     fn children(&self) -> AbstractNodeChildrenIterator;
     fn child_elements(&self) -> ChildElementIterator;
     fn following_siblings(&self) -> AbstractNodeChildrenIterator;
@@ -421,6 +430,14 @@ pub trait NodeHelpers {
 
     fn get_hover_state(&self) -> bool;
     fn set_hover_state(&mut self, state: bool);
+    fn get_focus_state(&self) -> bool;
+    fn set_focus_state(&mut self, state: bool);
+    fn get_active_state(&self) -> bool;
+    fn set_active_state(&mut self, state: bool);
+    fn get_checked_state(&self) -> bool;
+    fn set_checked_state(&mut self, state: bool);
+    fn get_target_state(&self) -> bool;
+    fn set_target_state(&mut self, state: bool);
 
     fn dump(&self);
     fn dump_indent(&self, indent: uint);
@@ -532,6 +549,38 @@ impl<'a> NodeHelpers for JSRef<'a, Node> {
         self.flags.set_is_in_hover_state(state);
     }
 
+    fn get_focus_state(&self) -> bool {
+        self.flags.get_in_focus_state()
+    }
+
+    fn set_focus_state(&mut self, state: bool) {
+        self.flags.set_is_in_focus_state(state);
+    }
+
+    fn get_active_state(&self) -> bool {
+        self.flags.get_in_active_state()
+    }
+
+    fn set_active_state(&mut self, state: bool) {
+        self.flags.set_is_in_active_state(state);
+    }
+
+    fn get_checked_state(&self) -> bool {
+        self.flags.get_in_checked_state()
+    }
+
+    fn set_checked_state(&mut self, state: bool) {
+        self.flags.set_is_in_checked_state(state);
+    }
+
+    fn get_target_state(&self) -> bool {
+        self.flags.get_in_target_state()
+    }
+
+    fn set_target_state(&mut self, state: bool) {
+        self.flags.set_is_in_target_state(state);
+    }
+
     /// Iterates over this node and all its descendants, in preorder.
     fn traverse_preorder<'a>(&'a self) -> TreeIterator<'a> {
         let mut nodes = vec!();
@@ -597,6 +646,12 @@ impl<'a> NodeHelpers for JSRef<'a, Node> {
         }
     }
 
+    fn inclusive_ancestors(&self) -> AncestorIterator {
+        AncestorIterator {
+            current: Some(self.clone()),
+        }
+    }
+
     fn owner_doc(&self) -> Temporary<Document> {
         Temporary::new(self.owner_doc.get_ref().clone())
     }
@@ -706,12 +761,32 @@ impl LayoutNodeHelpers for JS<Node> {
 
 pub trait RawLayoutNodeHelpers {
     unsafe fn get_hover_state_for_layout(&self) -> bool;
+    unsafe fn get_focus_state_for_layout(&self) -> bool;
+    unsafe fn get_active_state_for_layout(&self) -> bool;
+    unsafe fn get_checked_state_for_layout(&self) -> bool;
+    unsafe fn get_target_state_for_layout(&self) -> bool;
 }
 
 impl RawLayoutNodeHelpers for Node {
     unsafe fn get_hover_state_for_layout(&self) -> bool {
         self.flags.get_in_hover_state()
     }
+
+    unsafe fn get_focus_state_for_layout(&self) -> bool {
+        self.flags.get_in_focus_state()
+    }
+
+    unsafe fn get_active_state_for_layout(&self) -> bool {
+        self.flags.get_in_active_state()
+    }
+
+    unsafe fn get_checked_state_for_layout(&self) -> bool {
+        self.flags.get_in_checked_state()
+    }
+
+    unsafe fn get_target_state_for_layout(&self) -> bool {
+        self.flags.get_in_target_state()
+    }
 }
 
 
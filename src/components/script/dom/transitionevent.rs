@@ -0,0 +1,90 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::BindingDeclarations::TransitionEventBinding;
+use dom::bindings::codegen::InheritTypes::{EventCast, TransitionEventDerived};
+use dom::bindings::error::Fallible;
+use dom::bindings::js::{JSRef, Temporary};
+use dom::bindings::utils::{Reflectable, Reflector, reflect_dom_object};
+use dom::event::{Event, EventMethods, TransitionEventTypeId};
+use dom::window::Window;
+use servo_util::str::DOMString;
+
+#[deriving(Encodable)]
+pub struct TransitionEvent {
+    event: Event,
+    property_name: DOMString,
+    elapsed_time: f32,
+    pseudo_element: DOMString,
+}
+
+impl TransitionEventDerived for Event {
+    fn is_transitionevent(&self) -> bool {
+        self.type_id == TransitionEventTypeId
+    }
+}
+
+impl TransitionEvent {
+    pub fn new_inherited(property_name: DOMString, elapsed_time: f32, pseudo_element: DOMString)
+                         -> TransitionEvent {
+        TransitionEvent {
+            event: Event::new_inherited(TransitionEventTypeId),
+            property_name: property_name,
+            elapsed_time: elapsed_time,
+            pseudo_element: pseudo_element,
+        }
+    }
+    pub fn new(window: &JSRef<Window>, type_: DOMString,
+               can_bubble: bool, cancelable: bool,
+               property_name: DOMString, elapsed_time: f32, pseudo_element: DOMString)
+               -> Temporary<TransitionEvent> {
+        let mut ev = reflect_dom_object(box TransitionEvent::new_inherited(property_name,
+                                                                           elapsed_time,
+                                                                           pseudo_element),
+                                        window,
+                                        TransitionEventBinding::Wrap).root();
+        {
+            let event: &mut JSRef<Event> = EventCast::from_mut_ref(&mut *ev);
+            event.InitEvent(type_, can_bubble, cancelable);
+        }
+        Temporary::from_rooted(&*ev)
+    }
+    pub fn Constructor(owner: &JSRef<Window>,
+                       type_: DOMString,
+                       init: &TransitionEventBinding::TransitionEventInit)
+                       -> Fallible<Temporary<TransitionEvent>> {
+        let ev = TransitionEvent::new(owner, type_, init.parent.bubbles, init.parent.cancelable,
+                                      init.propertyName.clone(), init.elapsedTime,
+                                      init.pseudoElement.clone());
+        Ok(ev)
+    }
+}
+
+pub trait TransitionEventMethods {
+    fn PropertyName(&self) -> DOMString;
+    fn ElapsedTime(&self) -> f32;
+    fn PseudoElement(&self) -> DOMString;
+}
+
+impl<'a> TransitionEventMethods for JSRef<'a, TransitionEvent> {
+    fn PropertyName(&self) -> DOMString {
+        self.property_name.clone()
+    }
+    fn ElapsedTime(&self) -> f32 {
+        self.elapsed_time
+    }
+    fn PseudoElement(&self) -> DOMString {
+        self.pseudo_element.clone()
+    }
+}
+
+impl Reflectable for TransitionEvent {
+    fn reflector<'a>(&'a self) -> &'a Reflector {
+        self.event.reflector()
+    }
+
+    fn mut_reflector<'a>(&'a mut self) -> &'a mut Reflector {
+        self.event.mut_reflector()
+    }
+}
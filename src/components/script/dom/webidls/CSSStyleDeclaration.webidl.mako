@@ -0,0 +1,38 @@
+/* -*- Mode: IDL; tab-width: 2; indent-tabs-mode: nil; c-basic-offset: 2 -*- */
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this file,
+ * You can obtain one at http://mozilla.org/MPL/2.0/.
+ *
+ * The origin of this IDL file is
+ * http://dev.w3.org/csswg/cssom/#the-cssstyledeclaration-interface
+ */
+
+## This file is a Mako template. The camel-cased attributes are generated from the properties in
+## style/properties.rs.mako, whose path is passed in as `properties_template`.
+
+interface CSSStyleDeclaration {
+  [SetterThrows]
+           attribute DOMString cssText;
+  readonly attribute unsigned long length;
+  getter DOMString item(unsigned long index);
+  DOMString getPropertyValue(DOMString property);
+  DOMString getPropertyPriority(DOMString property);
+  [Throws]
+  void setProperty(DOMString property, [TreatNullAs=EmptyString] DOMString value,
+                   [TreatNullAs=EmptyString] optional DOMString priority = "");
+  [Throws]
+  DOMString removeProperty(DOMString property);
+  //readonly attribute CSSRule? parentRule;
+};
+
+<%
+    from mako.template import Template
+    properties = Template(filename=properties_template)
+    properties.render()
+%>\
+// http://dev.w3.org/csswg/cssom/#dom-cssstyledeclaration-camel-cased-attribute
+partial interface CSSStyleDeclaration {
+% for (name, camel_case) in properties.module.cssom_properties():
+  [TreatNullAs=EmptyString, SetterThrows] attribute DOMString ${camel_case[0].lower() + camel_case[1:]};
+% endfor
+};
@@ -9,6 +9,7 @@ use dom::bindings::js::{JS, JSRef, Temporary, OptionalSettable};
 use dom::bindings::trace::{Traceable, Untraceable};
 use dom::bindings::utils::{Reflectable, Reflector};
 use dom::browsercontext::BrowserContext;
+use dom::cssstyledeclaration::CSSStyleDeclaration;
 use dom::document::Document;
 use dom::element::Element;
 use dom::eventtarget::{EventTarget, WindowTypeId, EventTargetHelpers};
@@ -17,13 +18,14 @@ use dom::location::Location;
 use dom::navigator::Navigator;
 use dom::performance::Performance;
 
-use layout_interface::{ReflowForDisplay, DocumentDamageLevel};
+use layout_interface::{AttributeMutation, ReflowForDisplay, DocumentDamageLevel};
 use script_task::{ExitWindowMsg, FireTimerMsg, Page, ScriptChan, TriggerLoadMsg, TriggerFragmentMsg};
 use servo_msg::compositor_msg::ScriptListener;
 use servo_net::image_cache_task::ImageCacheTask;
 use servo_util::str::DOMString;
 use servo_util::task::{spawn_named};
 use servo_util::url::parse_url;
+use style::parse_pseudo_element;
 
 use js::jsapi::JSContext;
 use js::jsapi::{JS_GC, JS_GetRuntime};
@@ -142,6 +144,8 @@ pub trait WindowMethods {
     fn Window(&self) -> Temporary<Window>;
     fn Self(&self) -> Temporary<Window>;
     fn Performance(&mut self) -> Temporary<Performance>;
+    fn GetComputedStyle(&self, element: &JSRef<Element>, pseudo_element: Option<DOMString>)
+                        -> Temporary<CSSStyleDeclaration>;
     fn GetOnload(&self) -> Option<EventHandlerNonNull>;
     fn SetOnload(&mut self, listener: Option<EventHandlerNonNull>);
     fn GetOnunload(&self) -> Option<EventHandlerNonNull>;
@@ -315,6 +319,23 @@ impl<'a> WindowMethods for JSRef<'a, Window> {
             JS_GC(JS_GetRuntime(self.get_cx()));
         }
     }
+
+    fn GetComputedStyle(&self, element: &JSRef<Element>, pseudo_element: Option<DOMString>)
+                        -> Temporary<CSSStyleDeclaration> {
+        // A missing or empty argument, or one not starting with a colon, means the element
+        // itself. Unknown pseudo-elements yield an empty declaration.
+        let pseudo_element = pseudo_element.unwrap_or("".to_owned());
+        let (owner, pseudo_element) = if pseudo_element.starts_with("::") {
+            let pseudo_element = parse_pseudo_element(pseudo_element.slice_from(2));
+            (pseudo_element.map(|_| element.clone()), pseudo_element)
+        } else if pseudo_element.starts_with(":") {
+            let pseudo_element = parse_pseudo_element(pseudo_element.slice_from(1));
+            (pseudo_element.map(|_| element.clone()), pseudo_element)
+        } else {
+            (Some(element.clone()), None)
+        };
+        CSSStyleDeclaration::new(self, owner, pseudo_element, true)
+    }
 }
 
 impl Reflectable for Window {
@@ -329,6 +350,8 @@ impl Reflectable for Window {
 
 pub trait WindowHelpers {
     fn damage_and_reflow(&self, damage: DocumentDamageLevel);
+    fn damage_attribute_and_reflow(&self, mutation: AttributeMutation);
+    fn flush_layout(&self);
     fn wait_until_safe_to_modify_dom(&self);
     fn init_browser_context(&mut self, doc: &JSRef<Document>);
     fn load_url(&self, href: DOMString);
@@ -347,6 +370,19 @@ impl<'a> WindowHelpers for JSRef<'a, Window> {
         self.page().reflow(ReflowForDisplay, self.script_chan.clone(), *self.compositor);
     }
 
+    fn damage_attribute_and_reflow(&self, mutation: AttributeMutation) {
+        self.page().damage_attribute(mutation);
+        self.page().reflow(ReflowForDisplay, self.script_chan.clone(), *self.compositor);
+    }
+
+    /// Reflows the page if any damage has not been sent to layout yet, so that a layout query
+    /// made after this sees the current state of the document.
+    fn flush_layout(&self) {
+        if self.page().has_pending_damage() {
+            self.page().reflow(ReflowForDisplay, self.script_chan.clone(), *self.compositor);
+        }
+    }
+
     fn wait_until_safe_to_modify_dom(&self) {
         // FIXME: This disables concurrent layout while we are modifying the DOM, since
         //        our current architecture is entirely unsafe in the presence of races.
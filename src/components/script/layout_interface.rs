@@ -14,10 +14,13 @@ use geom::rect::Rect;
 use geom::size::Size2D;
 use libc::c_void;
 use script_task::{ScriptChan};
+use servo_msg::compositor_msg::LayerId;
 use servo_util::geometry::Au;
+use servo_util::str::DOMString;
 use std::cmp;
 use std::comm::{channel, Receiver, Sender};
-use style::Stylesheet;
+use style::{ComputedValues, PseudoElement, Stylesheet};
+use sync::Arc;
 use url::Url;
 
 use serialize::{Encodable, Encoder};
@@ -37,6 +40,10 @@ pub enum Msg {
     /// FIXME(pcwalton): As noted below, this isn't very type safe.
     QueryMsg(LayoutQuery),
 
+    /// Tells layout where the page or a scrollable box with the given layer was scrolled to, so
+    /// that hit testing can account for it.
+    SetScrollOffsetMsg(LayerId, Point2D<f32>),
+
     /// Destroys layout data associated with a DOM node.
     ///
     /// TODO(pcwalton): Maybe think about batching to avoid message traffic.
@@ -61,6 +68,9 @@ pub enum LayoutQuery {
     /// Requests the node containing the point of interest
     HitTestQuery(TrustedNodeAddress, Point2D<f32>, Sender<Result<HitTestResponse, ()>>),
     MouseOverQuery(TrustedNodeAddress, Point2D<f32>, Sender<Result<MouseOverResponse, ()>>),
+    /// Requests the computed style of an element or one of its pseudo-elements, as in the
+    /// `getComputedStyle()` call.
+    ComputedStyleQuery(TrustedNodeAddress, Option<PseudoElement>, Sender<ComputedStyleResponse>),
 }
 
 /// The address of a node known to be valid. These must only be sent from content -> layout,
@@ -85,12 +95,17 @@ pub struct ContentBoxResponse(pub Rect<Au>);
 pub struct ContentBoxesResponse(pub Vec<Rect<Au>>);
 pub struct HitTestResponse(pub UntrustedNodeAddress);
 pub struct MouseOverResponse(pub Vec<UntrustedNodeAddress>);
+/// The computed style, or `None` if the node has not been styled or has no such pseudo-element.
+pub struct ComputedStyleResponse(pub Option<Arc<ComputedValues>>);
 
 /// Determines which part of the
 #[deriving(Eq, Ord, TotalEq, TotalOrd, Encodable)]
 pub enum DocumentDamageLevel {
     /// Reflow, but do not perform CSS selector matching.
     ReflowDocumentDamage,
+    /// Perform CSS selector matching only on the elements affected by the attribute mutations
+    /// in `DocumentDamage::attribute_mutations`, and reflow.
+    RestyleDocumentDamage,
     /// Perform CSS selector matching and reflow.
     MatchSelectorsDocumentDamage,
     /// Content changed; set full style damage and do the above.
@@ -104,6 +119,19 @@ impl DocumentDamageLevel {
     }
 }
 
+/// A change to an attribute of an element, from which layout computes restyle hints.
+#[deriving(Encodable)]
+pub struct AttributeMutation {
+    /// The element whose attribute changed.
+    pub node: TrustedNodeAddress,
+    /// The local name of the attribute.
+    pub local_name: DOMString,
+    /// The value of the attribute before the change, or `None` if it was absent.
+    pub old_value: Option<DOMString>,
+    /// The value of the attribute after the change, or `None` if it is now absent.
+    pub new_value: Option<DOMString>,
+}
+
 /// What parts of the document have changed, as far as the script task can tell.
 ///
 /// Note that this is fairly coarse-grained and is separate from layout's notion of the document
@@ -113,6 +141,9 @@ pub struct DocumentDamage {
     pub root: TrustedNodeAddress,
     /// The amount of damage that occurred.
     pub level: DocumentDamageLevel,
+    /// The attribute mutations that occurred. Only used when `level` is
+    /// `RestyleDocumentDamage`.
+    pub attribute_mutations: Vec<AttributeMutation>,
 }
 
 /// Why we're doing reflow.
@@ -171,4 +202,7 @@ fn test_add_damage() {
     assert_add(ContentChangedDocumentDamage, ReflowDocumentDamage, ContentChangedDocumentDamage);
     assert_add(MatchSelectorsDocumentDamage, ContentChangedDocumentDamage, ContentChangedDocumentDamage);
     assert_add(ContentChangedDocumentDamage, MatchSelectorsDocumentDamage, ContentChangedDocumentDamage);
+    assert_add(ReflowDocumentDamage, RestyleDocumentDamage, RestyleDocumentDamage);
+    assert_add(RestyleDocumentDamage, MatchSelectorsDocumentDamage, MatchSelectorsDocumentDamage);
+    assert_add(ContentChangedDocumentDamage, RestyleDocumentDamage, ContentChangedDocumentDamage);
 }
@@ -70,6 +70,8 @@ pub mod dom {
     pub mod clientrectlist;
     pub mod comment;
     pub mod console;
+    pub mod cssstyledeclaration;
+    pub mod cssstyledeclarationmethods;
     pub mod customevent;
     pub mod document;
     pub mod documentfragment;
@@ -162,6 +164,8 @@ pub mod dom {
     pub mod performance;
     pub mod performancetiming;
     pub mod progressevent;
+    pub mod animationevent;
+    pub mod transitionevent;
     pub mod uievent;
     pub mod text;
     pub mod validitystate;
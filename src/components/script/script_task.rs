@@ -8,14 +8,18 @@
 use dom::bindings::codegen::RegisterBindings;
 use dom::bindings::codegen::InheritTypes::{EventTargetCast, NodeCast, ElementCast, EventCast};
 use dom::bindings::js::{JS, JSRef, RootCollection, Temporary, OptionalSettable};
-use dom::bindings::js::OptionalRootable;
+use dom::bindings::js::{OptionalRootable, OptionalRootedRootable};
 use dom::bindings::trace::{Traceable, Untraceable};
 use dom::bindings::utils::{Reflectable, GlobalStaticData};
 use dom::bindings::utils::{wrap_for_same_compartment, pre_wrap};
 use dom::document::{Document, HTMLDocument, DocumentMethods, DocumentHelpers};
-use dom::element::{Element, AttributeHandlers};
+use dom::element::{Element, AttributeHandlers, ElementHelpers};
 use dom::event::{Event_, ResizeEvent, ReflowEvent, ClickEvent, MouseDownEvent, MouseMoveEvent, MouseUpEvent};
-use dom::event::{Event, EventMethods};
+use dom::animationevent::AnimationEvent;
+use dom::event::{AnimationEndEvent, AnimationFrameEvent, AnimationIterationEvent};
+use dom::event::{AnimationStartEvent};
+use dom::event::{Event, EventMethods, ScrollEvent, TransitionEndEvent};
+use dom::transitionevent::TransitionEvent;
 use dom::uievent::UIEvent;
 use dom::eventtarget::{EventTarget, EventTargetHelpers};
 use dom::node;
@@ -25,14 +29,16 @@ use dom::xmlhttprequest::{TrustedXHRAddress, XMLHttpRequest, XHRProgress};
 use html::hubbub_html_parser::HtmlParserResult;
 use html::hubbub_html_parser::{HtmlDiscoveredStyle, HtmlDiscoveredScript};
 use html::hubbub_html_parser;
-use layout_interface::{AddStylesheetMsg, DocumentDamage};
+use layout_interface::{AddStylesheetMsg, AttributeMutation, DocumentDamage};
 use layout_interface::{DocumentDamageLevel, HitTestQuery, HitTestResponse, LayoutQuery, MouseOverQuery, MouseOverResponse};
 use layout_interface::{LayoutChan, MatchSelectorsDocumentDamage, QueryMsg};
 use layout_interface::{Reflow, ReflowDocumentDamage, ReflowForDisplay, ReflowGoal, ReflowMsg};
-use layout_interface::ContentChangedDocumentDamage;
+use layout_interface::SetScrollOffsetMsg;
+use layout_interface::{ContentChangedDocumentDamage, RestyleDocumentDamage};
 use layout_interface::UntrustedNodeAddress;
 use layout_interface;
 
+use collections::hashmap::HashMap;
 use geom::point::Point2D;
 use geom::size::Size2D;
 use js::jsapi::JS_CallFunctionValue;
@@ -164,6 +170,11 @@ pub struct Page {
     /// Pending scroll to fragment event, if any
     fragment_node: Traceable<RefCell<Option<JS<Element>>>>,
 
+    /// The scroll offsets of this page and of its scrollable boxes, keyed by the IDs of the layers
+    /// holding their contents. The page itself has the null layer ID. Boxes that were never
+    /// scrolled have no entry.
+    pub scroll_offsets: Untraceable<RefCell<HashMap<LayerId, Point2D<f32>>>>,
+
     /// Associated resource task for use by DOM objects like XMLHttpRequest
     pub resource_task: Untraceable<ResourceTask>,
 
@@ -222,6 +233,7 @@ impl Page {
             next_subpage_id: Untraceable::new(Cell::new(SubpageId(0))),
             resize_event: Untraceable::new(Cell::new(None)),
             fragment_node: Traceable::new(RefCell::new(None)),
+            scroll_offsets: Untraceable::new(RefCell::new(HashMap::new())),
             last_reflow_id: Traceable::new(Cell::new(0)),
             resource_task: Untraceable::new(resource_task),
             constellation_chan: Untraceable::new(constellation_chan),
@@ -309,6 +321,21 @@ impl Page {
 
     /// Adds the given damage.
     pub fn damage(&self, level: DocumentDamageLevel) {
+        self.add_damage(level, None)
+    }
+
+    /// Adds damage for a change to an attribute of an element, so that layout can restyle only
+    /// the elements affected by the change.
+    pub fn damage_attribute(&self, mutation: AttributeMutation) {
+        self.add_damage(RestyleDocumentDamage, Some(mutation))
+    }
+
+    /// Returns true if there is damage that has not been sent to layout in a reflow yet.
+    pub fn has_pending_damage(&self) -> bool {
+        self.damage.deref().borrow().is_some()
+    }
+
+    fn add_damage(&self, level: DocumentDamageLevel, mutation: Option<AttributeMutation>) {
         let root = match *self.frame() {
             None => return,
             Some(ref frame) => frame.document.root().GetDocumentElement()
@@ -317,21 +344,30 @@ impl Page {
             None => {},
             Some(root) => {
                 let root: &JSRef<Node> = NodeCast::from_ref(&*root);
-                let mut damage = *self.damage.deref().borrow_mut();
-                match damage {
-                    None => {}
-                    Some(ref mut damage) => {
-                        // FIXME(pcwalton): This is wrong. We should trace up to the nearest ancestor.
-                        damage.root = root.to_trusted_node_address();
-                        damage.level.add(level);
-                        return
-                    }
+                let mut damage = self.damage.deref().borrow_mut();
+                if damage.is_none() {
+                    *damage = Some(DocumentDamage {
+                        root: root.to_trusted_node_address(),
+                        level: level,
+                        attribute_mutations: vec!(),
+                    })
+                } else {
+                    let damage = damage.get_mut_ref();
+                    // FIXME(pcwalton): This is wrong. We should trace up to the nearest ancestor.
+                    damage.root = root.to_trusted_node_address();
+                    damage.level.add(level);
                 }
 
-                *self.damage.deref().borrow_mut() = Some(DocumentDamage {
-                    root: root.to_trusted_node_address(),
-                    level: level,
-                })
+                // Attribute mutations are only needed if layout won't restyle everything anyway.
+                let damage = damage.get_mut_ref();
+                if damage.level != RestyleDocumentDamage {
+                    damage.attribute_mutations.clear();
+                    return
+                }
+                match mutation {
+                    Some(mutation) => damage.attribute_mutations.push(mutation),
+                    None => {}
+                }
             }
         };
     }
@@ -563,7 +599,10 @@ pub struct ScriptTask {
     /// The JSContext.
     js_context: RefCell<Option<Rc<Cx>>>,
 
-    mouse_over_targets: RefCell<Option<Vec<JS<Node>>>>
+    mouse_over_targets: RefCell<Option<Vec<JS<Node>>>>,
+
+    /// The elements that are currently being activated by a mouse button, for `:active`.
+    active_targets: RefCell<Option<Vec<JS<Node>>>>
 }
 
 /// In the event of task failure, all data on the stack runs its destructor. However, there
@@ -632,7 +671,8 @@ impl ScriptTask {
 
             js_runtime: js_runtime,
             js_context: RefCell::new(Some(js_context)),
-            mouse_over_targets: RefCell::new(None)
+            mouse_over_targets: RefCell::new(None),
+            active_targets: RefCell::new(None)
         })
     }
 
@@ -1045,6 +1085,13 @@ impl ScriptTask {
 
         let mut fragment_node = page.fragment_node.deref().borrow_mut();
         (*fragment_node).assign(fragment.map_or(None, |fragid| page.find_fragment_node(fragid)));
+        match (*fragment_node).root() {
+            Some(node) => {
+                let mut document = (*document).clone();
+                document.set_target_element(Some(&*node));
+            }
+            None => {}
+        }
 
         let ConstellationChan(ref chan) = self.constellation_chan;
         chan.send(LoadCompleteMsg(page.id, url));
@@ -1115,6 +1162,16 @@ impl ScriptTask {
                 }
             }
 
+            AnimationFrameEvent => {
+                debug!("script got animation frame event");
+                let page = get_page(&*self.page.borrow(), pipeline_id);
+                let frame = page.frame();
+                if frame.is_some() {
+                    page.damage(RestyleDocumentDamage);
+                    page.reflow(ReflowForDisplay, self.chan.clone(), self.compositor)
+                }
+            }
+
             ClickEvent(_button, point) => {
                 debug!("ClickEvent: clicked at {:?}", point);
                 let page = get_page(&*self.page.borrow(), pipeline_id);
@@ -1148,8 +1205,63 @@ impl ScriptTask {
                     None => {}
                 }
             }
-            MouseDownEvent(..) => {}
-            MouseUpEvent(..) => {}
+            MouseDownEvent(_button, point) => {
+                let page = get_page(&*self.page.borrow(), pipeline_id);
+                self.clear_active_targets(&*page);
+                match page.hit_test(&point) {
+                    Some(node_address) => {
+                        let temp_node =
+                                node::from_untrusted_node_address(
+                                    self.js_runtime.deref().ptr, node_address);
+
+                        let maybe_node = temp_node.root().ancestors().find(|node| node.is_element());
+                        match maybe_node {
+                            Some(node) => {
+                                // Activate the element and all of its ancestors.
+                                // http://www.whatwg.org/html/#selector-active
+                                let mut target_list = vec!();
+                                for ancestor in node.inclusive_ancestors() {
+                                    let mut ancestor = ancestor;
+                                    if ancestor.is_element() {
+                                        ancestor.set_active_state(true);
+                                        target_list.push(ancestor.unrooted());
+                                    }
+                                }
+                                *self.active_targets.borrow_mut() = Some(target_list);
+
+                                // Focus the nearest focusable inclusive ancestor, or clear focus
+                                // if there is none.
+                                let focus_target = node.inclusive_ancestors().find(|ancestor| {
+                                    match ElementCast::to_ref(ancestor) {
+                                        Some(element) => element.is_focusable_area(),
+                                        None => false,
+                                    }
+                                });
+                                match *page.frame() {
+                                    Some(ref frame) => {
+                                        let mut document = frame.document.root();
+                                        match focus_target {
+                                            Some(ref target) => {
+                                                let element: &JSRef<Element> =
+                                                    ElementCast::to_ref(target).unwrap();
+                                                document.set_focused_element(Some(element));
+                                            }
+                                            None => document.set_focused_element(None),
+                                        }
+                                    }
+                                    None => {}
+                                }
+                            }
+                            None => {}
+                        }
+                    }
+                    None => {}
+                }
+            }
+            MouseUpEvent(..) => {
+                let page = get_page(&*self.page.borrow(), pipeline_id);
+                self.clear_active_targets(&*page);
+            }
             MouseMoveEvent(point) => {
                 let page = get_page(&*self.page.borrow(), pipeline_id);
                 match page.get_nodes_under_mouse(&point) {
@@ -1214,6 +1326,78 @@ impl ScriptTask {
                     None => {}
               }
             }
+
+            ScrollEvent(layer_id, scroll_offset) => {
+                let page = get_page(&*self.page.borrow(), pipeline_id);
+                page.scroll_offsets.deref().borrow_mut().insert(layer_id, scroll_offset);
+                let LayoutChan(ref chan) = *page.layout_chan;
+                chan.send(SetScrollOffsetMsg(layer_id, scroll_offset));
+            }
+
+            // http://dev.w3.org/csswg/css-transitions/#transition-events
+            TransitionEndEvent(node_address, property_name, elapsed_time) => {
+                self.dispatch_event_at_node(pipeline_id, node_address, |window| {
+                    EventCast::from_temporary(TransitionEvent::new(window,
+                                                                   "transitionend".to_owned(),
+                                                                   true,
+                                                                   true,
+                                                                   property_name.clone(),
+                                                                   elapsed_time,
+                                                                   "".to_owned()))
+                })
+            }
+
+            // http://dev.w3.org/csswg/css-animations/#events
+            AnimationStartEvent(node_address, animation_name, elapsed_time) => {
+                self.fire_animation_event(pipeline_id, "animationstart", node_address,
+                                          animation_name, elapsed_time)
+            }
+            AnimationIterationEvent(node_address, animation_name, elapsed_time) => {
+                self.fire_animation_event(pipeline_id, "animationiteration", node_address,
+                                          animation_name, elapsed_time)
+            }
+            AnimationEndEvent(node_address, animation_name, elapsed_time) => {
+                self.fire_animation_event(pipeline_id, "animationend", node_address,
+                                          animation_name, elapsed_time)
+            }
+        }
+    }
+
+    /// Dispatches an animation event of the given type at the node with the given address.
+    fn fire_animation_event(&self,
+                            pipeline_id: PipelineId,
+                            type_: &str,
+                            node_address: UntrustedNodeAddress,
+                            animation_name: DOMString,
+                            elapsed_time: f32) {
+        self.dispatch_event_at_node(pipeline_id, node_address, |window| {
+            EventCast::from_temporary(AnimationEvent::new(window,
+                                                          type_.to_owned(),
+                                                          true,
+                                                          false,
+                                                          animation_name.clone(),
+                                                          elapsed_time,
+                                                          "".to_owned()))
+        })
+    }
+
+    /// Dispatches the event that `create_event` creates for the window of the given pipeline at
+    /// the node with the given address. Nothing is dispatched if the pipeline has no window.
+    fn dispatch_event_at_node(&self,
+                              pipeline_id: PipelineId,
+                              node_address: UntrustedNodeAddress,
+                              create_event: |&JSRef<Window>| -> Temporary<Event>) {
+        let page = get_page(&*self.page.borrow(), pipeline_id);
+        let node = node::from_untrusted_node_address(self.js_runtime.deref().ptr,
+                                                     node_address).root();
+        match *page.frame() {
+            Some(ref frame) => {
+                let window = frame.window.root();
+                let mut event = create_event(&*window).root();
+                let target: &JSRef<EventTarget> = EventTargetCast::from_ref(&*node);
+                let _ = target.dispatch_event_with_target(None, &mut *event);
+            }
+            None => {}
         }
     }
 
@@ -1228,13 +1412,32 @@ impl ScriptTask {
     /// for the given pipeline.
     fn trigger_fragment(&self, pipeline_id: PipelineId, url: Url) {
         let page = get_page(&*self.page.borrow(), pipeline_id);
+        let mut document = page.frame().get_ref().document.root();
         match page.find_fragment_node(url.fragment.unwrap()).root() {
             Some(node) => {
+                document.set_target_element(Some(&*node));
                 self.scroll_fragment_point(pipeline_id, &*node);
             }
+            // A fragment that no element is named after leaves the document without a target.
+            None => document.set_target_element(None),
+        }
+    }
+
+    /// Clears the `:active` state of the elements activated by the last mouse down, if any.
+    fn clear_active_targets(&self, page: &Page) {
+        let active_targets = &mut *self.active_targets.borrow_mut();
+        match active_targets.take() {
+            Some(mut active_targets) => {
+                for node in active_targets.mut_iter() {
+                    let mut node = node.root();
+                    node.set_active_state(false);
+                }
+                page.damage(MatchSelectorsDocumentDamage);
+                page.reflow(ReflowForDisplay, self.chan.clone(), self.compositor);
+            }
             None => {}
-         }
-     }
+        }
+    }
 }
 
 /// Shuts down layout for the given page tree.
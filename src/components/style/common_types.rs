@@ -13,9 +13,13 @@ pub static DEFAULT_LINE_HEIGHT: CSSFloat = 1.14;
 
 pub mod specified {
     use std::ascii::StrAsciiExt;
+    use std::f64::consts::PI;
     use cssparser::ast;
     use cssparser::ast::*;
+    use parsing_utils::get_ident_lower;
+    use servo_util::url::parse_url;
     use super::{Au, CSSFloat};
+    use url::Url;
     pub use CSSColor = cssparser::Color;
 
     #[deriving(Clone)]
@@ -169,6 +173,538 @@ pub mod specified {
             LengthOrPercentageOrNone::parse_internal(input, /* negative_ok = */ false)
         }
     }
+
+    /// An angle, in radians.
+    #[deriving(Clone, Eq)]
+    pub struct Angle(pub CSSFloat);
+    impl Angle {
+        #[inline]
+        pub fn radians(self) -> CSSFloat {
+            let Angle(radians) = self;
+            radians
+        }
+        pub fn parse(input: &ComponentValue) -> Option<Angle> {
+            match input {
+                &Dimension(ref value, ref unit) => {
+                    match unit.as_slice().to_ascii_lower().as_slice() {
+                        "deg" => Some(Angle(value.value * PI / 180.)),
+                        "grad" => Some(Angle(value.value * PI / 200.)),
+                        "rad" => Some(Angle(value.value)),
+                        "turn" => Some(Angle(value.value * PI * 2.)),
+                        _ => None,
+                    }
+                }
+                &Number(ref value) if value.value == 0. => Some(Angle(0.)),
+                _ => None,
+            }
+        }
+    }
+
+    /// A time, in seconds.
+    #[deriving(Clone, Eq)]
+    pub struct Time(pub CSSFloat);
+    impl Time {
+        #[inline]
+        pub fn seconds(self) -> CSSFloat {
+            let Time(seconds) = self;
+            seconds
+        }
+        pub fn parse(input: &ComponentValue) -> Option<Time> {
+            match input {
+                &Dimension(ref value, ref unit) => {
+                    match unit.as_slice().to_ascii_lower().as_slice() {
+                        "s" => Some(Time(value.value)),
+                        "ms" => Some(Time(value.value / 1000.)),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            }
+        }
+    }
+
+    /// Whether each step of a step timing function happens at the start or at the end of its
+    /// interval.
+    #[deriving(Clone, Eq)]
+    pub enum StepPosition {
+        StepStart,
+        StepEnd,
+    }
+
+    /// A timing function, which maps the progress of a transition to the progress of its value.
+    /// CSS Transitions § 2.3.
+    #[deriving(Clone, Eq)]
+    pub enum TimingFunction {
+        CubicBezierTimingFunction(CSSFloat, CSSFloat, CSSFloat, CSSFloat),
+        StepsTimingFunction(uint, StepPosition),
+    }
+    impl TimingFunction {
+        pub fn parse(input: &ComponentValue) -> Option<TimingFunction> {
+            match input {
+                &Ident(ref value) => {
+                    match value.as_slice().to_ascii_lower().as_slice() {
+                        "ease" => Some(CubicBezierTimingFunction(0.25, 0.1, 0.25, 1.)),
+                        "linear" => Some(CubicBezierTimingFunction(0., 0., 1., 1.)),
+                        "ease-in" => Some(CubicBezierTimingFunction(0.42, 0., 1., 1.)),
+                        "ease-out" => Some(CubicBezierTimingFunction(0., 0., 0.58, 1.)),
+                        "ease-in-out" => Some(CubicBezierTimingFunction(0.42, 0., 0.58, 1.)),
+                        "step-start" => Some(StepsTimingFunction(1, StepStart)),
+                        "step-end" => Some(StepsTimingFunction(1, StepEnd)),
+                        _ => None,
+                    }
+                }
+                &Function(ref name, ref arguments) => {
+                    let arguments = split_arguments(arguments.as_slice());
+                    if arguments.iter().any(|argument| argument.len() != 1) {
+                        return None
+                    }
+                    let arguments: Vec<&ComponentValue> =
+                        arguments.iter().map(|argument| *argument.get(0)).collect();
+                    match name.as_slice().to_ascii_lower().as_slice() {
+                        "cubic-bezier" => TimingFunction::parse_cubic_bezier(arguments.as_slice()),
+                        "steps" => TimingFunction::parse_steps(arguments.as_slice()),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            }
+        }
+
+        fn parse_cubic_bezier(arguments: &[&ComponentValue]) -> Option<TimingFunction> {
+            if arguments.len() != 4 {
+                return None
+            }
+            let mut values = [0., ..4];
+            for (i, argument) in arguments.iter().enumerate() {
+                match **argument {
+                    Number(ref value) => values[i] = value.value,
+                    _ => return None,
+                }
+            }
+            // The x coordinates must stay within [0, 1] so that the curve is a function of time.
+            if values[0] < 0. || values[0] > 1. || values[2] < 0. || values[2] > 1. {
+                return None
+            }
+            Some(CubicBezierTimingFunction(values[0], values[1], values[2], values[3]))
+        }
+
+        fn parse_steps(arguments: &[&ComponentValue]) -> Option<TimingFunction> {
+            let steps = match arguments.head() {
+                Some(&&Number(ref value)) => {
+                    match value.int_value {
+                        Some(steps) if steps > 0 => steps as uint,
+                        _ => return None,
+                    }
+                }
+                _ => return None,
+            };
+            match arguments.len() {
+                1 => Some(StepsTimingFunction(steps, StepEnd)),
+                2 => {
+                    match get_ident_lower(arguments[1]) {
+                        Some(ref position) if position.as_slice() == "start" => {
+                            Some(StepsTimingFunction(steps, StepStart))
+                        }
+                        Some(ref position) if position.as_slice() == "end" => {
+                            Some(StepsTimingFunction(steps, StepEnd))
+                        }
+                        _ => None,
+                    }
+                }
+                _ => None,
+            }
+        }
+
+        /// Returns the output progress for an input progress between 0 and 1.
+        pub fn solve(&self, progress: CSSFloat) -> CSSFloat {
+            match *self {
+                CubicBezierTimingFunction(x1, y1, x2, y2) => {
+                    // The curve runs from (0, 0) to (1, 1). Find the parameter at which its x
+                    // coordinate is the input progress by bisection, which always converges
+                    // because x is monotonic in the parameter.
+                    let bezier = |p1: CSSFloat, p2: CSSFloat, t: CSSFloat| {
+                        let u = 1. - t;
+                        3. * u * u * t * p1 + 3. * u * t * t * p2 + t * t * t
+                    };
+                    let (mut low, mut high) = (0., 1.);
+                    let mut t = progress;
+                    for _ in range(0, 32) {
+                        let x = bezier(x1, x2, t);
+                        if (x - progress).abs() < 1e-6 {
+                            break
+                        }
+                        if x < progress {
+                            low = t
+                        } else {
+                            high = t
+                        }
+                        t = (low + high) * 0.5
+                    }
+                    bezier(y1, y2, t)
+                }
+                StepsTimingFunction(steps, position) => {
+                    let steps = steps as CSSFloat;
+                    let step = match position {
+                        StepStart => (progress * steps).ceil(),
+                        StepEnd => (progress * steps).floor(),
+                    };
+                    (step / steps).max(0.).min(1.)
+                }
+            }
+        }
+    }
+
+    /// An image, as in the CSS `<image>` type: either a URL or a gradient.
+    #[deriving(Clone)]
+    pub enum Image {
+        UrlImage(Url),
+        LinearGradientImage(LinearGradient),
+        RadialGradientImage(RadialGradient),
+    }
+    impl Image {
+        pub fn from_component_value(component_value: &ComponentValue, base_url: &Url)
+                                    -> Option<Image> {
+            match component_value {
+                &URL(ref url) => Some(UrlImage(parse_url(url.as_slice(), Some(base_url.clone())))),
+                &Function(ref name, ref arguments) => {
+                    let arguments = split_arguments(arguments.as_slice());
+                    match name.as_slice().to_ascii_lower().as_slice() {
+                        "linear-gradient" => {
+                            LinearGradient::parse(arguments.as_slice(), false)
+                                .map(LinearGradientImage)
+                        }
+                        "repeating-linear-gradient" => {
+                            LinearGradient::parse(arguments.as_slice(), true)
+                                .map(LinearGradientImage)
+                        }
+                        "radial-gradient" => {
+                            RadialGradient::parse(arguments.as_slice(), false)
+                                .map(RadialGradientImage)
+                        }
+                        "repeating-radial-gradient" => {
+                            RadialGradient::parse(arguments.as_slice(), true)
+                                .map(RadialGradientImage)
+                        }
+                        _ => None,
+                    }
+                }
+                _ => None,
+            }
+        }
+    }
+
+    /// Splits the arguments of a function into its comma-separated groups, dropping whitespace.
+    pub fn split_arguments<'a>(arguments: &'a [ComponentValue]) -> Vec<Vec<&'a ComponentValue>> {
+        let mut groups = vec!(vec!());
+        for component_value in arguments.skip_whitespace() {
+            match component_value {
+                &Comma => groups.push(vec!()),
+                _ => groups.mut_last().unwrap().push(component_value),
+            }
+        }
+        groups
+    }
+
+    /// A color stop of a gradient. Stops without a position are spaced evenly at use time.
+    #[deriving(Clone)]
+    pub struct ColorStop {
+        pub color: CSSColor,
+        pub position: Option<LengthOrPercentage>,
+    }
+
+    /// Parses a list of at least two color stops.
+    fn parse_color_stops(groups: &[Vec<&ComponentValue>]) -> Option<Vec<ColorStop>> {
+        if groups.len() < 2 {
+            return None
+        }
+        let mut stops = Vec::with_capacity(groups.len());
+        for group in groups.iter() {
+            let color = match group.as_slice().head() {
+                Some(component_value) => match CSSColor::parse(*component_value) {
+                    Some(color) => color,
+                    None => return None,
+                },
+                None => return None,
+            };
+            let position = match group.len() {
+                1 => None,
+                2 => match LengthOrPercentage::parse(*group.get(1)) {
+                    Some(position) => Some(position),
+                    None => return None,
+                },
+                _ => return None,
+            };
+            stops.push(ColorStop {
+                color: color,
+                position: position,
+            })
+        }
+        Some(stops)
+    }
+
+    #[deriving(Clone, Eq)]
+    pub enum HorizontalDirection {
+        Left,
+        Right,
+    }
+
+    #[deriving(Clone, Eq)]
+    pub enum VerticalDirection {
+        Top,
+        Bottom,
+    }
+
+    /// The direction of the gradient line of a linear gradient. A corner depends on the size of
+    /// the box, so it cannot be turned into an angle until use time.
+    #[deriving(Clone, Eq)]
+    pub enum GradientDirection {
+        AngleDirection(Angle),
+        CornerDirection(HorizontalDirection, VerticalDirection),
+    }
+    impl GradientDirection {
+        /// Parses the words following `to` in a linear gradient.
+        fn parse_sides(input: &[&ComponentValue]) -> Option<GradientDirection> {
+            let (mut horizontal, mut vertical) = (None, None);
+            for component_value in input.iter() {
+                match get_ident_lower(*component_value) {
+                    Some(ref keyword) if horizontal.is_none() && keyword.as_slice() == "left" => {
+                        horizontal = Some(Left)
+                    }
+                    Some(ref keyword) if horizontal.is_none() && keyword.as_slice() == "right" => {
+                        horizontal = Some(Right)
+                    }
+                    Some(ref keyword) if vertical.is_none() && keyword.as_slice() == "top" => {
+                        vertical = Some(Top)
+                    }
+                    Some(ref keyword) if vertical.is_none() && keyword.as_slice() == "bottom" => {
+                        vertical = Some(Bottom)
+                    }
+                    _ => return None,
+                }
+            }
+            match (horizontal, vertical) {
+                (Some(horizontal), Some(vertical)) => Some(CornerDirection(horizontal, vertical)),
+                (None, Some(Top)) => Some(AngleDirection(Angle(0.))),
+                (Some(Right), None) => Some(AngleDirection(Angle(PI * 0.5))),
+                (None, Some(Bottom)) => Some(AngleDirection(Angle(PI))),
+                (Some(Left), None) => Some(AngleDirection(Angle(PI * 1.5))),
+                (None, None) => None,
+            }
+        }
+    }
+
+    #[deriving(Clone)]
+    pub struct LinearGradient {
+        pub direction: GradientDirection,
+        pub stops: Vec<ColorStop>,
+        pub repeating: bool,
+    }
+    impl LinearGradient {
+        /// Parses the comma-separated arguments of `linear-gradient()`.
+        fn parse(groups: &[Vec<&ComponentValue>], repeating: bool) -> Option<LinearGradient> {
+            let first = match groups.head() {
+                Some(first) => first.as_slice(),
+                None => return None,
+            };
+            let (direction, stops) = match first.head().and_then(|v| get_ident_lower(*v)) {
+                Some(ref keyword) if keyword.as_slice() == "to" => {
+                    match GradientDirection::parse_sides(first.tail()) {
+                        Some(direction) => (direction, groups.tail()),
+                        None => return None,
+                    }
+                }
+                _ => {
+                    match first.head().and_then(|v| Angle::parse(*v)) {
+                        Some(angle) if first.len() == 1 => (AngleDirection(angle), groups.tail()),
+                        _ => (AngleDirection(Angle(PI)), groups),
+                    }
+                }
+            };
+            parse_color_stops(stops).map(|stops| {
+                LinearGradient {
+                    direction: direction,
+                    stops: stops,
+                    repeating: repeating,
+                }
+            })
+        }
+    }
+
+    #[deriving(Clone, Eq)]
+    pub enum EndingShape {
+        Circle,
+        Ellipse,
+    }
+
+    /// The size of the ending shape of a radial gradient.
+    #[deriving(Clone)]
+    pub enum RadialGradientExtent {
+        ClosestSide,
+        FarthestSide,
+        ClosestCorner,
+        FarthestCorner,
+        ExplicitCircleExtent(Length),
+        ExplicitEllipseExtent(LengthOrPercentage, LengthOrPercentage),
+    }
+
+    #[deriving(Clone)]
+    pub struct RadialGradient {
+        pub shape: EndingShape,
+        pub extent: RadialGradientExtent,
+        pub center_x: LengthOrPercentage,
+        pub center_y: LengthOrPercentage,
+        pub stops: Vec<ColorStop>,
+        pub repeating: bool,
+    }
+    impl RadialGradient {
+        /// Parses the comma-separated arguments of `radial-gradient()`.
+        fn parse(groups: &[Vec<&ComponentValue>], repeating: bool) -> Option<RadialGradient> {
+            let first = match groups.head() {
+                Some(first) => first.as_slice(),
+                None => return None,
+            };
+            match RadialGradient::parse_shape_and_position(first) {
+                Some((shape, extent, center_x, center_y)) => {
+                    parse_color_stops(groups.tail()).map(|stops| {
+                        RadialGradient {
+                            shape: shape,
+                            extent: extent,
+                            center_x: center_x,
+                            center_y: center_y,
+                            stops: stops,
+                            repeating: repeating,
+                        }
+                    })
+                }
+                None => {
+                    parse_color_stops(groups).map(|stops| {
+                        RadialGradient {
+                            shape: Ellipse,
+                            extent: FarthestCorner,
+                            center_x: LP_Percentage(0.5),
+                            center_y: LP_Percentage(0.5),
+                            stops: stops,
+                            repeating: repeating,
+                        }
+                    })
+                }
+            }
+        }
+
+        /// Parses `[<ending-shape> || <size>]? [at <position>]?`.
+        fn parse_shape_and_position(input: &[&ComponentValue])
+                                    -> Option<(EndingShape,
+                                               RadialGradientExtent,
+                                               LengthOrPercentage,
+                                               LengthOrPercentage)> {
+            let at = input.iter().position(|v| {
+                get_ident_lower(*v).map_or(false, |keyword| keyword.as_slice() == "at")
+            });
+            let (shape_and_extent, position) = match at {
+                Some(index) => (input.slice_to(index), Some(input.slice_from(index + 1))),
+                None => (input, None),
+            };
+            if shape_and_extent.is_empty() && position.is_none() {
+                return None
+            }
+
+            let (mut shape, mut keyword_extent, mut lengths) = (None, None, vec!());
+            for component_value in shape_and_extent.iter() {
+                match get_ident_lower(*component_value) {
+                    Some(keyword) => {
+                        match keyword.as_slice() {
+                            "circle" if shape.is_none() => shape = Some(Circle),
+                            "ellipse" if shape.is_none() => shape = Some(Ellipse),
+                            "closest-side" if keyword_extent.is_none() => {
+                                keyword_extent = Some(ClosestSide)
+                            }
+                            "farthest-side" if keyword_extent.is_none() => {
+                                keyword_extent = Some(FarthestSide)
+                            }
+                            "closest-corner" if keyword_extent.is_none() => {
+                                keyword_extent = Some(ClosestCorner)
+                            }
+                            "farthest-corner" if keyword_extent.is_none() => {
+                                keyword_extent = Some(FarthestCorner)
+                            }
+                            _ => return None,
+                        }
+                    }
+                    None => {
+                        match LengthOrPercentage::parse_non_negative(*component_value) {
+                            Some(length) => lengths.push(length),
+                            None => return None,
+                        }
+                    }
+                }
+            }
+
+            let (shape, extent) = match (shape, keyword_extent, lengths.len()) {
+                (shape, Some(extent), 0) => (shape.unwrap_or(Ellipse), extent),
+                (None, None, 0) => (Ellipse, FarthestCorner),
+                (Some(shape), None, 0) => (shape, FarthestCorner),
+                (None, None, 1) | (Some(Circle), None, 1) => {
+                    match *lengths.get(0) {
+                        LP_Length(length) => (Circle, ExplicitCircleExtent(length)),
+                        LP_Percentage(_) => return None,
+                    }
+                }
+                (None, None, 2) | (Some(Ellipse), None, 2) => {
+                    (Ellipse, ExplicitEllipseExtent(lengths.get(0).clone(),
+                                                    lengths.get(1).clone()))
+                }
+                _ => return None,
+            };
+
+            let (center_x, center_y) = match position {
+                Some(position) => match parse_position(position) {
+                    Some(center) => center,
+                    None => return None,
+                },
+                None => (LP_Percentage(0.5), LP_Percentage(0.5)),
+            };
+            Some((shape, extent, center_x, center_y))
+        }
+    }
+
+    /// Parses a position of one or two keywords or lengths, as in `background-position`.
+    pub fn parse_position(input: &[&ComponentValue])
+                      -> Option<(LengthOrPercentage, LengthOrPercentage)> {
+        fn parse_component(input: &ComponentValue) -> Option<(LengthOrPercentage, Option<bool>)> {
+            // The flag is `Some(true)` for vertical keywords and `Some(false)` for horizontal
+            // ones.
+            match get_ident_lower(input) {
+                Some(keyword) => match keyword.as_slice() {
+                    "left" => Some((LP_Percentage(0.), Some(false))),
+                    "right" => Some((LP_Percentage(1.), Some(false))),
+                    "top" => Some((LP_Percentage(0.), Some(true))),
+                    "bottom" => Some((LP_Percentage(1.), Some(true))),
+                    "center" => Some((LP_Percentage(0.5), None)),
+                    _ => None,
+                },
+                None => LengthOrPercentage::parse(input).map(|length| (length, None)),
+            }
+        }
+
+        match input.len() {
+            1 => match parse_component(input[0]) {
+                Some((value, Some(true))) => Some((LP_Percentage(0.5), value)),
+                Some((value, _)) => Some((value, LP_Percentage(0.5))),
+                None => None,
+            },
+            2 => match (parse_component(input[0]), parse_component(input[1])) {
+                (Some((first, first_vertical)), Some((second, second_vertical))) => {
+                    if first_vertical == Some(true) || second_vertical == Some(false) {
+                        Some((second, first))
+                    } else {
+                        Some((first, second))
+                    }
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
 }
 
 pub mod computed {
@@ -178,6 +714,12 @@ pub mod computed {
     use super::super::longhands;
     pub use servo_util::geometry::Au;
     pub use std::fmt;
+    pub use super::specified::{Angle, GradientDirection, AngleDirection, CornerDirection};
+    pub use super::specified::{HorizontalDirection, Left, Right, VerticalDirection, Top, Bottom};
+    pub use super::specified::{EndingShape, Circle, Ellipse};
+    pub use super::specified::{Time, TimingFunction, CubicBezierTimingFunction};
+    pub use super::specified::{StepsTimingFunction, StepPosition, StepStart, StepEnd};
+    use url::Url;
 
     pub struct Context {
         pub inherited_font_weight: longhands::font_weight::computed_value::T,
@@ -185,6 +727,7 @@ pub mod computed {
         pub inherited_minimum_line_height: longhands::_servo_minimum_line_height::T,
         pub inherited_text_decorations_in_effect: longhands::_servo_text_decorations_in_effect::T,
         pub inherited_height: longhands::height::T,
+        pub inherited_display: longhands::display::computed_value::T,
         pub color: longhands::color::computed_value::T,
         pub text_decoration: longhands::text_decoration::computed_value::T,
         pub font_size: longhands::font_size::computed_value::T,
@@ -277,4 +820,224 @@ pub mod computed {
             specified::LPN_None => LPN_None,
         }
     }
+
+    #[deriving(Eq, Clone)]
+    pub enum Image {
+        UrlImage(Url),
+        LinearGradientImage(LinearGradient),
+        RadialGradientImage(RadialGradient),
+    }
+    pub fn compute_Image(value: specified::Image, context: &Context) -> Image {
+        match value {
+            specified::UrlImage(url) => UrlImage(url),
+            specified::LinearGradientImage(gradient) => {
+                LinearGradientImage(LinearGradient {
+                    direction: gradient.direction,
+                    stops: compute_ColorStops(gradient.stops, context),
+                    repeating: gradient.repeating,
+                })
+            }
+            specified::RadialGradientImage(gradient) => {
+                let extent = match gradient.extent {
+                    specified::ClosestSide => ClosestSide,
+                    specified::FarthestSide => FarthestSide,
+                    specified::ClosestCorner => ClosestCorner,
+                    specified::FarthestCorner => FarthestCorner,
+                    specified::ExplicitCircleExtent(radius) => {
+                        ExplicitCircleExtent(compute_Au(radius, context))
+                    }
+                    specified::ExplicitEllipseExtent(width, height) => {
+                        ExplicitEllipseExtent(compute_LengthOrPercentage(width, context),
+                                              compute_LengthOrPercentage(height, context))
+                    }
+                };
+                RadialGradientImage(RadialGradient {
+                    shape: gradient.shape,
+                    extent: extent,
+                    center_x: compute_LengthOrPercentage(gradient.center_x, context),
+                    center_y: compute_LengthOrPercentage(gradient.center_y, context),
+                    stops: compute_ColorStops(gradient.stops, context),
+                    repeating: gradient.repeating,
+                })
+            }
+        }
+    }
+
+    /// A color stop of a gradient. The color is resolved against `color` at use time.
+    #[deriving(Eq, Clone)]
+    pub struct ColorStop {
+        pub color: CSSColor,
+        pub position: Option<LengthOrPercentage>,
+    }
+    fn compute_ColorStops(stops: Vec<specified::ColorStop>, context: &Context) -> Vec<ColorStop> {
+        stops.move_iter().map(|stop| {
+            ColorStop {
+                color: stop.color,
+                position: stop.position.map(|position| {
+                    compute_LengthOrPercentage(position, context)
+                }),
+            }
+        }).collect()
+    }
+
+    #[deriving(Eq, Clone)]
+    pub struct LinearGradient {
+        pub direction: GradientDirection,
+        pub stops: Vec<ColorStop>,
+        pub repeating: bool,
+    }
+
+    #[deriving(Eq, Clone)]
+    pub enum RadialGradientExtent {
+        ClosestSide,
+        FarthestSide,
+        ClosestCorner,
+        FarthestCorner,
+        ExplicitCircleExtent(Au),
+        ExplicitEllipseExtent(LengthOrPercentage, LengthOrPercentage),
+    }
+
+    #[deriving(Eq, Clone)]
+    pub struct RadialGradient {
+        pub shape: EndingShape,
+        pub extent: RadialGradientExtent,
+        pub center_x: LengthOrPercentage,
+        pub center_y: LengthOrPercentage,
+        pub stops: Vec<ColorStop>,
+        pub repeating: bool,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cssparser;
+    use std::f64::consts::PI;
+    use super::specified::*;
+    use url::Url;
+
+    fn parse_image(input: &str) -> Option<Image> {
+        let base_url = from_str::<Url>("http://example.com/").unwrap();
+        let component_values: Vec<_> = cssparser::tokenize(input).map(|(v, _)| v).collect();
+        assert!(component_values.len() == 1);
+        Image::from_component_value(component_values.get(0), &base_url)
+    }
+
+    #[test]
+    fn test_linear_gradient_directions() {
+        match parse_image("linear-gradient(red, blue)") {
+            Some(LinearGradientImage(gradient)) => {
+                assert!(gradient.direction == AngleDirection(Angle(PI)));
+                assert!(gradient.stops.len() == 2);
+                assert!(!gradient.repeating);
+            }
+            _ => fail!("expected a linear gradient"),
+        }
+        match parse_image("linear-gradient(90deg, red, blue 50%, green)") {
+            Some(LinearGradientImage(gradient)) => {
+                assert!(gradient.direction == AngleDirection(Angle(PI * 0.5)));
+                assert!(gradient.stops.len() == 3);
+                assert!(gradient.stops.get(1).position.is_some());
+            }
+            _ => fail!("expected a linear gradient"),
+        }
+        match parse_image("repeating-linear-gradient(to bottom left, red, blue 10px)") {
+            Some(LinearGradientImage(gradient)) => {
+                assert!(gradient.direction == CornerDirection(Left, Bottom));
+                assert!(gradient.repeating);
+            }
+            _ => fail!("expected a linear gradient"),
+        }
+        assert!(parse_image("linear-gradient(to middle, red, blue)").is_none());
+        assert!(parse_image("linear-gradient(red)").is_none());
+    }
+
+    #[test]
+    fn test_radial_gradient_shapes() {
+        match parse_image("radial-gradient(circle closest-side at left top, red, blue)") {
+            Some(RadialGradientImage(gradient)) => {
+                assert!(gradient.shape == Circle);
+                match gradient.extent {
+                    ClosestSide => {}
+                    _ => fail!("expected closest-side"),
+                }
+            }
+            _ => fail!("expected a radial gradient"),
+        }
+        match parse_image("radial-gradient(20px 30%, red, blue)") {
+            Some(RadialGradientImage(gradient)) => {
+                assert!(gradient.shape == Ellipse);
+                match gradient.extent {
+                    ExplicitEllipseExtent(..) => {}
+                    _ => fail!("expected an explicit ellipse"),
+                }
+            }
+            _ => fail!("expected a radial gradient"),
+        }
+        assert!(parse_image("radial-gradient(circle 20%, red, blue)").is_none());
+    }
+
+    fn parse_timing_function(input: &str) -> Option<TimingFunction> {
+        let component_values: Vec<_> = cssparser::tokenize(input).map(|(v, _)| v).collect();
+        assert!(component_values.len() == 1);
+        TimingFunction::parse(component_values.get(0))
+    }
+
+    #[test]
+    fn test_timing_functions() {
+        let linear = parse_timing_function("linear").unwrap();
+        assert!((linear.solve(0.3) - 0.3).abs() < 1e-4);
+        let ease = parse_timing_function("ease").unwrap();
+        assert!(ease.solve(0.) == 0.);
+        assert!((ease.solve(1.) - 1.).abs() < 1e-4);
+        assert!(ease.solve(0.5) > 0.5);
+
+        assert!(parse_timing_function("steps(4)") == Some(StepsTimingFunction(4, StepEnd)));
+        let steps = parse_timing_function("steps(4, start)").unwrap();
+        assert!(steps.solve(0.1) == 0.25);
+        assert!(parse_timing_function("step-end").unwrap().solve(0.9) == 0.);
+
+        assert!(parse_timing_function("cubic-bezier(0.1, 2, 0.9, -1)").is_some());
+        assert!(parse_timing_function("cubic-bezier(1.5, 0, 0, 1)").is_none());
+        assert!(parse_timing_function("steps(0)").is_none());
+        assert!(parse_timing_function("bounce").is_none());
+    }
+
+    #[test]
+    fn test_timing_function_reference_values() {
+        // The outputs of the keyword curves at 25%, 50% and 75% of the input, computed to four
+        // decimal places from the cubic Bézier definitions.
+        let expected = [
+            ("ease", [0.4085, 0.8024, 0.9605]),
+            ("ease-in", [0.0935, 0.3154, 0.6219]),
+            ("ease-out", [0.3781, 0.6846, 0.9065]),
+            ("ease-in-out", [0.1292, 0.5, 0.8708]),
+            ("linear", [0.25, 0.5, 0.75]),
+        ];
+        for &(name, outputs) in expected.iter() {
+            let timing_function = parse_timing_function(name).unwrap();
+            for (i, &progress) in [0.25, 0.5, 0.75].iter().enumerate() {
+                let output = timing_function.solve(progress);
+                assert!((output - outputs[i]).abs() < 1e-3,
+                        "{} at {}: {} instead of {}", name, progress, output, outputs[i]);
+            }
+            assert!(timing_function.solve(0.).abs() < 1e-4);
+            assert!((timing_function.solve(1.) - 1.).abs() < 1e-4);
+        }
+
+        // A curve whose y coordinates leave [0, 1] overshoots.
+        let back = parse_timing_function("cubic-bezier(0.5, -1, 0.5, 2)").unwrap();
+        assert!(back.solve(0.1) < 0.);
+        assert!(back.solve(0.9) > 1.);
+
+        let steps_end = parse_timing_function("steps(4)").unwrap();
+        let steps_start = parse_timing_function("steps(4, start)").unwrap();
+        let inputs = [0., 0.1, 0.25, 0.4, 0.5, 0.99, 1.];
+        let end_outputs = [0., 0., 0.25, 0.25, 0.5, 0.75, 1.];
+        let start_outputs = [0., 0.25, 0.25, 0.5, 0.5, 1., 1.];
+        for (i, &progress) in inputs.iter().enumerate() {
+            assert!(steps_end.solve(progress) == end_outputs[i]);
+            assert!(steps_start.solve(progress) == start_outputs[i]);
+        }
+        assert!(parse_timing_function("step-start").unwrap().solve(0.1) == 1.);
+    }
 }
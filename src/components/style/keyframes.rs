@@ -0,0 +1,114 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::ascii::StrAsciiExt;
+use cssparser::parse_rule_list;
+use cssparser::ast::*;
+use sync::Arc;
+
+use errors::{ErrorLoggerIterator, log_css_error};
+use stylesheets::{CSSRule, CSSKeyframesRule};
+use parsing_utils::{one_component_value, get_ident_lower};
+use properties::{CSSFloat, PropertyDeclaration, parse_property_declaration_list};
+use properties::specified::split_arguments;
+use url::Url;
+
+
+/// An `@keyframes` rule. See CSS Animations § 4.
+#[deriving(Clone)]
+pub struct KeyframesRule {
+    pub name: ~str,
+    /// The keyframes of the rule, sorted by offset. Keyframes with the same offset are kept in
+    /// source order, so that the later ones override the earlier ones.
+    pub keyframes: Vec<Keyframe>,
+}
+
+
+#[deriving(Clone)]
+pub struct Keyframe {
+    /// The offset of the keyframe within an iteration, between 0 (`from`) and 1 (`to`).
+    pub offset: CSSFloat,
+    /// The declarations of the keyframe. `!important` declarations are ignored in keyframes.
+    pub declarations: Arc<Vec<PropertyDeclaration>>,
+}
+
+
+pub fn parse_keyframes_rule(rule: AtRule, parent_rules: &mut Vec<CSSRule>, base_url: &Url) {
+    let name = match one_component_value(rule.prelude.as_slice()) {
+        Some(&Ident(ref value)) => match value.as_slice().to_ascii_lower().as_slice() {
+            "none" | "initial" | "inherit" => None,
+            _ => Some(value.clone()),
+        },
+        Some(&String(ref value)) => Some(value.clone()),
+        _ => None,
+    };
+    let (name, block) = match (name, rule.block) {
+        (Some(name), Some(block)) => (name, block),
+        _ => {
+            log_css_error(rule.location, "Invalid @keyframes rule");
+            return
+        }
+    };
+    let mut keyframes = vec!();
+    for rule in ErrorLoggerIterator(parse_rule_list(block.move_iter())) {
+        match rule {
+            QualifiedRule(rule) => {
+                let QualifiedRule{location: location, prelude: prelude, block: block} = rule;
+                match parse_keyframe_selector(prelude.as_slice()) {
+                    Some(offsets) => {
+                        let declarations =
+                            parse_property_declaration_list(block.move_iter(), base_url).normal;
+                        for &offset in offsets.iter() {
+                            keyframes.push(Keyframe {
+                                offset: offset,
+                                declarations: declarations.clone(),
+                            })
+                        }
+                    }
+                    None => log_css_error(location, "Invalid keyframe selector"),
+                }
+            }
+            AtRule(rule) => log_css_error(
+                rule.location, format!("Unsupported at-rule in @keyframes: @{:s}", rule.name)),
+        }
+    }
+    // A stable sort keeps keyframes with the same offset in source order.
+    let mut keyframes: Vec<(uint, Keyframe)> = keyframes.move_iter().enumerate().collect();
+    keyframes.sort_by(|&(a_index, ref a), &(b_index, ref b)| {
+        if a.offset < b.offset {
+            Less
+        } else if a.offset > b.offset {
+            Greater
+        } else {
+            a_index.cmp(&b_index)
+        }
+    });
+    parent_rules.push(CSSKeyframesRule(KeyframesRule {
+        name: name,
+        keyframes: keyframes.move_iter().map(|(_, keyframe)| keyframe).collect(),
+    }))
+}
+
+
+/// Parses a comma-separated list of `from`, `to` and percentages into offsets between 0 and 1.
+fn parse_keyframe_selector(input: &[ComponentValue]) -> Option<Vec<CSSFloat>> {
+    let mut offsets = vec!();
+    for selector in split_arguments(input).iter() {
+        if selector.len() != 1 {
+            return None
+        }
+        let offset = match *selector.get(0) {
+            &Percentage(ref value) if value.value >= 0. && value.value <= 100. => {
+                value.value / 100.
+            }
+            component_value => match get_ident_lower(component_value) {
+                Some(ref keyword) if keyword.as_slice() == "from" => 0.,
+                Some(ref keyword) if keyword.as_slice() == "to" => 1.,
+                _ => return None,
+            },
+        };
+        offsets.push(offset)
+    }
+    Some(offsets)
+}
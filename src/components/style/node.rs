@@ -17,6 +17,9 @@ pub trait TNode<E:TElement> : Clone {
     fn is_element(&self) -> bool;
     fn as_element(&self) -> E;
     fn match_attr(&self, attr: &AttrSelector, test: |&str| -> bool) -> bool;
+    /// Returns true if this node has no element children and no non-empty text children, per
+    /// the `:empty` pseudo-class.
+    fn is_empty(&self) -> bool;
 }
 
 pub trait TElement {
@@ -25,5 +28,11 @@ pub trait TElement {
     fn get_local_name<'a>(&'a self) -> &'a str;
     fn get_namespace<'a>(&'a self) -> &'a Namespace;
     fn get_hover_state(&self) -> bool;
+    fn get_focus_state(&self) -> bool;
+    fn get_active_state(&self) -> bool;
+    fn get_checked_state(&self) -> bool;
+    fn get_disabled_state(&self) -> bool;
+    fn get_enabled_state(&self) -> bool;
+    fn get_target_state(&self) -> bool;
 }
 
@@ -8,6 +8,7 @@ pub use std::ascii::StrAsciiExt;
 use serialize::{Encodable, Encoder};
 
 pub use servo_util::url::parse_url;
+use servo_util::geometry;
 use sync::Arc;
 pub use url::Url;
 
@@ -34,14 +35,15 @@ def to_rust_ident(name):
         name += "_"
     return name
 
+def to_camel_case(ident):
+    camel_case, _ = re.subn("_([a-z])", lambda m: m.group(1).upper(), ident.strip("_").capitalize())
+    return camel_case
+
 class Longhand(object):
     def __init__(self, name, derived_from=None):
         self.name = name
         self.ident = to_rust_ident(name)
-        self.camel_case, _ = re.subn(
-            "_([a-z])",
-            lambda m: m.group(1).upper(),
-            self.ident.strip("_").capitalize())
+        self.camel_case = to_camel_case(self.ident)
         self.style_struct = THIS_STYLE_STRUCT
         if derived_from is None:
             self.derived_from = None
@@ -52,6 +54,7 @@ class Shorthand(object):
     def __init__(self, name, sub_properties):
         self.name = name
         self.ident = to_rust_ident(name)
+        self.camel_case = to_camel_case(self.ident)
         self.sub_properties = [LONGHANDS_BY_NAME[s] for s in sub_properties]
 
 class StyleStruct(object):
@@ -76,6 +79,18 @@ def new_style_struct(name, is_inherited):
     THIS_STYLE_STRUCT = style_struct
     return ""
 
+def cssom_properties():
+    """Returns the properties that `CSSStyleDeclaration` has camel-cased attributes for, as
+    (name, camel case) pairs sorted by name. Only call this after rendering the template, which
+    is what fills in the properties. `float` is a keyword in some languages, so its attribute is
+    `cssFloat`."""
+    properties = [(property.name, property.camel_case)
+                  for property in LONGHANDS + SHORTHANDS
+                  if getattr(property, "derived_from", None) is None]
+    properties = [(name, "CssFloat" if name == "float" else camel_case)
+                  for (name, camel_case) in properties]
+    return sorted(properties)
+
 def switch_to_style_struct(name):
     global THIS_STYLE_STRUCT
 
@@ -160,6 +175,15 @@ pub mod longhands {
                         ${to_rust_ident(value)},
                     % endfor
                 }
+                impl super::super::ToComputedCss for T {
+                    fn to_computed_css(&self, _: &super::super::ComputedValues) -> ~str {
+                        match *self {
+                            % for value in values.split():
+                                ${to_rust_ident(value)} => "${value}".to_owned(),
+                            % endfor
+                        }
+                    }
+                }
             }
             pub type SpecifiedValue = computed_value::T;
             #[inline] pub fn get_initial_value() -> computed_value::T {
@@ -187,6 +211,64 @@ pub mod longhands {
         </%self:single_keyword_computed>
     </%def>
 
+    <%def name="keyword_list(name, values)">
+        <%self:longhand name="${name}">
+            pub mod computed_value {
+                #[allow(non_camel_case_types)]
+                #[deriving(Eq, Clone)]
+                pub enum SingleComputedValue {
+                    % for value in values.split():
+                        ${to_rust_ident(value)},
+                    % endfor
+                }
+                impl SingleComputedValue {
+                    pub fn from_keyword(keyword: &str) -> Option<SingleComputedValue> {
+                        match keyword {
+                            % for value in values.split():
+                                "${value}" => Some(${to_rust_ident(value)}),
+                            % endfor
+                            _ => None,
+                        }
+                    }
+                }
+                pub type T = Vec<SingleComputedValue>;
+                impl super::super::ToComputedCss for T {
+                    fn to_computed_css(&self, _: &super::super::ComputedValues) -> ~str {
+                        let keywords: Vec<~str> = self.iter().map(|value| {
+                            match *value {
+                                % for value in values.split():
+                                    ${to_rust_ident(value)} => "${value}".to_owned(),
+                                % endfor
+                            }
+                        }).collect();
+                        keywords.as_slice().connect(", ")
+                    }
+                }
+            }
+            pub type SpecifiedValue = computed_value::T;
+            pub use to_computed_value = super::computed_as_specified;
+            #[inline] pub fn get_initial_value() -> computed_value::T {
+                vec!(${to_rust_ident(values.split()[0])})
+            }
+            /// Parses a comma-separated list of keywords.
+            pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Option<SpecifiedValue> {
+                let mut keywords = vec!();
+                for group in specified::split_arguments(input).iter() {
+                    if group.len() != 1 {
+                        return None
+                    }
+                    match get_ident_lower(*group.get(0)).and_then(|keyword| {
+                        SingleComputedValue::from_keyword(keyword.as_slice())
+                    }) {
+                        Some(keyword) => keywords.push(keyword),
+                        None => return None,
+                    }
+                }
+                Some(keywords)
+            }
+        </%self:longhand>
+    </%def>
+
     <%def name="predefined_type(name, type, initial_value, parse_method='parse')">
         <%self:single_component_value name="${name}">
             pub use to_computed_value = super::super::common_types::computed::compute_${type};
@@ -277,6 +359,69 @@ pub mod longhands {
         </%self:longhand>
     % endfor
 
+    <%self:longhand name="border-top-left-radius">
+        pub mod computed_value {
+            use super::super::super::common_types::computed::LengthOrPercentage;
+
+            /// The horizontal and vertical radii of the corner.
+            #[deriving(Eq, Clone)]
+            pub struct T {
+                pub width: LengthOrPercentage,
+                pub height: LengthOrPercentage,
+            }
+        }
+
+        #[deriving(Clone)]
+        pub struct SpecifiedValue {
+            pub width: specified::LengthOrPercentage,
+            pub height: specified::LengthOrPercentage,
+        }
+
+        #[inline]
+        pub fn to_computed_value(value: SpecifiedValue, context: &computed::Context)
+                                 -> computed_value::T {
+            computed_value::T {
+                width: computed::compute_LengthOrPercentage(value.width, context),
+                height: computed::compute_LengthOrPercentage(value.height, context),
+            }
+        }
+
+        #[inline]
+        pub fn get_initial_value() -> computed_value::T {
+            computed_value::T {
+                width: computed::LP_Length(Au(0)),
+                height: computed::LP_Length(Au(0)),
+            }
+        }
+
+        /// One value sets both radii; two values set the horizontal and vertical radii.
+        pub fn parse(input: &[ComponentValue], _: &Url) -> Option<SpecifiedValue> {
+            let mut iter = input.skip_whitespace()
+                                .map(|c| specified::LengthOrPercentage::parse_non_negative(c));
+            let width = iter.next().unwrap_or(None);
+            let height = iter.next().unwrap_or(width);
+            match (width, height) {
+                (Some(width), Some(height)) if iter.next().is_none() => {
+                    Some(SpecifiedValue {
+                        width: width,
+                        height: height,
+                    })
+                }
+                _ => None,
+            }
+        }
+    </%self:longhand>
+
+    % for corner in ["top-right", "bottom-right", "bottom-left"]:
+        <%self:longhand name="border-${corner}-radius", no_super="True">
+            pub use super::border_top_left_radius::{get_initial_value, parse, to_computed_value};
+            pub type SpecifiedValue = super::border_top_left_radius::SpecifiedValue;
+            pub mod computed_value {
+                pub type T = super::super::border_top_left_radius::computed_value::T;
+            }
+        </%self:longhand>
+    % endfor
+
     ${new_style_struct("PositionOffsets", is_inherited=False)}
 
     % for side in ["top", "right", "bottom", "left"]:
@@ -293,7 +438,7 @@ pub mod longhands {
             values="inline block inline-block
             table inline-table table-row-group table-header-group table-footer-group
             table-row table-column-group table-column table-cell table-caption
-            list-item
+            list-item flex inline-flex
             none">
         #[inline]
         pub fn to_computed_value(value: SpecifiedValue, context: &computed::Context)
@@ -301,9 +446,17 @@ pub mod longhands {
 //            if context.is_root_element && value == list_item {
 //                return block
 //            }
-            if context.positioned || context.floated || context.is_root_element {
+            // The in-flow children of flex containers are flex items, which are blockified too
+            // (CSS Flexible Box Layout section 4).
+            let parent_is_flex_container = match context.inherited_display {
+                flex | inline_flex => true,
+                _ => false,
+            };
+            if context.positioned || context.floated || context.is_root_element ||
+                    parent_is_flex_container {
                 match value {
                     inline_table => table,
+                    inline_flex => flex,
                     inline | inline_block
                     | table_row_group | table_column | table_column_group
                     | table_header_group | table_footer_group | table_row
@@ -321,6 +474,44 @@ pub mod longhands {
     ${single_keyword("float", "none left right")}
     ${single_keyword("clear", "none left right both")}
 
+    <%self:single_component_value name="z-index">
+        pub use to_computed_value = super::computed_as_specified;
+        pub type SpecifiedValue = computed_value::T;
+        pub mod computed_value {
+            #[deriving(Eq, Clone)]
+            pub enum T {
+                Auto,
+                Number(i32),
+            }
+            impl T {
+                /// Returns the stacking level, treating `auto` as zero.
+                pub fn number_or_zero(self) -> i32 {
+                    match self {
+                        Auto => 0,
+                        Number(value) => value,
+                    }
+                }
+            }
+            impl super::super::ToComputedCss for T {
+                fn to_computed_css(&self, _: &super::super::ComputedValues) -> ~str {
+                    match *self {
+                        Auto => "auto".to_owned(),
+                        Number(value) => format!("{}", value),
+                    }
+                }
+            }
+        }
+        #[inline] pub fn get_initial_value() -> computed_value::T { Auto }
+        pub fn from_component_value(input: &ComponentValue, _base_url: &Url)
+                                    -> Option<SpecifiedValue> {
+            match input {
+                &Ident(ref value) if value.to_owned().eq_ignore_ascii_case("auto") => Some(Auto),
+                &ast::Number(ref value) => value.int_value.map(|value| Number(value as i32)),
+                _ => None,
+            }
+        }
+    </%self:single_component_value>
+
     // CSS 2.1, Section 10 - Visual formatting model details
 
     ${predefined_type("width", "LengthOrPercentageOrAuto",
@@ -391,13 +582,22 @@ pub mod longhands {
             }
         }
         pub mod computed_value {
-            use super::super::{Au, CSSFloat};
+            use super::super::{Au, CSSFloat, ComputedValues, ToComputedCss};
             #[deriving(Eq, Clone)]
             pub enum T {
                 Normal,
                 Length(Au),
                 Number(CSSFloat),
             }
+            impl ToComputedCss for T {
+                fn to_computed_css(&self, style: &ComputedValues) -> ~str {
+                    match *self {
+                        Normal => "normal".to_owned(),
+                        Length(length) => length.to_computed_css(style),
+                        Number(number) => format!("{}", number),
+                    }
+                }
+            }
         }
         #[inline]
         pub fn get_initial_value() -> computed_value::T { Normal }
@@ -478,7 +678,8 @@ pub mod longhands {
             }
         }
         pub mod computed_value {
-            use super::super::{Au, CSSFloat};
+            use super::super::{Au, CSSFloat, ComputedValues, ToComputedCss};
+            use super::super::computed::LP_Percentage;
             #[allow(non_camel_case_types)]
             #[deriving(Eq, Clone)]
             pub enum T {
@@ -488,6 +689,17 @@ pub mod longhands {
                 Length(Au),
                 Percentage(CSSFloat),
             }
+            impl ToComputedCss for T {
+                fn to_computed_css(&self, style: &ComputedValues) -> ~str {
+                    match *self {
+                        % for keyword in vertical_align_keywords:
+                            ${to_rust_ident(keyword)} => "${keyword}".to_owned(),
+                        % endfor
+                        Length(length) => length.to_computed_css(style),
+                        Percentage(percentage) => LP_Percentage(percentage).to_computed_css(style),
+                    }
+                }
+            }
         }
         #[inline]
         pub fn get_initial_value() -> computed_value::T { baseline }
@@ -509,7 +721,7 @@ pub mod longhands {
 
 
     // CSS 2.1, Section 11 - Visual effects
-    ${single_keyword("overflow", "visible hidden")} // TODO: scroll auto
+    ${single_keyword("overflow", "visible hidden scroll auto")}
 
     ${switch_to_style_struct("InheritedBox")}
 
@@ -523,9 +735,17 @@ pub mod longhands {
     <%self:longhand name="content">
             pub use to_computed_value = super::computed_as_specified;
             pub mod computed_value {
+                use super::super::list_style_type;
+                use super::super::ToComputedCss;
+
                 #[deriving(Eq, Clone)]
                 pub enum Content {
                     StringContent(~str),
+                    /// `counter(name, style)`: the innermost value of the named counter.
+                    CounterContent(~str, list_style_type::T),
+                    /// `counters(name, separator, style)`: all the values of the named counter,
+                    /// outermost first, joined by the separator.
+                    CountersContent(~str, ~str, list_style_type::T),
                 }
                 #[allow(non_camel_case_types)]
                 #[deriving(Eq, Clone)]
@@ -534,13 +754,75 @@ pub mod longhands {
                     none,
                     Content(Vec<Content>),
                 }
+                impl ToComputedCss for T {
+                    fn to_computed_css(&self, style: &super::super::ComputedValues) -> ~str {
+                        match *self {
+                            normal => "normal".to_owned(),
+                            none => "none".to_owned(),
+                            Content(ref content) => {
+                                let items: Vec<~str> = content.iter().map(|item| {
+                                    match *item {
+                                        StringContent(ref string) => format!("\"{}\"", *string),
+                                        CounterContent(ref name, ref list_style_type) => {
+                                            format!("counter({}, {})",
+                                                    *name,
+                                                    list_style_type.to_computed_css(style))
+                                        }
+                                        CountersContent(ref name, ref separator,
+                                                        ref list_style_type) => {
+                                            format!("counters({}, \"{}\", {})",
+                                                    *name,
+                                                    *separator,
+                                                    list_style_type.to_computed_css(style))
+                                        }
+                                    }
+                                }).collect();
+                                items.as_slice().connect(" ")
+                            }
+                        }
+                    }
+                }
             }
             pub type SpecifiedValue = computed_value::T;
             #[inline] pub fn get_initial_value() -> computed_value::T  { normal }
 
-            // normal | none | [ <string> ]+
-            // TODO: <uri>, <counter>, attr(<identifier>), open-quote, close-quote, no-open-quote, no-close-quote
-            pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Option<SpecifiedValue> {
+            /// Parses the arguments of `counter()` or `counters()`. The separator is only allowed,
+            /// and required, for `counters()`.
+            fn parse_counter_function(arguments: &[ComponentValue], with_separator: bool,
+                                      base_url: &Url) -> Option<(~str, ~str, list_style_type::T)> {
+                let groups = specified::split_arguments(arguments);
+                let style_index = if with_separator { 2 } else { 1 };
+                if groups.len() < style_index || groups.len() > style_index + 1 ||
+                        groups.iter().any(|group| group.len() != 1) {
+                    return None
+                }
+                let name = match *groups.get(0).get(0) {
+                    &Ident(ref name) => name.clone(),
+                    _ => return None,
+                };
+                let separator = if with_separator {
+                    match *groups.get(1).get(0) {
+                        &String(ref separator) => separator.clone(),
+                        _ => return None,
+                    }
+                } else {
+                    "".to_owned()
+                };
+                let list_style_type = if groups.len() > style_index {
+                    match list_style_type::from_component_value(*groups.get(style_index).get(0),
+                                                                base_url) {
+                        Some(list_style_type) => list_style_type,
+                        None => return None,
+                    }
+                } else {
+                    list_style_type::decimal
+                };
+                Some((name, separator, list_style_type))
+            }
+
+            // normal | none | [ <string> | <counter> ]+
+            // TODO: <uri>, attr(<identifier>), open-quote, close-quote, no-open-quote, no-close-quote
+            pub fn parse(input: &[ComponentValue], base_url: &Url) -> Option<SpecifiedValue> {
                 match one_component_value(input) {
                     Some(&Ident(ref keyword)) => {
                         match keyword.to_owned().to_ascii_lower().as_slice() {
@@ -556,12 +838,114 @@ pub mod longhands {
                     match component_value {
                         &String(ref value)
                         => content.push(StringContent(value.to_owned())),
+                        &Function(ref name, ref arguments) => {
+                            let with_separator = match name.as_slice().to_ascii_lower().as_slice() {
+                                "counter" => false,
+                                "counters" => true,
+                                _ => return None,
+                            };
+                            let (name, separator, list_style_type) =
+                                match parse_counter_function(arguments.as_slice(),
+                                                             with_separator,
+                                                             base_url) {
+                                    Some(counter) => counter,
+                                    None => return None,
+                                };
+                            if with_separator {
+                                content.push(CountersContent(name, separator, list_style_type))
+                            } else {
+                                content.push(CounterContent(name, list_style_type))
+                            }
+                        }
                         _ => return None  // invalid/unsupported value
                     }
                 }
                 Some(Content(content))
             }
     </%self:longhand>
+
+    <%def name="counter_list(name, default_value)">
+        <%self:longhand name="${name}">
+            pub mod computed_value {
+                /// The counters and the values that they are reset to or incremented by. `none`
+                /// is the empty list.
+                pub type T = Vec<(~str, i32)>;
+            }
+            pub type SpecifiedValue = computed_value::T;
+            pub use to_computed_value = super::computed_as_specified;
+            #[inline] pub fn get_initial_value() -> computed_value::T { vec!() }
+
+            pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Option<SpecifiedValue> {
+                match one_component_value(input).and_then(get_ident_lower) {
+                    Some(ref keyword) if keyword.as_slice() == "none" => return Some(vec!()),
+                    _ => {}
+                }
+                let mut counters: Vec<(~str, i32)> = vec!();
+                let mut can_have_value = false;
+                for component_value in input.skip_whitespace() {
+                    match component_value {
+                        &Ident(ref name) => {
+                            match name.as_slice().to_ascii_lower().as_slice() {
+                                "none" | "initial" | "inherit" => return None,
+                                _ => counters.push((name.clone(), ${default_value})),
+                            }
+                            can_have_value = true
+                        }
+                        &Number(ref value) if can_have_value => {
+                            let int_value = match value.int_value {
+                                Some(int_value) => int_value as i32,
+                                None => return None,
+                            };
+                            let (name, _) = counters.pop().unwrap();
+                            counters.push((name, int_value));
+                            can_have_value = false
+                        }
+                        _ => return None,
+                    }
+                }
+                if counters.is_empty() {
+                    return None
+                }
+                Some(counters)
+            }
+        </%self:longhand>
+    </%def>
+
+    ${counter_list("counter-reset", "0")}
+    ${counter_list("counter-increment", "1")}
+
+    // CSS Lists and Counters Module Level 3, section 4.3 - Setting counters
+    ${counter_list("counter-set", "0")}
+
+    ${new_style_struct("List", is_inherited=True)}
+
+    ${single_keyword("list-style-position", "outside inside")}
+
+    ${single_keyword("list-style-type", """disc none circle square decimal lower-alpha upper-alpha
+                                           lower-latin upper-latin lower-roman upper-roman""")}
+
+    <%self:single_component_value name="list-style-image">
+            pub use to_computed_value = super::computed_as_specified;
+            pub mod computed_value {
+                use super::super::super::Url;
+
+                /// The URL of the marker image, or `None` for `none`.
+                pub type T = Option<Url>;
+            }
+            pub type SpecifiedValue = computed_value::T;
+            #[inline] pub fn get_initial_value() -> computed_value::T { None }
+            pub fn from_component_value(component_value: &ComponentValue, base_url: &Url)
+                                        -> Option<SpecifiedValue> {
+                match component_value {
+                    &URL(ref url) => Some(Some(parse_url(url.as_slice(), Some(base_url.clone())))),
+                    _ => match get_ident_lower(component_value) {
+                        Some(ref keyword) if keyword.as_slice() == "none" => Some(None),
+                        _ => None,
+                    },
+                }
+            }
+    </%self:single_component_value>
+
     // CSS 2.1, Section 13 - Paged media
 
     // CSS 2.1, Section 14 - Colors and Backgrounds
@@ -571,24 +955,25 @@ pub mod longhands {
                       "RGBA(RGBA { red: 0., green: 0., blue: 0., alpha: 0. }) /* transparent */")}
 
     <%self:single_component_value name="background-image">
-            // The computed value is the same as the specified value.
-            pub use to_computed_value = super::computed_as_specified;
+            use super::super::common_types::specified;
+
             pub mod computed_value {
-                pub use url::Url;
-                pub type T = Option<Url>;
+                use super::super::super::common_types::computed;
+                pub type T = Option<computed::Image>;
             }
-            pub type SpecifiedValue = computed_value::T;
-            #[inline] pub fn get_initial_value() -> SpecifiedValue {
+            pub type SpecifiedValue = Option<specified::Image>;
+            #[inline]
+            pub fn to_computed_value(value: SpecifiedValue, context: &computed::Context)
+                                     -> computed_value::T {
+                value.map(|image| computed::compute_Image(image, context))
+            }
+            #[inline] pub fn get_initial_value() -> computed_value::T {
                 None
             }
             pub fn from_component_value(component_value: &ComponentValue, base_url: &Url) -> Option<SpecifiedValue> {
                 match component_value {
-                    &ast::URL(ref url) => {
-                        let image_url = parse_url(url.as_slice(), Some(base_url.clone()));
-                        Some(Some(image_url))
-                    },
                     &ast::Ident(ref value) if value.to_owned().eq_ignore_ascii_case("none") => Some(None),
-                    _ => None,
+                    _ => specified::Image::from_component_value(component_value, base_url).map(Some),
                 }
             }
     </%self:single_component_value>
@@ -810,7 +1195,7 @@ pub mod longhands {
             }
         }
         pub mod computed_value {
-            #[deriving(Eq, Clone)]
+            #[deriving(Eq, Clone, FromPrimitive)]
             pub enum T {
                 % for weight in range(100, 901, 100):
                     Weight${weight},
@@ -824,6 +1209,15 @@ pub mod longhands {
                     }
                 }
             }
+            impl super::super::ToComputedCss for T {
+                fn to_computed_css(&self, _: &super::super::ComputedValues) -> ~str {
+                    match *self {
+                        % for weight in range(100, 901, 100):
+                            Weight${weight} => "${weight}".to_owned(),
+                        % endfor
+                    }
+                }
+            }
         }
         #[inline]
         pub fn get_initial_value() -> computed_value::T { Weight400 }  // normal
@@ -1027,67 +1421,767 @@ pub mod longhands {
 
     ${single_keyword("table-layout", "auto fixed")}
 
-    // CSS 2.1, Section 18 - User interface
-}
-
-
-pub mod shorthands {
-    pub use super::*;
-    pub use super::longhands::*;
+    // CSS Color Module Level 3, section 3.2 - Transparency
+    ${new_style_struct("Effects", is_inherited=False)}
 
-    <%def name="shorthand(name, sub_properties)">
-    <%
-        shorthand = Shorthand(name, sub_properties.split())
-        SHORTHANDS.append(shorthand)
-    %>
-        pub mod ${shorthand.ident} {
-            use super::*;
-            pub struct Longhands {
-                % for sub_property in shorthand.sub_properties:
-                    pub ${sub_property.ident}: Option<${sub_property.ident}::SpecifiedValue>,
-                % endfor
+    <%self:single_component_value name="opacity">
+        pub type SpecifiedValue = CSSFloat;
+        pub mod computed_value {
+            use super::super::CSSFloat;
+            pub type T = CSSFloat;
+        }
+        #[inline] pub fn get_initial_value() -> computed_value::T { 1.0 }
+        #[inline]
+        pub fn to_computed_value(value: SpecifiedValue, _context: &computed::Context)
+                                 -> computed_value::T {
+            // Values outside the range [0, 1] are clamped.
+            if value < 0.0 {
+                0.0
+            } else if value > 1.0 {
+                1.0
+            } else {
+                value
             }
-            pub fn parse(input: &[ComponentValue], base_url: &Url) -> Option<Longhands> {
-                ${caller.body()}
+        }
+        pub fn from_component_value(input: &ComponentValue, _base_url: &Url)
+                                    -> Option<SpecifiedValue> {
+            match input {
+                &ast::Number(ref value) => Some(value.value),
+                _ => None,
             }
         }
-    </%def>
+    </%self:single_component_value>
 
-    <%def name="four_sides_shorthand(name, sub_property_pattern, parser_function)">
-        <%self:shorthand name="${name}" sub_properties="${
-                ' '.join(sub_property_pattern % side
-                         for side in ['top', 'right', 'bottom', 'left'])}">
-            let mut iter = input.skip_whitespace().map(|c| ${parser_function}(c, base_url));
-            // zero or more than four values is invalid.
-            // one value sets them all
-            // two values set (top, bottom) and (left, right)
-            // three values set top, (left, right) and bottom
-            // four values set them in order
-            let top = iter.next().unwrap_or(None);
-            let right = iter.next().unwrap_or(top);
-            let bottom = iter.next().unwrap_or(top);
-            let left = iter.next().unwrap_or(right);
-            if top.is_some() && right.is_some() && bottom.is_some() && left.is_some()
-            && iter.next().is_none() {
-                Some(Longhands {
-                    % for side in ["top", "right", "bottom", "left"]:
-                        ${to_rust_ident(sub_property_pattern % side)}: ${side},
-                    % endfor
-                })
-            } else {
-                None
+    // CSS Backgrounds and Borders Module Level 3, section 7.1 - Drop Shadows
+    <%self:longhand name="box-shadow">
+        pub mod computed_value {
+            use super::super::Au;
+            use super::super::computed::CSSColor;
+
+            /// A single shadow. Text shadows are never inset and have no spread radius.
+            #[deriving(Eq, Clone)]
+            pub struct Shadow {
+                pub offset_x: Au,
+                pub offset_y: Au,
+                pub blur_radius: Au,
+                pub spread_radius: Au,
+                pub color: CSSColor,
+                pub inset: bool,
             }
-        </%self:shorthand>
-    </%def>
 
-    // TODO: other background-* properties
-    <%self:shorthand name="background"
-                     sub_properties="background-color background-position background-repeat background-attachment background-image">
-                use std::mem;
+            /// The shadows, from front to back.
+            pub type T = Vec<Shadow>;
+        }
 
-                let (mut color, mut image, mut position, mut repeat, mut attachment) =
-                    (None, None, None, None, None);
-                let mut last_component_value = None;
+        #[deriving(Clone)]
+        pub struct SpecifiedShadow {
+            pub offset_x: specified::Length,
+            pub offset_y: specified::Length,
+            pub blur_radius: specified::Length,
+            pub spread_radius: specified::Length,
+            pub color: specified::CSSColor,
+            pub inset: bool,
+        }
+
+        pub type SpecifiedValue = Vec<SpecifiedShadow>;
+
+        #[inline] pub fn get_initial_value() -> computed_value::T { vec!() }
+
+        pub fn to_computed_value(value: SpecifiedValue, context: &computed::Context)
+                                 -> computed_value::T {
+            value.move_iter().map(|shadow| {
+                computed_value::Shadow {
+                    offset_x: computed::compute_Au(shadow.offset_x, context),
+                    offset_y: computed::compute_Au(shadow.offset_y, context),
+                    blur_radius: computed::compute_Au(shadow.blur_radius, context),
+                    spread_radius: computed::compute_Au(shadow.spread_radius, context),
+                    color: shadow.color,
+                    inset: shadow.inset,
+                }
+            }).collect()
+        }
+
+        pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Option<SpecifiedValue> {
+            parse_shadow_list(input, true)
+        }
+
+        /// Parses `none` or a comma-separated list of shadows. Only box shadows may have the
+        /// `inset` keyword and a spread radius.
+        pub fn parse_shadow_list(input: &[ComponentValue], is_box_shadow: bool)
+                                 -> Option<SpecifiedValue> {
+            match one_component_value(input) {
+                Some(&Ident(ref value)) if value.to_owned().eq_ignore_ascii_case("none") => {
+                    return Some(vec!())
+                }
+                _ => {}
+            }
+
+            let mut shadows = vec!();
+            let mut shadow = vec!();
+            for component_value in input.skip_whitespace() {
+                match component_value {
+                    &Comma => {
+                        match parse_shadow(shadow.as_slice(), is_box_shadow) {
+                            Some(value) => shadows.push(value),
+                            None => return None,
+                        }
+                        shadow = vec!();
+                    }
+                    _ => shadow.push(component_value),
+                }
+            }
+            match parse_shadow(shadow.as_slice(), is_box_shadow) {
+                Some(value) => shadows.push(value),
+                None => return None,
+            }
+            Some(shadows)
+        }
+
+        /// Parses `inset? && <length>{2,4} && <color>?`. The lengths must be adjacent.
+        fn parse_shadow(input: &[&ComponentValue], is_box_shadow: bool)
+                        -> Option<SpecifiedShadow> {
+            let mut lengths = vec!();
+            let mut lengths_done = false;
+            let mut color = None;
+            let mut inset = false;
+            for &component_value in input.iter() {
+                match component_value {
+                    &Ident(ref value) if is_box_shadow && !inset &&
+                            value.to_owned().eq_ignore_ascii_case("inset") => {
+                        inset = true;
+                        lengths_done = !lengths.is_empty();
+                        continue
+                    }
+                    _ => {}
+                }
+                match specified::Length::parse(component_value) {
+                    Some(length) if !lengths_done => {
+                        lengths.push(length);
+                        continue
+                    }
+                    Some(_) => return None,
+                    None => {}
+                }
+                if color.is_none() {
+                    match specified::CSSColor::parse(component_value) {
+                        Some(value) => {
+                            color = Some(value);
+                            lengths_done = !lengths.is_empty();
+                            continue
+                        }
+                        None => {}
+                    }
+                }
+                return None
+            }
+
+            let max_lengths = if is_box_shadow { 4 } else { 3 };
+            if lengths.len() < 2 || lengths.len() > max_lengths {
+                return None
+            }
+            let lengths = lengths.as_slice();
+            let zero = specified::Au_(Au(0));
+            let blur_radius = if lengths.len() > 2 { lengths[2] } else { zero };
+            let is_negative = match blur_radius {
+                specified::Au_(Au(value)) => value < 0,
+                specified::Em(value) | specified::Ex(value) => value < 0.,
+            };
+            if is_negative {
+                return None
+            }
+            Some(SpecifiedShadow {
+                offset_x: lengths[0],
+                offset_y: lengths[1],
+                blur_radius: blur_radius,
+                spread_radius: if lengths.len() > 3 { lengths[3] } else { zero },
+                color: color.unwrap_or(CurrentColor),
+                inset: inset,
+            })
+        }
+    </%self:longhand>
+
+    // CSS Transforms, section 7 - The transform property
+    <%self:longhand name="transform">
+        pub mod computed_value {
+            use super::super::{Au, CSSFloat};
+            use super::super::computed::LengthOrPercentage;
+            use super::super::specified::Angle;
+
+            /// A single transform function. Percentages in translations are relative to the size
+            /// of the border box, so they are resolved at layout time.
+            #[deriving(Eq, Clone)]
+            pub enum TransformOperation {
+                Matrix(CSSFloat, CSSFloat, CSSFloat, CSSFloat, Au, Au),
+                Translate(LengthOrPercentage, LengthOrPercentage),
+                Scale(CSSFloat, CSSFloat),
+                Rotate(Angle),
+                Skew(Angle, Angle),
+            }
+
+            /// The transform functions, in the order they appear in the style.
+            pub type T = Vec<TransformOperation>;
+        }
+
+        #[deriving(Clone)]
+        pub enum SpecifiedOperation {
+            SpecifiedMatrix(CSSFloat, CSSFloat, CSSFloat, CSSFloat,
+                            specified::Length, specified::Length),
+            SpecifiedTranslate(specified::LengthOrPercentage, specified::LengthOrPercentage),
+            SpecifiedScale(CSSFloat, CSSFloat),
+            SpecifiedRotate(specified::Angle),
+            SpecifiedSkew(specified::Angle, specified::Angle),
+        }
+
+        pub type SpecifiedValue = Vec<SpecifiedOperation>;
+
+        #[inline] pub fn get_initial_value() -> computed_value::T { vec!() }
+
+        pub fn to_computed_value(value: SpecifiedValue, context: &computed::Context)
+                                 -> computed_value::T {
+            value.move_iter().map(|operation| {
+                match operation {
+                    SpecifiedMatrix(a, b, c, d, e, f) => {
+                        Matrix(a, b, c, d,
+                               computed::compute_Au(e, context),
+                               computed::compute_Au(f, context))
+                    }
+                    SpecifiedTranslate(x, y) => {
+                        Translate(computed::compute_LengthOrPercentage(x, context),
+                                  computed::compute_LengthOrPercentage(y, context))
+                    }
+                    SpecifiedScale(x, y) => Scale(x, y),
+                    SpecifiedRotate(angle) => Rotate(angle),
+                    SpecifiedSkew(x, y) => Skew(x, y),
+                }
+            }).collect()
+        }
+
+        pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Option<SpecifiedValue> {
+            match one_component_value(input) {
+                Some(&Ident(ref value)) if value.to_owned().eq_ignore_ascii_case("none") => {
+                    return Some(vec!())
+                }
+                _ => {}
+            }
+
+            let mut operations = vec!();
+            for component_value in input.skip_whitespace() {
+                match component_value {
+                    &Function(ref name, ref arguments) => {
+                        match parse_function(name.as_slice(), arguments.as_slice()) {
+                            Some(operation) => operations.push(operation),
+                            None => return None,
+                        }
+                    }
+                    _ => return None,
+                }
+            }
+            if operations.is_empty() {
+                None
+            } else {
+                Some(operations)
+            }
+        }
+
+        fn parse_number(input: &ComponentValue) -> Option<CSSFloat> {
+            match input {
+                &Number(ref value) => Some(value.value),
+                _ => None,
+            }
+        }
+
+        /// Parses one of the 2D transform functions.
+        fn parse_function(name: &str, arguments: &[ComponentValue]) -> Option<SpecifiedOperation> {
+            let mut values = vec!();
+            for argument in specified::split_arguments(arguments).iter() {
+                if argument.len() != 1 {
+                    return None
+                }
+                values.push(*argument.get(0));
+            }
+            let values = values.as_slice();
+            let zero = specified::LP_Length(specified::Au_(Au(0)));
+            let no_angle = specified::Angle(0.);
+
+            match (name.to_ascii_lower().as_slice(), values.len()) {
+                ("matrix", 6) => {
+                    let mut numbers = [0., ..6];
+                    for (i, value) in values.iter().enumerate() {
+                        match parse_number(*value) {
+                            Some(number) => numbers[i] = number,
+                            None => return None,
+                        }
+                    }
+                    Some(SpecifiedMatrix(numbers[0], numbers[1], numbers[2], numbers[3],
+                                         specified::Length::from_px(numbers[4]),
+                                         specified::Length::from_px(numbers[5])))
+                }
+                ("translate", 1) => {
+                    specified::LengthOrPercentage::parse(values[0]).map(|x| {
+                        SpecifiedTranslate(x, zero)
+                    })
+                }
+                ("translate", 2) => {
+                    match (specified::LengthOrPercentage::parse(values[0]),
+                           specified::LengthOrPercentage::parse(values[1])) {
+                        (Some(x), Some(y)) => Some(SpecifiedTranslate(x, y)),
+                        _ => None,
+                    }
+                }
+                ("translatex", 1) => {
+                    specified::LengthOrPercentage::parse(values[0]).map(|x| {
+                        SpecifiedTranslate(x, zero)
+                    })
+                }
+                ("translatey", 1) => {
+                    specified::LengthOrPercentage::parse(values[0]).map(|y| {
+                        SpecifiedTranslate(zero, y)
+                    })
+                }
+                ("scale", 1) => parse_number(values[0]).map(|scale| SpecifiedScale(scale, scale)),
+                ("scale", 2) => {
+                    match (parse_number(values[0]), parse_number(values[1])) {
+                        (Some(x), Some(y)) => Some(SpecifiedScale(x, y)),
+                        _ => None,
+                    }
+                }
+                ("scalex", 1) => parse_number(values[0]).map(|x| SpecifiedScale(x, 1.)),
+                ("scaley", 1) => parse_number(values[0]).map(|y| SpecifiedScale(1., y)),
+                ("rotate", 1) => specified::Angle::parse(values[0]).map(SpecifiedRotate),
+                ("skew", 1) => {
+                    specified::Angle::parse(values[0]).map(|x| SpecifiedSkew(x, no_angle))
+                }
+                ("skew", 2) => {
+                    match (specified::Angle::parse(values[0]), specified::Angle::parse(values[1])) {
+                        (Some(x), Some(y)) => Some(SpecifiedSkew(x, y)),
+                        _ => None,
+                    }
+                }
+                ("skewx", 1) => {
+                    specified::Angle::parse(values[0]).map(|x| SpecifiedSkew(x, no_angle))
+                }
+                ("skewy", 1) => {
+                    specified::Angle::parse(values[0]).map(|y| SpecifiedSkew(no_angle, y))
+                }
+                _ => None,
+            }
+        }
+    </%self:longhand>
+
+    // CSS Transforms, section 8 - The transform-origin property
+    <%self:longhand name="transform-origin">
+        pub mod computed_value {
+            use super::super::computed::LengthOrPercentage;
+
+            #[deriving(Eq, Clone)]
+            pub struct T {
+                pub horizontal: LengthOrPercentage,
+                pub vertical: LengthOrPercentage,
+            }
+        }
+
+        #[deriving(Clone)]
+        pub struct SpecifiedValue {
+            pub horizontal: specified::LengthOrPercentage,
+            pub vertical: specified::LengthOrPercentage,
+        }
+
+        #[inline]
+        pub fn get_initial_value() -> computed_value::T {
+            computed_value::T {
+                horizontal: computed::LP_Percentage(0.5),
+                vertical: computed::LP_Percentage(0.5),
+            }
+        }
+
+        #[inline]
+        pub fn to_computed_value(value: SpecifiedValue, context: &computed::Context)
+                                 -> computed_value::T {
+            computed_value::T {
+                horizontal: computed::compute_LengthOrPercentage(value.horizontal, context),
+                vertical: computed::compute_LengthOrPercentage(value.vertical, context),
+            }
+        }
+
+        pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Option<SpecifiedValue> {
+            let values: Vec< &ComponentValue> = input.skip_whitespace().collect();
+            specified::parse_position(values.as_slice()).map(|(horizontal, vertical)| {
+                SpecifiedValue {
+                    horizontal: horizontal,
+                    vertical: vertical,
+                }
+            })
+        }
+    </%self:longhand>
+
+    ${switch_to_style_struct("InheritedText")}
+
+    <%self:longhand name="text-shadow">
+        pub use super::box_shadow::to_computed_value;
+        pub type SpecifiedValue = super::box_shadow::SpecifiedValue;
+        pub mod computed_value {
+            pub type T = super::super::box_shadow::computed_value::T;
+        }
+        #[inline] pub fn get_initial_value() -> computed_value::T { vec!() }
+        pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Option<SpecifiedValue> {
+            super::box_shadow::parse_shadow_list(input, false)
+        }
+    </%self:longhand>
+
+    // CSS Flexible Box Layout Module Level 1
+
+    ${new_style_struct("Flex", is_inherited=False)}
+
+    ${single_keyword("flex-direction", "row row-reverse column column-reverse")}
+    ${single_keyword("flex-wrap", "nowrap wrap wrap-reverse")}
+    ${single_keyword("justify-content", "flex-start flex-end center space-between space-around")}
+    // FIXME: `baseline` is not supported by layout, so it is rejected rather than aligning the
+    // items as if it were `flex-start`.
+    ${single_keyword("align-items", "stretch flex-start flex-end center")}
+    ${single_keyword("align-self", "auto stretch flex-start flex-end center")}
+
+    <%self:single_component_value name="flex-grow">
+        pub use to_computed_value = super::computed_as_specified;
+        pub type SpecifiedValue = CSSFloat;
+        pub mod computed_value {
+            use super::super::CSSFloat;
+            pub type T = CSSFloat;
+        }
+        #[inline] pub fn get_initial_value() -> computed_value::T { 0.0 }
+        pub fn from_component_value(input: &ComponentValue, _base_url: &Url)
+                                    -> Option<SpecifiedValue> {
+            match input {
+                &ast::Number(ref value) if value.value >= 0. => Some(value.value),
+                _ => None,
+            }
+        }
+    </%self:single_component_value>
+
+    <%self:single_component_value name="flex-shrink">
+        pub use to_computed_value = super::computed_as_specified;
+        pub use super::flex_grow::from_component_value;
+        pub type SpecifiedValue = CSSFloat;
+        pub mod computed_value {
+            use super::super::CSSFloat;
+            pub type T = CSSFloat;
+        }
+        #[inline] pub fn get_initial_value() -> computed_value::T { 1.0 }
+    </%self:single_component_value>
+
+    ${predefined_type("flex-basis", "LengthOrPercentageOrAuto",
+                      "computed::LPA_Auto",
+                      "parse_non_negative")}
+
+    <%self:single_component_value name="order">
+        pub use to_computed_value = super::computed_as_specified;
+        pub type SpecifiedValue = i32;
+        pub mod computed_value {
+            pub type T = i32;
+        }
+        #[inline] pub fn get_initial_value() -> computed_value::T { 0 }
+        pub fn from_component_value(input: &ComponentValue, _base_url: &Url)
+                                    -> Option<SpecifiedValue> {
+            match input {
+                &ast::Number(ref value) => value.int_value.map(|value| value as i32),
+                _ => None,
+            }
+        }
+    </%self:single_component_value>
+
+    // CSS Transitions, section 2 - Transitions
+
+    ${new_style_struct("Animation", is_inherited=False)}
+
+    <%self:longhand name="transition-property">
+        pub mod computed_value {
+            use super::super::AnimatableProperty;
+
+            #[deriving(Eq, Clone)]
+            pub enum SingleComputedValue {
+                TransitionAll,
+                TransitionProperty(AnimatableProperty),
+                /// A property that cannot be transitioned. It is kept so that the other
+                /// transition lists still line up with this one.
+                TransitionUnknown(~str),
+            }
+
+            /// The transitioned properties. `none` is the empty list.
+            pub type T = Vec<SingleComputedValue>;
+        }
+        pub type SpecifiedValue = computed_value::T;
+        pub use to_computed_value = super::computed_as_specified;
+        #[inline] pub fn get_initial_value() -> computed_value::T { vec!(TransitionAll) }
+
+        pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Option<SpecifiedValue> {
+            let groups = specified::split_arguments(input);
+            let mut properties = vec!();
+            for group in groups.iter() {
+                if group.len() != 1 {
+                    return None
+                }
+                let name = match get_ident_lower(*group.get(0)) {
+                    Some(name) => name,
+                    None => return None,
+                };
+                match name.as_slice() {
+                    "none" if groups.len() == 1 => return Some(vec!()),
+                    "none" | "initial" | "inherit" => return None,
+                    "all" => properties.push(TransitionAll),
+                    _ => {
+                        properties.push(match AnimatableProperty::from_name(name.as_slice()) {
+                            Some(property) => TransitionProperty(property),
+                            None => TransitionUnknown(name.clone()),
+                        })
+                    }
+                }
+            }
+            Some(properties)
+        }
+    </%self:longhand>
+
+    <%self:longhand name="transition-duration">
+        pub mod computed_value {
+            use super::super::computed::Time;
+
+            pub type T = Vec<Time>;
+        }
+        pub type SpecifiedValue = computed_value::T;
+        pub use to_computed_value = super::computed_as_specified;
+        #[inline] pub fn get_initial_value() -> computed_value::T { vec!(specified::Time(0.)) }
+
+        /// Parses a comma-separated list of times.
+        pub fn parse_time_list(input: &[ComponentValue], negative_ok: bool)
+                               -> Option<SpecifiedValue> {
+            let mut times = vec!();
+            for group in specified::split_arguments(input).iter() {
+                if group.len() != 1 {
+                    return None
+                }
+                match specified::Time::parse(*group.get(0)) {
+                    Some(time) if negative_ok || time.seconds() >= 0. => times.push(time),
+                    _ => return None,
+                }
+            }
+            Some(times)
+        }
+
+        pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Option<SpecifiedValue> {
+            parse_time_list(input, false)
+        }
+    </%self:longhand>
+
+    <%self:longhand name="transition-timing-function">
+        pub mod computed_value {
+            use super::super::computed::TimingFunction;
+
+            pub type T = Vec<TimingFunction>;
+        }
+        pub type SpecifiedValue = computed_value::T;
+        pub use to_computed_value = super::computed_as_specified;
+        #[inline]
+        pub fn get_initial_value() -> computed_value::T {
+            vec!(specified::CubicBezierTimingFunction(0.25, 0.1, 0.25, 1.))
+        }
+
+        pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Option<SpecifiedValue> {
+            let mut timing_functions = vec!();
+            for group in specified::split_arguments(input).iter() {
+                if group.len() != 1 {
+                    return None
+                }
+                match specified::TimingFunction::parse(*group.get(0)) {
+                    Some(timing_function) => timing_functions.push(timing_function),
+                    None => return None,
+                }
+            }
+            Some(timing_functions)
+        }
+    </%self:longhand>
+
+    <%self:longhand name="transition-delay">
+        pub use super::transition_duration::{get_initial_value, to_computed_value};
+        pub type SpecifiedValue = super::transition_duration::SpecifiedValue;
+        pub mod computed_value {
+            pub type T = super::super::transition_duration::computed_value::T;
+        }
+        pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Option<SpecifiedValue> {
+            super::transition_duration::parse_time_list(input, true)
+        }
+    </%self:longhand>
+
+    // CSS Animations, section 3 - Assigning Animations to an Element
+
+    <%self:longhand name="animation-name">
+        pub mod computed_value {
+            /// The names of the `@keyframes` rules to run, where `None` stands for `none`. The
+            /// `none` entries are kept so that the other animation lists still line up with this
+            /// one.
+            pub type T = Vec<Option<~str>>;
+        }
+        pub type SpecifiedValue = computed_value::T;
+        pub use to_computed_value = super::computed_as_specified;
+        #[inline] pub fn get_initial_value() -> computed_value::T { vec!(None) }
+
+        /// Parses the name of a `@keyframes` rule or `none`.
+        pub fn parse_one(input: &ComponentValue) -> Option<Option<~str>> {
+            match input {
+                &Ident(ref value) => match value.as_slice().to_ascii_lower().as_slice() {
+                    "none" => Some(None),
+                    "initial" | "inherit" => None,
+                    _ => Some(Some(value.clone())),
+                },
+                &String(ref value) => Some(Some(value.clone())),
+                _ => None,
+            }
+        }
+
+        pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Option<SpecifiedValue> {
+            let mut names = vec!();
+            for group in specified::split_arguments(input).iter() {
+                if group.len() != 1 {
+                    return None
+                }
+                match parse_one(*group.get(0)) {
+                    Some(name) => names.push(name),
+                    None => return None,
+                }
+            }
+            Some(names)
+        }
+    </%self:longhand>
+
+    <%self:longhand name="animation-duration">
+        pub use super::transition_duration::{get_initial_value, to_computed_value, parse};
+        pub type SpecifiedValue = super::transition_duration::SpecifiedValue;
+        pub mod computed_value {
+            pub type T = super::super::transition_duration::computed_value::T;
+        }
+    </%self:longhand>
+
+    <%self:longhand name="animation-timing-function">
+        pub use super::transition_timing_function::{get_initial_value, to_computed_value, parse};
+        pub type SpecifiedValue = super::transition_timing_function::SpecifiedValue;
+        pub mod computed_value {
+            pub type T = super::super::transition_timing_function::computed_value::T;
+        }
+    </%self:longhand>
+
+    <%self:longhand name="animation-iteration-count">
+        pub mod computed_value {
+            #[deriving(Eq, Clone)]
+            pub enum SingleComputedValue {
+                Infinite,
+                Iterations(super::super::CSSFloat),
+            }
+            pub type T = Vec<SingleComputedValue>;
+        }
+        pub type SpecifiedValue = computed_value::T;
+        pub use to_computed_value = super::computed_as_specified;
+        #[inline] pub fn get_initial_value() -> computed_value::T { vec!(Iterations(1.)) }
+
+        pub fn parse_one(input: &ComponentValue) -> Option<SingleComputedValue> {
+            match input {
+                &Number(ref value) if value.value >= 0. => Some(Iterations(value.value)),
+                _ => match get_ident_lower(input) {
+                    Some(ref keyword) if keyword.as_slice() == "infinite" => Some(Infinite),
+                    _ => None,
+                },
+            }
+        }
+
+        pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Option<SpecifiedValue> {
+            let mut counts = vec!();
+            for group in specified::split_arguments(input).iter() {
+                if group.len() != 1 {
+                    return None
+                }
+                match parse_one(*group.get(0)) {
+                    Some(count) => counts.push(count),
+                    None => return None,
+                }
+            }
+            Some(counts)
+        }
+    </%self:longhand>
+
+    ${keyword_list("animation-direction", "normal reverse alternate alternate-reverse")}
+
+    ${keyword_list("animation-fill-mode", "none forwards backwards both")}
+
+    <%self:longhand name="animation-delay">
+        pub use super::transition_duration::{get_initial_value, to_computed_value};
+        pub type SpecifiedValue = super::transition_duration::SpecifiedValue;
+        pub mod computed_value {
+            pub type T = super::super::transition_duration::computed_value::T;
+        }
+        pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Option<SpecifiedValue> {
+            super::transition_duration::parse_time_list(input, true)
+        }
+    </%self:longhand>
+
+    ${keyword_list("animation-play-state", "running paused")}
+
+    // CSS 2.1, Section 18 - User interface
+}
+
+
+pub mod shorthands {
+    pub use super::*;
+    pub use super::longhands::*;
+
+    <%def name="shorthand(name, sub_properties)">
+    <%
+        shorthand = Shorthand(name, sub_properties.split())
+        SHORTHANDS.append(shorthand)
+    %>
+        pub mod ${shorthand.ident} {
+            use super::*;
+            pub struct Longhands {
+                % for sub_property in shorthand.sub_properties:
+                    pub ${sub_property.ident}: Option<${sub_property.ident}::SpecifiedValue>,
+                % endfor
+            }
+            pub fn parse(input: &[ComponentValue], base_url: &Url) -> Option<Longhands> {
+                ${caller.body()}
+            }
+        }
+    </%def>
+
+    <%def name="four_sides_shorthand(name, sub_property_pattern, parser_function)">
+        <%self:shorthand name="${name}" sub_properties="${
+                ' '.join(sub_property_pattern % side
+                         for side in ['top', 'right', 'bottom', 'left'])}">
+            let mut iter = input.skip_whitespace().map(|c| ${parser_function}(c, base_url));
+            // zero or more than four values is invalid.
+            // one value sets them all
+            // two values set (top, bottom) and (left, right)
+            // three values set top, (left, right) and bottom
+            // four values set them in order
+            let top = iter.next().unwrap_or(None);
+            let right = iter.next().unwrap_or(top);
+            let bottom = iter.next().unwrap_or(top);
+            let left = iter.next().unwrap_or(right);
+            if top.is_some() && right.is_some() && bottom.is_some() && left.is_some()
+            && iter.next().is_none() {
+                Some(Longhands {
+                    % for side in ["top", "right", "bottom", "left"]:
+                        ${to_rust_ident(sub_property_pattern % side)}: ${side},
+                    % endfor
+                })
+            } else {
+                None
+            }
+        </%self:shorthand>
+    </%def>
+
+    // TODO: other background-* properties
+    <%self:shorthand name="background"
+                     sub_properties="background-color background-position background-repeat background-attachment background-image">
+                use std::mem;
+
+                let (mut color, mut image, mut position, mut repeat, mut attachment) =
+                    (None, None, None, None, None);
+                let mut last_component_value = None;
                 let mut any = false;
 
                 for component_value in input.skip_whitespace() {
@@ -1249,6 +2343,64 @@ pub mod shorthands {
         })
     </%self:shorthand>
 
+    <%self:shorthand name="border-radius" sub_properties="${' '.join(
+        'border-%s-radius' % corner
+        for corner in ['top-left', 'top-right', 'bottom-right', 'bottom-left']
+    )}">
+        // One to four horizontal radii, optionally followed by a slash and one to four vertical
+        // radii. Each list is expanded to the four corners like the `margin` shorthand, starting
+        // at the top left corner.
+        fn parse_corners(input: &[&ComponentValue])
+                         -> Option<[specified::LengthOrPercentage, ..4]> {
+            let mut iter = input.iter()
+                                .map(|c| specified::LengthOrPercentage::parse_non_negative(*c));
+            let top_left = iter.next().unwrap_or(None);
+            let top_right = iter.next().unwrap_or(top_left);
+            let bottom_right = iter.next().unwrap_or(top_left);
+            let bottom_left = iter.next().unwrap_or(top_right);
+            match (top_left, top_right, bottom_right, bottom_left) {
+                (Some(top_left), Some(top_right), Some(bottom_right), Some(bottom_left))
+                        if iter.next().is_none() => {
+                    Some([top_left, top_right, bottom_right, bottom_left])
+                }
+                _ => None,
+            }
+        }
+
+        let mut horizontal = vec!();
+        let mut vertical = vec!();
+        let mut seen_slash = false;
+        for component_value in input.skip_whitespace() {
+            match component_value {
+                &Delim('/') if !seen_slash => seen_slash = true,
+                _ if seen_slash => vertical.push(component_value),
+                _ => horizontal.push(component_value),
+            }
+        }
+
+        let widths = match parse_corners(horizontal.as_slice()) {
+            Some(widths) => widths,
+            None => return None,
+        };
+        let heights = if seen_slash {
+            match parse_corners(vertical.as_slice()) {
+                Some(heights) => heights,
+                None => return None,
+            }
+        } else {
+            widths
+        };
+
+        Some(Longhands {
+            % for (i, corner) in enumerate(["top_left", "top_right", "bottom_right", "bottom_left"]):
+                border_${corner}_radius: Some(border_top_left_radius::SpecifiedValue {
+                    width: widths[${i}],
+                    height: heights[${i}],
+                }),
+            % endfor
+        })
+    </%self:shorthand>
+
     <%self:shorthand name="font" sub_properties="font-style font-variant font-weight
                                                  font-size line-height font-family">
         let mut iter = input.skip_whitespace();
@@ -1326,6 +2478,373 @@ pub mod shorthands {
         })
     </%self:shorthand>
 
+    <%self:shorthand name="list-style"
+                     sub_properties="list-style-position list-style-type list-style-image">
+        // `none` sets whichever of `list-style-type` and `list-style-image` is not set otherwise;
+        // a second `none` sets the other one.
+        let mut nones = 0u;
+        let (mut position, mut list_style_type, mut image) = (None, None, None);
+        for component_value in input.skip_whitespace() {
+            match get_ident_lower(component_value) {
+                Some(ref keyword) if keyword.as_slice() == "none" => {
+                    nones += 1;
+                    continue
+                }
+                _ => {}
+            }
+            if position.is_none() {
+                match list_style_position::from_component_value(component_value, base_url) {
+                    Some(value) => {
+                        position = Some(value);
+                        continue
+                    }
+                    None => {}
+                }
+            }
+            if list_style_type.is_none() {
+                match list_style_type::from_component_value(component_value, base_url) {
+                    Some(value) => {
+                        list_style_type = Some(value);
+                        continue
+                    }
+                    None => {}
+                }
+            }
+            if image.is_none() {
+                match list_style_image::from_component_value(component_value, base_url) {
+                    Some(value) => {
+                        image = Some(value);
+                        continue
+                    }
+                    None => {}
+                }
+            }
+            return None
+        }
+        match (nones, list_style_type.is_some(), image.is_some()) {
+            (0, _, _) => {}
+            (1, false, _) => list_style_type = Some(list_style_type::none),
+            (1, true, false) | (2, false, false) => {
+                list_style_type = Some(list_style_type::none);
+                image = Some(None)
+            }
+            _ => return None,
+        }
+        Some(Longhands {
+            list_style_position: position,
+            list_style_type: list_style_type,
+            list_style_image: image,
+        })
+    </%self:shorthand>
+
+    <%self:shorthand name="flex-flow" sub_properties="flex-direction flex-wrap">
+        let (mut direction, mut wrap) = (None, None);
+        for component_value in input.skip_whitespace() {
+            if direction.is_none() {
+                match flex_direction::from_component_value(component_value, base_url) {
+                    Some(value) => {
+                        direction = Some(value);
+                        continue
+                    }
+                    None => {}
+                }
+            }
+            if wrap.is_none() {
+                match flex_wrap::from_component_value(component_value, base_url) {
+                    Some(value) => {
+                        wrap = Some(value);
+                        continue
+                    }
+                    None => {}
+                }
+            }
+            return None
+        }
+        if direction.is_none() && wrap.is_none() {
+            return None
+        }
+        Some(Longhands {
+            flex_direction: direction,
+            flex_wrap: wrap,
+        })
+    </%self:shorthand>
+
+    <%self:shorthand name="flex" sub_properties="flex-grow flex-shrink flex-basis">
+        // `none` is `0 0 auto` and `auto` is `1 1 auto`. Otherwise, an omitted `flex-basis` is
+        // `0%` rather than its initial value (CSS Flexible Box Layout section 7.1.1).
+        match one_component_value(input).and_then(get_ident_lower) {
+            Some(ref keyword) if keyword.as_slice() == "none" => {
+                return Some(Longhands {
+                    flex_grow: Some(0.),
+                    flex_shrink: Some(0.),
+                    flex_basis: Some(specified::LPA_Auto),
+                })
+            }
+            Some(ref keyword) if keyword.as_slice() == "auto" => {
+                return Some(Longhands {
+                    flex_grow: Some(1.),
+                    flex_shrink: Some(1.),
+                    flex_basis: Some(specified::LPA_Auto),
+                })
+            }
+            _ => {}
+        }
+        let (mut grow, mut shrink, mut basis) = (None, None, None);
+        let mut previous_was_grow = false;
+        for component_value in input.skip_whitespace() {
+            // A unitless zero is a flex factor, not a basis, unless both factors have been seen.
+            if grow.is_none() || (previous_was_grow && shrink.is_none()) {
+                match flex_grow::from_component_value(component_value, base_url) {
+                    Some(value) => {
+                        if grow.is_none() {
+                            grow = Some(value);
+                            previous_was_grow = true
+                        } else {
+                            shrink = Some(value);
+                            previous_was_grow = false
+                        }
+                        continue
+                    }
+                    None => {}
+                }
+            }
+            previous_was_grow = false;
+            if basis.is_none() {
+                match flex_basis::from_component_value(component_value, base_url) {
+                    Some(value) => {
+                        basis = Some(value);
+                        continue
+                    }
+                    None => {}
+                }
+            }
+            return None
+        }
+        match (grow, basis) {
+            (None, None) => None,
+            (Some(_), None) => {
+                Some(Longhands {
+                    flex_grow: grow,
+                    flex_shrink: shrink,
+                    flex_basis: Some(specified::LPA_Percentage(0.)),
+                })
+            }
+            _ => {
+                Some(Longhands {
+                    flex_grow: grow.or(Some(1.)),
+                    flex_shrink: shrink,
+                    flex_basis: basis,
+                })
+            }
+        }
+    </%self:shorthand>
+
+    <%self:shorthand name="transition" sub_properties="transition-property transition-duration
+                                                       transition-timing-function transition-delay">
+        let groups = specified::split_arguments(input);
+        let mut properties = vec!();
+        let mut durations = vec!();
+        let mut timing_functions = vec!();
+        let mut delays = vec!();
+        for group in groups.iter() {
+            let (mut property, mut duration, mut timing_function, mut delay) =
+                (None, None, None, None);
+            for component_value in group.iter() {
+                // The first time is the duration and the second one is the delay.
+                match specified::Time::parse(*component_value) {
+                    Some(time) if duration.is_none() && time.seconds() >= 0. => {
+                        duration = Some(time);
+                        continue
+                    }
+                    Some(time) if duration.is_some() && delay.is_none() => {
+                        delay = Some(time);
+                        continue
+                    }
+                    Some(_) => return None,
+                    None => {}
+                }
+                if timing_function.is_none() {
+                    match specified::TimingFunction::parse(*component_value) {
+                        Some(value) => {
+                            timing_function = Some(value);
+                            continue
+                        }
+                        None => {}
+                    }
+                }
+                if property.is_none() {
+                    match get_ident_lower(*component_value) {
+                        Some(ref name) if name.as_slice() == "none" => {
+                            if groups.len() != 1 {
+                                return None
+                            }
+                            property = Some(None);
+                            continue
+                        }
+                        Some(name) => {
+                            property = Some(Some(if name.as_slice() == "all" {
+                                transition_property::TransitionAll
+                            } else {
+                                match AnimatableProperty::from_name(name.as_slice()) {
+                                    Some(animatable) => {
+                                        transition_property::TransitionProperty(animatable)
+                                    }
+                                    None => transition_property::TransitionUnknown(name),
+                                }
+                            }));
+                            continue
+                        }
+                        None => {}
+                    }
+                }
+                return None
+            }
+            if group.is_empty() {
+                return None
+            }
+            match property {
+                Some(None) => {}
+                Some(Some(property)) => properties.push(property),
+                None => properties.push(transition_property::TransitionAll),
+            }
+            durations.push(duration.unwrap_or(specified::Time(0.)));
+            timing_functions.push(timing_function.unwrap_or_else(|| {
+                specified::CubicBezierTimingFunction(0.25, 0.1, 0.25, 1.)
+            }));
+            delays.push(delay.unwrap_or(specified::Time(0.)));
+        }
+        Some(Longhands {
+            transition_property: Some(properties),
+            transition_duration: Some(durations),
+            transition_timing_function: Some(timing_functions),
+            transition_delay: Some(delays),
+        })
+    </%self:shorthand>
+
+    <%self:shorthand name="animation" sub_properties="animation-name animation-duration
+                                                      animation-timing-function
+                                                      animation-iteration-count
+                                                      animation-direction animation-fill-mode
+                                                      animation-delay animation-play-state">
+        let groups = specified::split_arguments(input);
+        let mut names = vec!();
+        let mut durations = vec!();
+        let mut timing_functions = vec!();
+        let mut iteration_counts = vec!();
+        let mut directions = vec!();
+        let mut fill_modes = vec!();
+        let mut delays = vec!();
+        let mut play_states = vec!();
+        for group in groups.iter() {
+            let (mut name, mut duration, mut timing_function, mut iteration_count) =
+                (None, None, None, None);
+            let (mut direction, mut fill_mode, mut delay, mut play_state) =
+                (None, None, None, None);
+            for component_value in group.iter() {
+                // The first time is the duration and the second one is the delay.
+                match specified::Time::parse(*component_value) {
+                    Some(time) if duration.is_none() && time.seconds() >= 0. => {
+                        duration = Some(time);
+                        continue
+                    }
+                    Some(time) if duration.is_some() && delay.is_none() => {
+                        delay = Some(time);
+                        continue
+                    }
+                    Some(_) => return None,
+                    None => {}
+                }
+                if timing_function.is_none() {
+                    match specified::TimingFunction::parse(*component_value) {
+                        Some(value) => {
+                            timing_function = Some(value);
+                            continue
+                        }
+                        None => {}
+                    }
+                }
+                if iteration_count.is_none() {
+                    match animation_iteration_count::parse_one(*component_value) {
+                        Some(value) => {
+                            iteration_count = Some(value);
+                            continue
+                        }
+                        None => {}
+                    }
+                }
+                // Keywords of the other properties take precedence over animation names.
+                match get_ident_lower(*component_value) {
+                    Some(keyword) => {
+                        let keyword = keyword.as_slice();
+                        if direction.is_none() {
+                            match animation_direction::SingleComputedValue::from_keyword(keyword) {
+                                Some(value) => {
+                                    direction = Some(value);
+                                    continue
+                                }
+                                None => {}
+                            }
+                        }
+                        if fill_mode.is_none() {
+                            match animation_fill_mode::SingleComputedValue::from_keyword(keyword) {
+                                Some(value) => {
+                                    fill_mode = Some(value);
+                                    continue
+                                }
+                                None => {}
+                            }
+                        }
+                        if play_state.is_none() {
+                            match animation_play_state::SingleComputedValue::from_keyword(
+                                    keyword) {
+                                Some(value) => {
+                                    play_state = Some(value);
+                                    continue
+                                }
+                                None => {}
+                            }
+                        }
+                    }
+                    None => {}
+                }
+                if name.is_none() {
+                    match animation_name::parse_one(*component_value) {
+                        Some(value) => {
+                            name = Some(value);
+                            continue
+                        }
+                        None => {}
+                    }
+                }
+                return None
+            }
+            if group.is_empty() {
+                return None
+            }
+            names.push(name.unwrap_or(None));
+            durations.push(duration.unwrap_or(specified::Time(0.)));
+            timing_functions.push(timing_function.unwrap_or_else(|| {
+                specified::CubicBezierTimingFunction(0.25, 0.1, 0.25, 1.)
+            }));
+            iteration_counts.push(iteration_count.unwrap_or(
+                animation_iteration_count::Iterations(1.)));
+            directions.push(direction.unwrap_or(animation_direction::normal));
+            fill_modes.push(fill_mode.unwrap_or(animation_fill_mode::none));
+            delays.push(delay.unwrap_or(specified::Time(0.)));
+            play_states.push(play_state.unwrap_or(animation_play_state::running));
+        }
+        Some(Longhands {
+            animation_name: Some(names),
+            animation_duration: Some(durations),
+            animation_timing_function: Some(timing_functions),
+            animation_iteration_count: Some(iteration_counts),
+            animation_direction: Some(directions),
+            animation_fill_mode: Some(fill_modes),
+            animation_delay: Some(delays),
+            animation_play_state: Some(play_states),
+        })
+    </%self:shorthand>
+
 }
 
 
@@ -1428,6 +2947,126 @@ pub fn parse_property_declaration_list<I: Iterator<Node>>(input: I, base_url: &U
     }
 }
 
+impl PropertyDeclarationBlock {
+    /// Parses `value` as the value of the property `name` and replaces any declarations of the
+    /// longhands it sets. The block is only modified if the declaration is valid.
+    pub fn set_property(&mut self, name: &str, value: &str, important: bool, base_url: &Url)
+                        -> PropertyDeclarationParseResult {
+        let value: Vec<ComponentValue> = tokenize(value).map(|(v, _)| v).collect();
+        let mut declarations = vec!();
+        let mut seen = PropertyBitField::new();
+        match PropertyDeclaration::parse(name, value.as_slice(), &mut declarations, base_url,
+                                         &mut seen) {
+            ValidOrIgnoredDeclaration => (),
+            result => return result,
+        }
+
+        for declaration in declarations.iter() {
+            self.remove_longhand(declaration.name())
+        }
+        let list = if important {
+            self.important.make_unique_experimental()
+        } else {
+            self.normal.make_unique_experimental()
+        };
+        // Declarations are stored in reverse order, so the new ones take precedence.
+        for declaration in declarations.move_iter() {
+            list.unshift(declaration)
+        }
+        ValidOrIgnoredDeclaration
+    }
+
+    /// Removes the declarations of all the longhands set by the property `name`.
+    pub fn remove_property(&mut self, name: &str) {
+        for longhand in longhands_for_property(name.to_ascii_lower().as_slice()).iter() {
+            self.remove_longhand(*longhand)
+        }
+    }
+
+    fn remove_longhand(&mut self, name: &str) {
+        if self.important.iter().any(|declaration| declaration.name() == name) {
+            self.important.make_unique_experimental().retain(|declaration| {
+                declaration.name() != name
+            })
+        }
+        if self.normal.iter().any(|declaration| declaration.name() == name) {
+            self.normal.make_unique_experimental().retain(|declaration| {
+                declaration.name() != name
+            })
+        }
+    }
+}
+
+/// The longhand properties set by the property `name`, which must be in lowercase. Empty if
+/// there is no such property.
+pub fn longhands_for_property(name: &str) -> &'static [&'static str] {
+    match name {
+        % for property in LONGHANDS:
+            % if property.derived_from is None:
+                "${property.name}" => {
+                    static LONGHANDS: &'static [&'static str] = &["${property.name}"];
+                    LONGHANDS
+                }
+            % endif
+        % endfor
+        % for shorthand in SHORTHANDS:
+            "${shorthand.name}" => {
+                static LONGHANDS: &'static [&'static str] = &[
+                    % for sub_property in shorthand.sub_properties:
+                        "${sub_property.name}",
+                    % endfor
+                ];
+                LONGHANDS
+            }
+        % endfor
+        _ => {
+            static NO_LONGHANDS: &'static [&'static str] = &[];
+            NO_LONGHANDS
+        }
+    }
+}
+
+/// A declaration as written in a style attribute, used by the CSSOM to read back and edit inline
+/// style without having to serialize specified values.
+#[deriving(Clone)]
+pub struct SourceDeclaration {
+    /// The property name, in lowercase.
+    pub name: ~str,
+    pub value: ~str,
+    pub important: bool,
+}
+
+/// Splits a style attribute into its declarations, in source order. Unknown properties and
+/// invalid values are kept, since they are part of the attribute.
+pub fn parse_source_declarations(input: &str) -> Vec<SourceDeclaration> {
+    let mut declarations = vec!();
+    for item in ErrorLoggerIterator(parse_declaration_list(tokenize(input))) {
+        match item {
+            DeclAtRule(_) => (),
+            Declaration(Declaration{ location: _, name: n, value: v, important: i}) => {
+                declarations.push(SourceDeclaration {
+                    name: n.as_slice().to_ascii_lower(),
+                    value: v.iter().to_css().as_slice().trim().to_owned(),
+                    important: i,
+                })
+            }
+        }
+    }
+    declarations
+}
+
+/// Serializes declarations back into the text of a style attribute.
+pub fn serialize_source_declarations(declarations: &[SourceDeclaration]) -> ~str {
+    let declarations: Vec<~str> = declarations.iter().map(|declaration| {
+        if declaration.important {
+            format!("{}: {} !important;", declaration.name, declaration.value)
+        } else {
+            format!("{}: {};", declaration.name, declaration.value)
+        }
+    }).collect();
+    declarations.as_slice().connect(" ")
+}
+
 
 #[deriving(Clone)]
 pub enum CSSWideKeyword {
@@ -1471,6 +3110,15 @@ pub enum PropertyDeclarationParseResult {
 
 
 impl PropertyDeclaration {
+    /// The name of the longhand property that this declaration sets.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            % for property in LONGHANDS:
+                ${property.camel_case}Declaration(..) => "${property.name}",
+            % endfor
+        }
+    }
+
     pub fn parse(name: &str, value: &[ComponentValue],
                  result_list: &mut Vec<PropertyDeclaration>,
                  base_url: &Url,
@@ -1596,6 +3244,553 @@ impl ComputedValues {
     % endfor
 }
 
+/// Serializes a computed value to CSS text, as returned by `getComputedStyle()`.
+pub trait ToComputedCss {
+    fn to_computed_css(&self, style: &ComputedValues) -> ~str;
+}
+
+impl ToComputedCss for Au {
+    fn to_computed_css(&self, _: &ComputedValues) -> ~str {
+        format!("{}px", geometry::to_frac_px(*self))
+    }
+}
+
+impl ToComputedCss for CSSFloat {
+    fn to_computed_css(&self, _: &ComputedValues) -> ~str {
+        format!("{}", *self)
+    }
+}
+
+impl ToComputedCss for i32 {
+    fn to_computed_css(&self, _: &ComputedValues) -> ~str {
+        format!("{}", *self)
+    }
+}
+
+impl ToComputedCss for RGBA {
+    fn to_computed_css(&self, _: &ComputedValues) -> ~str {
+        let red = (self.red * 255.).round() as int;
+        let green = (self.green * 255.).round() as int;
+        let blue = (self.blue * 255.).round() as int;
+        if self.alpha == 1. {
+            format!("rgb({}, {}, {})", red, green, blue)
+        } else {
+            format!("rgba({}, {}, {}, {})", red, green, blue, self.alpha)
+        }
+    }
+}
+
+impl ToComputedCss for Color {
+    fn to_computed_css(&self, style: &ComputedValues) -> ~str {
+        style.resolve_color(*self).to_computed_css(style)
+    }
+}
+
+impl ToComputedCss for computed::LengthOrPercentage {
+    fn to_computed_css(&self, style: &ComputedValues) -> ~str {
+        match *self {
+            computed::LP_Length(length) => length.to_computed_css(style),
+            computed::LP_Percentage(percentage) => format!("{}%", percentage * 100.),
+        }
+    }
+}
+
+impl ToComputedCss for computed::LengthOrPercentageOrAuto {
+    fn to_computed_css(&self, style: &ComputedValues) -> ~str {
+        match *self {
+            computed::LPA_Length(length) => length.to_computed_css(style),
+            computed::LPA_Percentage(percentage) => format!("{}%", percentage * 100.),
+            computed::LPA_Auto => "auto".to_owned(),
+        }
+    }
+}
+
+impl ToComputedCss for computed::LengthOrPercentageOrNone {
+    fn to_computed_css(&self, style: &ComputedValues) -> ~str {
+        match *self {
+            computed::LPN_Length(length) => length.to_computed_css(style),
+            computed::LPN_Percentage(percentage) => format!("{}%", percentage * 100.),
+            computed::LPN_None => "none".to_owned(),
+        }
+    }
+}
+
+impl ToComputedCss for Option<computed::Image> {
+    fn to_computed_css(&self, style: &ComputedValues) -> ~str {
+        match *self {
+            None => "none".to_owned(),
+            Some(computed::UrlImage(ref url)) => format!("url(\"{}\")", url.to_str()),
+            Some(computed::LinearGradientImage(ref gradient)) => {
+                let direction = match gradient.direction {
+                    specified::AngleDirection(angle) => {
+                        format!("{}deg", angle.radians().to_degrees())
+                    }
+                    specified::CornerDirection(horizontal, vertical) => {
+                        format!("to {} {}",
+                                match horizontal {
+                                    specified::Left => "left",
+                                    specified::Right => "right",
+                                },
+                                match vertical {
+                                    specified::Top => "top",
+                                    specified::Bottom => "bottom",
+                                })
+                    }
+                };
+                format!("{}linear-gradient({}, {})",
+                        if gradient.repeating { "repeating-" } else { "" },
+                        direction,
+                        color_stops_to_computed_css(gradient.stops.as_slice(), style))
+            }
+            Some(computed::RadialGradientImage(ref gradient)) => {
+                let shape = match gradient.shape {
+                    specified::Circle => "circle",
+                    specified::Ellipse => "ellipse",
+                };
+                let extent = match gradient.extent {
+                    computed::ClosestSide => "closest-side".to_owned(),
+                    computed::FarthestSide => "farthest-side".to_owned(),
+                    computed::ClosestCorner => "closest-corner".to_owned(),
+                    computed::FarthestCorner => "farthest-corner".to_owned(),
+                    computed::ExplicitCircleExtent(radius) => radius.to_computed_css(style),
+                    computed::ExplicitEllipseExtent(ref width, ref height) => {
+                        format!("{} {}", width.to_computed_css(style), height.to_computed_css(style))
+                    }
+                };
+                format!("{}radial-gradient({} {} at {} {}, {})",
+                        if gradient.repeating { "repeating-" } else { "" },
+                        shape,
+                        extent,
+                        gradient.center_x.to_computed_css(style),
+                        gradient.center_y.to_computed_css(style),
+                        color_stops_to_computed_css(gradient.stops.as_slice(), style))
+            }
+        }
+    }
+}
+
+fn color_stops_to_computed_css(stops: &[computed::ColorStop], style: &ComputedValues) -> ~str {
+    let stops: Vec<~str> = stops.iter().map(|stop| {
+        match stop.position {
+            None => stop.color.to_computed_css(style),
+            Some(ref position) => {
+                format!("{} {}", stop.color.to_computed_css(style), position.to_computed_css(style))
+            }
+        }
+    }).collect();
+    stops.as_slice().connect(", ")
+}
+
+impl ToComputedCss for Vec<longhands::font_family::computed_value::FontFamily> {
+    fn to_computed_css(&self, _: &ComputedValues) -> ~str {
+        let names: Vec<~str> = self.iter().map(|family| {
+            match *family {
+                longhands::font_family::computed_value::FamilyName(ref name) => name.clone(),
+            }
+        }).collect();
+        names.as_slice().connect(", ")
+    }
+}
+
+impl ToComputedCss for longhands::text_decoration::computed_value::T {
+    fn to_computed_css(&self, _: &ComputedValues) -> ~str {
+        let mut keywords = vec!();
+        if self.underline {
+            keywords.push("underline")
+        }
+        if self.overline {
+            keywords.push("overline")
+        }
+        if self.line_through {
+            keywords.push("line-through")
+        }
+        if keywords.is_empty() {
+            "none".to_owned()
+        } else {
+            keywords.as_slice().connect(" ")
+        }
+    }
+}
+
+impl ToComputedCss for longhands::background_position::computed_value::T {
+    fn to_computed_css(&self, style: &ComputedValues) -> ~str {
+        format!("{} {}",
+                self.horizontal.to_computed_css(style),
+                self.vertical.to_computed_css(style))
+    }
+}
+
+impl ToComputedCss for longhands::box_shadow::computed_value::T {
+    fn to_computed_css(&self, style: &ComputedValues) -> ~str {
+        if self.is_empty() {
+            return "none".to_owned()
+        }
+        let shadows: Vec<~str> = self.iter().map(|shadow| {
+            let mut result = format!("{} {} {} {}",
+                                     shadow.color.to_computed_css(style),
+                                     shadow.offset_x.to_computed_css(style),
+                                     shadow.offset_y.to_computed_css(style),
+                                     shadow.blur_radius.to_computed_css(style));
+            if shadow.spread_radius != Au(0) {
+                result = format!("{} {}", result, shadow.spread_radius.to_computed_css(style));
+            }
+            if shadow.inset {
+                result = format!("{} inset", result);
+            }
+            result
+        }).collect();
+        shadows.as_slice().connect(", ")
+    }
+}
+
+impl ToComputedCss for longhands::transform::computed_value::T {
+    fn to_computed_css(&self, style: &ComputedValues) -> ~str {
+        if self.is_empty() {
+            return "none".to_owned()
+        }
+        let operations: Vec<~str> = self.iter().map(|operation| {
+            match *operation {
+                longhands::transform::Matrix(a, b, c, d, e, f) => {
+                    format!("matrix({}, {}, {}, {}, {}, {})",
+                            a, b, c, d, geometry::to_frac_px(e), geometry::to_frac_px(f))
+                }
+                longhands::transform::Translate(ref x, ref y) => {
+                    format!("translate({}, {})", x.to_computed_css(style), y.to_computed_css(style))
+                }
+                longhands::transform::Scale(x, y) => format!("scale({}, {})", x, y),
+                longhands::transform::Rotate(angle) => {
+                    format!("rotate({}deg)", angle.radians().to_degrees())
+                }
+                longhands::transform::Skew(x, y) => {
+                    format!("skew({}deg, {}deg)",
+                            x.radians().to_degrees(),
+                            y.radians().to_degrees())
+                }
+            }
+        }).collect();
+        operations.as_slice().connect(" ")
+    }
+}
+
+impl ToComputedCss for longhands::transform_origin::computed_value::T {
+    fn to_computed_css(&self, style: &ComputedValues) -> ~str {
+        format!("{} {}",
+                self.horizontal.to_computed_css(style),
+                self.vertical.to_computed_css(style))
+    }
+}
+
+impl ToComputedCss for longhands::transition_property::computed_value::T {
+    fn to_computed_css(&self, _: &ComputedValues) -> ~str {
+        if self.is_empty() {
+            return "none".to_owned()
+        }
+        let names: Vec<~str> = self.iter().map(|property| {
+            match *property {
+                longhands::transition_property::TransitionAll => "all".to_owned(),
+                longhands::transition_property::TransitionProperty(property) => {
+                    property.name().to_owned()
+                }
+                longhands::transition_property::TransitionUnknown(ref name) => name.clone(),
+            }
+        }).collect();
+        names.as_slice().connect(", ")
+    }
+}
+
+impl ToComputedCss for longhands::animation_name::computed_value::T {
+    fn to_computed_css(&self, _: &ComputedValues) -> ~str {
+        let names: Vec<~str> = self.iter().map(|name| {
+            match *name {
+                Some(ref name) => name.clone(),
+                None => "none".to_owned(),
+            }
+        }).collect();
+        names.as_slice().connect(", ")
+    }
+}
+
+impl ToComputedCss for longhands::animation_iteration_count::computed_value::T {
+    fn to_computed_css(&self, _: &ComputedValues) -> ~str {
+        let counts: Vec<~str> = self.iter().map(|count| {
+            match *count {
+                longhands::animation_iteration_count::Infinite => "infinite".to_owned(),
+                longhands::animation_iteration_count::Iterations(count) => count.to_str(),
+            }
+        }).collect();
+        counts.as_slice().connect(", ")
+    }
+}
+
+impl ToComputedCss for Vec<(~str, i32)> {
+    fn to_computed_css(&self, _: &ComputedValues) -> ~str {
+        if self.is_empty() {
+            return "none".to_owned()
+        }
+        let counters: Vec<~str> = self.iter().map(|&(ref name, value)| {
+            format!("{} {}", *name, value)
+        }).collect();
+        counters.as_slice().connect(" ")
+    }
+}
+
+impl ToComputedCss for longhands::list_style_image::computed_value::T {
+    fn to_computed_css(&self, _: &ComputedValues) -> ~str {
+        match *self {
+            None => "none".to_owned(),
+            Some(ref url) => format!("url(\"{}\")", url.to_str()),
+        }
+    }
+}
+
+impl ToComputedCss for Vec<computed::Time> {
+    fn to_computed_css(&self, _: &ComputedValues) -> ~str {
+        let times: Vec<~str> = self.iter().map(|time| format!("{}s", time.seconds())).collect();
+        times.as_slice().connect(", ")
+    }
+}
+
+impl ToComputedCss for Vec<computed::TimingFunction> {
+    fn to_computed_css(&self, _: &ComputedValues) -> ~str {
+        let timing_functions: Vec<~str> = self.iter().map(|timing_function| {
+            match *timing_function {
+                computed::CubicBezierTimingFunction(x1, y1, x2, y2) => {
+                    format!("cubic-bezier({}, {}, {}, {})", x1, y1, x2, y2)
+                }
+                computed::StepsTimingFunction(steps, computed::StepStart) => {
+                    format!("steps({}, start)", steps)
+                }
+                computed::StepsTimingFunction(steps, computed::StepEnd) => {
+                    format!("steps({}, end)", steps)
+                }
+            }
+        }).collect();
+        timing_functions.as_slice().connect(", ")
+    }
+}
+
+impl ToComputedCss for longhands::border_top_left_radius::computed_value::T {
+    fn to_computed_css(&self, style: &ComputedValues) -> ~str {
+        if self.width == self.height {
+            self.width.to_computed_css(style)
+        } else {
+            format!("{} {}", self.width.to_computed_css(style), self.height.to_computed_css(style))
+        }
+    }
+}
+
+<%
+    # Internal `-servo-` properties are not exposed to content.
+    COMPUTED_LONGHANDS = [property for property in sorted(LONGHANDS, key=lambda p: p.name)
+                          if not property.name.startswith("-servo-")]
+%>
+
+/// The longhand properties listed by `getComputedStyle()`, in alphabetical order.
+pub static COMPUTED_PROPERTY_NAMES: &'static [&'static str] = &[
+    % for property in COMPUTED_LONGHANDS:
+        "${property.name}",
+    % endfor
+];
+
+/// Serializes the computed value of the longhand property `name`, which must be in lowercase.
+/// Returns `None` if there is no such property.
+pub fn computed_value_to_css(style: &ComputedValues, name: &str) -> Option<~str> {
+    match name {
+        % for property in COMPUTED_LONGHANDS:
+            "${property.name}" => {
+                let style_struct = style.get_${property.style_struct.name.lower()}();
+                Some(style_struct.${property.ident}.to_computed_css(style))
+            }
+        % endfor
+        _ => None,
+    }
+}
+
+/// Interpolation between two computed values, as used by transitions. CSS Transitions section 5.
+pub trait Interpolate {
+    /// Returns the value at `progress` of the way from `self` to `other`, or `None` if the two
+    /// values cannot be interpolated. `progress` may fall outside [0, 1] for timing functions
+    /// that overshoot.
+    fn interpolate(&self, other: &Self, progress: CSSFloat) -> Option<Self>;
+}
+
+impl Interpolate for Au {
+    fn interpolate(&self, other: &Au, progress: CSSFloat) -> Option<Au> {
+        let (Au(from), Au(to)) = (*self, *other);
+        Some(Au(from + ((to - from) as CSSFloat * progress).round() as i32))
+    }
+}
+
+impl Interpolate for CSSFloat {
+    fn interpolate(&self, other: &CSSFloat, progress: CSSFloat) -> Option<CSSFloat> {
+        Some(*self + (*other - *self) * progress)
+    }
+}
+
+impl Interpolate for RGBA {
+    fn interpolate(&self, other: &RGBA, progress: CSSFloat) -> Option<RGBA> {
+        let channel = |from: f32, to: f32| {
+            let value = from as CSSFloat + (to - from) as CSSFloat * progress;
+            value.max(0.).min(1.) as f32
+        };
+        Some(RGBA {
+            red: channel(self.red, other.red),
+            green: channel(self.green, other.green),
+            blue: channel(self.blue, other.blue),
+            alpha: channel(self.alpha, other.alpha),
+        })
+    }
+}
+
+impl Interpolate for Color {
+    /// `currentColor` is not resolved here, so it can only be interpolated with itself.
+    fn interpolate(&self, other: &Color, progress: CSSFloat) -> Option<Color> {
+        match (self, other) {
+            (&RGBA(ref from), &RGBA(ref to)) => from.interpolate(to, progress).map(RGBA),
+            _ => None,
+        }
+    }
+}
+
+impl Interpolate for computed::LengthOrPercentage {
+    fn interpolate(&self, other: &computed::LengthOrPercentage, progress: CSSFloat)
+                   -> Option<computed::LengthOrPercentage> {
+        match (*self, *other) {
+            (computed::LP_Length(from), computed::LP_Length(to)) => {
+                from.interpolate(&to, progress).map(computed::LP_Length)
+            }
+            (computed::LP_Percentage(from), computed::LP_Percentage(to)) => {
+                from.interpolate(&to, progress).map(computed::LP_Percentage)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Interpolate for computed::LengthOrPercentageOrAuto {
+    fn interpolate(&self, other: &computed::LengthOrPercentageOrAuto, progress: CSSFloat)
+                   -> Option<computed::LengthOrPercentageOrAuto> {
+        match (*self, *other) {
+            (computed::LPA_Length(from), computed::LPA_Length(to)) => {
+                from.interpolate(&to, progress).map(computed::LPA_Length)
+            }
+            (computed::LPA_Percentage(from), computed::LPA_Percentage(to)) => {
+                from.interpolate(&to, progress).map(computed::LPA_Percentage)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Interpolate for computed::LengthOrPercentageOrNone {
+    fn interpolate(&self, other: &computed::LengthOrPercentageOrNone, progress: CSSFloat)
+                   -> Option<computed::LengthOrPercentageOrNone> {
+        match (*self, *other) {
+            (computed::LPN_Length(from), computed::LPN_Length(to)) => {
+                from.interpolate(&to, progress).map(computed::LPN_Length)
+            }
+            (computed::LPN_Percentage(from), computed::LPN_Percentage(to)) => {
+                from.interpolate(&to, progress).map(computed::LPN_Percentage)
+            }
+            _ => None,
+        }
+    }
+}
+
+<%
+    ANIMATABLE_LONGHANDS = [LONGHANDS_BY_NAME[name] for name in
+        ["margin-%s" % side for side in ["top", "right", "bottom", "left"]] +
+        ["padding-%s" % side for side in ["top", "right", "bottom", "left"]] +
+        ["border-%s-width" % side for side in ["top", "right", "bottom", "left"]] +
+        ["border-%s-color" % side for side in ["top", "right", "bottom", "left"]] +
+        ["top", "right", "bottom", "left", "width", "height", "min-width", "min-height",
+         "max-width", "max-height", "color", "background-color", "font-size", "opacity"]]
+%>
+
+/// A longhand property whose computed values can be interpolated, and which can therefore be
+/// transitioned.
+#[deriving(Eq, Clone)]
+pub enum AnimatableProperty {
+    % for property in ANIMATABLE_LONGHANDS:
+        Animatable${property.camel_case},
+    % endfor
+}
+
+/// All the animatable properties, which are the ones that `transition-property: all` covers.
+pub static ANIMATABLE_PROPERTIES: &'static [AnimatableProperty] = &[
+    % for property in ANIMATABLE_LONGHANDS:
+        Animatable${property.camel_case},
+    % endfor
+];
+
+impl AnimatableProperty {
+    /// Returns the animatable property with the given name, which must be in lowercase.
+    pub fn from_name(name: &str) -> Option<AnimatableProperty> {
+        match name {
+            % for property in ANIMATABLE_LONGHANDS:
+                "${property.name}" => Some(Animatable${property.camel_case}),
+            % endfor
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            % for property in ANIMATABLE_LONGHANDS:
+                Animatable${property.camel_case} => "${property.name}",
+            % endfor
+        }
+    }
+
+    /// Returns true if this property has different values in the two styles and a transition
+    /// can interpolate between them.
+    pub fn can_interpolate(&self, from: &ComputedValues, to: &ComputedValues) -> bool {
+        match *self {
+            % for property in ANIMATABLE_LONGHANDS:
+                Animatable${property.camel_case} => {
+                    let from = &from.${property.style_struct.ident}.${property.ident};
+                    let to = &to.${property.style_struct.ident}.${property.ident};
+                    *from != *to && from.interpolate(to, 0.5).is_some()
+                }
+            % endfor
+        }
+    }
+
+    /// Returns true if this property has the same value in the two styles.
+    pub fn has_same_value(&self, a: &ComputedValues, b: &ComputedValues) -> bool {
+        match *self {
+            % for property in ANIMATABLE_LONGHANDS:
+                Animatable${property.camel_case} => {
+                    a.${property.style_struct.ident}.${property.ident} ==
+                        b.${property.style_struct.ident}.${property.ident}
+                }
+            % endfor
+        }
+    }
+
+    /// Sets this property in `style` to its value at `progress` of the way from its value in
+    /// `from` to its value in `to`.
+    pub fn interpolate(&self,
+                       from: &ComputedValues,
+                       to: &ComputedValues,
+                       progress: CSSFloat,
+                       style: &mut ComputedValues) {
+        match *self {
+            % for property in ANIMATABLE_LONGHANDS:
+                Animatable${property.camel_case} => {
+                    let to = &to.${property.style_struct.ident}.${property.ident};
+                    let value = from.${property.style_struct.ident}
+                                    .${property.ident}
+                                    .interpolate(to, progress)
+                                    .unwrap_or_else(|| to.clone());
+                    style.${property.style_struct.ident}.make_unique_experimental()
+                         .${property.ident} = value;
+                }
+            % endfor
+        }
+    }
+}
+
 /// The initial values for all style structs as defined by the specification.
 lazy_init! {
     static ref INITIAL_VALUES: ComputedValues = ComputedValues {
@@ -1743,6 +3938,7 @@ pub fn cascade(applicable_declarations: &[MatchedProperty],
             inherited_font_weight: inherited_font_style.font_weight,
             inherited_font_size: inherited_font_style.font_size,
             inherited_height: inherited_style.get_box().height,
+            inherited_display: inherited_style.get_box().display,
             inherited_minimum_line_height: inherited_style.get_inheritedbox()
                                                           ._servo_minimum_line_height,
             inherited_text_decorations_in_effect:
@@ -1820,8 +4016,15 @@ pub fn cascade(applicable_declarations: &[MatchedProperty],
         }
     }
 
+    // The applicable declarations cache is keyed only on the declarations, but the display of a
+    // flex item depends on its parent too.
+    let parent_is_flex_container = match context.inherited_display {
+        longhands::display::flex | longhands::display::inline_flex => true,
+        _ => false,
+    };
+
     match (cached_style, parent_style) {
-        (Some(cached_style), Some(parent_style)) => {
+        (Some(cached_style), Some(parent_style)) if !parent_is_flex_container => {
             return (cascade_with_cached_declarations(applicable_declarations,
                                                      shareable,
                                                      parent_style,
@@ -1841,7 +4044,7 @@ pub fn cascade(applicable_declarations: &[MatchedProperty],
             % endif
             .${style_struct.ident}.clone();
     % endfor
-    let mut cacheable = true;
+    let mut cacheable = !parent_is_flex_container;
     let mut seen = PropertyBitField::new();
     // Declaration blocks are stored in increasing precedence order,
     // we want them in decreasing order here.
@@ -1957,6 +4160,48 @@ pub fn cascade_anonymous(parent_style: &ComputedValues) -> ComputedValues {
     result
 }
 
+#[inline]
+fn arc_ptr_eq<T>(a: &Arc<T>, b: &Arc<T>) -> bool {
+    let a: &T = &**a;
+    let b: &T = &**b;
+    (a as *T) == (b as *T)
+}
+
+/// Computes the style of a fragment that falls inside the `::first-line` or `::first-letter`
+/// pseudo-element of its block (CSS 2.1 section 5.12).
+///
+///   * `style`: The computed style of the fragment itself.
+///
+///   * `block_style`: The computed style of the block that the pseudo-element belongs to.
+///
+///   * `pseudo_style`: The computed style of the pseudo-element.
+///
+/// Each style struct that the fragment got unchanged from the block (for inherited structs) or
+/// from the initial values (for the others) is taken from the pseudo-element instead. Structs that
+/// an intervening inline element modified are kept, which approximates inheritance through the
+/// fictional tag sequence at the granularity of whole style structs.
+pub fn cascade_pseudo_element_fragment(style: &ComputedValues,
+                                       block_style: &ComputedValues,
+                                       pseudo_style: &ComputedValues)
+                                       -> ComputedValues {
+    let initial_values = &*INITIAL_VALUES;
+    ComputedValues {
+        % for style_struct in STYLE_STRUCTS:
+            ${style_struct.ident}:
+                % if style_struct.inherited:
+                    if arc_ptr_eq(&style.${style_struct.ident}, &block_style.${style_struct.ident}) {
+                % else:
+                    if arc_ptr_eq(&style.${style_struct.ident}, &initial_values.${style_struct.ident}) {
+                % endif
+                    pseudo_style.${style_struct.ident}.clone()
+                } else {
+                    style.${style_struct.ident}.clone()
+                },
+        % endfor
+        shareable: false,
+    }
+}
+
 
 // Only re-export the types for computed values.
 pub mod computed_values {
@@ -1970,5 +4215,212 @@ pub mod computed_values {
     pub use super::common_types::computed::{
         LengthOrPercentage, LP_Length, LP_Percentage,
         LengthOrPercentageOrAuto, LPA_Length, LPA_Percentage, LPA_Auto,
-        LengthOrPercentageOrNone, LPN_Length, LPN_Percentage, LPN_None};
+        LengthOrPercentageOrNone, LPN_Length, LPN_Percentage, LPN_None,
+        Image, UrlImage, LinearGradientImage, RadialGradientImage, ColorStop, LinearGradient,
+        RadialGradient, RadialGradientExtent, ClosestSide, FarthestSide, ClosestCorner,
+        FarthestCorner, ExplicitCircleExtent, ExplicitEllipseExtent, Angle, GradientDirection,
+        AngleDirection, CornerDirection, HorizontalDirection, Left, Right, VerticalDirection, Top,
+        Bottom, EndingShape, Circle, Ellipse, Time, TimingFunction, CubicBezierTimingFunction,
+        StepsTimingFunction, StepPosition, StepStart, StepEnd};
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{COMPUTED_PROPERTY_NAMES, ComputedValues, cascade, computed_value_to_css};
+    use super::parse_style_attribute;
+    use super::computed_values::{LengthOrPercentageOrAuto, LPA_Auto, LPA_Length, LPA_Percentage};
+    use super::computed_values::{LengthOrPercentage, LP_Length, LP_Percentage};
+    use super::computed_values::{align_items, align_self};
+    use selector_matching::MatchedProperty;
+    use servo_util::geometry::Au;
+    use url::Url;
+
+    fn computed_style(style_attribute: &str) -> ComputedValues {
+        let base_url = from_str::<Url>("http://example.com/").unwrap();
+        let declarations = parse_style_attribute(style_attribute, &base_url);
+        let (style, _) = cascade([MatchedProperty::from_declarations(declarations.normal)]
+                                     .as_slice(),
+                                 false,
+                                 None,
+                                 None);
+        style
+    }
+
+    /// Returns the computed `flex-grow`, `flex-shrink` and `flex-basis` of an element with the
+    /// given style attribute.
+    fn computed_flex(style_attribute: &str) -> (f64, f64, LengthOrPercentageOrAuto) {
+        let style = computed_style(style_attribute);
+        let flex = style.get_flex();
+        (flex.flex_grow, flex.flex_shrink, flex.flex_basis)
+    }
+
+    #[test]
+    fn test_flex_shorthand() {
+        // The initial values are `0 1 auto`.
+        assert!(computed_flex("") == (0., 1., LPA_Auto));
+
+        assert!(computed_flex("flex: none") == (0., 0., LPA_Auto));
+        assert!(computed_flex("flex: auto") == (1., 1., LPA_Auto));
+
+        // An omitted basis is `0%`, and an omitted shrink factor is 1.
+        assert!(computed_flex("flex: 2") == (2., 1., LPA_Percentage(0.)));
+        assert!(computed_flex("flex: 2 3") == (2., 3., LPA_Percentage(0.)));
+        assert!(computed_flex("flex: 2 3 10px") == (2., 3., LPA_Length(Au::from_px(10))));
+
+        // An omitted grow factor is 1, and a unitless zero after both factors is a basis.
+        assert!(computed_flex("flex: 10px") == (1., 1., LPA_Length(Au::from_px(10))));
+        assert!(computed_flex("flex: 0 0 0") == (0., 0., LPA_Length(Au(0))));
+
+        // Invalid values leave the longhands alone.
+        assert!(computed_flex("flex: 1 2 3 4") == (0., 1., LPA_Auto));
+        assert!(computed_flex("flex: red") == (0., 1., LPA_Auto));
+    }
+
+    #[test]
+    fn test_flex_alignment_keywords() {
+        let style = computed_style("align-items: center; align-self: flex-end");
+        assert!(style.get_flex().align_items == align_items::center);
+        assert!(style.get_flex().align_self == align_self::flex_end);
+
+        // `baseline` is not supported, so it is ignored.
+        let style = computed_style("align-items: baseline; align-self: baseline");
+        assert!(style.get_flex().align_items == align_items::stretch);
+        assert!(style.get_flex().align_self == align_self::auto);
+    }
+
+    /// Returns the computed horizontal and vertical radii of the corners of an element with the
+    /// given style attribute, starting at the top left corner.
+    fn computed_radii(style_attribute: &str) -> Vec<(LengthOrPercentage, LengthOrPercentage)> {
+        let style = computed_style(style_attribute);
+        let border = style.get_border();
+        let radii = [
+            &border.border_top_left_radius,
+            &border.border_top_right_radius,
+            &border.border_bottom_right_radius,
+            &border.border_bottom_left_radius,
+        ];
+        radii.iter().map(|radius| (radius.width, radius.height)).collect()
+    }
+
+    fn px(pixels: int) -> LengthOrPercentage {
+        LP_Length(Au::from_px(pixels))
+    }
+
+    /// Returns corners with the same horizontal and vertical radii, in pixels.
+    fn circles(radii: [int, ..4]) -> Vec<(LengthOrPercentage, LengthOrPercentage)> {
+        radii.iter().map(|&radius| (px(radius), px(radius))).collect()
+    }
+
+    #[test]
+    fn test_border_radius_shorthand() {
+        // One to four values are expanded to the corners like the `margin` shorthand.
+        assert!(computed_radii("border-radius: 1px") == circles([1, 1, 1, 1]));
+        assert!(computed_radii("border-radius: 1px 2px") == circles([1, 2, 1, 2]));
+        assert!(computed_radii("border-radius: 1px 2px 3px") == circles([1, 2, 3, 2]));
+        assert!(computed_radii("border-radius: 1px 2px 3px 4px") == circles([1, 2, 3, 4]));
+
+        // Vertical radii follow a slash, and are expanded the same way.
+        assert!(computed_radii("border-radius: 1px 2px / 5px") ==
+                vec!((px(1), px(5)), (px(2), px(5)), (px(1), px(5)), (px(2), px(5))));
+        assert!(computed_radii("border-radius: 1px / 5px 6px 7px") ==
+                vec!((px(1), px(5)), (px(1), px(6)), (px(1), px(7)), (px(1), px(6))));
+        assert!(computed_radii("border-radius: 1px 2px 3px 4px / 5px 6px 7px 8px") ==
+                vec!((px(1), px(5)), (px(2), px(6)), (px(3), px(7)), (px(4), px(8))));
+
+        // Percentages are kept until the size of the border box is known.
+        assert!(computed_radii("border-radius: 10% 2px / 20%") ==
+                vec!((LP_Percentage(0.1), LP_Percentage(0.2)),
+                     (px(2), LP_Percentage(0.2)),
+                     (LP_Percentage(0.1), LP_Percentage(0.2)),
+                     (px(2), LP_Percentage(0.2))));
+
+        // Invalid values leave the longhands alone.
+        for value in ["1px 2px 3px 4px 5px", "1px /", "/ 1px", "1px / 2px / 3px", "-1px", "red"]
+                .iter() {
+            assert!(computed_radii(format!("border-radius: {}", value).as_slice()) ==
+                    circles([0, 0, 0, 0]));
+        }
+    }
+
+    /// Returns the given longhands of an element with the given style attribute, serialized as
+    /// `getComputedStyle()` returns them.
+    fn computed_css(style_attribute: &str, names: &[&str]) -> Vec<~str> {
+        let style = computed_style(style_attribute);
+        names.iter().map(|name| computed_value_to_css(&style, *name).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_computed_css_lengths() {
+        assert!(computed_css("width: 10px; height: 50%; margin-left: auto; max-width: none",
+                             ["width", "height", "margin-left", "max-width"]) ==
+                vec!("10px".to_owned(), "50%".to_owned(), "auto".to_owned(), "none".to_owned()));
+
+        // Relative lengths are resolved to pixels.
+        assert!(computed_css("font-size: 20px; width: 2em; padding-top: 1.5px",
+                             ["width", "padding-top"]) ==
+                vec!("40px".to_owned(), "1.5px".to_owned()));
+
+        // Borders without a style have no width.
+        assert!(computed_css("border-top-width: 5px; border-left: 5px solid",
+                             ["border-top-width", "border-left-width"]) ==
+                vec!("0px".to_owned(), "5px".to_owned()));
+    }
+
+    #[test]
+    fn test_computed_css_colors() {
+        assert!(computed_css("color: red; background-color: rgba(0, 0, 255, 0.5)",
+                             ["color", "background-color"]) ==
+                vec!("rgb(255, 0, 0)".to_owned(), "rgba(0, 0, 255, 0.5)".to_owned()));
+
+        // `currentColor` is resolved against `color`, and the initial background is transparent.
+        assert!(computed_css("color: #00ff00; border-top-color: currentColor",
+                             ["border-top-color", "background-color"]) ==
+                vec!("rgb(0, 255, 0)".to_owned(), "rgba(0, 0, 0, 0)".to_owned()));
+    }
+
+    #[test]
+    fn test_computed_css_keywords() {
+        assert!(computed_css("display: block; position: absolute; font-weight: bold",
+                             ["display", "position", "font-weight", "float"]) ==
+                vec!("block".to_owned(), "absolute".to_owned(), "700".to_owned(),
+                     "none".to_owned()));
+        assert!(computed_css("text-decoration: underline", ["text-decoration"]) ==
+                vec!("underline".to_owned()));
+        assert!(computed_css("", ["text-decoration", "z-index"]) ==
+                vec!("none".to_owned(), "auto".to_owned()));
+    }
+
+    #[test]
+    fn test_computed_css_shorthands() {
+        // Shorthands are serialized through their longhands, and not by themselves.
+        assert!(computed_css("margin: 1px 2px",
+                             ["margin-top", "margin-right", "margin-bottom", "margin-left"]) ==
+                vec!("1px".to_owned(), "2px".to_owned(), "1px".to_owned(), "2px".to_owned()));
+        assert!(computed_css("border: 2px solid red",
+                             ["border-right-width", "border-right-style", "border-right-color"]) ==
+                vec!("2px".to_owned(), "solid".to_owned(), "rgb(255, 0, 0)".to_owned()));
+        assert!(computed_css("font: bold 12px/1.5 serif",
+                             ["font-weight", "font-size", "line-height", "font-family"]) ==
+                vec!("700".to_owned(), "12px".to_owned(), "1.5".to_owned(), "serif".to_owned()));
+        assert!(computed_css("border-radius: 5px / 10%",
+                             ["border-top-left-radius", "border-bottom-right-radius"]) ==
+                vec!("5px 10%".to_owned(), "5px 10%".to_owned()));
+
+        let style = computed_style("margin: 1px");
+        assert!(computed_value_to_css(&style, "margin").is_none());
+        assert!(computed_value_to_css(&style, "border").is_none());
+    }
+
+    #[test]
+    fn test_computed_property_names() {
+        // Every listed property is serialized, and internal properties are not listed.
+        let style = computed_style("");
+        for name in COMPUTED_PROPERTY_NAMES.iter() {
+            assert!(computed_value_to_css(&style, *name).is_some());
+            assert!(!name.starts_with("-servo-"));
+        }
+        assert!(computed_value_to_css(&style, "-servo-minimum-line-height").is_none());
+        assert!(computed_value_to_css(&style, "not-a-property").is_none());
+    }
 }
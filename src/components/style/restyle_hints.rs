@@ -0,0 +1,270 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Restyle hints: a conservative description of which elements may need to be restyled after an
+//! attribute of an element changes, computed from the IDs, classes and attributes that appear in
+//! the selectors of the stylist.
+
+use collections::hashmap::HashMap;
+use std::ascii::StrAsciiExt;
+
+use selector_matching::SELECTOR_WHITESPACE;
+use selectors::*;
+
+/// The kinds of elements that may need to be restyled after an element changes.
+pub enum RestyleHintFlag {
+    /// The element itself.
+    RestyleSelf = 0x01,
+    /// The descendants of the element.
+    RestyleDescendants = 0x02,
+    /// The later siblings of the element, and their descendants.
+    RestyleLaterSiblings = 0x04,
+}
+
+/// A set of `RestyleHintFlag`s.
+#[deriving(Clone, Eq)]
+pub struct RestyleHint {
+    bits: u8,
+}
+
+impl RestyleHint {
+    pub fn none() -> RestyleHint {
+        RestyleHint {
+            bits: 0,
+        }
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.bits == 0
+    }
+
+    pub fn add(self, flag: RestyleHintFlag) -> RestyleHint {
+        RestyleHint {
+            bits: self.bits | (flag as u8),
+        }
+    }
+
+    pub fn has(self, flag: RestyleHintFlag) -> bool {
+        (self.bits & (flag as u8)) != 0
+    }
+
+    pub fn union(self, other: RestyleHint) -> RestyleHint {
+        RestyleHint {
+            bits: self.bits | other.bits,
+        }
+    }
+
+    pub fn union_in_place(&mut self, other: RestyleHint) {
+        self.bits = self.bits | other.bits
+    }
+}
+
+/// The IDs, classes and attribute names that appear in selectors, mapped to the elements that
+/// must be restyled when they change on an element.
+pub struct DependencySet {
+    ids: HashMap<~str, RestyleHint>,
+    classes: HashMap<~str, RestyleHint>,
+    /// Keyed by lowercase attribute name.
+    attrs: HashMap<~str, RestyleHint>,
+}
+
+fn add_dependency(map: &mut HashMap<~str, RestyleHint>, key: &str, hint: RestyleHint) {
+    let found = match map.find_mut_equiv(&key) {
+        Some(existing) => {
+            existing.union_in_place(hint);
+            true
+        }
+        None => false,
+    };
+    if !found {
+        map.insert(key.to_owned(), hint);
+    }
+}
+
+fn find_dependency(map: &HashMap<~str, RestyleHint>, key: &str) -> RestyleHint {
+    match map.find_equiv(&key) {
+        Some(hint) => *hint,
+        None => RestyleHint::none(),
+    }
+}
+
+impl DependencySet {
+    pub fn new() -> DependencySet {
+        DependencySet {
+            ids: HashMap::new(),
+            classes: HashMap::new(),
+            attrs: HashMap::new(),
+        }
+    }
+
+    /// Records the dependencies of the given selector.
+    ///
+    /// Changes to the rightmost compound selector affect the element itself. A compound selector
+    /// to the left of a `>` or ` ` combinator matches an ancestor, so changes to it affect
+    /// descendants; one to the left of a `+` or `~` combinator matches an earlier sibling (or an
+    /// earlier sibling of an ancestor), so changes to it affect later siblings and their
+    /// descendants.
+    pub fn note_selector(&mut self, selector: &CompoundSelector) {
+        self.note_compound_selectors(selector, RestyleHint::none().add(RestyleSelf))
+    }
+
+    /// Records the dependencies of the given selector, whose rightmost compound selector matches
+    /// an element that needs the given hint when it changes. The selectors inside `:not()` match
+    /// the same element as the compound selector they are in, and their other compound selectors
+    /// match ancestors or earlier siblings of that element, which are covered by the hint of
+    /// their combinator whatever that element is.
+    fn note_compound_selectors(&mut self, selector: &CompoundSelector, hint: RestyleHint) {
+        let mut selector = selector;
+        let mut hint = hint;
+        loop {
+            self.note_simple_selectors(selector.simple_selectors.as_slice(), hint);
+            match selector.next {
+                None => break,
+                Some((ref next_selector, combinator)) => {
+                    hint = match combinator {
+                        Child | Descendant => RestyleHint::none().add(RestyleDescendants),
+                        NextSibling | LaterSibling => {
+                            RestyleHint::none().add(RestyleLaterSiblings)
+                        }
+                    };
+                    selector = &**next_selector;
+                }
+            }
+        }
+    }
+
+    fn note_simple_selectors(&mut self, simple_selectors: &[SimpleSelector], hint: RestyleHint) {
+        for simple_selector in simple_selectors.iter() {
+            match *simple_selector {
+                IDSelector(ref id) => add_dependency(&mut self.ids, id.as_slice(), hint),
+                ClassSelector(ref class) => {
+                    add_dependency(&mut self.classes, class.as_slice(), hint)
+                }
+                AttrExists(ref attr) |
+                AttrEqual(ref attr, _) |
+                AttrIncludes(ref attr, _) |
+                AttrDashMatch(ref attr, _, _) |
+                AttrPrefixMatch(ref attr, _) |
+                AttrSubstringMatch(ref attr, _) |
+                AttrSuffixMatch(ref attr, _) => {
+                    add_dependency(&mut self.attrs, attr.lower_name.as_slice(), hint)
+                }
+                Negation(ref negated) => {
+                    for selector in negated.iter() {
+                        self.note_compound_selectors(&**selector, hint)
+                    }
+                }
+                AnyLink | Link | Visited => add_dependency(&mut self.attrs, "href", hint),
+                Lang(_) => {
+                    // The language of an element is inherited from its ancestors.
+                    let hint = hint.add(RestyleSelf).add(RestyleDescendants);
+                    add_dependency(&mut self.attrs, "lang", hint)
+                }
+                LocalNameSelector(..) | NamespaceSelector(..) | Hover | Focus | Active |
+                Checked | Disabled | Enabled | Target | FirstChild | LastChild | OnlyChild |
+                Empty | Root | NthChild(..) | NthLastChild(..) | NthOfType(..) |
+                NthLastOfType(..) | FirstOfType | LastOfType | OnlyOfType => {}
+            }
+        }
+    }
+
+    /// Returns the elements that may need to be restyled after the attribute `local_name` of an
+    /// element changes from `old_value` to `new_value`, where `None` means the attribute is
+    /// absent.
+    ///
+    /// Attributes other than `id` and `class` may affect the element itself regardless of
+    /// selectors: `style` and presentational attributes change its style, and attributes such as
+    /// `src` change its contents. So the element is always restyled when they change.
+    pub fn compute_hint(&self,
+                        local_name: &str,
+                        old_value: Option<&str>,
+                        new_value: Option<&str>)
+                        -> RestyleHint {
+        if old_value == new_value {
+            return RestyleHint::none()
+        }
+
+        let lower_name = local_name.to_ascii_lower();
+        let mut hint = find_dependency(&self.attrs, lower_name.as_slice());
+        match lower_name.as_slice() {
+            "id" => {
+                for id in old_value.iter().chain(new_value.iter()) {
+                    hint.union_in_place(find_dependency(&self.ids, *id))
+                }
+            }
+            "class" => {
+                let old_value = old_value.unwrap_or("");
+                let new_value = new_value.unwrap_or("");
+                for class in old_value.split(SELECTOR_WHITESPACE) {
+                    if !new_value.split(SELECTOR_WHITESPACE).any(|other| other == class) {
+                        hint.union_in_place(find_dependency(&self.classes, class))
+                    }
+                }
+                for class in new_value.split(SELECTOR_WHITESPACE) {
+                    if !old_value.split(SELECTOR_WHITESPACE).any(|other| other == class) {
+                        hint.union_in_place(find_dependency(&self.classes, class))
+                    }
+                }
+            }
+            _ => hint = hint.add(RestyleSelf),
+        }
+        hint
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cssparser;
+    use namespaces::NamespaceMap;
+    use selectors::parse_selector_list;
+    use super::{DependencySet, RestyleHint, RestyleSelf, RestyleDescendants};
+    use super::{RestyleLaterSiblings};
+
+    fn dependency_set(selectors: &str) -> DependencySet {
+        let mut dependencies = DependencySet::new();
+        let selectors = parse_selector_list(
+            cssparser::tokenize(selectors).map(|(v, _)| v).collect(),
+            &NamespaceMap::new()).unwrap();
+        for selector in selectors.iter() {
+            dependencies.note_selector(&*selector.compound_selectors)
+        }
+        dependencies
+    }
+
+    #[test]
+    fn test_class_hints() {
+        let dependencies = dependency_set(".a, .b p, .c + p, div");
+        let hint = |old, new| dependencies.compute_hint("class", old, new);
+        assert!(hint(None, Some("a")) == RestyleHint::none().add(RestyleSelf));
+        assert!(hint(Some("b"), None) == RestyleHint::none().add(RestyleDescendants));
+        assert!(hint(Some("x"), Some("x c")) == RestyleHint::none().add(RestyleLaterSiblings));
+        assert!(hint(Some("a b"), Some("b a")).is_empty());
+        assert!(hint(Some("x"), Some("y")).is_empty());
+    }
+
+    #[test]
+    fn test_id_and_attribute_hints() {
+        let dependencies = dependency_set("#main > p, [data-state] span, :not([hidden])");
+        assert!(dependencies.compute_hint("id", Some("main"), Some("other")) ==
+                RestyleHint::none().add(RestyleDescendants));
+        assert!(dependencies.compute_hint("DATA-STATE", None, Some("open")) ==
+                RestyleHint::none().add(RestyleDescendants));
+        assert!(dependencies.compute_hint("hidden", None, Some("")) ==
+                RestyleHint::none().add(RestyleSelf));
+        assert!(dependencies.compute_hint("title", None, Some("x")) ==
+                RestyleHint::none().add(RestyleSelf));
+        assert!(dependencies.compute_hint("title", Some("x"), Some("x")).is_empty());
+        assert!(dependencies.compute_hint("style", None, Some("color: red")) ==
+                RestyleHint::none().add(RestyleSelf));
+    }
+
+    #[test]
+    fn test_negation_hints() {
+        let dependencies = dependency_set("p:not(.a > .b), p:not(.c ~ p) span");
+        let hint = |class| dependencies.compute_hint("class", None, Some(class));
+        assert!(hint("a") == RestyleHint::none().add(RestyleDescendants));
+        assert!(hint("b") == RestyleHint::none().add(RestyleSelf));
+        assert!(hint("c") == RestyleHint::none().add(RestyleLaterSiblings));
+    }
+}
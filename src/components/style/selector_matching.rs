@@ -4,21 +4,25 @@
 
 use collections::hashmap::HashMap;
 use std::ascii::StrAsciiExt;
-use std::hash::Hash;
+use std::hash::{Hash, hash};
 use std::hash::sip::SipState;
 use num::div_rem;
 use sync::Arc;
 
+use servo_util::bloom::BloomFilter;
 use servo_util::namespace;
 use servo_util::smallvec::SmallVec;
 use servo_util::sort;
 use servo_util::str::DOMString;
 
+use keyframes::KeyframesRule;
 use media_queries::{Device, Screen};
 use node::{TElement, TNode};
-use properties::{PropertyDeclaration, PropertyDeclarationBlock};
+use properties::{PropertyDeclaration, PropertyDeclarationBlock, SpecifiedValue};
+use properties::{CounterResetDeclaration, CounterSetDeclaration};
+use restyle_hints::{DependencySet, RestyleHint};
 use selectors::*;
-use stylesheets::{Stylesheet, iter_style_rules};
+use stylesheets::{Stylesheet, iter_style_rules, iter_keyframes_rules};
 
 pub enum StylesheetOrigin {
     UserAgentOrigin,
@@ -27,7 +31,7 @@ pub enum StylesheetOrigin {
 }
 
 /// The definition of whitespace per CSS Selectors Level 3 § 4.
-static SELECTOR_WHITESPACE: &'static [char] = &'static [' ', '\t', '\n', '\r', '\x0C'];
+pub static SELECTOR_WHITESPACE: &'static [char] = &'static [' ', '\t', '\n', '\r', '\x0C'];
 
 /// A newtype struct used to perform lowercase ASCII comparisons without allocating a whole new
 /// string.
@@ -109,6 +113,7 @@ impl SelectorMap {
                               V:SmallVec<MatchedProperty>>(
                               &self,
                               node: &N,
+                              parent_bf: Option<&BloomFilter>,
                               matching_rules_list: &mut V,
                               shareable: &mut bool) {
         if self.empty {
@@ -121,6 +126,7 @@ impl SelectorMap {
         match element.get_attr(&namespace::Null, "id") {
             Some(id) => {
                 SelectorMap::get_matching_rules_from_hash(node,
+                                                            parent_bf,
                                                             &self.id_hash,
                                                             id,
                                                             matching_rules_list,
@@ -133,6 +139,7 @@ impl SelectorMap {
             Some(ref class_attr) => {
                 for class in class_attr.split(SELECTOR_WHITESPACE) {
                     SelectorMap::get_matching_rules_from_hash(node,
+                                                                parent_bf,
                                                                 &self.class_hash,
                                                                 class,
                                                                 matching_rules_list,
@@ -145,12 +152,14 @@ impl SelectorMap {
         // HTML elements in HTML documents must be matched case-insensitively.
         // TODO(pradeep): Case-sensitivity depends on the document type.
         SelectorMap::get_matching_rules_from_hash_ignoring_case(node,
+                                                                parent_bf,
                                                                 &self.element_hash,
                                                                 element.get_local_name(),
                                                                 matching_rules_list,
                                                                 shareable);
 
         SelectorMap::get_matching_rules(node,
+                                        parent_bf,
                                         self.universal_rules.as_slice(),
                                         matching_rules_list,
                                         shareable);
@@ -163,13 +172,18 @@ impl SelectorMap {
                                     N:TNode<E>,
                                     V:SmallVec<MatchedProperty>>(
                                     node: &N,
+                                    parent_bf: Option<&BloomFilter>,
                                     hash: &HashMap<DOMString, Vec<Rule>>,
                                     key: &str,
                                     matching_rules: &mut V,
                                     shareable: &mut bool) {
         match hash.find_equiv(&key) {
             Some(rules) => {
-                SelectorMap::get_matching_rules(node, rules.as_slice(), matching_rules, shareable)
+                SelectorMap::get_matching_rules(node,
+                                                parent_bf,
+                                                rules.as_slice(),
+                                                matching_rules,
+                                                shareable)
             }
             None => {}
         }
@@ -179,13 +193,18 @@ impl SelectorMap {
                                                   N:TNode<E>,
                                                   V:SmallVec<MatchedProperty>>(
                                                   node: &N,
+                                                  parent_bf: Option<&BloomFilter>,
                                                   hash: &HashMap<DOMString, Vec<Rule>>,
                                                   key: &str,
                                                   matching_rules: &mut V,
                                                   shareable: &mut bool) {
         match hash.find_equiv(&LowercaseAsciiString(key)) {
             Some(rules) => {
-                SelectorMap::get_matching_rules(node, rules.as_slice(), matching_rules, shareable)
+                SelectorMap::get_matching_rules(node,
+                                                parent_bf,
+                                                rules.as_slice(),
+                                                matching_rules,
+                                                shareable)
             }
             None => {}
         }
@@ -196,11 +215,12 @@ impl SelectorMap {
                           N:TNode<E>,
                           V:SmallVec<MatchedProperty>>(
                           node: &N,
+                          parent_bf: Option<&BloomFilter>,
                           rules: &[Rule],
                           matching_rules: &mut V,
                           shareable: &mut bool) {
         for rule in rules.iter() {
-            if matches_compound_selector(&*rule.selector, node, shareable) {
+            if matches_compound_selector(&*rule.selector, node, parent_bf, shareable) {
                 // TODO(pradeep): Is the cloning inefficient?
                 matching_rules.push(rule.property.clone());
             }
@@ -306,7 +326,15 @@ pub struct Stylist {
     element_map: PerPseudoElementSelectorMap,
     before_map: PerPseudoElementSelectorMap,
     after_map: PerPseudoElementSelectorMap,
+    first_line_map: PerPseudoElementSelectorMap,
+    first_letter_map: PerPseudoElementSelectorMap,
     rules_source_order: uint,
+
+    /// The IDs, classes and attributes used in selectors, for computing restyle hints.
+    dependencies: DependencySet,
+
+    /// The `@keyframes` rules by name. A later rule replaces an earlier one with the same name.
+    keyframes: HashMap<~str, KeyframesRule>,
 }
 
 impl Stylist {
@@ -316,29 +344,41 @@ impl Stylist {
             element_map: PerPseudoElementSelectorMap::new(),
             before_map: PerPseudoElementSelectorMap::new(),
             after_map: PerPseudoElementSelectorMap::new(),
+            first_line_map: PerPseudoElementSelectorMap::new(),
+            first_letter_map: PerPseudoElementSelectorMap::new(),
             rules_source_order: 0u,
+            dependencies: DependencySet::new(),
+            keyframes: HashMap::new(),
         }
     }
 
     pub fn add_stylesheet(&mut self, stylesheet: Stylesheet, origin: StylesheetOrigin) {
-        let (mut element_map, mut before_map, mut after_map) = match origin {
+        let (mut element_map, mut before_map, mut after_map, mut first_line_map,
+             mut first_letter_map) = match origin {
             UserAgentOrigin => (
                 &mut self.element_map.user_agent,
                 &mut self.before_map.user_agent,
                 &mut self.after_map.user_agent,
+                &mut self.first_line_map.user_agent,
+                &mut self.first_letter_map.user_agent,
             ),
             AuthorOrigin => (
                 &mut self.element_map.author,
                 &mut self.before_map.author,
                 &mut self.after_map.author,
+                &mut self.first_line_map.author,
+                &mut self.first_letter_map.author,
             ),
             UserOrigin => (
                 &mut self.element_map.user,
                 &mut self.before_map.user,
                 &mut self.after_map.user,
+                &mut self.first_line_map.user,
+                &mut self.first_letter_map.user,
             ),
         };
         let mut rules_source_order = self.rules_source_order;
+        let dependencies = &mut self.dependencies;
 
         // Take apart the StyleRule into individual Rules and insert
         // them into the SelectorMap of that priority.
@@ -350,6 +390,8 @@ impl Stylist {
                             None => &mut element_map,
                             Some(Before) => &mut before_map,
                             Some(After) => &mut after_map,
+                            Some(FirstLine) => &mut first_line_map,
+                            Some(FirstLetter) => &mut first_letter_map,
                         };
                         map.$priority.insert(Rule {
                                 selector: selector.compound_selectors.clone(),
@@ -366,11 +408,35 @@ impl Stylist {
 
         let device = &Device { media_type: Screen };  // TODO, use Print when printing
         iter_style_rules(stylesheet.rules.as_slice(), device, |style_rule| {
+            for selector in style_rule.selectors.iter() {
+                dependencies.note_selector(&*selector.compound_selectors);
+            }
             append!(normal);
             append!(important);
             rules_source_order += 1;
         });
         self.rules_source_order = rules_source_order;
+
+        let keyframes = &mut self.keyframes;
+        iter_keyframes_rules(stylesheet.rules.as_slice(), device, |keyframes_rule| {
+            keyframes.insert(keyframes_rule.name.clone(), keyframes_rule.clone());
+        });
+    }
+
+    /// Returns the `@keyframes` rule with the given name, if any.
+    pub fn get_keyframes<'a>(&'a self, name: &str) -> Option<&'a KeyframesRule> {
+        self.keyframes.find_equiv(&name)
+    }
+
+    /// Returns the elements that may need to be restyled after the attribute `local_name` of an
+    /// element changes from `old_value` to `new_value`, where `None` means the attribute is
+    /// absent.
+    pub fn compute_restyle_hint(&self,
+                                local_name: &str,
+                                old_value: Option<&str>,
+                                new_value: Option<&str>)
+                                -> RestyleHint {
+        self.dependencies.compute_hint(local_name, old_value, new_value)
     }
 
     /// Returns the applicable CSS declarations for the given element. This corresponds to
@@ -379,11 +445,16 @@ impl Stylist {
     /// The returned boolean indicates whether the style is *shareable*; that is, whether the
     /// matched selectors are simple enough to allow the matching logic to be reduced to the logic
     /// in `css::matching::PrivateMatchMethods::candidate_element_allows_for_style_sharing`.
+    ///
+    /// `parent_bf`, if present, is a Bloom filter containing the hashes of all of the element's
+    /// ancestors (see `insert_element_into_bloom_filter`). It is used to reject selectors whose
+    /// ancestor parts cannot match without walking up the tree.
     pub fn push_applicable_declarations<E:TElement,
                                         N:TNode<E>,
                                         V:SmallVec<MatchedProperty>>(
                                         &self,
                                         element: &N,
+                                        parent_bf: Option<&BloomFilter>,
                                         style_attribute: Option<&PropertyDeclarationBlock>,
                                         pseudo_element: Option<PseudoElement>,
                                         applicable_declarations: &mut V)
@@ -396,16 +467,35 @@ impl Stylist {
             None => &self.element_map,
             Some(Before) => &self.before_map,
             Some(After) => &self.after_map,
+            Some(FirstLine) => &self.first_line_map,
+            Some(FirstLetter) => &self.first_letter_map,
         };
 
         let mut shareable = true;
 
         // Step 1: Normal rules.
         map.user_agent.normal.get_all_matching_rules(element,
+                                                     parent_bf,
                                                      applicable_declarations,
                                                      &mut shareable);
-        map.user.normal.get_all_matching_rules(element, applicable_declarations, &mut shareable);
-        map.author.normal.get_all_matching_rules(element, applicable_declarations, &mut shareable);
+        map.user.normal.get_all_matching_rules(element,
+                                               parent_bf,
+                                               applicable_declarations,
+                                               &mut shareable);
+        if pseudo_element.is_none() {
+            match presentational_hints(&element.as_element()) {
+                Some(declarations) => {
+                    shareable = false;
+                    applicable_declarations.push(MatchedProperty::from_declarations(
+                            Arc::new(declarations)))
+                }
+                None => {}
+            }
+        }
+        map.author.normal.get_all_matching_rules(element,
+                                                 parent_bf,
+                                                 applicable_declarations,
+                                                 &mut shareable);
 
         // Step 2: Normal style attributes.
         style_attribute.map(|sa| {
@@ -415,6 +505,7 @@ impl Stylist {
 
         // Step 3: Author-supplied `!important` rules.
         map.author.important.get_all_matching_rules(element,
+                                                    parent_bf,
                                                     applicable_declarations,
                                                     &mut shareable);
 
@@ -426,9 +517,11 @@ impl Stylist {
 
         // Step 5: User and UA `!important` rules.
         map.user.important.get_all_matching_rules(element,
+                                                  parent_bf,
                                                   applicable_declarations,
                                                   &mut shareable);
         map.user_agent.important.get_all_matching_rules(element,
+                                                        parent_bf,
                                                         applicable_declarations,
                                                         &mut shareable);
 
@@ -436,6 +529,33 @@ impl Stylist {
     }
 }
 
+/// Returns true if the element has attributes that map to CSS declarations, in which case it
+/// must not share its style with other elements.
+pub fn has_presentational_hints<E:TElement>(element: &E) -> bool {
+    presentational_hints(element).is_some()
+}
+
+/// Returns the declarations that the HTML attributes of the element map to. They apply before
+/// author rules, as if they were author rules with a specificity of zero. See HTML § 10.3.
+fn presentational_hints<E:TElement>(element: &E) -> Option<Vec<PropertyDeclaration>> {
+    if *element.get_namespace() != namespace::HTML {
+        return None
+    }
+    let parse_integer = |name: &str| -> Option<i32> {
+        element.get_attr(&namespace::Null, name).and_then(|value| from_str(value.trim()))
+    };
+    match element.get_local_name() {
+        // The counter is incremented before the first item is numbered.
+        "ol" => parse_integer("start").map(|start| {
+            vec!(CounterResetDeclaration(SpecifiedValue(vec!(("list-item".to_owned(), start - 1)))))
+        }),
+        "li" => parse_integer("value").map(|value| {
+            vec!(CounterSetDeclaration(SpecifiedValue(vec!(("list-item".to_owned(), value)))))
+        }),
+        _ => None,
+    }
+}
+
 struct PerOriginSelectorMap {
     normal: SelectorMap,
     important: SelectorMap,
@@ -525,14 +645,96 @@ fn matches_compound_selector<E:TElement,
                              N:TNode<E>>(
                              selector: &CompoundSelector,
                              element: &N,
+                             parent_bf: Option<&BloomFilter>,
                              shareable: &mut bool)
                              -> bool {
+    match parent_bf {
+        Some(parent_bf) if can_fast_reject(selector, parent_bf) => return false,
+        _ => {}
+    }
     match matches_compound_selector_internal(selector, element, shareable) {
         Matched => true,
         _ => false
     }
 }
 
+/// Returns true if the ancestor parts of `selector` cannot possibly match, given a Bloom filter
+/// containing the hashes of the local names, IDs and classes of all of the element's ancestors.
+///
+/// Every compound selector to the left of a `Child` or `Descendant` combinator must match an
+/// ancestor of the element (sibling combinators to its right don't change that, since siblings
+/// share ancestors), so if any of its local name, ID or class selectors is missing from the
+/// filter the whole selector fails.
+fn can_fast_reject(selector: &CompoundSelector, parent_bf: &BloomFilter) -> bool {
+    let mut selector = selector;
+    let mut matches_ancestor = false;
+    loop {
+        match selector.next {
+            None => return false,
+            Some((ref next_selector, combinator)) => {
+                match combinator {
+                    Child | Descendant => matches_ancestor = true,
+                    NextSibling | LaterSibling => {}
+                }
+                selector = &**next_selector;
+            }
+        }
+
+        if !matches_ancestor {
+            continue
+        }
+
+        for simple_selector in selector.simple_selectors.iter() {
+            let might_match = match *simple_selector {
+                LocalNameSelector(ref name) => {
+                    parent_bf.might_contain(&LowercaseAsciiString(name.as_slice()))
+                }
+                IDSelector(ref id) => parent_bf.might_contain(&id.as_slice()),
+                ClassSelector(ref class) => parent_bf.might_contain(&class.as_slice()),
+                _ => true,
+            };
+            if !might_match {
+                return true
+            }
+        }
+    }
+}
+
+/// Calls `callback` with the hash of each of the element's local name, ID and classes, as checked
+/// by `can_fast_reject`.
+fn each_element_hash<E:TElement,N:TNode<E>>(element: &N, callback: |u32|) {
+    let element = element.as_element();
+    callback(hash(&LowercaseAsciiString(element.get_local_name())) as u32);
+    match element.get_attr(&namespace::Null, "id") {
+        Some(id) => callback(hash(&id) as u32),
+        None => {}
+    }
+    match element.get_attr(&namespace::Null, "class") {
+        Some(class_attr) => {
+            for class in class_attr.split(SELECTOR_WHITESPACE) {
+                if !class.is_empty() {
+                    callback(hash(&class) as u32)
+                }
+            }
+        }
+        None => {}
+    }
+}
+
+/// Inserts the local name, ID and classes of `element` into the given Bloom filter, so that
+/// matching its descendants can use `Stylist::push_applicable_declarations`'s fast rejection.
+pub fn insert_element_into_bloom_filter<E:TElement,N:TNode<E>>(element: &N,
+                                                              bf: &mut BloomFilter) {
+    each_element_hash(element, |hash| bf.insert_hash(hash))
+}
+
+/// Removes the hashes inserted by `insert_element_into_bloom_filter` for `element`. Its ID and
+/// class attributes must not have changed in the meantime.
+pub fn remove_element_from_bloom_filter<E:TElement,N:TNode<E>>(element: &N,
+                                                              bf: &mut BloomFilter) {
+    each_element_hash(element, |hash| bf.remove_hash(hash))
+}
+
 /// A result of selector matching, includes 3 failure types,
 ///
 ///     NotMatchedAndRestartFromClosestLaterSibling
@@ -767,6 +969,35 @@ fn matches_simple_selector<E:TElement,
             let elem = element.as_element();
             elem.get_hover_state()
         },
+        Focus => {
+            *shareable = false;
+            let elem = element.as_element();
+            elem.get_focus_state()
+        },
+        Active => {
+            *shareable = false;
+            let elem = element.as_element();
+            elem.get_active_state()
+        },
+        Checked => {
+            *shareable = false;
+            matches_checked(&element.as_element())
+        },
+        Disabled => {
+            *shareable = false;
+            let elem = element.as_element();
+            elem.get_disabled_state()
+        },
+        Enabled => {
+            *shareable = false;
+            let elem = element.as_element();
+            elem.get_enabled_state()
+        },
+        Target => {
+            *shareable = false;
+            let elem = element.as_element();
+            elem.get_target_state()
+        },
         FirstChild => {
             *shareable = false;
             matches_first_child(element)
@@ -780,11 +1011,21 @@ fn matches_simple_selector<E:TElement,
             matches_first_child(element) && matches_last_child(element)
         }
 
+        Empty => {
+            *shareable = false;
+            element.is_empty()
+        }
+
         Root => {
             *shareable = false;
             matches_root(element)
         }
 
+        Lang(ref lang) => {
+            *shareable = false;
+            matches_lang(element, lang.as_slice())
+        }
+
         NthChild(a, b) => {
             *shareable = false;
             matches_generic_nth_child(element, a, b, false, false)
@@ -818,7 +1059,7 @@ fn matches_simple_selector<E:TElement,
 
         Negation(ref negated) => {
             *shareable = false;
-            !negated.iter().all(|s| matches_simple_selector(s, element, shareable))
+            !negated.iter().any(|s| matches_compound_selector(&**s, element, None, shareable))
         },
     }
 }
@@ -887,6 +1128,28 @@ fn matches_generic_nth_child<'a,
     n >= 0 && r == 0
 }
 
+/// Matches `:checked`, which only applies to checkboxes and radio buttons that are checked and to
+/// selected options. See HTML § 4.14.3.
+fn matches_checked<E:TElement>(element: &E) -> bool {
+    if *element.get_namespace() != namespace::HTML {
+        return false
+    }
+    match element.get_local_name() {
+        "input" => {
+            let is_checkable = match element.get_attr(&namespace::Null, "type") {
+                Some(input_type) => {
+                    input_type.eq_ignore_ascii_case("checkbox") ||
+                        input_type.eq_ignore_ascii_case("radio")
+                }
+                None => false,
+            };
+            is_checkable && element.get_checked_state()
+        }
+        "option" => element.get_attr(&namespace::Null, "selected").is_some(),
+        _ => false,
+    }
+}
+
 #[inline]
 fn matches_root<E:TElement,N:TNode<E>>(element: &N) -> bool {
     match element.parent_node() {
@@ -895,6 +1158,35 @@ fn matches_root<E:TElement,N:TNode<E>>(element: &N) -> bool {
     }
 }
 
+/// Matches `:lang()` against the language of the element, which is taken from the nearest
+/// inclusive ancestor with an `xml:lang` or `lang` attribute. `lang` is already lowercased.
+fn matches_lang<E:TElement,N:TNode<E>>(element: &N, lang: &str) -> bool {
+    let mut node = element.clone();
+    loop {
+        if node.is_element() {
+            let element = node.as_element();
+            let value = element.get_attr(&namespace::XML, "lang").or_else(|| {
+                element.get_attr(&namespace::Null, "lang")
+            });
+            match value {
+                Some(value) => {
+                    // Matches if the language is exactly `lang` or begins with `lang` followed by
+                    // a hyphen, case-insensitively.
+                    return value.len() >= lang.len() &&
+                        value.is_char_boundary(lang.len()) &&
+                        value.slice_to(lang.len()).eq_ignore_ascii_case(lang) &&
+                        (value.len() == lang.len() || value.char_at(lang.len()) == '-')
+                }
+                None => {}
+            }
+        }
+        match node.parent_node() {
+            Some(parent) => node = parent,
+            None => return false,
+        }
+    }
+}
+
 #[inline]
 fn matches_first_child<E:TElement,N:TNode<E>>(element: &N) -> bool {
     let mut node = element.clone();
@@ -943,7 +1235,13 @@ fn matches_last_child<E:TElement,N:TNode<E>>(element: &N) -> bool {
 #[cfg(test)]
 mod tests {
     use sync::Arc;
-    use super::{MatchedProperty, Rule, SelectorMap};
+    use super::{LowercaseAsciiString, MatchedProperty, Rule, SelectorMap, can_fast_reject};
+    use super::matches_compound_selector;
+    use node::{TElement, TNode};
+    use selectors::AttrSelector;
+    use servo_util::bloom::BloomFilter;
+    use servo_util::namespace;
+    use servo_util::namespace::Namespace;
 
     /// Helper method to get some Rules from selector strings.
     /// Each sublist of the result contains the Rules for one StyleRule.
@@ -1009,4 +1307,154 @@ mod tests {
         assert_eq!(0, selector_map.class_hash.find_equiv(&("intro")).unwrap().get(0).property.source_order);
         assert!(selector_map.class_hash.find_equiv(&("foo")).is_none());
     }
+
+    #[test]
+    fn test_can_fast_reject() {
+        let rules_list = get_mock_rules(["div p", "#main > p", ".sidebar p + p", "p", "DIV p"]);
+        let selector = |i: uint| rules_list.get(i).get(0).selector.clone();
+        let mut bf = BloomFilter::new();
+        bf.insert(&LowercaseAsciiString("div"));
+        bf.insert(&("sidebar"));
+
+        assert!(!can_fast_reject(&*selector(0), &bf));
+        assert!(can_fast_reject(&*selector(1), &bf));
+        assert!(!can_fast_reject(&*selector(2), &bf));
+        assert!(!can_fast_reject(&*selector(3), &bf));
+        assert!(!can_fast_reject(&*selector(4), &bf));
+
+        bf.clear();
+        assert!(can_fast_reject(&*selector(0), &bf));
+        assert!(can_fast_reject(&*selector(2), &bf));
+        assert!(!can_fast_reject(&*selector(3), &bf));
+    }
+
+    #[test]
+    fn test_keyframes() {
+        use super::{AuthorOrigin, Stylist};
+        use stylesheets::Stylesheet;
+        use url;
+
+        let css = "@keyframes spin { to { opacity: 0 } 0%, 50% { opacity: 1 } }
+                   @media screen { @keyframes \"fade\" { from { opacity: 0 } } }
+                   @keyframes invalid { 120% { opacity: 0 } }";
+        let stylesheet = Stylesheet::from_bytes(css.as_bytes(),
+                                                url::from_str("http://example.com/").unwrap(),
+                                                None,
+                                                None);
+        let mut stylist = Stylist::new();
+        stylist.add_stylesheet(stylesheet, AuthorOrigin);
+
+        let offsets: Vec<f64> = stylist.get_keyframes("spin").unwrap().keyframes.iter()
+                                       .map(|keyframe| keyframe.offset).collect();
+        assert_eq!(offsets, vec!(0., 0.5, 1.));
+        assert_eq!(stylist.get_keyframes("fade").unwrap().keyframes.len(), 1);
+        assert!(stylist.get_keyframes("invalid").unwrap().keyframes.is_empty());
+        assert!(stylist.get_keyframes("none").is_none());
+    }
+
+    /// An element without parent or siblings, for testing the pseudo-classes that depend on the
+    /// state of the element.
+    #[deriving(Clone)]
+    struct MockElement {
+        local_name: &'static str,
+        namespace: Namespace,
+        attrs: Vec<(&'static str, &'static str)>,
+        checked: bool,
+        target: bool,
+    }
+
+    impl MockElement {
+        fn new(local_name: &'static str, attrs: Vec<(&'static str, &'static str)>)
+               -> MockElement {
+            MockElement {
+                local_name: local_name,
+                namespace: namespace::HTML,
+                attrs: attrs,
+                checked: false,
+                target: false,
+            }
+        }
+    }
+
+    impl TElement for MockElement {
+        fn get_attr(&self, namespace: &Namespace, attr: &str) -> Option<&'static str> {
+            if *namespace != namespace::Null {
+                return None
+            }
+            self.attrs.iter().find(|&&(name, _)| name == attr).map(|&(_, value)| value)
+        }
+        fn get_link(&self) -> Option<&'static str> { None }
+        fn get_local_name<'a>(&'a self) -> &'a str { self.local_name }
+        fn get_namespace<'a>(&'a self) -> &'a Namespace { &self.namespace }
+        fn get_hover_state(&self) -> bool { false }
+        fn get_focus_state(&self) -> bool { false }
+        fn get_active_state(&self) -> bool { false }
+        fn get_checked_state(&self) -> bool { self.checked }
+        fn get_disabled_state(&self) -> bool { false }
+        fn get_enabled_state(&self) -> bool { false }
+        fn get_target_state(&self) -> bool { self.target }
+    }
+
+    impl TNode<MockElement> for MockElement {
+        fn parent_node(&self) -> Option<MockElement> { None }
+        fn prev_sibling(&self) -> Option<MockElement> { None }
+        fn next_sibling(&self) -> Option<MockElement> { None }
+        fn is_document(&self) -> bool { false }
+        fn is_element(&self) -> bool { true }
+        fn as_element(&self) -> MockElement { self.clone() }
+        fn match_attr(&self, attr: &AttrSelector, test: |&str| -> bool) -> bool {
+            let value = self.get_attr(&namespace::Null, attr.lower_name.as_slice());
+            value.map_or(false, |value| test(value))
+        }
+        fn is_empty(&self) -> bool { true }
+    }
+
+    fn matches(selector: &str, element: &MockElement) -> bool {
+        let rules_list = get_mock_rules([selector]);
+        let mut shareable = true;
+        matches_compound_selector(&*rules_list.get(0).get(0).selector,
+                                  element,
+                                  None,
+                                  &mut shareable)
+    }
+
+    #[test]
+    fn test_checked() {
+        let mut checkbox = MockElement::new("input", vec!(("type", "checkbox")));
+        assert!(!matches(":checked", &checkbox));
+        checkbox.checked = true;
+        assert!(matches(":checked", &checkbox));
+        assert!(matches("input:checked", &checkbox));
+
+        let mut radio = MockElement::new("input", vec!(("type", "RADIO")));
+        radio.checked = true;
+        assert!(matches(":checked", &radio));
+
+        // Only checkboxes and radio buttons can be checked, whatever the state of other inputs.
+        let mut text_input = MockElement::new("input", vec!(("type", "text")));
+        text_input.checked = true;
+        assert!(!matches(":checked", &text_input));
+        let mut untyped_input = MockElement::new("input", vec!());
+        untyped_input.checked = true;
+        assert!(!matches(":checked", &untyped_input));
+        let mut div = MockElement::new("div", vec!(("type", "checkbox")));
+        div.checked = true;
+        assert!(!matches(":checked", &div));
+        checkbox.namespace = namespace::SVG;
+        assert!(!matches(":checked", &checkbox));
+
+        // Options are checked when they are selected.
+        assert!(matches(":checked", &MockElement::new("option", vec!(("selected", "")))));
+        assert!(!matches(":checked", &MockElement::new("option", vec!())));
+    }
+
+    #[test]
+    fn test_target() {
+        let mut element = MockElement::new("section", vec!(("id", "intro")));
+        assert!(!matches(":target", &element));
+        element.target = true;
+        assert!(matches(":target", &element));
+        assert!(matches("section:target", &element));
+        assert!(!matches("div:target", &element));
+    }
 }
@@ -16,7 +16,7 @@ use servo_util::namespace;
 use namespaces::NamespaceMap;
 
 
-// Only used in tests
+// Used by `Negation` and in tests
 impl Eq for Arc<CompoundSelector> {
     fn eq(&self, other: &Arc<CompoundSelector>) -> bool {
         **self == **other
@@ -35,8 +35,8 @@ pub struct Selector {
 pub enum PseudoElement {
     Before,
     After,
-//    FirstLine,
-//    FirstLetter,
+    FirstLine,
+    FirstLetter,
 }
 
 
@@ -71,15 +71,21 @@ pub enum SimpleSelector {
     AttrSuffixMatch(AttrSelector, ~str),  // [foo$=bar]
 
     // Pseudo-classes
-    Negation(Vec<SimpleSelector>),
+    Negation(Vec<Arc<CompoundSelector>>),
     AnyLink,
     Link,
     Visited,
     Hover,
+    Focus,
+    Active,
+    Checked,
+    Disabled,
+    Enabled,
+    Target,
     FirstChild, LastChild, OnlyChild,
-//    Empty,
+    Empty,
     Root,
-//    Lang(~str),
+    Lang(~str),
     NthChild(i32, i32),
     NthLastChild(i32, i32),
     NthOfType(i32, i32),
@@ -218,15 +224,24 @@ fn compute_specificity(mut selector: &CompoundSelector,
                 | &AttrExists(..) | &AttrEqual(..) | &AttrIncludes(..) | &AttrDashMatch(..)
                 | &AttrPrefixMatch(..) | &AttrSubstringMatch(..) | &AttrSuffixMatch(..)
                 | &AnyLink | &Link | &Visited | &Hover
+                | &Focus | &Active | &Checked | &Disabled | &Enabled | &Target
                 | &FirstChild | &LastChild | &OnlyChild | &Root
-//                | &Empty | &Lang(*)
+                | &Empty | &Lang(..)
                 | &NthChild(..) | &NthLastChild(..)
                 | &NthOfType(..) | &NthLastOfType(..)
                 | &FirstOfType | &LastOfType | &OnlyOfType
                 => specificity.class_like_selectors += 1,
                 &NamespaceSelector(..) => (),
-                &Negation(ref negated)
-                => simple_selectors_specificity(negated.as_slice(), specificity),
+                // Selectors Level 4: the specificity of `:not()` is that of its most specific
+                // argument.
+                &Negation(ref negated) => {
+                    let most_specific = negated.iter().map(|selector| {
+                        compute_specificity(&**selector, &None)
+                    }).max().unwrap_or(0);
+                    specificity.id_selectors += most_specific >> 20;
+                    specificity.class_like_selectors += (most_specific >> 10) & MAX_10BIT;
+                    specificity.element_selectors += most_specific & MAX_10BIT;
+                }
             }
         }
     }
@@ -254,7 +269,7 @@ fn parse_simple_selectors(iter: &mut Iter, namespaces: &NamespaceMap)
 
     let mut pseudo_element = None;
     loop {
-        match parse_one_simple_selector(iter, namespaces, /* inside_negation = */ false) {
+        match parse_one_simple_selector(iter, namespaces) {
             InvalidSimpleSelector => return None,
             NotASimpleSelector => break,
             SimpleSelectorResult(s) => { simple_selectors.push(s); empty = false },
@@ -302,7 +317,7 @@ enum SimpleSelectorParseResult {
 }
 
 // Parse a simple selector other than a type selector
-fn parse_one_simple_selector(iter: &mut Iter, namespaces: &NamespaceMap, inside_negation: bool)
+fn parse_one_simple_selector(iter: &mut Iter, namespaces: &NamespaceMap)
                          -> SimpleSelectorParseResult {
     match iter.peek() {
         Some(&IDHash(_)) => match iter.next() {
@@ -334,21 +349,21 @@ fn parse_one_simple_selector(iter: &mut Iter, namespaces: &NamespaceMap, inside_
                             // ** Do not add to this list! **
                             "before" => PseudoElementResult(Before),
                             "after" => PseudoElementResult(After),
-//                            "first-line" => PseudoElementResult(FirstLine),
-//                            "first-letter" => PseudoElementResult(FirstLetter),
+                            "first-line" => PseudoElementResult(FirstLine),
+                            "first-letter" => PseudoElementResult(FirstLetter),
                             _ => InvalidSimpleSelector
                         }
                     },
                     Some(result) => SimpleSelectorResult(result),
                 },
                 Some(Function(name, arguments)) => match parse_functional_pseudo_class(
-                        name, arguments, namespaces, inside_negation) {
+                        name, arguments, namespaces) {
                     None => InvalidSimpleSelector,
                     Some(simple_selector) => SimpleSelectorResult(simple_selector),
                 },
                 Some(Colon) => {
                     match iter.next() {
-                        Some(Ident(name)) => match parse_pseudo_element(name) {
+                        Some(Ident(name)) => match parse_pseudo_element(name.as_slice()) {
                             Some(pseudo_element) => PseudoElementResult(pseudo_element),
                             _ => InvalidSimpleSelector,
                         },
@@ -478,6 +493,12 @@ fn parse_simple_pseudo_class(name: &str) -> Option<SimpleSelector> {
         "link" => Some(Link),
         "visited" => Some(Visited),
         "hover" => Some(Hover),
+        "focus" => Some(Focus),
+        "active" => Some(Active),
+        "checked" => Some(Checked),
+        "disabled" => Some(Disabled),
+        "enabled" => Some(Enabled),
+        "target" => Some(Target),
         "first-child" => Some(FirstChild),
         "last-child"  => Some(LastChild),
         "only-child"  => Some(OnlyChild),
@@ -485,65 +506,67 @@ fn parse_simple_pseudo_class(name: &str) -> Option<SimpleSelector> {
         "first-of-type" => Some(FirstOfType),
         "last-of-type"  => Some(LastOfType),
         "only-of-type"  => Some(OnlyOfType),
-//        "empty" => Some(Empty),
+        "empty" => Some(Empty),
         _ => None
     }
 }
 
 
 fn parse_functional_pseudo_class(name: StrBuf, arguments: Vec<ComponentValue>,
-                                 namespaces: &NamespaceMap, inside_negation: bool)
+                                 namespaces: &NamespaceMap)
                                  -> Option<SimpleSelector> {
     match name.as_slice().to_ascii_lower().as_slice() {
-//        "lang" => parse_lang(arguments),
+        "lang" => parse_lang(arguments),
         "nth-child"        => parse_nth(arguments.as_slice()).map(|(a, b)| NthChild(a, b)),
         "nth-last-child"   => parse_nth(arguments.as_slice()).map(|(a, b)| NthLastChild(a, b)),
         "nth-of-type"      => parse_nth(arguments.as_slice()).map(|(a, b)| NthOfType(a, b)),
         "nth-last-of-type" => parse_nth(arguments.as_slice()).map(|(a, b)| NthLastOfType(a, b)),
-        "not" => if inside_negation { None } else { parse_negation(arguments, namespaces) },
+        "not" => parse_negation(arguments, namespaces),
         _ => None
     }
 }
 
 
-fn parse_pseudo_element(name: StrBuf) -> Option<PseudoElement> {
-    match name.as_slice().to_ascii_lower().as_slice() {
+/// Parses the name of a pseudo-element, without the leading colons. Case-insensitive.
+pub fn parse_pseudo_element(name: &str) -> Option<PseudoElement> {
+    match name.to_ascii_lower().as_slice() {
         // All supported pseudo-elements
         "before" => Some(Before),
         "after" => Some(After),
-//        "first-line" => Some(FirstLine),
-//        "first-letter" => Some(FirstLetter),
+        "first-line" => Some(FirstLine),
+        "first-letter" => Some(FirstLetter),
         _ => None
     }
 }
 
 
-//fn parse_lang(arguments: vec!(ComponentValue)) -> Option<SimpleSelector> {
-//    let mut iter = arguments.move_skip_whitespace();
-//    match iter.next() {
-//        Some(Ident(value)) => {
-//            if "" == value || iter.next().is_some() { None }
-//            else { Some(Lang(value)) }
-//        },
-//        _ => None,
-//    }
-//}
+fn parse_lang(arguments: Vec<ComponentValue>) -> Option<SimpleSelector> {
+    let iter = &mut arguments.move_iter().peekable();
+    skip_whitespace(iter);
+    let value = match iter.next() {
+        Some(Ident(value)) | Some(String(value)) => value,
+        _ => return None,
+    };
+    skip_whitespace(iter);
+    if value.as_slice() == "" || iter.next().is_some() { None }
+    else { Some(Lang(value.as_slice().to_ascii_lower())) }
+}
 
 
-// Level 3: Parse ONE simple_selector
+// Level 4: Parse a comma-separated list of complex selectors. Pseudo-elements are not allowed.
 fn parse_negation(arguments: Vec<ComponentValue>, namespaces: &NamespaceMap)
                   -> Option<SimpleSelector> {
-    let iter = &mut arguments.move_iter().peekable();
-    Some(Negation(match parse_type_selector(iter, namespaces) {
-        InvalidTypeSelector => return None,
-        TypeSelector(s) => s,
-        NotATypeSelector => {
-            match parse_one_simple_selector(iter, namespaces, /* inside_negation = */ true) {
-                SimpleSelectorResult(s) => vec!(s),
-                _ => return None
+    match parse_selector_list(arguments, namespaces) {
+        None => None,
+        Some(selectors) => {
+            if selectors.iter().any(|selector| selector.pseudo_element.is_some()) {
+                return None
             }
-        },
-    }))
+            Some(Negation(selectors.move_iter().map(|selector| {
+                selector.compound_selectors
+            }).collect()))
+        }
+    }
 }
 
 
@@ -701,5 +724,74 @@ mod tests {
             pseudo_element: Some(After),
             specificity: specificity(0, 0, 2),
         })))
+        assert!(parse("p::first-line") == Some(vec!(Selector{
+            compound_selectors: Arc::new(CompoundSelector {
+                simple_selectors: vec!(LocalNameSelector("p".to_owned())),
+                next: None,
+            }),
+            pseudo_element: Some(FirstLine),
+            specificity: specificity(0, 0, 2),
+        })))
+        assert!(parse("p:first-letter") == Some(vec!(Selector{
+            compound_selectors: Arc::new(CompoundSelector {
+                simple_selectors: vec!(LocalNameSelector("p".to_owned())),
+                next: None,
+            }),
+            pseudo_element: Some(FirstLetter),
+            specificity: specificity(0, 0, 2),
+        })))
+    }
+
+    #[test]
+    fn test_parsing_pseudo_classes() {
+        assert!(parse(":empty") == Some(vec!(Selector{
+            compound_selectors: Arc::new(CompoundSelector {
+                simple_selectors: vec!(Empty),
+                next: None,
+            }),
+            pseudo_element: None,
+            specificity: specificity(0, 1, 0),
+        })))
+        assert!(parse("input:focus:checked") == Some(vec!(Selector{
+            compound_selectors: Arc::new(CompoundSelector {
+                simple_selectors: vec!(LocalNameSelector("input".to_owned()), Focus, Checked),
+                next: None,
+            }),
+            pseudo_element: None,
+            specificity: specificity(0, 2, 1),
+        })))
+        assert!(parse(":lang(EN)") == Some(vec!(Selector{
+            compound_selectors: Arc::new(CompoundSelector {
+                simple_selectors: vec!(Lang("en".to_owned())),
+                next: None,
+            }),
+            pseudo_element: None,
+            specificity: specificity(0, 1, 0),
+        })))
+        assert!(parse(":lang()") == None)
+        // The specificity of :not() is that of its most specific argument.
+        assert!(parse(":not(.foo, div #bar)") == Some(vec!(Selector{
+            compound_selectors: Arc::new(CompoundSelector {
+                simple_selectors: vec!(Negation(vec!(
+                    Arc::new(CompoundSelector {
+                        simple_selectors: vec!(ClassSelector("foo".to_owned())),
+                        next: None,
+                    }),
+                    Arc::new(CompoundSelector {
+                        simple_selectors: vec!(IDSelector("bar".to_owned())),
+                        next: Some((box CompoundSelector {
+                            simple_selectors: vec!(LocalNameSelector("div".to_owned())),
+                            next: None,
+                        }, Descendant)),
+                    }),
+                ))),
+                next: None,
+            }),
+            pseudo_element: None,
+            specificity: specificity(1, 0, 1),
+        })))
+        assert!(parse(":not(:not(p))").is_some())
+        assert!(parse(":not()") == None)
+        assert!(parse(":not(::before)") == None)
     }
 }
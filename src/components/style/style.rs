@@ -32,25 +32,37 @@ extern crate servo_util = "util";
 // Public API
 pub use stylesheets::{Stylesheet, CSSRule, StyleRule};
 pub use selector_matching::{Stylist, StylesheetOrigin, UserAgentOrigin, AuthorOrigin, UserOrigin};
-pub use selector_matching::{MatchedProperty};
-pub use properties::{cascade, cascade_anonymous};
+pub use selector_matching::{MatchedProperty, has_presentational_hints};
+pub use selector_matching::{insert_element_into_bloom_filter, remove_element_from_bloom_filter};
+pub use restyle_hints::{RestyleHint, RestyleHintFlag, RestyleSelf, RestyleDescendants};
+pub use restyle_hints::{RestyleLaterSiblings};
+pub use properties::{cascade, cascade_anonymous, cascade_pseudo_element_fragment};
 pub use properties::{PropertyDeclaration, ComputedValues, computed_values, style_structs};
 pub use properties::{PropertyDeclarationBlock, parse_style_attribute};  // Style attributes
+pub use properties::{SourceDeclaration, parse_source_declarations};
+pub use properties::{serialize_source_declarations, longhands_for_property};
 pub use properties::{CSSFloat, DeclaredValue, PropertyDeclarationParseResult};
+pub use properties::{UnknownProperty, InvalidValue, ValidOrIgnoredDeclaration};
 pub use properties::longhands;
+pub use properties::{COMPUTED_PROPERTY_NAMES, computed_value_to_css};
+pub use properties::{AnimatableProperty, ANIMATABLE_PROPERTIES, Interpolate};
 pub use errors::with_errors_silenced;
 pub use node::{TElement, TNode};
-pub use selectors::{PseudoElement, Before, After, AttrSelector, SpecificNamespace, AnyNamespace};
+pub use selectors::{PseudoElement, Before, After, FirstLine, FirstLetter, parse_pseudo_element};
+pub use selectors::{AttrSelector, SpecificNamespace, AnyNamespace};
 pub use selectors::{NamespaceConstraint, Selector, CompoundSelector, SimpleSelector, Combinator};
 pub use namespaces::NamespaceMap;
+pub use keyframes::{KeyframesRule, Keyframe};
 pub use media_queries::{MediaRule, MediaQueryList, MediaQuery, Device, MediaType, MediaQueryType};
 
 mod stylesheets;
 mod errors;
 mod selectors;
 mod selector_matching;
+mod restyle_hints;
 mod properties;
 mod namespaces;
 mod node;
 mod media_queries;
+mod keyframes;
 mod parsing_utils;
@@ -15,6 +15,7 @@ use properties;
 use errors::{ErrorLoggerIterator, log_css_error};
 use namespaces::{NamespaceMap, parse_namespace_rule};
 use media_queries::{MediaRule, parse_media_rule};
+use keyframes::{KeyframesRule, parse_keyframes_rule};
 use media_queries;
 
 
@@ -31,6 +32,7 @@ pub struct Stylesheet {
 pub enum CSSRule {
     CSSStyleRule(StyleRule),
     CSSMediaRule(MediaRule),
+    CSSKeyframesRule(KeyframesRule),
 }
 
 
@@ -146,6 +148,7 @@ pub fn parse_nested_at_rule(lower_name: &str, rule: AtRule,
                             parent_rules: &mut Vec<CSSRule>, namespaces: &NamespaceMap, base_url: &Url) {
     match lower_name {
         "media" => parse_media_rule(rule, parent_rules, namespaces, base_url),
+        "keyframes" => parse_keyframes_rule(rule, parent_rules, base_url),
         _ => log_css_error(rule.location, format!("Unsupported at-rule: @{:s}", lower_name))
     }
 }
@@ -158,7 +161,22 @@ pub fn iter_style_rules<'a>(rules: &[CSSRule], device: &media_queries::Device,
             CSSStyleRule(ref rule) => callback(rule),
             CSSMediaRule(ref rule) => if rule.media_queries.evaluate(device) {
                 iter_style_rules(rule.rules.as_slice(), device, |s| callback(s))
-            }
+            },
+            CSSKeyframesRule(_) => {}
+        }
+    }
+}
+
+
+pub fn iter_keyframes_rules<'a>(rules: &[CSSRule], device: &media_queries::Device,
+                                callback: |&KeyframesRule|) {
+    for rule in rules.iter() {
+        match *rule {
+            CSSStyleRule(_) => {}
+            CSSMediaRule(ref rule) => if rule.media_queries.evaluate(device) {
+                iter_keyframes_rules(rule.rules.as_slice(), device, |k| callback(k))
+            },
+            CSSKeyframesRule(ref rule) => callback(rule),
         }
     }
 }
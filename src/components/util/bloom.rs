@@ -0,0 +1,160 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A counting Bloom filter, used during style recalc to quickly reject selectors whose ancestor
+//! parts cannot possibly match.
+
+use std::hash::{Hash, hash};
+
+static KEY_SIZE: uint = 12;
+static ARRAY_SIZE: uint = 1 << KEY_SIZE;
+static KEY_MASK: u32 = (1 << KEY_SIZE) - 1;
+
+/// A counting Bloom filter with 8-bit counters. Each value is hashed into two slots. Counters
+/// saturate at 255; once saturated, a counter is never decremented, so the filter may yield false
+/// positives but never false negatives.
+pub struct BloomFilter {
+    counters: [u8, ..ARRAY_SIZE],
+}
+
+impl Clone for BloomFilter {
+    #[inline]
+    fn clone(&self) -> BloomFilter {
+        BloomFilter {
+            counters: self.counters,
+        }
+    }
+}
+
+#[inline]
+fn hash1(hash: u32) -> uint {
+    (hash & KEY_MASK) as uint
+}
+
+#[inline]
+fn hash2(hash: u32) -> uint {
+    ((hash >> KEY_SIZE) & KEY_MASK) as uint
+}
+
+impl BloomFilter {
+    /// Creates a new, empty Bloom filter.
+    #[inline]
+    pub fn new() -> BloomFilter {
+        BloomFilter {
+            counters: [0, ..ARRAY_SIZE],
+        }
+    }
+
+    /// Removes all values from the filter.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.counters = [0, ..ARRAY_SIZE]
+    }
+
+    #[inline]
+    fn increment_slot(&mut self, index: uint) {
+        let slot = &mut self.counters[index];
+        if *slot != 0xff {
+            *slot += 1;
+        }
+    }
+
+    #[inline]
+    fn decrement_slot(&mut self, index: uint) {
+        let slot = &mut self.counters[index];
+        if *slot == 0 {
+            debug!("bloom filter: removing a value that was never inserted");
+            return
+        }
+        if *slot != 0xff {
+            *slot -= 1;
+        }
+    }
+
+    /// Inserts a precomputed hash into the filter.
+    #[inline]
+    pub fn insert_hash(&mut self, hash: u32) {
+        self.increment_slot(hash1(hash));
+        self.increment_slot(hash2(hash));
+    }
+
+    /// Removes a precomputed hash from the filter. The hash must previously have been inserted.
+    #[inline]
+    pub fn remove_hash(&mut self, hash: u32) {
+        self.decrement_slot(hash1(hash));
+        self.decrement_slot(hash2(hash));
+    }
+
+    /// Returns false if the hash has definitely not been inserted into the filter, and true if it
+    /// might have been.
+    #[inline]
+    pub fn might_contain_hash(&self, hash: u32) -> bool {
+        self.counters[hash1(hash)] != 0 && self.counters[hash2(hash)] != 0
+    }
+
+    /// Inserts a value into the filter.
+    #[inline]
+    pub fn insert<T:Hash>(&mut self, value: &T) {
+        self.insert_hash(hash(value) as u32)
+    }
+
+    /// Removes a value from the filter. The value must previously have been inserted.
+    #[inline]
+    pub fn remove<T:Hash>(&mut self, value: &T) {
+        self.remove_hash(hash(value) as u32)
+    }
+
+    /// Returns false if the value has definitely not been inserted into the filter, and true if
+    /// it might have been.
+    #[inline]
+    pub fn might_contain<T:Hash>(&self, value: &T) -> bool {
+        self.might_contain_hash(hash(value) as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BloomFilter;
+
+    #[test]
+    fn test_insert_and_remove() {
+        let mut bf = BloomFilter::new();
+        for i in range(0u, 1000) {
+            bf.insert(&i);
+        }
+        for i in range(0u, 1000) {
+            assert!(bf.might_contain(&i));
+        }
+        for i in range(0u, 1000) {
+            bf.remove(&i);
+        }
+        for i in range(0u, 1000) {
+            assert!(!bf.might_contain(&i));
+        }
+    }
+
+    #[test]
+    fn test_false_positive_rate() {
+        let mut bf = BloomFilter::new();
+        for i in range(0u, 100) {
+            bf.insert(&i);
+        }
+        let mut false_positives = 0;
+        for i in range(1000u, 11000) {
+            if bf.might_contain(&i) {
+                false_positives += 1;
+            }
+        }
+        assert!(false_positives < 1000);
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut bf = BloomFilter::new();
+        bf.insert(&"div");
+        assert!(bf.might_contain(&"div"));
+        bf.clear();
+        assert!(!bf.might_contain(&"div"));
+    }
+}
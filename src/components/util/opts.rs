@@ -22,9 +22,8 @@ pub struct Opts {
     /// The rendering backend to use (`-r`).
     pub render_backend: BackendType,
 
-    /// How many threads to use for CPU rendering (`-t`).
-    ///
-    /// FIXME(pcwalton): This is not currently used. All rendering is sequential.
+    /// How many threads to use for CPU rendering (`-t`). With more than one, tiles are
+    /// rasterized in parallel on that many render workers when CPU painting.
     pub n_render_threads: uint,
 
     /// True to use CPU painting, false to use GPU painting via Skia-GL (`-c`). Note that
@@ -46,7 +45,11 @@ pub struct Opts {
     pub exit_after_load: bool,
 
     pub output_file: Option<~str>,
+
+    /// True to run without a window or graphics context (`-z`). With an output file, the page is
+    /// rendered and composited on the CPU and written to the file once it has loaded.
     pub headless: bool,
+
     pub hard_fail: bool,
 
     /// True if we should bubble intrinsic widths sequentially (`-b`). If this is true, then
@@ -54,6 +57,18 @@ pub struct Opts {
     /// may wish to turn this flag on in order to benchmark style recalculation against other
     /// browser engines.
     pub bubble_widths_separately: bool,
+
+    /// A file to write the display lists of all render layers to after each reflow
+    /// (`--dump-display-list`), so that they can be replayed later.
+    pub dump_display_list: Option<~str>,
+
+    /// A file to write a textual dump of the flow tree to after each reflow (`--dump-flow-tree`),
+    /// so that the layout of a page can be compared against an expected dump.
+    pub dump_flow_tree: Option<~str>,
+
+    /// A serialized display list to render to the output file instead of loading any URLs
+    /// (`--replay-display-list`). Script and layout do not run.
+    pub replay_display_list: Option<~str>,
 }
 
 fn print_usage(app: &str, opts: &[getopts::OptGroup]) {
@@ -82,6 +97,9 @@ pub fn from_cmdline_args(args: &[~str]) -> Option<Opts> {
         getopts::optflag("z", "headless", "Headless mode"),
         getopts::optflag("f", "hard-fail", "Exit on task failure instead of displaying about:failure"),
         getopts::optflag("b", "bubble-widths", "Bubble intrinsic widths separately like other engines"),
+        getopts::optopt("", "dump-display-list", "Write the display lists of all layers to a file after each reflow", "display-list.bin"),
+        getopts::optopt("", "dump-flow-tree", "Write the flow tree to a file after each reflow", "flow-tree.txt"),
+        getopts::optopt("", "replay-display-list", "Render dumped display lists to the output file", "display-list.bin"),
         getopts::optflag("h", "help", "Print this message")
     ];
 
@@ -98,7 +116,13 @@ pub fn from_cmdline_args(args: &[~str]) -> Option<Opts> {
         return None;
     };
 
-    let urls = if opt_match.free.is_empty() {
+    let replay_display_list = opt_match.opt_str("replay-display-list");
+    if replay_display_list.is_some() && !opt_match.opt_present("o") {
+        args_fail("replaying a display list requires an output file (-o)");
+        return None;
+    }
+
+    let urls = if opt_match.free.is_empty() && replay_display_list.is_none() {
         print_usage(app_name, opts);
         args_fail("servo asks that you provide 1 or more URLs");
         return None;
@@ -160,5 +184,8 @@ pub fn from_cmdline_args(args: &[~str]) -> Option<Opts> {
         headless: opt_match.opt_present("z"),
         hard_fail: opt_match.opt_present("f"),
         bubble_widths_separately: opt_match.opt_present("b"),
+        dump_display_list: opt_match.opt_str("dump-display-list"),
+        dump_flow_tree: opt_match.opt_str("dump-flow-tree"),
+        replay_display_list: replay_display_list,
     })
 }
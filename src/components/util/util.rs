@@ -24,6 +24,7 @@ extern crate sync;
 extern crate std_time = "time";
 extern crate std_url = "url";
 
+pub mod bloom;
 pub mod cache;
 pub mod concurrentmap;
 pub mod debug;
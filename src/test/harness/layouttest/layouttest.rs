@@ -0,0 +1,204 @@
+// Copyright 2014 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Runs each test page through style, flow construction and layout, and compares the flow tree
+//! dumped with `--dump-flow-tree` against an expected dump. If the expected dump does not exist
+//! yet, the current one is recorded there and the test fails, so that it gets reviewed.
+
+extern crate std;
+extern crate test;
+
+use std::cmp;
+use std::io;
+use std::io::{File, Reader, Process};
+use std::io::process::ExitStatus;
+use std::os;
+use test::{DynTestName, DynTestFn, TestDesc, TestOpts, TestDescAndFn};
+use test::run_tests_console;
+
+fn main() {
+    let args = os::args();
+    let mut parts = args.tail().split(|e| "--" == e.as_slice());
+
+    let files = parts.next().unwrap();  // .split() is never empty
+    let servo_args = parts.next().unwrap_or(&[]);
+
+    if files.len() == 0 {
+        fail!("error: at least one layout test list must be given");
+    }
+
+    let tests = parse_lists(files, servo_args);
+    let test_opts = TestOpts {
+        filter: None,
+        run_ignored: false,
+        logfile: None,
+        run_tests: true,
+        run_benchmarks: false,
+        ratchet_noise_percent: None,
+        ratchet_metrics: None,
+        save_metrics: None,
+        test_shard: None,
+        nocapture: false,
+    };
+
+    match run_tests_console(&test_opts, tests) {
+        Ok(false) => os::set_exit_status(1), // tests failed
+        Err(_) => os::set_exit_status(2),    // I/O-related failure
+        _ => (),
+    }
+}
+
+struct LayoutTest {
+    name: ~str,
+    file: ~str,
+    expected: ~str,
+    id: uint,
+    servo_args: Vec<~str>,
+}
+
+fn parse_lists(filenames: &[~str], servo_args: &[~str]) -> Vec<TestDescAndFn> {
+    let mut tests = Vec::new();
+    let mut next_id = 0;
+    for file in filenames.iter() {
+        let file_path = Path::new(file.clone());
+        let contents = match File::open_mode(&file_path, io::Open, io::Read)
+            .and_then(|mut f| {
+                f.read_to_str()
+            }) {
+                Ok(s) => s,
+                _ => fail!("Could not read file"),
+            };
+
+        for line in contents.lines() {
+            // ignore comments
+            if line.starts_with("#") {
+                continue;
+            }
+
+            let parts: Vec<&str> = line.split(' ').filter(|p| !p.is_empty()).collect();
+            if parts.is_empty() {
+                continue;
+            }
+
+            if parts.len() != 2 {
+                fail!("layout test line: '{:s}' doesn't match 'TEST EXPECTED'", line);
+            }
+
+            let src_path = file_path.dir_path();
+            let src_dir = src_path.display().to_str();
+
+            let layout_test = LayoutTest {
+                name: parts.get(0).to_owned(),
+                file: src_dir + "/" + *parts.get(0),
+                expected: src_dir + "/" + *parts.get(1),
+                id: next_id,
+                servo_args: servo_args.iter().map(|x| x.clone()).collect(),
+            };
+
+            next_id += 1;
+
+            tests.push(make_test(layout_test));
+        }
+    }
+    tests
+}
+
+fn make_test(layout_test: LayoutTest) -> TestDescAndFn {
+    let name = layout_test.name.clone();
+    TestDescAndFn {
+        desc: TestDesc {
+            name: DynTestName(name),
+            ignore: false,
+            should_fail: false,
+        },
+        testfn: DynTestFn(proc() {
+            check_layout_test(layout_test);
+        }),
+    }
+}
+
+fn read_file(path: &Path) -> ~str {
+    match File::open(path).and_then(|mut f| f.read_to_str()) {
+        Ok(s) => s,
+        Err(e) => fail!("failed to read {}: {}", path.display(), e),
+    }
+}
+
+fn check_layout_test(layout_test: LayoutTest) {
+    // Servo only exits by itself once it has written an output image.
+    let output_file = format!("/tmp/servo-layouttest-{:06u}.png", layout_test.id);
+    let dump_file = format!("/tmp/servo-layouttest-{:06u}.txt", layout_test.id);
+    let mut args = layout_test.servo_args.clone();
+    args.push_all_move(vec!("-f".to_owned(), "-o".to_owned(), output_file,
+                            "--dump-flow-tree".to_owned(), dump_file.clone(),
+                            layout_test.file.clone()));
+
+    let retval = match Process::status("./servo", args.as_slice()) {
+        Ok(status) => status,
+        Err(e) => fail!("failed to execute process: {}", e),
+    };
+    assert!(retval == ExitStatus(0));
+
+    let actual = read_file(&Path::new(dump_file));
+    let expected_path = Path::new(layout_test.expected.clone());
+    if !expected_path.exists() {
+        let res = File::create(&expected_path).and_then(|mut f| f.write_str(actual));
+        assert!(res.is_ok());
+        fail!("no expected flow tree; recorded the current one in {}", layout_test.expected);
+    }
+
+    let expected = read_file(&expected_path);
+    if actual != expected {
+        fail!("flow tree differs from {}:\n{}", layout_test.expected, diff_lines(expected, actual));
+    }
+}
+
+/// Formats a line diff from `expected` to `actual`. Removed lines start with `-`, added lines
+/// with `+` and unchanged lines with a space.
+fn diff_lines(expected: &str, actual: &str) -> ~str {
+    let old: Vec<&str> = expected.lines().collect();
+    let new: Vec<&str> = actual.lines().collect();
+    let (m, n) = (old.len(), new.len());
+
+    // `common[i * (n + 1) + j]` is the length of the longest common subsequence of `old[i..]`
+    // and `new[j..]`.
+    let mut common = Vec::from_elem((m + 1) * (n + 1), 0u);
+    for i in range(0, m).rev() {
+        for j in range(0, n).rev() {
+            let length = if old.get(i) == new.get(j) {
+                *common.get((i + 1) * (n + 1) + j + 1) + 1
+            } else {
+                cmp::max(*common.get((i + 1) * (n + 1) + j), *common.get(i * (n + 1) + j + 1))
+            };
+            *common.get_mut(i * (n + 1) + j) = length;
+        }
+    }
+
+    let mut diff = StrBuf::new();
+    let (mut i, mut j) = (0, 0);
+    while i < m || j < n {
+        if i < m && j < n && old.get(i) == new.get(j) {
+            diff.push_char(' ');
+            diff.push_str(*old.get(i));
+            i += 1;
+            j += 1;
+        } else if i < m && (j == n || *common.get((i + 1) * (n + 1) + j) >=
+                                      *common.get(i * (n + 1) + j + 1)) {
+            diff.push_char('-');
+            diff.push_str(*old.get(i));
+            i += 1;
+        } else {
+            diff.push_char('+');
+            diff.push_str(*new.get(j));
+            j += 1;
+        }
+        diff.push_char('\n');
+    }
+    diff.into_owned()
+}
@@ -11,6 +11,7 @@ extern crate png;
 extern crate std;
 extern crate test;
 
+use std::cmp;
 use std::io;
 use std::io::{File, Reader, Process};
 use std::io::process::ExitStatus;
@@ -56,9 +57,24 @@ enum ReftestKind {
     Different,
 }
 
+/// How far two renderings may drift apart and still count as the same, following the
+/// `fuzzy(max_difference,max_pixels)` annotation of Gecko's reftest manifests.
+struct Fuzz {
+    /// The largest per-channel difference tolerated at any one pixel.
+    max_difference: u8,
+    /// The largest number of pixels that may differ at all.
+    max_pixels: uint,
+}
+
+static EXACT: Fuzz = Fuzz {
+    max_difference: 0,
+    max_pixels: 0,
+};
+
 struct Reftest {
     name: ~str,
     kind: ReftestKind,
+    fuzz: Fuzz,
     files: [~str, ..2],
     id: uint,
     servo_args: Vec<~str>,
@@ -83,10 +99,20 @@ fn parse_lists(filenames: &[~str], servo_args: &[~str]) -> Vec<TestDescAndFn> {
                 continue;
             }
 
-            let parts: Vec<&str> = line.split(' ').filter(|p| !p.is_empty()).collect();
+            let mut parts: Vec<&str> = line.split(' ').filter(|p| !p.is_empty()).collect();
+            if parts.is_empty() {
+                continue;
+            }
+
+            let fuzz = if parts.get(0).starts_with("fuzzy(") {
+                parse_fuzz(line, parts.shift().unwrap())
+            } else {
+                EXACT
+            };
 
             if parts.len() != 3 {
-                fail!("reftest line: '{:s}' doesn't match 'KIND LEFT RIGHT'", line);
+                fail!("reftest line: '{:s}' doesn't match '[fuzzy(DIFF,PIXELS)] KIND LEFT RIGHT'",
+                      line);
             }
 
             let kind = match parts.get(0) {
@@ -103,6 +129,7 @@ fn parse_lists(filenames: &[~str], servo_args: &[~str]) -> Vec<TestDescAndFn> {
             let reftest = Reftest {
                 name: parts.get(1) + " / " + *parts.get(2),
                 kind: kind,
+                fuzz: fuzz,
                 files: [file_left, file_right],
                 id: next_id,
                 servo_args: servo_args.iter().map(|x| x.clone()).collect(),
@@ -116,6 +143,26 @@ fn parse_lists(filenames: &[~str], servo_args: &[~str]) -> Vec<TestDescAndFn> {
     tests
 }
 
+fn parse_fuzz(line: &str, annotation: &str) -> Fuzz {
+    let values: Vec<&str> = if annotation.ends_with(")") {
+        annotation.slice("fuzzy(".len(), annotation.len() - 1).split(',').collect()
+    } else {
+        Vec::new()
+    };
+    if values.len() == 2 {
+        match (from_str::<u8>(*values.get(0)), from_str::<uint>(*values.get(1))) {
+            (Some(max_difference), Some(max_pixels)) => {
+                return Fuzz {
+                    max_difference: max_difference,
+                    max_pixels: max_pixels,
+                }
+            }
+            _ => (),
+        }
+    }
+    fail!("reftest line: '{:s}' has invalid fuzz annotation '{:s}'", line, annotation)
+}
+
 fn make_test(reftest: Reftest) -> TestDescAndFn {
     let name = reftest.name.clone();
     TestDescAndFn {
@@ -144,37 +191,79 @@ fn capture(reftest: &Reftest, side: uint) -> png::Image {
     png::load_png(&from_str::<Path>(filename).unwrap()).unwrap()
 }
 
+/// Returns the number of bytes per pixel of a captured rendering. The windowed compositor writes
+/// RGB images, while headless rendering writes RGBA ones.
+fn bytes_per_pixel(image: &png::Image) -> uint {
+    match image.color_type {
+        png::RGB8 => 3,
+        png::RGBA8 => 4,
+        _ => fail!("unexpected color type in a captured rendering"),
+    }
+}
+
 fn check_reftest(reftest: Reftest) {
     let left  = capture(&reftest, 0);
     let right = capture(&reftest, 1);
 
-    let pixels: Vec<u8> = left.pixels.iter().zip(right.pixels.iter()).map(|(&a, &b)| {
-            if a as i8 - b as i8 == 0 {
+    if left.width != right.width || left.height != right.height {
+        assert!(reftest.kind == Different,
+                "rendering sizes differ: {}x{} vs. {}x{}",
+                left.width, left.height, right.width, right.height);
+        return;
+    }
+
+    let stride = bytes_per_pixel(&left);
+    assert!(bytes_per_pixel(&right) == stride, "renderings have different color types");
+
+    let mut differing_pixels = 0u;
+    let mut max_difference = 0u8;
+    let mut pixels: Vec<u8> = Vec::with_capacity(left.pixels.len());
+    let (left_pixels, right_pixels) = (left.pixels.as_slice(), right.pixels.as_slice());
+    for (a, b) in left_pixels.chunks(stride).zip(right_pixels.chunks(stride)) {
+        let mut pixel_differs = false;
+        for (&a, &b) in a.iter().zip(b.iter()) {
+            let difference = if a > b { a - b } else { b - a };
+            if difference == 0 {
                 // White for correct
-                0xFF
+                pixels.push(0xFF);
             } else {
-                // "1100" in the RGBA channel with an error for an incorrect value
+                // "1100" in each channel with an error for an incorrect value
                 // This results in some number of C0 and FFs, which is much more
                 // readable (and distinguishable) than the previous difference-wise
                 // scaling but does not require reconstructing the actual RGBA pixel.
-                0xC0
+                pixels.push(0xC0);
+                pixel_differs = true;
+                max_difference = cmp::max(max_difference, difference);
             }
-        }).collect();
+        }
+        if pixel_differs {
+            differing_pixels += 1;
+        }
+    }
 
-    if pixels.iter().any(|&a| a < 255) {
+    if differing_pixels > 0 {
         let output_str = format!("/tmp/servo-reftest-{:06u}-diff.png", reftest.id);
         let output = from_str::<Path>(output_str).unwrap();
 
         let img = png::Image {
             width: left.width,
             height: left.height,
-            color_type: png::RGBA8,
+            color_type: left.color_type,
             pixels: pixels,
         };
         let res = png::store_png(&img, &output);
         assert!(res.is_ok());
 
-        assert!(reftest.kind == Different, "rendering difference: {}", output_str);
+        let within_fuzz = max_difference <= reftest.fuzz.max_difference &&
+                          differing_pixels <= reftest.fuzz.max_pixels;
+        match reftest.kind {
+            Same => assert!(within_fuzz,
+                            "rendering difference of up to {} in {} pixels: {}",
+                            max_difference, differing_pixels, output_str),
+            Different => assert!(!within_fuzz,
+                                 "renderings differ only within fuzz tolerance: {}",
+                                 output_str),
+        }
     } else {
         assert!(reftest.kind == Same);
     }